@@ -1,5 +1,6 @@
 use std::error::Error;
-use std::sync::{LazyLock, OnceLock};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs as _};
+use std::sync::{Arc, LazyLock, OnceLock};
 use std::{borrow::Cow, mem, pin::Pin, task::Poll, time::Duration};
 
 use anyhow::anyhow;
@@ -80,6 +81,44 @@ impl ReqwestClient {
         client.user_agent = Some(user_agent);
         Ok(client)
     }
+
+    /// A client that only connects to addresses `allow` accepts. Names are
+    /// looked up when connecting and refused if any of their addresses
+    /// isn't allowed, so a name checked beforehand can't be re-pointed
+    /// between the check and the request. Addresses written in a URL
+    /// aren't looked up, so check those before sending. Proxies would look
+    /// names up themselves, so none is used.
+    pub fn with_address_filter(allow: fn(IpAddr) -> bool) -> Self {
+        Self::builder()
+            .no_proxy()
+            .dns_resolver(Arc::new(FilteringResolver { allow }))
+            .build()
+            .expect("Failed to initialize HTTP client")
+            .into()
+    }
+}
+
+/// The system resolver, failing for names with an address `allow` refuses.
+struct FilteringResolver {
+    allow: fn(IpAddr) -> bool,
+}
+
+impl reqwest::dns::Resolve for FilteringResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let allow = self.allow;
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let lookup = tokio::task::spawn_blocking(move || {
+                let addrs: Vec<SocketAddr> = (host.as_str(), 0).to_socket_addrs()?.collect();
+                Ok::<_, std::io::Error>((host, addrs))
+            });
+            let (host, addrs) = lookup.await??;
+            if addrs.is_empty() || !addrs.iter().all(|addr| allow(addr.ip())) {
+                return Err(format!("{host} resolves to an address that isn't allowed").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
 }
 
 impl From<reqwest::Client> for ReqwestClient {
@@ -186,11 +225,11 @@ pub fn poll_read_buf(
         let unfilled_portion = buf.initialize_unfilled();
         // SAFETY: Pin projection
         let io_pin = unsafe { Pin::new_unchecked(io) };
-        std::task::ready!(io_pin.poll_read(cx, unfilled_portion)?);
+        let n = std::task::ready!(io_pin.poll_read(cx, unfilled_portion)?);
 
         // Ensure the pointer does not change from under us
         assert_eq!(ptr, buf.filled().as_ptr());
-        buf.filled().len()
+        n
     };
 
     // Safety: This is guaranteed to be the number of initialized (and read)
@@ -221,6 +260,10 @@ impl http_client::HttpClient for ReqwestClient {
         self.user_agent.as_ref()
     }
 
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
     fn send(
         &self,
         req: http::Request<http_client::AsyncBody>,
@@ -274,9 +317,30 @@ impl http_client::HttpClient for ReqwestClient {
 
 #[cfg(test)]
 mod tests {
-    use gpui::http_client::{HttpClient, Url};
+    use std::io::{BufRead as _, BufReader, Write as _};
+    use std::net::TcpListener;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use std::thread;
 
-    use crate::ReqwestClient;
+    use bytes::BytesMut;
+    use futures::AsyncRead;
+    use gpui::http_client::{AsyncBody, HttpClient, Url, http};
+
+    use crate::{ReqwestClient, poll_read_buf};
+
+    #[test]
+    fn test_poll_read_buf_reports_bytes_read() {
+        let mut io: Pin<Box<dyn AsyncRead + Send + Sync>> =
+            Box::pin(futures::io::Cursor::new(b"hello".to_vec()));
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut buf = BytesMut::with_capacity(16);
+
+        // This used to report, and keep, 0 bytes however many were read.
+        assert!(matches!(poll_read_buf(&mut io, &mut cx, &mut buf), Poll::Ready(Ok(5))));
+        assert_eq!(&buf[..], b"hello");
+        assert!(matches!(poll_read_buf(&mut io, &mut cx, &mut buf), Poll::Ready(Ok(0))));
+    }
 
     #[test]
     fn test_proxy_uri() {
@@ -308,6 +372,36 @@ mod tests {
         assert_eq!(client.proxy(), Some(&proxy));
     }
 
+    #[test]
+    fn test_address_filter_is_applied_when_connecting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://localhost:{}/", listener.local_addr().unwrap().port());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+                    .unwrap();
+            }
+        });
+        let get = |client: ReqwestClient| {
+            let request = http::Request::get(url.as_str())
+                .body(AsyncBody::empty())
+                .unwrap();
+            futures::executor::block_on(client.send(request))
+        };
+
+        let allowed = get(ReqwestClient::with_address_filter(|_| true)).unwrap();
+        assert_eq!(allowed.status().as_u16(), 204);
+        let refused = get(ReqwestClient::with_address_filter(|ip| !ip.is_loopback()));
+        assert!(refused.is_err());
+    }
+
     #[test]
     fn test_invalid_proxy_uri() {
         let proxy = Url::parse("socks://127.0.0.1:20170").unwrap();
@@ -0,0 +1,118 @@
+//! Generates the emoji table, `$OUT_DIR/emoji_table.rs`, which `src/emoji.rs`
+//! includes, from Unicode's `data/emoji-test.txt`: every fully-qualified
+//! emoji with its CLDR short name, category and skin tone variants.
+
+use std::fmt::Write as _;
+use std::path::Path;
+use std::{env, fs};
+
+const SOURCE: &str = "data/emoji-test.txt";
+
+/// Name suffixes of the five skin tone variants, in `SkinTone` order.
+const TONES: [&str; 5] = [
+    "light skin tone",
+    "medium-light skin tone",
+    "medium skin tone",
+    "medium-dark skin tone",
+    "dark skin tone",
+];
+
+struct Row {
+    emoji: String,
+    name: String,
+    category: &'static str,
+    tones: Vec<String>,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={SOURCE}");
+    let text = fs::read_to_string(SOURCE).unwrap_or_else(|err| panic!("reading {SOURCE}: {err}"));
+
+    let mut rows: Vec<Row> = Vec::new();
+    let mut category = None;
+    for line in text.lines() {
+        if let Some(group) = line.strip_prefix("# group: ") {
+            category = category_variant(group);
+            continue;
+        }
+        let Some(category) = category else {
+            continue;
+        };
+        // `1F44B 1F3FB ; fully-qualified # 👋🏻 E1.0 waving hand: light skin tone`
+        let Some((_, comment)) = line.split_once("; fully-qualified") else {
+            continue;
+        };
+        let comment = comment.trim_start().strip_prefix("# ").unwrap_or_default();
+        let mut fields = comment.splitn(3, ' ');
+        let (Some(emoji), Some(_version), Some(name)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+
+        if let Some((base, tone)) = name.rsplit_once(": ")
+            && TONES.contains(&tone)
+        {
+            if let Some(row) = rows.iter_mut().rev().find(|row| row.name == base) {
+                row.tones.push(emoji.to_string());
+            }
+            continue;
+        }
+        // Mixed tones, and tones combined with hair styles, aren't offered.
+        if name.contains("skin tone") {
+            continue;
+        }
+        rows.push(Row {
+            emoji: emoji.to_string(),
+            name: name.to_string(),
+            category,
+            tones: Vec::new(),
+        });
+    }
+
+    let mut out = String::from("/// Every emoji, in Unicode's order.\npub static EMOJI: &[Emoji] = &[\n");
+    for row in &rows {
+        let tones = if row.tones.len() == TONES.len() {
+            format!("&{:?}", row.tones)
+        } else {
+            "&[]".to_string()
+        };
+        writeln!(
+            out,
+            "    Emoji {{ emoji: {:?}, name: {:?}, shortcode: {:?}, category: Category::{}, tones: {tones} }},",
+            row.emoji,
+            row.name,
+            shortcode(&row.name),
+            row.category,
+        )
+        .unwrap();
+    }
+    out.push_str("];\n");
+
+    let dest = Path::new(&env::var("OUT_DIR").unwrap()).join("emoji_table.rs");
+    fs::write(&dest, out).unwrap_or_else(|err| panic!("writing {}: {err}", dest.display()));
+}
+
+/// The `Category` variant for an emoji-test group; `None` for components.
+fn category_variant(group: &str) -> Option<&'static str> {
+    Some(match group {
+        "Smileys & Emotion" => "SmileysEmotion",
+        "People & Body" => "PeopleBody",
+        "Animals & Nature" => "AnimalsNature",
+        "Food & Drink" => "FoodDrink",
+        "Travel & Places" => "TravelPlaces",
+        "Activities" => "Activities",
+        "Objects" => "Objects",
+        "Symbols" => "Symbols",
+        "Flags" => "Flags",
+        _ => return None,
+    })
+}
+
+/// `flag: United States` → `flag_united_states`.
+fn shortcode(name: &str) -> String {
+    let name = name.replace('#', " hash").replace('*', " asterisk").to_lowercase();
+    let words: Vec<&str> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    words.join("_")
+}
@@ -1,12 +1,35 @@
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use gpui::{App, Context, Entity, FocusHandle, Focusable, Subscription, Window};
-use gpui_component::input::{InputEvent, InputState};
+use gpui::{AnyWindowHandle, App, Context, Entity, FocusHandle, Focusable, HighlightStyle, Subscription, Task, UniformListScrollHandle, Window};
+use gpui_component::WindowExt as _;
+use gpui_component::input::{InputEvent, InputState, OtpState};
+use gpui_component::notification::Notification;
 use gpui::AppContext;
+use reqwest_client::ReqwestClient;
+use crate::attachments::UploadLimits;
 use crate::auth_state::AuthState;
+use crate::backend::{
+    BackendFuture, BackendSession, ChatBackend, GatewayConnection, MessageDraft, MessageTarget,
+};
+use crate::emoji_usage::EmojiUsage;
+use crate::notifications::{NotificationBus, NotificationSettings};
+use crate::mentions;
+use crate::message_store::{self, MessageStore};
 use crate::mock_data;
-use crate::models::{Attachment, Channel, ChannelKind, DirectMessageChannel, Message, MessageReply, Server, User, UserProfile, VoiceState};
+use crate::outbox::Outbox;
+use crate::permissions::Permissions;
+use crate::lightbox::Lightbox;
+use crate::privacy::PrivacySettings;
+use crate::read_state::ReadStates;
+use crate::models::{Channel, ChannelKind, DirectMessageChannel, Message, MessageReply, Server, User, UserProfile, UserStatus, VoiceState};
 use crate::titlebar::TripwireTitleBar;
+use crate::totp;
+use crate::unfurl::{self, UnfurlLimits, Unfurler};
 use crate::app::app_view::settings::SettingsScreen;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -15,8 +38,30 @@ pub enum AppView {
     DirectMessages,
 }
 
+mod accounts;
+mod audit_log;
 pub mod app_view;
 pub mod auth_view;
+mod channels;
+mod delivery;
+mod members;
+mod message_cache;
+mod message_list;
+mod mention_completions;
+mod notifications;
+mod permissions;
+mod read_state;
+mod realtime;
+mod roles;
+mod search;
+mod session;
+mod two_factor;
+mod uploads;
+
+/// Number of messages requested per history page.
+const HISTORY_PAGE_SIZE: usize = 50;
+/// Minimum gap between typing notifications sent to the backend.
+const TYPING_NOTIFY_INTERVAL: Duration = Duration::from_secs(8);
 
 // ── TripwireApp ───────────────────────────────────────────────────────────────
 
@@ -25,11 +70,26 @@ pub mod auth_view;
 pub struct TripwireApp {
     pub(crate) focus_handle: FocusHandle,
     pub(crate) titlebar: Entity<TripwireTitleBar>,
+    pub(crate) backend: Arc<dyn ChatBackend>,
+    pub(crate) gateway: Option<GatewayConnection>,
+    pub(crate) _gateway_events: Option<Task<()>>,
+    pub(crate) _token_refresh: Option<Task<()>>,
 
     // ── Auth state ──────────────────────────────────────────────────────────
     pub(crate) auth: AuthState,
+    /// Showing the login screen to add another account.
+    pub(crate) adding_account: bool,
+    /// Loaded data of signed-in accounts other than the active one.
+    pub(crate) account_caches: HashMap<String, accounts::AccountCache>,
     pub(crate) email_input: Entity<InputState>,
     pub(crate) password_input: Entity<InputState>,
+    /// Six-digit code, shared by the login challenge and 2FA setup.
+    pub(crate) otp_input: Entity<OtpState>,
+    pub(crate) backup_code_input: Entity<InputState>,
+    /// Asking for a backup code instead of an authenticator code.
+    pub(crate) use_backup_code: bool,
+    /// Two-factor setup in progress on the Account screen.
+    pub(crate) mfa_setup: Option<two_factor::MfaSetup>,
 
     // ── App state ──────────────────────────────────────────────────────────
     pub(crate) current_view: AppView,
@@ -42,12 +102,58 @@ pub struct TripwireApp {
     pub(crate) active_dm_id: Option<String>,
     /// DM messages keyed by dm_id
     pub(crate) dm_messages: HashMap<String, Vec<Message>>,
+    /// On-disk cache of the active account's history, if it could be opened.
+    pub(crate) message_store: Option<MessageStore>,
+    /// Sent messages the server hasn't acknowledged yet.
+    pub(crate) outbox: Outbox,
+    /// In-flight sends and scheduled retries, by nonce.
+    pub(crate) outbox_tasks: HashMap<String, Task<()>>,
     pub(crate) message_input: Entity<InputState>,
+    pub(crate) message_list: message_list::MessageList,
+    /// How far each conversation has been read, for badges and the unread
+    /// divider.
+    pub(crate) read_states: ReadStates,
+    /// Who can be mentioned in the open conversation, shared with the
+    /// composers' completion providers.
+    pub(crate) mention_directory: Rc<RefCell<mentions::Directory>>,
     pub(crate) show_members: bool,
-    pub(crate) pending_attachment: Option<Attachment>,
+    /// Files attached in composers, uploading or ready to send.
+    pub(crate) pending_uploads: Vec<uploads::PendingUpload>,
+    pub(crate) next_upload_id: usize,
+    pub(crate) upload_limits: UploadLimits,
     pub(crate) emoji_search_input: Entity<InputState>,
     pub(crate) active_emoji_picker_message: Option<String>,
+    pub(crate) emoji_picker_scroll: UniformListScrollHandle,
+    /// Frequently used emoji and skin tone, shared by all accounts.
+    pub(crate) emoji_usage: EmojiUsage,
+    /// When and where new messages notify, shared by all accounts.
+    pub(crate) notification_settings: NotificationSettings,
+    pub(crate) notification_bus: Option<Arc<dyn NotificationBus>>,
+    /// The window this app renders in, for toasts raised outside of a
+    /// render or event handler.
+    pub(crate) window_handle: AnyWindowHandle,
     
+    // ── Search state ────────────────────────────────────────────────────────
+    pub(crate) search_input: Entity<InputState>,
+    /// Open search results; the search panel replaces the right sidebar.
+    pub(crate) search: Option<search::SearchResults>,
+    /// Message just jumped to from search, briefly highlighted.
+    pub(crate) highlighted_message: Option<String>,
+    pub(crate) _highlight_clear: Option<Task<()>>,
+
+    // ── Message rendering ───────────────────────────────────────────────────
+    /// Spoilers clicked open, by message ID and spoiler index.
+    pub(crate) revealed_spoilers: HashSet<(String, usize)>,
+    /// Syntax highlighting of fenced code, keyed by language, code and theme.
+    pub(crate) code_highlights: HashMap<u64, Vec<(Range<usize>, HighlightStyle)>>,
+    /// Previews of links in messages.
+    pub(crate) unfurler: Arc<Unfurler>,
+    /// Shared by all accounts.
+    pub(crate) privacy_settings: PrivacySettings,
+    /// Media open in the full-window viewer.
+    pub(crate) lightbox: Option<Lightbox>,
+    pub(crate) lightbox_focus: FocusHandle,
+
     // ── Reply state ─────────────────────────────────────────────────────────
     pub(crate) replying_to: Option<MessageReply>,
     
@@ -62,9 +168,12 @@ pub struct TripwireApp {
     
     // ── Edit state ──────────────────────────────────────────────────────────
     pub(crate) editing_message_id: Option<String>,
+    /// Message whose edit history dialog is open.
+    pub(crate) edit_history_message_id: Option<String>,
     
     // ── Typing indicators ────────────────────────────────────────────────────
-    pub(crate) typing_users: HashMap<String, Vec<String>>, // channel_id -> [user_ids]
+    pub(crate) typing_users: HashMap<String, HashMap<String, Instant>>, // channel_id -> user_id -> started
+    pub(crate) last_typing_sent: Option<Instant>,
     
     // ── Voice state ─────────────────────────────────────────────────────────
     pub(crate) voice_state: Option<VoiceState>,
@@ -81,13 +190,23 @@ pub struct TripwireApp {
     // ── Server Settings state ───────────────────────────────────────────────
     pub(crate) show_server_settings: bool,
     pub(crate) server_settings_screen: app_view::server_settings::ServerSettingsScreen,
+    /// Staged role changes while the Roles screen is open.
+    pub(crate) role_editor: Option<roles::RoleEditor>,
+    /// The member table and its dialog while the Members screen is open.
+    pub(crate) member_manager: Option<members::MemberManager>,
+    /// The audit log table while the Audit Log screen is open.
+    pub(crate) audit_log: Option<audit_log::AuditLogViewer>,
+    /// The open create, settings or delete dialog for channels.
+    pub(crate) channel_dialog: Option<channels::ChannelDialog>,
 
     pub(crate) _subscriptions: Vec<Subscription>,
 }
 
 impl TripwireApp {
-    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
-        let auth = AuthState::new();
+    pub fn new(backend: Arc<dyn ChatBackend>, auth: AuthState, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let emoji_usage = EmojiUsage::load(auth.data_dir());
+        let notification_settings = NotificationSettings::load(auth.data_dir());
+        let privacy_settings = PrivacySettings::load(auth.data_dir());
         let titlebar = cx.new(|cx| TripwireTitleBar::new(window, cx));
 
         // Auth inputs
@@ -95,21 +214,9 @@ impl TripwireApp {
             cx.new(|cx| InputState::new(window, cx).placeholder("Email or username"));
         let password_input =
             cx.new(|cx| InputState::new(window, cx).placeholder("Password").masked(true));
-
-        // Pre-load first server / channel messages
-        let servers = mock_data::make_servers();
-        let active_channel_id = servers
-            .first()
-            .and_then(|s| s.all_channels().first().map(|c| c.id.clone()));
-
-        let mut messages: HashMap<String, Vec<Message>> = HashMap::new();
-        if let Some(ref ch_id) = active_channel_id {
-            messages.insert(ch_id.clone(), mock_data::make_messages_for(ch_id));
-        }
-
-        // Pre-load DM channels
-        let dm_channels = mock_data::make_dm_channels();
-        let dm_messages: HashMap<String, Vec<Message>> = HashMap::new();
+        let otp_input = cx.new(|cx| OtpState::new(totp::DIGITS as usize, window, cx));
+        let backup_code_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder("xxxx-xxxx"));
 
         let message_input =
             cx.new(|cx| InputState::new(window, cx).placeholder("Send a message..."));
@@ -119,38 +226,134 @@ impl TripwireApp {
             cx.new(|cx| InputState::new(window, cx).placeholder("Chat in voice..."));
         let emoji_search_input =
             cx.new(|cx| InputState::new(window, cx).placeholder("Search emojis..."));
+        let search_input =
+            cx.new(|cx| InputState::new(window, cx).placeholder("Search"));
+
+        // `@`, `#` and `:` completions in the composers
+        let mention_directory = Rc::new(RefCell::new(mentions::Directory::default()));
+        for input in [&message_input, &thread_input] {
+            let provider = mention_completions::MentionCompletions {
+                directory: mention_directory.clone(),
+            };
+            input.update(cx, |state, _| state.lsp.completion_provider = Some(Rc::new(provider)));
+        }
 
         // Subscribe message input to catch Enter key to send
         let msg_sub = cx.subscribe(
             &message_input,
             |this: &mut TripwireApp, _, event: &InputEvent, cx| {
+                match event {
+                    InputEvent::PressEnter { .. } => {
+                        // Pressing enter outside multi-line triggers send
+                        // We handle this via the send button; here just notify
+                        cx.notify();
+                    }
+                    InputEvent::Change => this.notify_typing(cx),
+                    _ => {}
+                }
+            },
+        );
+
+        // Enter in the password field submits the login form
+        let password_sub = cx.subscribe(
+            &password_input,
+            |this: &mut TripwireApp, _, event: &InputEvent, cx| {
+                if let InputEvent::PressEnter { .. } = event {
+                    this.submit_login(cx);
+                }
+            },
+        );
+
+        // A complete code submits itself
+        let otp_sub = cx.subscribe_in(
+            &otp_input,
+            window,
+            |this: &mut TripwireApp, _, event: &InputEvent, window, cx| {
+                if let InputEvent::Change = event {
+                    this.on_otp_complete(window, cx);
+                }
+            },
+        );
+        let backup_code_sub = cx.subscribe_in(
+            &backup_code_input,
+            window,
+            |this: &mut TripwireApp, _, event: &InputEvent, window, cx| {
                 if let InputEvent::PressEnter { .. } = event {
-                    // Pressing enter outside multi-line triggers send
-                    // We handle this via the send button; here just notify
+                    this.on_otp_complete(window, cx);
+                }
+            },
+        );
+
+        // Enter runs the search; clearing the box closes the results
+        let search_sub = cx.subscribe(
+            &search_input,
+            |this: &mut TripwireApp, input, event: &InputEvent, cx| match event {
+                InputEvent::PressEnter { .. } => this.run_search(cx),
+                InputEvent::Change if input.read(cx).value().is_empty() => {
+                    this.search = None;
                     cx.notify();
                 }
+                _ => {}
             },
         );
 
-        Self {
+        let mut this = Self {
             focus_handle: cx.focus_handle(),
             titlebar,
+            backend,
+            gateway: None,
+            _gateway_events: None,
+            _token_refresh: None,
             auth,
+            adding_account: false,
+            account_caches: HashMap::new(),
             email_input,
             password_input,
+            otp_input,
+            backup_code_input,
+            use_backup_code: false,
+            mfa_setup: None,
             current_view: AppView::Servers,
-            servers,
+            servers: Vec::new(),
             active_server: 0,
-            active_channel_id,
-            messages,
-            dm_channels,
+            active_channel_id: None,
+            messages: HashMap::new(),
+            dm_channels: Vec::new(),
             active_dm_id: None,
-            dm_messages,
+            dm_messages: HashMap::new(),
+            message_store: None,
+            outbox: Outbox::default(),
+            outbox_tasks: HashMap::new(),
             message_input,
+            message_list: message_list::MessageList::new(cx),
+            read_states: ReadStates::default(),
+            mention_directory,
             show_members: true,
-            pending_attachment: None,
+            pending_uploads: Vec::new(),
+            next_upload_id: 0,
+            upload_limits: UploadLimits::default(),
             emoji_search_input,
             active_emoji_picker_message: None,
+            emoji_picker_scroll: UniformListScrollHandle::new(),
+            emoji_usage,
+            notification_settings,
+            notification_bus: crate::notifications::bus::desktop_bus(),
+            window_handle: window.window_handle(),
+            search_input,
+            search: None,
+            highlighted_message: None,
+            _highlight_clear: None,
+            revealed_spoilers: HashSet::new(),
+            code_highlights: HashMap::new(),
+            // Its own client, so links can only reach what `is_public_ip`
+            // allows, however their names resolve by the time they're fetched.
+            unfurler: Arc::new(Unfurler::new(
+                Arc::new(ReqwestClient::with_address_filter(unfurl::is_public_ip)),
+                UnfurlLimits::default(),
+            )),
+            privacy_settings,
+            lightbox: None,
+            lightbox_focus: cx.focus_handle(),
             replying_to: None,
             open_thread_id: None,
             thread_messages: HashMap::new(),
@@ -158,7 +361,9 @@ impl TripwireApp {
             show_voice_chat_sidebar: false,
             voice_chat_input,
             editing_message_id: None,
+            edit_history_message_id: None,
             typing_users: HashMap::new(),
+            last_typing_sent: None,
             voice_state: None,
             show_voice_switch_warning: None,
             show_profile: None,
@@ -167,8 +372,185 @@ impl TripwireApp {
             settings_screen: SettingsScreen::Account,
             show_server_settings: false,
             server_settings_screen: app_view::server_settings::ServerSettingsScreen::Overview,
-            _subscriptions: vec![msg_sub],
+            role_editor: None,
+            member_manager: None,
+            audit_log: None,
+            channel_dialog: None,
+            _subscriptions: vec![msg_sub, password_sub, otp_sub, backup_code_sub, search_sub],
+        };
+        this.restore_session(cx);
+        this
+    }
+
+    // ── Backend ────────────────────────────────────────────────────────────
+
+    /// Hand the current auth session to the backend and (re)load the server
+    /// and DM lists. Called on startup, after login and on logout.
+    pub(crate) fn sync_backend_session(&mut self, cx: &mut Context<Self>) {
+        let session = self.backend_session();
+        let logged_in = session.is_some();
+        self.backend.set_session(session);
+        self.open_message_store();
+        self.restore_outbox(cx);
+
+        self.servers.clear();
+        self.dm_channels.clear();
+        self.messages.clear();
+        self.dm_messages.clear();
+        self.active_server = 0;
+        self.active_channel_id = None;
+        self.active_dm_id = None;
+        self.typing_users.clear();
+        self.search = None;
+        self.highlighted_message = None;
+        self.message_list.clear();
+        self.read_states.clear();
+        self.pending_uploads.clear();
+        if logged_in {
+            self.load_servers_and_dms(cx);
+        }
+        self.connect_gateway(cx);
+        self.schedule_token_refresh(cx);
+        cx.notify();
+    }
+
+    pub(crate) fn backend_session(&self) -> Option<BackendSession> {
+        self.auth
+            .current_user
+            .clone()
+            .zip(self.auth.token.clone())
+            .map(|(user, token)| BackendSession { user, token })
+    }
+
+    fn load_servers_and_dms(&mut self, cx: &mut Context<Self>) {
+        self.restore_stored_lists(cx);
+        self.load_read_states(cx);
+        self.load_upload_limits(cx);
+        let servers = self.backend.list_servers();
+        let dm_channels = self.backend.list_dm_channels();
+        cx.spawn(async move |this, cx| {
+            let (servers, dm_channels) = futures::join!(servers, dm_channels);
+            this.update(cx, |this, cx| {
+                match servers {
+                    Ok(servers) => {
+                        this.apply_servers(servers, cx);
+                        this.store_server_list();
+                    }
+                    Err(err) => eprintln!("Failed to load servers: {err:#}"),
+                }
+                match dm_channels {
+                    Ok(dm_channels) => {
+                        this.dm_channels = dm_channels;
+                        this.store_dm_list();
+                    }
+                    Err(err) => eprintln!("Failed to load direct messages: {err:#}"),
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Take a fresh server list, opening the first channel the user can see
+    /// if none is open.
+    pub(crate) fn apply_servers(&mut self, servers: Vec<Server>, cx: &mut Context<Self>) {
+        self.servers = servers;
+        if self.active_channel_id.is_none() {
+            let first_channel = self.first_visible_channel().map(|c| c.id.clone());
+            if let Some(channel_id) = first_channel {
+                self.load_history(MessageTarget::Channel(channel_id.clone()), cx);
+                self.active_channel_id = Some(channel_id);
+            }
+        }
+    }
+
+    /// Fetch the newest page of history for `target` unless it's already
+    /// loaded. Whatever the disk cache has is shown until the page arrives.
+    fn load_history(&mut self, target: MessageTarget, cx: &mut Context<Self>) {
+        if self.cached_messages(&target).is_some() {
+            return;
+        }
+        if let Some(stored) = self.stored_history(target.id()) {
+            self.history_map(&target).insert(target.id().to_string(), stored);
+            self.append_outbox_echoes(&target);
+        }
+        let history = self.backend.fetch_history(&target, None, HISTORY_PAGE_SIZE);
+        cx.spawn(async move |this, cx| match history.await {
+            Ok(history) => {
+                this.update(cx, |this, cx| {
+                    let complete = history.len() < HISTORY_PAGE_SIZE;
+                    if complete {
+                        this.message_list.mark_complete(target.id());
+                    }
+                    this.store_history(target.id(), &history, complete);
+                    let entry = this
+                        .history_map(&target)
+                        .entry(target.id().to_string())
+                        .or_default();
+                    *entry = message_store::merge_latest(std::mem::take(entry), history, complete);
+                    this.append_outbox_echoes(&target);
+                    cx.notify();
+                })
+                .ok();
+            }
+            Err(err) => eprintln!("Failed to load history for {}: {err:#}", target.id()),
+        })
+        .detach();
+    }
+
+    /// Append `message` to the cached history of `target`, ignoring
+    /// duplicates (our own sends arrive both as an ack and over the gateway).
+    /// Uncached conversations are left alone; they load in full on open.
+    pub(crate) fn push_message(&mut self, target: &MessageTarget, message: Message) {
+        let Some(messages) = self.cached_messages_mut(target) else {
+            return;
+        };
+        if messages.iter().any(|m| m.id == message.id) {
+            return;
         }
+        messages.push(message.clone());
+        self.store_messages(target.id(), &[message]);
+    }
+
+    /// Run a fire-and-forget backend call, logging failures.
+    fn detach_backend_call(&self, call: BackendFuture<()>, what: &'static str, cx: &mut Context<Self>) {
+        cx.background_spawn(async move {
+            if let Err(err) = call.await {
+                eprintln!("Failed to {what}: {err:#}");
+            }
+        })
+        .detach();
+    }
+
+    fn notify_typing(&mut self, cx: &mut Context<Self>) {
+        let Some(target) = self.active_target() else {
+            return;
+        };
+        if self.message_input.read(cx).value().is_empty() {
+            return;
+        }
+        if self
+            .last_typing_sent
+            .is_some_and(|sent| sent.elapsed() < TYPING_NOTIFY_INTERVAL)
+        {
+            return;
+        }
+        self.last_typing_sent = Some(Instant::now());
+        let call = self.backend.start_typing(&target);
+        self.detach_backend_call(call, "send typing indicator", cx);
+    }
+
+    /// Set the current user's presence; Do Not Disturb also silences
+    /// notifications.
+    pub(crate) fn set_status(&mut self, status: UserStatus, cx: &mut Context<Self>) {
+        let Some(user) = self.auth.current_user.as_mut() else {
+            return;
+        };
+        user.status = status.clone();
+        let call = self.backend.set_presence(status);
+        self.detach_backend_call(call, "set status", cx);
+        cx.notify();
     }
 
     // ── Queries ────────────────────────────────────────────────────────────
@@ -212,6 +594,35 @@ impl TripwireApp {
             .unwrap_or(&[])
     }
 
+    /// The channel or DM the composer currently posts to.
+    pub(crate) fn active_target(&self) -> Option<MessageTarget> {
+        match self.current_view {
+            AppView::Servers => self.active_channel_id.clone().map(MessageTarget::Channel),
+            AppView::DirectMessages => self.active_dm_id.clone().map(MessageTarget::DirectMessage),
+        }
+    }
+
+    fn cached_messages(&self, target: &MessageTarget) -> Option<&Vec<Message>> {
+        match target {
+            MessageTarget::Channel(id) => self.messages.get(id),
+            MessageTarget::DirectMessage(id) => self.dm_messages.get(id),
+        }
+    }
+
+    fn history_map(&mut self, target: &MessageTarget) -> &mut HashMap<String, Vec<Message>> {
+        match target {
+            MessageTarget::Channel(_) => &mut self.messages,
+            MessageTarget::DirectMessage(_) => &mut self.dm_messages,
+        }
+    }
+
+    fn cached_messages_mut(&mut self, target: &MessageTarget) -> Option<&mut Vec<Message>> {
+        match target {
+            MessageTarget::Channel(id) => self.messages.get_mut(id),
+            MessageTarget::DirectMessage(id) => self.dm_messages.get_mut(id),
+        }
+    }
+
     // ── Mutations ─────────────────────────────────────────────────────────
 
     pub(crate) fn switch_to_servers(&mut self, cx: &mut Context<Self>) {
@@ -229,13 +640,10 @@ impl TripwireApp {
         self.current_view = AppView::Servers;
         self.active_server = index;
         self.active_dm_id = None;
-        if let Some(server) = self.servers.get(index) {
-            let channel_id = server.all_channels().first().map(|c| c.id.clone());
+        if self.servers.get(index).is_some() {
+            let channel_id = self.first_visible_channel().map(|c| c.id.clone());
             if let Some(ref ch_id) = channel_id {
-                if !self.messages.contains_key(ch_id.as_str()) {
-                    self.messages
-                        .insert(ch_id.clone(), mock_data::make_messages_for(ch_id));
-                }
+                self.load_history(MessageTarget::Channel(ch_id.clone()), cx);
             }
             self.active_channel_id = channel_id;
         }
@@ -249,10 +657,7 @@ impl TripwireApp {
         cx: &mut Context<Self>,
     ) {
         self.current_view = AppView::Servers;
-        if !self.messages.contains_key(&channel_id) {
-            self.messages
-                .insert(channel_id.clone(), mock_data::make_messages_for(&channel_id));
-        }
+        self.load_history(MessageTarget::Channel(channel_id.clone()), cx);
         self.active_channel_id = Some(channel_id);
         self.active_dm_id = None;
         cx.notify();
@@ -265,10 +670,7 @@ impl TripwireApp {
         cx: &mut Context<Self>,
     ) {
         self.current_view = AppView::DirectMessages;
-        if !self.dm_messages.contains_key(&dm_id) {
-            self.dm_messages
-                .insert(dm_id.clone(), mock_data::make_dm_messages_for(&dm_id));
-        }
+        self.load_history(MessageTarget::DirectMessage(dm_id.clone()), cx);
         self.active_dm_id = Some(dm_id);
         self.active_channel_id = None;
         cx.notify();
@@ -289,123 +691,43 @@ impl TripwireApp {
 
     pub(crate) fn send_message(&mut self, window: &mut Window, cx: &mut Context<Self>) {
         let content = self.message_input.read(cx).value().trim().to_string();
-        if content.is_empty() && self.pending_attachment.is_none() {
+        let Some(target) = self.active_target() else {
+            return;
+        };
+        if !self.can(Permissions::SEND_MESSAGES) {
             return;
         }
-        
-        if let Some(user) = self.auth.current_user.clone() {
-            let msg = Message {
-                id: timestamp_id(),
-                author: user,
-                content,
-                timestamp: "Just now".to_string(),
-                edited: false,
-                edited_timestamp: None,
-                attachment: self.pending_attachment.take(),
-                reactions: std::collections::HashMap::new(),
-                reply_to: self.replying_to.take().map(Box::new),
-                mentioned_users: vec![],
-                pinned: false,
-                thread_id: None,
-                thread_count: 0,
-                created_at: std::time::SystemTime::now(),
-            };
-            
-            match self.current_view {
-                AppView::Servers => {
-                    if let Some(channel_id) = self.active_channel_id.clone() {
-                        self.messages.entry(channel_id).or_default().push(msg);
-                    }
-                }
-                AppView::DirectMessages => {
-                    if let Some(dm_id) = self.active_dm_id.clone() {
-                        self.dm_messages.entry(dm_id).or_default().push(msg);
-                    }
-                }
-            }
+        let composer = uploads::Composer::Conversation(target.clone());
+        if content.is_empty() && self.pending_uploads_for(&composer).next().is_none() {
+            return;
         }
-        
-        self.message_input.update(cx, |state, cx| {
-            state.set_value("", window, cx);
-        });
-        cx.notify();
-    }
-
-    pub(crate) fn attach_file(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        let paths_future = cx.prompt_for_paths(gpui::PathPromptOptions {
-            files: true,
-            directories: false,
-            multiple: false,
-            prompt: Some("Select an image or GIF".into()),
-        });
-
-        let entity = cx.entity();
-        cx.spawn_in(window, async move |_, window| {
-            if let Ok(Ok(Some(paths))) = paths_future.await {
-                if let Some(path) = paths.first() {
-                    window.update(|window, cx| {
-                        entity.update(cx, |this, cx| {
-                            this.process_attachment(path.clone(), window, cx);
-                        })
-                    }).ok();
-                }
-            }
-        }).detach();
-    }
-
-    fn process_attachment(
-        &mut self,
-        path: std::path::PathBuf,
-        _window: &mut Window,
-        cx: &mut Context<Self>,
-    ) {
-        // Read file
-        if let Ok(data) = std::fs::read(&path) {
-            // Check file size (5MB limit)
-            let size = data.len();
-            if size > 5 * 1024 * 1024 {
-                // TODO: Show error notification
-                eprintln!("File too large: {} bytes (max 5MB)", size);
+        let attachments = match self.take_attachments(&composer) {
+            Ok(attachments) => attachments,
+            Err(reason) => {
+                window.push_notification(Notification::warning(reason), cx);
                 return;
             }
-
-            // Determine MIME type from extension
-            let mime_type = match path.extension().and_then(|s| s.to_str()) {
-                Some("png") => "image/png",
-                Some("jpg") | Some("jpeg") => "image/jpeg",
-                Some("gif") => "image/gif",
-                Some("webp") => "image/webp",
-                _ => {
-                    eprintln!("Unsupported file type");
-                    return;
-                }
-            };
-
-            // Convert to base64
-            let base64_data = base64_encode(&data);
-
-            // Get filename
-            let filename = path
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("unknown")
-                .to_string();
-
-            self.pending_attachment = Some(Attachment {
-                filename,
-                mime_type: mime_type.to_string(),
-                base64_data,
-                size,
-            });
-
+        };
+        if content.is_empty() && attachments.is_empty() {
             cx.notify();
-        } else {
-            eprintln!("Failed to read file: {:?}", path);
+            return;
         }
-    }
 
-    pub(crate) fn clear_attachment(&mut self, cx: &mut Context<Self>) {
-        self.pending_attachment = None;
+        let (content, mentioned_users) = self.serialize_mentions(&content);
+        let draft = MessageDraft {
+            content,
+            attachments,
+            reply_to: self.replying_to.take(),
+            mentioned_users,
+            nonce: None,
+        };
+        self.enqueue_message(target, draft, cx);
+        self.message_list.scroll_to_present();
+        self.last_typing_sent = None;
+        
+        self.message_input.update(cx, |state, cx| {
+            state.set_value("", window, cx);
+        });
         cx.notify();
     }
 
@@ -415,32 +737,33 @@ impl TripwireApp {
         emoji: String,
         cx: &mut Context<Self>,
     ) {
-        if let Some(user_id) = self.auth.current_user.as_ref().map(|u| u.id.clone()) {
-            let messages = match self.current_view {
-                AppView::Servers => {
-                    if let Some(channel_id) = &self.active_channel_id {
-                        self.messages.get_mut(channel_id)
-                    } else {
-                        None
-                    }
-                }
-                AppView::DirectMessages => {
-                    if let Some(dm_id) = &self.active_dm_id {
-                        self.dm_messages.get_mut(dm_id)
-                    } else {
-                        None
-                    }
-                }
-            };
-
-            if let Some(messages) = messages {
-                if let Some(msg) = messages.iter_mut().find(|m| m.id == message_id) {
-                    msg.toggle_reaction(emoji, user_id);
-                }
-            }
+        let Some(user_id) = self.auth.current_user.as_ref().map(|u| u.id.clone()) else {
+            return;
+        };
+        let Some(target) = self.active_target() else {
+            return;
+        };
 
-            cx.notify();
+        let add = self
+            .cached_messages(&target)
+            .and_then(|messages| messages.iter().find(|m| m.id == message_id))
+            .is_some_and(|msg| !msg.user_reacted(&emoji, &user_id));
+        // Taking a reaction back is always allowed.
+        if add && !self.can(Permissions::ADD_REACTIONS) {
+            return;
         }
+        let Some(msg) = self
+            .cached_messages_mut(&target)
+            .and_then(|messages| messages.iter_mut().find(|m| m.id == message_id))
+        else {
+            return;
+        };
+        msg.toggle_reaction(emoji.clone(), user_id);
+        self.store_message(&target, &message_id);
+
+        let call = self.backend.set_reaction(&target, &message_id, &emoji, add);
+        self.detach_backend_call(call, "update reaction", cx);
+        cx.notify();
     }
 
     pub(crate) fn start_reply(&mut self, message: &Message, cx: &mut Context<Self>) {
@@ -494,15 +817,40 @@ impl TripwireApp {
     pub(crate) fn open_server_settings(&mut self, cx: &mut Context<Self>) {
         self.show_server_settings = true;
         self.server_settings_screen = app_view::server_settings::ServerSettingsScreen::Overview;
+        self.role_editor = None;
+        self.member_manager = None;
+        self.audit_log = None;
         cx.notify();
     }
     
+    /// Close server settings, unless there are unsaved role changes.
     pub(crate) fn close_server_settings(&mut self, cx: &mut Context<Self>) {
+        if self.hold_unsaved_roles(cx) {
+            return;
+        }
         self.show_server_settings = false;
+        self.role_editor = None;
+        self.member_manager = None;
+        self.audit_log = None;
         cx.notify();
     }
     
-    pub(crate) fn switch_server_settings_screen(&mut self, screen: app_view::server_settings::ServerSettingsScreen, cx: &mut Context<Self>) {
+    pub(crate) fn switch_server_settings_screen(&mut self, screen: app_view::server_settings::ServerSettingsScreen, window: &mut Window, cx: &mut Context<Self>) {
+        if screen == self.server_settings_screen {
+            return;
+        }
+        if self.hold_unsaved_roles(cx) {
+            return;
+        }
+        self.role_editor = None;
+        self.member_manager = None;
+        self.audit_log = None;
+        match screen {
+            app_view::server_settings::ServerSettingsScreen::Roles => self.open_role_editor(window, cx),
+            app_view::server_settings::ServerSettingsScreen::Members => self.open_member_manager(window, cx),
+            app_view::server_settings::ServerSettingsScreen::AuditLog => self.open_audit_log(window, cx),
+            _ => {}
+        }
         self.server_settings_screen = screen;
         cx.notify();
     }
@@ -511,31 +859,18 @@ impl TripwireApp {
     
     pub(crate) fn start_edit_message(&mut self, message_id: String, window: &mut Window, cx: &mut Context<Self>) {
         // Find the message and populate input with its content
-        let messages = match self.current_view {
-            AppView::Servers => {
-                if let Some(channel_id) = &self.active_channel_id {
-                    self.messages.get(channel_id)
-                } else {
-                    None
-                }
-            }
-            AppView::DirectMessages => {
-                if let Some(dm_id) = &self.active_dm_id {
-                    self.dm_messages.get(dm_id)
-                } else {
-                    None
-                }
-            }
-        };
-        
-        if let Some(messages) = messages {
-            if let Some(msg) = messages.iter().find(|m| m.id == message_id) {
-                self.editing_message_id = Some(message_id);
-                self.message_input.update(cx, |state, cx| {
-                    state.set_value(&msg.content, window, cx);
-                });
-                cx.notify();
-            }
+        let content = self
+            .active_target()
+            .and_then(|target| self.cached_messages(&target))
+            .and_then(|messages| messages.iter().find(|m| m.id == message_id))
+            .map(|msg| msg.content.clone());
+
+        if let Some(content) = content {
+            self.editing_message_id = Some(message_id);
+            self.message_input.update(cx, |state, cx| {
+                state.set_value(&content, window, cx);
+            });
+            cx.notify();
         }
     }
     
@@ -553,30 +888,61 @@ impl TripwireApp {
             if new_content.is_empty() {
                 return;
             }
+            let unchanged = self.active_target().is_some_and(|target| {
+                self.cached_messages(&target)
+                    .and_then(|messages| messages.iter().find(|m| m.id == message_id))
+                    .is_some_and(|message| message.content == new_content)
+            });
+            if unchanged {
+                self.cancel_edit(window, cx);
+                return;
+            }
             
-            let messages = match self.current_view {
-                AppView::Servers => {
-                    if let Some(channel_id) = &self.active_channel_id {
-                        self.messages.get_mut(channel_id)
-                    } else {
-                        None
+            if let Some(target) = self.active_target() {
+                // Kept to put back if the backend turns the edit down.
+                let mut previous = None;
+                if let Some(messages) = self.cached_messages_mut(&target) {
+                    if let Some(msg) = messages.iter_mut().find(|m| m.id == message_id) {
+                        previous = Some(msg.clone());
+                        msg.edit(new_content.clone(), "Just now".to_string());
                     }
                 }
-                AppView::DirectMessages => {
-                    if let Some(dm_id) = &self.active_dm_id {
-                        self.dm_messages.get_mut(dm_id)
-                    } else {
-                        None
+                self.store_message(&target, &message_id);
+
+                let edit = self.backend.edit_message(&target, &message_id, new_content);
+                cx.spawn(async move |this, cx| match edit.await {
+                    Ok(stored) => {
+                        this.update(cx, |this, cx| {
+                            if let Some(msg) = this
+                                .cached_messages_mut(&target)
+                                .and_then(|messages| messages.iter_mut().find(|m| m.id == stored.id))
+                            {
+                                let id = stored.id.clone();
+                                *msg = stored;
+                                this.store_message(&target, &id);
+                                cx.notify();
+                            }
+                        })
+                        .ok();
                     }
-                }
-            };
-            
-            if let Some(messages) = messages {
-                if let Some(msg) = messages.iter_mut().find(|m| m.id == message_id) {
-                    msg.content = new_content;
-                    msg.edited = true;
-                    msg.edited_timestamp = Some("Just now".to_string());
-                }
+                    Err(err) => {
+                        this.update(cx, |this, cx| {
+                            if let Some(previous) = previous
+                                && let Some(msg) = this
+                                    .cached_messages_mut(&target)
+                                    .and_then(|messages| messages.iter_mut().find(|m| m.id == previous.id))
+                            {
+                                let id = previous.id.clone();
+                                *msg = previous;
+                                this.store_message(&target, &id);
+                            }
+                            this.show_error(format!("Couldn't edit the message: {err:#}"), cx);
+                            cx.notify();
+                        })
+                        .ok();
+                    }
+                })
+                .detach();
             }
             
             self.cancel_edit(window, cx);
@@ -584,66 +950,99 @@ impl TripwireApp {
     }
     
     pub(crate) fn delete_message(&mut self, message_id: String, cx: &mut Context<Self>) {
-        let messages = match self.current_view {
-            AppView::Servers => {
-                if let Some(channel_id) = &self.active_channel_id {
-                    self.messages.get_mut(channel_id)
-                } else {
-                    None
-                }
-            }
-            AppView::DirectMessages => {
-                if let Some(dm_id) = &self.active_dm_id {
-                    self.dm_messages.get_mut(dm_id)
-                } else {
-                    None
-                }
-            }
+        let Some(target) = self.active_target() else {
+            return;
         };
-        
-        if let Some(messages) = messages {
-            messages.retain(|m| m.id != message_id);
+        if self.delivery_state(&message_id).is_some() {
+            self.discard_message(&message_id, cx);
+            return;
         }
-        
+        let allowed = self
+            .cached_messages(&target)
+            .and_then(|messages| messages.iter().find(|m| m.id == message_id))
+            .is_some_and(|message| self.can_delete_message(message));
+        if !allowed {
+            return;
+        }
+        // Kept, with where it was, to put back if the backend turns the
+        // delete down.
+        let removed = self.cached_messages_mut(&target).and_then(|messages| {
+            let ix = messages.iter().position(|m| m.id == message_id)?;
+            Some((ix, messages.remove(ix)))
+        });
+        self.unstore_message(target.id(), &message_id);
+
+        let call = self.backend.delete_message(&target, &message_id);
+        cx.spawn(async move |this, cx| {
+            let Err(err) = call.await else {
+                return;
+            };
+            this.update(cx, |this, cx| {
+                if let Some((ix, message)) = removed
+                    && let Some(messages) = this.cached_messages_mut(&target)
+                    && !messages.iter().any(|m| m.id == message.id)
+                {
+                    let id = message.id.clone();
+                    messages.insert(ix.min(messages.len()), message);
+                    this.store_message(&target, &id);
+                }
+                this.show_error(format!("Couldn't delete the message: {err:#}"), cx);
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+
         cx.notify();
     }
     
     // ── Typing indicator helpers ────────────────────────────────────────────
     
+    /// Display names of other users typing in the active channel or DM.
     pub(crate) fn get_typing_users(&self) -> Vec<String> {
-        let channel_or_dm_id = match self.current_view {
-            AppView::Servers => self.active_channel_id.as_ref(),
-            AppView::DirectMessages => self.active_dm_id.as_ref(),
+        let Some(target) = self.active_target() else {
+            return Vec::new();
         };
-        
-        channel_or_dm_id
-            .and_then(|id| self.typing_users.get(id))
-            .map(|users| {
-                users
+        let Some(typing) = self.typing_users.get(target.id()) else {
+            return Vec::new();
+        };
+        let current_user_id = self.auth.current_user.as_ref().map(|u| u.id.as_str());
+
+        let mut names: Vec<String> = typing
+            .keys()
+            .filter(|user_id| Some(user_id.as_str()) != current_user_id)
+            .filter_map(|user_id| match &target {
+                MessageTarget::Channel(_) => self
+                    .active_server()?
+                    .members
+                    .iter()
+                    .find(|m| &m.id == user_id)
+                    .map(|m| m.display_name()),
+                MessageTarget::DirectMessage(dm_id) => self
+                    .dm_channels
                     .iter()
-                    .filter_map(|user_id| {
-                        // Look up username from servers or DMs
-                        if let Some(server) = self.active_server() {
-                            server.members.iter()
-                                .find(|m| &m.id == user_id)
-                                .map(|m| m.username.clone())
-                        } else if let Some(dm_id) = &self.active_dm_id {
-                            self.dm_channels.iter()
-                                .find(|dm| &dm.id == dm_id)
-                                .map(|dm| dm.recipient.username.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect()
+                    .find(|dm| &dm.id == dm_id && &dm.recipient.id == user_id)
+                    .map(|dm| dm.recipient.display_name()),
             })
-            .unwrap_or_default()
+            .collect();
+        names.sort();
+        names
     }
 
+    /// Sign out of the active account and continue with the next one, if
+    /// any other is signed in.
     pub(crate) fn logout(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(user) = &self.auth.current_user {
+            self.account_caches.remove(&user.id);
+        }
+        self.mfa_setup = None;
+        self.role_editor = None;
+        self.member_manager = None;
+        self.audit_log = None;
+        self.channel_dialog = None;
+        self.delete_message_store();
         self.auth.logout();
-        // Clear message input value happens implicitly since we reset auth
-        cx.notify();
+        self.resume_active_account(cx);
     }
     
     // ── Voice management helpers ────────────────────────────────────────────
@@ -655,6 +1054,15 @@ impl TripwireApp {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
+        let allowed = match server {
+            Some(server) => self.auth.current_user.as_ref().is_some_and(|user| {
+                crate::permissions::compute_permissions(server, &user.id, channel).contains(Permissions::CONNECT)
+            }),
+            None => true,
+        };
+        if !allowed {
+            return;
+        }
         // If already in a voice channel, show warning
         if let Some(ref current_voice) = self.voice_state {
             if current_voice.channel_id != channel.id {
@@ -765,57 +1173,18 @@ impl Focusable for TripwireApp {
 use gpui::{IntoElement, Render};
 use gpui::Styled;
 use gpui::ParentElement;
-use gpui_component::v_flex;
+use gpui_component::{Root, v_flex};
 
 impl Render for TripwireApp {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         v_flex()
             .size_full()
             .child(self.titlebar.clone())
-            .child(if self.auth.is_authenticated() {
+            .child(if self.auth.is_authenticated() && !self.adding_account {
                 self.render_app(window, cx).into_any_element()
             } else {
                 self.render_auth(window, cx).into_any_element()
             })
+            .children(Root::render_notification_layer(window, cx))
     }
 }
-
-// ── Helpers ───────────────────────────────────────────────────────────────────
-
-fn timestamp_id() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_nanos())
-        .unwrap_or(0);
-    format!("msg_{nanos}")
-}
-
-fn base64_encode(data: &[u8]) -> String {
-    const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-    let mut result = String::with_capacity((data.len() + 2) / 3 * 4);
-    
-    for chunk in data.chunks(3) {
-        let mut buf = [0u8; 3];
-        for (i, &byte) in chunk.iter().enumerate() {
-            buf[i] = byte;
-        }
-        
-        result.push(CHARS[(buf[0] >> 2) as usize] as char);
-        result.push(CHARS[(((buf[0] & 0x03) << 4) | (buf[1] >> 4)) as usize] as char);
-        
-        if chunk.len() > 1 {
-            result.push(CHARS[(((buf[1] & 0x0f) << 2) | (buf[2] >> 6)) as usize] as char);
-            if chunk.len() > 2 {
-                result.push(CHARS[(buf[2] & 0x3f) as usize] as char);
-            } else {
-                result.push('=');
-            }
-        } else {
-            result.push('=');
-            result.push('=');
-        }
-    }
-    
-    result
-}
@@ -0,0 +1,121 @@
+//! Multiple signed-in accounts — switching between them, and keeping each
+//! account's loaded servers, DMs and messages so switching back is instant.
+
+use std::collections::HashMap;
+use std::mem;
+use std::time::Duration;
+
+use gpui::Context;
+
+use crate::app::{AppView, TripwireApp};
+use crate::models::{DirectMessageChannel, Message, Server};
+use crate::read_state::ReadStates;
+
+/// What was loaded for an account while it was active.
+pub(crate) struct AccountCache {
+    current_view: AppView,
+    servers: Vec<Server>,
+    active_server: usize,
+    active_channel_id: Option<String>,
+    messages: HashMap<String, Vec<Message>>,
+    dm_channels: Vec<DirectMessageChannel>,
+    active_dm_id: Option<String>,
+    dm_messages: HashMap<String, Vec<Message>>,
+    read_states: ReadStates,
+}
+
+impl TripwireApp {
+    pub(crate) fn switch_account(&mut self, user_id: &str, cx: &mut Context<Self>) {
+        let already_active = self
+            .auth
+            .current_user
+            .as_ref()
+            .is_some_and(|user| user.id == user_id);
+        if already_active || !self.auth.accounts.iter().any(|a| a.user.id == user_id) {
+            return;
+        }
+
+        self.stash_account_cache();
+        // Per-conversation UI state belongs to the account being left.
+        self.replying_to = None;
+        self.editing_message_id = None;
+        self.open_thread_id = None;
+        self.show_profile = None;
+        self.voice_state = None;
+        self.mfa_setup = None;
+        self.search = None;
+        self.highlighted_message = None;
+        self.message_list.clear();
+        self.auth.switch_account(user_id);
+        self.adding_account = false;
+        self.resume_active_account(cx);
+    }
+
+    /// Show the login screen on top of the signed-in accounts.
+    pub(crate) fn begin_add_account(&mut self, cx: &mut Context<Self>) {
+        self.adding_account = true;
+        self.auth.login_error = None;
+        cx.notify();
+    }
+
+    pub(crate) fn cancel_add_account(&mut self, cx: &mut Context<Self>) {
+        self.adding_account = false;
+        self.auth.login_error = None;
+        self.auth.mfa_ticket = None;
+        cx.notify();
+    }
+
+    /// Bring up whichever account `AuthState` now has active, from its cache
+    /// if it has one and its token is still good, otherwise from scratch.
+    pub(crate) fn resume_active_account(&mut self, cx: &mut Context<Self>) {
+        let cache = self
+            .auth
+            .current_user
+            .as_ref()
+            .and_then(|user| self.account_caches.remove(&user.id));
+        match cache {
+            Some(cache) if self.auth.expires_in() != Some(Duration::ZERO) => {
+                self.restore_account_cache(cache);
+                self.typing_users.clear();
+                self.backend.set_session(self.backend_session());
+                self.open_message_store();
+                self.restore_outbox(cx);
+                self.connect_gateway(cx);
+                self.schedule_token_refresh(cx);
+                cx.notify();
+            }
+            _ => self.restore_session(cx),
+        }
+    }
+
+    /// Move the active account's loaded data aside, keyed by its user id.
+    pub(crate) fn stash_account_cache(&mut self) {
+        let Some(user_id) = self.auth.current_user.as_ref().map(|u| u.id.clone()) else {
+            return;
+        };
+        let cache = AccountCache {
+            current_view: self.current_view.clone(),
+            servers: mem::take(&mut self.servers),
+            active_server: mem::take(&mut self.active_server),
+            active_channel_id: self.active_channel_id.take(),
+            messages: mem::take(&mut self.messages),
+            dm_channels: mem::take(&mut self.dm_channels),
+            active_dm_id: self.active_dm_id.take(),
+            dm_messages: mem::take(&mut self.dm_messages),
+            read_states: mem::take(&mut self.read_states),
+        };
+        self.account_caches.insert(user_id, cache);
+    }
+
+    fn restore_account_cache(&mut self, cache: AccountCache) {
+        self.current_view = cache.current_view;
+        self.servers = cache.servers;
+        self.active_server = cache.active_server;
+        self.active_channel_id = cache.active_channel_id;
+        self.messages = cache.messages;
+        self.dm_channels = cache.dm_channels;
+        self.active_dm_id = cache.active_dm_id;
+        self.dm_messages = cache.dm_messages;
+        self.read_states = cache.read_states;
+    }
+}
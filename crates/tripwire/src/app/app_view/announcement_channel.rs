@@ -117,7 +117,7 @@ impl TripwireApp {
                             .child({
                                 let mut content = v_flex();
                                 
-                                if let Some(attachment) = &message.attachment {
+                                for attachment in &message.attachments {
                                     content = content.child(
                                         div()
                                             .pl(px(60.0))
@@ -136,7 +136,7 @@ impl TripwireApp {
                                                             .items_center()
                                                             .bg(cx.theme().sidebar)
                                                             .child(
-                                                                Icon::new(IconName::Settings)
+                                                                Icon::new(IconName::File)
                                                                     .xsmall()
                                                                     .text_color(cx.theme().muted_foreground)
                                                             )
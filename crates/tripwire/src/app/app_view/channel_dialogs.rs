@@ -0,0 +1,433 @@
+//! Channel dialogs — creating a channel of any kind or a category, a
+//! channel's settings and permission overwrites, and confirming deletion.
+//! Shown over everything, so they open from the sidebar and from server
+//! settings alike.
+
+use gpui::{
+    AnyElement, App, Context, Div, InteractiveElement as _, IntoElement as _, ParentElement, SharedString,
+    StatefulInteractiveElement as _, Styled, Window, div, prelude::FluentBuilder as _, px,
+};
+use gpui_component::{
+    ActiveTheme as _, Disableable as _, Icon, IconName, Selectable as _, Sizable as _, StyledExt as _,
+    button::{Button, ButtonGroup, ButtonVariants as _},
+    h_flex,
+    input::Input,
+    menu::{DropdownMenu as _, PopupMenuItem},
+    scroll::ScrollableElement as _,
+    switch::Switch,
+    v_flex,
+};
+
+use crate::app::TripwireApp;
+use crate::app::channels::{ChannelDialogKind, ChannelSettings};
+use crate::channels::{self, SLOWMODE_CHOICES};
+use crate::models::{ChannelKind, Server};
+use crate::permissions::{GROUPS, OverwriteTarget, PermissionOverwrite, Permissions};
+
+impl TripwireApp {
+    pub(crate) fn render_channel_dialog(&self, _window: &mut Window, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let dialog = self.channel_dialog.as_ref()?;
+        let server = self.servers.iter().find(|s| s.id == dialog.server_id)?;
+
+        let (subject, body, ready, destructive) = match &dialog.kind {
+            ChannelDialogKind::CreateChannel { category, kind, name_input } => (
+                format!("in {category}"),
+                render_create_channel(kind, name_input, cx),
+                !channels::channel_name(&name_input.read(cx).value(), kind).is_empty(),
+                false,
+            ),
+            ChannelDialogKind::CreateCategory { name_input } => (
+                server.name.clone(),
+                v_flex()
+                    .gap_1()
+                    .child(field_label("Category Name", cx))
+                    .child(Input::new(name_input).small()),
+                !name_input.read(cx).value().trim().is_empty(),
+                false,
+            ),
+            ChannelDialogKind::Settings(settings) => {
+                let channel = server.all_channels().into_iter().find(|c| c.id == settings.channel_id)?;
+                (
+                    format!("{} {}", channel.kind.prefix(), channel.name),
+                    render_settings(settings, &channel.kind, server, cx),
+                    true,
+                    false,
+                )
+            }
+            ChannelDialogKind::DeleteChannel { channel_id } => {
+                let channel = server.all_channels().into_iter().find(|c| c.id == *channel_id)?;
+                (
+                    format!("{} {}", channel.kind.prefix(), channel.name),
+                    note(format!("Delete {} and all of its messages? This can't be undone.", channel.name), cx),
+                    true,
+                    true,
+                )
+            }
+            ChannelDialogKind::DeleteCategory { name } => {
+                let count = server.categories.iter().find(|c| c.name == *name).map_or(0, |c| c.channels.len());
+                let text = match count {
+                    0 => format!("Delete {name}? This can't be undone."),
+                    1 => format!("Delete {name} and the channel in it? This can't be undone."),
+                    count => format!("Delete {name} and the {count} channels in it? This can't be undone."),
+                };
+                (name.clone(), note(text, cx), true, true)
+            }
+        };
+        let wide = matches!(dialog.kind, ChannelDialogKind::Settings(_));
+        let confirm = match &dialog.kind {
+            ChannelDialogKind::Settings(_) => "Save",
+            kind => kind.title(),
+        };
+
+        Some(
+            div()
+                .absolute()
+                .inset_0()
+                .flex()
+                .items_center()
+                .justify_center()
+                .bg(gpui::rgba(0x00000099))
+                .on_mouse_down(
+                    gpui::MouseButton::Left,
+                    cx.listener(|this, _, _, cx| {
+                        cx.stop_propagation();
+                        this.close_channel_dialog(cx);
+                    }),
+                )
+                .child(
+                    v_flex()
+                        .occlude()
+                        .on_mouse_down(gpui::MouseButton::Left, |_, _, cx| cx.stop_propagation())
+                        .w(px(if wide { 640.0 } else { 440.0 }))
+                        .max_h(px(640.0))
+                        .gap_4()
+                        .p_5()
+                        .bg(cx.theme().popover)
+                        .rounded(cx.theme().radius_lg)
+                        .border_1()
+                        .border_color(cx.theme().border)
+                        .shadow_lg()
+                        .child(
+                            v_flex()
+                                .child(
+                                    div()
+                                        .text_lg()
+                                        .font_semibold()
+                                        .text_color(cx.theme().foreground)
+                                        .child(dialog.kind.title()),
+                                )
+                                .child(div().text_sm().text_color(cx.theme().muted_foreground).child(subject)),
+                        )
+                        .child(div().flex_1().min_h_0().overflow_y_scrollbar().child(body))
+                        .child(
+                            h_flex()
+                                .gap_2()
+                                .justify_end()
+                                .child(
+                                    Button::new("channel-dialog-cancel")
+                                        .label("Cancel")
+                                        .ghost()
+                                        .disabled(dialog.busy)
+                                        .on_click(cx.listener(|this, _, _, cx| this.close_channel_dialog(cx))),
+                                )
+                                .child(
+                                    Button::new("channel-dialog-confirm")
+                                        .label(confirm)
+                                        .map(|button| if destructive { button.danger() } else { button.primary() })
+                                        .loading(dialog.busy)
+                                        .disabled(!ready)
+                                        .on_click(cx.listener(|this, _, _, cx| this.confirm_channel_dialog(cx))),
+                                ),
+                        ),
+                )
+                .into_any_element(),
+        )
+    }
+}
+
+/// Every kind of channel to pick from, then the name.
+fn render_create_channel(
+    chosen: &ChannelKind,
+    name_input: &gpui::Entity<gpui_component::input::InputState>,
+    cx: &mut Context<TripwireApp>,
+) -> Div {
+    v_flex()
+        .gap_4()
+        .child(
+            v_flex()
+                .gap_1()
+                .child(field_label("Channel Type", cx))
+                .children(ChannelKind::ALL.into_iter().enumerate().map(|(ix, kind)| {
+                    let selected = kind == *chosen;
+                    h_flex()
+                        .id(("channel-kind", ix))
+                        .gap_3()
+                        .px_3()
+                        .py_2()
+                        .rounded(cx.theme().radius)
+                        .border_1()
+                        .border_color(if selected { cx.theme().primary } else { cx.theme().border })
+                        .when(selected, |this| this.bg(cx.theme().accent))
+                        .cursor_pointer()
+                        .hover(|s| s.bg(cx.theme().accent))
+                        .child(Icon::new(kind.icon()).small().text_color(cx.theme().muted_foreground))
+                        .child(
+                            v_flex()
+                                .child(div().text_sm().font_medium().text_color(cx.theme().foreground).child(kind.label()))
+                                .child(div().text_xs().text_color(cx.theme().muted_foreground).child(kind.description())),
+                        )
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            let kind = kind.clone();
+                            this.update_channel_dialog(cx, |dialog| {
+                                if let ChannelDialogKind::CreateChannel { kind: chosen, .. } = dialog {
+                                    *chosen = kind;
+                                }
+                            });
+                        }))
+                })),
+        )
+        .child(
+            v_flex()
+                .gap_1()
+                .child(field_label("Channel Name", cx))
+                .child(Input::new(name_input).small()),
+        )
+}
+
+fn render_settings(settings: &ChannelSettings, kind: &ChannelKind, server: &Server, cx: &mut Context<TripwireApp>) -> Div {
+    let slowmode = settings.slowmode_secs;
+    let slowmode_label = SLOWMODE_CHOICES
+        .iter()
+        .find(|(_, secs)| *secs == slowmode)
+        .map_or_else(|| format!("{slowmode} seconds"), |(label, _)| label.to_string());
+    let app = cx.entity();
+
+    v_flex()
+        .gap_4()
+        .child(
+            v_flex()
+                .gap_1()
+                .child(field_label("Channel Name", cx))
+                .child(Input::new(&settings.name_input).small()),
+        )
+        .child(
+            v_flex()
+                .gap_1()
+                .child(field_label("Channel Topic", cx))
+                .child(Input::new(&settings.topic_input).small()),
+        )
+        .when(!kind.is_voice_based(), |this| {
+            this.child(
+                h_flex()
+                    .justify_between()
+                    .items_center()
+                    .gap_4()
+                    .child(
+                        v_flex()
+                            .gap_1()
+                            .child(field_label("Slowmode", cx))
+                            .child(note("How long members wait between messages.".to_string(), cx)),
+                    )
+                    .child(
+                        Button::new("channel-slowmode")
+                            .label(slowmode_label)
+                            .outline()
+                            .small()
+                            .dropdown_menu(move |menu, _, _| {
+                                SLOWMODE_CHOICES.iter().fold(menu, |menu, &(label, secs)| {
+                                    let app = app.clone();
+                                    menu.item(PopupMenuItem::new(label).checked(secs == slowmode).on_click(
+                                        move |_: &gpui::ClickEvent, _: &mut Window, cx: &mut App| {
+                                            app.update(cx, |app, cx| {
+                                                app.update_channel_settings(cx, |settings| settings.slowmode_secs = secs);
+                                            });
+                                        },
+                                    ))
+                                })
+                            }),
+                    ),
+            )
+        })
+        .child(
+            h_flex()
+                .justify_between()
+                .items_center()
+                .gap_4()
+                .child(
+                    v_flex()
+                        .gap_1()
+                        .child(field_label("Age-Restricted", cx))
+                        .child(note("Members have to confirm they're of age to view it.".to_string(), cx)),
+                )
+                .child(
+                    Switch::new("channel-nsfw")
+                        .checked(settings.nsfw)
+                        .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                            let checked = *checked;
+                            this.update_channel_settings(cx, |settings| settings.nsfw = checked);
+                        })),
+                ),
+        )
+        .child(render_overwrites(settings, kind, server, cx))
+}
+
+/// Who has an overwrite, with a menu to add more, then what the chosen
+/// one allows and denies.
+fn render_overwrites(settings: &ChannelSettings, kind: &ChannelKind, server: &Server, cx: &mut Context<TripwireApp>) -> Div {
+    let mut targets = vec![OverwriteTarget::Everyone];
+    targets.extend(settings.overwrites.iter().map(|o| o.target.clone()).filter(|t| *t != OverwriteTarget::Everyone));
+    let addable: Vec<(OverwriteTarget, String)> = server
+        .roles
+        .iter()
+        .map(|role| OverwriteTarget::Role(role.id.clone()))
+        .chain(server.members.iter().map(|user| OverwriteTarget::Member(user.id.clone())))
+        .filter(|target| !targets.contains(target))
+        .map(|target| {
+            let name = target.name(server);
+            (target, name)
+        })
+        .collect();
+    let app = cx.entity();
+    let overwritable = channels::overwritable(kind);
+
+    v_flex()
+        .gap_2()
+        .child(
+            h_flex()
+                .justify_between()
+                .items_center()
+                .child(field_label("Permissions", cx))
+                .child(
+                    Button::new("channel-overwrite-add")
+                        .icon(IconName::Plus)
+                        .label("Add")
+                        .ghost()
+                        .xsmall()
+                        .disabled(addable.is_empty())
+                        .dropdown_menu(move |menu, _, _| {
+                            addable.iter().fold(menu, |menu, (target, name)| {
+                                let (app, target) = (app.clone(), target.clone());
+                                menu.item(PopupMenuItem::new(name.clone()).on_click(
+                                    move |_: &gpui::ClickEvent, _: &mut Window, cx: &mut App| {
+                                        let target = target.clone();
+                                        app.update(cx, |app, cx| {
+                                            app.update_channel_settings(cx, |settings| {
+                                                settings.overwrites.push(PermissionOverwrite {
+                                                    target: target.clone(),
+                                                    allow: Permissions::empty(),
+                                                    deny: Permissions::empty(),
+                                                });
+                                                settings.target = target;
+                                            });
+                                        });
+                                    },
+                                ))
+                            })
+                        }),
+                ),
+        )
+        .child(
+            h_flex()
+                .items_start()
+                .gap_3()
+                .child(
+                    v_flex()
+                        .w(px(160.0))
+                        .flex_shrink_0()
+                        .gap_1()
+                        .children(targets.into_iter().enumerate().map(|(ix, target)| {
+                            let selected = target == settings.target;
+                            Button::new(("channel-overwrite-target", ix))
+                                .label(target.name(server))
+                                .small()
+                                .w_full()
+                                .map(|button| if selected { button.primary() } else { button.ghost() })
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    let target = target.clone();
+                                    this.update_channel_settings(cx, |settings| settings.target = target);
+                                }))
+                        })),
+                )
+                .child(
+                    v_flex()
+                        .flex_1()
+                        .gap_3()
+                        .when(settings.target != OverwriteTarget::Everyone, |this| {
+                            this.child(
+                                Button::new("channel-overwrite-remove")
+                                    .icon(IconName::Delete)
+                                    .label(format!("Remove {}", settings.target.name(server)))
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(cx.listener(|this, _, _, cx| {
+                                        this.update_channel_settings(cx, |settings| {
+                                            let target = std::mem::replace(&mut settings.target, OverwriteTarget::Everyone);
+                                            settings.overwrites.retain(|o| o.target != target);
+                                        });
+                                    })),
+                            )
+                        })
+                        .children(GROUPS.iter().filter_map(|(group, infos)| {
+                            let infos: Vec<_> = infos.iter().filter(|info| overwritable.contains(info.flag)).collect();
+                            (!infos.is_empty()).then(|| {
+                                v_flex()
+                                    .gap_1()
+                                    .child(div().text_xs().font_semibold().text_color(cx.theme().muted_foreground).child(*group))
+                                    .children(infos.into_iter().map(|info| {
+                                        let state = channels::overwrite_state(&settings.overwrites, &settings.target, info.flag);
+                                        overwrite_row(info.flag, info.name, info.description, state, cx)
+                                    }))
+                            })
+                        })),
+                ),
+        )
+}
+
+/// One permission with deny, inherit and allow to pick from.
+fn overwrite_row(
+    flag: Permissions,
+    name: &'static str,
+    description: &'static str,
+    state: Option<bool>,
+    cx: &mut Context<TripwireApp>,
+) -> Div {
+    let choices = [(IconName::Close, Some(false)), (IconName::Minus, None), (IconName::Check, Some(true))];
+    h_flex()
+        .justify_between()
+        .items_center()
+        .gap_4()
+        .py_1()
+        .child(
+            v_flex()
+                .child(div().text_sm().text_color(cx.theme().foreground).child(name))
+                .child(div().text_xs().text_color(cx.theme().muted_foreground).child(description)),
+        )
+        .child(choices.into_iter().enumerate().fold(
+            ButtonGroup::new(SharedString::from(format!("overwrite-{}", flag.bits()))).outline().xsmall(),
+            |group, (ix, (icon, choice))| {
+                group.child(
+                    Button::new(("overwrite-choice", ix))
+                        .icon(icon)
+                        .selected(state == choice)
+                        .on_click(cx.listener(move |this, _, _, cx| {
+                            this.update_channel_settings(cx, |settings| {
+                                let target = settings.target.clone();
+                                channels::set_overwrite(&mut settings.overwrites, &target, flag, choice);
+                            });
+                        })),
+                )
+            },
+        ))
+}
+
+fn note(text: String, cx: &App) -> Div {
+    div().text_sm().text_color(cx.theme().muted_foreground).child(text)
+}
+
+fn field_label(label: &'static str, cx: &App) -> Div {
+    div()
+        .text_xs()
+        .font_semibold()
+        .text_color(cx.theme().muted_foreground)
+        .child(label.to_uppercase())
+}
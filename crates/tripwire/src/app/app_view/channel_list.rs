@@ -2,7 +2,7 @@
 //! the current user's status bar at the bottom.
 
 use gpui::{
-    AnyElement, Context, ElementId, IntoElement as _, SharedString, Window, div,
+    AnyElement, AppContext as _, Context, Corner, ElementId, IntoElement, Render, SharedString, Window, div,
     prelude::FluentBuilder as _, px,
 };
 use gpui::InteractiveElement;
@@ -15,14 +15,71 @@ use gpui_component::{
     avatar::Avatar,
     button::Button,
     h_flex, v_flex,
+    menu::{ContextMenuExt as _, DropdownMenu as _, PopupMenuItem},
     scroll::ScrollableElement as _,
 };
 
 use crate::app::TripwireApp;
-use crate::models::ChannelCategory;
+use crate::app::notifications::notification_menu;
+use crate::backend::MessageTarget;
+use crate::models::{Channel, ChannelCategory, UserStatus};
+use crate::notifications::Scope;
+use crate::permissions::Permissions;
 
 const PANEL_WIDTH: f32 = 240.;
 
+/// A channel being dragged to a new place in the sidebar.
+#[derive(Clone)]
+struct DraggedChannel {
+    id: String,
+    name: SharedString,
+    icon: IconName,
+}
+
+impl Render for DraggedChannel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        h_flex()
+            .gap_2()
+            .px_3()
+            .py_1()
+            .rounded(cx.theme().radius)
+            .bg(cx.theme().popover)
+            .border_1()
+            .border_color(cx.theme().border)
+            .shadow_md()
+            .opacity(0.9)
+            .text_sm()
+            .text_color(cx.theme().foreground)
+            .child(Icon::new(self.icon.clone()).xsmall().text_color(cx.theme().muted_foreground))
+            .child(self.name.clone())
+    }
+}
+
+/// A category being dragged to a new place in the sidebar.
+#[derive(Clone)]
+struct DraggedCategory {
+    ix: usize,
+    name: SharedString,
+}
+
+impl Render for DraggedCategory {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .px_3()
+            .py_1()
+            .rounded(cx.theme().radius)
+            .bg(cx.theme().popover)
+            .border_1()
+            .border_color(cx.theme().border)
+            .shadow_md()
+            .opacity(0.9)
+            .text_xs()
+            .font_weight(gpui::FontWeight::SEMIBOLD)
+            .text_color(cx.theme().muted_foreground)
+            .child(self.name.clone())
+    }
+}
+
 impl TripwireApp {
     pub(crate) fn render_channel_list(
         &mut self,
@@ -41,9 +98,20 @@ impl TripwireApp {
         };
 
         // Pre-compute category elements to avoid FnMut borrow checker issues.
+        // Channels the user can't see are left out, and so are categories
+        // left empty by that.
+        let can_manage = self.can_manage_channels();
         let mut category_elements: Vec<AnyElement> = Vec::new();
-        for cat in &server.categories {
-            category_elements.push(self.render_category(cat, cx).into_any_element());
+        for (cat_ix, cat) in server.categories.iter().enumerate() {
+            let visible: Vec<&Channel> = cat
+                .channels
+                .iter()
+                .filter(|c| self.channel_permissions(c).contains(Permissions::VIEW_CHANNEL))
+                .collect();
+            if visible.is_empty() && !cat.channels.is_empty() {
+                continue;
+            }
+            category_elements.push(self.render_category(cat_ix, cat, visible, can_manage, cx).into_any_element());
         }
 
         v_flex()
@@ -78,8 +146,43 @@ impl TripwireApp {
                             .icon(IconName::EllipsisVertical)
                             .ghost()
                             .xsmall()
-                            .on_click(|_, _, _| {
-                                // TODO: server settings dropdown
+                            .dropdown_menu({
+                                let app = cx.entity();
+                                move |menu, _, _| {
+                                    menu.item(
+                                        PopupMenuItem::new("Server Settings")
+                                            .icon(IconName::Settings)
+                                            .on_click({
+                                                let app = app.clone();
+                                                move |_, _, cx| {
+                                                    app.update(cx, |this, cx| this.open_server_settings(cx));
+                                                }
+                                            }),
+                                    )
+                                    .when(can_manage, |menu| {
+                                        menu.separator()
+                                            .item(
+                                                PopupMenuItem::new("Create Channel")
+                                                    .icon(IconName::Plus)
+                                                    .on_click({
+                                                        let app = app.clone();
+                                                        move |_, window, cx| {
+                                                            app.update(cx, |this, cx| this.open_create_channel(None, window, cx));
+                                                        }
+                                                    }),
+                                            )
+                                            .item(
+                                                PopupMenuItem::new("Create Category")
+                                                    .icon(IconName::FolderClosed)
+                                                    .on_click({
+                                                        let app = app.clone();
+                                                        move |_, window, cx| {
+                                                            app.update(cx, |this, cx| this.open_create_category(window, cx));
+                                                        }
+                                                    }),
+                                            )
+                                    })
+                                }
                             }),
                     ),
             )
@@ -96,14 +199,28 @@ impl TripwireApp {
             .into_any_element()
     }
 
+    /// A category and the channels in it the user can see. Those who
+    /// manage channels can drag channels and categories into a new order.
     fn render_category(
         &mut self,
+        cat_ix: usize,
         cat: &ChannelCategory,
+        channels: Vec<&Channel>,
+        can_manage: bool,
         cx: &mut Context<Self>,
     ) -> impl gpui::IntoElement {
         let active_id = self.active_channel_id.clone();
         let cat_name = cat.name.clone();
+        let dragged_category = DraggedCategory {
+            ix: cat_ix,
+            name: cat.name.to_uppercase().into(),
+        };
         let is_collapsed = cat.collapsed;
+        let cat_targets: Vec<MessageTarget> = channels
+            .iter()
+            .map(|c| MessageTarget::Channel(c.id.clone()))
+            .collect();
+        let cat_unread = cat_targets.iter().any(|t| self.read_states.unread(t.id()) > 0);
 
         v_flex()
             .w_full()
@@ -122,9 +239,68 @@ impl TripwireApp {
                     .cursor_pointer()
                     .hover(|s| s.bg(cx.theme().sidebar_accent))
                     .rounded(cx.theme().radius)
-                    .on_click(cx.listener(move |this, _, _window, cx| {
-                        this.toggle_category(&cat_name, cx);
+                    .on_click(cx.listener({
+                        let cat_name = cat_name.clone();
+                        move |this, _, _window, cx| {
+                            this.toggle_category(&cat_name, cx);
+                        }
                     }))
+                    .when(can_manage, |this| {
+                        this.on_drag(dragged_category, |drag, _, _, cx| {
+                            cx.stop_propagation();
+                            cx.new(|_| drag.clone())
+                        })
+                        .drag_over::<DraggedCategory>(|this, _, _, cx| this.border_t_2().border_color(cx.theme().drag_border))
+                        .drag_over::<DraggedChannel>(|this, _, _, cx| this.border_b_2().border_color(cx.theme().drag_border))
+                        .on_drop(cx.listener(move |this, drag: &DraggedCategory, _, cx| {
+                            this.move_category(drag.ix, cat_ix, cx);
+                        }))
+                        .on_drop(cx.listener(move |this, drag: &DraggedChannel, _, cx| {
+                            this.move_channel(&drag.id, cat_ix, 0, cx);
+                        }))
+                    })
+                    .context_menu({
+                        let app = cx.entity().clone();
+                        move |menu, _, _| {
+                            let targets = cat_targets.clone();
+                            menu.item(
+                                PopupMenuItem::new("Mark As Read")
+                                    .icon(IconName::Check)
+                                    .disabled(!cat_unread)
+                                    .on_click({
+                                        let app = app.clone();
+                                        move |_, _, cx| {
+                                            app.update(cx, |this, cx| this.mark_read(targets.clone(), cx));
+                                        }
+                                    }),
+                            )
+                            .when(can_manage, |menu| {
+                                let (create_in, delete) = (cat_name.clone(), cat_name.clone());
+                                menu.separator()
+                                    .item(
+                                        PopupMenuItem::new("Create Channel")
+                                            .icon(IconName::Plus)
+                                            .on_click({
+                                                let app = app.clone();
+                                                move |_, window, cx| {
+                                                    let category = Some(create_in.clone());
+                                                    app.update(cx, |this, cx| this.open_create_channel(category, window, cx));
+                                                }
+                                            }),
+                                    )
+                                    .item(
+                                        PopupMenuItem::new("Delete Category")
+                                            .icon(IconName::Delete)
+                                            .on_click({
+                                                let app = app.clone();
+                                                move |_, _, cx| {
+                                                    app.update(cx, |this, cx| this.open_delete_category(&delete, cx));
+                                                }
+                                            }),
+                                    )
+                            })
+                        }
+                    })
                     .child(
                         Icon::new(if is_collapsed {
                             IconName::ChevronRight
@@ -146,15 +322,24 @@ impl TripwireApp {
             .when(!is_collapsed, |this| {
                 let mut channel_elements: Vec<AnyElement> = Vec::new();
                 
-                for channel in &cat.channels {
+                for channel in channels {
+                    let ch_ix = cat.channels.iter().position(|c| c.id == channel.id).unwrap_or_default();
+                    let dragged_channel = DraggedChannel {
+                        id: channel.id.clone(),
+                        name: channel.name.clone().into(),
+                        icon: channel.kind.icon(),
+                    };
                     let ch_id = channel.id.clone();
                     let ch_id_for_later = ch_id.clone(); // For voice check
                     let ch_name = channel.name.clone();
                     let is_active = active_id.as_deref() == Some(ch_id.as_str());
-                    let has_unread = channel.unread > 0 && !is_active;
+                    let unread = self.read_states.unread(&ch_id) > 0;
+                    let has_unread = unread && !is_active;
+                    let mentions = self.read_states.mentions(&ch_id);
                     let kind = channel.kind.clone();
                     let members_connected = channel.members_connected;
-                    let unread = channel.unread;
+                    let locked = kind.is_voice_based()
+                        && !self.channel_permissions(channel).contains(Permissions::CONNECT);
 
                     // Channel row
                     channel_elements.push(
@@ -170,9 +355,68 @@ impl TripwireApp {
                             .cursor_pointer()
                             .when(is_active, |this| this.bg(cx.theme().sidebar_accent))
                             .hover(|s| s.bg(cx.theme().sidebar_accent))
-                            .on_click(cx.listener(move |this, _, window, cx| {
-                                this.switch_channel(ch_id.clone(), window, cx);
+                            .on_click(cx.listener({
+                                let ch_id = ch_id.clone();
+                                move |this, _, window, cx| {
+                                    this.switch_channel(ch_id.clone(), window, cx);
+                                }
                             }))
+                            .when(can_manage, |this| {
+                                this.on_drag(dragged_channel, |drag, _, _, cx| {
+                                    cx.stop_propagation();
+                                    cx.new(|_| drag.clone())
+                                })
+                                .drag_over::<DraggedChannel>(|this, _, _, cx| this.border_t_2().border_color(cx.theme().drag_border))
+                                .on_drop(cx.listener(move |this, drag: &DraggedChannel, _, cx| {
+                                    this.move_channel(&drag.id, cat_ix, ch_ix, cx);
+                                }))
+                            })
+                            .context_menu({
+                                let app = cx.entity().clone();
+                                move |menu, window, cx| {
+                                    let target = MessageTarget::Channel(ch_id.clone());
+                                    let scope = Scope::Channel(ch_id.clone());
+                                    menu.item(
+                                        PopupMenuItem::new("Mark As Read")
+                                            .icon(IconName::Check)
+                                            .disabled(!unread)
+                                            .on_click({
+                                                let app = app.clone();
+                                                move |_, _, cx| {
+                                                    app.update(cx, |this, cx| this.mark_read(vec![target.clone()], cx));
+                                                }
+                                            }),
+                                    )
+                                    .submenu("Notifications", window, cx, {
+                                        let app = app.clone();
+                                        move |menu, _, cx| notification_menu(menu, scope.clone(), &app, cx)
+                                    })
+                                    .when(can_manage, |menu| {
+                                        let (edit, delete) = (ch_id.clone(), ch_id.clone());
+                                        menu.separator()
+                                            .item(
+                                                PopupMenuItem::new("Edit Channel")
+                                                    .icon(IconName::Settings)
+                                                    .on_click({
+                                                        let app = app.clone();
+                                                        move |_, window, cx| {
+                                                            app.update(cx, |this, cx| this.open_channel_settings(&edit, window, cx));
+                                                        }
+                                                    }),
+                                            )
+                                            .item(
+                                                PopupMenuItem::new("Delete Channel")
+                                                    .icon(IconName::Delete)
+                                                    .on_click({
+                                                        let app = app.clone();
+                                                        move |_, _, cx| {
+                                                            app.update(cx, |this, cx| this.open_delete_channel(&delete, cx));
+                                                        }
+                                                    }),
+                                            )
+                                    })
+                                }
+                            })
                             .child(
                                 h_flex()
                                     .gap_2()
@@ -204,6 +448,13 @@ impl TripwireApp {
                                             })
                                             .child(ch_name),
                                     )
+                                    .when(locked, |this| {
+                                        this.child(
+                                            Icon::new(IconName::EyeOff)
+                                                .xsmall()
+                                                .text_color(cx.theme().muted_foreground),
+                                        )
+                                    })
                                     .when(members_connected > 0 && kind.is_voice_based(), |this| {
                                         this.child(
                                             div()
@@ -223,21 +474,21 @@ impl TripwireApp {
                                                 ),
                                         )
                                     })
-                                    .when(has_unread, |this| {
+                                    .when(mentions > 0 && !is_active, |this| {
                                         this.child(
                                             div()
                                                 .min_w(px(18.))
                                                 .h(px(18.))
                                                 .px(px(5.))
                                                 .rounded_full()
-                                                .bg(cx.theme().foreground)
+                                                .bg(cx.theme().danger)
                                                 .text_xs()
                                                 .font_weight(gpui::FontWeight::SEMIBOLD)
-                                                .text_color(cx.theme().background)
+                                                .text_color(gpui::white())
                                                 .flex()
                                                 .items_center()
                                                 .justify_center()
-                                                .child(unread.to_string()),
+                                                .child(mentions.to_string()),
                                         )
                                     }),
                             )
@@ -309,6 +560,21 @@ impl TripwireApp {
 
     pub(crate) fn render_user_bar(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
         let user = self.auth.current_user.clone();
+        let accounts: Vec<(String, String)> = self
+            .auth
+            .accounts
+            .iter()
+            .map(|a| (a.user.id.clone(), a.user.tag()))
+            .collect();
+        let active_id = user.as_ref().map(|u| u.id.clone());
+        let status = user.as_ref().map_or(UserStatus::Offline, |u| u.status.clone());
+        let status_color = match status {
+            UserStatus::Online => gpui::hsla(142. / 360., 0.71, 0.45, 1.),
+            UserStatus::Idle => gpui::hsla(43. / 360., 0.85, 0.56, 1.),
+            UserStatus::DoNotDisturb => gpui::hsla(0. / 360., 0.85, 0.60, 1.),
+            UserStatus::Offline => gpui::hsla(0., 0., 0.55, 1.),
+        };
+        let app = cx.entity();
 
         h_flex()
             .h(px(52.))
@@ -332,7 +598,7 @@ impl TripwireApp {
                             )
                             .xsmall(),
                     )
-                    // Green online dot
+                    // Status dot
                     .child(
                         div()
                             .absolute()
@@ -341,7 +607,7 @@ impl TripwireApp {
                             .w(px(10.))
                             .h(px(10.))
                             .rounded_full()
-                            .bg(gpui::hsla(142. / 360., 0.71, 0.45, 1.))
+                            .bg(status_color)
                             .border_2()
                             .border_color(cx.theme().sidebar),
                     ),
@@ -369,9 +635,73 @@ impl TripwireApp {
                         div()
                             .text_xs()
                             .text_color(cx.theme().muted_foreground)
-                            .child("Online"),
+                            .child(status.label()),
                     ),
             )
+            // Account switcher
+            .child(
+                Button::new("btn-switch-account")
+                    .icon(IconName::ChevronsUpDown)
+                    .ghost()
+                    .xsmall()
+                    .tooltip("Status and Accounts")
+                    .dropdown_menu_with_anchor(Corner::BottomLeft, move |menu, _, _| {
+                        let mut menu = menu.label("Status");
+                        for choice in [
+                            UserStatus::Online,
+                            UserStatus::Idle,
+                            UserStatus::DoNotDisturb,
+                            UserStatus::Offline,
+                        ] {
+                            menu = menu.item(
+                                PopupMenuItem::new(choice.label())
+                                    .checked(choice == status)
+                                    .on_click({
+                                        let app = app.clone();
+                                        let choice = choice.clone();
+                                        move |_, _, cx| {
+                                            app.update(cx, |this, cx| this.set_status(choice.clone(), cx));
+                                        }
+                                    }),
+                            );
+                        }
+                        menu = menu.separator().label("Switch Accounts");
+                        for (id, tag) in &accounts {
+                            menu = menu.item(
+                                PopupMenuItem::new(tag.clone())
+                                    .checked(active_id.as_ref() == Some(id))
+                                    .on_click({
+                                        let app = app.clone();
+                                        let id = id.clone();
+                                        move |_, _, cx| {
+                                            app.update(cx, |this, cx| this.switch_account(&id, cx));
+                                        }
+                                    }),
+                            );
+                        }
+                        menu.separator()
+                            .item(
+                                PopupMenuItem::new("Add an Account")
+                                    .icon(IconName::Plus)
+                                    .on_click({
+                                        let app = app.clone();
+                                        move |_, _, cx| {
+                                            app.update(cx, |this, cx| this.begin_add_account(cx));
+                                        }
+                                    }),
+                            )
+                            .item(
+                                PopupMenuItem::new("Log Out")
+                                    .icon(IconName::ArrowLeft)
+                                    .on_click({
+                                        let app = app.clone();
+                                        move |_, window, cx| {
+                                            app.update(cx, |this, cx| this.logout(window, cx));
+                                        }
+                                    }),
+                            )
+                    }),
+            )
             // Settings icon
             .child(
                 Button::new("btn-user-settings")
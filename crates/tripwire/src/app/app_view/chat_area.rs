@@ -1,26 +1,33 @@
 //! Main chat area — channel header, scrollable message list, and message input.
 
 use gpui::{
-    AnyElement, Context, ElementId, IntoElement as _, SharedString, Window, div,
-    prelude::FluentBuilder as _, px, StyledImage as _, IntoElement,
+    AnyElement, Context, ElementId, ExternalPaths, IntoElement as _, SharedString, Window, div, list,
+    prelude::FluentBuilder as _, px, StatefulInteractiveElement as _, StyledImage as _, IntoElement,
 };
 use gpui::InteractiveElement;
-use gpui::StatefulInteractiveElement;
 use gpui_component::button::ButtonVariants;
 use gpui::ParentElement;
 use gpui_component::StyledExt;
 use gpui::Styled;
 use gpui_component::{
-    ActiveTheme as _, IconName, Sizable as _,
+    ActiveTheme as _, Disableable as _, IconName, Sizable as _,
     avatar::Avatar,
     button::Button,
     h_flex, v_flex,
-    input::Input,
+    input::{Input, Paste},
+    menu::{ContextMenuExt as _, PopupMenuItem},
+    progress::Progress,
     scroll::ScrollableElement as _,
+    tooltip::Tooltip,
 };
 
+use crate::app::app_view::emoji_picker::EmojiPickTarget;
+use crate::app::uploads::{Composer, UploadState};
 use crate::app::{AppView, TripwireApp};
-use crate::models::Message;
+use crate::attachments::format_size;
+use crate::models::{Attachment, Message};
+use crate::outbox::DeliveryState;
+use crate::permissions::Permissions;
 
 impl TripwireApp {
     pub(crate) fn render_chat_area(
@@ -28,11 +35,16 @@ impl TripwireApp {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> AnyElement {
+        self.sync_message_list();
+        self.ack_open_conversation(window, cx);
+        self.refresh_mention_directory();
         match self.current_view {
             AppView::Servers => {
                 let channel_name = self.active_channel_name().unwrap_or("general").to_string();
                 let channel_topic = self.active_channel_topic().map(|t| t.to_string());
                 let messages: Vec<Message> = self.active_messages().to_vec();
+                self.highlight_code_blocks(&messages, cx);
+                self.unfurl_links(&messages, cx);
                 let channel_kind = self.active_channel_kind();
                 let members_connected = self.active_channel().map(|c| c.members_connected).unwrap_or(0);
 
@@ -138,8 +150,12 @@ impl TripwireApp {
                                 members_connected,
                                 cx,
                             ))
-                            .child(self.render_message_list(&messages, cx))
+                            .child(self.render_message_list(cx))
                             .child(self.render_message_composer(&channel_name, window, cx))
+                            .map(|this| {
+                                let composer = self.conversation_composer();
+                                self.drop_zone(this, "drop-chat", composer, format!("#{channel_name}"), cx)
+                            })
                             .into_any_element()
                     }
                 }
@@ -156,6 +172,8 @@ impl TripwireApp {
                     })
                     .unwrap_or_else(|| "Select a DM".to_string());
                 let messages: Vec<Message> = self.active_dm_messages().to_vec();
+                self.highlight_code_blocks(&messages, cx);
+                self.unfurl_links(&messages, cx);
 
                 v_flex()
                     .flex_1()
@@ -164,8 +182,12 @@ impl TripwireApp {
                     .overflow_hidden()
                     .bg(cx.theme().background)
                     .child(self.render_dm_header(&dm_name, cx))
-                    .child(self.render_message_list(&messages, cx))
+                    .child(self.render_message_list(cx))
                     .child(self.render_message_composer(&dm_name, window, cx))
+                    .map(|this| {
+                        let composer = self.conversation_composer();
+                        self.drop_zone(this, "drop-chat", composer, format!("@{dm_name}"), cx)
+                    })
                     .into_any_element()
             }
         }
@@ -270,14 +292,7 @@ impl TripwireApp {
                         }))
                 )
             })
-            .child(
-                Button::new("btn-search-msgs")
-                    .icon(IconName::Search)
-                    .ghost()
-                    .xsmall()
-                    .tooltip("Search")
-                    .on_click(|_, _, _| {}),
-            )
+            .child(self.render_search_box(cx))
             .child(
                 Button::new("btn-toggle-members")
                     .icon(IconName::PanelRight)
@@ -324,49 +339,113 @@ impl TripwireApp {
             // Spacer
             .child(div().flex_1())
             // Toolbar buttons
-            .child(
-                Button::new("btn-search-dms")
-                    .icon(IconName::Search)
-                    .ghost()
-                    .xsmall()
-                    .tooltip("Search")
-                    .on_click(|_, _, _| {}),
-            )
+            .child(self.render_search_box(cx))
     }
 
-    fn render_message_list(
-        &self,
-        messages: &[Message],
-        cx: &mut Context<Self>,
-    ) -> impl gpui::IntoElement {
-        // Pre-compute elements to avoid FnMut borrow-checker issues with cx.
-        let mut message_elements: Vec<gpui::AnyElement> = Vec::new();
-        for (ix, msg) in messages.iter().enumerate() {
-            message_elements.push(self.render_message(ix, msg, cx).into_any_element());
-        }
-
-        // TODO: Add auto-scroll to bottom behavior
-        // Need to track if user has manually scrolled up
-        // If not scrolled up, scroll to bottom when new messages arrive
-        // GPUI's scrollbar component would need scroll position tracking
-
+    fn render_message_list(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
         div()
             .flex_1()
             .min_h_0()
+            .relative()
             .overflow_hidden()
             .child(
-                div()
-                    .h_full()
-                    .overflow_y_scrollbar()
-                    .px_4()
-                    .py_4()
-                    .children(message_elements)
+                // Rows are indexed like the open conversation's messages
+                list(
+                    self.message_list.state.clone(),
+                    cx.processor(|this, ix, _window, cx| this.render_message_row(ix, cx)),
+                )
+                .size_full()
+                .py_4(),
             )
+            .vertical_scrollbar(&self.message_list.state)
+            .when(self.message_list.is_away_from_present(), |this| {
+                this.child(
+                    h_flex()
+                        .absolute()
+                        .bottom_4()
+                        .left_0()
+                        .right_0()
+                        .justify_center()
+                        .child(
+                            Button::new("btn-jump-to-present")
+                                .label("Jump to present")
+                                .icon(IconName::ArrowDown)
+                                .primary()
+                                .small()
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.message_list.scroll_to_present();
+                                    cx.notify();
+                                })),
+                        ),
+                )
+            })
+    }
+
+    fn render_message_row(&self, ix: usize, cx: &mut Context<Self>) -> AnyElement {
+        let Some(msg) = self.open_conversation_messages().get(ix).cloned() else {
+            return div().into_any_element();
+        };
+        let unread = self.message_list.unread_marker.as_deref() == Some(msg.id.as_str());
+        v_flex()
+            .px_4()
+            .when(ix == 0, |this| this.child(self.render_history_start(cx)))
+            .when(unread, |this| {
+                this.child(
+                    h_flex()
+                        .my_2()
+                        .gap_2()
+                        .items_center()
+                        .child(div().flex_1().h(px(1.)).bg(cx.theme().danger))
+                        .child(
+                            div()
+                                .text_xs()
+                                .font_semibold()
+                                .text_color(cx.theme().danger)
+                                .child("New"),
+                        ),
+                )
+            })
+            .child(self.render_message(&msg, cx))
+            .into_any_element()
+    }
+
+    /// Shown above the oldest loaded message: older ones are loading, or
+    /// there are none.
+    fn render_history_start(&self, cx: &mut Context<Self>) -> AnyElement {
+        let Some(target) = self.active_target() else {
+            return div().into_any_element();
+        };
+        let text = if self.message_list.is_loading(target.id()) {
+            "Loading older messages…"
+        } else if self.message_list.is_complete(target.id()) {
+            "This is the beginning of the conversation."
+        } else {
+            return div().into_any_element();
+        };
+        div()
+            .py_4()
+            .text_xs()
+            .text_center()
+            .text_color(cx.theme().muted_foreground)
+            .child(text)
+            .into_any_element()
+    }
+
+    fn render_search_box(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
+        div().w(px(220.)).child(
+            Input::new(&self.search_input)
+                .small()
+                .cleanable(true)
+                .prefix(
+                    gpui_component::Icon::new(IconName::Search)
+                        .small()
+                        .text_color(cx.theme().muted_foreground),
+                ),
+        )
     }
 
     fn render_message(
         &self,
-        index: usize,
         msg: &Message,
         cx: &mut Context<Self>,
     ) -> impl gpui::IntoElement {
@@ -377,12 +456,26 @@ impl TripwireApp {
         let content = msg.content.clone();
         let timestamp = msg.timestamp.clone();
         let is_edited = msg.edited;
-        let has_attachment = msg.attachment.is_some();
+        let edited_at = msg.edited_timestamp.clone();
+        let can_view_history = self.can_view_edit_history(msg);
+        let can_edit = self.can_edit_message(msg);
+        let can_delete = self.can_delete_message(msg);
+        let can_pin = self.can_pin_messages();
+        let can_react = self.can(Permissions::ADD_REACTIONS);
+        let can_open_thread = self.can_open_thread(msg);
+        let pinned = msg.pinned;
         let message_id = msg.id.clone();
         let reactions = msg.reactions.clone();
         let user_id = self.auth.current_user.as_ref().map(|u| u.id.clone()).unwrap_or_default();
         let reply_to = msg.reply_to.clone();
         let is_reply = msg.is_reply();
+        let delivery = self.delivery_state(&msg.id);
+        let highlighted = self.highlighted_message.as_deref() == Some(msg.id.as_str());
+        let mentions_me = self
+            .auth
+            .current_user
+            .as_ref()
+            .is_some_and(|user| msg.is_mentioned(&user.id));
 
         div()
             .relative()
@@ -390,6 +483,7 @@ impl TripwireApp {
             .child(
                 v_flex()
                     .gap_1()
+                    .when(delivery == Some(DeliveryState::Pending), |this| this.opacity(0.5))
                     // Reply preview (if this is a reply)
                     .when(is_reply, |this| {
                         if let Some(reply) = reply_to.as_ref() {
@@ -438,13 +532,102 @@ impl TripwireApp {
                     // Main message
                     .child(
                         h_flex()
-                            .id(ElementId::Name(SharedString::from(format!("msg-{index}"))))
+                            .id(ElementId::Name(SharedString::from(format!("msg-{}", msg.id))))
                             .gap_3()
                             .py_2()
                             .px_3()
                             .items_start()
                             .rounded(cx.theme().radius)
+                            .when(mentions_me, |this| {
+                                this.bg(cx.theme().warning.opacity(0.1))
+                                    .border_l_2()
+                                    .border_color(cx.theme().warning)
+                            })
+                            .when(highlighted, |this| this.bg(cx.theme().primary.opacity(0.15)))
                             .hover(|s| s.bg(cx.theme().accent))
+                            .context_menu({
+                                let app = cx.entity();
+                                let content = content.clone();
+                                let attachments = msg.attachments.clone();
+                                let message_id = message_id.clone();
+                                move |menu, _, _| {
+                                    let mut menu = menu.item(
+                                        PopupMenuItem::new("Copy Text")
+                                            .icon(IconName::Copy)
+                                            .disabled(content.is_empty())
+                                            .on_click({
+                                                let content = content.clone();
+                                                move |_, _, cx| {
+                                                    cx.write_to_clipboard(gpui::ClipboardItem::new_string(content.clone()));
+                                                }
+                                            }),
+                                    );
+                                    if can_view_history {
+                                        menu = menu.item(
+                                            PopupMenuItem::new("View Edit History")
+                                                .icon(IconName::Undo)
+                                                .on_click({
+                                                    let (app, message_id) = (app.clone(), message_id.clone());
+                                                    move |_, _, cx| {
+                                                        app.update(cx, |this, cx| this.open_edit_history(message_id.clone(), cx));
+                                                    }
+                                                }),
+                                        );
+                                    }
+                                    if can_edit {
+                                        menu = menu.item(
+                                            PopupMenuItem::new("Edit Message")
+                                                .icon(IconName::Replace)
+                                                .on_click({
+                                                    let (app, message_id) = (app.clone(), message_id.clone());
+                                                    move |_, window, cx| {
+                                                        app.update(cx, |this, cx| this.start_edit_message(message_id.clone(), window, cx));
+                                                    }
+                                                }),
+                                        );
+                                    }
+                                    if can_pin {
+                                        menu = menu.item(
+                                            PopupMenuItem::new(if pinned { "Unpin Message" } else { "Pin Message" })
+                                                .icon(if pinned { IconName::StarOff } else { IconName::Star })
+                                                .on_click({
+                                                    let (app, message_id) = (app.clone(), message_id.clone());
+                                                    move |_, _, cx| {
+                                                        app.update(cx, |this, cx| this.set_pinned(message_id.clone(), !pinned, cx));
+                                                    }
+                                                }),
+                                        );
+                                    }
+                                    if !attachments.is_empty() {
+                                        menu = menu.separator();
+                                    }
+                                    for attachment in &attachments {
+                                        menu = menu.item(
+                                            PopupMenuItem::new(format!("Save {}", attachment.filename))
+                                                .icon(IconName::ArrowDown)
+                                                .on_click({
+                                                    let (app, attachment) = (app.clone(), attachment.clone());
+                                                    move |_, window, cx| {
+                                                        app.update(cx, |this, cx| this.save_attachment(attachment.clone(), window, cx));
+                                                    }
+                                                }),
+                                        );
+                                    }
+                                    if can_delete {
+                                        menu = menu.separator().item(
+                                            PopupMenuItem::new("Delete Message")
+                                                .icon(IconName::Delete)
+                                                .on_click({
+                                                    let (app, message_id) = (app.clone(), message_id.clone());
+                                                    move |_, _, cx| {
+                                                        app.update(cx, |this, cx| this.delete_message(message_id.clone(), cx));
+                                                    }
+                                                }),
+                                        );
+                                    }
+                                    menu
+                                }
+                            })
                             // Avatar
                             .child(
                                 div()
@@ -489,32 +672,52 @@ impl TripwireApp {
                                             .child(timestamp),
                                     )
                                     .when(is_edited, |this| {
+                                        let history_id = message_id.clone();
                                         this.child(
                                             div()
+                                                .id(SharedString::from(format!("edited-{}", message_id)))
                                                 .text_xs()
                                                 .text_color(cx.theme().muted_foreground)
                                                 .italic()
+                                                .when_some(edited_at.clone(), |this, edited_at| {
+                                                    this.tooltip(move |window, cx| {
+                                                        Tooltip::new(format!("Edited {edited_at}")).build(window, cx)
+                                                    })
+                                                })
+                                                .when(can_view_history, |this| {
+                                                    this.cursor_pointer()
+                                                        .hover(|s| s.underline())
+                                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                                            this.open_edit_history(history_id.clone(), cx);
+                                                        }))
+                                                })
                                                 .child("(edited)"),
                                         )
+                                    })
+                                    .when(delivery == Some(DeliveryState::Pending), |this| {
+                                        this.child(
+                                            div()
+                                                .text_xs()
+                                                .text_color(cx.theme().muted_foreground)
+                                                .italic()
+                                                .child("Sending…"),
+                                        )
                                     }),
                             )
                             // Message body
                             .when(!content.is_empty(), |this| {
+                                this.child(self.render_message_body(&message_id, &content, cx))
+                            })
+                            // Attachments (if any)
+                            .when(!msg.attachments.is_empty(), |this| {
                                 this.child(
-                                    div()
-                                        .text_sm()
-                                        .text_color(cx.theme().foreground)
-                                        .child(content),
+                                    v_flex()
+                                        .gap_2()
+                                        .children(msg.attachments.iter().map(|a| self.render_attachment(&message_id, a, cx))),
                                 )
                             })
-                            // Attachment (if present)
-                            .when(has_attachment, |this| {
-                                if let Some(ref attachment) = msg.attachment {
-                                    this.child(self.render_attachment(attachment, cx))
-                                } else {
-                                    this
-                                }
-                            })
+                            // Embeds and link previews
+                            .children(self.render_embeds(msg, cx))
                             // Reactions (if any)
                             .when(!reactions.is_empty(), |this| {
                                 this.child(
@@ -542,19 +745,19 @@ impl TripwireApp {
                                                         this.bg(cx.theme().secondary)
                                                             .border_color(cx.theme().border)
                                                     })
-                                                    .hover(|s| s.bg(cx.theme().accent).cursor_pointer())
-                                                    .on_mouse_down(gpui::MouseButton::Left, cx.listener(move |this, _, _, cx| {
-                                                        this.toggle_reaction(msg_id.clone(), emoji_clone.clone(), cx);
-                                                    }))
+                                                    // Reactions can always be taken back, but only
+                                                    // added with permission.
+                                                    .when(user_reacted || can_react, |this| {
+                                                        this.hover(|s| s.bg(cx.theme().accent).cursor_pointer())
+                                                            .on_mouse_down(gpui::MouseButton::Left, cx.listener(move |this, _, _, cx| {
+                                                                this.toggle_reaction(msg_id.clone(), emoji_clone.clone(), cx);
+                                                            }))
+                                                    })
                                                     .child(
                                                         h_flex()
                                                             .gap_1()
                                                             .items_center()
-                                                            .child(
-                                                                div()
-                                                                    .text_sm()
-                                                                    .child(emoji.clone())
-                                                            )
+                                                            .child(self.render_reaction_emoji(emoji))
                                                             .child(
                                                                 div()
                                                                     .text_xs()
@@ -571,6 +774,45 @@ impl TripwireApp {
                                         )
                                 )
                             })
+                            // Delivery failed — offer to retry or give up
+                            .when(delivery == Some(DeliveryState::Failed), |this| {
+                                let retry_id = message_id.clone();
+                                let delete_id = message_id.clone();
+                                this.child(
+                                    h_flex()
+                                        .gap_2()
+                                        .items_center()
+                                        .child(
+                                            gpui_component::Icon::new(IconName::CircleX)
+                                                .xsmall()
+                                                .text_color(cx.theme().danger)
+                                        )
+                                        .child(
+                                            div()
+                                                .text_xs()
+                                                .text_color(cx.theme().danger)
+                                                .child("Failed to send.")
+                                        )
+                                        .child(
+                                            Button::new(format!("retry-send-{}", message_id))
+                                                .label("Retry")
+                                                .link()
+                                                .xsmall()
+                                                .on_click(cx.listener(move |this, _, _, cx| {
+                                                    this.retry_message(&retry_id, cx);
+                                                }))
+                                        )
+                                        .child(
+                                            Button::new(format!("discard-send-{}", message_id))
+                                                .label("Delete")
+                                                .link()
+                                                .xsmall()
+                                                .on_click(cx.listener(move |this, _, _, cx| {
+                                                    this.discard_message(&delete_id, cx);
+                                                }))
+                                        )
+                                )
+                            })
                             // Thread count badge (if message has replies)
                             .when(msg.thread_count > 0, |this| {
                                 let msg_id = message_id.clone();
@@ -614,8 +856,9 @@ impl TripwireApp {
                     )
             )
             // Hover toolbar (Discord-style) - positioned at top-right of message
-            // The toolbar is part of the hover group, so hovering over it keeps it visible
-            .child(
+            // The toolbar is part of the hover group, so hovering over it keeps it visible.
+            // Unsent messages have no server id to act on yet.
+            .when(delivery.is_none(), |this| this.child(
                 div()
                     .absolute()
                     .top(px(-8.0))
@@ -634,7 +877,7 @@ impl TripwireApp {
                             .border_color(cx.theme().border)
                             .shadow_md()
                             // Reply button - opens thread
-                            .child({
+                            .when(can_open_thread, |this| this.child({
                                 let message_id_clone = message_id.clone();
                                 Button::new(format!("reply-{}", message_id))
                                     .icon(IconName::ArrowLeft)
@@ -644,9 +887,9 @@ impl TripwireApp {
                                     .on_click(cx.listener(move |this, _, _, cx| {
                                         this.open_thread(message_id_clone.clone(), cx);
                                     }))
-                            })
+                            }))
                             // Emoji picker
-                            .child(
+                            .when(can_react, |this| this.child(
                                 gpui_component::popover::Popover::new(format!("emoji-picker-{}", message_id))
                                     .trigger(
                                         Button::new(format!("add-reaction-{}", message_id))
@@ -659,33 +902,247 @@ impl TripwireApp {
                                         let msg_id = message_id.clone();
                                         let app_entity = cx.entity();
                                         move |_state, _window, cx| {
-                                            Self::render_simple_emoji_picker(msg_id.clone(), app_entity.clone(), cx)
+                                            Self::render_emoji_picker(EmojiPickTarget::Reaction(msg_id.clone()), app_entity.clone(), cx)
                                         }
                                     })
-                            )
+                            ))
                     )
-            )
+            ))
     }
 
-    fn render_attachment(
+    /// Images show inline; other files as a card with a download button.
+    pub(crate) fn render_attachment(
         &self,
-        attachment: &crate::models::Attachment,
-        cx: &mut Context<Self>,
-    ) -> impl gpui::IntoElement {
-        let data_url = format!("data:{};base64,{}", attachment.mime_type, attachment.base64_data);
-        
-        div()
+        message_id: &str,
+        attachment: &Attachment,
+        cx: &Context<Self>,
+    ) -> AnyElement {
+        if attachment.is_image() {
+            let image = match attachment.local_path() {
+                Some(path) => gpui::img(path.to_path_buf()),
+                None => gpui::img(attachment.url.clone()),
+            };
+            let id = format!("attachment-{}-{}", message_id, attachment.id);
+            let open = attachment.clone();
+            return div()
+                .id(SharedString::from(id.clone()))
+                .mt_2()
+                .max_w(px(400.0))
+                .rounded(cx.theme().radius)
+                .border_1()
+                .border_color(cx.theme().border)
+                .overflow_hidden()
+                .cursor_pointer()
+                .on_click(cx.listener(move |this, _, window, cx| {
+                    this.open_lightbox(&open, window, cx);
+                }))
+                // The image's ID keeps GIFs animating
+                .child(image.w_full().object_fit(gpui::ObjectFit::Cover).id(SharedString::from(format!("{id}-image"))))
+                .into_any_element();
+        }
+
+        let icon = if attachment.is_video() { IconName::Play } else { IconName::File };
+        let download = attachment.clone();
+        h_flex()
             .mt_2()
             .max_w(px(400.0))
+            .gap_3()
+            .items_center()
+            .px_3()
+            .py_2()
             .rounded(cx.theme().radius)
+            .bg(cx.theme().muted)
             .border_1()
             .border_color(cx.theme().border)
-            .overflow_hidden()
-            .cursor_pointer()
+            .child(gpui_component::Icon::new(icon).text_color(cx.theme().muted_foreground))
             .child(
-                gpui::img(data_url)
-                    .w_full()
-                    .object_fit(gpui::ObjectFit::Cover)
+                v_flex()
+                    .flex_1()
+                    .min_w_0()
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(cx.theme().foreground)
+                            .overflow_hidden()
+                            .text_ellipsis()
+                            .child(attachment.filename.clone())
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(format_size(attachment.size))
+                    )
+            )
+            .child(
+                Button::new(format!("download-{}-{}", message_id, attachment.id))
+                    .icon(IconName::ArrowDown)
+                    .ghost()
+                    .xsmall()
+                    .tooltip("Download")
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.save_attachment(download.clone(), window, cx);
+                    }))
+            )
+            .into_any_element()
+    }
+
+    /// The files attached in `composer`: a preview, and progress while
+    /// uploading, with a button to cancel or remove each.
+    pub(crate) fn render_pending_uploads(&self, composer: Option<&Composer>, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let uploads: Vec<_> = self.pending_uploads_for(composer?).collect();
+        if uploads.is_empty() {
+            return None;
+        }
+        Some(
+            h_flex()
+                .gap_2()
+                .flex_wrap()
+                .children(uploads.into_iter().map(|upload| {
+                    let id = upload.id;
+                    let uploading = matches!(upload.state, UploadState::Uploading { .. });
+                    let preview = if upload.file.mime_type.starts_with("image/") {
+                        gpui::img(upload.file.path.clone())
+                            .size(px(40.0))
+                            .flex_shrink_0()
+                            .rounded(cx.theme().radius)
+                            .object_fit(gpui::ObjectFit::Cover)
+                            .into_any_element()
+                    } else {
+                        div()
+                            .size(px(40.0))
+                            .flex_shrink_0()
+                            .flex()
+                            .items_center()
+                            .justify_center()
+                            .rounded(cx.theme().radius)
+                            .bg(cx.theme().background)
+                            .child(
+                                gpui_component::Icon::new(IconName::File)
+                                    .small()
+                                    .text_color(cx.theme().muted_foreground)
+                            )
+                            .into_any_element()
+                    };
+                    v_flex()
+                        .w(px(220.0))
+                        .gap_1()
+                        .px_3()
+                        .py_2()
+                        .rounded(cx.theme().radius)
+                        .bg(cx.theme().muted)
+                        .border_1()
+                        .border_color(cx.theme().border)
+                        .child(
+                            h_flex()
+                                .gap_2()
+                                .items_center()
+                                .child(preview)
+                                .child(
+                                    v_flex()
+                                        .flex_1()
+                                        .min_w_0()
+                                        .child(
+                                            div()
+                                                .text_sm()
+                                                .text_color(cx.theme().foreground)
+                                                .overflow_hidden()
+                                                .text_ellipsis()
+                                                .child(upload.file.filename.clone())
+                                        )
+                                        .child(match &upload.state {
+                                            UploadState::Failed(err) => div()
+                                                .text_xs()
+                                                .text_color(cx.theme().danger)
+                                                .overflow_hidden()
+                                                .text_ellipsis()
+                                                .child(format!("Failed: {err}")),
+                                            _ => div()
+                                                .text_xs()
+                                                .text_color(cx.theme().muted_foreground)
+                                                .child(format_size(upload.file.size)),
+                                        })
+                                )
+                                .child(
+                                    Button::new(("btn-cancel-upload", id))
+                                        .icon(IconName::Close)
+                                        .ghost()
+                                        .xsmall()
+                                        .tooltip(if uploading { "Cancel upload" } else { "Remove" })
+                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                            this.cancel_upload(id, cx);
+                                        })),
+                                )
+                        )
+                        .when(uploading, |this| {
+                            this.child(Progress::new(("upload-progress", id)).value(upload.percent()))
+                        })
+                }))
+                .into_any_element(),
+        )
+    }
+
+    /// Let files be dropped onto `zone` to attach them to `composer`, with
+    /// an overlay saying where they'll go while they're dragged over it.
+    /// Nothing is accepted where the user can't attach files.
+    pub(crate) fn drop_zone(
+        &self,
+        zone: gpui::Div,
+        group: &'static str,
+        composer: Option<Composer>,
+        destination: String,
+        cx: &mut Context<Self>,
+    ) -> gpui::Div {
+        let Some(composer) = composer.filter(|_| self.can(Permissions::ATTACH_FILES)) else {
+            return zone;
+        };
+        let limits = self.upload_limits;
+        zone.relative()
+            .group(group)
+            .on_drop(cx.listener(move |this, paths: &ExternalPaths, window, cx| {
+                this.attach_dropped(paths, composer.clone(), window, cx);
+            }))
+            .child(
+                div()
+                    .absolute()
+                    .inset_0()
+                    .p_4()
+                    .invisible()
+                    .group_drag_over::<ExternalPaths>(group, |s| s.visible())
+                    .child(
+                        v_flex()
+                            .size_full()
+                            .gap_2()
+                            .items_center()
+                            .justify_center()
+                            .rounded(cx.theme().radius_lg)
+                            .border_2()
+                            .border_dashed()
+                            .border_color(cx.theme().primary)
+                            .bg(cx.theme().background.opacity(0.9))
+                            .child(
+                                gpui_component::Icon::new(IconName::ArrowUp)
+                                    .large()
+                                    .text_color(cx.theme().primary)
+                            )
+                            .child(
+                                div()
+                                    .text_lg()
+                                    .font_semibold()
+                                    .text_color(cx.theme().foreground)
+                                    .child(format!("Drop to upload to {destination}"))
+                            )
+                            .child(
+                                div()
+                                    .text_sm()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child(format!(
+                                        "Up to {} files, {} each",
+                                        limits.max_files,
+                                        format_size(limits.max_file_size)
+                                    ))
+                            )
+                    )
             )
     }
 
@@ -694,14 +1151,47 @@ impl TripwireApp {
         channel_name: &str,
         window: &mut Window,
         cx: &mut Context<Self>,
-    ) -> impl gpui::IntoElement {
+    ) -> AnyElement {
         let _ = channel_name;
-        let has_attachment = self.pending_attachment.is_some();
+        if !self.can(Permissions::SEND_MESSAGES) {
+            return div()
+                .flex_shrink_0()
+                .px_4()
+                .pt_2()
+                .pb_6()
+                .child(
+                    div()
+                        .px_3()
+                        .py_3()
+                        .rounded(cx.theme().radius_lg)
+                        .bg(cx.theme().muted)
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("You don't have permission to send messages in this channel."),
+                )
+                .into_any_element();
+        }
+        let can_attach = self.can(Permissions::ATTACH_FILES);
+        let composer = self.conversation_composer();
+        let pending_uploads = self.render_pending_uploads(composer.as_ref(), cx);
+        let has_uploads = pending_uploads.is_some();
         let has_reply = self.replying_to.is_some();
+        let typing = typing_label(&self.get_typing_users());
         
         v_flex()
             .flex_shrink_0()
             .gap_2()
+            // Pasted images are attached; anything else goes to the input
+            .capture_action(cx.listener({
+                let composer = composer.clone();
+                move |this, _: &Paste, window, cx| {
+                    if let Some(composer) = composer.clone()
+                        && this.paste_image(composer, window, cx)
+                    {
+                        cx.stop_propagation();
+                    }
+                }
+            }))
             // Reply preview (if replying)
             .when(has_reply, |this| {
                 if let Some(reply) = self.replying_to.as_ref() {
@@ -757,58 +1247,12 @@ impl TripwireApp {
                     this
                 }
             })
-            .child(
-                // Attachment preview
-                if let Some(ref attachment) = self.pending_attachment {
-                    div()
-                        .px_4()
-                        .child(
-                            h_flex()
-                                .gap_2()
-                                .items_center()
-                                .px_3()
-                                .py_2()
-                                .rounded(cx.theme().radius)
-                                .bg(cx.theme().muted)
-                                .border_1()
-                                .border_color(cx.theme().border)
-                                .child(
-                                    div()
-                                        .flex_1()
-                                        .child(
-                                            div()
-                                                .text_sm()
-                                                .text_color(cx.theme().foreground)
-                                                .child(format!("📎 {}", attachment.filename))
-                                        )
-                                        .child(
-                                            div()
-                                                .text_xs()
-                                                .text_color(cx.theme().muted_foreground)
-                                                .child(format!("{:.2} MB", attachment.size_mb()))
-                                        )
-                                )
-                                .child(
-                                    Button::new("btn-remove-attachment")
-                                        .icon(IconName::Close)
-                                        .ghost()
-                                        .xsmall()
-                                        .tooltip("Remove attachment")
-                                        .on_click(cx.listener(|this, _, _, cx| {
-                                            this.clear_attachment(cx);
-                                        })),
-                                )
-                        )
-                        .into_any_element()
-                } else {
-                    div().into_any_element()
-                }
-            )
+            // Attached files
+            .children(pending_uploads.map(|strip| div().px_4().child(strip)))
             .child(
                 h_flex()
                     .px_4()
-                    .pb_4()
-                    .when(!has_attachment, |this| this.pt_2())
+                    .when(!has_uploads, |this| this.pt_2())
                     .gap_2()
                     .items_center()
                     .child(
@@ -831,21 +1275,36 @@ impl TripwireApp {
                                             .icon(IconName::Plus)
                                             .ghost()
                                             .xsmall()
-                                            .tooltip("Attach File")
-                                            .on_click(cx.listener(|this, _, window, cx| {
-                                                this.attach_file(window, cx);
+                                            .disabled(!can_attach)
+                                            .tooltip("Attach Files")
+                                            .on_click(cx.listener({
+                                                let composer = composer.clone();
+                                                move |this, _, window, cx| {
+                                                    if let Some(composer) = composer.clone() {
+                                                        this.attach_files(composer, window, cx);
+                                                    }
+                                                }
                                             })),
                                     )
                                     // Text input
                                     .child(div().flex_1().child(Input::new(&self.message_input).appearance(false)))
                                     // Emoji button
                                     .child(
-                                        Button::new("btn-emoji")
-                                            .icon(IconName::Star)
-                                            .ghost()
-                                            .xsmall()
-                                            .tooltip("Emoji")
-                                            .on_click(|_, _, _| {}),
+                                        gpui_component::popover::Popover::new("composer-emoji-picker")
+                                            .anchor(gpui::Corner::BottomRight)
+                                            .trigger(
+                                                Button::new("btn-emoji")
+                                                    .icon(IconName::Star)
+                                                    .ghost()
+                                                    .xsmall()
+                                                    .tooltip("Emoji"),
+                                            )
+                                            .content({
+                                                let app_entity = cx.entity();
+                                                move |_state, _window, cx| {
+                                                    Self::render_emoji_picker(EmojiPickTarget::Composer, app_entity.clone(), cx)
+                                                }
+                                            }),
                                     ),
                             ),
                     )
@@ -861,241 +1320,27 @@ impl TripwireApp {
                             })),
                     )
             )
-    }
-
-    fn render_simple_emoji_picker(
-        message_id: String,
-        app_entity: gpui::Entity<TripwireApp>,
-        cx: &mut Context<gpui_component::popover::PopoverState>,
-    ) -> gpui::AnyElement {
-        use gpui_component::input::Input;
-        
-        // Emoji list with searchable names
-        let emoji_list: Vec<(&str, &str)> = vec![
-            // Smileys & Emotion
-            ("😀", "grinning smile happy joy"),
-            ("😃", "grin smile happy smiley"),
-            ("😄", "laugh smile happy joy grin"),
-            ("😁", "beaming smile grin happy"),
-            ("😅", "sweat grin smile relief"),
-            ("😂", "joy laugh tears crying happy lol"),
-            ("🤣", "rofl rolling floor laughing"),
-            ("😊", "blush smile happy pleased"),
-            ("😇", "innocent angel halo smile"),
-            ("🙂", "smile slight happy"),
-            ("🙃", "upside down smile sarcasm"),
-            ("😉", "wink flirt smile"),
-            ("😌", "relieved content peaceful"),
-            ("😍", "heart eyes love adore"),
-            ("🥰", "smiling hearts love adore"),
-            ("😘", "kiss love blow kiss"),
-            ("😗", "kissing pucker lips"),
-            ("😙", "kissing smile"),
-            ("😚", "kissing closed eyes"),
-            ("😋", "yum delicious tasty savoring"),
-            ("😛", "tongue out playful"),
-            ("😝", "squinting tongue wink"),
-            ("😜", "winking tongue playful"),
-            ("🤪", "zany crazy wacky goofy"),
-            ("🤨", "raised eyebrow skeptical suspicious"),
-            ("🧐", "monocle thinking curious"),
-            ("🤓", "nerd geek glasses"),
-            ("😎", "sunglasses cool awesome"),
-            ("🥳", "party celebrate partying"),
-            ("😏", "smirk smug confident"),
-            ("😒", "unamused annoyed bored"),
-            ("😞", "disappointed sad unhappy"),
-            ("😔", "pensive sad thoughtful"),
-            ("😟", "worried concerned anxious"),
-            ("😕", "confused puzzled uncertain"),
-            ("🙁", "frown sad unhappy"),
-            ("😣", "persevere struggle helpless"),
-            ("😖", "confounded frustrated upset"),
-            ("😫", "tired exhausted weary"),
-            ("😩", "weary tired exhausted fed up"),
-            ("🥺", "pleading puppy eyes"),
-            ("😢", "cry tears sad"),
-            ("😭", "sob crying tears bawl"),
-            ("😤", "triumph victory smug proud"),
-            ("😠", "angry mad annoyed"),
-            ("😡", "rage furious mad angry"),
-            ("🤬", "cursing swearing symbols"),
-            ("🤯", "exploding head mind blown shocked"),
-            ("😳", "flushed surprised embarrassed"),
-            ("🥵", "hot heat sweat"),
-            ("🥶", "cold freezing frozen"),
-            ("😱", "scream fear shocked afraid"),
-            ("😨", "fearful scared afraid"),
-            ("😰", "anxious nervous worried sweat"),
-            ("😥", "sad sweat disappointed"),
-            ("😓", "downcast sweat sad"),
-            // Hearts
-            ("❤️", "red heart love"),
-            ("🧡", "orange heart love"),
-            ("💛", "yellow heart love"),
-            ("💚", "green heart love"),
-            ("💙", "blue heart love"),
-            ("💜", "purple heart love"),
-            ("🖤", "black heart love"),
-            ("🤍", "white heart love"),
-            ("💔", "broken heart heartbreak sad"),
-            ("❣️", "heart exclamation love emphasis"),
-            ("💕", "two hearts love"),
-            ("💞", "revolving hearts love"),
-            ("💓", "beating heart love pulse"),
-            ("💗", "growing heart love"),
-            ("💖", "sparkling heart love shine"),
-            ("💘", "heart arrow cupid love"),
-            // Hands
-            ("👍", "thumbs up like yes agree good"),
-            ("👎", "thumbs down dislike no disagree bad"),
-            ("👌", "ok okay perfect good"),
-            ("✌️", "victory peace two fingers"),
-            ("🤞", "crossed fingers hope luck wish"),
-            ("🤟", "love you gesture"),
-            ("🤘", "rock on horns metal"),
-            ("🤙", "call me hang loose shaka"),
-            ("👈", "left point backhand"),
-            ("👉", "right point backhand"),
-            ("👆", "up point backhand"),
-            ("👇", "down point backhand"),
-            ("☝️", "index pointing up one"),
-            ("✋", "raised hand stop"),
-            ("🤚", "raised back hand"),
-            ("🖐️", "hand fingers splayed five"),
-            ("👋", "wave hello goodbye hi bye"),
-            ("🤝", "handshake deal agreement shake"),
-            ("🙏", "pray please thanks folded hands"),
-            ("✍️", "writing hand write"),
-            ("💪", "muscle strong flex bicep"),
-            ("👏", "clap applause clapping"),
-            ("🙌", "raised hands celebration hooray yay"),
-            ("👐", "open hands"),
-            // Celebration
-            ("🎉", "party popper celebrate confetti"),
-            ("🎊", "confetti ball celebrate party"),
-            ("🎈", "balloon party celebrate"),
-            ("🎁", "gift present box wrapped"),
-            ("🏆", "trophy win winner champion award"),
-            ("🥇", "first place gold medal winner"),
-            ("🥈", "second place silver medal"),
-            ("🥉", "third place bronze medal"),
-            // Symbols
-            ("⚡", "lightning bolt zap electric power fast"),
-            ("✨", "sparkles stars shine magic"),
-            ("💫", "dizzy stars shine"),
-            ("⭐", "star favorite"),
-            ("🌟", "glowing star shine bright"),
-            ("💥", "collision boom bang explosion"),
-            ("🔥", "fire flame hot lit"),
-            ("🌈", "rainbow pride colorful"),
-            ("✅", "check mark done complete yes correct green"),
-            ("❌", "cross mark x no wrong red"),
-            ("⚠️", "warning caution alert"),
-            ("❗", "exclamation mark red important"),
-            ("❓", "question mark ask help"),
-            ("💯", "hundred points full perfect 100"),
-            ("🆒", "cool button word"),
-            ("🆕", "new button word"),
-        ];
-
-        let search_query = app_entity.read(cx).emoji_search_input.read(cx).value().to_lowercase();
-        
-        // Filter emojis based on search (flat list, not rows)
-        let filtered_emojis: Vec<&str> = if search_query.is_empty() {
-            emoji_list.iter().map(|(emoji, _)| *emoji).collect()
-        } else {
-            emoji_list
-                .iter()
-                .filter(|(_, keywords)| keywords.to_lowercase().contains(&search_query))
-                .map(|(emoji, _)| *emoji)
-                .collect()
-        };
-
-        v_flex()
-            .w(gpui::px(320.0))
-            .h(gpui::px(360.0))
-            .child(
-                // Search input header (fixed)
-                div()
-                    .flex_shrink_0()
-                    .p_2()
-                    .border_b_1()
-                    .border_color(cx.theme().border)
-                    .child(
-                        Input::new(&app_entity.read(cx).emoji_search_input)
-                            .appearance(false)
-                    )
-            )
+            // Typing indicator (fixed height so the composer doesn't jump)
             .child(
-                // Emoji grid (scrollable)
                 div()
-                    .flex_1()
-                    .min_h_0()
-                    .overflow_hidden()
-                    .child(
-                        div()
-                            .h_full()
-                            .overflow_y_scrollbar()
-                            .p_2()
-                            .child(
-                                div()
-                                    .when(filtered_emojis.is_empty(), |this| {
-                                        this.child(
-                                            div()
-                                                .p_4()
-                                                .text_center()
-                                                .text_sm()
-                                                .text_color(cx.theme().muted_foreground)
-                                                .child("No emojis found")
-                                        )
-                                    })
-                                    .when(!filtered_emojis.is_empty(), |this| {
-                                        this.grid()
-                                            .grid_cols(8)
-                                            .gap_1()
-                                            .children(
-                                                filtered_emojis.iter().map(|emoji| {
-                                                    let emoji_str = emoji.to_string();
-                                                    let msg_id = message_id.clone();
-                                                    let app = app_entity.clone();
-                                                    let emoji_for_closure = emoji_str.clone();
-                                                    
-                                                    div()
-                                                        .flex()
-                                                        .items_center()
-                                                        .justify_center()
-                                                        .w(gpui::px(32.0))
-                                                        .h(gpui::px(32.0))
-                                                        .rounded(cx.theme().radius)
-                                                        .hover(|s| s.bg(cx.theme().accent).cursor_pointer())
-                                                        .on_mouse_down(
-                                                            gpui::MouseButton::Left,
-                                                            cx.listener(move |_state, _, _, cx| {
-                                                                let app = app.clone();
-                                                                let msg_id = msg_id.clone();
-                                                                let emoji_str = emoji_for_closure.clone();
-                                                                
-                                                                cx.defer(move |cx| {
-                                                                    _ = app.update(cx, |app, cx| {
-                                                                        app.toggle_reaction(msg_id, emoji_str, cx);
-                                                                    });
-                                                                });
-                                                                
-                                                                cx.emit(gpui::DismissEvent);
-                                                            }),
-                                                        )
-                                                        .child(
-                                                            div()
-                                                                .text_lg()
-                                                                .child(emoji_str)
-                                                        )
-                                                })
-                                            )
-                                    })
-                            )
-                    )
+                    .h(px(20.))
+                    .px_4()
+                    .mb_1()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .children(typing),
             )
             .into_any_element()
     }
 }
+
+/// "Alice is typing…" style label for the users currently typing.
+fn typing_label(names: &[String]) -> Option<String> {
+    match names {
+        [] => None,
+        [one] => Some(format!("{one} is typing…")),
+        [a, b] => Some(format!("{a} and {b} are typing…")),
+        [a, b, c] => Some(format!("{a}, {b} and {c} are typing…")),
+        _ => Some("Several people are typing…".to_string()),
+    }
+}
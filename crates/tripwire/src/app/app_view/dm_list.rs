@@ -14,11 +14,15 @@ use gpui_component::{
     avatar::Avatar,
     button::Button,
     h_flex, v_flex,
+    menu::{ContextMenuExt as _, PopupMenuItem},
     scroll::ScrollableElement as _,
     tooltip::Tooltip,
 };
 
 use crate::app::TripwireApp;
+use crate::app::notifications::notification_menu;
+use crate::backend::MessageTarget;
+use crate::notifications::Scope;
 
 const PANEL_WIDTH: f32 = 240.;
 
@@ -36,7 +40,9 @@ impl TripwireApp {
             let dm_id = dm.id.clone();
             let recipient = dm.recipient.clone();
             let is_active = active_dm.as_deref() == Some(dm_id.as_str());
-            let has_unread = dm.unread > 0;
+            // Every message in a DM is meant for the user, so all of them count.
+            let unread = self.read_states.unread(&dm_id);
+            let has_unread = unread > 0 && !is_active;
             let last_message = dm.last_message.clone();
             let status = recipient.status.clone();
             
@@ -57,9 +63,32 @@ impl TripwireApp {
                     .cursor_pointer()
                     .when(is_active, |this| this.bg(cx.theme().sidebar_accent))
                     .hover(|s| s.bg(cx.theme().sidebar_accent))
-                    .on_click(cx.listener(move |this, _, window, cx| {
-                        this.switch_dm(dm_id.clone(), window, cx);
+                    .on_click(cx.listener({
+                        let dm_id = dm_id.clone();
+                        move |this, _, window, cx| this.switch_dm(dm_id.clone(), window, cx)
                     }))
+                    .context_menu({
+                        let app = cx.entity().clone();
+                        move |menu, window, cx| {
+                            let target = MessageTarget::DirectMessage(dm_id.clone());
+                            let scope = Scope::Channel(dm_id.clone());
+                            menu.item(
+                                PopupMenuItem::new("Mark As Read")
+                                    .icon(IconName::Check)
+                                    .disabled(unread == 0)
+                                    .on_click({
+                                        let app = app.clone();
+                                        move |_, _, cx| {
+                                            app.update(cx, |this, cx| this.mark_read(vec![target.clone()], cx));
+                                        }
+                                    }),
+                            )
+                            .submenu("Notifications", window, cx, {
+                                let app = app.clone();
+                                move |menu, _, cx| notification_menu(menu, scope.clone(), &app, cx)
+                            })
+                        }
+                    })
                     .child(
                         h_flex()
                             .gap_3()
@@ -134,7 +163,7 @@ impl TripwireApp {
                                         .text_color(gpui::white())
                                         .text_xs()
                                         .font_weight(gpui::FontWeight::BOLD)
-                                        .child(dm.unread.to_string()),
+                                        .child(unread.to_string()),
                                 )
                             }),
                     )
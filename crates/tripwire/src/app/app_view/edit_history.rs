@@ -0,0 +1,209 @@
+//! Edit history dialog — every version of an edited message, newest first,
+//! each marked up with what changed from the one before it.
+
+use gpui::{
+    AnyElement, Context, HighlightStyle, InteractiveElement as _, IntoElement, ParentElement, SharedString,
+    StrikethroughStyle, Styled, StyledText, Window, div, prelude::FluentBuilder as _, px,
+};
+use gpui_component::{
+    ActiveTheme as _, IconName, Sizable as _, StyledExt as _,
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    scroll::ScrollableElement as _,
+    v_flex,
+};
+
+use crate::app::{AppView, TripwireApp};
+use crate::diff::{self, Change};
+use crate::models::Message;
+use crate::permissions::Permissions;
+
+impl TripwireApp {
+    /// Whether the user may see how `message` was edited: theirs, or any in
+    /// a channel where they manage messages.
+    pub(crate) fn can_view_edit_history(&self, message: &Message) -> bool {
+        let Some(user) = self.auth.current_user.as_ref() else {
+            return false;
+        };
+        let moderates = self.current_view == AppView::Servers && self.can(Permissions::MANAGE_MESSAGES);
+        message.edited && (message.author.id == user.id || moderates)
+    }
+
+    pub(crate) fn open_edit_history(&mut self, message_id: String, cx: &mut Context<Self>) {
+        self.edit_history_message_id = Some(message_id);
+        cx.notify();
+    }
+
+    fn close_edit_history(&mut self, cx: &mut Context<Self>) {
+        self.edit_history_message_id = None;
+        cx.notify();
+    }
+
+    pub(crate) fn render_edit_history_modal(&self, _window: &mut Window, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let message_id = self.edit_history_message_id.as_ref()?;
+        let message = self
+            .active_target()
+            .and_then(|target| self.cached_messages(&target))
+            .and_then(|messages| messages.iter().find(|m| m.id == *message_id))
+            .filter(|message| self.can_view_edit_history(message))?;
+
+        // Oldest first, ending with the current text.
+        let versions: Vec<(&str, &str)> = message
+            .revisions
+            .iter()
+            .map(|r| (r.content.as_str(), r.timestamp.as_str()))
+            .chain([(message.content.as_str(), message.edited_timestamp.as_deref().unwrap_or_default())])
+            .collect();
+        let last = versions.len() - 1;
+
+        Some(
+            div()
+                .absolute()
+                .inset_0()
+                .flex()
+                .items_center()
+                .justify_center()
+                .bg(gpui::rgba(0x00000099))
+                .on_mouse_down(gpui::MouseButton::Left, cx.listener(|this, _, _, cx| {
+                    this.close_edit_history(cx);
+                }))
+                .child(
+                    v_flex()
+                        .occlude()
+                        .on_mouse_down(gpui::MouseButton::Left, |_, _, cx| cx.stop_propagation())
+                        .w(px(560.0))
+                        .max_h(px(640.0))
+                        .rounded(cx.theme().radius_lg)
+                        .bg(cx.theme().popover)
+                        .border_1()
+                        .border_color(cx.theme().border)
+                        .shadow_lg()
+                        .child(
+                            h_flex()
+                                .justify_between()
+                                .items_center()
+                                .px_6()
+                                .pt_5()
+                                .pb_3()
+                                .child(
+                                    v_flex()
+                                        .gap_1()
+                                        .child(
+                                            div()
+                                                .text_lg()
+                                                .font_weight(gpui::FontWeight::BOLD)
+                                                .text_color(cx.theme().foreground)
+                                                .child("Edit History"),
+                                        )
+                                        .child(
+                                            div()
+                                                .text_xs()
+                                                .text_color(cx.theme().muted_foreground)
+                                                .child(format!(
+                                                    "{} · posted {}",
+                                                    message.author.username, message.timestamp
+                                                )),
+                                        ),
+                                )
+                                .child(
+                                    Button::new("edit-history-close")
+                                        .icon(IconName::Close)
+                                        .ghost()
+                                        .small()
+                                        .tooltip("Close")
+                                        .on_click(cx.listener(|this, _, _, cx| this.close_edit_history(cx))),
+                                ),
+                        )
+                        .child(
+                            v_flex()
+                                .flex_1()
+                                .min_h_0()
+                                .overflow_y_scrollbar()
+                                .px_6()
+                                .pb_6()
+                                .gap_3()
+                                // Edited before revisions were kept.
+                                .when(message.revisions.is_empty(), |this| {
+                                    this.child(
+                                        div()
+                                            .text_sm()
+                                            .text_color(cx.theme().muted_foreground)
+                                            .child("Earlier versions of this message weren't kept."),
+                                    )
+                                })
+                                .children((0..versions.len()).rev().map(|ix| {
+                                    let (content, timestamp) = versions[ix];
+                                    let label = match ix {
+                                        ix if ix == last => "Current".to_string(),
+                                        0 => "Original".to_string(),
+                                        ix => format!("Edit {ix}"),
+                                    };
+                                    let previous = ix.checked_sub(1).map(|ix| versions[ix].0);
+                                    v_flex()
+                                        .gap_1()
+                                        .p_3()
+                                        .rounded(cx.theme().radius)
+                                        .bg(cx.theme().muted)
+                                        .child(
+                                            h_flex()
+                                                .gap_2()
+                                                .text_xs()
+                                                .child(
+                                                    div()
+                                                        .font_semibold()
+                                                        .text_color(cx.theme().foreground)
+                                                        .child(label),
+                                                )
+                                                .when(!timestamp.is_empty(), |this| {
+                                                    this.child(
+                                                        div()
+                                                            .text_color(cx.theme().muted_foreground)
+                                                            .child(timestamp.to_string()),
+                                                    )
+                                                }),
+                                        )
+                                        .child(
+                                            div()
+                                                .text_sm()
+                                                .text_color(cx.theme().foreground)
+                                                .child(render_changes(previous, content, cx)),
+                                        )
+                                })),
+                        ),
+                )
+                .into_any_element(),
+        )
+    }
+}
+
+/// `content`, with what was added since `previous` highlighted and what
+/// was removed struck through.
+fn render_changes(previous: Option<&str>, content: &str, cx: &Context<TripwireApp>) -> StyledText {
+    let Some(previous) = previous else {
+        return StyledText::new(SharedString::from(content.to_string()));
+    };
+    let mut text = String::new();
+    let mut highlights = Vec::new();
+    for (change, run) in diff::word_diff(previous, content) {
+        let start = text.len();
+        text.push_str(run);
+        let style = match change {
+            Change::Same => continue,
+            Change::Added => HighlightStyle {
+                background_color: Some(cx.theme().success.opacity(0.25)),
+                ..Default::default()
+            },
+            Change::Removed => HighlightStyle {
+                color: Some(cx.theme().muted_foreground),
+                background_color: Some(cx.theme().danger.opacity(0.2)),
+                strikethrough: Some(StrikethroughStyle {
+                    thickness: px(1.),
+                    color: None,
+                }),
+                ..Default::default()
+            },
+        };
+        highlights.push((start..text.len(), style));
+    }
+    StyledText::new(text).with_highlights(highlights)
+}
@@ -0,0 +1,209 @@
+//! Embeds — rich cards under a message, sent by the server or previewing
+//! the links in it. Previews and the images of all embeds are fetched by
+//! `unfurl` while the message is on screen; with link previews turned off
+//! in the privacy settings neither are, so embeds show without images.
+
+use std::sync::Arc;
+
+use gpui::{
+    AnyElement, AppContext as _, Context, ElementId, Image, InteractiveElement as _, IntoElement, ParentElement,
+    SharedString, StatefulInteractiveElement as _, Styled, StyledImage as _, div, img, prelude::FluentBuilder as _, px, rgb,
+};
+use gpui_component::{ActiveTheme as _, StyledExt as _, h_flex, v_flex};
+
+use crate::app::TripwireApp;
+use crate::markdown;
+use crate::models::{Embed, Message};
+
+const MAX_EMBED_WIDTH: f32 = 520.;
+const THUMBNAIL_SIZE: f32 = 80.;
+/// Width of an inline field, so a few share a row.
+const INLINE_FIELD_WIDTH: f32 = 150.;
+
+impl TripwireApp {
+    /// Start fetching previews of the links in `messages`, and the images
+    /// of their embeds, that aren't cached yet. Call before rendering them.
+    pub(crate) fn unfurl_links(&mut self, messages: &[Message], cx: &mut Context<Self>) {
+        if !self.privacy_settings.link_previews {
+            return;
+        }
+        let timeout = self.unfurler.limits().timeout;
+        for message in messages {
+            let links = if message.content.contains("://") {
+                self.preview_links(message)
+            } else {
+                Vec::new()
+            };
+            for url in &links {
+                let fetch = self.unfurler.fetch(url, cx.background_executor().timer(timeout));
+                spawn_fetch(fetch, cx);
+            }
+            let previews = links.iter().filter_map(|link| self.unfurler.cached(link));
+            for embed in message.embeds.iter().cloned().chain(previews) {
+                if let Some(src) = &embed.thumbnail {
+                    let fetch = self.unfurler.fetch_image(src, cx.background_executor().timer(timeout));
+                    spawn_fetch(fetch, cx);
+                }
+            }
+        }
+    }
+
+    /// Links of `message` to preview: the first few without an embed from
+    /// the server.
+    fn preview_links(&self, message: &Message) -> Vec<String> {
+        markdown::links(&message.content)
+            .into_iter()
+            .filter(|link| !message.embeds.iter().any(|e| e.url.as_ref() == Some(link)))
+            .take(self.unfurler.limits().per_message)
+            .collect()
+    }
+
+    pub(crate) fn render_embeds(&self, message: &Message, cx: &Context<Self>) -> Option<AnyElement> {
+        let mut embeds = message.embeds.clone();
+        if self.privacy_settings.link_previews {
+            embeds.extend(
+                self.preview_links(message)
+                    .iter()
+                    .filter_map(|link| self.unfurler.cached(link)),
+            );
+        } else {
+            // An image is all some embeds have.
+            embeds.retain(|e| e.title.is_some() || e.description.is_some() || !e.fields.is_empty());
+        }
+        if embeds.is_empty() {
+            return None;
+        }
+        Some(
+            v_flex()
+                .mt_1()
+                .gap_2()
+                .children(
+                    embeds
+                        .iter()
+                        .enumerate()
+                        .map(|(ix, embed)| {
+                            let image = embed
+                                .thumbnail
+                                .as_ref()
+                                .filter(|_| self.privacy_settings.link_previews)
+                                .and_then(|src| self.unfurler.cached_image(src));
+                            render_embed(&message.id, ix, embed, image, cx)
+                        }),
+                )
+                .into_any_element(),
+        )
+    }
+}
+
+/// Run a fetch from the unfurler in the background, then re-render.
+fn spawn_fetch(fetch: Option<futures::future::BoxFuture<'static, ()>>, cx: &mut Context<TripwireApp>) {
+    let Some(fetch) = fetch else {
+        return;
+    };
+    let fetch = cx.background_spawn(fetch);
+    cx.spawn(async move |this, cx| {
+        fetch.await;
+        this.update(cx, |_, cx| cx.notify()).ok();
+    })
+    .detach();
+}
+
+/// `image` is the embed's thumbnail, once fetched.
+fn render_embed(
+    message_id: &str,
+    ix: usize,
+    embed: &Embed,
+    image: Option<Arc<Image>>,
+    cx: &Context<TripwireApp>,
+) -> AnyElement {
+    let has_text = embed.title.is_some() || embed.description.is_some() || !embed.fields.is_empty();
+    let accent = embed.color.map(|color| rgb(color).into()).unwrap_or(cx.theme().border);
+
+    h_flex()
+        .max_w(px(MAX_EMBED_WIDTH))
+        .items_start()
+        .rounded(cx.theme().radius)
+        .overflow_hidden()
+        .bg(cx.theme().muted)
+        .border_l_4()
+        .border_color(accent)
+        .child(
+            v_flex()
+                .flex_1()
+                .min_w_0()
+                .p_3()
+                .gap_1()
+                .when_some(embed.site_name.clone(), |this, site| {
+                    this.child(div().text_xs().text_color(cx.theme().muted_foreground).child(site))
+                })
+                .when_some(embed.title.clone(), |this, title| {
+                    let title = div()
+                        .text_sm()
+                        .font_semibold()
+                        .text_color(cx.theme().foreground)
+                        .child(title);
+                    match embed.url.clone() {
+                        Some(url) => this.child(
+                            title
+                                .id(ElementId::Name(SharedString::from(format!("embed-{message_id}-{ix}"))))
+                                .text_color(cx.theme().link)
+                                .cursor_pointer()
+                                .hover(|s| s.underline())
+                                .on_click(move |_, _, cx| cx.open_url(&url)),
+                        ),
+                        None => this.child(title),
+                    }
+                })
+                .when_some(embed.description.clone(), |this, description| {
+                    this.child(div().text_sm().text_color(cx.theme().foreground).child(description))
+                })
+                .when(!embed.fields.is_empty(), |this| {
+                    this.child(
+                        h_flex()
+                            .mt_1()
+                            .flex_wrap()
+                            .gap_x_4()
+                            .gap_y_2()
+                            .children(embed.fields.iter().map(|field| {
+                                v_flex()
+                                    .when(field.inline, |this| this.w(px(INLINE_FIELD_WIDTH)))
+                                    .when(!field.inline, |this| this.w_full())
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .font_semibold()
+                                            .text_color(cx.theme().foreground)
+                                            .child(field.name.clone()),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_sm()
+                                            .text_color(cx.theme().foreground)
+                                            .child(field.value.clone()),
+                                    )
+                            })),
+                    )
+                })
+                // An image with nothing to say about it is shown large.
+                .when_some(image.clone().filter(|_| !has_text), |this, image| {
+                    this.child(
+                        img(image)
+                            .max_w(px(MAX_EMBED_WIDTH - 24.))
+                            .max_h(px(300.))
+                            .rounded(cx.theme().radius)
+                            .object_fit(gpui::ObjectFit::Contain),
+                    )
+                }),
+        )
+        .when_some(image.filter(|_| has_text), |this, image| {
+            this.child(
+                div().p_3().flex_shrink_0().child(
+                    img(image)
+                        .size(px(THUMBNAIL_SIZE))
+                        .rounded(cx.theme().radius)
+                        .object_fit(gpui::ObjectFit::Cover),
+                ),
+            )
+        })
+        .into_any_element()
+}
@@ -0,0 +1,344 @@
+//! Emoji picker — search, category tabs, skin tones, frequently used and
+//! the open server's custom emoji. Opens in a popover to react to a message
+//! or to insert into the composer.
+
+use std::ops::Range;
+use std::rc::Rc;
+
+use gpui::{
+    AnyElement, App, Context, DismissEvent, Entity, InteractiveElement, IntoElement, MouseButton,
+    ParentElement, ScrollStrategy, SharedString, StatefulInteractiveElement as _, Styled,
+    WeakEntity, Window, div, img, prelude::FluentBuilder as _, px, uniform_list,
+};
+use gpui_component::{
+    ActiveTheme as _, h_flex, input::Input, popover::PopoverState, tooltip::Tooltip, v_flex,
+};
+
+use crate::app::TripwireApp;
+use crate::emoji::{self, Category, SkinTone};
+use crate::models::CustomEmoji;
+
+const COLUMNS: usize = 8;
+const CELL: f32 = 36.;
+/// Emoji shown under "Frequently Used".
+const FREQUENT: usize = 2 * COLUMNS;
+
+/// What picking an emoji does.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum EmojiPickTarget {
+    /// React to the message with this ID.
+    Reaction(String),
+    /// Insert into the channel composer.
+    Composer,
+}
+
+#[derive(Clone)]
+enum Pick {
+    Unicode {
+        emoji: &'static str,
+        shortcode: &'static str,
+    },
+    Custom(CustomEmoji),
+}
+
+impl Pick {
+    fn name(&self) -> String {
+        match self {
+            Pick::Unicode { shortcode, .. } => format!(":{shortcode}:"),
+            Pick::Custom(custom) => format!(":{}:", custom.name),
+        }
+    }
+
+    /// What's reacted with or remembered as used.
+    fn value(&self) -> String {
+        match self {
+            Pick::Unicode { emoji, .. } => emoji.to_string(),
+            Pick::Custom(custom) => custom.to_ref().token(),
+        }
+    }
+
+    /// What's typed into the composer; `mentions::serialize` turns custom
+    /// names into tokens on send.
+    fn text(&self) -> String {
+        match self {
+            Pick::Unicode { emoji, .. } => emoji.to_string(),
+            Pick::Custom(custom) => format!(":{}: ", custom.name),
+        }
+    }
+}
+
+/// A run of emoji under a heading, with the icon of its tab.
+struct Section {
+    icon: &'static str,
+    heading: SharedString,
+    picks: Vec<Pick>,
+}
+
+/// One line of the picker: a section heading or a row of emoji.
+enum Row {
+    Heading(SharedString),
+    Picks(Vec<Pick>),
+}
+
+impl TripwireApp {
+    pub(crate) fn pick_emoji(
+        &mut self,
+        target: &EmojiPickTarget,
+        value: String,
+        text: String,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.emoji_usage.record(&value);
+        match target {
+            EmojiPickTarget::Reaction(message_id) => self.toggle_reaction(message_id.clone(), value, cx),
+            EmojiPickTarget::Composer => self.message_input.update(cx, |input, cx| {
+                input.insert(text, window, cx);
+                input.focus(window, cx);
+            }),
+        }
+    }
+
+    pub(crate) fn set_skin_tone(&mut self, tone: SkinTone, cx: &mut Context<Self>) {
+        self.emoji_usage.set_skin_tone(tone);
+        cx.notify();
+    }
+
+    /// The custom emoji with `id`, from any server.
+    pub(crate) fn custom_emoji(&self, id: &str) -> Option<&CustomEmoji> {
+        self.servers
+            .iter()
+            .flat_map(|server| &server.emojis)
+            .find(|emoji| emoji.id == id)
+    }
+
+    /// What the picker lists: matches for `query`, or everything.
+    fn emoji_picker_sections(&self, query: &str) -> Vec<Section> {
+        let tone = self.emoji_usage.skin_tone;
+        let unicode = |e: &'static emoji::Emoji| Pick::Unicode {
+            emoji: e.with_tone(tone),
+            shortcode: e.shortcode,
+        };
+        let server_emojis = self.active_server().map(|s| s.emojis.as_slice()).unwrap_or_default();
+
+        if !query.is_empty() {
+            let query = query.to_lowercase();
+            let picks: Vec<Pick> = server_emojis
+                .iter()
+                .filter(|custom| custom.name.to_lowercase().contains(&query))
+                .cloned()
+                .map(Pick::Custom)
+                .chain(emoji::matching(&query).into_iter().map(unicode))
+                .collect();
+            return vec![Section {
+                icon: "🔍",
+                heading: "Search Results".into(),
+                picks,
+            }];
+        }
+
+        let mut sections = Vec::new();
+        let frequent: Vec<Pick> = self
+            .emoji_usage
+            .frequent(FREQUENT)
+            .into_iter()
+            .filter_map(|used| match emoji::parse_custom(&used) {
+                Some((custom, _)) => self.custom_emoji(&custom.id).cloned().map(Pick::Custom),
+                None => emoji::find(&used).map(unicode),
+            })
+            .collect();
+        if !frequent.is_empty() {
+            sections.push(Section {
+                icon: "🕘",
+                heading: "Frequently Used".into(),
+                picks: frequent,
+            });
+        }
+        if let Some(server) = self.active_server()
+            && !server.emojis.is_empty()
+        {
+            sections.push(Section {
+                icon: "⭐",
+                heading: server.name.clone().into(),
+                picks: server.emojis.iter().cloned().map(Pick::Custom).collect(),
+            });
+        }
+        for category in Category::ALL {
+            sections.push(Section {
+                icon: category.icon(),
+                heading: category.label().into(),
+                picks: category.emoji().map(unicode).collect(),
+            });
+        }
+        sections
+    }
+
+    /// Render the picker inside its popover.
+    pub(crate) fn render_emoji_picker(
+        target: EmojiPickTarget,
+        app_entity: Entity<TripwireApp>,
+        cx: &mut Context<PopoverState>,
+    ) -> AnyElement {
+        let app = app_entity.read(cx);
+        let query = app.emoji_search_input.read(cx).value().trim().to_string();
+        let tone = app.emoji_usage.skin_tone;
+        let scroll = app.emoji_picker_scroll.clone();
+        let sections = app.emoji_picker_sections(&query);
+
+        // Flatten into rows, remembering where each section starts.
+        let mut rows = Vec::new();
+        let mut tabs = Vec::new();
+        for section in sections {
+            tabs.push((section.icon, section.heading.clone(), rows.len()));
+            rows.push(Row::Heading(section.heading));
+            rows.extend(section.picks.chunks(COLUMNS).map(|chunk| Row::Picks(chunk.to_vec())));
+        }
+        let empty = rows.len() <= 1;
+        let rows = Rc::new(rows);
+        let popover = cx.entity().downgrade();
+        let app = app_entity.downgrade();
+
+        let tab_bar = h_flex()
+            .flex_shrink_0()
+            .px_1()
+            .gap_0p5()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .children(tabs.into_iter().enumerate().map(|(ix, (icon, heading, row))| {
+                let scroll = scroll.clone();
+                div()
+                    .id(ix)
+                    .p_1()
+                    .rounded(cx.theme().radius)
+                    .text_sm()
+                    .cursor_pointer()
+                    .hover(|s| s.bg(cx.theme().accent))
+                    .child(icon)
+                    .tooltip(move |window, cx| Tooltip::new(heading.clone()).build(window, cx))
+                    .on_click(move |_, _, _| scroll.scroll_to_item(row, ScrollStrategy::Top))
+            }));
+
+        let tones = h_flex()
+            .flex_shrink_0()
+            .px_2()
+            .py_1()
+            .gap_1()
+            .items_center()
+            .border_t_1()
+            .border_color(cx.theme().border)
+            .child(
+                div()
+                    .flex_1()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child("Skin Tone"),
+            )
+            .children(SkinTone::ALL.into_iter().map(|swatch| {
+                let app = app.clone();
+                div()
+                    .id(swatch as usize)
+                    .px_1()
+                    .rounded(cx.theme().radius)
+                    .cursor_pointer()
+                    .when(swatch == tone, |this| this.bg(cx.theme().accent))
+                    .hover(|s| s.bg(cx.theme().accent))
+                    .child(swatch.swatch())
+                    .on_click(move |_, _, cx| {
+                        app.update(cx, |this, cx| this.set_skin_tone(swatch, cx)).ok();
+                    })
+            }));
+
+        let list = uniform_list("emoji-picker-rows", rows.len(), {
+            let rows = rows.clone();
+            move |range: Range<usize>, _, cx: &mut App| {
+                range
+                    .map(|ix| render_row(ix, &rows[ix], &target, &app, &popover, cx))
+                    .collect()
+            }
+        })
+        .track_scroll(scroll)
+        .size_full();
+
+        v_flex()
+            .w(px(CELL * COLUMNS as f32 + 24.))
+            .h(px(400.))
+            .child(
+                div()
+                    .flex_shrink_0()
+                    .p_2()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(Input::new(&app_entity.read(cx).emoji_search_input).appearance(false)),
+            )
+            .child(tab_bar)
+            .child(if empty {
+                div()
+                    .flex_1()
+                    .p_4()
+                    .text_center()
+                    .text_sm()
+                    .text_color(cx.theme().muted_foreground)
+                    .child("No emoji found")
+                    .into_any_element()
+            } else {
+                div().flex_1().min_h_0().px_2().child(list).into_any_element()
+            })
+            .child(tones)
+            .into_any_element()
+    }
+}
+
+fn render_row(
+    ix: usize,
+    row: &Row,
+    target: &EmojiPickTarget,
+    app: &WeakEntity<TripwireApp>,
+    popover: &WeakEntity<PopoverState>,
+    cx: &mut App,
+) -> AnyElement {
+    let picks = match row {
+        Row::Heading(heading) => {
+            return div()
+                .h(px(CELL))
+                .flex()
+                .items_end()
+                .pb_1()
+                .text_xs()
+                .font_weight(gpui::FontWeight::SEMIBOLD)
+                .text_color(cx.theme().muted_foreground)
+                .child(heading.clone())
+                .into_any_element();
+        }
+        Row::Picks(picks) => picks,
+    };
+    h_flex()
+        .h(px(CELL))
+        .children(picks.iter().enumerate().map(|(col, pick)| {
+            let (app, popover, target) = (app.clone(), popover.clone(), target.clone());
+            let (value, text, name) = (pick.value(), pick.text(), pick.name());
+            div()
+                .id(ix * COLUMNS + col)
+                .size(px(CELL))
+                .flex()
+                .items_center()
+                .justify_center()
+                .rounded(cx.theme().radius)
+                .cursor_pointer()
+                .hover(|s| s.bg(cx.theme().accent))
+                .tooltip(move |window, cx| Tooltip::new(name.clone()).build(window, cx))
+                .child(match pick {
+                    Pick::Unicode { emoji, .. } => div().text_xl().child(*emoji).into_any_element(),
+                    Pick::Custom(custom) => img(custom.url.clone()).size(px(24.)).into_any_element(),
+                })
+                .on_mouse_down(MouseButton::Left, move |_, window, cx| {
+                    let (app, target, value, text) = (app.clone(), target.clone(), value.clone(), text.clone());
+                    window.defer(cx, move |window, cx| {
+                        app.update(cx, |this, cx| this.pick_emoji(&target, value, text, window, cx))
+                            .ok();
+                    });
+                    popover.update(cx, |_, cx| cx.emit(DismissEvent)).ok();
+                })
+                .into_any_element()
+        }))
+        .into_any_element()
+}
@@ -2,7 +2,7 @@
 
 use gpui::{
     AnyElement, Context, InteractiveElement, ParentElement, SharedString, Styled, Window, div, px,
-    IntoElement,
+    IntoElement, ObjectFit, StatefulInteractiveElement as _, StyledImage as _, img,
 };
 use gpui_component::{
     h_flex, v_flex, ActiveTheme as _, Icon, IconName, Sizable as _,
@@ -21,10 +21,10 @@ impl TripwireApp {
         _window: &mut Window,
         cx: &mut Context<Self>,
     ) -> AnyElement {
-        // Filter messages to only those with attachments (images/videos)
+        // Filter messages to only those with images or videos
         let media_messages: Vec<&Message> = messages
             .iter()
-            .filter(|m| m.attachment.is_some())
+            .filter(|m| m.attachments.iter().any(|a| a.is_image() || a.is_video()))
             .collect();
 
         v_flex()
@@ -105,9 +105,10 @@ impl TripwireApp {
         let mut grid_items = Vec::new();
 
         for message in media_messages {
-            if let Some(attachment) = &message.attachment {
-                let item_id = format!("{}-media", message.id);
+            for attachment in message.attachments.iter().filter(|a| a.is_image() || a.is_video()) {
+                let item_id = format!("{}-media-{}", message.id, attachment.id);
                 
+                let open = attachment.clone();
                 grid_items.push(
                     div()
                         .id(SharedString::from(item_id.clone()))
@@ -121,7 +122,18 @@ impl TripwireApp {
                         .cursor_pointer()
                         .hover(|s| s.border_color(cx.theme().accent))
                         .relative()
-                        .child(
+                        .on_click(cx.listener(move |this, _, window, cx| {
+                            this.open_lightbox(&open, window, cx);
+                        }))
+                        .child(if attachment.is_image() {
+                            match attachment.local_path() {
+                                Some(path) => img(path.to_path_buf()),
+                                None => img(attachment.url.clone()),
+                            }
+                            .size_full()
+                            .object_fit(ObjectFit::Cover)
+                            .into_any_element()
+                        } else {
                             div()
                                 .size_full()
                                 .flex()
@@ -129,16 +141,12 @@ impl TripwireApp {
                                 .justify_center()
                                 .bg(cx.theme().sidebar)
                                 .child(
-                                    Icon::new(if attachment.filename.ends_with(".mp4") 
-                                        || attachment.filename.ends_with(".webm") {
-                                        IconName::Settings
-                                    } else {
-                                        IconName::User
-                                    })
-                                    .size(px(48.0))
-                                    .text_color(cx.theme().muted_foreground)
+                                    Icon::new(IconName::Play)
+                                        .size(px(48.0))
+                                        .text_color(cx.theme().muted_foreground)
                                 )
-                        )
+                                .into_any_element()
+                        })
                         .child(
                             div()
                                 .absolute()
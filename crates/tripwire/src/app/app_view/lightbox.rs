@@ -0,0 +1,380 @@
+//! Lightbox — a full-window viewer for the images and videos of the open
+//! conversation. Scroll or `+`/`-` to zoom, drag to pan, double-click or `0`
+//! for actual size, arrow keys to step through the media.
+
+use gpui::{
+    AnyElement, App, ClickEvent, Context, CursorStyle, ImageSource, ImgResourceLoader, InteractiveElement as _,
+    IntoElement, KeyBinding, MouseButton, MouseDownEvent, MouseMoveEvent, ParentElement, Resource, ScrollDelta,
+    ScrollWheelEvent, SharedUri, StatefulInteractiveElement as _, Styled, Window, actions, canvas, div, img,
+    prelude::FluentBuilder as _, px,
+};
+use gpui_component::{
+    ActiveTheme as _, Disableable as _, Icon, IconName, Sizable as _, StyledExt as _,
+    button::{Button, ButtonVariants as _},
+    h_flex, v_flex,
+};
+
+use crate::app::TripwireApp;
+use crate::attachments::format_size;
+use crate::lightbox::{self, Lightbox, ZOOM_STEP, Zoom};
+use crate::models::Attachment;
+
+const KEY_CONTEXT: &str = "Lightbox";
+/// Scroll distance of one zoom step on touchpads and other pixel-precise
+/// devices; wheels zoom a step per line.
+const PIXELS_PER_ZOOM_STEP: f32 = 50.;
+
+actions!(lightbox, [CloseLightbox, PreviousMedia, NextMedia, ZoomIn, ZoomOut, ToggleActualSize]);
+
+pub fn init(cx: &mut App) {
+    cx.bind_keys([
+        KeyBinding::new("escape", CloseLightbox, Some(KEY_CONTEXT)),
+        KeyBinding::new("left", PreviousMedia, Some(KEY_CONTEXT)),
+        KeyBinding::new("right", NextMedia, Some(KEY_CONTEXT)),
+        KeyBinding::new("=", ZoomIn, Some(KEY_CONTEXT)),
+        KeyBinding::new("+", ZoomIn, Some(KEY_CONTEXT)),
+        KeyBinding::new("-", ZoomOut, Some(KEY_CONTEXT)),
+        KeyBinding::new("0", ToggleActualSize, Some(KEY_CONTEXT)),
+    ]);
+}
+
+/// Where the image of `attachment` loads from.
+fn resource(attachment: &Attachment) -> Resource {
+    match attachment.local_path() {
+        Some(path) => Resource::from(path.to_path_buf()),
+        None => Resource::Uri(SharedUri::from(attachment.url.clone())),
+    }
+}
+
+fn is_web_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+impl TripwireApp {
+    /// Show `attachment` in the lightbox, stepping through the media of the
+    /// open conversation from there.
+    pub(crate) fn open_lightbox(&mut self, attachment: &Attachment, window: &mut Window, cx: &mut Context<Self>) {
+        let media = self
+            .active_target()
+            .and_then(|target| self.cached_messages(&target).map(|messages| lightbox::media(messages)))
+            .unwrap_or_default();
+        self.lightbox = Some(Lightbox::new(media, attachment));
+        window.focus(&self.lightbox_focus, cx);
+        cx.notify();
+    }
+
+    fn close_lightbox(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.lightbox = None;
+        window.focus(&self.focus_handle, cx);
+        cx.notify();
+    }
+
+    /// Change the open lightbox, if there is one.
+    fn update_lightbox(&mut self, cx: &mut Context<Self>, update: impl FnOnce(&mut Lightbox)) {
+        if let Some(lightbox) = self.lightbox.as_mut() {
+            update(lightbox);
+            cx.notify();
+        }
+    }
+
+    pub(crate) fn render_lightbox(&mut self, window: &mut Window, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let lightbox = self.lightbox.as_mut()?;
+        let attachment = lightbox.current().clone();
+        let resource = resource(&attachment);
+        let loaded = attachment
+            .is_image()
+            .then(|| window.use_asset::<ImgResourceLoader>(&resource, cx))
+            .flatten();
+        if let Some(Ok(image)) = &loaded {
+            let natural = image.size(0);
+            lightbox.set_image_size(gpui::size(natural.width.0 as f32, natural.height.0 as f32));
+        }
+        let lightbox = self.lightbox.as_ref()?;
+        let (index, count) = lightbox.position();
+        let zoom_label = match lightbox.zoom() {
+            Zoom::Fit => "Fit".to_string(),
+            Zoom::Scale(scale) => format!("{:.0}%", scale * 100.),
+        };
+
+        let stage = match (&loaded, lightbox.image_bounds()) {
+            (Some(Ok(_)), Some(bounds)) => img(ImageSource::Resource(resource))
+                // An ID keeps GIFs animating across frames.
+                .id("lightbox-image")
+                .absolute()
+                .left(px(bounds.origin.x))
+                .top(px(bounds.origin.y))
+                .w(px(bounds.size.width))
+                .h(px(bounds.size.height))
+                .into_any_element(),
+            (Some(Err(err)), _) => div()
+                .size_full()
+                .flex()
+                .items_center()
+                .justify_center()
+                .text_sm()
+                .text_color(gpui::white())
+                .child(format!("Couldn't load {}: {err}", attachment.filename))
+                .into_any_element(),
+            (None, _) if attachment.is_video() => {
+                let video = attachment.clone();
+                v_flex()
+                    .size_full()
+                    .items_center()
+                    .justify_center()
+                    .gap_3()
+                    .child(Icon::new(IconName::Play).size(px(64.)).text_color(gpui::white()))
+                    .child(
+                        div()
+                            .text_sm()
+                            .text_color(gpui::white())
+                            .child("Videos can't be played here."),
+                    )
+                    .child(
+                        // Only files the backend put on this machine are
+                        // opened as files; anything else from the server
+                        // must be a web address.
+                        Button::new("lightbox-open-video")
+                            .label("Open in Player")
+                            .icon(IconName::ExternalLink)
+                            .disabled(video.local_path().is_none() && !is_web_url(&video.url))
+                            .on_click(move |_, _, cx| match video.local_path() {
+                                Some(path) => cx.open_with_system(path),
+                                None if is_web_url(&video.url) => cx.open_url(&video.url),
+                                None => {}
+                            }),
+                    )
+                    .into_any_element()
+            }
+            _ => div()
+                .size_full()
+                .flex()
+                .items_center()
+                .justify_center()
+                .child(Icon::new(IconName::LoaderCircle).size(px(32.)).text_color(gpui::white()))
+                .into_any_element(),
+        };
+
+        let app = cx.entity();
+        let copy = attachment.clone();
+        let save = attachment.clone();
+        let toolbar = h_flex()
+            .flex_shrink_0()
+            .justify_between()
+            .items_center()
+            .gap_4()
+            .px_4()
+            .py_2()
+            .bg(cx.theme().background)
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .child(
+                v_flex()
+                    .min_w_0()
+                    .child(
+                        div()
+                            .text_sm()
+                            .font_semibold()
+                            .text_color(cx.theme().foreground)
+                            .overflow_hidden()
+                            .text_ellipsis()
+                            .child(attachment.filename.clone()),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(format!("{} of {count} · {}", index + 1, format_size(attachment.size))),
+                    ),
+            )
+            .child(
+                h_flex()
+                    .gap_1()
+                    .items_center()
+                    .when(attachment.is_image(), |this| {
+                        this.child(
+                            Button::new("lightbox-zoom-out")
+                                .icon(IconName::Minus)
+                                .ghost()
+                                .small()
+                                .tooltip("Zoom Out")
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.update_lightbox(cx, |lightbox| lightbox.zoom_by(1. / ZOOM_STEP, None));
+                                })),
+                        )
+                        .child(
+                            div()
+                                .w(px(44.))
+                                .text_center()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(zoom_label),
+                        )
+                        .child(
+                            Button::new("lightbox-zoom-in")
+                                .icon(IconName::Plus)
+                                .ghost()
+                                .small()
+                                .tooltip("Zoom In")
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.update_lightbox(cx, |lightbox| lightbox.zoom_by(ZOOM_STEP, None));
+                                })),
+                        )
+                        .child(
+                            Button::new("lightbox-actual-size")
+                                .icon(match lightbox.zoom() {
+                                    Zoom::Fit => IconName::Maximize,
+                                    Zoom::Scale(_) => IconName::Minimize,
+                                })
+                                .ghost()
+                                .small()
+                                .tooltip(match lightbox.zoom() {
+                                    Zoom::Fit => "Actual Size",
+                                    Zoom::Scale(_) => "Fit to Window",
+                                })
+                                .on_click(cx.listener(|this, _, _, cx| {
+                                    this.update_lightbox(cx, Lightbox::toggle_actual_size);
+                                })),
+                        )
+                        .child(
+                            Button::new("lightbox-copy")
+                                .icon(IconName::Copy)
+                                .ghost()
+                                .small()
+                                .tooltip("Copy Image")
+                                .on_click(cx.listener(move |this, _, _, cx| {
+                                    this.copy_image(copy.clone(), cx);
+                                })),
+                        )
+                    })
+                    .child(
+                        Button::new("lightbox-save")
+                            .icon(IconName::ArrowDown)
+                            .ghost()
+                            .small()
+                            .tooltip("Save As…")
+                            .on_click(cx.listener(move |this, _, window, cx| {
+                                this.save_attachment(save.clone(), window, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("lightbox-close")
+                            .icon(IconName::Close)
+                            .ghost()
+                            .small()
+                            .tooltip("Close")
+                            .on_click(cx.listener(|this, _, window, cx| this.close_lightbox(window, cx))),
+                    ),
+            );
+
+        Some(
+            v_flex()
+                .absolute()
+                .inset_0()
+                .occlude()
+                .track_focus(&self.lightbox_focus)
+                .key_context(KEY_CONTEXT)
+                .on_action(cx.listener(|this, _: &CloseLightbox, window, cx| this.close_lightbox(window, cx)))
+                .on_action(cx.listener(|this, _: &PreviousMedia, _, cx| this.update_lightbox(cx, Lightbox::previous)))
+                .on_action(cx.listener(|this, _: &NextMedia, _, cx| this.update_lightbox(cx, Lightbox::next)))
+                .on_action(cx.listener(|this, _: &ZoomIn, _, cx| {
+                    this.update_lightbox(cx, |lightbox| lightbox.zoom_by(ZOOM_STEP, None));
+                }))
+                .on_action(cx.listener(|this, _: &ZoomOut, _, cx| {
+                    this.update_lightbox(cx, |lightbox| lightbox.zoom_by(1. / ZOOM_STEP, None));
+                }))
+                .on_action(cx.listener(|this, _: &ToggleActualSize, _, cx| {
+                    this.update_lightbox(cx, Lightbox::toggle_actual_size);
+                }))
+                .child(toolbar)
+                .child(
+                    div()
+                        .id("lightbox-stage")
+                        .flex_1()
+                        .relative()
+                        .overflow_hidden()
+                        .bg(gpui::rgba(0x000000E6))
+                        .when(attachment.is_image(), |this| {
+                            this.cursor(if lightbox.is_dragging() { CursorStyle::ClosedHand } else { CursorStyle::OpenHand })
+                        })
+                        .on_scroll_wheel(cx.listener(|this, event: &ScrollWheelEvent, _, cx| {
+                            let steps = match event.delta {
+                                ScrollDelta::Lines(delta) => delta.y,
+                                ScrollDelta::Pixels(delta) => f32::from(delta.y) / PIXELS_PER_ZOOM_STEP,
+                            };
+                            let anchor = event.position.map(f32::from);
+                            this.update_lightbox(cx, |lightbox| lightbox.zoom_by(ZOOM_STEP.powf(steps), Some(anchor)));
+                        }))
+                        .on_mouse_down(MouseButton::Left, cx.listener(|this, event: &MouseDownEvent, _, cx| {
+                            this.update_lightbox(cx, |lightbox| lightbox.start_drag(event.position.map(f32::from)));
+                        }))
+                        .on_mouse_move(cx.listener(|this, event: &MouseMoveEvent, _, cx| {
+                            if let Some(lightbox) = this.lightbox.as_mut()
+                                && lightbox.drag_to(event.position.map(f32::from))
+                            {
+                                cx.notify();
+                            }
+                        }))
+                        .on_mouse_up(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                            this.update_lightbox(cx, Lightbox::end_drag);
+                        }))
+                        .on_mouse_up_out(MouseButton::Left, cx.listener(|this, _, _, cx| {
+                            this.update_lightbox(cx, Lightbox::end_drag);
+                        }))
+                        .on_click(cx.listener(|this, event: &ClickEvent, _, cx| {
+                            if event.click_count() == 2 {
+                                this.update_lightbox(cx, Lightbox::toggle_actual_size);
+                            }
+                        }))
+                        // Measures the stage for fitting and zooming.
+                        .child(
+                            canvas(
+                                move |bounds, _, cx| {
+                                    app.update(cx, |this, cx| {
+                                        if let Some(lightbox) = this.lightbox.as_mut()
+                                            && lightbox.set_viewport(bounds.map(f32::from))
+                                        {
+                                            cx.notify();
+                                        }
+                                    });
+                                },
+                                |_, _, _, _| {},
+                            )
+                            .absolute()
+                            .size_full(),
+                        )
+                        .child(stage)
+                        .when(lightbox.has_previous(), |this| {
+                            this.child(nav_button("lightbox-previous", IconName::ChevronLeft, true, cx))
+                        })
+                        .when(lightbox.has_next(), |this| {
+                            this.child(nav_button("lightbox-next", IconName::ChevronRight, false, cx))
+                        }),
+                )
+                .into_any_element(),
+        )
+    }
+}
+
+/// A button on the left or right edge of the stage, stepping to the
+/// previous or next item.
+fn nav_button(id: &'static str, icon: IconName, previous: bool, cx: &Context<TripwireApp>) -> impl IntoElement {
+    div()
+        .absolute()
+        .top_0()
+        .bottom_0()
+        .when(previous, |this| this.left_0())
+        .when(!previous, |this| this.right_0())
+        .px_4()
+        .flex()
+        .items_center()
+        // Clicking the button doesn't start panning.
+        .on_mouse_down(MouseButton::Left, |_, _, cx| cx.stop_propagation())
+        .child(
+            Button::new(id)
+                .icon(icon)
+                .large()
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.update_lightbox(cx, if previous { Lightbox::previous } else { Lightbox::next });
+                })),
+        )
+}
@@ -1,4 +1,5 @@
-//! Right-side member list panel — 240 px wide, showing online / offline users.
+//! Right-side member list panel — 240 px wide, showing online users under
+//! their hoisted roles, then the rest online, then offline users.
 
 use gpui::{AnyElement, Context, ElementId, IntoElement as _, SharedString, div, px};
 use gpui::InteractiveElement;
@@ -20,24 +21,44 @@ const PANEL_WIDTH: f32 = 240.;
 
 impl TripwireApp {
     pub(crate) fn render_members_panel(&mut self, cx: &mut Context<Self>) -> AnyElement {
-        let members = self
-            .active_server()
-            .map(|s| s.members.clone())
-            .unwrap_or_default();
+        let Some(server) = self.active_server() else {
+            return div().into_any_element();
+        };
 
         let (online, offline): (Vec<_>, Vec<_>) =
-            members.iter().partition(|u| u.is_online());
+            server.members.iter().partition(|u| u.is_online());
+
+        // Online members sorted into hoisted roles, highest first, then
+        // everyone else online.
+        let mut sections: Vec<(String, Vec<&User>)> = server
+            .roles
+            .iter()
+            .filter(|role| role.hoist)
+            .map(|role| {
+                let holders = online
+                    .iter()
+                    .copied()
+                    .filter(|u| server.hoisted_role(&u.id).is_some_and(|r| r.id == role.id))
+                    .collect();
+                (role.name.to_uppercase(), holders)
+            })
+            .collect();
+        let unhoisted = online.iter().copied().filter(|u| server.hoisted_role(&u.id).is_none()).collect();
+        sections.push(("ONLINE".to_string(), unhoisted));
 
         // Pre-compute all children before building the element tree to avoid
         // multiple mutable borrows of `cx` inside closures.
         let mut items: Vec<AnyElement> = Vec::new();
 
-        if !online.is_empty() {
+        for (ix, (label, users)) in sections.iter().filter(|(_, users)| !users.is_empty()).enumerate() {
+            if ix > 0 {
+                items.push(div().mt_4().into_any_element());
+            }
             items.push(
-                self.render_section_header(&format!("ONLINE — {}", online.len()), cx)
+                self.render_section_header(&format!("{label} — {}", users.len()), cx)
                     .into_any_element(),
             );
-            for u in &online {
+            for u in users {
                 items.push(self.render_member_row(u, cx).into_any_element());
             }
         }
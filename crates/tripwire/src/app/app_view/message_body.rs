@@ -0,0 +1,305 @@
+//! Message bodies — renders the blocks `markdown::parse` finds in a message.
+//! Spoilers stay hidden until clicked; fenced code is highlighted with the
+//! `highlighter` module, cached by content. Mention tokens show as the name
+//! they point at, custom emoji tokens as their image.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use gpui::{
+    AnyElement, App, Context, ElementId, FontStyle, FontWeight, HighlightStyle, InteractiveText,
+    IntoElement, ParentElement, SharedString, StrikethroughStyle, Styled, StyledText,
+    UnderlineStyle, div, img, px,
+};
+use gpui_component::{
+    ActiveTheme as _, Rope, h_flex,
+    highlighter::{HighlightTheme, SyntaxHighlighter},
+    v_flex,
+};
+
+use crate::app::TripwireApp;
+use crate::emoji;
+use crate::markdown::{self, Block, Span};
+use crate::mentions::Mention;
+use crate::models::Message;
+
+/// Highlighted code blocks kept around; the cache starts over past this.
+const MAX_CACHED_CODE_BLOCKS: usize = 256;
+/// Custom emoji size within text, and in a paragraph of nothing else.
+const INLINE_EMOJI_SIZE: f32 = 22.;
+const JUMBO_EMOJI_SIZE: f32 = 48.;
+
+/// What clicking part of a message body does.
+enum SpanAction {
+    OpenLink(String),
+    RevealSpoiler(usize),
+    OpenChannel(String),
+}
+
+impl TripwireApp {
+    /// Highlight the code blocks of `messages` that aren't cached yet. Call
+    /// before rendering them.
+    pub(crate) fn highlight_code_blocks(&mut self, messages: &[Message], cx: &App) {
+        let theme = cx.theme().highlight_theme.clone();
+        for message in messages.iter().filter(|m| m.content.contains("```")) {
+            for block in markdown::parse(&message.content) {
+                let Block::Code { lang: Some(lang), code } = block else {
+                    continue;
+                };
+                let key = code_key(&lang, &code, &theme);
+                if self.code_highlights.contains_key(&key) {
+                    continue;
+                }
+                if self.code_highlights.len() >= MAX_CACHED_CODE_BLOCKS {
+                    self.code_highlights.clear();
+                }
+                let mut highlighter = SyntaxHighlighter::new(&lang);
+                highlighter.update(None, &Rope::from_str(&code));
+                let styles = highlighter.styles(&(0..code.len()), &theme);
+                self.code_highlights.insert(key, styles);
+            }
+        }
+    }
+
+    pub(crate) fn reveal_spoiler(&mut self, message_id: String, spoiler: usize, cx: &mut Context<Self>) {
+        self.revealed_spoilers.insert((message_id, spoiler));
+        cx.notify();
+    }
+
+    pub(crate) fn render_message_body(
+        &self,
+        message_id: &str,
+        content: &str,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let mut children: Vec<AnyElement> = Vec::new();
+        for (ix, block) in markdown::parse(content).into_iter().enumerate() {
+            let child = match block {
+                Block::Paragraph(spans) => self.render_spans(message_id, ix, &spans, cx),
+                Block::Quote(spans) => div()
+                    .border_l_4()
+                    .border_color(cx.theme().border)
+                    .pl_3()
+                    .child(self.render_spans(message_id, ix, &spans, cx))
+                    .into_any_element(),
+                Block::Code { lang, code } => {
+                    let styles = lang
+                        .map(|lang| code_key(&lang, &code, &cx.theme().highlight_theme))
+                        .and_then(|key| self.code_highlights.get(&key).cloned())
+                        .unwrap_or_default();
+                    div()
+                        .p_2()
+                        .rounded(cx.theme().radius)
+                        .bg(cx.theme().muted)
+                        .border_1()
+                        .border_color(cx.theme().border)
+                        .font_family(cx.theme().mono_font_family.clone())
+                        .text_size(cx.theme().mono_font_size)
+                        .child(StyledText::new(code).with_highlights(styles))
+                        .into_any_element()
+                }
+            };
+            children.push(child);
+        }
+        v_flex()
+            .gap_1()
+            .text_sm()
+            .text_color(cx.theme().foreground)
+            .children(children)
+            .into_any_element()
+    }
+
+    /// A paragraph: runs of text, with custom emoji images between them.
+    fn render_spans(
+        &self,
+        message_id: &str,
+        block_ix: usize,
+        spans: &[Span],
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let spans = self.resolve_tokens(spans);
+        if spans.iter().all(|span| span.emoji.is_none()) {
+            return self.render_text(message_id, &format!("{block_ix}"), &spans, cx);
+        }
+
+        let jumbo = spans
+            .iter()
+            .all(|span| span.emoji.is_some() || span.text.trim().is_empty());
+        let size = px(if jumbo { JUMBO_EMOJI_SIZE } else { INLINE_EMOJI_SIZE });
+        // Each run of text is followed by an emoji, bar the last.
+        let mut images = spans
+            .iter()
+            .filter_map(|span| span.emoji.as_ref())
+            .filter_map(|custom| self.custom_emoji(&custom.id))
+            .map(|custom| img(custom.url.clone()).size(size).into_any_element());
+        let mut children = Vec::new();
+        for (run_ix, run) in spans.split(|span| span.emoji.is_some()).enumerate() {
+            if !run.is_empty() && !jumbo {
+                children.push(self.render_text(message_id, &format!("{block_ix}-{run_ix}"), run, cx));
+            }
+            children.extend(images.next());
+        }
+        h_flex()
+            .flex_wrap()
+            .gap_0p5()
+            .children(children)
+            .into_any_element()
+    }
+
+    fn render_text(
+        &self,
+        message_id: &str,
+        run_id: &str,
+        spans: &[Span],
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let (text, ranges) = markdown::layout(spans);
+        let mut highlights = Vec::with_capacity(spans.len());
+        let mut clickable = Vec::new();
+        let mut actions = Vec::new();
+        for (span, range) in spans.iter().zip(ranges) {
+            let hidden = span.style.spoiler.filter(|&spoiler| {
+                !self
+                    .revealed_spoilers
+                    .contains(&(message_id.to_string(), spoiler))
+            });
+            highlights.push((range.clone(), span_highlight(span, hidden.is_some(), cx)));
+            if let Some(spoiler) = hidden {
+                clickable.push(range);
+                actions.push(SpanAction::RevealSpoiler(spoiler));
+            } else if let Some(url) = &span.link {
+                clickable.push(range);
+                actions.push(SpanAction::OpenLink(url.clone()));
+            } else if let Some(Mention::Channel(channel_id)) = &span.mention {
+                clickable.push(range);
+                actions.push(SpanAction::OpenChannel(channel_id.clone()));
+            }
+        }
+
+        let text = StyledText::new(text).with_highlights(highlights);
+        if clickable.is_empty() {
+            return div().child(text).into_any_element();
+        }
+        let app = cx.entity().downgrade();
+        let id = message_id.to_string();
+        InteractiveText::new(
+            ElementId::Name(SharedString::from(format!("msg-body-{message_id}-{run_id}"))),
+            text,
+        )
+        .on_click(clickable, move |ix, window, cx| match &actions[ix] {
+            SpanAction::OpenLink(url) => cx.open_url(url),
+            SpanAction::RevealSpoiler(spoiler) => {
+                let (id, spoiler) = (id.clone(), *spoiler);
+                app.update(cx, |this, cx| this.reveal_spoiler(id, spoiler, cx))
+                    .ok();
+            }
+            SpanAction::OpenChannel(channel_id) => {
+                let channel_id = channel_id.clone();
+                app.update(cx, |this, cx| this.switch_channel(channel_id, window, cx))
+                    .ok();
+            }
+        })
+        .into_any_element()
+    }
+
+    /// `spans` with mention tokens replaced by the names they point at, and
+    /// custom emoji nobody has an image for by their `:name:`.
+    fn resolve_tokens(&self, spans: &[Span]) -> Vec<Span> {
+        let directory = self.mention_directory.borrow();
+        spans
+            .iter()
+            .map(|span| {
+                if let Some(custom) = &span.emoji
+                    && self.custom_emoji(&custom.id).is_none()
+                {
+                    return Span {
+                        text: format!(":{}:", custom.name),
+                        emoji: None,
+                        ..span.clone()
+                    };
+                }
+                let Some(mention) = &span.mention else {
+                    return span.clone();
+                };
+                let text = directory.display(mention).unwrap_or_else(|| {
+                    match mention {
+                        Mention::User(_) => "@unknown-user",
+                        Mention::Role(_) => "@deleted-role",
+                        Mention::Channel(_) => "#deleted-channel",
+                        Mention::Everyone => "@everyone",
+                    }
+                    .to_string()
+                });
+                Span { text, ..span.clone() }
+            })
+            .collect()
+    }
+}
+
+impl TripwireApp {
+    /// A reaction's emoji: the image for custom emoji tokens.
+    pub(crate) fn render_reaction_emoji(&self, reaction: &str) -> AnyElement {
+        let custom = emoji::parse_custom(reaction)
+            .filter(|(_, len)| *len == reaction.len())
+            .and_then(|(custom, _)| self.custom_emoji(&custom.id));
+        match custom {
+            Some(custom) => img(custom.url.clone()).size_4().into_any_element(),
+            None => div().text_sm().child(reaction.to_string()).into_any_element(),
+        }
+    }
+}
+
+fn span_highlight(span: &Span, hidden: bool, cx: &App) -> HighlightStyle {
+    let theme = cx.theme();
+    let style = &span.style;
+    let mut highlight = HighlightStyle::default();
+    if style.bold {
+        highlight.font_weight = Some(FontWeight::BOLD);
+    }
+    if style.italic {
+        highlight.font_style = Some(FontStyle::Italic);
+    }
+    if style.underline || span.link.is_some() {
+        highlight.underline = Some(UnderlineStyle {
+            thickness: px(1.),
+            ..Default::default()
+        });
+    }
+    if style.strikethrough {
+        highlight.strikethrough = Some(StrikethroughStyle {
+            thickness: px(1.),
+            ..Default::default()
+        });
+    }
+    if style.code {
+        highlight.background_color = Some(theme.muted);
+    }
+    if span.link.is_some() {
+        highlight.color = Some(theme.link);
+    }
+    if span.mention.is_some() {
+        highlight.color = Some(theme.link);
+        highlight.background_color = Some(theme.link.opacity(0.15));
+    }
+    if style.spoiler.is_some() {
+        if hidden {
+            // Text the same color as its background until revealed.
+            highlight.color = Some(theme.muted_foreground);
+            highlight.background_color = Some(theme.muted_foreground);
+            highlight.underline = None;
+        } else {
+            highlight.background_color = Some(theme.muted);
+        }
+    }
+    highlight
+}
+
+/// Cache key for a highlighted code block.
+fn code_key(lang: &str, code: &str, theme: &Arc<HighlightTheme>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    lang.hash(&mut hasher);
+    code.hash(&mut hasher);
+    Arc::as_ptr(theme).hash(&mut hasher);
+    hasher.finish()
+}
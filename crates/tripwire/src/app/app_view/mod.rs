@@ -3,11 +3,18 @@
 //! Sub-modules each implement one panel of the UI via `impl TripwireApp`.
 //! This file wires them all together into the top-level `render_app` method.
 
+pub mod channel_dialogs;
 pub mod channel_list;
 pub mod chat_area;
 pub mod dm_list;
+pub mod edit_history;
+pub mod embeds;
+pub mod emoji_picker;
+pub mod lightbox;
 pub mod members_panel;
+pub mod message_body;
 pub mod profile_card;
+pub mod search_panel;
 pub mod server_list;
 pub mod settings;
 pub mod server_settings;
@@ -56,14 +63,21 @@ impl TripwireApp {
             })
             // Main content: header + messages + input
             .child(self.render_chat_area(window, cx))
+            // Search results replace any other right-hand sidebar
+            .when_some(self.render_search_panel(cx), |this, panel| {
+                this.child(panel)
+            })
             // Thread sidebar (if a thread is open OR voice chat sidebar is open)
-            .when_some(self.render_thread_sidebar(window, cx), |this, sidebar| {
-                this.child(sidebar)
+            .when(self.search.is_none(), |this| {
+                this.when_some(self.render_thread_sidebar(window, cx), |this, sidebar| {
+                    this.child(sidebar)
+                })
             })
             // Right panel: members list (only show for servers, not DMs, and not when any sidebar is open)
             .when(
                 self.show_members 
                 && self.current_view == AppView::Servers 
+                && self.search.is_none()
                 && self.open_thread_id.is_none() 
                 && !self.show_voice_chat_sidebar, 
                 |this| {
@@ -106,10 +120,22 @@ impl TripwireApp {
             .when(self.show_server_settings, |this| {
                 this.child(self.render_server_settings_modal(window, cx))
             })
+            // Channel create / settings / delete dialog, above server settings
+            .when_some(self.render_channel_dialog(window, cx), |this, dialog| {
+                this.child(dialog)
+            })
             // Voice switch warning modal (if open)
             .when(self.show_voice_switch_warning.is_some(), |this| {
                 this.child(self.render_voice_switch_warning_modal(window, cx))
             })
+            // Edit history dialog (if open)
+            .when_some(self.render_edit_history_modal(window, cx), |this, modal| {
+                this.child(modal)
+            })
+            // Lightbox over everything else (if open)
+            .when_some(self.render_lightbox(window, cx), |this, lightbox| {
+                this.child(lightbox)
+            })
             .into_any_element()
     }
     
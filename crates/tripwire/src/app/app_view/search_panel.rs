@@ -0,0 +1,189 @@
+//! Search results sidebar — hits grouped by channel or DM, with the matched
+//! text highlighted. Clicking a hit jumps to it in the chat area.
+
+use gpui::{
+    AnyElement, Context, FontWeight, HighlightStyle, InteractiveElement, IntoElement,
+    MouseButton, ParentElement, SharedString, StatefulInteractiveElement as _, Styled,
+    StyledText, div, px,
+};
+use gpui_component::{
+    ActiveTheme as _, IconName, Sizable as _, StyledExt,
+    avatar::Avatar,
+    button::{Button, ButtonVariants},
+    h_flex,
+    scroll::ScrollableElement as _,
+    v_flex,
+};
+
+use crate::app::TripwireApp;
+
+impl TripwireApp {
+    pub(crate) fn render_search_panel(&self, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let results = self.search.as_ref()?;
+        let count = results.hit_count();
+
+        let body = if let Some(error) = &results.error {
+            div()
+                .p_4()
+                .text_sm()
+                .text_color(cx.theme().danger)
+                .child(error.clone())
+                .into_any_element()
+        } else if results.groups.is_empty() {
+            v_flex()
+                .p_4()
+                .gap_2()
+                .child(
+                    div()
+                        .text_sm()
+                        .text_color(cx.theme().foreground)
+                        .child(format!("No results for \"{}\".", results.query)),
+                )
+                .child(
+                    div()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(
+                            "Only messages saved on this device are searched. Filters: \
+                             from:user in:#channel has:image mentions:user \
+                             before:YYYY-MM-DD after:YYYY-MM-DD pinned:true",
+                        ),
+                )
+                .into_any_element()
+        } else {
+            let highlight = HighlightStyle {
+                background_color: Some(cx.theme().warning.opacity(0.35)),
+                font_weight: Some(FontWeight::SEMIBOLD),
+                ..Default::default()
+            };
+            let mut groups: Vec<AnyElement> = Vec::new();
+            for group in &results.groups {
+                let mut hits: Vec<AnyElement> = Vec::new();
+                for hit in &group.hits {
+                    let target = group.target.clone();
+                    let message_id = hit.message.id.clone();
+                    let snippet = StyledText::new(hit.snippet.text.clone()).with_highlights(
+                        hit.snippet
+                            .highlights
+                            .iter()
+                            .map(|range| (range.clone(), highlight)),
+                    );
+                    hits.push(
+                        h_flex()
+                            .id(SharedString::from(format!("search-hit-{}", hit.message.id)))
+                            .gap_3()
+                            .items_start()
+                            .p_3()
+                            .rounded(cx.theme().radius)
+                            .bg(cx.theme().sidebar)
+                            .border_1()
+                            .border_color(cx.theme().border)
+                            .cursor_pointer()
+                            .hover(|s| s.bg(cx.theme().accent))
+                            .on_click(cx.listener(move |this, _, window, cx| {
+                                this.jump_to_message(target.clone(), message_id.clone(), window, cx);
+                            }))
+                            .child(
+                                Avatar::new()
+                                    .name(hit.message.author.username.clone())
+                                    .with_size(gpui_component::Size::Small),
+                            )
+                            .child(
+                                v_flex()
+                                    .flex_1()
+                                    .min_w_0()
+                                    .gap_1()
+                                    .child(
+                                        h_flex()
+                                            .gap_2()
+                                            .items_baseline()
+                                            .child(
+                                                div()
+                                                    .text_sm()
+                                                    .font_weight(FontWeight::SEMIBOLD)
+                                                    .text_color(cx.theme().foreground)
+                                                    .child(hit.message.author.username.clone()),
+                                            )
+                                            .child(
+                                                div()
+                                                    .text_xs()
+                                                    .text_color(cx.theme().muted_foreground)
+                                                    .child(hit.message.timestamp.clone()),
+                                            ),
+                                    )
+                                    .child(
+                                        div()
+                                            .text_sm()
+                                            .text_color(cx.theme().foreground)
+                                            .child(snippet),
+                                    ),
+                            )
+                            .into_any_element(),
+                    );
+                }
+                groups.push(
+                    v_flex()
+                        .gap_2()
+                        .child(
+                            div()
+                                .text_xs()
+                                .font_semibold()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(group.label.clone()),
+                        )
+                        .children(hits)
+                        .into_any_element(),
+                );
+            }
+            v_flex().gap_4().p_4().children(groups).into_any_element()
+        };
+
+        Some(
+            v_flex()
+                .w(px(420.0))
+                .h_full()
+                .bg(cx.theme().background)
+                .border_l_1()
+                .border_color(cx.theme().border)
+                .child(
+                    h_flex()
+                        .flex_shrink_0()
+                        .h(px(48.0))
+                        .px_4()
+                        .items_center()
+                        .justify_between()
+                        .border_b_1()
+                        .border_color(cx.theme().border)
+                        .child(
+                            div()
+                                .text_sm()
+                                .font_semibold()
+                                .text_color(cx.theme().foreground)
+                                .child(match count {
+                                    1 => "1 result".to_string(),
+                                    n => format!("{n} results"),
+                                }),
+                        )
+                        .child(
+                            Button::new("btn-close-search")
+                                .icon(IconName::Close)
+                                .ghost()
+                                .xsmall()
+                                .tooltip("Close search")
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.close_search(window, cx);
+                                })),
+                        ),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .min_h_0()
+                        .overflow_hidden()
+                        .child(div().size_full().overflow_y_scrollbar().child(body)),
+                )
+                .on_mouse_down(MouseButton::Left, |_, _, cx| cx.stop_propagation())
+                .into_any_element(),
+        )
+    }
+}
@@ -15,7 +15,9 @@ use gpui_component::{
     menu::{ContextMenuExt as _, PopupMenu, PopupMenuItem},
 };
 
+use crate::app::notifications::notification_menu;
 use crate::app::{AppView, TripwireApp};
+use crate::notifications::Scope;
 
 const STRIP_WIDTH: f32 = 56.;
 const SERVER_ICON_SIZE: f32 = 48.;
@@ -49,11 +51,12 @@ impl TripwireApp {
                     .map(|(ix, server)| {
                         let is_active = self.active_server == ix;
                         let name = server.name.clone();
+                        let server_id = server.id.clone();
                         let initials = server.initials();
-                        let unread: usize = server.notification_count
-                            + server.all_channels().iter().map(|c| c.unread).sum::<usize>();
+                        let (unread, mentions) = self.server_unread(server);
                         let danger_color = cx.theme().danger;
                         let primary_color = cx.theme().primary;
+                        let foreground_color = cx.theme().foreground;
                         let sidebar_color = cx.theme().sidebar;
 
                         div()
@@ -68,7 +71,7 @@ impl TripwireApp {
                             .on_click(cx.listener(move |this, _, window, cx| {
                                 this.switch_server(ix, window, cx);
                             }))
-                            // Active indicator pill on left edge
+                            // Active indicator pill on left edge, a dot when unread
                             .child(
                                 div()
                                     .absolute()
@@ -77,7 +80,8 @@ impl TripwireApp {
                                     .rounded_r(px(4.))
                                     .bg(primary_color)
                                     .when(is_active, |s| s.h(px(36.)))
-                                    .when(!is_active, |s| s.h(px(0.))),
+                                    .when(!is_active && unread, |s| s.h(px(8.)).bg(foreground_color))
+                                    .when(!is_active && !unread, |s| s.h(px(0.))),
                             )
                             // Server avatar with tooltip - SQUIRCLE SHAPE
                             .child(
@@ -91,8 +95,27 @@ impl TripwireApp {
                                     })
                                     .context_menu({
                                         let app = cx.entity().clone();
-                                        move |menu, _, cx| {
+                                        move |menu, window, cx| {
+                                            let scope = Scope::Server(server_id.clone());
                                             menu.item(
+                                                PopupMenuItem::new("Mark As Read")
+                                                    .icon(IconName::Check)
+                                                    .disabled(!unread)
+                                                    .on_click({
+                                                        let app = app.clone();
+                                                        move |_, _, cx| {
+                                                            app.update(cx, |this, cx| {
+                                                                this.mark_server_read(ix, cx);
+                                                            });
+                                                        }
+                                                    })
+                                            )
+                                            .submenu("Notifications", window, cx, {
+                                                let app = app.clone();
+                                                move |menu, _, cx| notification_menu(menu, scope.clone(), &app, cx)
+                                            })
+                                            .separator()
+                                            .item(
                                                 PopupMenuItem::new("Server Settings")
                                                     .icon(IconName::Settings)
                                                     .on_click({
@@ -134,8 +157,8 @@ impl TripwireApp {
                                             .when(is_active, |this| this.rounded(px(12.)))
                                             .child(initials),
                                     )
-                                    // Unread badge
-                                    .when(unread > 0 && !is_active, move |this| {
+                                    // Mention badge
+                                    .when(mentions > 0, move |this| {
                                         this.child(
                                             div()
                                                 .absolute()
@@ -154,10 +177,10 @@ impl TripwireApp {
                                                 .text_color(gpui::white())
                                                 .text_xs()
                                                 .font_weight(gpui::FontWeight::BOLD)
-                                                .child(if unread > 9 {
+                                                .child(if mentions > 9 {
                                                     "9+".to_string()
                                                 } else {
-                                                    unread.to_string()
+                                                    mentions.to_string()
                                                 }),
                                         )
                                     }),
@@ -19,6 +19,7 @@ pub enum ServerSettingsScreen {
     Roles,
     Channels,
     Members,
+    AuditLog,
 }
 
 impl ServerSettingsScreen {
@@ -28,6 +29,7 @@ impl ServerSettingsScreen {
             ServerSettingsScreen::Roles => "Roles",
             ServerSettingsScreen::Channels => "Channels",
             ServerSettingsScreen::Members => "Members",
+            ServerSettingsScreen::AuditLog => "Audit Log",
         }
     }
 }
@@ -72,6 +74,9 @@ impl TripwireApp {
                             .child(self.render_server_settings_sidebar(&server_name, &current_screen, cx))
                             .child(self.render_server_settings_content(&current_screen, window, cx))
                     )
+                    .when(current_screen == ServerSettingsScreen::Members, |this| {
+                        this.children(screens::members::render_member_dialog(self, cx))
+                    })
             )
             .into_any_element()
     }
@@ -87,6 +92,7 @@ impl TripwireApp {
             ServerSettingsScreen::Roles,
             ServerSettingsScreen::Channels,
             ServerSettingsScreen::Members,
+            ServerSettingsScreen::AuditLog,
         ];
 
         v_flex()
@@ -119,8 +125,8 @@ impl TripwireApp {
                     .cursor_pointer()
                     .when(is_selected, |this| this.bg(cx.theme().accent))
                     .hover(|s| s.bg(cx.theme().accent))
-                    .on_mouse_down(gpui::MouseButton::Left, cx.listener(move |this, _, _, cx| {
-                        this.switch_server_settings_screen(screen_clone.clone(), cx);
+                    .on_mouse_down(gpui::MouseButton::Left, cx.listener(move |this, _, window, cx| {
+                        this.switch_server_settings_screen(screen_clone.clone(), window, cx);
                     }))
                     .child(
                         div()
@@ -186,7 +192,11 @@ impl TripwireApp {
                         ServerSettingsScreen::Roles => screens::roles::render(self, window, cx),
                         ServerSettingsScreen::Channels => screens::channels::render(self, window, cx),
                         ServerSettingsScreen::Members => screens::members::render(self, window, cx),
+                        ServerSettingsScreen::AuditLog => screens::audit_log::render(self, window, cx),
                     })
             )
+            .when(*screen == ServerSettingsScreen::Roles, |this| {
+                this.children(screens::roles::render_unsaved_bar(self, cx))
+            })
     }
 }
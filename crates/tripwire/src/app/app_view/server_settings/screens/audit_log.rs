@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use anyhow::Result;
+use gpui::{div, prelude::FluentBuilder as _, px, AnyElement, App, Context, IntoElement, ParentElement, Styled, WeakEntity, Window};
+use gpui_component::{
+    h_flex, v_flex, ActiveTheme as _, Sizable as _,
+    button::Button,
+    menu::{DropdownMenu as _, PopupMenuItem},
+    table::{Column, Table, TableDelegate, TableState},
+};
+
+use crate::app::TripwireApp;
+use crate::audit_log::{self, AuditAction, AuditChange, AuditLogEntry, AuditLogQuery, PAGE_SIZE};
+use crate::backend::ChatBackend;
+use crate::models::Server;
+use crate::permissions::{self, Permissions};
+
+/// The audit log, newest first, fetched a page at a time as it's
+/// scrolled.
+pub(crate) struct AuditLogTable {
+    app: WeakEntity<TripwireApp>,
+    backend: Arc<dyn ChatBackend>,
+    server_id: String,
+    filter: AuditLogQuery,
+    entries: Vec<AuditLogEntry>,
+    /// Names of the server's members, roles and channels, by ID.
+    names: HashMap<String, String>,
+    loading: bool,
+    exhausted: bool,
+    /// The last fetch failed. Nothing more is fetched until `retry`, so a
+    /// failing server isn't asked again on every frame.
+    failed: bool,
+    /// Bumped on every reset, so pages fetched for an old filter are
+    /// dropped.
+    generation: usize,
+    now: SystemTime,
+}
+
+impl AuditLogTable {
+    pub fn new(app: WeakEntity<TripwireApp>, backend: Arc<dyn ChatBackend>, server: &Server) -> Self {
+        Self {
+            app,
+            backend,
+            server_id: server.id.clone(),
+            filter: AuditLogQuery::default(),
+            entries: Vec::new(),
+            names: HashMap::new(),
+            loading: false,
+            exhausted: false,
+            failed: false,
+            generation: 0,
+            now: SystemTime::now(),
+        }
+    }
+
+    /// Start over with `filter`, naming things as `server` now does.
+    pub fn reset(&mut self, server: &Server, filter: AuditLogQuery, cx: &mut Context<TableState<Self>>) {
+        self.names = server
+            .members
+            .iter()
+            .map(|user| (user.id.clone(), user.tag()))
+            .chain(server.roles.iter().map(|role| (role.id.clone(), format!("@{}", role.name))))
+            .chain(server.all_channels().into_iter().map(|channel| (channel.id.clone(), format!("#{}", channel.name))))
+            .collect();
+        self.filter = filter;
+        self.entries.clear();
+        self.exhausted = false;
+        self.failed = false;
+        self.loading = false;
+        self.generation += 1;
+        self.load_page(cx);
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Fetch the page that failed again.
+    pub fn retry(&mut self, cx: &mut Context<TableState<Self>>) {
+        self.failed = false;
+        self.load_page(cx);
+    }
+
+    fn load_page(&mut self, cx: &mut Context<TableState<Self>>) {
+        if self.loading || self.exhausted || self.failed {
+            return;
+        }
+        self.loading = true;
+        let query = AuditLogQuery {
+            before: self.entries.last().map(|entry| entry.id.clone()),
+            ..self.filter.clone()
+        };
+        let request = self.backend.fetch_audit_log(&self.server_id, query);
+        let generation = self.generation;
+        cx.spawn(async move |table, cx| {
+            let result = request.await;
+            table
+                .update(cx, |table, cx| {
+                    table.delegate_mut().loaded(generation, result, cx);
+                    cx.notify();
+                })
+                .ok();
+        })
+        .detach();
+    }
+
+    fn loaded(&mut self, generation: usize, result: Result<Vec<AuditLogEntry>>, cx: &mut App) {
+        if generation != self.generation {
+            return;
+        }
+        self.loading = false;
+        self.now = SystemTime::now();
+        match result {
+            Ok(page) => {
+                self.exhausted = page.len() < PAGE_SIZE;
+                self.entries.extend(page);
+            }
+            Err(err) => {
+                self.failed = true;
+                self.app
+                    .update(cx, |app, cx| app.show_error(format!("Couldn't load the audit log: {err:#}"), cx))
+                    .ok();
+            }
+        }
+    }
+
+    fn name(&self, id: &str) -> String {
+        self.names.get(id).cloned().unwrap_or_else(|| id.to_string())
+    }
+
+    fn target(&self, entry: &AuditLogEntry) -> String {
+        let target = entry.target_id.as_deref().map(|id| self.name(id)).unwrap_or_default();
+        match &entry.channel_id {
+            Some(channel_id) => format!("{target} in {}", self.name(channel_id)),
+            None => target,
+        }
+    }
+}
+
+fn describe_change(change: &AuditChange) -> String {
+    match (&change.old, &change.new) {
+        (Some(old), Some(new)) => format!("{}: {old} → {new}", change.key),
+        (None, Some(new)) => format!("{}: +{new}", change.key),
+        (Some(old), None) => format!("{}: −{old}", change.key),
+        (None, None) => change.key.clone(),
+    }
+}
+
+impl TableDelegate for AuditLogTable {
+    fn columns_count(&self, _: &App) -> usize {
+        6
+    }
+
+    fn rows_count(&self, _: &App) -> usize {
+        self.entries.len()
+    }
+
+    fn column(&self, col_ix: usize, _: &App) -> Column {
+        match col_ix {
+            0 => Column::new("when", "When").width(px(110.)),
+            1 => Column::new("actor", "By").width(px(130.)),
+            2 => Column::new("action", "Action").width(px(130.)),
+            3 => Column::new("target", "Target").width(px(150.)),
+            4 => Column::new("changes", "Changes").width(px(240.)),
+            _ => Column::new("reason", "Reason").width(px(160.)),
+        }
+    }
+
+    fn render_td(
+        &mut self,
+        row_ix: usize,
+        col_ix: usize,
+        _: &mut Window,
+        cx: &mut Context<TableState<Self>>,
+    ) -> impl IntoElement {
+        let Some(entry) = self.entries.get(row_ix) else {
+            return div();
+        };
+        let (text, muted) = match col_ix {
+            0 => {
+                let age = self.now.duration_since(entry.created_at).unwrap_or_default();
+                (format!("{} ago", audit_log::format_duration(age)), true)
+            }
+            1 => (self.name(&entry.actor_id), false),
+            2 => (entry.action.label().to_string(), false),
+            3 => (self.target(entry), false),
+            4 => (entry.changes.iter().map(describe_change).collect::<Vec<_>>().join("; "), true),
+            _ => (entry.reason.clone().unwrap_or_default(), true),
+        };
+        div()
+            .text_sm()
+            .truncate()
+            .text_color(if muted { cx.theme().muted_foreground } else { cx.theme().foreground })
+            .child(text)
+    }
+
+    fn render_empty(&mut self, _: &mut Window, cx: &mut Context<TableState<Self>>) -> impl IntoElement {
+        h_flex()
+            .size_full()
+            .justify_center()
+            .text_sm()
+            .text_color(cx.theme().muted_foreground)
+            .child("Nothing has been logged yet.")
+    }
+
+    fn loading(&self, _: &App) -> bool {
+        self.loading && self.entries.is_empty()
+    }
+
+    fn has_more(&self, _: &App) -> bool {
+        !self.exhausted && !self.failed
+    }
+
+    fn load_more_threshold(&self) -> usize {
+        10
+    }
+
+    fn load_more(&mut self, _: &mut Window, cx: &mut Context<TableState<Self>>) {
+        self.load_page(cx);
+    }
+}
+
+pub fn render(app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
+    let (Some(server), Some(me)) = (app.active_server(), app.auth.current_user.as_ref()) else {
+        return div().into_any_element();
+    };
+    let Some(viewer) = app
+        .audit_log
+        .as_ref()
+        .filter(|_| permissions::server_permissions(server, &me.id).contains(Permissions::VIEW_AUDIT_LOG))
+    else {
+        return div()
+            .text_sm()
+            .text_color(cx.theme().muted_foreground)
+            .child("You need the View Audit Log permission to see this.")
+            .into_any_element();
+    };
+    let entity = cx.entity();
+
+    let user_filter = viewer.filter.user_id.clone();
+    let user_label = user_filter
+        .as_ref()
+        .and_then(|id| server.members.iter().find(|user| user.id == *id))
+        .map_or("All users".to_string(), |user| user.tag());
+    let users: Vec<(String, String)> = server.members.iter().map(|user| (user.id.clone(), user.tag())).collect();
+    let action_filter = viewer.filter.action;
+    let failed = viewer.table.read(cx).delegate().failed();
+
+    v_flex()
+        .gap_4()
+        .child(
+            h_flex()
+                .gap_2()
+                .child(
+                    Button::new("audit-filter-user")
+                        .label(user_label)
+                        .outline()
+                        .small()
+                        .dropdown_menu({
+                            let app = entity.clone();
+                            move |menu, _, _| {
+                                let pick = |user_id: Option<String>| {
+                                    let app = app.clone();
+                                    move |_: &gpui::ClickEvent, _: &mut Window, cx: &mut App| {
+                                        app.update(cx, |app, cx| app.set_audit_log_user(user_id.clone(), cx));
+                                    }
+                                };
+                                let mut menu = menu.item(
+                                    PopupMenuItem::new("All users")
+                                        .checked(user_filter.is_none())
+                                        .on_click(pick(None)),
+                                );
+                                for (id, tag) in &users {
+                                    menu = menu.item(
+                                        PopupMenuItem::new(tag.clone())
+                                            .checked(user_filter.as_ref() == Some(id))
+                                            .on_click(pick(Some(id.clone()))),
+                                    );
+                                }
+                                menu
+                            }
+                        }),
+                )
+                .child(
+                    Button::new("audit-filter-action")
+                        .label(action_filter.map_or("All actions", AuditAction::label))
+                        .outline()
+                        .small()
+                        .dropdown_menu({
+                            let app = entity.clone();
+                            move |menu, _, _| {
+                                let pick = |action: Option<AuditAction>| {
+                                    let app = app.clone();
+                                    move |_: &gpui::ClickEvent, _: &mut Window, cx: &mut App| {
+                                        app.update(cx, |app, cx| app.set_audit_log_action(action, cx));
+                                    }
+                                };
+                                let mut menu = menu.item(
+                                    PopupMenuItem::new("All actions")
+                                        .checked(action_filter.is_none())
+                                        .on_click(pick(None)),
+                                );
+                                for action in AuditAction::ALL {
+                                    menu = menu.item(
+                                        PopupMenuItem::new(action.label())
+                                            .checked(action_filter == Some(action))
+                                            .on_click(pick(Some(action))),
+                                    );
+                                }
+                                menu
+                            }
+                        }),
+                )
+                .when(failed, |this| {
+                    this.child(
+                        Button::new("audit-retry")
+                            .label("Retry")
+                            .small()
+                            .on_click(cx.listener(|this, _, _, cx| this.retry_audit_log(cx))),
+                    )
+                }),
+        )
+        .child(div().h(px(460.0)).child(Table::new(&viewer.table).stripe(true).bordered(true)))
+        .into_any_element()
+}
@@ -1,34 +1,162 @@
-use gpui::{div, px, AnyElement, Context, IntoElement, ParentElement, Styled, Window};
-use gpui_component::{h_flex, v_flex, ActiveTheme as _, StyledExt, button::Button};
+use gpui::{div, prelude::FluentBuilder as _, px, AnyElement, Context, Div, IntoElement, ParentElement, SharedString, Styled, Window};
+use gpui_component::{
+    h_flex, v_flex, ActiveTheme as _, Icon, IconName, Sizable as _, StyledExt,
+    button::{Button, ButtonVariants},
+};
 
 use crate::app::TripwireApp;
+use crate::models::{Channel, ChannelCategory};
 
 pub fn render(app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
-    let channel_count = app.active_server().map(|s| {
-        s.categories.iter().map(|c| c.channels.len()).sum::<usize>()
-    }).unwrap_or(0);
-    
+    let Some(server) = app.active_server() else {
+        return div().into_any_element();
+    };
+    let can_manage = app.can_manage_channels();
+    let channel_count: usize = server.categories.iter().map(|c| c.channels.len()).sum();
+
     v_flex()
-        .gap_6()
+        .gap_4()
         .max_w(px(700.0))
         .child(
-            v_flex()
-                .gap_4()
-                .child(div().text_lg().font_weight(gpui::FontWeight::SEMIBOLD).text_color(cx.theme().foreground).child("Channels"))
+            h_flex()
+                .justify_between()
+                .items_center()
                 .child(
-                    div()
-                        .p_4()
-                        .rounded(cx.theme().radius_lg)
-                        .bg(cx.theme().muted)
-                        .border_1()
-                        .border_color(cx.theme().border)
-                        .child(
-                            v_flex()
-                                .gap_2()
-                                .child(div().text_sm().text_color(cx.theme().foreground).child(format!("This server has {} channels", channel_count)))
-                                .child(Button::new("btn-create-channel").label("Create Channel"))
-                        )
+                    v_flex()
+                        .gap_1()
+                        .child(div().text_lg().font_weight(gpui::FontWeight::SEMIBOLD).text_color(cx.theme().foreground).child("Channels"))
+                        .child(div().text_sm().text_color(cx.theme().muted_foreground).child(if can_manage {
+                            format!("This server has {channel_count} channels. Drag them in the channel list to reorder.")
+                        } else {
+                            "You need the Manage Channels permission to change channels.".to_string()
+                        })),
                 )
+                .when(can_manage, |this| {
+                    this.child(
+                        h_flex()
+                            .gap_2()
+                            .child(
+                                Button::new("btn-create-category")
+                                    .label("Create Category")
+                                    .outline()
+                                    .small()
+                                    .on_click(cx.listener(|this, _, window, cx| this.open_create_category(window, cx))),
+                            )
+                            .child(
+                                Button::new("btn-create-channel")
+                                    .label("Create Channel")
+                                    .primary()
+                                    .small()
+                                    .on_click(cx.listener(|this, _, window, cx| this.open_create_channel(None, window, cx))),
+                            ),
+                    )
+                }),
         )
+        .children(server.categories.iter().enumerate().map(|(cat_ix, category)| render_category(cat_ix, category, can_manage, cx)))
         .into_any_element()
 }
+
+fn render_category(cat_ix: usize, category: &ChannelCategory, can_manage: bool, cx: &mut Context<TripwireApp>) -> Div {
+    let name = category.name.clone();
+    v_flex()
+        .rounded(cx.theme().radius_lg)
+        .border_1()
+        .border_color(cx.theme().border)
+        .child(
+            h_flex()
+                .justify_between()
+                .items_center()
+                .px_3()
+                .py_2()
+                .bg(cx.theme().muted)
+                .rounded_t(cx.theme().radius_lg)
+                .child(div().text_xs().font_semibold().text_color(cx.theme().muted_foreground).child(category.name.to_uppercase()))
+                .when(can_manage, |this| {
+                    let delete = name.clone();
+                    this.child(
+                        h_flex()
+                            .gap_1()
+                            .child(
+                                Button::new(("category-create-channel", cat_ix))
+                                    .icon(IconName::Plus)
+                                    .ghost()
+                                    .xsmall()
+                                    .tooltip("Create Channel")
+                                    .on_click(cx.listener(move |this, _, window, cx| {
+                                        this.open_create_channel(Some(name.clone()), window, cx);
+                                    })),
+                            )
+                            .child(
+                                Button::new(("category-delete", cat_ix))
+                                    .icon(IconName::Delete)
+                                    .ghost()
+                                    .xsmall()
+                                    .tooltip("Delete Category")
+                                    .on_click(cx.listener(move |this, _, _, cx| this.open_delete_category(&delete, cx))),
+                            ),
+                    )
+                }),
+        )
+        .when(category.channels.is_empty(), |this| {
+            this.child(div().px_3().py_2().text_sm().text_color(cx.theme().muted_foreground).child("No channels yet."))
+        })
+        .children(category.channels.iter().map(|channel| render_channel(channel, can_manage, cx)))
+}
+
+fn render_channel(channel: &Channel, can_manage: bool, cx: &mut Context<TripwireApp>) -> Div {
+    let details: Vec<&str> = [
+        Some(channel.kind.label()),
+        (channel.slowmode_secs > 0).then_some("Slowmode"),
+        channel.nsfw.then_some("Age-restricted"),
+        (!channel.overwrites.is_empty()).then_some("Custom permissions"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    h_flex()
+        .justify_between()
+        .items_center()
+        .gap_3()
+        .px_3()
+        .py_2()
+        .border_t_1()
+        .border_color(cx.theme().border)
+        .child(
+            h_flex()
+                .gap_2()
+                .items_center()
+                .min_w_0()
+                .child(Icon::new(channel.kind.icon()).small().text_color(cx.theme().muted_foreground))
+                .child(
+                    v_flex()
+                        .min_w_0()
+                        .child(div().text_sm().text_color(cx.theme().foreground).child(channel.name.clone()))
+                        .child(div().text_xs().truncate().text_color(cx.theme().muted_foreground).child(details.join(" · "))),
+                ),
+        )
+        .when(can_manage, |this| {
+            let (edit, delete) = (channel.id.clone(), channel.id.clone());
+            this.child(
+                h_flex()
+                    .gap_1()
+                    .flex_shrink_0()
+                    .child(
+                        Button::new(SharedString::from(format!("channel-edit-{}", channel.id)))
+                            .icon(IconName::Settings)
+                            .ghost()
+                            .xsmall()
+                            .tooltip("Edit Channel")
+                            .on_click(cx.listener(move |this, _, window, cx| this.open_channel_settings(&edit, window, cx))),
+                    )
+                    .child(
+                        Button::new(SharedString::from(format!("channel-delete-{}", channel.id)))
+                            .icon(IconName::Delete)
+                            .ghost()
+                            .xsmall()
+                            .tooltip("Delete Channel")
+                            .on_click(cx.listener(move |this, _, _, cx| this.open_delete_channel(&delete, cx))),
+                    ),
+            )
+        })
+}
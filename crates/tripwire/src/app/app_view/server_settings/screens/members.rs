@@ -1,29 +1,636 @@
-use gpui::{div, px, AnyElement, Context, IntoElement, ParentElement, Styled, Window};
-use gpui_component::{v_flex, ActiveTheme as _, StyledExt};
+use std::collections::HashSet;
+use std::time::SystemTime;
 
+use gpui::{
+    div, prelude::FluentBuilder as _, px, AnyElement, App, Context, Div, InteractiveElement, IntoElement,
+    ParentElement, SharedString, Styled, WeakEntity, Window,
+};
+use gpui_component::{
+    h_flex, v_flex, ActiveTheme as _, Disableable as _, Sizable as _, StyledExt,
+    avatar::Avatar,
+    button::{Button, ButtonVariants},
+    checkbox::Checkbox,
+    input::Input,
+    menu::{DropdownMenu as _, PopupMenuItem},
+    table::{Column, ColumnSort, Table, TableDelegate, TableState},
+};
+
+use super::roles::role_color;
 use crate::app::TripwireApp;
+use crate::app::members::{MemberDialog, MemberDialogKind};
+use crate::members::{self, BAN_DELETE_WINDOWS, JoinedWithin, MemberFilter, MemberRow, MemberSort, ModerationAction, TIMEOUT_DURATIONS};
+use crate::models::{Role, Server};
+use crate::permissions::{self, Permissions};
+
+/// The member table: a pick box, then name, roles, join date and a button
+/// to manage the member.
+pub(crate) struct MemberTable {
+    app: WeakEntity<TripwireApp>,
+    /// The server's roles, highest first, to sort by.
+    roles: Vec<Role>,
+    rows: Vec<MemberRow>,
+    picked: HashSet<String>,
+    /// The sorted column's sort and whether it's descending, if any.
+    sort: Option<(MemberSort, bool)>,
+    now: SystemTime,
+}
+
+impl MemberTable {
+    pub fn new(app: WeakEntity<TripwireApp>) -> Self {
+        Self {
+            app,
+            roles: Vec::new(),
+            rows: Vec::new(),
+            picked: HashSet::new(),
+            sort: None,
+            now: SystemTime::now(),
+        }
+    }
+
+    /// List `server`'s members matching `filter`, keeping the sort. Picks
+    /// not listed any more are dropped.
+    pub fn list(&mut self, server: &Server, filter: &MemberFilter, now: SystemTime) {
+        let (sort, descending) = self.sort.unwrap_or_default();
+        self.roles = server.roles.clone();
+        self.rows = members::member_rows(server, filter, sort, descending, now);
+        self.picked.retain(|id| self.rows.iter().any(|row| row.user.id == *id));
+        self.now = now;
+    }
+
+    /// IDs of the picked members, in the order listed.
+    pub fn picked(&self) -> Vec<String> {
+        self.rows
+            .iter()
+            .filter(|row| self.picked.contains(&row.user.id))
+            .map(|row| row.user.id.clone())
+            .collect()
+    }
+
+    pub fn set_picked(&mut self, user_id: &str, picked: bool) {
+        if picked {
+            self.picked.insert(user_id.to_string());
+        } else {
+            self.picked.remove(user_id);
+        }
+    }
+
+    pub fn pick_all(&mut self, picked: bool) {
+        self.picked.clear();
+        if picked {
+            self.picked.extend(self.rows.iter().map(|row| row.user.id.clone()));
+        }
+    }
+
+    fn column_sort(col_ix: usize) -> Option<MemberSort> {
+        match col_ix {
+            1 => Some(MemberSort::Name),
+            2 => Some(MemberSort::TopRole),
+            3 => Some(MemberSort::Joined),
+            _ => None,
+        }
+    }
+}
+
+impl TableDelegate for MemberTable {
+    fn columns_count(&self, _: &App) -> usize {
+        5
+    }
+
+    fn rows_count(&self, _: &App) -> usize {
+        self.rows.len()
+    }
+
+    fn column(&self, col_ix: usize, _: &App) -> Column {
+        let column = match col_ix {
+            0 => Column::new("pick", "").width(px(40.)).resizable(false),
+            1 => Column::new("name", "Member").width(px(220.)),
+            2 => Column::new("roles", "Roles").width(px(180.)),
+            3 => Column::new("joined", "Joined").width(px(120.)),
+            _ => Column::new("actions", "").width(px(80.)).resizable(false),
+        };
+        match Self::column_sort(col_ix) {
+            Some(sort) => match self.sort {
+                Some((sorted, false)) if sorted == sort => column.ascending(),
+                Some((sorted, true)) if sorted == sort => column.descending(),
+                _ => column.sortable(),
+            },
+            None => column,
+        }
+    }
+
+    fn perform_sort(&mut self, col_ix: usize, sort: ColumnSort, _: &mut Window, _: &mut Context<TableState<Self>>) {
+        self.sort = Self::column_sort(col_ix).and_then(|by| match sort {
+            ColumnSort::Ascending => Some((by, false)),
+            ColumnSort::Descending => Some((by, true)),
+            ColumnSort::Default => None,
+        });
+        let (sort, descending) = self.sort.unwrap_or_default();
+        members::sort_rows(&mut self.rows, &self.roles, sort, descending);
+    }
+
+    fn render_th(&mut self, col_ix: usize, _: &mut Window, cx: &mut Context<TableState<Self>>) -> impl IntoElement {
+        if col_ix != 0 {
+            return div().size_full().child(self.column(col_ix, cx).name.clone()).into_any_element();
+        }
+        let all = !self.rows.is_empty() && self.picked.len() == self.rows.len();
+        let app = self.app.clone();
+        Checkbox::new("member-pick-all")
+            .checked(all)
+            .disabled(self.rows.is_empty())
+            .on_click(move |checked, _, cx| {
+                app.update(cx, |app, cx| app.pick_all_members(*checked, cx)).ok();
+            })
+            .into_any_element()
+    }
+
+    fn render_td(
+        &mut self,
+        row_ix: usize,
+        col_ix: usize,
+        _: &mut Window,
+        cx: &mut Context<TableState<Self>>,
+    ) -> impl IntoElement {
+        let Some(row) = self.rows.get(row_ix) else {
+            return div().into_any_element();
+        };
+        let user_id = row.user.id.clone();
+        match col_ix {
+            0 => {
+                let app = self.app.clone();
+                Checkbox::new(("member-pick", row_ix))
+                    .checked(self.picked.contains(&user_id))
+                    .on_click(move |checked, _, cx| {
+                        app.update(cx, |app, cx| app.set_member_picked(&user_id, *checked, cx)).ok();
+                    })
+                    .into_any_element()
+            }
+            1 => h_flex()
+                .gap_2()
+                .items_center()
+                .overflow_hidden()
+                .child(Avatar::new().name(row.user.username.clone()).xsmall())
+                .child(
+                    v_flex()
+                        .overflow_hidden()
+                        .child(div().text_sm().text_color(cx.theme().foreground).truncate().child(row.display_name().to_string()))
+                        .child(div().text_xs().text_color(cx.theme().muted_foreground).truncate().child(row.user.tag())),
+                )
+                .when(row.info.is_timed_out(self.now), |this| {
+                    this.child(
+                        div()
+                            .px_1()
+                            .rounded(cx.theme().radius)
+                            .bg(cx.theme().warning)
+                            .text_xs()
+                            .text_color(cx.theme().warning_foreground)
+                            .child("Timed out"),
+                    )
+                })
+                .into_any_element(),
+            2 => h_flex()
+                .gap_1()
+                .overflow_hidden()
+                .children(row.roles.iter().take(2).map(|role| role_chip(role, cx)))
+                .when(row.roles.len() > 2, |this| {
+                    this.child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(format!("+{}", row.roles.len() - 2)),
+                    )
+                })
+                .into_any_element(),
+            3 => div()
+                .text_sm()
+                .text_color(cx.theme().muted_foreground)
+                .child(
+                    row.info
+                        .joined_at
+                        .map(|joined| members::format_age(self.now.duration_since(joined).unwrap_or_default()))
+                        .unwrap_or_else(|| "Unknown".to_string()),
+                )
+                .into_any_element(),
+            _ => {
+                let app = self.app.clone();
+                Button::new(("member-manage", row_ix))
+                    .label("Manage")
+                    .ghost()
+                    .xsmall()
+                    .on_click(move |_, window, cx| {
+                        app.update(cx, |app, cx| {
+                            app.open_member_dialog(MemberDialogKind::Edit, vec![user_id.clone()], window, cx)
+                        })
+                        .ok();
+                    })
+                    .into_any_element()
+            }
+        }
+    }
+
+    fn render_empty(&mut self, _: &mut Window, cx: &mut Context<TableState<Self>>) -> impl IntoElement {
+        h_flex()
+            .size_full()
+            .justify_center()
+            .text_sm()
+            .text_color(cx.theme().muted_foreground)
+            .child("No members match these filters.")
+    }
+}
+
+fn role_chip(role: &Role, cx: &App) -> Div {
+    h_flex()
+        .gap_1()
+        .items_center()
+        .px_1p5()
+        .rounded(cx.theme().radius)
+        .border_1()
+        .border_color(cx.theme().border)
+        .child(div().size(px(8.0)).rounded_full().bg(role_color(role)))
+        .child(div().text_xs().text_color(cx.theme().foreground).truncate().child(role.name.clone()))
+}
 
 pub fn render(app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
-    let members = app.active_server().map(|s| s.members.clone()).unwrap_or_default();
-    
+    let (Some(manager), Some(server), Some(me)) = (
+        app.member_manager.as_ref(),
+        app.active_server(),
+        app.auth.current_user.as_ref(),
+    ) else {
+        return div()
+            .text_sm()
+            .text_color(cx.theme().muted_foreground)
+            .child("Members can't be managed right now.")
+            .into_any_element();
+    };
+    let granted = permissions::server_permissions(server, &me.id);
+    let table = manager.table.read(cx).delegate();
+    let (listed, picked) = (table.rows_count(cx), table.picked());
+    let entity = cx.entity();
+
+    let role_label = manager
+        .filter
+        .role_id
+        .as_ref()
+        .and_then(|id| server.roles.iter().find(|role| role.id == *id))
+        .map_or("All roles".to_string(), |role| role.name.clone());
+    let role_filter = manager.filter.role_id.clone();
+    let roles: Vec<(String, String)> = server.roles.iter().map(|role| (role.id.clone(), role.name.clone())).collect();
+    let joined = manager.filter.joined;
+
     v_flex()
-        .gap_6()
-        .max_w(px(700.0))
+        .gap_4()
         .child(
+            h_flex()
+                .gap_2()
+                .child(div().w(px(240.0)).child(Input::new(&manager.search_input).small()))
+                .child(
+                    Button::new("member-filter-role")
+                        .label(role_label)
+                        .outline()
+                        .small()
+                        .dropdown_menu({
+                            let app = entity.clone();
+                            move |menu, _, _| {
+                                let pick = |role_id: Option<String>| {
+                                    let app = app.clone();
+                                    move |_: &gpui::ClickEvent, _: &mut Window, cx: &mut App| {
+                                        app.update(cx, |app, cx| app.set_member_role_filter(role_id.clone(), cx));
+                                    }
+                                };
+                                let mut menu = menu.item(
+                                    PopupMenuItem::new("All roles")
+                                        .checked(role_filter.is_none())
+                                        .on_click(pick(None)),
+                                );
+                                for (id, name) in &roles {
+                                    menu = menu.item(
+                                        PopupMenuItem::new(name.clone())
+                                            .checked(role_filter.as_ref() == Some(id))
+                                            .on_click(pick(Some(id.clone()))),
+                                    );
+                                }
+                                menu
+                            }
+                        }),
+                )
+                .child(
+                    Button::new("member-filter-joined")
+                        .label(if joined == JoinedWithin::Any { "Joined any time" } else { joined.label() })
+                        .outline()
+                        .small()
+                        .dropdown_menu({
+                            let app = entity.clone();
+                            move |mut menu, _, _| {
+                                for choice in JoinedWithin::ALL {
+                                    let app = app.clone();
+                                    menu = menu.item(
+                                        PopupMenuItem::new(choice.label())
+                                            .checked(choice == joined)
+                                            .on_click(move |_, _, cx| {
+                                                app.update(cx, |app, cx| app.set_member_joined_filter(choice, cx));
+                                            }),
+                                    );
+                                }
+                                menu
+                            }
+                        }),
+                )
+                .child(div().flex_1())
+                .child(
+                    div()
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(format!("{listed} of {}", server.members.len())),
+                ),
+        )
+        .when(!picked.is_empty(), |this| this.child(render_bulk_bar(picked, granted, cx)))
+        .child(
+            div()
+                .h(px(400.0))
+                .child(Table::new(&manager.table).stripe(true).bordered(true)),
+        )
+        .into_any_element()
+}
+
+/// Actions on every picked member.
+fn render_bulk_bar(picked: Vec<String>, granted: Permissions, cx: &mut Context<TripwireApp>) -> Div {
+    let action = |id: &'static str, kind: MemberDialogKind, required: Permissions| {
+        let picked = picked.clone();
+        Button::new(id)
+            .label(kind.title())
+            .small()
+            .disabled(!granted.contains(required))
+            .on_click(cx.listener(move |this, _, window, cx| {
+                this.open_member_dialog(kind, picked.clone(), window, cx);
+            }))
+    };
+
+    h_flex()
+        .gap_2()
+        .items_center()
+        .px_3()
+        .py_2()
+        .rounded(cx.theme().radius)
+        .bg(cx.theme().muted)
+        .child(
+            div()
+                .flex_1()
+                .text_sm()
+                .font_semibold()
+                .text_color(cx.theme().foreground)
+                .child(format!("{} selected", picked.len())),
+        )
+        .child(action("member-bulk-add-role", MemberDialogKind::Role { add: true }, Permissions::MANAGE_ROLES).outline())
+        .child(action("member-bulk-remove-role", MemberDialogKind::Role { add: false }, Permissions::MANAGE_ROLES).outline())
+        .child(action("member-bulk-timeout", MemberDialogKind::Timeout, Permissions::MODERATE_MEMBERS).outline())
+        .child(action("member-bulk-kick", MemberDialogKind::Kick, Permissions::KICK_MEMBERS).danger())
+        .child(action("member-bulk-ban", MemberDialogKind::Ban, Permissions::BAN_MEMBERS).danger())
+        .child(
+            Button::new("member-bulk-clear")
+                .label("Clear")
+                .ghost()
+                .small()
+                .on_click(cx.listener(|this, _, _, cx| this.pick_all_members(false, cx))),
+        )
+}
+
+/// The open member dialog, over the whole of server settings.
+pub fn render_member_dialog(app: &TripwireApp, cx: &mut Context<TripwireApp>) -> Option<AnyElement> {
+    let dialog = app.member_manager.as_ref()?.dialog.as_ref()?;
+    let server = app.active_server()?;
+    let me = app.auth.current_user.as_ref()?;
+    let subject = match dialog.targets.as_slice() {
+        [user_id] => server
+            .members
+            .iter()
+            .find(|user| user.id == *user_id)
+            .map_or_else(|| user_id.clone(), |user| user.tag()),
+        targets => format!("{} members", targets.len()),
+    };
+    let destructive = matches!(dialog.kind, MemberDialogKind::Kick | MemberDialogKind::Ban | MemberDialogKind::Timeout);
+    let ready = !matches!(dialog.kind, MemberDialogKind::Role { .. }) || !dialog.roles.is_empty();
+
+    Some(
+        div()
+            .absolute()
+            .inset_0()
+            .flex()
+            .items_center()
+            .justify_center()
+            .bg(gpui::rgba(0x00000099))
+            .on_mouse_down(
+                gpui::MouseButton::Left,
+                cx.listener(|this, _, _, cx| {
+                    cx.stop_propagation();
+                    this.close_member_dialog(cx);
+                }),
+            )
+            .child(
+                v_flex()
+                    .occlude()
+                    .on_mouse_down(gpui::MouseButton::Left, |_, _, cx| cx.stop_propagation())
+                    .w(px(440.0))
+                    .max_h(px(560.0))
+                    .gap_4()
+                    .p_5()
+                    .bg(cx.theme().popover)
+                    .rounded(cx.theme().radius_lg)
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .shadow_lg()
+                    .child(
+                        v_flex()
+                            .child(
+                                div()
+                                    .text_lg()
+                                    .font_semibold()
+                                    .text_color(cx.theme().foreground)
+                                    .child(dialog.kind.title()),
+                            )
+                            .child(div().text_sm().text_color(cx.theme().muted_foreground).child(subject)),
+                    )
+                    .child(render_dialog_body(dialog, server, &me.id, cx))
+                    .child(
+                        v_flex()
+                            .gap_1()
+                            .child(field_label("Reason", cx))
+                            .child(Input::new(&dialog.reason_input).small()),
+                    )
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .justify_end()
+                            .child(
+                                Button::new("member-dialog-cancel")
+                                    .label("Cancel")
+                                    .ghost()
+                                    .disabled(dialog.busy)
+                                    .on_click(cx.listener(|this, _, _, cx| this.close_member_dialog(cx))),
+                            )
+                            .child(
+                                Button::new("member-dialog-confirm")
+                                    .label(if dialog.kind == MemberDialogKind::Edit { "Save" } else { dialog.kind.title() })
+                                    .map(|button| if destructive { button.danger() } else { button.primary() })
+                                    .loading(dialog.busy)
+                                    .disabled(!ready)
+                                    .on_click(cx.listener(|this, _, _, cx| this.confirm_member_dialog(cx))),
+                            ),
+                    ),
+            )
+            .into_any_element(),
+    )
+}
+
+fn render_dialog_body(dialog: &MemberDialog, server: &Server, me: &str, cx: &mut Context<TripwireApp>) -> Div {
+    match dialog.kind {
+        MemberDialogKind::Edit => {
+            let target = dialog.targets.first().map(String::as_str).unwrap_or_default();
+            let timed_out = server
+                .member_info
+                .get(target)
+                .is_some_and(|info| info.is_timed_out(SystemTime::now()));
+            let can = |action: ModerationAction| members::can_moderate(server, me, target, &action);
+            let switch_to = |id: &'static str, kind: MemberDialogKind, allowed: bool| {
+                Button::new(id)
+                    .label(kind.title())
+                    .outline()
+                    .xsmall()
+                    .disabled(!allowed)
+                    .on_click(cx.listener(move |this, _, _, cx| {
+                        this.update_member_dialog(cx, |dialog| dialog.kind = kind);
+                    }))
+            };
+            let timeout = if timed_out {
+                switch_to("member-dialog-untimeout", MemberDialogKind::RemoveTimeout, can(ModerationAction::RemoveTimeout))
+            } else {
+                let until = SystemTime::now();
+                switch_to("member-dialog-timeout", MemberDialogKind::Timeout, can(ModerationAction::Timeout { until }))
+            };
+
             v_flex()
                 .gap_4()
-                .child(div().text_lg().font_weight(gpui::FontWeight::SEMIBOLD).text_color(cx.theme().foreground).child(format!("Members ({})", members.len())))
                 .child(
                     v_flex()
+                        .gap_1()
+                        .child(field_label("Nickname", cx))
+                        .child(
+                            Input::new(&dialog.nickname_input)
+                                .small()
+                                .disabled(!members::can_change_nickname(server, me, target)),
+                        ),
+                )
+                .child(
+                    v_flex()
+                        .gap_1()
+                        .child(field_label("Roles", cx))
+                        .child(
+                            v_flex()
+                                .gap_1()
+                                .max_h(px(160.0))
+                                .overflow_hidden()
+                                .children(server.roles.iter().map(|role| {
+                                    let role_id = role.id.clone();
+                                    Checkbox::new(SharedString::from(format!("member-dialog-role-{}", role.id)))
+                                        .label(role.name.clone())
+                                        .checked(dialog.roles.contains(&role.id))
+                                        .disabled(!members::can_assign_role(server, me, target, &role.id))
+                                        .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                                            let role_id = role_id.clone();
+                                            this.update_member_dialog(cx, |dialog| {
+                                                dialog.roles.retain(|id| *id != role_id);
+                                                if *checked {
+                                                    dialog.roles.push(role_id);
+                                                }
+                                            });
+                                        }))
+                                })),
+                        ),
+                )
+                .child(
+                    h_flex()
                         .gap_2()
-                        .children(members.into_iter().take(10).map(|member| {
-                            div()
-                                .p_3()
-                                .rounded(cx.theme().radius)
-                                .bg(cx.theme().muted)
-                                .child(div().text_sm().text_color(cx.theme().foreground).child(member.username))
-                        }))
+                        .child(timeout)
+                        .child(switch_to("member-dialog-kick", MemberDialogKind::Kick, can(ModerationAction::Kick)))
+                        .child(switch_to(
+                            "member-dialog-ban",
+                            MemberDialogKind::Ban,
+                            can(ModerationAction::Ban { delete_messages: Default::default() }),
+                        )),
                 )
-        )
-        .into_any_element()
+        }
+        MemberDialogKind::Role { .. } => {
+            let choices = server
+                .roles
+                .iter()
+                .filter(|role| permissions::can_manage_role(server, me, &role.id))
+                .map(|role| (role.id.clone(), role.name.clone()))
+                .collect();
+            let chosen = dialog.roles.first().cloned();
+            choice_list("member-dialog-role", "Role", choices, chosen, cx, |dialog, id| dialog.roles = vec![id])
+        }
+        MemberDialogKind::Ban => choice_list(
+            "member-dialog-ban-window",
+            "Delete message history",
+            indexed(&BAN_DELETE_WINDOWS),
+            Some(dialog.ban_window.to_string()),
+            cx,
+            |dialog, ix| dialog.ban_window = ix.parse().unwrap_or_default(),
+        ),
+        MemberDialogKind::Timeout => choice_list(
+            "member-dialog-timeout",
+            "Duration",
+            indexed(&TIMEOUT_DURATIONS),
+            Some(dialog.timeout.to_string()),
+            cx,
+            |dialog, ix| dialog.timeout = ix.parse().unwrap_or_default(),
+        ),
+        MemberDialogKind::Kick => note("They can rejoin with a new invite.", cx),
+        MemberDialogKind::RemoveTimeout => note("They'll be able to talk again right away.", cx),
+    }
+}
+
+/// `choices`' labels keyed by index.
+fn indexed<T>(choices: &[(&str, T)]) -> Vec<(String, String)> {
+    choices
+        .iter()
+        .enumerate()
+        .map(|(ix, (label, _))| (ix.to_string(), label.to_string()))
+        .collect()
+}
+
+/// A column of buttons choosing one of `choices`, by key.
+fn choice_list(
+    id: &'static str,
+    label: &'static str,
+    choices: Vec<(String, String)>,
+    chosen: Option<String>,
+    cx: &mut Context<TripwireApp>,
+    choose: fn(&mut MemberDialog, String),
+) -> Div {
+    v_flex()
+        .gap_1()
+        .child(field_label(label, cx))
+        .children(choices.into_iter().map(|(key, label)| {
+            let selected = chosen.as_ref() == Some(&key);
+            Button::new(SharedString::from(format!("{id}-{key}")))
+                .label(label)
+                .small()
+                .w_full()
+                .map(|button| if selected { button.primary() } else { button.ghost() })
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    let key = key.clone();
+                    this.update_member_dialog(cx, |dialog| choose(dialog, key));
+                }))
+        }))
+}
+
+fn note(text: &'static str, cx: &App) -> Div {
+    div().text_sm().text_color(cx.theme().muted_foreground).child(text)
+}
+
+fn field_label(label: &'static str, cx: &App) -> Div {
+    div()
+        .text_xs()
+        .font_semibold()
+        .text_color(cx.theme().muted_foreground)
+        .child(label.to_uppercase())
 }
@@ -2,3 +2,4 @@ pub mod overview;
 pub mod roles;
 pub mod channels;
 pub mod members;
+pub mod audit_log;
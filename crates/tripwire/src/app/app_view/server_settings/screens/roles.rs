@@ -1,30 +1,447 @@
-use gpui::{div, px, AnyElement, Context, IntoElement, ParentElement, Styled, Window};
-use gpui_component::{h_flex, v_flex, ActiveTheme as _, StyledExt, button::Button};
+use gpui::{
+    div, prelude::FluentBuilder as _, px, AnyElement, AppContext as _, Context, Div, Hsla, InteractiveElement, IntoElement,
+    ParentElement, Render, SharedString, StatefulInteractiveElement as _, Styled, Window,
+};
+use gpui_component::{
+    h_flex, v_flex, ActiveTheme as _, Colorize as _, Disableable as _, Sizable as _, StyledExt,
+    avatar::Avatar,
+    button::{Button, ButtonVariants},
+    color_picker::ColorPicker,
+    input::Input,
+    switch::Switch,
+    tab::TabBar,
+};
 
 use crate::app::TripwireApp;
+use crate::app::roles::{RoleEditor, RoleTab};
+use crate::models::{Role, User};
+use crate::permissions;
 
-pub fn render(_app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
-    v_flex()
+/// Swatches offered above the full palette.
+const ROLE_COLORS: &[&str] = &[
+    "#1ABC9C", "#2ECC71", "#3498DB", "#9B59B6", "#E91E63", "#F1C40F", "#E67E22", "#E74C3C", "#95A5A6", "#607D8B",
+];
+
+pub(super) fn role_color(role: &Role) -> gpui::Rgba {
+    gpui::rgb(u32::from_str_radix(role.color.trim_start_matches('#'), 16).unwrap_or(0x99AAB5))
+}
+
+/// A role being dragged to a new rank.
+#[derive(Clone)]
+struct DraggedRole {
+    id: String,
+    name: SharedString,
+    color: gpui::Rgba,
+}
+
+impl Render for DraggedRole {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        h_flex()
+            .gap_2()
+            .px_3()
+            .py_1()
+            .rounded(cx.theme().radius)
+            .bg(cx.theme().popover)
+            .border_1()
+            .border_color(cx.theme().border)
+            .shadow_md()
+            .opacity(0.9)
+            .child(div().size(px(10.0)).rounded_full().bg(self.color))
+            .child(div().text_sm().text_color(cx.theme().foreground).child(self.name.clone()))
+    }
+}
+
+pub fn render(app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
+    let Some(editor) = app.role_editor.as_ref() else {
+        return div()
+            .text_sm()
+            .text_color(cx.theme().muted_foreground)
+            .child("Roles can't be edited right now.")
+            .into_any_element();
+    };
+
+    h_flex()
+        .items_start()
         .gap_6()
-        .max_w(px(700.0))
+        .child(render_role_list(editor, cx))
         .child(
             v_flex()
+                .flex_1()
+                .min_w_0()
                 .gap_4()
-                .child(div().text_lg().font_weight(gpui::FontWeight::SEMIBOLD).text_color(cx.theme().foreground).child("Roles"))
+                .map(|this| match editor.selected.as_deref().and_then(|id| editor.draft.role(id)) {
+                    Some(role) => this.child(render_role(app, editor, role, cx)),
+                    None => this.child(
+                        div()
+                            .text_sm()
+                            .text_color(cx.theme().muted_foreground)
+                            .child("Select a role to edit it."),
+                    ),
+                }),
+        )
+        .into_any_element()
+}
+
+fn render_role_list(editor: &RoleEditor, cx: &mut Context<TripwireApp>) -> impl IntoElement {
+    let roles = editor.draft.roles();
+
+    v_flex()
+        .w(px(200.0))
+        .flex_shrink_0()
+        .gap_1()
+        .child(
+            h_flex()
+                .justify_between()
+                .items_center()
+                .pb_2()
+                .child(
+                    div()
+                        .text_xs()
+                        .font_semibold()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(format!("ROLES — {}", roles.len())),
+                )
+                .child(
+                    Button::new("btn-create-role")
+                        .label("Create Role")
+                        .primary()
+                        .xsmall()
+                        .disabled(!editor.draft.can_create())
+                        .on_click(cx.listener(|this, _, window, cx| this.create_role(window, cx))),
+                ),
+        )
+        .child(
+            div()
+                .text_xs()
+                .text_color(cx.theme().muted_foreground)
+                .pb_2()
+                .child("Members use the color of their highest role. Drag to reorder."),
+        )
+        .children(roles.iter().enumerate().map(|(ix, role)| {
+            let selected = editor.selected.as_deref() == Some(role.id.as_str());
+            let editable = editor.draft.can_edit(&role.id);
+            let id = role.id.clone();
+            let dragged = DraggedRole {
+                id: role.id.clone(),
+                name: role.name.clone().into(),
+                color: role_color(role),
+            };
+
+            h_flex()
+                .id(SharedString::from(format!("role-{}", role.id)))
+                .gap_2()
+                .items_center()
+                .px_2()
+                .py_1()
+                .rounded(cx.theme().radius)
+                .cursor_pointer()
+                .when(selected, |this| this.bg(cx.theme().accent))
+                .hover(|s| s.bg(cx.theme().accent))
+                .on_click(cx.listener(move |this, _, window, cx| this.select_role(id.clone(), window, cx)))
+                .child(div().size(px(10.0)).flex_shrink_0().rounded_full().bg(role_color(role)))
                 .child(
                     div()
-                        .p_4()
-                        .rounded(cx.theme().radius_lg)
-                        .bg(cx.theme().muted)
-                        .border_1()
-                        .border_color(cx.theme().border)
-                        .child(
-                            v_flex()
+                        .flex_1()
+                        .min_w_0()
+                        .overflow_hidden()
+                        .text_ellipsis()
+                        .text_sm()
+                        .text_color(if editable { cx.theme().foreground } else { cx.theme().muted_foreground })
+                        .child(role.name.clone()),
+                )
+                .when(editable, |this| {
+                    // Dropping a role here moves it to this rank.
+                    this.on_drag(dragged, |drag, _, _, cx| {
+                        cx.stop_propagation();
+                        cx.new(|_| drag.clone())
+                    })
+                    .drag_over::<DraggedRole>(|this, _, _, cx| this.border_t_2().border_color(cx.theme().drag_border))
+                    .on_drop(cx.listener(move |this, drag: &DraggedRole, _, cx| {
+                        this.move_role(&drag.id, ix, cx);
+                    }))
+                })
+        }))
+}
+
+fn render_role(
+    app: &TripwireApp,
+    editor: &RoleEditor,
+    role: &Role,
+    cx: &mut Context<TripwireApp>,
+) -> impl IntoElement {
+    let editable = editor.draft.can_edit(&role.id);
+    let selected_ix = RoleTab::ALL.iter().position(|tab| *tab == editor.tab).unwrap_or(0);
+
+    v_flex()
+        .gap_4()
+        .child(
+            div()
+                .text_lg()
+                .font_weight(gpui::FontWeight::SEMIBOLD)
+                .text_color(cx.theme().foreground)
+                .child(format!("Edit Role — {}", role.name)),
+        )
+        .when(!editable, |this| {
+            this.child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child("You can only edit roles below your highest role."),
+            )
+        })
+        .child(
+            TabBar::new("role-tabs")
+                .underline()
+                .selected_index(selected_ix)
+                .on_click(cx.listener(|this, ix: &usize, _, cx| {
+                    this.set_role_tab(RoleTab::ALL[*ix], cx);
+                }))
+                .children(RoleTab::ALL.iter().map(|tab| tab.label())),
+        )
+        .child(match editor.tab {
+            RoleTab::Display => render_display_tab(editor, role, editable, cx).into_any_element(),
+            RoleTab::Permissions => render_permissions_tab(editor, role, editable, cx).into_any_element(),
+            RoleTab::Members => render_members_tab(app, editor, role, editable, cx).into_any_element(),
+        })
+}
+
+fn render_display_tab(
+    editor: &RoleEditor,
+    role: &Role,
+    editable: bool,
+    cx: &mut Context<TripwireApp>,
+) -> impl IntoElement {
+    let featured: Vec<Hsla> = ROLE_COLORS.iter().filter_map(|hex| Hsla::parse_hex(hex).ok()).collect();
+
+    v_flex()
+        .gap_4()
+        .child(
+            v_flex()
+                .gap_2()
+                .child(field_label("Role Name", cx))
+                .child(Input::new(&editor.name_input).disabled(!editable)),
+        )
+        .child(
+            v_flex()
+                .gap_2()
+                .child(field_label("Role Color", cx))
+                .map(|this| {
+                    if editable {
+                        this.child(ColorPicker::new(&editor.color_picker).featured_colors(featured))
+                    } else {
+                        this.child(
+                            h_flex()
                                 .gap_2()
-                                .child(div().text_sm().text_color(cx.theme().foreground).child("Create and manage roles for your server"))
-                                .child(Button::new("btn-create-role").label("Create Role"))
+                                .items_center()
+                                .child(div().size(px(24.0)).rounded(cx.theme().radius).bg(role_color(role)))
+                                .child(div().text_sm().text_color(cx.theme().muted_foreground).child(role.color.clone())),
                         )
+                    }
+                }),
+        )
+        .child(toggle_row(
+            "switch-role-hoist",
+            "Display separately",
+            "List online members with this role under it in the member list.",
+            role.hoist,
+            editable,
+            cx,
+            |role, on| role.hoist = on,
+        ))
+        .child(toggle_row(
+            "switch-role-mentionable",
+            "Allow anyone to @mention this role",
+            "Members without \"Mention @everyone and All Roles\" can still mention it.",
+            role.mentionable,
+            editable,
+            cx,
+            |role, on| role.mentionable = on,
+        ))
+        .child(
+            h_flex().pt_2().child(
+                Button::new("btn-delete-role")
+                    .label("Delete Role")
+                    .danger()
+                    .small()
+                    .disabled(!editable)
+                    .on_click(cx.listener(|this, _, window, cx| this.delete_selected_role(window, cx))),
+            ),
+        )
+}
+
+fn render_permissions_tab(
+    editor: &RoleEditor,
+    role: &Role,
+    editable: bool,
+    cx: &mut Context<TripwireApp>,
+) -> impl IntoElement {
+    v_flex()
+        .gap_6()
+        .children(permissions::GROUPS.iter().map(|(group, infos)| {
+            v_flex()
+                .gap_1()
+                .child(
+                    div()
+                        .text_xs()
+                        .font_semibold()
+                        .text_color(cx.theme().muted_foreground)
+                        .child(group.to_uppercase()),
                 )
+                .children(infos.iter().map(|info| {
+                    let flag = info.flag;
+                    let grantable = editable && editor.draft.can_grant(flag);
+                    toggle_row(
+                        SharedString::from(format!("switch-perm-{}", flag.bits())),
+                        info.name,
+                        info.description,
+                        role.permissions.contains(flag),
+                        grantable,
+                        cx,
+                        move |role, on| role.permissions.set(flag, on),
+                    )
+                }))
+        }))
+}
+
+fn render_members_tab(
+    app: &TripwireApp,
+    editor: &RoleEditor,
+    role: &Role,
+    editable: bool,
+    cx: &mut Context<TripwireApp>,
+) -> impl IntoElement {
+    let members = app.active_server().map(|s| s.members.clone()).unwrap_or_default();
+    let holders = editor.draft.holders(&role.id);
+    let (with, without): (Vec<_>, Vec<_>) = members.iter().partition(|u| holders.contains(&u.id));
+
+    v_flex()
+        .gap_4()
+        .child(
+            v_flex()
+                .gap_1()
+                .child(field_label(&format!("Members with this role — {}", with.len()), cx))
+                .when(with.is_empty(), |this| {
+                    this.child(div().text_sm().text_color(cx.theme().muted_foreground).child("No one has this role yet."))
+                })
+                .children(with.into_iter().map(|user| member_row(user, true, editable, cx))),
+        )
+        .when(editable && !without.is_empty(), |this| {
+            this.child(
+                v_flex()
+                    .gap_1()
+                    .child(field_label("Add Members", cx))
+                    .children(without.into_iter().map(|user| member_row(user, false, editable, cx))),
+            )
+        })
+}
+
+fn member_row(user: &User, holds: bool, editable: bool, cx: &mut Context<TripwireApp>) -> Div {
+    let user_id = user.id.clone();
+    h_flex()
+        .gap_2()
+        .items_center()
+        .px_2()
+        .py_1()
+        .rounded(cx.theme().radius)
+        .hover(|s| s.bg(cx.theme().accent))
+        .child(Avatar::new().name(user.username.clone()).xsmall())
+        .child(div().flex_1().text_sm().text_color(cx.theme().foreground).child(user.username.clone()))
+        .child(
+            Button::new(SharedString::from(format!("btn-role-member-{}", user.id)))
+                .label(if holds { "Remove" } else { "Add" })
+                .when(holds, |this| this.ghost())
+                .when(!holds, |this| this.outline())
+                .xsmall()
+                .disabled(!editable)
+                .on_click(cx.listener(move |this, _, _, cx| this.set_role_holder(&user_id, !holds, cx))),
+        )
+}
+
+/// The bar at the bottom of server settings while roles have unsaved
+/// changes.
+pub fn render_unsaved_bar(app: &TripwireApp, cx: &mut Context<TripwireApp>) -> Option<AnyElement> {
+    let editor = app.role_editor.as_ref().filter(|editor| editor.draft.is_dirty())?;
+
+    Some(
+        h_flex()
+            .flex_shrink_0()
+            .mx_6()
+            .mb_4()
+            .px_4()
+            .py_2()
+            .gap_3()
+            .items_center()
+            .rounded(cx.theme().radius_lg)
+            .shadow_lg()
+            .bg(if editor.nag { cx.theme().danger } else { cx.theme().popover })
+            .border_1()
+            .border_color(cx.theme().border)
+            .child(
+                div()
+                    .flex_1()
+                    .text_sm()
+                    .font_medium()
+                    .text_color(if editor.nag { cx.theme().danger_foreground } else { cx.theme().foreground })
+                    .child("Careful — you have unsaved changes!"),
+            )
+            .child(
+                Button::new("btn-reset-roles")
+                    .label("Reset")
+                    .ghost()
+                    .small()
+                    .disabled(editor.saving)
+                    .on_click(cx.listener(|this, _, window, cx| this.reset_roles(window, cx))),
+            )
+            .child(
+                Button::new("btn-save-roles")
+                    .label("Save Changes")
+                    .success()
+                    .small()
+                    .loading(editor.saving)
+                    .on_click(cx.listener(|this, _, _, cx| this.save_roles(cx))),
+            )
+            .into_any_element(),
+    )
+}
+
+fn field_label(label: &str, cx: &mut Context<TripwireApp>) -> impl IntoElement {
+    div()
+        .text_xs()
+        .font_semibold()
+        .text_color(cx.theme().muted_foreground)
+        .child(label.to_uppercase())
+}
+
+/// A setting of the selected role, switched on or off with `set`.
+fn toggle_row(
+    id: impl Into<gpui::ElementId>,
+    name: &'static str,
+    description: &'static str,
+    checked: bool,
+    enabled: bool,
+    cx: &mut Context<TripwireApp>,
+    set: impl Fn(&mut Role, bool) + 'static,
+) -> Div {
+    h_flex()
+        .justify_between()
+        .items_center()
+        .gap_4()
+        .py_3()
+        .border_b_1()
+        .border_color(cx.theme().border)
+        .child(
+            v_flex()
+                .gap_1()
+                .child(div().text_sm().font_weight(gpui::FontWeight::MEDIUM).text_color(cx.theme().foreground).child(name))
+                .child(div().text_xs().text_color(cx.theme().muted_foreground).child(description)),
+        )
+        .child(
+            Switch::new(id)
+                .checked(checked)
+                .disabled(!enabled)
+                .on_click(cx.listener(move |this, checked: &bool, _, cx| {
+                    let checked = *checked;
+                    this.update_selected_role(cx, |role| set(role, checked));
+                })),
         )
-        .into_any_element()
 }
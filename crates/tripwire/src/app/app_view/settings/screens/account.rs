@@ -1,11 +1,13 @@
 use gpui::{div, px, AnyElement, Context, IntoElement, ParentElement, Styled, Window};
-use gpui_component::{h_flex, v_flex, ActiveTheme as _, Sizable as _, StyledExt, avatar::Avatar, button::{Button, ButtonVariants}};
+use gpui_component::{h_flex, v_flex, ActiveTheme as _, Disableable as _, Sizable as _, StyledExt, avatar::Avatar, button::{Button, ButtonVariants}, clipboard::Clipboard, input::{Input, OtpInput}};
 use gpui::prelude::FluentBuilder as _;
 
 use crate::app::TripwireApp;
+use crate::app::two_factor::{MfaSetup, MfaSetupStep};
 
 pub fn render(app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
     let user = app.auth.current_user.as_ref();
+    let mfa_enabled = app.auth.mfa_enabled();
     
     v_flex()
         .gap_6()
@@ -100,11 +102,147 @@ pub fn render(app: &TripwireApp, _window: &mut Window, cx: &mut Context<Tripwire
                                     v_flex()
                                         .gap_1()
                                         .child(div().text_sm().font_weight(gpui::FontWeight::MEDIUM).text_color(cx.theme().foreground).child("Two-Factor Authentication"))
-                                        .child(div().text_xs().text_color(cx.theme().muted_foreground).child("Add extra security to your account"))
+                                        .child(div().text_xs().text_color(cx.theme().muted_foreground).child(if mfa_enabled {
+                                            "Enabled — logging in asks for a code from your authenticator app"
+                                        } else {
+                                            "Add extra security to your account"
+                                        }))
                                 )
-                                .child(Button::new("btn-setup-2fa").label("Enable").ghost().with_size(gpui_component::Size::Small))
+                                .when(app.mfa_setup.is_none(), |this| {
+                                    this.child(if mfa_enabled {
+                                        Button::new("btn-disable-2fa").label("Disable").danger().with_size(gpui_component::Size::Small)
+                                            .on_click(cx.listener(|this, _, _, cx| { this.begin_mfa_disable(cx); }))
+                                    } else {
+                                        Button::new("btn-setup-2fa").label("Enable").ghost().with_size(gpui_component::Size::Small)
+                                            .on_click(cx.listener(|this, _, _, cx| { this.begin_mfa_setup(cx); }))
+                                    })
+                                })
                         )
+                        .when_some(app.mfa_setup.as_ref(), |this, setup| this.child(render_two_factor_setup(app, setup, cx)))
                 )
         )
         .into_any_element()
 }
+
+/// Two-factor setup panel under the 2FA row: the secret to add to an
+/// authenticator app and the code that confirms it, the one-time list of
+/// backup codes, or the code needed to turn it off.
+fn render_two_factor_setup(app: &TripwireApp, setup: &MfaSetup, cx: &mut Context<TripwireApp>) -> AnyElement {
+    let muted_foreground = cx.theme().muted_foreground;
+    let hint = move |text: &'static str| div().text_sm().text_color(muted_foreground).child(text);
+
+    let body = match &setup.step {
+        MfaSetupStep::Starting => v_flex().child(hint("Generating a secret…")),
+        MfaSetupStep::Verifying(enrollment) => v_flex()
+            .gap_3()
+            .child(hint("1. Add this key to your authenticator app, or open the setup link on a device that has it."))
+            .child(secret_row("clip-2fa-secret", "KEY", enrollment.secret.clone(), cx))
+            .child(secret_row("clip-2fa-uri", "SETUP LINK", enrollment.uri.clone(), cx))
+            .child(hint("2. Enter the 6-digit code it shows to confirm."))
+            .child(OtpInput::new(&app.otp_input).groups(2).disabled(setup.pending))
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(Button::new("btn-activate-2fa").label("Activate").primary().with_size(gpui_component::Size::Small).loading(setup.pending).disabled(setup.pending)
+                        .on_click(cx.listener(|this, _, window, cx| { this.submit_mfa_setup_code(window, cx); })))
+                    .child(Button::new("btn-cancel-2fa").label("Cancel").ghost().with_size(gpui_component::Size::Small)
+                        .on_click(cx.listener(|this, _, _, cx| { this.close_mfa_setup(cx); })))
+            ),
+        MfaSetupStep::BackupCodes(codes) => v_flex()
+            .gap_3()
+            .child(hint("Two-factor authentication is on. Save these backup codes somewhere safe — each one lets you log in once if you lose your device. They won't be shown again."))
+            .child(
+                h_flex()
+                    .flex_wrap()
+                    .gap_2()
+                    .children(codes.iter().map(|code| {
+                        div()
+                            .w(px(120.0))
+                            .px_2()
+                            .py_1()
+                            .rounded(cx.theme().radius)
+                            .bg(cx.theme().background)
+                            .font_family("monospace")
+                            .text_sm()
+                            .text_color(cx.theme().foreground)
+                            .child(code.clone())
+                    }))
+            )
+            .child(
+                h_flex()
+                    .gap_2()
+                    .items_center()
+                    .child(Clipboard::new("clip-2fa-backup-codes").value(codes.join("\n")))
+                    .child(div().text_xs().text_color(cx.theme().muted_foreground).child("Copy all"))
+            )
+            .child(
+                h_flex().child(Button::new("btn-done-2fa").label("Done").primary().with_size(gpui_component::Size::Small)
+                    .on_click(cx.listener(|this, _, _, cx| { this.close_mfa_setup(cx); })))
+            ),
+        MfaSetupStep::Disabling => v_flex()
+            .gap_3()
+            .child(hint(if app.use_backup_code {
+                "Enter one of your backup codes to turn off two-factor authentication."
+            } else {
+                "Enter the code from your authenticator app to turn off two-factor authentication."
+            }))
+            .map(|this| {
+                if app.use_backup_code {
+                    this.child(Input::new(&app.backup_code_input).max_w(px(240.0)))
+                } else {
+                    this.child(OtpInput::new(&app.otp_input).groups(2).disabled(setup.pending))
+                }
+            })
+            .child(
+                h_flex()
+                    .gap_2()
+                    .child(Button::new("btn-confirm-disable-2fa").label("Disable").danger().with_size(gpui_component::Size::Small).loading(setup.pending).disabled(setup.pending)
+                        .on_click(cx.listener(|this, _, window, cx| { this.submit_mfa_setup_code(window, cx); })))
+                    .child(Button::new("btn-cancel-2fa").label("Cancel").ghost().with_size(gpui_component::Size::Small)
+                        .on_click(cx.listener(|this, _, _, cx| { this.close_mfa_setup(cx); })))
+                    .child(Button::new("btn-toggle-2fa-backup").label(if app.use_backup_code { "Use authenticator app" } else { "Use a backup code" }).link().with_size(gpui_component::Size::Small)
+                        .on_click(cx.listener(|this, _, window, cx| { this.toggle_backup_code(window, cx); })))
+            ),
+    };
+
+    v_flex()
+        .gap_3()
+        .p_4()
+        .rounded(cx.theme().radius_lg)
+        .bg(cx.theme().muted)
+        .border_1()
+        .border_color(cx.theme().border)
+        .child(body)
+        .when_some(setup.error.as_ref(), |this, err| {
+            this.child(div().text_sm().text_color(cx.theme().danger).child(err.to_string()))
+        })
+        .into_any_element()
+}
+
+/// A labelled value the user copies into their authenticator app.
+fn secret_row(id: &'static str, label: &'static str, value: String, cx: &mut Context<TripwireApp>) -> impl IntoElement {
+    v_flex()
+        .gap_1()
+        .child(div().text_xs().font_weight(gpui::FontWeight::SEMIBOLD).text_color(cx.theme().muted_foreground).child(label))
+        .child(
+            h_flex()
+                .gap_2()
+                .items_center()
+                .child(
+                    div()
+                        .flex_1()
+                        .min_w_0()
+                        .px_2()
+                        .py_1()
+                        .rounded(cx.theme().radius)
+                        .bg(cx.theme().background)
+                        .font_family("monospace")
+                        .text_sm()
+                        .text_color(cx.theme().foreground)
+                        .overflow_hidden()
+                        .text_ellipsis()
+                        .child(value.clone())
+                )
+                .child(Clipboard::new(id).value(value))
+        )
+}
@@ -1,9 +1,29 @@
-use gpui::{div, px, AnyElement, Context, IntoElement, ParentElement, Styled, Window};
-use gpui_component::{h_flex, v_flex, ActiveTheme as _, Sizable as _, StyledExt, button::{Button, ButtonVariants}};
+use std::time::SystemTime;
+
+use gpui::{div, px, AnyElement, App, Context, IntoElement, ParentElement, SharedString, Styled, Window};
+use gpui_component::{h_flex, v_flex, ActiveTheme as _, Disableable as _, Selectable as _, Sizable as _, StyledExt, button::{Button, ButtonGroup, ButtonVariants}, switch::Switch};
 
 use crate::app::TripwireApp;
+use crate::notifications::NotifyLevel;
+
+pub fn render(app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
+    let settings = &app.notification_settings;
+    let has_bus = app.notification_bus.is_some();
+    let now = SystemTime::now();
+
+    let mut overrides: Vec<_> = settings
+        .overrides()
+        .map(|(scope, o)| {
+            let mut state: Vec<String> = o.level.map(|l| l.label().to_string()).into_iter().collect();
+            if let Some(mute) = o.mute.filter(|m| m.is_active(now)) {
+                state.push(mute.label(now));
+            }
+            (app.scope_name(&scope), state.join(" · "), scope)
+        })
+        .filter(|(_, state, _)| !state.is_empty())
+        .collect();
+    overrides.sort_by(|a, b| a.0.cmp(&b.0));
 
-pub fn render(_app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
     v_flex()
         .gap_6()
         .max_w(px(700.0))
@@ -14,50 +34,123 @@ pub fn render(_app: &TripwireApp, _window: &mut Window, cx: &mut Context<Tripwir
                 .child(
                     v_flex()
                         .gap_2()
-                        .child(
-                            h_flex()
-                                .justify_between()
-                                .items_center()
-                                .py_3()
-                                .border_b_1()
-                                .border_color(cx.theme().border)
-                                .child(
-                                    v_flex()
-                                        .gap_1()
-                                        .child(div().text_sm().font_weight(gpui::FontWeight::MEDIUM).text_color(cx.theme().foreground).child("Enable Notifications"))
-                                        .child(div().text_xs().text_color(cx.theme().muted_foreground).child("Receive notifications for new messages"))
-                                )
-                                .child(Button::new("btn-notifs").label("On").ghost().with_size(gpui_component::Size::Small))
-                        )
-                        .child(
-                            h_flex()
-                                .justify_between()
-                                .items_center()
-                                .py_3()
-                                .border_b_1()
-                                .border_color(cx.theme().border)
-                                .child(
-                                    v_flex()
-                                        .gap_1()
-                                        .child(div().text_sm().font_weight(gpui::FontWeight::MEDIUM).text_color(cx.theme().foreground).child("Sound"))
-                                        .child(div().text_xs().text_color(cx.theme().muted_foreground).child("Play a sound for notifications"))
-                                )
-                                .child(Button::new("btn-sound").label("On").ghost().with_size(gpui_component::Size::Small))
-                        )
-                        .child(
-                            h_flex()
-                                .justify_between()
-                                .items_center()
-                                .py_3()
-                                .child(
-                                    v_flex()
-                                        .gap_1()
-                                        .child(div().text_sm().font_weight(gpui::FontWeight::MEDIUM).text_color(cx.theme().foreground).child("Show Badge"))
-                                        .child(div().text_xs().text_color(cx.theme().muted_foreground).child("Display unread count on app icon"))
+                        .child(setting_row(
+                            "Enable Notifications",
+                            "Receive notifications for new messages",
+                            Switch::new("switch-notifs")
+                                .checked(settings.enabled)
+                                .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                    this.notification_settings.enabled = *checked;
+                                    this.notification_settings.save();
+                                    cx.notify();
+                                })),
+                            cx,
+                        ))
+                        .child(setting_row(
+                            "Desktop Notifications",
+                            if has_bus {
+                                "Show notifications on the desktop while Tripwire isn't focused"
+                            } else {
+                                "Desktop notifications aren't supported on this system"
+                            },
+                            Switch::new("switch-desktop-notifs")
+                                .checked(settings.desktop && has_bus)
+                                .disabled(!has_bus)
+                                .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                    this.notification_settings.desktop = *checked;
+                                    this.notification_settings.save();
+                                    cx.notify();
+                                })),
+                            cx,
+                        ))
+                        .child(setting_row(
+                            "In-App Notifications",
+                            "Show a toast for new messages while Tripwire is focused",
+                            Switch::new("switch-toasts")
+                                .checked(settings.toasts)
+                                .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                    this.notification_settings.toasts = *checked;
+                                    this.notification_settings.save();
+                                    cx.notify();
+                                })),
+                            cx,
+                        ))
+                        .child(setting_row(
+                            "Sound",
+                            "Play a sound for notifications",
+                            Switch::new("switch-sound")
+                                .checked(settings.sound)
+                                .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                    this.notification_settings.sound = *checked;
+                                    this.notification_settings.save();
+                                    cx.notify();
+                                })),
+                            cx,
+                        ))
+                        .child(setting_row(
+                            "Server Notifications",
+                            "Default for servers and channels without their own setting. Direct messages always notify",
+                            NotifyLevel::ALL.into_iter().enumerate().fold(ButtonGroup::new("group-default-level").outline().small(), |group, (ix, level)| {
+                                group.child(
+                                    Button::new(("btn-default-level", ix))
+                                        .label(level.label())
+                                        .selected(settings.default_level == level)
+                                        .on_click(cx.listener(move |this, _, _, cx| {
+                                            this.notification_settings.default_level = level;
+                                            this.notification_settings.save();
+                                            cx.notify();
+                                        })),
                                 )
-                                .child(Button::new("btn-badge").label("On").ghost().with_size(gpui_component::Size::Small))
-                        )
+                            }),
+                            cx,
+                        ))
                 )
         )
+        .child(
+            v_flex()
+                .gap_4()
+                .child(
+                    v_flex()
+                        .gap_1()
+                        .child(div().text_lg().font_weight(gpui::FontWeight::SEMIBOLD).text_color(cx.theme().foreground).child("Overrides"))
+                        .child(div().text_xs().text_color(cx.theme().muted_foreground).child("Right-click a server, channel or direct message to change its notifications"))
+                )
+                .child(if overrides.is_empty() {
+                    div().text_sm().text_color(cx.theme().muted_foreground).child("No overrides").into_any_element()
+                } else {
+                    v_flex()
+                        .gap_2()
+                        .children(overrides.into_iter().enumerate().map(|(ix, (name, state, scope))| {
+                            setting_row(
+                                name,
+                                state,
+                                Button::new(("btn-reset-notifs", ix))
+                                    .label("Reset")
+                                    .ghost()
+                                    .with_size(gpui_component::Size::Small)
+                                    .on_click(cx.listener(move |this, _, _, cx| this.reset_notifications(&scope, cx))),
+                                cx,
+                            )
+                        }))
+                        .into_any_element()
+                })
+        )
         .into_any_element()
 }
+
+fn setting_row(title: impl Into<SharedString>, description: impl Into<SharedString>, control: impl IntoElement, cx: &App) -> impl IntoElement {
+    h_flex()
+        .justify_between()
+        .items_center()
+        .gap_4()
+        .py_3()
+        .border_b_1()
+        .border_color(cx.theme().border)
+        .child(
+            v_flex()
+                .gap_1()
+                .child(div().text_sm().font_weight(gpui::FontWeight::MEDIUM).text_color(cx.theme().foreground).child(title.into()))
+                .child(div().text_xs().text_color(cx.theme().muted_foreground).child(description.into()))
+        )
+        .child(control)
+}
@@ -1,9 +1,9 @@
 use gpui::{div, px, AnyElement, Context, IntoElement, ParentElement, Styled, Window};
-use gpui_component::{h_flex, v_flex, ActiveTheme as _, Sizable as _, StyledExt, button::{Button, ButtonVariants}};
+use gpui_component::{h_flex, v_flex, ActiveTheme as _, Sizable as _, StyledExt, button::{Button, ButtonVariants}, switch::Switch};
 
 use crate::app::TripwireApp;
 
-pub fn render(_app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
+pub fn render(app: &TripwireApp, _window: &mut Window, cx: &mut Context<TripwireApp>) -> AnyElement {
     v_flex()
         .gap_6()
         .max_w(px(700.0))
@@ -49,6 +49,8 @@ pub fn render(_app: &TripwireApp, _window: &mut Window, cx: &mut Context<Tripwir
                                 .justify_between()
                                 .items_center()
                                 .py_3()
+                                .border_b_1()
+                                .border_color(cx.theme().border)
                                 .child(
                                     v_flex()
                                         .gap_1()
@@ -57,6 +59,27 @@ pub fn render(_app: &TripwireApp, _window: &mut Window, cx: &mut Context<Tripwir
                                 )
                                 .child(Button::new("btn-typing").label("On").ghost().with_size(gpui_component::Size::Small))
                         )
+                        .child(
+                            h_flex()
+                                .justify_between()
+                                .items_center()
+                                .gap_4()
+                                .py_3()
+                                .child(
+                                    v_flex()
+                                        .gap_1()
+                                        .child(div().text_sm().font_weight(gpui::FontWeight::MEDIUM).text_color(cx.theme().foreground).child("Link Previews"))
+                                        .child(div().text_xs().text_color(cx.theme().muted_foreground).child("Fetch previews of links in messages and the images of embeds. Those sites can see your IP address when they're fetched"))
+                                )
+                                .child(
+                                    Switch::new("switch-link-previews")
+                                        .checked(app.privacy_settings.link_previews)
+                                        .on_click(cx.listener(|this, checked: &bool, _, cx| {
+                                            this.privacy_settings.set_link_previews(*checked);
+                                            cx.notify();
+                                        }))
+                                )
+                        )
                 )
         )
         .into_any_element()
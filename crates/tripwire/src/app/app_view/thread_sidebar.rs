@@ -1,19 +1,21 @@
 //! Thread sidebar - shows a message thread with original message and replies
 
 use gpui::{
-    div, px, AnyElement, Context, IntoElement,
+    div, prelude::FluentBuilder as _, px, AnyElement, Context, IntoElement,
     InteractiveElement, MouseButton, ParentElement, Styled, Window,
 };
 use gpui_component::{
-    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _,
+    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _, WindowExt as _,
     avatar::Avatar,
     button::{Button, ButtonVariants},
-    input::Input,
+    input::{Input, Paste},
+    notification::Notification,
     scroll::ScrollableElement as _,
     StyledExt,
 };
 
 use crate::app::TripwireApp;
+use crate::permissions::Permissions;
 
 impl TripwireApp {
     pub(crate) fn render_thread_sidebar(
@@ -193,6 +195,10 @@ impl TripwireApp {
                     // Thread reply input
                     self.render_thread_composer(window, cx)
                 )
+                .map(|this| {
+                    let composer = self.thread_composer();
+                    self.drop_zone(this, "drop-thread", composer, "the thread".to_string(), cx)
+                })
                 .into_any_element()
         )
     }
@@ -344,12 +350,15 @@ impl TripwireApp {
                                     .child(message.timestamp.clone())
                             )
                     )
-                    .child(
-                        div()
-                            .text_sm()
-                            .text_color(cx.theme().foreground)
-                            .child(message.content.clone())
-                    )
+                    .when(!message.content.is_empty(), |this| {
+                        this.child(
+                            div()
+                                .text_sm()
+                                .text_color(cx.theme().foreground)
+                                .child(message.content.clone())
+                        )
+                    })
+                    .children(message.attachments.iter().map(|a| self.render_attachment(&message.id, a, cx)))
             )
             .into_any_element()
     }
@@ -359,14 +368,46 @@ impl TripwireApp {
         _window: &mut Window,
         cx: &mut Context<Self>,
     ) -> AnyElement {
+        if !self.can(Permissions::SEND_MESSAGES) {
+            return div()
+                .flex_shrink_0()
+                .p_4()
+                .border_t_1()
+                .border_color(cx.theme().border)
+                .child(
+                    div()
+                        .px_3()
+                        .py_3()
+                        .rounded(cx.theme().radius)
+                        .bg(cx.theme().muted)
+                        .text_sm()
+                        .text_color(cx.theme().muted_foreground)
+                        .child("You don't have permission to reply in this thread."),
+                )
+                .into_any_element();
+        }
+        let composer = self.thread_composer();
+        let pending_uploads = self.render_pending_uploads(composer.as_ref(), cx);
         v_flex()
             .flex_shrink_0()
             .p_4()
             .border_t_1()
             .border_color(cx.theme().border)
+            // Pasted images are attached; anything else goes to the input
+            .capture_action(cx.listener({
+                let composer = composer.clone();
+                move |this, _: &Paste, window, cx| {
+                    if let Some(composer) = composer.clone()
+                        && this.paste_image(composer, window, cx)
+                    {
+                        cx.stop_propagation();
+                    }
+                }
+            }))
             .child(
                 v_flex()
                     .gap_2()
+                    .children(pending_uploads)
                     .child(
                         div()
                             .px_3()
@@ -381,7 +422,19 @@ impl TripwireApp {
                     )
                     .child(
                         h_flex()
-                            .justify_end()
+                            .justify_between()
+                            .child(
+                                Button::new("btn-attach-thread")
+                                    .icon(IconName::Plus)
+                                    .ghost()
+                                    .small()
+                                    .tooltip("Attach Files")
+                                    .on_click(cx.listener(move |this, _, window, cx| {
+                                        if let Some(composer) = composer.clone() {
+                                            this.attach_files(composer, window, cx);
+                                        }
+                                    }))
+                            )
                             .child(
                                 Button::new("btn-send-thread-reply")
                                     .label("Reply")
@@ -399,11 +452,18 @@ impl TripwireApp {
     // ── Thread actions ────────────────────────────────────────────────────
     
     pub(crate) fn open_thread(&mut self, message_id: String, cx: &mut Context<Self>) {
+        if !self
+            .get_message_by_id(&message_id)
+            .is_some_and(|message| self.can_open_thread(message))
+        {
+            return;
+        }
         self.open_thread_id = Some(message_id.clone());
         
-        // Load thread messages from mock data if not already loaded
+        // Replies live only in the local cache until the backend has threads
         if !self.thread_messages.contains_key(&message_id) {
-            self.thread_messages.insert(message_id, vec![]);
+            let replies = self.stored_history(&message_id).unwrap_or_default();
+            self.thread_messages.insert(message_id, replies);
         }
         
         cx.notify();
@@ -435,12 +495,36 @@ impl TripwireApp {
             Some(id) => id.clone(),
             None => return,
         };
+        // The first reply is what starts the thread.
+        let allowed = self.can(Permissions::SEND_MESSAGES)
+            && self
+                .get_message_by_id(&thread_id)
+                .is_some_and(|parent| self.can_open_thread(parent));
+        if !allowed {
+            return;
+        }
         
         let content = self.thread_input.read(cx).text().to_string();
-        if content.trim().is_empty() {
+        let Some(composer) = self.thread_composer() else {
+            return;
+        };
+        if content.trim().is_empty() && self.pending_uploads_for(&composer).next().is_none() {
+            return;
+        }
+        let attachments = match self.take_attachments(&composer) {
+            Ok(attachments) => attachments,
+            Err(reason) => {
+                window.push_notification(Notification::warning(reason), cx);
+                return;
+            }
+        };
+        if content.trim().is_empty() && attachments.is_empty() {
+            cx.notify();
             return;
         }
         
+        let (content, mentioned_users) = self.serialize_mentions(&content);
+
         // Create new message in thread
         let new_message = crate::models::Message {
             id: format!("thread-msg-{}", uuid::Uuid::new_v4()),
@@ -454,10 +538,12 @@ impl TripwireApp {
             timestamp: "Just now".to_string(),
             edited: false,
             edited_timestamp: None,
-            attachment: None,
+            revisions: Vec::new(),
+            attachments,
+            embeds: Vec::new(),
             reactions: std::collections::HashMap::new(),
             reply_to: None,
-            mentioned_users: vec![],
+            mentioned_users,
             pinned: false,
             thread_id: Some(thread_id.clone()),
             thread_count: 0,
@@ -467,12 +553,16 @@ impl TripwireApp {
         self.thread_messages
             .entry(thread_id.clone())
             .or_insert_with(Vec::new)
-            .push(new_message);
+            .push(new_message.clone());
+        self.store_messages(&thread_id, &[new_message]);
         
         // Update thread count on parent message
         if let Some(parent) = self.get_message_by_id_mut(&thread_id) {
             parent.thread_count += 1;
         }
+        if let Some(target) = self.active_target() {
+            self.store_message(&target, &thread_id);
+        }
         
         // Clear input
         self.thread_input.update(cx, |input, cx| {
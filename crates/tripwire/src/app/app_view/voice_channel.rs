@@ -5,7 +5,7 @@ use gpui::{
     InteractiveElement, ParentElement, Styled, Window,
 };
 use gpui_component::{
-    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _,
+    h_flex, v_flex, ActiveTheme as _, Disableable as _, IconName, Sizable as _,
     avatar::Avatar,
     button::{Button, ButtonVariants},
     input::Input,
@@ -14,6 +14,7 @@ use gpui_component::{
 };
 
 use crate::app::TripwireApp;
+use crate::permissions::Permissions;
 
 #[derive(Debug, Clone)]
 pub struct VoiceParticipant {
@@ -57,6 +58,7 @@ impl TripwireApp {
         let participants = self.active_channel()
             .map(|ch| ch.voice_participants.clone())
             .unwrap_or_default();
+        let can_connect = self.can(Permissions::CONNECT);
         
         v_flex()
             .flex_1()
@@ -125,6 +127,7 @@ impl TripwireApp {
                             .label("Join Voice Channel")
                             .icon(IconName::Plus)
                             .primary()
+                            .disabled(!can_connect)
                             .on_click(cx.listener(|this, _, window, cx| {
                                 if let Some(channel) = this.active_channel().cloned() {
                                     if let Some(server) = this.active_server().cloned() {
@@ -133,6 +136,14 @@ impl TripwireApp {
                                 }
                             }))
                     )
+                    .when(!can_connect, |this| {
+                        this.child(
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("You don't have permission to join this channel.")
+                        )
+                    })
             )
             .into_any_element()
     }
@@ -0,0 +1,85 @@
+//! Audit log viewer — the Audit Log screen of server settings. The table
+//! fetches a page at a time as it's scrolled; changing the filters starts
+//! it over. A page that fails to load can be retried.
+
+use gpui::{AppContext as _, Context, Entity, Window};
+use gpui_component::table::TableState;
+
+use crate::app::TripwireApp;
+use crate::app::app_view::server_settings::screens::audit_log::AuditLogTable;
+use crate::audit_log::{AuditAction, AuditLogQuery};
+use crate::permissions::{self, Permissions};
+
+pub(crate) struct AuditLogViewer {
+    pub server_id: String,
+    pub table: Entity<TableState<AuditLogTable>>,
+    /// Whose actions, and which, are shown.
+    pub filter: AuditLogQuery,
+}
+
+impl TripwireApp {
+    /// Start showing the active server's audit log, if the user may see it.
+    pub(crate) fn open_audit_log(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let (Some(server), Some(user)) = (self.active_server(), self.auth.current_user.as_ref()) else {
+            return;
+        };
+        if !permissions::server_permissions(server, &user.id).contains(Permissions::VIEW_AUDIT_LOG) {
+            return;
+        }
+        let server_id = server.id.clone();
+        let delegate = AuditLogTable::new(cx.entity().downgrade(), self.backend.clone(), server);
+        let table = cx.new(|cx| {
+            TableState::new(delegate, window, cx)
+                .row_selectable(false)
+                .col_movable(false)
+                .sortable(false)
+        });
+        self.audit_log = Some(AuditLogViewer {
+            server_id,
+            table,
+            filter: AuditLogQuery::default(),
+        });
+        self.reload_audit_log(cx);
+    }
+
+    pub(crate) fn set_audit_log_user(&mut self, user_id: Option<String>, cx: &mut Context<Self>) {
+        if let Some(viewer) = &mut self.audit_log {
+            viewer.filter.user_id = user_id;
+            self.reload_audit_log(cx);
+        }
+    }
+
+    pub(crate) fn set_audit_log_action(&mut self, action: Option<AuditAction>, cx: &mut Context<Self>) {
+        if let Some(viewer) = &mut self.audit_log {
+            viewer.filter.action = action;
+            self.reload_audit_log(cx);
+        }
+    }
+
+    /// Fetch the page that failed to load again.
+    pub(crate) fn retry_audit_log(&mut self, cx: &mut Context<Self>) {
+        if let Some(viewer) = &self.audit_log {
+            viewer.table.update(cx, |table, cx| {
+                table.delegate_mut().retry(cx);
+                cx.notify();
+            });
+            cx.notify();
+        }
+    }
+
+    /// Drop what's listed and fetch the newest page matching the filter.
+    fn reload_audit_log(&mut self, cx: &mut Context<Self>) {
+        let Some(viewer) = &self.audit_log else {
+            return;
+        };
+        let Some(server) = self.servers.iter().find(|s| s.id == viewer.server_id) else {
+            return;
+        };
+        let filter = viewer.filter.clone();
+        viewer.table.update(cx, |table, cx| {
+            table.delegate_mut().reset(server, filter, cx);
+            cx.notify();
+        });
+        cx.notify();
+    }
+}
@@ -3,17 +3,19 @@
 //! Implements `TripwireApp::render_auth`.
 
 use gpui::{
-    AnyElement, AppContext as _, Context, InteractiveElement as _, IntoElement as _,
+    AnyElement, Context, FontWeight, InteractiveElement as _, IntoElement as _,
     ParentElement as _, Styled as _, Window, div, prelude::FluentBuilder as _, px,
 };
 use gpui_component::{
-    ActiveTheme as _, StyledExt as _,
+    ActiveTheme as _, Disableable as _, StyledExt as _,
     button::{Button, ButtonVariants as _},
     h_flex, v_flex,
-    input::Input,
+    input::{Input, OtpInput},
 };
 
 use crate::app::TripwireApp;
+use crate::auth_state::AuthState;
+use crate::backend::AuthError;
 
 impl TripwireApp {
     /// Render the full auth / login screen.
@@ -22,8 +24,21 @@ impl TripwireApp {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> AnyElement {
-        let has_error = self.auth.login_error.is_some();
-        let error_msg = self.auth.login_error.clone().unwrap_or_default();
+        // Bad credentials are flagged on the field labels instead
+        let error_msg = self
+            .auth
+            .login_error
+            .as_ref()
+            .filter(|err| !matches!(err, AuthError::InvalidCredentials))
+            .map(|err| err.to_string());
+        let challenged = self.auth.mfa_ticket.is_some();
+        let subtitle = if challenged {
+            "Enter the code from your authenticator app to finish logging in."
+        } else if self.adding_account {
+            "Log in to add another account."
+        } else {
+            "Welcome back! We're so excited to see you again."
+        };
 
         v_flex()
             .size_full()
@@ -55,18 +70,21 @@ impl TripwireApp {
                                 div()
                                     .text_sm()
                                     .text_color(cx.theme().muted_foreground)
-                                    .child("Welcome back! We're so excited to see you again."),
+                                    .child(subtitle),
                             ),
                     )
                     // ── Fields ──────────────────────────────────────────────
-                    .child(
-                        v_flex()
-                            .gap_4()
-                            .child(self.email_field(cx))
-                            .child(self.password_field(cx)),
-                    )
+                    .when(!challenged, |this| {
+                        this.child(
+                            v_flex()
+                                .gap_4()
+                                .child(self.email_field(cx))
+                                .child(self.password_field(cx)),
+                        )
+                    })
+                    .when(challenged, |this| this.child(self.mfa_field(cx)))
                     // ── Error message ────────────────────────────────────────
-                    .when(has_error, |this| {
+                    .when_some(error_msg, |this, error_msg| {
                         this.child(
                             div()
                                 .text_sm()
@@ -75,41 +93,101 @@ impl TripwireApp {
                         )
                     })
                     // ── Actions ──────────────────────────────────────────────
-                    .child(self.login_actions(cx))
-                    // ── Divider ─────────────────────────────────────────────
-                    .child(
-                        h_flex()
-                            .items_center()
-                            .gap_3()
-                            .child(div().flex_1().h(px(1.)).bg(cx.theme().border))
+                    .when(challenged, |this| this.child(self.mfa_actions(cx)))
+                    .when(!challenged, |this| {
+                        this.child(self.login_actions(cx))
+                            // ── Divider ─────────────────────────────────────
                             .child(
-                                div()
-                                    .text_xs()
-                                    .text_color(cx.theme().muted_foreground)
-                                    .child("OR"),
+                                h_flex()
+                                    .items_center()
+                                    .gap_3()
+                                    .child(div().flex_1().h(px(1.)).bg(cx.theme().border))
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(cx.theme().muted_foreground)
+                                            .child("OR"),
+                                    )
+                                    .child(div().flex_1().h(px(1.)).bg(cx.theme().border)),
                             )
-                            .child(div().flex_1().h(px(1.)).bg(cx.theme().border)),
-                    )
-                    // ── Dev bypass ──────────────────────────────────────────
-                    .child(self.bypass_button(cx)),
+                            // ── Dev bypass ──────────────────────────────────
+                            .child(self.bypass_button(cx))
+                    }),
             )
             .into_any_element()
     }
 
+    /// Field label, turned red with the reason appended when the server
+    /// rejected the credentials.
+    fn field_label(&self, label: &'static str, cx: &mut Context<Self>) -> impl gpui::IntoElement {
+        let invalid = matches!(self.auth.login_error, Some(AuthError::InvalidCredentials));
+
+        h_flex()
+            .gap_1()
+            .text_xs()
+            .font_semibold()
+            .text_color(if invalid {
+                cx.theme().danger
+            } else {
+                cx.theme().muted_foreground
+            })
+            .child(label)
+            .when(invalid, |this| {
+                this.child(
+                    div()
+                        .font_weight(FontWeight::NORMAL)
+                        .child(format!("- {}", AuthError::InvalidCredentials)),
+                )
+            })
+    }
+
     fn email_field(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
         v_flex()
             .gap_1()
-            .child(
-                div()
-                    .text_xs()
-                    .font_semibold()
-                    .text_color(cx.theme().muted_foreground)
-                    .child("EMAIL OR PHONE NUMBER"),
-            )
+            .child(self.field_label("EMAIL OR PHONE NUMBER", cx))
             .child(Input::new(&self.email_input))
     }
 
     fn password_field(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
+        v_flex()
+            .gap_1()
+            .child(self.field_label("PASSWORD", cx))
+            .child(Input::new(&self.password_input).mask_toggle())
+    }
+
+    fn login_actions(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
+        let pending = self.auth.login_pending;
+
+        v_flex()
+            .gap_2()
+            .child(
+                Button::new("btn-login")
+                    .label(if pending { "Logging In…" } else { "Log In" })
+                    .primary()
+                    .w_full()
+                    .loading(pending)
+                    .disabled(pending)
+                    .on_click(cx.listener(|this, _, _window, cx| this.submit_login(cx))),
+            )
+            .when(self.adding_account, |this| {
+                this.child(
+                    Button::new("btn-cancel-add-account")
+                        .label("Back to Tripwire")
+                        .ghost()
+                        .w_full()
+                        .on_click(cx.listener(|this, _, _window, cx| this.cancel_add_account(cx))),
+                )
+            })
+    }
+
+    /// Second login step: the authenticator code, or a backup code instead.
+    fn mfa_field(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
+        let label = if self.use_backup_code {
+            "BACKUP CODE"
+        } else {
+            "AUTHENTICATION CODE"
+        };
+
         v_flex()
             .gap_1()
             .child(
@@ -117,24 +195,55 @@ impl TripwireApp {
                     .text_xs()
                     .font_semibold()
                     .text_color(cx.theme().muted_foreground)
-                    .child("PASSWORD"),
+                    .child(label),
             )
-            .child(Input::new(&self.password_input).mask_toggle())
+            .map(|this| {
+                if self.use_backup_code {
+                    this.child(Input::new(&self.backup_code_input))
+                } else {
+                    this.child(
+                        h_flex().justify_center().child(
+                            OtpInput::new(&self.otp_input)
+                                .groups(2)
+                                .disabled(self.auth.login_pending),
+                        ),
+                    )
+                }
+            })
     }
 
-    fn login_actions(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
-        v_flex().gap_2().child(
-            Button::new("btn-login")
-                .label("Log In")
-                .primary()
-                .w_full()
-                .on_click(cx.listener(|this, _, window, cx| {
-                    let email = this.email_input.read(cx).value().to_string();
-                    let password = this.password_input.read(cx).value().to_string();
-                    this.auth.login(&email, &password);
-                    cx.notify();
-                })),
-        )
+    fn mfa_actions(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
+        let pending = self.auth.login_pending;
+
+        v_flex()
+            .gap_2()
+            .child(
+                Button::new("btn-verify-mfa")
+                    .label(if pending { "Verifying…" } else { "Verify" })
+                    .primary()
+                    .w_full()
+                    .loading(pending)
+                    .disabled(pending)
+                    .on_click(cx.listener(|this, _, window, cx| this.submit_mfa_code(window, cx))),
+            )
+            .child(
+                Button::new("btn-toggle-backup-code")
+                    .label(if self.use_backup_code {
+                        "Use your authenticator app"
+                    } else {
+                        "Use a backup code"
+                    })
+                    .ghost()
+                    .w_full()
+                    .on_click(cx.listener(|this, _, window, cx| this.toggle_backup_code(window, cx))),
+            )
+            .child(
+                Button::new("btn-cancel-mfa")
+                    .label("Back to Login")
+                    .ghost()
+                    .w_full()
+                    .on_click(cx.listener(|this, _, _window, cx| this.cancel_mfa_challenge(cx))),
+            )
     }
 
     fn bypass_button(&self, cx: &mut Context<Self>) -> impl gpui::IntoElement {
@@ -153,8 +262,7 @@ impl TripwireApp {
                     .ghost()
                     .w_full()
                     .on_click(cx.listener(|this, _, _window, cx| {
-                        this.auth.bypass_login();
-                        cx.notify();
+                        this.start_session(AuthState::dev_session(), cx);
                     })),
             )
     }
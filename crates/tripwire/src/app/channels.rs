@@ -0,0 +1,387 @@
+//! Channel management — creating channels and categories, a channel's
+//! settings, dragging channels and categories into a new order, and
+//! deleting them. Each change is saved on its own as a `ChannelEdit`.
+
+use gpui::{AppContext as _, Context, Entity, Window};
+use gpui_component::input::InputState;
+
+use crate::app::TripwireApp;
+use crate::backend::MessageTarget;
+use crate::channels::{self, ChannelEdit, ChannelUpdate};
+use crate::models::{ChannelCategory, ChannelKind, Server};
+use crate::permissions::{OverwriteTarget, PermissionOverwrite, Permissions};
+
+/// What the channel dialog does.
+pub(crate) enum ChannelDialogKind {
+    CreateChannel {
+        /// Name of the category it goes in.
+        category: String,
+        kind: ChannelKind,
+        name_input: Entity<InputState>,
+    },
+    CreateCategory {
+        name_input: Entity<InputState>,
+    },
+    Settings(ChannelSettings),
+    DeleteChannel {
+        channel_id: String,
+    },
+    /// Deletes the channels in it too.
+    DeleteCategory {
+        name: String,
+    },
+}
+
+impl ChannelDialogKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            ChannelDialogKind::CreateChannel { .. } => "Create Channel",
+            ChannelDialogKind::CreateCategory { .. } => "Create Category",
+            ChannelDialogKind::Settings(_) => "Channel Settings",
+            ChannelDialogKind::DeleteChannel { .. } => "Delete Channel",
+            ChannelDialogKind::DeleteCategory { .. } => "Delete Category",
+        }
+    }
+}
+
+/// A channel's settings as they're being edited.
+pub(crate) struct ChannelSettings {
+    pub channel_id: String,
+    pub name_input: Entity<InputState>,
+    pub topic_input: Entity<InputState>,
+    pub slowmode_secs: u32,
+    pub nsfw: bool,
+    pub overwrites: Vec<PermissionOverwrite>,
+    /// Whose overwrite is shown.
+    pub target: OverwriteTarget,
+}
+
+/// How to take back a move shown before it was saved.
+struct Undo {
+    /// The layout the move made.
+    moved: Vec<(String, Vec<String>)>,
+    previous: Vec<ChannelCategory>,
+}
+
+/// Categories by name, with the IDs of their channels, in order.
+fn layout(categories: &[ChannelCategory]) -> Vec<(String, Vec<String>)> {
+    categories
+        .iter()
+        .map(|c| (c.name.clone(), c.channels.iter().map(|c| c.id.clone()).collect()))
+        .collect()
+}
+
+pub(crate) struct ChannelDialog {
+    pub server_id: String,
+    pub kind: ChannelDialogKind,
+    pub busy: bool,
+}
+
+impl TripwireApp {
+    /// Whether the user may create, edit, move and delete channels in the
+    /// active server.
+    pub(crate) fn can_manage_channels(&self) -> bool {
+        self.server_permissions().contains(Permissions::MANAGE_CHANNELS)
+    }
+
+    fn open_channel_dialog(&mut self, kind: ChannelDialogKind, cx: &mut Context<Self>) {
+        let Some(server) = self.active_server().filter(|_| self.can_manage_channels()) else {
+            return;
+        };
+        self.channel_dialog = Some(ChannelDialog {
+            server_id: server.id.clone(),
+            kind,
+            busy: false,
+        });
+        cx.notify();
+    }
+
+    /// Start creating a channel in `category`, or the first category.
+    pub(crate) fn open_create_channel(&mut self, category: Option<String>, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(category) = category.or_else(|| self.active_server()?.categories.first().map(|c| c.name.clone())) else {
+            return;
+        };
+        let name_input = cx.new(|cx| InputState::new(window, cx).placeholder("new-channel"));
+        self.open_channel_dialog(
+            ChannelDialogKind::CreateChannel {
+                category,
+                kind: ChannelKind::Text,
+                name_input,
+            },
+            cx,
+        );
+    }
+
+    pub(crate) fn open_create_category(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let name_input = cx.new(|cx| InputState::new(window, cx).placeholder("New Category"));
+        self.open_channel_dialog(ChannelDialogKind::CreateCategory { name_input }, cx);
+    }
+
+    pub(crate) fn open_channel_settings(&mut self, channel_id: &str, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(channel) = self
+            .active_server()
+            .and_then(|server| server.all_channels().into_iter().find(|c| c.id == channel_id))
+            .cloned()
+        else {
+            return;
+        };
+        let name_input = cx.new(|cx| InputState::new(window, cx).default_value(channel.name.clone()));
+        let topic_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("Let everyone know how to use this channel")
+                .default_value(channel.topic.clone().unwrap_or_default())
+        });
+        self.open_channel_dialog(
+            ChannelDialogKind::Settings(ChannelSettings {
+                channel_id: channel.id,
+                name_input,
+                topic_input,
+                slowmode_secs: channel.slowmode_secs,
+                nsfw: channel.nsfw,
+                overwrites: channel.overwrites,
+                target: OverwriteTarget::Everyone,
+            }),
+            cx,
+        );
+    }
+
+    pub(crate) fn open_delete_channel(&mut self, channel_id: &str, cx: &mut Context<Self>) {
+        let channel_id = channel_id.to_string();
+        self.open_channel_dialog(ChannelDialogKind::DeleteChannel { channel_id }, cx);
+    }
+
+    pub(crate) fn open_delete_category(&mut self, name: &str, cx: &mut Context<Self>) {
+        let name = name.to_string();
+        self.open_channel_dialog(ChannelDialogKind::DeleteCategory { name }, cx);
+    }
+
+    pub(crate) fn close_channel_dialog(&mut self, cx: &mut Context<Self>) {
+        if self.channel_dialog.as_ref().is_some_and(|dialog| !dialog.busy) {
+            self.channel_dialog = None;
+            cx.notify();
+        }
+    }
+
+    /// Change what the open channel dialog will do, e.g. the kind of
+    /// channel to create or an overwrite.
+    pub(crate) fn update_channel_dialog(&mut self, cx: &mut Context<Self>, f: impl FnOnce(&mut ChannelDialogKind)) {
+        if let Some(dialog) = self.channel_dialog.as_mut().filter(|dialog| !dialog.busy) {
+            f(&mut dialog.kind);
+            cx.notify();
+        }
+    }
+
+    /// Change the channel settings being edited, if that's the open dialog.
+    pub(crate) fn update_channel_settings(&mut self, cx: &mut Context<Self>, f: impl FnOnce(&mut ChannelSettings)) {
+        self.update_channel_dialog(cx, |kind| {
+            if let ChannelDialogKind::Settings(settings) = kind {
+                f(settings);
+            }
+        });
+    }
+
+    /// Save the change the open channel dialog makes. The dialog stays
+    /// open if that fails.
+    pub(crate) fn confirm_channel_dialog(&mut self, cx: &mut Context<Self>) {
+        let Some(dialog) = &self.channel_dialog else {
+            return;
+        };
+        let Some(server) = self.servers.iter().find(|s| s.id == dialog.server_id) else {
+            return;
+        };
+        if dialog.busy {
+            return;
+        }
+        let edit = match &dialog.kind {
+            ChannelDialogKind::CreateChannel { category, kind, name_input } => {
+                let name = channels::channel_name(&name_input.read(cx).value(), kind);
+                if name.is_empty() {
+                    return;
+                }
+                ChannelEdit::CreateChannel {
+                    category: category.clone(),
+                    name,
+                    kind: kind.clone(),
+                }
+            }
+            ChannelDialogKind::CreateCategory { name_input } => {
+                let name: String = name_input.read(cx).value().trim().chars().take(channels::MAX_NAME_LEN).collect();
+                if name.is_empty() {
+                    return;
+                }
+                if channels::has_category(&server.categories, &name) {
+                    self.show_error(format!("There's already a category called {name}."), cx);
+                    return;
+                }
+                ChannelEdit::CreateCategory { name }
+            }
+            ChannelDialogKind::Settings(settings) => {
+                let Some(channel) = server.all_channels().into_iter().find(|c| c.id == settings.channel_id) else {
+                    return;
+                };
+                let mut edited = channel.clone();
+                let name = channels::channel_name(&settings.name_input.read(cx).value(), &channel.kind);
+                if !name.is_empty() {
+                    edited.name = name;
+                }
+                let topic = settings.topic_input.read(cx).value().trim().to_string();
+                edited.topic = (!topic.is_empty()).then_some(topic);
+                edited.slowmode_secs = settings.slowmode_secs;
+                edited.nsfw = settings.nsfw;
+                edited.overwrites = settings.overwrites.clone();
+                edited.overwrites.retain(|o| !o.allow.is_empty() || !o.deny.is_empty());
+                let update = ChannelUpdate::between(channel, &edited);
+                if update.is_empty() {
+                    self.channel_dialog = None;
+                    cx.notify();
+                    return;
+                }
+                ChannelEdit::UpdateChannel {
+                    channel_id: channel.id.clone(),
+                    update,
+                }
+            }
+            ChannelDialogKind::DeleteChannel { channel_id } => ChannelEdit::DeleteChannel {
+                channel_id: channel_id.clone(),
+            },
+            ChannelDialogKind::DeleteCategory { name } => ChannelEdit::DeleteCategory { name: name.clone() },
+        };
+        let server_id = dialog.server_id.clone();
+        if let Some(dialog) = &mut self.channel_dialog {
+            dialog.busy = true;
+        }
+        self.save_channel_edit(server_id, edit, None, cx);
+    }
+
+    /// Drop `channel_id` at position `to` of the category at `category`.
+    pub(crate) fn move_channel(&mut self, channel_id: &str, category: usize, to: usize, cx: &mut Context<Self>) {
+        let Some(category) = self.active_server().and_then(|s| s.categories.get(category)) else {
+            return;
+        };
+        let edit = ChannelEdit::MoveChannel {
+            channel_id: channel_id.to_string(),
+            category: category.name.clone(),
+            to,
+        };
+        self.reorder_channels(edit, cx);
+    }
+
+    /// Drop the category at `from` at position `to`.
+    pub(crate) fn move_category(&mut self, from: usize, to: usize, cx: &mut Context<Self>) {
+        let Some(category) = self.active_server().and_then(|s| s.categories.get(from)) else {
+            return;
+        };
+        let edit = ChannelEdit::MoveCategory {
+            name: category.name.clone(),
+            to,
+        };
+        self.reorder_channels(edit, cx);
+    }
+
+    /// Make `edit` to the active server's channels straight away, and save
+    /// it; they go back where they were if that fails.
+    fn reorder_channels(&mut self, edit: ChannelEdit, cx: &mut Context<Self>) {
+        if !self.can_manage_channels() {
+            return;
+        }
+        let Some(server) = self.servers.get_mut(self.active_server) else {
+            return;
+        };
+        let previous = server.categories.clone();
+        if edit.apply(&mut server.categories).is_err() || layout(&server.categories) == layout(&previous) {
+            server.categories = previous;
+            return;
+        }
+        let server_id = server.id.clone();
+        let undo = Undo {
+            moved: layout(&server.categories),
+            previous,
+        };
+        cx.notify();
+        self.save_channel_edit(server_id, edit, Some(undo), cx);
+    }
+
+    /// Save `edit` to `server_id`'s channels. Reordering passes how to go
+    /// back on failure; dialogs stay open instead.
+    fn save_channel_edit(&mut self, server_id: String, edit: ChannelEdit, undo: Option<Undo>, cx: &mut Context<Self>) {
+        let request = self.backend.edit_channels(&server_id, edit);
+        cx.notify();
+
+        cx.spawn(async move |this, cx| {
+            let result = request.await;
+            this.update(cx, |this, cx| {
+                match result {
+                    Ok(server) => {
+                        this.channels_saved(server, cx);
+                        if undo.is_none() {
+                            this.channel_dialog = None;
+                        }
+                    }
+                    Err(err) => {
+                        match undo {
+                            // Unless something was saved since, which has
+                            // the layout as the backend has it.
+                            Some(undo) => {
+                                if let Some(server) = this.servers.iter_mut().find(|s| s.id == server_id)
+                                    && layout(&server.categories) == undo.moved
+                                {
+                                    server.categories = undo.previous;
+                                }
+                            }
+                            None => {
+                                if let Some(dialog) = &mut this.channel_dialog {
+                                    dialog.busy = false;
+                                }
+                            }
+                        }
+                        this.show_error(format!("Couldn't save channels: {err:#}"), cx);
+                    }
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Take `server` as the backend now has it, after its channels changed.
+    /// Categories stay collapsed or not as they were, a channel just
+    /// created is opened, and one just deleted is left.
+    fn channels_saved(&mut self, mut server: Server, cx: &mut Context<Self>) {
+        let Some(ix) = self.servers.iter().position(|s| s.id == server.id) else {
+            return;
+        };
+        let existing = &self.servers[ix];
+        for category in &mut server.categories {
+            category.collapsed = existing
+                .categories
+                .iter()
+                .find(|c| c.name == category.name)
+                .is_some_and(|c| c.collapsed);
+        }
+        let created = server
+            .all_channels()
+            .into_iter()
+            .find(|c| channels::find_channel(&existing.categories, &c.id).is_none())
+            .map(|c| c.id.clone());
+        self.servers[ix] = server;
+        self.refresh_mention_directory();
+        if ix != self.active_server {
+            return;
+        }
+
+        let active_gone = self
+            .active_channel_id
+            .as_ref()
+            .is_some_and(|id| channels::find_channel(&self.servers[ix].categories, id).is_none());
+        let open = match created {
+            Some(id) => Some(id),
+            None if active_gone => self.first_visible_channel().map(|c| c.id.clone()),
+            None => return,
+        };
+        if let Some(id) = &open {
+            self.load_history(MessageTarget::Channel(id.clone()), cx);
+        }
+        self.active_channel_id = open;
+    }
+}
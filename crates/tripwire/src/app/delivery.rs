@@ -0,0 +1,182 @@
+//! Message delivery — queues sends in the `Outbox`, retries them with
+//! backoff and swaps the local echo for the server's message on ack.
+
+use std::time::SystemTime;
+
+use anyhow::Result;
+use gpui::Context;
+
+use crate::app::TripwireApp;
+use crate::backend::{self, MessageDraft, MessageTarget};
+use crate::models::Message;
+use crate::outbox::{DeliveryState, Outbox, OutboxEntry};
+
+impl TripwireApp {
+    /// Queue `draft` for `target`, showing it as pending right away.
+    pub(crate) fn enqueue_message(&mut self, target: MessageTarget, mut draft: MessageDraft, cx: &mut Context<Self>) {
+        let Some(author) = self.auth.current_user.clone() else {
+            return;
+        };
+        let nonce = uuid::Uuid::new_v4().to_string();
+        draft.nonce = Some(nonce.clone());
+        let echo = Message {
+            id: nonce.clone(),
+            author,
+            content: draft.content.clone(),
+            timestamp: "Just now".to_string(),
+            edited: false,
+            edited_timestamp: None,
+            revisions: Vec::new(),
+            attachments: draft.attachments.clone(),
+            embeds: Vec::new(),
+            reactions: Default::default(),
+            reply_to: draft.reply_to.clone().map(Box::new),
+            mentioned_users: draft.mentioned_users.clone(),
+            pinned: false,
+            thread_id: None,
+            thread_count: 0,
+            created_at: SystemTime::now(),
+        };
+
+        if let Some(messages) = self.cached_messages_mut(&target) {
+            messages.push(echo.clone());
+        }
+        let entry = OutboxEntry::new(target, draft, echo);
+        self.save_outbox_entry(&entry);
+        self.outbox.push(entry);
+        self.attempt_delivery(nonce, cx);
+    }
+
+    /// Pending or failed, for messages that haven't been acknowledged yet.
+    pub(crate) fn delivery_state(&self, message_id: &str) -> Option<DeliveryState> {
+        self.outbox.state(message_id)
+    }
+
+    /// Send a failed message again.
+    pub(crate) fn retry_message(&mut self, nonce: &str, cx: &mut Context<Self>) {
+        if !self.outbox.reset(nonce) {
+            return;
+        }
+        if let Some(entry) = self.outbox.get(nonce).cloned() {
+            self.save_outbox_entry(&entry);
+        }
+        self.attempt_delivery(nonce.to_string(), cx);
+        cx.notify();
+    }
+
+    /// Give up on an unsent message and remove it from the conversation.
+    pub(crate) fn discard_message(&mut self, nonce: &str, cx: &mut Context<Self>) {
+        let Some(entry) = self.outbox.remove(nonce) else {
+            return;
+        };
+        self.outbox_tasks.remove(nonce);
+        self.remove_outbox_entry(nonce);
+        if let Some(messages) = self.cached_messages_mut(&entry.target) {
+            messages.retain(|m| m.id != nonce);
+        }
+        cx.notify();
+    }
+
+    /// Load the active account's outbox and resume sending what's pending.
+    pub(crate) fn restore_outbox(&mut self, cx: &mut Context<Self>) {
+        self.outbox_tasks.clear();
+        self.outbox = Outbox::new(self.load_outbox_entries());
+        let pending: Vec<String> = self
+            .outbox
+            .entries()
+            .filter(|e| e.state == DeliveryState::Pending)
+            .map(|e| e.nonce.clone())
+            .collect();
+        for nonce in pending {
+            self.attempt_delivery(nonce, cx);
+        }
+    }
+
+    /// Add the local echoes of unsent messages to `target`'s loaded history,
+    /// after the messages from the server.
+    pub(crate) fn append_outbox_echoes(&mut self, target: &MessageTarget) {
+        let echoes: Vec<Message> = self
+            .outbox
+            .entries()
+            .filter(|e| &e.target == target)
+            .map(|e| e.echo.clone())
+            .collect();
+        let Some(messages) = self.cached_messages_mut(target) else {
+            return;
+        };
+        messages.retain(|m| !echoes.iter().any(|echo| echo.id == m.id));
+        messages.extend(echoes);
+    }
+
+    fn attempt_delivery(&mut self, nonce: String, cx: &mut Context<Self>) {
+        let Some(entry) = self.outbox.get(&nonce) else {
+            return;
+        };
+        let request = self.backend.send_message(&entry.target, entry.draft.clone());
+        let task = cx.spawn({
+            let nonce = nonce.clone();
+            async move |this, cx| {
+                let result = request.await;
+                this.update(cx, |this, cx| this.finish_delivery(&nonce, result, cx))
+                    .ok();
+            }
+        });
+        self.outbox_tasks.insert(nonce, task);
+    }
+
+    fn finish_delivery(&mut self, nonce: &str, result: Result<Message>, cx: &mut Context<Self>) {
+        match result {
+            Ok(message) => {
+                let Some(entry) = self.outbox.remove(nonce) else {
+                    return;
+                };
+                self.outbox_tasks.remove(nonce);
+                self.remove_outbox_entry(nonce);
+                self.reconcile_echo(&entry.target, nonce, message);
+            }
+            Err(err) => {
+                eprintln!("Failed to send message: {err:#}");
+                match self.outbox.record_failure(nonce, backend::is_transient(&err)) {
+                    Some(delay) => {
+                        let nonce = nonce.to_string();
+                        let retry = cx.spawn({
+                            let nonce = nonce.clone();
+                            async move |this, cx| {
+                                cx.background_executor().timer(delay).await;
+                                this.update(cx, |this, cx| this.attempt_delivery(nonce, cx))
+                                    .ok();
+                            }
+                        });
+                        self.outbox_tasks.insert(nonce, retry);
+                    }
+                    None => {
+                        self.outbox_tasks.remove(nonce);
+                    }
+                }
+                if let Some(entry) = self.outbox.get(nonce).cloned() {
+                    self.save_outbox_entry(&entry);
+                }
+            }
+        }
+        cx.notify();
+    }
+
+    /// Put the server's copy of a sent message where its echo was. The
+    /// gateway may have delivered it already, in which case the echo just
+    /// goes away.
+    fn reconcile_echo(&mut self, target: &MessageTarget, nonce: &str, message: Message) {
+        let Some(messages) = self.cached_messages_mut(target) else {
+            return;
+        };
+        let already_delivered = messages.iter().any(|m| m.id == message.id);
+        match messages.iter().position(|m| m.id == nonce) {
+            Some(ix) if !already_delivered => messages[ix] = message.clone(),
+            Some(ix) => {
+                messages.remove(ix);
+            }
+            None if !already_delivered => messages.push(message.clone()),
+            None => {}
+        }
+        self.store_messages(target.id(), &[message]);
+    }
+}
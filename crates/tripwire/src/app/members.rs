@@ -0,0 +1,355 @@
+//! Member management — the Members screen of server settings. The table
+//! lists the server's members, filtered and sorted; a dialog edits one
+//! member's nickname and roles, or changes roles of, kicks, bans or times
+//! out everyone picked.
+
+use std::time::SystemTime;
+
+use gpui::{AppContext as _, Context, Entity, Subscription, Window};
+use gpui_component::input::{InputEvent, InputState};
+use gpui_component::table::TableState;
+
+use crate::app::TripwireApp;
+use crate::app::app_view::server_settings::screens::members::MemberTable;
+use crate::backend::{BackendFuture, MemberUpdate};
+use crate::members::{BAN_DELETE_WINDOWS, JoinedWithin, MemberFilter, ModerationAction, TIMEOUT_DURATIONS};
+use crate::models::Server;
+
+/// What the member dialog does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum MemberDialogKind {
+    /// Change one member's nickname and roles.
+    Edit,
+    /// Give the chosen role to everyone picked, or take it away.
+    Role { add: bool },
+    Kick,
+    Ban,
+    Timeout,
+    RemoveTimeout,
+}
+
+impl MemberDialogKind {
+    pub fn title(self) -> &'static str {
+        match self {
+            MemberDialogKind::Edit => "Edit Member",
+            MemberDialogKind::Role { add: true } => "Add Role",
+            MemberDialogKind::Role { add: false } => "Remove Role",
+            MemberDialogKind::Kick => "Kick",
+            MemberDialogKind::Ban => "Ban",
+            MemberDialogKind::Timeout => "Timeout",
+            MemberDialogKind::RemoveTimeout => "Remove Timeout",
+        }
+    }
+}
+
+pub(crate) struct MemberDialog {
+    pub kind: MemberDialogKind,
+    /// IDs of the members acted on.
+    pub targets: Vec<String>,
+    pub reason_input: Entity<InputState>,
+    pub nickname_input: Entity<InputState>,
+    /// For `Edit`, the roles the member will hold; for `Role`, the one
+    /// chosen.
+    pub roles: Vec<String>,
+    /// Index into `BAN_DELETE_WINDOWS`.
+    pub ban_window: usize,
+    /// Index into `TIMEOUT_DURATIONS`.
+    pub timeout: usize,
+    pub busy: bool,
+}
+
+pub(crate) struct MemberManager {
+    pub server_id: String,
+    pub table: Entity<TableState<MemberTable>>,
+    pub search_input: Entity<InputState>,
+    pub filter: MemberFilter,
+    pub dialog: Option<MemberDialog>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl TripwireApp {
+    /// Start managing the active server's members, unless already doing so.
+    pub(crate) fn open_member_manager(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(server) = self.active_server() else {
+            return;
+        };
+        if self.member_manager.as_ref().is_some_and(|manager| manager.server_id == server.id) {
+            return;
+        }
+        let server_id = server.id.clone();
+
+        let app = cx.entity().downgrade();
+        let table = cx.new(|cx| {
+            TableState::new(MemberTable::new(app), window, cx)
+                .row_selectable(false)
+                .col_movable(false)
+        });
+        let search_input = cx.new(|cx| InputState::new(window, cx).placeholder("Search members"));
+        let search_sub = cx.subscribe(&search_input, |this: &mut TripwireApp, input, event: &InputEvent, cx| {
+            if let InputEvent::Change = event {
+                let query = input.read(cx).value().to_string();
+                if let Some(manager) = &mut this.member_manager {
+                    manager.filter.query = query;
+                }
+                this.refresh_member_rows(cx);
+            }
+        });
+
+        self.member_manager = Some(MemberManager {
+            server_id,
+            table,
+            search_input,
+            filter: MemberFilter::default(),
+            dialog: None,
+            _subscriptions: vec![search_sub],
+        });
+        self.refresh_member_rows(cx);
+    }
+
+    /// List the server's members again, e.g. after the filter or the
+    /// server changed. Picked members who are gone are dropped.
+    pub(crate) fn refresh_member_rows(&mut self, cx: &mut Context<Self>) {
+        let Some(manager) = &self.member_manager else {
+            return;
+        };
+        let Some(server) = self.servers.iter().find(|s| s.id == manager.server_id) else {
+            return;
+        };
+        let filter = manager.filter.clone();
+        manager.table.update(cx, |table, cx| {
+            table.delegate_mut().list(server, &filter, SystemTime::now());
+            cx.notify();
+        });
+        cx.notify();
+    }
+
+    pub(crate) fn set_member_role_filter(&mut self, role_id: Option<String>, cx: &mut Context<Self>) {
+        if let Some(manager) = &mut self.member_manager {
+            manager.filter.role_id = role_id;
+            self.refresh_member_rows(cx);
+        }
+    }
+
+    pub(crate) fn set_member_joined_filter(&mut self, joined: JoinedWithin, cx: &mut Context<Self>) {
+        if let Some(manager) = &mut self.member_manager {
+            manager.filter.joined = joined;
+            self.refresh_member_rows(cx);
+        }
+    }
+
+    /// Pick `user_id` for bulk actions, or unpick them.
+    pub(crate) fn set_member_picked(&mut self, user_id: &str, picked: bool, cx: &mut Context<Self>) {
+        if let Some(manager) = &self.member_manager {
+            manager.table.update(cx, |table, cx| {
+                table.delegate_mut().set_picked(user_id, picked);
+                cx.notify();
+            });
+            cx.notify();
+        }
+    }
+
+    /// Pick every member listed, or none.
+    pub(crate) fn pick_all_members(&mut self, picked: bool, cx: &mut Context<Self>) {
+        if let Some(manager) = &self.member_manager {
+            manager.table.update(cx, |table, cx| {
+                table.delegate_mut().pick_all(picked);
+                cx.notify();
+            });
+            cx.notify();
+        }
+    }
+
+    pub(crate) fn open_member_dialog(
+        &mut self,
+        kind: MemberDialogKind,
+        targets: Vec<String>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(manager) = &self.member_manager else {
+            return;
+        };
+        let Some(server) = self.servers.iter().find(|s| s.id == manager.server_id) else {
+            return;
+        };
+        if targets.is_empty() {
+            return;
+        }
+        // Editing starts from what the member has now.
+        let (nickname, roles) = match (kind, targets.as_slice()) {
+            (MemberDialogKind::Edit, [user_id]) => (
+                server
+                    .member_info
+                    .get(user_id)
+                    .and_then(|info| info.nickname.clone())
+                    .unwrap_or_default(),
+                server.member_roles.get(user_id).cloned().unwrap_or_default(),
+            ),
+            _ => (String::new(), Vec::new()),
+        };
+        let reason_input = cx.new(|cx| InputState::new(window, cx).placeholder("Reason (optional)"));
+        let nickname_input = cx.new(|cx| {
+            InputState::new(window, cx)
+                .placeholder("Nickname")
+                .default_value(nickname)
+        });
+
+        if let Some(manager) = &mut self.member_manager {
+            manager.dialog = Some(MemberDialog {
+                kind,
+                targets,
+                reason_input,
+                nickname_input,
+                roles,
+                ban_window: 0,
+                timeout: 0,
+                busy: false,
+            });
+        }
+        cx.notify();
+    }
+
+    pub(crate) fn close_member_dialog(&mut self, cx: &mut Context<Self>) {
+        if let Some(manager) = &mut self.member_manager
+            && manager.dialog.as_ref().is_some_and(|dialog| !dialog.busy)
+        {
+            manager.dialog = None;
+            cx.notify();
+        }
+    }
+
+    /// Change what the open member dialog will do, e.g. from editing a
+    /// member to kicking them.
+    pub(crate) fn update_member_dialog(&mut self, cx: &mut Context<Self>, f: impl FnOnce(&mut MemberDialog)) {
+        if let Some(dialog) = self.member_manager.as_mut().and_then(|manager| manager.dialog.as_mut()) {
+            f(dialog);
+            cx.notify();
+        }
+    }
+
+    /// Carry out the open member dialog, one member at a time. The dialog
+    /// stays open if any of them fails, for just those members, so trying
+    /// again doesn't repeat what worked.
+    pub(crate) fn confirm_member_dialog(&mut self, cx: &mut Context<Self>) {
+        let Some(manager) = &mut self.member_manager else {
+            return;
+        };
+        let Some(server) = self.servers.iter().find(|s| s.id == manager.server_id) else {
+            return;
+        };
+        let Some(dialog) = &mut manager.dialog else {
+            return;
+        };
+        if dialog.busy {
+            return;
+        }
+        let reason = Some(dialog.reason_input.read(cx).value().trim().to_string()).filter(|r| !r.is_empty());
+        let requests: Vec<(String, BackendFuture<Server>)> = dialog
+            .targets
+            .iter()
+            .filter_map(|user_id| {
+                let moderate = |action| self.backend.moderate_member(&server.id, user_id, action, reason.clone());
+                match dialog.kind {
+                    MemberDialogKind::Edit => {
+                        let update = edit_update(server, user_id, dialog, cx);
+                        (update != MemberUpdate::default())
+                            .then(|| self.backend.update_member(&server.id, user_id, update, reason.clone()))
+                    }
+                    MemberDialogKind::Role { add } => {
+                        let role_id = dialog.roles.first()?;
+                        let mut held = server.member_roles.get(user_id).cloned().unwrap_or_default();
+                        if held.contains(role_id) == add {
+                            return None;
+                        }
+                        if add {
+                            held.push(role_id.clone());
+                        } else {
+                            held.retain(|id| id != role_id);
+                        }
+                        let update = MemberUpdate {
+                            roles: Some(held),
+                            ..MemberUpdate::default()
+                        };
+                        Some(self.backend.update_member(&server.id, user_id, update, reason.clone()))
+                    }
+                    MemberDialogKind::Kick => Some(moderate(ModerationAction::Kick)),
+                    MemberDialogKind::Ban => Some(moderate(ModerationAction::Ban {
+                        delete_messages: BAN_DELETE_WINDOWS[dialog.ban_window].1,
+                    })),
+                    MemberDialogKind::Timeout => Some(moderate(ModerationAction::Timeout {
+                        until: SystemTime::now() + TIMEOUT_DURATIONS[dialog.timeout].1,
+                    })),
+                    MemberDialogKind::RemoveTimeout => Some(moderate(ModerationAction::RemoveTimeout)),
+                }
+                .map(|request| (user_id.clone(), request))
+            })
+            .collect();
+        dialog.busy = true;
+        let action = dialog.kind.title();
+        cx.notify();
+
+        cx.spawn(async move |this, cx| {
+            let mut saved = None;
+            let mut failure = None;
+            let count = requests.len();
+            let mut failed = Vec::new();
+            for (user_id, request) in requests {
+                match request.await {
+                    Ok(server) => saved = Some(server),
+                    Err(err) => {
+                        failed.push(user_id);
+                        failure = Some(err);
+                    }
+                }
+            }
+            this.update(cx, |this, cx| {
+                if let Some(server) = saved {
+                    this.member_server_saved(server, cx);
+                }
+                let Some(manager) = &mut this.member_manager else {
+                    return;
+                };
+                match failure {
+                    None => manager.dialog = None,
+                    Some(err) => {
+                        if let Some(dialog) = &mut manager.dialog {
+                            dialog.targets.retain(|user_id| failed.contains(user_id));
+                            dialog.busy = false;
+                        }
+                        let what = if count > 1 {
+                            format!("{action} failed for {} of {count} members", failed.len())
+                        } else {
+                            format!("{action} failed")
+                        };
+                        this.show_error(format!("{what}: {err:#}"), cx);
+                    }
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Take `server` as the backend now has it, after a member changed.
+    fn member_server_saved(&mut self, server: Server, cx: &mut Context<Self>) {
+        if let Some(existing) = self.servers.iter_mut().find(|s| s.id == server.id) {
+            *existing = server;
+        }
+        self.refresh_mention_directory();
+        self.refresh_member_rows(cx);
+    }
+}
+
+/// What the edit dialog changes about `user_id`; nothing unchanged is sent,
+/// so moderators who may only do one of the two can still do that.
+fn edit_update(server: &Server, user_id: &str, dialog: &MemberDialog, cx: &gpui::App) -> MemberUpdate {
+    let nickname = Some(dialog.nickname_input.read(cx).value().trim().to_string()).filter(|n| !n.is_empty());
+    let current = server.member_info.get(user_id).and_then(|info| info.nickname.clone());
+    let held = server.member_roles.get(user_id).cloned().unwrap_or_default();
+    let same_roles = held.len() == dialog.roles.len() && held.iter().all(|id| dialog.roles.contains(id));
+    MemberUpdate {
+        nickname: (nickname != current).then_some(nickname),
+        roles: (!same_roles).then(|| dialog.roles.clone()),
+    }
+}
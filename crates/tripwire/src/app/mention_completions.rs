@@ -0,0 +1,153 @@
+//! Composer completions — `@user`, `@role`, `@everyone`, `#channel` and
+//! `:emoji:` suggestions through the input's `CompletionProvider` hook, and
+//! the mention directory they draw from.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use anyhow::Result;
+use gpui::{Context, Task, Window};
+use gpui_component::input::{CompletionProvider, InputState};
+use gpui_component::{Rope, RopeExt as _};
+use lsp_types::{CompletionContext, CompletionItem, CompletionResponse, CompletionTextEdit, TextEdit};
+
+use crate::app::{AppView, TripwireApp};
+use crate::mentions::{self, Directory, Entry, RoleEntry};
+use crate::models::{ChannelKind, Server, User};
+use crate::permissions::Permissions;
+
+/// Suggests mentions and emoji for the partial one before the cursor.
+pub(crate) struct MentionCompletions {
+    pub directory: Rc<RefCell<Directory>>,
+}
+
+impl CompletionProvider for MentionCompletions {
+    fn completions(
+        &self,
+        text: &Rope,
+        offset: usize,
+        _: CompletionContext,
+        _: &mut Window,
+        _: &mut Context<InputState>,
+    ) -> Task<Result<CompletionResponse>> {
+        let before_cursor = text.slice(..offset).to_string();
+        let Some((start, trigger, query)) = mentions::completion_query(&before_cursor) else {
+            return Task::ready(Ok(CompletionResponse::Array(vec![])));
+        };
+        let range = lsp_types::Range {
+            start: text.offset_to_position(start),
+            end: text.offset_to_position(offset),
+        };
+        let typed = &before_cursor[start..];
+        let items = mentions::complete(&self.directory.borrow(), trigger, query)
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.label,
+                detail: completion.detail,
+                filter_text: Some(typed.to_string()),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range,
+                    new_text: completion.insert,
+                })),
+                ..Default::default()
+            })
+            .collect();
+        Task::ready(Ok(CompletionResponse::Array(items)))
+    }
+
+    fn is_completion_trigger(&self, _: usize, _: &str, _: &mut Context<InputState>) -> bool {
+        // `completions` decides whether the cursor is in a mention.
+        true
+    }
+}
+
+impl TripwireApp {
+    /// Point the composers' completions at the open conversation.
+    pub(crate) fn refresh_mention_directory(&self) {
+        *self.mention_directory.borrow_mut() = self.build_mention_directory();
+    }
+
+    /// Members, roles and text channels of the open server, or the two
+    /// people in the open DM.
+    fn build_mention_directory(&self) -> Directory {
+        match self.current_view {
+            AppView::Servers => {
+                let mut directory = self.active_server().map(server_directory).unwrap_or_default();
+                // Those who may mention everyone may mention any role.
+                if self.server_permissions().contains(Permissions::MENTION_EVERYONE) {
+                    directory.can_mention_everyone = true;
+                    for role in &mut directory.roles {
+                        role.mentionable = true;
+                    }
+                }
+                directory
+            }
+            AppView::DirectMessages => {
+                let recipient = self
+                    .active_dm_id
+                    .as_deref()
+                    .and_then(|id| self.dm_channels.iter().find(|dm| dm.id == id))
+                    .map(|dm| &dm.recipient);
+                self.dm_directory(recipient)
+            }
+        }
+    }
+
+    /// The people in a DM with `recipient`.
+    pub(crate) fn dm_directory(&self, recipient: Option<&User>) -> Directory {
+        Directory {
+            users: recipient
+                .into_iter()
+                .chain(&self.auth.current_user)
+                .map(|u| entry(&u.id, &u.username))
+                .collect(),
+            ..Directory::default()
+        }
+    }
+
+    /// Composer text as sent: mentions become ID tokens, and the users they
+    /// notify.
+    pub(crate) fn serialize_mentions(&self, text: &str) -> (String, Vec<String>) {
+        let directory = self.mention_directory.borrow();
+        let content = mentions::serialize(text, &directory);
+        let mentioned = mentions::mentioned_users(&content, &directory);
+        (content, mentioned)
+    }
+}
+
+/// Members, roles and text channels of `server`.
+pub(crate) fn server_directory(server: &Server) -> Directory {
+    Directory {
+        users: server.members.iter().map(|u| entry(&u.id, &u.username)).collect(),
+        roles: server
+            .roles
+            .iter()
+            .map(|role| RoleEntry {
+                id: role.id.clone(),
+                name: role.name.clone(),
+                members: server
+                    .member_roles
+                    .iter()
+                    .filter(|(_, roles)| roles.contains(&role.id))
+                    .map(|(user_id, _)| user_id.clone())
+                    .collect(),
+                mentionable: role.mentionable,
+            })
+            .collect(),
+        channels: server
+            .all_channels()
+            .into_iter()
+            .filter(|c| !matches!(c.kind, ChannelKind::Voice | ChannelKind::Stage))
+            .map(|c| entry(&c.id, &c.name))
+            .collect(),
+        emojis: server.emojis.clone(),
+        can_mention_everyone: false,
+    }
+}
+
+fn entry(id: &str, name: &str) -> Entry {
+    Entry {
+        id: id.to_string(),
+        name: name.to_string(),
+    }
+}
@@ -0,0 +1,183 @@
+//! Offline message cache — mirrors what's loaded into the active account's
+//! `MessageStore`, and reads it back so conversations open instantly and
+//! stay readable without a connection.
+
+use gpui::Context;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::app::{HISTORY_PAGE_SIZE, TripwireApp};
+use crate::backend::MessageTarget;
+use crate::message_store::{EvictionPolicy, MessageStore};
+use crate::models::Message;
+use crate::outbox::OutboxEntry;
+
+const SERVERS_SNAPSHOT: &str = "servers";
+const DM_CHANNELS_SNAPSHOT: &str = "dm_channels";
+
+impl TripwireApp {
+    /// Open the active account's cache unless it's already open, or close
+    /// the cache when logged out.
+    pub(crate) fn open_message_store(&mut self) {
+        let Some(user_id) = self.auth.current_user.as_ref().map(|u| u.id.clone()) else {
+            self.message_store = None;
+            return;
+        };
+        if self
+            .message_store
+            .as_ref()
+            .is_some_and(|store| store.user_id() == user_id)
+        {
+            return;
+        }
+        self.message_store = match MessageStore::open_for_account(
+            self.auth.data_dir(),
+            &user_id,
+            EvictionPolicy::default(),
+        ) {
+            Ok(store) => Some(store),
+            Err(err) => {
+                eprintln!("Message cache unavailable: {err:#}");
+                None
+            }
+        };
+    }
+
+    /// Close and delete the active account's cache, for signing out.
+    pub(crate) fn delete_message_store(&mut self) {
+        self.message_store = None;
+        let Some(user) = &self.auth.current_user else {
+            return;
+        };
+        if let Err(err) = MessageStore::delete_for_account(self.auth.data_dir(), &user.id) {
+            eprintln!("Failed to delete message cache: {err:#}");
+        }
+    }
+
+    /// The newest cached page of a conversation, if anything is cached.
+    pub(crate) fn stored_history(&self, conversation_id: &str) -> Option<Vec<Message>> {
+        let store = self.message_store.as_ref()?;
+        match store.load(conversation_id, HISTORY_PAGE_SIZE) {
+            Ok(messages) if !messages.is_empty() => Some(messages),
+            Ok(_) => None,
+            Err(err) => {
+                eprintln!("Failed to read cached history for {conversation_id}: {err:#}");
+                None
+            }
+        }
+    }
+
+    /// The cached page of a conversation just older than `message_id`.
+    pub(crate) fn stored_history_before(&self, conversation_id: &str, message_id: &str) -> Vec<Message> {
+        let Some(store) = &self.message_store else {
+            return Vec::new();
+        };
+        store
+            .load_before(conversation_id, message_id, HISTORY_PAGE_SIZE)
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to read cached history for {conversation_id}: {err:#}");
+                Vec::new()
+            })
+    }
+
+    /// Record a fresh page of the newest history from the server.
+    pub(crate) fn store_history(&mut self, conversation_id: &str, page: &[Message], complete: bool) {
+        if let Some(store) = &mut self.message_store
+            && let Err(err) = store.replace_latest(conversation_id, page, complete)
+        {
+            eprintln!("Failed to cache history for {conversation_id}: {err:#}");
+        }
+    }
+
+    pub(crate) fn store_messages(&mut self, conversation_id: &str, messages: &[Message]) {
+        if let Some(store) = &mut self.message_store
+            && let Err(err) = store.save(conversation_id, messages)
+        {
+            eprintln!("Failed to cache messages for {conversation_id}: {err:#}");
+        }
+    }
+
+    /// Write the loaded copy of a message through to the cache after it
+    /// changed in memory.
+    pub(crate) fn store_message(&mut self, target: &MessageTarget, message_id: &str) {
+        let Some(message) = self
+            .cached_messages(target)
+            .and_then(|messages| messages.iter().find(|m| m.id == message_id))
+            .cloned()
+        else {
+            return;
+        };
+        self.store_messages(target.id(), &[message]);
+    }
+
+    pub(crate) fn unstore_message(&self, conversation_id: &str, message_id: &str) {
+        if let Some(store) = &self.message_store
+            && let Err(err) = store.remove(conversation_id, message_id)
+        {
+            eprintln!("Failed to remove cached message {message_id}: {err:#}");
+        }
+    }
+
+    pub(crate) fn load_outbox_entries(&self) -> Vec<OutboxEntry> {
+        let Some(store) = &self.message_store else {
+            return Vec::new();
+        };
+        store.load_outbox().unwrap_or_else(|err| {
+            eprintln!("Failed to read unsent messages: {err:#}");
+            Vec::new()
+        })
+    }
+
+    pub(crate) fn save_outbox_entry(&self, entry: &OutboxEntry) {
+        if let Some(store) = &self.message_store
+            && let Err(err) = store.save_outbox_entry(entry)
+        {
+            eprintln!("Failed to save unsent message: {err:#}");
+        }
+    }
+
+    pub(crate) fn remove_outbox_entry(&self, nonce: &str) {
+        if let Some(store) = &self.message_store
+            && let Err(err) = store.remove_outbox_entry(nonce)
+        {
+            eprintln!("Failed to remove unsent message: {err:#}");
+        }
+    }
+
+    /// Show the server and DM lists from the last session while the network
+    /// catches up.
+    pub(crate) fn restore_stored_lists(&mut self, cx: &mut Context<Self>) {
+        if let Some(servers) = self.load_snapshot(SERVERS_SNAPSHOT) {
+            self.apply_servers(servers, cx);
+        }
+        if let Some(dm_channels) = self.load_snapshot(DM_CHANNELS_SNAPSHOT) {
+            self.dm_channels = dm_channels;
+        }
+    }
+
+    pub(crate) fn store_server_list(&self) {
+        self.save_snapshot(SERVERS_SNAPSHOT, &self.servers);
+    }
+
+    pub(crate) fn store_dm_list(&self) {
+        self.save_snapshot(DM_CHANNELS_SNAPSHOT, &self.dm_channels);
+    }
+
+    fn load_snapshot<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let store = self.message_store.as_ref()?;
+        store
+            .load_snapshot(key)
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to read cached {key}: {err:#}");
+                None
+            })
+    }
+
+    fn save_snapshot<T: Serialize>(&self, key: &str, value: &T) {
+        if let Some(store) = &self.message_store
+            && let Err(err) = store.save_snapshot(key, value)
+        {
+            eprintln!("Failed to cache {key}: {err:#}");
+        }
+    }
+}
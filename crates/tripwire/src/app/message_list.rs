@@ -0,0 +1,232 @@
+//! Message list — keeps the chat area's virtualized list in step with the
+//! loaded history, pages in older messages as the user scrolls up, and
+//! places the unread divider.
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use gpui::{Context, ListAlignment, ListOffset, ListState, px};
+
+use crate::app::{HISTORY_PAGE_SIZE, TripwireApp};
+use crate::models::Message;
+
+/// Rendered height kept above and below the viewport, so rows don't pop in.
+const OVERDRAW: f32 = 600.;
+/// Once a row this close to the top shows, the next older page loads.
+const LOAD_OLDER_THRESHOLD: usize = 10;
+/// Rows above the newest message past which "Jump to present" shows.
+const JUMP_TO_PRESENT_DISTANCE: usize = 20;
+
+/// Rows of the open conversation, one per message. The list is bottom
+/// aligned, so it sticks to the newest message until scrolled away.
+pub(crate) struct MessageList {
+    pub state: ListState,
+    /// Conversation and message IDs `state` was last synced with.
+    conversation: Option<String>,
+    ids: Vec<String>,
+    /// First unread message of the open conversation, where the divider goes.
+    pub unread_marker: Option<String>,
+    /// Conversations whose oldest message is loaded.
+    complete: HashSet<String>,
+    /// Conversations with an older page on the way.
+    loading: HashSet<String>,
+}
+
+impl MessageList {
+    pub fn new(cx: &mut Context<TripwireApp>) -> Self {
+        let state = ListState::new(0, ListAlignment::Bottom, px(OVERDRAW));
+        let app = cx.entity().downgrade();
+        state.set_scroll_handler(move |event, _, cx| {
+            if event.visible_range.start < LOAD_OLDER_THRESHOLD {
+                app.update(cx, |this, cx| this.load_older_messages(cx)).ok();
+            }
+        });
+        Self {
+            state,
+            conversation: None,
+            ids: Vec::new(),
+            unread_marker: None,
+            complete: HashSet::new(),
+            loading: HashSet::new(),
+        }
+    }
+
+    /// Forget everything about the conversations of the account being left.
+    pub fn clear(&mut self) {
+        self.conversation = None;
+        self.ids.clear();
+        self.unread_marker = None;
+        self.complete.clear();
+        self.loading.clear();
+        self.state.reset(0);
+    }
+
+    pub fn mark_complete(&mut self, conversation_id: &str) {
+        self.complete.insert(conversation_id.to_string());
+    }
+
+    pub fn is_complete(&self, conversation_id: &str) -> bool {
+        self.complete.contains(conversation_id)
+    }
+
+    pub fn is_loading(&self, conversation_id: &str) -> bool {
+        self.loading.contains(conversation_id)
+    }
+
+    /// Whether the list is far enough from the newest message to offer
+    /// jumping back to it.
+    pub fn is_away_from_present(&self) -> bool {
+        self.state.logical_scroll_top().item_ix + JUMP_TO_PRESENT_DISTANCE < self.state.item_count()
+    }
+
+    /// Scroll to the newest message and stick to it again.
+    pub fn scroll_to_present(&self) {
+        self.state.scroll_to(ListOffset {
+            item_ix: self.state.item_count(),
+            offset_in_item: px(0.),
+        });
+    }
+
+    /// Scroll so `ix` is at the top of the list.
+    pub fn scroll_to_item(&self, ix: usize) {
+        self.state.scroll_to(ListOffset {
+            item_ix: ix,
+            offset_in_item: px(0.),
+        });
+    }
+
+    /// Whether the newest message is out of view.
+    pub fn is_scrolled_up(&self) -> bool {
+        self.state.logical_scroll_top().item_ix < self.state.item_count()
+    }
+}
+
+impl TripwireApp {
+    /// The loaded history of the open channel or DM.
+    pub(crate) fn open_conversation_messages(&self) -> &[Message] {
+        self.active_target()
+            .and_then(|target| self.cached_messages(&target))
+            .map(|messages| messages.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Tell the list which rows changed since it was last synced. Rows keep
+    /// their measured height as long as their message stays put.
+    pub(crate) fn sync_message_list(&mut self) {
+        let conversation = self.active_target().map(|target| target.id().to_string());
+        let own_id = self.auth.current_user.as_ref().map(|u| u.id.as_str());
+        let (ids, from_others): (Vec<String>, Vec<bool>) = self
+            .open_conversation_messages()
+            .iter()
+            .map(|m| (m.id.clone(), Some(m.author.id.as_str()) != own_id))
+            .unzip();
+        // First message from someone else at or after `start`.
+        let first_unread = |start: usize| {
+            (start..ids.len())
+                .find(|&ix| from_others[ix])
+                .map(|ix| ids[ix].clone())
+        };
+
+        let list = &mut self.message_list;
+        // Placed once the conversation's first messages show up, before the
+        // open conversation is acked.
+        if list.conversation != conversation || list.ids.is_empty() {
+            let unread = conversation.as_deref().map_or(0, |id| self.read_states.unread(id));
+            let last_read = conversation.as_deref().and_then(|id| self.read_states.last_read(id));
+            list.unread_marker = match last_read.and_then(|seen| ids.iter().position(|id| id == seen)) {
+                Some(seen) => first_unread(seen + 1),
+                // Without the last read message loaded, count the unread
+                // messages back from the newest.
+                None if unread > 0 => {
+                    let others: Vec<usize> = (0..ids.len()).filter(|&ix| from_others[ix]).collect();
+                    others
+                        .get(others.len().saturating_sub(unread))
+                        .map(|&ix| ids[ix].clone())
+                }
+                None => None,
+            };
+            list.state.reset(ids.len());
+        } else if list.ids != ids {
+            let (range, count) = changed_range(&list.ids, &ids);
+            // Messages arriving while scrolled up are unread until seen.
+            if list.unread_marker.is_none() && range.start == list.ids.len() && list.is_scrolled_up() {
+                list.unread_marker = first_unread(range.start);
+            }
+            list.state.splice(range, count);
+        }
+        list.conversation = conversation;
+        list.ids = ids;
+    }
+
+    /// Fetch the page of history before the oldest loaded message of the
+    /// open conversation, falling back to the message cache when offline.
+    pub(crate) fn load_older_messages(&mut self, cx: &mut Context<Self>) {
+        let Some(target) = self.active_target() else {
+            return;
+        };
+        let conversation_id = target.id().to_string();
+        if self.message_list.is_complete(&conversation_id) || self.message_list.is_loading(&conversation_id) {
+            return;
+        }
+        // Unsent echoes have no place in the server's history.
+        let Some(oldest) = self
+            .cached_messages(&target)
+            .and_then(|messages| messages.iter().find(|m| self.delivery_state(&m.id).is_none()))
+            .map(|m| m.id.clone())
+        else {
+            return;
+        };
+
+        self.message_list.loading.insert(conversation_id);
+        let page = self
+            .backend
+            .fetch_history(&target, Some(oldest.clone()), HISTORY_PAGE_SIZE);
+        cx.spawn(async move |this, cx| {
+            let page = page.await;
+            this.update(cx, |this, cx| {
+                this.message_list.loading.remove(target.id());
+                let page = match page {
+                    Ok(page) => {
+                        if page.len() < HISTORY_PAGE_SIZE {
+                            this.message_list.mark_complete(target.id());
+                        }
+                        this.store_messages(target.id(), &page);
+                        page
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to load older messages for {}: {err:#}", target.id());
+                        this.stored_history_before(target.id(), &oldest)
+                    }
+                };
+                let Some(messages) = this.cached_messages_mut(&target) else {
+                    return;
+                };
+                let mut older: Vec<Message> = page
+                    .into_iter()
+                    .filter(|m| !messages.iter().any(|loaded| loaded.id == m.id))
+                    .collect();
+                if older.is_empty() {
+                    return;
+                }
+                older.append(messages);
+                *messages = older;
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+}
+
+/// The range of `old` to replace, and with how many items, to turn it into
+/// `new`, going by what the two have in common at either end.
+fn changed_range(old: &[String], new: &[String]) -> (Range<usize>, usize) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix..old.len() - suffix, new.len() - prefix - suffix)
+}
@@ -0,0 +1,224 @@
+//! Notifications — deciding whether a new message notifies, showing it as a
+//! toast or on the desktop, and the Notifications menus of servers, channels
+//! and DMs.
+
+use std::time::{Duration, SystemTime};
+
+use gpui::{App, AppContext as _, Context, Entity, SharedString};
+use gpui_component::WindowExt as _;
+use gpui_component::menu::{PopupMenu, PopupMenuItem};
+use gpui_component::notification::Notification;
+
+use crate::app::TripwireApp;
+use crate::app::mention_completions::server_directory;
+use crate::backend::MessageTarget;
+use crate::mentions;
+use crate::models::Message;
+use crate::notifications::{DesktopNotification, Incoming, Mute, NotifyLevel, Routed, Scope};
+
+/// Mute lengths offered in menus; `None` lasts until unmuted.
+const MUTE_FOR: [(&str, Option<Duration>); 5] = [
+    ("For 15 Minutes", Some(Duration::from_secs(15 * 60))),
+    ("For 1 Hour", Some(Duration::from_secs(60 * 60))),
+    ("For 8 Hours", Some(Duration::from_secs(8 * 60 * 60))),
+    ("For 24 Hours", Some(Duration::from_secs(24 * 60 * 60))),
+    ("Until I Turn It Back On", None),
+];
+
+/// Toasts of one conversation replace each other.
+struct MessageToast;
+
+impl TripwireApp {
+    /// Notify about `message`, just received in `target`, if the settings
+    /// say it should.
+    pub(crate) fn notify_message(&mut self, target: &MessageTarget, message: &Message, cx: &mut Context<Self>) {
+        let Some(user) = self.auth.current_user.as_ref() else {
+            return;
+        };
+        let focused = self
+            .window_handle
+            .update(cx, |_, window, _| window.is_window_active())
+            .unwrap_or(false);
+        let server = match target {
+            MessageTarget::Channel(id) => self
+                .servers
+                .iter()
+                .find(|s| s.all_channels().iter().any(|c| c.id == *id)),
+            MessageTarget::DirectMessage(_) => None,
+        };
+        let incoming = Incoming {
+            server_id: server.map(|s| s.id.as_str()),
+            conversation_id: target.id(),
+            message,
+            user_id: &user.id,
+            status: user.status.clone(),
+            viewing: focused && self.active_target().as_ref() == Some(target),
+        };
+        if !self.notification_settings.should_notify(&incoming, SystemTime::now()) {
+            return;
+        }
+
+        let author = &message.author.username;
+        let (summary, directory) = match server {
+            Some(server) => {
+                let channel = server
+                    .all_channels()
+                    .into_iter()
+                    .find(|c| c.id == target.id())
+                    .map(|c| c.name.clone())
+                    .unwrap_or_default();
+                (format!("{author} (#{channel}, {})", server.name), server_directory(server))
+            }
+            None => {
+                let recipient = self.dm_channels.iter().find(|dm| dm.id == target.id()).map(|dm| &dm.recipient);
+                (author.clone(), self.dm_directory(recipient))
+            }
+        };
+        let mut body = mentions::plain_text(&message.content, &directory);
+        if body.trim().is_empty() && !message.attachments.is_empty() {
+            body = match message.attachments.len() {
+                1 => "Sent an attachment".to_string(),
+                n => format!("Sent {n} attachments"),
+            };
+        }
+        let notification = DesktopNotification::new(summary, &body, self.notification_settings.sound);
+
+        match self
+            .notification_settings
+            .route(notification, focused, self.notification_bus.as_deref())
+        {
+            Routed::Toast(notification) => self.show_message_toast(target, &message.id, notification, cx),
+            Routed::Desktop(shown) => cx
+                .background_spawn(async move {
+                    if let Err(err) = shown.await {
+                        eprintln!("Failed to show desktop notification: {err:#}");
+                    }
+                })
+                .detach(),
+            Routed::Dropped => {}
+        }
+    }
+
+    /// Show `notification` in the window; clicking it opens the message.
+    fn show_message_toast(
+        &self,
+        target: &MessageTarget,
+        message_id: &str,
+        notification: DesktopNotification,
+        cx: &mut Context<Self>,
+    ) {
+        let app = cx.weak_entity();
+        let (target, message_id) = (target.clone(), message_id.to_string());
+        let toast = Notification::new()
+            .title(notification.summary)
+            .message(notification.body)
+            .id1::<MessageToast>(target.id().to_string())
+            .on_click(move |_, window, cx| {
+                app.update(cx, |this, cx| {
+                    this.jump_to_message(target.clone(), message_id.clone(), window, cx);
+                })
+                .ok();
+            });
+        self.show_toast(toast, cx);
+    }
+
+    /// Show `toast` in the window. Only for use outside of the window's
+    /// event handlers and render, which have a `Window` to push to.
+    pub(crate) fn show_toast(&self, toast: Notification, cx: &mut App) {
+        self.window_handle
+            .update(cx, |_, window, cx| window.push_notification(toast, cx))
+            .ok();
+    }
+
+    /// Tell the user something went wrong, as with `show_toast`.
+    pub(crate) fn show_error(&self, message: impl Into<SharedString>, cx: &mut App) {
+        self.show_toast(Notification::error(message), cx);
+    }
+
+    /// Override the notification level of `scope`, or inherit it with `None`.
+    pub(crate) fn set_notify_level(&mut self, scope: Scope, level: Option<NotifyLevel>, cx: &mut Context<Self>) {
+        self.notification_settings.set_level(scope, level);
+        cx.notify();
+    }
+
+    /// Mute `scope` for `duration`, or until unmuted.
+    pub(crate) fn mute_notifications(&mut self, scope: Scope, duration: Option<Duration>, cx: &mut Context<Self>) {
+        let mute = Mute::new(duration, SystemTime::now());
+        self.notification_settings.set_mute(scope, Some(mute));
+        cx.notify();
+    }
+
+    pub(crate) fn unmute_notifications(&mut self, scope: Scope, cx: &mut Context<Self>) {
+        self.notification_settings.set_mute(scope, None);
+        cx.notify();
+    }
+
+    pub(crate) fn reset_notifications(&mut self, scope: &Scope, cx: &mut Context<Self>) {
+        self.notification_settings.clear_override(scope);
+        cx.notify();
+    }
+
+    /// What `scope` is called in the settings: a server's name, `#channel`
+    /// with its server, or a DM's recipient.
+    pub(crate) fn scope_name(&self, scope: &Scope) -> String {
+        match scope {
+            Scope::Server(id) => self
+                .servers
+                .iter()
+                .find(|s| s.id == *id)
+                .map(|s| s.name.clone())
+                .unwrap_or_else(|| "Unknown server".to_string()),
+            Scope::Channel(id) => {
+                let channel = self.servers.iter().find_map(|s| {
+                    let channel = s.all_channels().into_iter().find(|c| c.id == *id)?;
+                    Some(format!("#{} ({})", channel.name, s.name))
+                });
+                channel
+                    .or_else(|| self.dm_channels.iter().find(|dm| dm.id == *id).map(|dm| dm.display_name()))
+                    .unwrap_or_else(|| "Unknown channel".to_string())
+            }
+        }
+    }
+}
+
+/// Items of the Notifications submenu for `scope`: its level, and muting.
+pub(crate) fn notification_menu(menu: PopupMenu, scope: Scope, app: &Entity<TripwireApp>, cx: &App) -> PopupMenu {
+    let settings = &app.read(cx).notification_settings;
+    let level = settings.level(&scope);
+    let mute = settings.mute(&scope).filter(|m| m.is_active(SystemTime::now()));
+
+    let mut menu = menu.item(PopupMenuItem::new("Use Default").checked(level.is_none()).on_click({
+        let (app, scope) = (app.clone(), scope.clone());
+        move |_, _, cx| {
+            app.update(cx, |this, cx| this.set_notify_level(scope.clone(), None, cx));
+        }
+    }));
+    for option in NotifyLevel::ALL {
+        menu = menu.item(PopupMenuItem::new(option.label()).checked(level == Some(option)).on_click({
+            let (app, scope) = (app.clone(), scope.clone());
+            move |_, _, cx| {
+                app.update(cx, |this, cx| this.set_notify_level(scope.clone(), Some(option), cx));
+            }
+        }));
+    }
+
+    menu = menu.separator();
+    if let Some(mute) = mute {
+        let app = app.clone();
+        return menu
+            .label(mute.label(SystemTime::now()))
+            .item(PopupMenuItem::new("Unmute").on_click(move |_, _, cx| {
+                app.update(cx, |this, cx| this.unmute_notifications(scope.clone(), cx));
+            }));
+    }
+    menu = menu.label("Mute");
+    for (label, duration) in MUTE_FOR {
+        menu = menu.item(PopupMenuItem::new(label).on_click({
+            let (app, scope) = (app.clone(), scope.clone());
+            move |_, _, cx| {
+                app.update(cx, |this, cx| this.mute_notifications(scope.clone(), duration, cx));
+            }
+        }));
+    }
+    menu
+}
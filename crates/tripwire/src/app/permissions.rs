@@ -0,0 +1,99 @@
+//! Permissions — what the signed-in user may do where they are, so actions
+//! they can't take are hidden or disabled rather than failing at the
+//! backend.
+
+use gpui::Context;
+
+use crate::app::{AppView, TripwireApp};
+use crate::backend::MessageTarget;
+use crate::models::{Channel, Message};
+use crate::permissions::{self, Permissions};
+
+impl TripwireApp {
+    /// The user's permissions in the active server, outside any channel.
+    pub(crate) fn server_permissions(&self) -> Permissions {
+        match (self.auth.current_user.as_ref(), self.active_server()) {
+            (Some(user), Some(server)) => permissions::server_permissions(server, &user.id),
+            _ => Permissions::empty(),
+        }
+    }
+
+    /// The user's permissions in `channel` of the active server.
+    pub(crate) fn channel_permissions(&self, channel: &Channel) -> Permissions {
+        match (self.auth.current_user.as_ref(), self.active_server()) {
+            (Some(user), Some(server)) => permissions::compute_permissions(server, &user.id, channel),
+            _ => Permissions::empty(),
+        }
+    }
+
+    /// The user's permissions in the open channel or DM.
+    pub(crate) fn active_permissions(&self) -> Permissions {
+        match self.active_target() {
+            Some(MessageTarget::DirectMessage(_)) => Permissions::direct_message(),
+            Some(MessageTarget::Channel(_)) => self
+                .active_channel()
+                .map_or(Permissions::empty(), |channel| self.channel_permissions(channel)),
+            None => Permissions::empty(),
+        }
+    }
+
+    /// Whether the user has all of `required` in the open channel or DM.
+    pub(crate) fn can(&self, required: Permissions) -> bool {
+        self.active_permissions().contains(required)
+    }
+
+    fn is_own_message(&self, message: &Message) -> bool {
+        self.auth.current_user.as_ref().is_some_and(|user| user.id == message.author.id)
+    }
+
+    pub(crate) fn can_edit_message(&self, message: &Message) -> bool {
+        self.is_own_message(message) && self.can(Permissions::SEND_MESSAGES)
+    }
+
+    /// Anyone can read a message's replies; starting a thread on one that
+    /// has none takes Create Threads.
+    pub(crate) fn can_open_thread(&self, message: &Message) -> bool {
+        message.thread_count > 0 || self.can(Permissions::CREATE_THREADS)
+    }
+
+    /// Anyone can delete their own messages; moderators anyone's.
+    pub(crate) fn can_delete_message(&self, message: &Message) -> bool {
+        self.is_own_message(message) || self.can(Permissions::MANAGE_MESSAGES)
+    }
+
+    /// Pins are shared with everyone in the conversation, so only
+    /// moderators can change them in channels.
+    pub(crate) fn can_pin_messages(&self) -> bool {
+        self.current_view == AppView::DirectMessages || self.can(Permissions::MANAGE_MESSAGES)
+    }
+
+    pub(crate) fn set_pinned(&mut self, message_id: String, pinned: bool, cx: &mut Context<Self>) {
+        let Some(target) = self.active_target() else {
+            return;
+        };
+        if !self.can_pin_messages() {
+            return;
+        }
+        let Some(msg) = self
+            .cached_messages_mut(&target)
+            .and_then(|messages| messages.iter_mut().find(|m| m.id == message_id))
+        else {
+            return;
+        };
+        msg.pinned = pinned;
+        self.store_message(&target, &message_id);
+
+        let call = self.backend.set_pinned(&target, &message_id, pinned);
+        self.detach_backend_call(call, "update pin", cx);
+        cx.notify();
+    }
+
+    /// Where to land in the active server: the first channel the user can
+    /// see.
+    pub(crate) fn first_visible_channel(&self) -> Option<&Channel> {
+        self.active_server()?
+            .all_channels()
+            .into_iter()
+            .find(|channel| self.channel_permissions(channel).contains(Permissions::VIEW_CHANNEL))
+    }
+}
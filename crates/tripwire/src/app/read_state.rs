@@ -0,0 +1,152 @@
+//! Read state — counting what arrives in conversations that aren't being
+//! looked at, acking the open one, and "Mark as read" on servers, categories
+//! and channels. Every change is cached locally and sent to the backend.
+
+use gpui::{Context, Window};
+
+use crate::app::TripwireApp;
+use crate::backend::MessageTarget;
+use crate::models::{Message, Server};
+use crate::read_state::ReadState;
+
+impl TripwireApp {
+    /// Show the cached read state, then take the server's.
+    pub(crate) fn load_read_states(&mut self, cx: &mut Context<Self>) {
+        if let Some(store) = &self.message_store {
+            match store.load_read_states() {
+                Ok(states) => self.read_states.merge(states),
+                Err(err) => eprintln!("Failed to read cached read states: {err:#}"),
+            }
+        }
+        let states = self.backend.fetch_read_states();
+        cx.spawn(async move |this, cx| {
+            let states = states.await;
+            this.update(cx, |this, cx| match states {
+                Ok(states) => {
+                    for state in &states {
+                        this.save_read_state(state);
+                    }
+                    this.read_states.merge(states);
+                    cx.notify();
+                }
+                Err(err) => eprintln!("Failed to load read states: {err:#}"),
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Count `message`, just received in `target`.
+    pub(crate) fn note_message(&mut self, target: &MessageTarget, message: &Message) {
+        let Some(user_id) = self.auth.current_user.as_ref().map(|u| u.id.clone()) else {
+            return;
+        };
+        if let Some(state) = self.read_states.note_message(target.id(), message, &user_id).cloned() {
+            self.save_read_state(&state);
+        }
+    }
+
+    /// An ack made by this account elsewhere, relayed by the gateway.
+    pub(crate) fn apply_ack(&mut self, conversation_id: &str, message_id: &str) {
+        if let Some(state) = self.read_states.ack(conversation_id, message_id).cloned() {
+            self.save_read_state(&state);
+        }
+    }
+
+    /// Mark the open conversation read while its newest message is on
+    /// screen and the window has focus.
+    pub(crate) fn ack_open_conversation(&mut self, window: &Window, cx: &mut Context<Self>) {
+        if !window.is_window_active() || self.message_list.is_scrolled_up() {
+            return;
+        }
+        let Some(target) = self.active_target() else {
+            return;
+        };
+        // Unsent echoes have no ID the server knows.
+        let newest = self
+            .open_conversation_messages()
+            .iter()
+            .rev()
+            .find(|m| self.delivery_state(&m.id).is_none())
+            .map(|m| m.id.clone());
+        if let Some(message_id) = newest {
+            self.ack(&target, &message_id, cx);
+        }
+    }
+
+    /// Mark `target` read up to `message_id`.
+    fn ack(&mut self, target: &MessageTarget, message_id: &str, cx: &mut Context<Self>) {
+        let Some(state) = self.read_states.ack(target.id(), message_id).cloned() else {
+            return;
+        };
+        self.save_read_state(&state);
+        let call = self.backend.ack_message(target, message_id);
+        self.detach_backend_call(call, "mark as read", cx);
+    }
+
+    /// Mark everything in each of `targets` read. Conversations whose history
+    /// isn't loaded ask the backend for their newest message first.
+    pub(crate) fn mark_read(&mut self, targets: Vec<MessageTarget>, cx: &mut Context<Self>) {
+        for target in targets {
+            if self.read_states.unread(target.id()) == 0 {
+                continue;
+            }
+            let newest = self
+                .cached_messages(&target)
+                .and_then(|messages| messages.iter().rev().find(|m| self.delivery_state(&m.id).is_none()))
+                .map(|m| m.id.clone());
+            if let Some(message_id) = newest {
+                self.ack(&target, &message_id, cx);
+                continue;
+            }
+            let page = self.backend.fetch_history(&target, None, 1);
+            cx.spawn(async move |this, cx| {
+                let page = page.await;
+                this.update(cx, |this, cx| match page {
+                    Ok(page) => {
+                        if let Some(newest) = page.last() {
+                            this.ack(&target, &newest.id, cx);
+                            cx.notify();
+                        }
+                    }
+                    Err(err) => eprintln!("Failed to mark {} as read: {err:#}", target.id()),
+                })
+                .ok();
+            })
+            .detach();
+        }
+        cx.notify();
+    }
+
+    /// Mark every channel of `server` read.
+    pub(crate) fn mark_server_read(&mut self, server_ix: usize, cx: &mut Context<Self>) {
+        let Some(server) = self.servers.get(server_ix) else {
+            return;
+        };
+        let targets = server
+            .all_channels()
+            .iter()
+            .map(|c| MessageTarget::Channel(c.id.clone()))
+            .collect();
+        self.mark_read(targets, cx);
+    }
+
+    /// Whether anything in `server` is unread, and how many unread messages
+    /// mention the user.
+    pub(crate) fn server_unread(&self, server: &Server) -> (bool, usize) {
+        server.all_channels().iter().fold((false, 0), |(unread, mentions), channel| {
+            (
+                unread || self.read_states.unread(&channel.id) > 0,
+                mentions + self.read_states.mentions(&channel.id),
+            )
+        })
+    }
+
+    fn save_read_state(&self, state: &ReadState) {
+        if let Some(store) = &self.message_store
+            && let Err(err) = store.save_read_state(state)
+        {
+            eprintln!("Failed to cache read state of {}: {err:#}", state.conversation_id);
+        }
+    }
+}
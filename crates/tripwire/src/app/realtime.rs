@@ -0,0 +1,207 @@
+//! Real-time updates — keeps the gateway connection open while logged in
+//! and applies its events to `TripwireApp` state.
+
+use std::time::{Duration, Instant};
+
+use futures::StreamExt as _;
+use gpui::Context;
+
+use crate::app::TripwireApp;
+use crate::backend::{GatewayConfig, GatewayConnection, GatewayEvent, MessageTarget};
+use crate::models::{Message, UserStatus, VoiceParticipant};
+
+/// How long a typing indicator stays up without a fresh TYPING_START.
+const TYPING_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl TripwireApp {
+    /// (Re)open the gateway for the current session, or close it when
+    /// logged out or the backend has no gateway.
+    pub(crate) fn connect_gateway(&mut self, cx: &mut Context<Self>) {
+        self.gateway = None;
+        self._gateway_events = None;
+
+        let Some(url) = self.backend.gateway_url() else {
+            return;
+        };
+        let Some(token) = self.auth.token.clone().filter(|_| self.auth.is_authenticated()) else {
+            return;
+        };
+
+        let (connection, mut events) = GatewayConnection::connect(GatewayConfig::new(url, token));
+        self.gateway = Some(connection);
+        self._gateway_events = Some(cx.spawn(async move |this, cx| {
+            while let Some(event) = events.next().await {
+                if this
+                    .update(cx, |this, cx| this.apply_gateway_event(event, cx))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }));
+    }
+
+    pub(crate) fn apply_gateway_event(&mut self, event: GatewayEvent, cx: &mut Context<Self>) {
+        match event {
+            GatewayEvent::Ready { .. } | GatewayEvent::Resumed => return,
+            GatewayEvent::MessageCreate {
+                channel_id,
+                message,
+            } => {
+                self.stop_typing(&channel_id, &message.author.id);
+                let target = self.target_for(channel_id);
+                if self.find_message_mut(&target, &message.id).is_none() {
+                    self.note_message(&target, &message);
+                    self.notify_message(&target, &message, cx);
+                }
+                self.push_message(&target, message);
+            }
+            GatewayEvent::MessageUpdate {
+                channel_id,
+                message,
+            } => {
+                let target = self.target_for(channel_id);
+                let message_id = message.id.clone();
+                if let Some(existing) = self.find_message_mut(&target, &message_id) {
+                    *existing = message;
+                    self.store_message(&target, &message_id);
+                }
+            }
+            GatewayEvent::MessageDelete {
+                channel_id,
+                message_id,
+            } => {
+                let target = self.target_for(channel_id);
+                if let Some(messages) = self.cached_messages_mut(&target) {
+                    messages.retain(|m| m.id != message_id);
+                }
+                self.unstore_message(target.id(), &message_id);
+            }
+            GatewayEvent::MessageReactionAdd {
+                channel_id,
+                message_id,
+                user_id,
+                emoji,
+            } => {
+                let target = self.target_for(channel_id);
+                if let Some(msg) = self.find_message_mut(&target, &message_id) {
+                    if !msg.user_reacted(&emoji, &user_id) {
+                        msg.add_reaction(emoji, user_id);
+                        self.store_message(&target, &message_id);
+                    }
+                }
+            }
+            GatewayEvent::MessageReactionRemove {
+                channel_id,
+                message_id,
+                user_id,
+                emoji,
+            } => {
+                let target = self.target_for(channel_id);
+                if let Some(msg) = self.find_message_mut(&target, &message_id) {
+                    msg.remove_reaction(&emoji, &user_id);
+                    self.store_message(&target, &message_id);
+                }
+            }
+            GatewayEvent::MessageAck {
+                channel_id,
+                message_id,
+            } => self.apply_ack(&channel_id, &message_id),
+            GatewayEvent::TypingStart {
+                channel_id,
+                user_id,
+            } => self.start_typing(channel_id, user_id, cx),
+            GatewayEvent::PresenceUpdate { user_id, status } => {
+                self.apply_presence(&user_id, status);
+            }
+            GatewayEvent::VoiceStateUpdate {
+                channel_id,
+                participant,
+            } => self.apply_voice_state(channel_id, participant),
+        }
+        cx.notify();
+    }
+
+    /// Gateway events carry a bare channel id; DMs are told apart by
+    /// looking them up in the DM list.
+    fn target_for(&self, channel_id: String) -> MessageTarget {
+        if self.dm_channels.iter().any(|dm| dm.id == channel_id) {
+            MessageTarget::DirectMessage(channel_id)
+        } else {
+            MessageTarget::Channel(channel_id)
+        }
+    }
+
+    fn find_message_mut(&mut self, target: &MessageTarget, message_id: &str) -> Option<&mut Message> {
+        self.cached_messages_mut(target)?
+            .iter_mut()
+            .find(|m| m.id == message_id)
+    }
+
+    // ── Typing ──────────────────────────────────────────────────────────────
+
+    fn start_typing(&mut self, channel_id: String, user_id: String, cx: &mut Context<Self>) {
+        self.typing_users
+            .entry(channel_id.clone())
+            .or_default()
+            .insert(user_id, Instant::now());
+
+        cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(TYPING_TIMEOUT).await;
+            this.update(cx, |this, cx| {
+                if let Some(users) = this.typing_users.get_mut(&channel_id) {
+                    users.retain(|_, started| started.elapsed() < TYPING_TIMEOUT);
+                    if users.is_empty() {
+                        this.typing_users.remove(&channel_id);
+                    }
+                    cx.notify();
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn stop_typing(&mut self, channel_id: &str, user_id: &str) {
+        if let Some(users) = self.typing_users.get_mut(channel_id) {
+            users.remove(user_id);
+            if users.is_empty() {
+                self.typing_users.remove(channel_id);
+            }
+        }
+    }
+
+    // ── Presence & voice ────────────────────────────────────────────────────
+
+    fn apply_presence(&mut self, user_id: &str, status: UserStatus) {
+        let members = self.servers.iter_mut().flat_map(|s| s.members.iter_mut());
+        let recipients = self.dm_channels.iter_mut().map(|dm| &mut dm.recipient);
+        for user in members.chain(recipients).chain(self.auth.current_user.as_mut()) {
+            if user.id == user_id {
+                user.status = status.clone();
+            }
+        }
+    }
+
+    fn apply_voice_state(&mut self, channel_id: Option<String>, participant: VoiceParticipant) {
+        let channels = self
+            .servers
+            .iter_mut()
+            .flat_map(|s| s.categories.iter_mut())
+            .flat_map(|c| c.channels.iter_mut());
+
+        for channel in channels {
+            let before = channel.voice_participants.len();
+            channel
+                .voice_participants
+                .retain(|p| p.user_id != participant.user_id);
+            let joined = channel_id.as_deref() == Some(channel.id.as_str());
+            if joined {
+                channel.voice_participants.push(participant.clone());
+            }
+            if joined || channel.voice_participants.len() != before {
+                channel.members_connected = channel.voice_participants.len();
+            }
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Role editor — the Roles screen of server settings. Changes are staged in
+//! a `RoleDraft` and saved or reset together; leaving the screen with
+//! unsaved changes is refused until one or the other happens.
+
+use gpui::{AppContext as _, Context, Entity, Hsla, Subscription, Window};
+use gpui_component::Colorize as _;
+use gpui_component::color_picker::{ColorPickerEvent, ColorPickerState};
+use gpui_component::input::{InputEvent, InputState};
+
+use crate::app::TripwireApp;
+use crate::role_draft::RoleDraft;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RoleTab {
+    Display,
+    Permissions,
+    Members,
+}
+
+impl RoleTab {
+    pub const ALL: [RoleTab; 3] = [RoleTab::Display, RoleTab::Permissions, RoleTab::Members];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RoleTab::Display => "Display",
+            RoleTab::Permissions => "Permissions",
+            RoleTab::Members => "Members",
+        }
+    }
+}
+
+pub(crate) struct RoleEditor {
+    pub server_id: String,
+    pub draft: RoleDraft,
+    pub selected: Option<String>,
+    pub tab: RoleTab,
+    pub name_input: Entity<InputState>,
+    pub color_picker: Entity<ColorPickerState>,
+    pub saving: bool,
+    /// Tried to leave with unsaved changes; the bar stands out until
+    /// they're saved or reset.
+    pub nag: bool,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl TripwireApp {
+    /// Start editing the active server's roles, unless already editing them.
+    pub(crate) fn open_role_editor(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let (Some(server), Some(user)) = (self.active_server(), self.auth.current_user.as_ref()) else {
+            return;
+        };
+        if self.role_editor.as_ref().is_some_and(|editor| editor.server_id == server.id) {
+            return;
+        }
+        let draft = RoleDraft::new(server, &user.id);
+        let server_id = server.id.clone();
+
+        let name_input = cx.new(|cx| InputState::new(window, cx).placeholder("Role name"));
+        let color_picker = cx.new(|cx| ColorPickerState::new(window, cx));
+        let name_sub = cx.subscribe(&name_input, |this: &mut TripwireApp, input, event: &InputEvent, cx| {
+            if let InputEvent::Change = event {
+                let name = input.read(cx).value().to_string();
+                this.update_selected_role(cx, |role| role.name = name);
+            }
+        });
+        let color_sub = cx.subscribe(
+            &color_picker,
+            |this: &mut TripwireApp, _, event: &ColorPickerEvent, cx| {
+                let ColorPickerEvent::Change(Some(color)) = event else {
+                    return;
+                };
+                let color = color.to_hex();
+                this.update_selected_role(cx, |role| role.color = color);
+            },
+        );
+
+        self.role_editor = Some(RoleEditor {
+            server_id,
+            draft,
+            selected: None,
+            tab: RoleTab::Display,
+            name_input,
+            color_picker,
+            saving: false,
+            nag: false,
+            _subscriptions: vec![name_sub, color_sub],
+        });
+        let first = self
+            .role_editor
+            .as_ref()
+            .and_then(|editor| editor.draft.roles().first())
+            .map(|role| role.id.clone());
+        if let Some(id) = first {
+            self.select_role(id, window, cx);
+        }
+        cx.notify();
+    }
+
+    /// Whether the role editor has changes that would be lost by leaving
+    /// it. Flags them to the user if so.
+    pub(crate) fn hold_unsaved_roles(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(editor) = &mut self.role_editor else {
+            return false;
+        };
+        if !editor.draft.is_dirty() {
+            return false;
+        }
+        editor.nag = true;
+        cx.notify();
+        true
+    }
+
+    pub(crate) fn select_role(&mut self, id: String, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = &mut self.role_editor else {
+            return;
+        };
+        let Some(role) = editor.draft.role(&id) else {
+            return;
+        };
+        let (name, color) = (role.name.clone(), role.color.clone());
+        editor.selected = Some(id);
+        editor
+            .name_input
+            .update(cx, |input, cx| input.set_value(&name, window, cx));
+        if let Ok(color) = Hsla::parse_hex(&color) {
+            editor
+                .color_picker
+                .update(cx, |picker, cx| picker.set_value(color, window, cx));
+        }
+        cx.notify();
+    }
+
+    pub(crate) fn set_role_tab(&mut self, tab: RoleTab, cx: &mut Context<Self>) {
+        if let Some(editor) = &mut self.role_editor {
+            editor.tab = tab;
+            cx.notify();
+        }
+    }
+
+    pub(crate) fn update_selected_role(
+        &mut self,
+        cx: &mut Context<Self>,
+        f: impl FnOnce(&mut crate::models::Role),
+    ) {
+        let Some(editor) = &mut self.role_editor else {
+            return;
+        };
+        if let Some(id) = editor.selected.clone() {
+            editor.draft.update(&id, f);
+            cx.notify();
+        }
+    }
+
+    pub(crate) fn create_role(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = &mut self.role_editor else {
+            return;
+        };
+        if let Some(id) = editor.draft.create_role() {
+            editor.tab = RoleTab::Display;
+            self.select_role(id, window, cx);
+        }
+    }
+
+    pub(crate) fn delete_selected_role(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = &mut self.role_editor else {
+            return;
+        };
+        let Some(id) = editor.selected.take() else {
+            return;
+        };
+        let ix = editor.draft.roles().iter().position(|role| role.id == id);
+        editor.draft.delete_role(&id);
+        // Select the role that took its place, or the new last one.
+        let roles = editor.draft.roles();
+        let next = ix
+            .and_then(|ix| roles.get(ix).or(roles.last()))
+            .map(|role| role.id.clone());
+        match next {
+            Some(next) => self.select_role(next, window, cx),
+            None => cx.notify(),
+        }
+    }
+
+    pub(crate) fn move_role(&mut self, id: &str, to: usize, cx: &mut Context<Self>) {
+        if let Some(editor) = &mut self.role_editor {
+            editor.draft.move_role(id, to);
+            cx.notify();
+        }
+    }
+
+    pub(crate) fn set_role_holder(&mut self, user_id: &str, holds: bool, cx: &mut Context<Self>) {
+        let Some(editor) = &mut self.role_editor else {
+            return;
+        };
+        if let Some(id) = editor.selected.clone() {
+            editor.draft.set_holder(&id, user_id, holds);
+            cx.notify();
+        }
+    }
+
+    /// Throw away every staged change to the roles.
+    pub(crate) fn reset_roles(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(editor) = &mut self.role_editor else {
+            return;
+        };
+        editor.draft.discard();
+        editor.nag = false;
+        let selected = editor
+            .selected
+            .clone()
+            .filter(|id| editor.draft.role(id).is_some())
+            .or_else(|| editor.draft.roles().first().map(|role| role.id.clone()));
+        match selected {
+            Some(id) => self.select_role(id, window, cx),
+            None => {
+                editor.selected = None;
+                cx.notify();
+            }
+        }
+    }
+
+    /// Send the staged changes to the backend. The server comes back with
+    /// new roles' real IDs, so the editor starts over from it.
+    pub(crate) fn save_roles(&mut self, cx: &mut Context<Self>) {
+        let Some(editor) = &mut self.role_editor else {
+            return;
+        };
+        if editor.saving || !editor.draft.is_dirty() {
+            return;
+        }
+        editor.saving = true;
+        let request = self.backend.update_roles(&editor.server_id, editor.draft.changes());
+        cx.notify();
+
+        cx.spawn(async move |this, cx| {
+            let result = request.await;
+            this.update(cx, |this, cx| {
+                let Some(editor) = &mut this.role_editor else {
+                    return;
+                };
+                editor.saving = false;
+                match result {
+                    Ok(server) => {
+                        let selected_ix = editor
+                            .selected
+                            .as_ref()
+                            .and_then(|id| editor.draft.roles().iter().position(|role| role.id == *id));
+                        editor.draft.saved(&server);
+                        editor.nag = false;
+                        editor.selected = selected_ix
+                            .and_then(|ix| server.roles.get(ix))
+                            .map(|role| role.id.clone());
+                        if let Some(existing) = this.servers.iter_mut().find(|s| s.id == server.id) {
+                            *existing = server;
+                        }
+                        this.refresh_mention_directory();
+                    }
+                    Err(err) => this.show_error(format!("Couldn't save roles: {err:#}"), cx),
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+}
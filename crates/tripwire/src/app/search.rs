@@ -0,0 +1,275 @@
+//! Message search — runs the header search box's query against the message
+//! cache and jumps to hits in the chat area.
+
+use std::time::Duration;
+
+use gpui::{Context, Window};
+
+use crate::app::TripwireApp;
+use crate::backend::MessageTarget;
+use crate::models::Message;
+use crate::search::{self, SearchQuery, Snippet};
+
+/// Hits shown for a single search.
+const MAX_RESULTS: usize = 100;
+/// Length of a hit's snippet, in bytes.
+const SNIPPET_LEN: usize = 160;
+/// Messages loaded on either side of a hit that isn't loaded yet.
+const JUMP_CONTEXT: usize = 25;
+/// Rows left visible above the message jumped to.
+const JUMP_CONTEXT_ROWS: usize = 2;
+/// How long the message jumped to stays highlighted.
+const JUMP_HIGHLIGHT: Duration = Duration::from_secs(3);
+
+/// Results of the last search, shown in the search panel.
+pub(crate) struct SearchResults {
+    pub query: String,
+    /// Why the query couldn't run, e.g. a malformed date.
+    pub error: Option<String>,
+    /// Hits grouped by conversation, the one with the newest hit first.
+    pub groups: Vec<SearchGroup>,
+}
+
+impl SearchResults {
+    pub fn hit_count(&self) -> usize {
+        self.groups.iter().map(|g| g.hits.len()).sum()
+    }
+}
+
+pub(crate) struct SearchGroup {
+    pub target: MessageTarget,
+    /// `#channel · Server` or `@user`.
+    pub label: String,
+    pub hits: Vec<SearchHit>,
+}
+
+pub(crate) struct SearchHit {
+    pub message: Message,
+    pub snippet: Snippet,
+}
+
+impl TripwireApp {
+    /// Search for what's in the search box.
+    pub(crate) fn run_search(&mut self, cx: &mut Context<Self>) {
+        let input = self.search_input.read(cx).value().trim().to_string();
+        if input.is_empty() {
+            self.search = None;
+            cx.notify();
+            return;
+        }
+        let mut results = SearchResults {
+            query: input.clone(),
+            error: None,
+            groups: Vec::new(),
+        };
+        match SearchQuery::parse(&input) {
+            Ok(query) => match self.search_messages(&query) {
+                Ok(groups) => results.groups = groups,
+                Err(err) => results.error = Some(err),
+            },
+            Err(err) => results.error = Some(err.to_string()),
+        }
+        self.search = Some(results);
+        cx.notify();
+    }
+
+    pub(crate) fn close_search(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.search = None;
+        self.search_input.update(cx, |input, cx| input.set_value("", window, cx));
+        cx.notify();
+    }
+
+    /// Open the conversation holding `message_id`, loading cached messages
+    /// around it if needed, and scroll to it.
+    pub(crate) fn jump_to_message(
+        &mut self,
+        target: MessageTarget,
+        message_id: String,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        match &target {
+            MessageTarget::Channel(channel_id) => {
+                if let Some(ix) = self
+                    .servers
+                    .iter()
+                    .position(|s| s.all_channels().iter().any(|c| c.id == *channel_id))
+                {
+                    self.active_server = ix;
+                }
+                self.switch_channel(channel_id.clone(), window, cx);
+            }
+            MessageTarget::DirectMessage(dm_id) => self.switch_dm(dm_id.clone(), window, cx),
+        }
+        self.load_message_context(&target, &message_id);
+        self.sync_message_list();
+        if let Some(ix) = self
+            .cached_messages(&target)
+            .and_then(|messages| messages.iter().position(|m| m.id == message_id))
+        {
+            self.message_list.scroll_to_item(ix.saturating_sub(JUMP_CONTEXT_ROWS));
+        }
+
+        self.highlighted_message = Some(message_id.clone());
+        self._highlight_clear = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(JUMP_HIGHLIGHT).await;
+            this.update(cx, |this, cx| {
+                if this.highlighted_message.as_deref() == Some(message_id.as_str()) {
+                    this.highlighted_message = None;
+                    cx.notify();
+                }
+            })
+            .ok();
+        }));
+        cx.notify();
+    }
+
+    fn search_messages(&self, query: &SearchQuery) -> Result<Vec<SearchGroup>, String> {
+        let conversations = match &query.channel {
+            Some(name) => {
+                let ids = self.conversations_named(name);
+                if ids.is_empty() {
+                    return Err(format!("No channel or DM named \"{name}\"."));
+                }
+                Some(ids)
+            }
+            None => None,
+        };
+        let mentioned_ids = query
+            .mentions
+            .as_deref()
+            .map(|name| self.user_ids_named(name))
+            .unwrap_or_default();
+        let accept = |conversation_id: &str, message: &Message| {
+            conversations
+                .as_ref()
+                .is_none_or(|ids| ids.iter().any(|id| id == conversation_id))
+                && self.locate_conversation(conversation_id).is_some()
+                && query.matches_filters(message, &mentioned_ids)
+        };
+
+        let hits = match &self.message_store {
+            Some(store) => store
+                .search(query, accept, MAX_RESULTS)
+                .map_err(|err| format!("Search failed: {err:#}"))?,
+            // Without a cache, search what's loaded.
+            None => {
+                let mut hits: Vec<(String, Message)> = self
+                    .messages
+                    .iter()
+                    .chain(&self.dm_messages)
+                    .flat_map(|(id, messages)| messages.iter().map(move |m| (id.clone(), m.clone())))
+                    .filter(|(id, m)| query.matches_text(&m.content) && accept(id, m))
+                    .collect();
+                hits.sort_by_key(|(_, m)| std::cmp::Reverse(m.created_at));
+                hits.truncate(MAX_RESULTS);
+                hits
+            }
+        };
+
+        let mut groups: Vec<SearchGroup> = Vec::new();
+        for (conversation_id, message) in hits {
+            let snippet = search::snippet(&message.content, &query.terms, SNIPPET_LEN);
+            let hit = SearchHit { message, snippet };
+            match groups.iter_mut().find(|g| g.target.id() == conversation_id) {
+                Some(group) => group.hits.push(hit),
+                None => {
+                    let Some((target, label)) = self.locate_conversation(&conversation_id) else {
+                        continue;
+                    };
+                    groups.push(SearchGroup {
+                        target,
+                        label,
+                        hits: vec![hit],
+                    });
+                }
+            }
+        }
+        Ok(groups)
+    }
+
+    /// Where a cached conversation lives, and how to label it. `None` for
+    /// conversations no longer listed, and for threads.
+    fn locate_conversation(&self, conversation_id: &str) -> Option<(MessageTarget, String)> {
+        for server in &self.servers {
+            if let Some(channel) = server.all_channels().into_iter().find(|c| c.id == conversation_id) {
+                return Some((
+                    MessageTarget::Channel(channel.id.clone()),
+                    format!("#{} · {}", channel.name, server.name),
+                ));
+            }
+        }
+        self.dm_channels
+            .iter()
+            .find(|dm| dm.id == conversation_id)
+            .map(|dm| {
+                (
+                    MessageTarget::DirectMessage(dm.id.clone()),
+                    format!("@{}", dm.display_name()),
+                )
+            })
+    }
+
+    /// Channels called `name` in any server, plus the DM with the user of
+    /// that name.
+    fn conversations_named(&self, name: &str) -> Vec<String> {
+        let channels = self
+            .servers
+            .iter()
+            .flat_map(|s| s.all_channels())
+            .filter(|c| c.name.eq_ignore_ascii_case(name))
+            .map(|c| c.id.clone());
+        let dms = self
+            .dm_channels
+            .iter()
+            .filter(|dm| dm.recipient.username.eq_ignore_ascii_case(name))
+            .map(|dm| dm.id.clone());
+        channels.chain(dms).collect()
+    }
+
+    fn user_ids_named(&self, name: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .servers
+            .iter()
+            .flat_map(|s| &s.members)
+            .chain(self.dm_channels.iter().map(|dm| &dm.recipient))
+            .chain(&self.auth.current_user)
+            .filter(|u| u.username.eq_ignore_ascii_case(name) || u.tag().eq_ignore_ascii_case(name))
+            .map(|u| u.id.clone())
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /// Make sure `message_id` is among the loaded messages of `target`,
+    /// bringing in cached messages around it if it isn't.
+    fn load_message_context(&mut self, target: &MessageTarget, message_id: &str) {
+        if self
+            .cached_messages(target)
+            .is_some_and(|messages| messages.iter().any(|m| m.id == message_id))
+        {
+            return;
+        }
+        let Some(store) = &self.message_store else {
+            return;
+        };
+        let context = match store.load_around(target.id(), message_id, JUMP_CONTEXT) {
+            Ok(context) => context,
+            Err(err) => {
+                eprintln!("Failed to load messages around {message_id}: {err:#}");
+                return;
+            }
+        };
+        let messages = self
+            .history_map(target)
+            .entry(target.id().to_string())
+            .or_default();
+        for message in context {
+            if !messages.iter().any(|m| m.id == message.id) {
+                messages.push(message);
+            }
+        }
+        messages.sort_by_key(|m| m.created_at);
+    }
+}
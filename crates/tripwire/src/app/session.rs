@@ -0,0 +1,153 @@
+//! Session lifecycle — logging in through the backend and keeping the token
+//! fresh until logout. The two-factor step lives in `two_factor`.
+
+use std::time::Duration;
+
+use gpui::Context;
+
+use crate::app::TripwireApp;
+use crate::backend::{AuthError, AuthSession};
+
+/// Refresh this long before the token actually expires.
+const REFRESH_MARGIN: Duration = Duration::from_secs(60);
+/// Wait before retrying a refresh that failed for a transient reason.
+const REFRESH_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+impl TripwireApp {
+    /// Pick up the session persisted on disk. A token that has already
+    /// expired is refreshed before anything is loaded with it.
+    pub(crate) fn restore_session(&mut self, cx: &mut Context<Self>) {
+        if self.auth.expires_in() == Some(Duration::ZERO) {
+            self.refresh_session(true, cx);
+        } else {
+            self.sync_backend_session(cx);
+        }
+    }
+
+    /// Log in with the credentials in the auth screen's inputs.
+    pub(crate) fn submit_login(&mut self, cx: &mut Context<Self>) {
+        if self.auth.login_pending {
+            return;
+        }
+        let login = self.email_input.read(cx).value().trim().to_string();
+        let password = self.password_input.read(cx).value().to_string();
+        if login.is_empty() {
+            self.auth.login_error = Some(AuthError::Validation("Email is required.".into()));
+            cx.notify();
+            return;
+        }
+        if password.is_empty() {
+            self.auth.login_error = Some(AuthError::Validation("Password is required.".into()));
+            cx.notify();
+            return;
+        }
+
+        self.auth.login_pending = true;
+        self.auth.login_error = None;
+        cx.notify();
+
+        let request = self.backend.login(&login, &password);
+        cx.spawn(async move |this, cx| {
+            let result = request.await;
+            this.update(cx, |this, cx| {
+                this.auth.login_pending = false;
+                match result {
+                    Ok(session) => this.start_session(session, cx),
+                    Err(AuthError::TwoFactorRequired { ticket }) => {
+                        this.auth.mfa_ticket = Some(ticket);
+                        this.use_backup_code = false;
+                        cx.notify();
+                    }
+                    Err(err) => {
+                        this.auth.login_error = Some(err);
+                        cx.notify();
+                    }
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Adopt a brand-new session and load everything for it. When adding
+    /// an account, the one that was active keeps its data for switching back.
+    pub(crate) fn start_session(&mut self, session: AuthSession, cx: &mut Context<Self>) {
+        if self.adding_account {
+            self.stash_account_cache();
+            self.adding_account = false;
+        }
+        self.account_caches.remove(&session.user.id);
+        self.auth.apply_session(session);
+        self.sync_backend_session(cx);
+    }
+
+    /// Arrange for the token to be refreshed shortly before it expires.
+    /// Replaces any previously scheduled refresh.
+    pub(crate) fn schedule_token_refresh(&mut self, cx: &mut Context<Self>) {
+        self._token_refresh = None;
+        let Some(expires_in) = self.auth.expires_in() else {
+            return;
+        };
+        let delay = expires_in.saturating_sub(REFRESH_MARGIN);
+        self.schedule_refresh_in(delay, cx);
+    }
+
+    fn schedule_refresh_in(&mut self, delay: Duration, cx: &mut Context<Self>) {
+        self._token_refresh = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(delay).await;
+            this.update(cx, |this, cx| this.refresh_session(false, cx))
+                .ok();
+        }));
+    }
+
+    /// Trade the refresh token for a new session. `reload` re-fetches
+    /// servers and DMs afterwards, for sessions that couldn't be used yet;
+    /// otherwise only the backend and gateway pick up the new token.
+    fn refresh_session(&mut self, reload: bool, cx: &mut Context<Self>) {
+        let Some(refresh_token) = self.auth.refresh_token.clone() else {
+            self.expire_session(cx);
+            return;
+        };
+
+        let request = self.backend.refresh_session(&refresh_token);
+        self._token_refresh = Some(cx.spawn(async move |this, cx| {
+            let result = request.await;
+            this.update(cx, |this, cx| match result {
+                Ok(session) if reload => this.start_session(session, cx),
+                Ok(session) => {
+                    this.auth.apply_session(session);
+                    this.backend.set_session(this.backend_session());
+                    this.connect_gateway(cx);
+                    this.schedule_token_refresh(cx);
+                }
+                Err(err) if err.is_transient() => {
+                    eprintln!("Failed to refresh session: {err}");
+                    let delay = match err {
+                        AuthError::RateLimited {
+                            retry_after: Some(after),
+                        } => after,
+                        _ => REFRESH_RETRY_DELAY,
+                    };
+                    if reload {
+                        // Nothing has been loaded yet; show what we can with
+                        // the stale token while retrying.
+                        this.sync_backend_session(cx);
+                    }
+                    this.schedule_refresh_in(delay, cx);
+                }
+                Err(_) => this.expire_session(cx),
+            })
+            .ok();
+        }));
+    }
+
+    /// The session can't be renewed; sign the account out and continue with
+    /// the next one, or return to the login screen.
+    fn expire_session(&mut self, cx: &mut Context<Self>) {
+        if let Some(user) = &self.auth.current_user {
+            self.account_caches.remove(&user.id);
+        }
+        self.auth.expire();
+        self.resume_active_account(cx);
+    }
+}
@@ -0,0 +1,243 @@
+//! Two-factor authentication — the second step of a login that needs one,
+//! and turning TOTP on or off from the Account settings screen.
+
+use gpui::{Context, Window};
+
+use crate::app::TripwireApp;
+use crate::backend::{AuthError, MfaCode, MfaEnrollment};
+use crate::totp;
+
+/// Where the Account screen's two-factor setup is at.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MfaSetupStep {
+    /// Waiting for the backend to hand out a secret.
+    Starting,
+    /// The secret is shown; the first code from the app turns TOTP on.
+    Verifying(MfaEnrollment),
+    /// TOTP is on; the backup codes are shown this one time.
+    BackupCodes(Vec<String>),
+    /// Asking for a code before turning TOTP off.
+    Disabling,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MfaSetup {
+    pub step: MfaSetupStep,
+    pub pending: bool,
+    pub error: Option<AuthError>,
+}
+
+impl TripwireApp {
+    /// The six-digit input filled up, so submit it to whichever form is
+    /// showing it.
+    pub(crate) fn on_otp_complete(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.auth.mfa_ticket.is_some() {
+            self.submit_mfa_code(window, cx);
+        } else if self.mfa_setup.is_some() {
+            self.submit_mfa_setup_code(window, cx);
+        }
+    }
+
+    /// Switch between the authenticator code and a backup code.
+    pub(crate) fn toggle_backup_code(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.use_backup_code = !self.use_backup_code;
+        self.clear_mfa_inputs(window, cx);
+        if let Some(setup) = &mut self.mfa_setup {
+            setup.error = None;
+        }
+        self.auth.login_error = None;
+        cx.notify();
+    }
+
+    /// Take the code out of whichever input is showing, clearing it for the
+    /// next attempt. `None` until a full code has been entered.
+    fn take_mfa_code(&mut self, window: &mut Window, cx: &mut Context<Self>) -> Option<MfaCode> {
+        let code = if self.use_backup_code {
+            let code = self.backup_code_input.read(cx).value().trim().to_string();
+            (!totp::normalize_backup_code(&code).is_empty()).then_some(MfaCode::Backup(code))
+        } else {
+            let code = self.otp_input.read(cx).value().to_string();
+            (code.len() == totp::DIGITS as usize).then_some(MfaCode::Totp(code))
+        };
+        if code.is_some() {
+            self.clear_mfa_inputs(window, cx);
+        }
+        code
+    }
+
+    fn clear_mfa_inputs(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.otp_input
+            .update(cx, |input, cx| input.set_value("", window, cx));
+        self.backup_code_input
+            .update(cx, |input, cx| input.set_value("", window, cx));
+    }
+
+    // ── Login challenge ─────────────────────────────────────────────────────
+
+    /// Finish a login that came back `TwoFactorRequired`.
+    pub(crate) fn submit_mfa_code(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.auth.login_pending {
+            return;
+        }
+        let Some(ticket) = self.auth.mfa_ticket.clone() else {
+            return;
+        };
+        let Some(code) = self.take_mfa_code(window, cx) else {
+            return;
+        };
+
+        self.auth.login_pending = true;
+        self.auth.login_error = None;
+        cx.notify();
+
+        let request = self.backend.verify_mfa(&ticket, code);
+        cx.spawn(async move |this, cx| {
+            let result = request.await;
+            this.update(cx, |this, cx| {
+                this.auth.login_pending = false;
+                match result {
+                    Ok(session) => {
+                        this.use_backup_code = false;
+                        this.start_session(session, cx);
+                    }
+                    Err(err) => {
+                        // The ticket is gone; start the login over.
+                        if err == AuthError::MfaTicketExpired {
+                            this.auth.mfa_ticket = None;
+                            this.use_backup_code = false;
+                        }
+                        this.auth.login_error = Some(err);
+                        cx.notify();
+                    }
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Give up on the second step and go back to the password form.
+    pub(crate) fn cancel_mfa_challenge(&mut self, cx: &mut Context<Self>) {
+        self.auth.mfa_ticket = None;
+        self.auth.login_error = None;
+        self.use_backup_code = false;
+        cx.notify();
+    }
+
+    // ── Enrollment ──────────────────────────────────────────────────────────
+
+    /// Ask the backend for a new secret to show in the Account screen.
+    pub(crate) fn begin_mfa_setup(&mut self, cx: &mut Context<Self>) {
+        self.use_backup_code = false;
+        self.mfa_setup = Some(MfaSetup {
+            step: MfaSetupStep::Starting,
+            pending: true,
+            error: None,
+        });
+        cx.notify();
+
+        let request = self.backend.begin_mfa_enrollment();
+        cx.spawn(async move |this, cx| {
+            let result = request.await;
+            this.update(cx, |this, cx| {
+                let Some(setup) = &mut this.mfa_setup else {
+                    return;
+                };
+                setup.pending = false;
+                match result {
+                    Ok(enrollment) => setup.step = MfaSetupStep::Verifying(enrollment),
+                    Err(err) => setup.error = Some(err),
+                }
+                cx.notify();
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Ask for a code before turning two-factor authentication off.
+    pub(crate) fn begin_mfa_disable(&mut self, cx: &mut Context<Self>) {
+        self.use_backup_code = false;
+        self.mfa_setup = Some(MfaSetup {
+            step: MfaSetupStep::Disabling,
+            pending: false,
+            error: None,
+        });
+        cx.notify();
+    }
+
+    pub(crate) fn close_mfa_setup(&mut self, cx: &mut Context<Self>) {
+        self.mfa_setup = None;
+        self.use_backup_code = false;
+        cx.notify();
+    }
+
+    /// Submit the entered code to finish enabling or disabling.
+    pub(crate) fn submit_mfa_setup_code(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(setup) = &self.mfa_setup else {
+            return;
+        };
+        if setup.pending {
+            return;
+        }
+        let disabling = match &setup.step {
+            MfaSetupStep::Verifying(_) => false,
+            MfaSetupStep::Disabling => true,
+            MfaSetupStep::Starting | MfaSetupStep::BackupCodes(_) => return,
+        };
+        let Some(code) = self.take_mfa_code(window, cx) else {
+            return;
+        };
+        if let Some(setup) = &mut self.mfa_setup {
+            setup.pending = true;
+            setup.error = None;
+        }
+        cx.notify();
+
+        if disabling {
+            let request = self.backend.disable_mfa(code);
+            cx.spawn(async move |this, cx| {
+                let result = request.await;
+                this.update(cx, |this, cx| match result {
+                    Ok(()) => {
+                        this.auth.set_mfa_enabled(false);
+                        this.close_mfa_setup(cx);
+                    }
+                    Err(err) => this.fail_mfa_setup(err, cx),
+                })
+                .ok();
+            })
+            .detach();
+        } else {
+            let MfaCode::Totp(code) = code else {
+                return;
+            };
+            let request = self.backend.enable_mfa(&code);
+            cx.spawn(async move |this, cx| {
+                let result = request.await;
+                this.update(cx, |this, cx| match result {
+                    Ok(backup_codes) => {
+                        this.auth.set_mfa_enabled(true);
+                        this.mfa_setup = Some(MfaSetup {
+                            step: MfaSetupStep::BackupCodes(backup_codes),
+                            pending: false,
+                            error: None,
+                        });
+                        cx.notify();
+                    }
+                    Err(err) => this.fail_mfa_setup(err, cx),
+                })
+                .ok();
+            })
+            .detach();
+        }
+    }
+
+    fn fail_mfa_setup(&mut self, err: AuthError, cx: &mut Context<Self>) {
+        if let Some(setup) = &mut self.mfa_setup {
+            setup.pending = false;
+            setup.error = Some(err);
+        }
+        cx.notify();
+    }
+}
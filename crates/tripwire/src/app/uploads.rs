@@ -0,0 +1,400 @@
+//! Attachment uploads and downloads — files start uploading as soon as
+//! they're attached (picked, dropped or pasted), with progress in the
+//! composer and a cancel button; sending waits for them. Attachments on
+//! messages are saved to disk, or images copied, through the backend.
+//!
+//! Files that only exist to be uploaded or copied, like pasted images, are
+//! kept in a `tmp` directory of the data dir only this user can open, and
+//! deleted as soon as they're done with.
+
+use std::io::Write as _;
+use std::path::PathBuf;
+
+use futures::{FutureExt as _, StreamExt as _};
+use gpui::{AppContext as _, ClipboardEntry, ClipboardItem, Context, ExternalPaths, Image, ImageFormat, Task, Window};
+use gpui_component::WindowExt as _;
+use gpui_component::notification::Notification;
+
+use crate::app::TripwireApp;
+use crate::attachments::AttachmentUpload;
+use crate::backend::MessageTarget;
+use crate::models::Attachment;
+use crate::permissions::Permissions;
+
+/// The composer a file was attached in; it's sent from there only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Composer {
+    Conversation(MessageTarget),
+    /// The reply box of the thread under a message of the conversation.
+    Thread(MessageTarget, String),
+}
+
+impl Composer {
+    /// The conversation files attached here are uploaded to.
+    pub fn target(&self) -> &MessageTarget {
+        match self {
+            Composer::Conversation(target) | Composer::Thread(target, _) => target,
+        }
+    }
+}
+
+/// A file attached in a composer.
+pub(crate) struct PendingUpload {
+    pub id: usize,
+    pub composer: Composer,
+    pub file: AttachmentUpload,
+    pub state: UploadState,
+    /// Dropping it cancels the upload.
+    _task: Option<Task<()>>,
+    /// The file, if it was written just for this upload; dropping it
+    /// deletes it.
+    _temp_file: Option<tempfile::TempPath>,
+}
+
+pub(crate) enum UploadState {
+    Uploading { sent: u64 },
+    Done(Attachment),
+    Failed(String),
+}
+
+impl PendingUpload {
+    /// How much is uploaded, from 0 to 100.
+    pub fn percent(&self) -> f32 {
+        match self.state {
+            UploadState::Uploading { sent } if self.file.size > 0 => sent as f32 / self.file.size as f32 * 100.,
+            UploadState::Uploading { .. } | UploadState::Failed(_) => 0.,
+            UploadState::Done(_) => 100.,
+        }
+    }
+}
+
+impl TripwireApp {
+    /// The main composer of the open conversation.
+    pub(crate) fn conversation_composer(&self) -> Option<Composer> {
+        self.active_target().map(Composer::Conversation)
+    }
+
+    /// The reply box of the open thread.
+    pub(crate) fn thread_composer(&self) -> Option<Composer> {
+        let thread_id = self.open_thread_id.clone()?;
+        Some(Composer::Thread(self.active_target()?, thread_id))
+    }
+
+    /// Ask for files and attach them to `composer`.
+    pub(crate) fn attach_files(&mut self, composer: Composer, window: &mut Window, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(gpui::PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: true,
+            prompt: Some("Attach".into()),
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            this.update_in(cx, |this, window, cx| {
+                for path in paths {
+                    this.attach_path(path, composer.clone(), window, cx);
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Attach files dropped onto `composer`'s part of the window.
+    pub(crate) fn attach_dropped(
+        &mut self,
+        paths: &ExternalPaths,
+        composer: Composer,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        for path in paths.paths() {
+            if path.is_dir() {
+                window.push_notification(
+                    Notification::warning(format!("{} is a folder; only files can be attached", path.display())),
+                    cx,
+                );
+                continue;
+            }
+            self.attach_path(path.clone(), composer.clone(), window, cx);
+        }
+    }
+
+    /// Attach the image on the clipboard, if there is one, to `composer`.
+    /// Returns whether it did; otherwise the paste is left to the input.
+    pub(crate) fn paste_image(&mut self, composer: Composer, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        if !self.can(Permissions::ATTACH_FILES) {
+            return false;
+        }
+        let Some(item) = cx.read_from_clipboard() else {
+            return false;
+        };
+        let Some(image) = item.entries().iter().find_map(|entry| match entry {
+            ClipboardEntry::Image(image) => Some(image),
+            ClipboardEntry::String(_) => None,
+        }) else {
+            return false;
+        };
+        // Uploads stream from disk, so the image goes to a file first.
+        let written = self.scratch_dir().and_then(|dir| {
+            let mut file = tempfile::Builder::new()
+                .prefix("pasted-image-")
+                .suffix(&format!(".{}", image_extension(image.format)))
+                .tempfile_in(dir)?;
+            file.write_all(&image.bytes)?;
+            Ok(file.into_temp_path())
+        });
+        match written {
+            Ok(temp_file) => self.attach_file(temp_file.to_path_buf(), Some(temp_file), composer, window, cx),
+            Err(err) => {
+                window.push_notification(Notification::error(format!("Couldn't attach the pasted image: {err}")), cx)
+            }
+        }
+        true
+    }
+
+    /// Attach the file at `path` to `composer` and start uploading it,
+    /// unless it breaks the server's limits.
+    pub(crate) fn attach_path(&mut self, path: PathBuf, composer: Composer, window: &mut Window, cx: &mut Context<Self>) {
+        self.attach_file(path, None, composer, window, cx);
+    }
+
+    /// `attach_path`, deleting `temp_file` once the upload is done with.
+    fn attach_file(
+        &mut self,
+        path: PathBuf,
+        temp_file: Option<tempfile::TempPath>,
+        composer: Composer,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if !self.can(Permissions::ATTACH_FILES) {
+            window.push_notification(Notification::warning("You don't have permission to attach files here"), cx);
+            return;
+        }
+        let file = match AttachmentUpload::from_path(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                window.push_notification(Notification::error(format!("Couldn't attach {}: {err}", path.display())), cx);
+                return;
+            }
+        };
+        // Failed uploads stay listed until removed, but aren't sent.
+        let attached = self
+            .pending_uploads_for(&composer)
+            .filter(|u| !matches!(u.state, UploadState::Failed(_)))
+            .count();
+        if let Err(err) = self.upload_limits.check(&file, attached) {
+            window.push_notification(Notification::error(err.to_string()), cx);
+            return;
+        }
+
+        let id = self.next_upload_id;
+        self.next_upload_id += 1;
+        let (progress, mut sent) = futures::channel::mpsc::unbounded();
+        let mut upload = self.backend.upload_attachment(composer.target(), file.clone(), progress).fuse();
+        let task = cx.spawn(async move |this, cx| {
+            loop {
+                futures::select_biased! {
+                    result = upload => {
+                        this.update(cx, |this, cx| this.finish_upload(id, result, cx)).ok();
+                        break;
+                    }
+                    bytes = sent.next() => {
+                        let Some(bytes) = bytes else { continue };
+                        let updated = this.update(cx, |this, cx| {
+                            if let Some(upload) = this.pending_uploads.iter_mut().find(|u| u.id == id) {
+                                upload.state = UploadState::Uploading { sent: bytes };
+                                cx.notify();
+                            }
+                        });
+                        if updated.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+        self.pending_uploads.push(PendingUpload {
+            id,
+            composer,
+            file,
+            state: UploadState::Uploading { sent: 0 },
+            _task: Some(task),
+            _temp_file: temp_file,
+        });
+        cx.notify();
+    }
+
+    fn finish_upload(&mut self, id: usize, result: anyhow::Result<Attachment>, cx: &mut Context<Self>) {
+        let Some(upload) = self.pending_uploads.iter_mut().find(|u| u.id == id) else {
+            return;
+        };
+        upload._task = None;
+        upload._temp_file = None;
+        match result {
+            Ok(attachment) => upload.state = UploadState::Done(attachment),
+            Err(err) => {
+                let message = format!("Couldn't upload {}: {err:#}", upload.file.filename);
+                upload.state = UploadState::Failed(format!("{err:#}"));
+                self.show_error(message, cx);
+            }
+        }
+        cx.notify();
+    }
+
+    /// Stop uploading a file, or take an uploaded one off the message.
+    pub(crate) fn cancel_upload(&mut self, id: usize, cx: &mut Context<Self>) {
+        self.pending_uploads.retain(|u| u.id != id);
+        cx.notify();
+    }
+
+    pub(crate) fn pending_uploads_for<'a>(
+        &'a self,
+        composer: &'a Composer,
+    ) -> impl Iterator<Item = &'a PendingUpload> + 'a {
+        self.pending_uploads.iter().filter(move |u| u.composer == *composer)
+    }
+
+    /// The uploaded attachments of `composer`, taken off it, or why the
+    /// message can't be sent yet: some are still uploading, or failed and
+    /// have to be removed first so they aren't silently left out.
+    pub(crate) fn take_attachments(&mut self, composer: &Composer) -> Result<Vec<Attachment>, &'static str> {
+        for upload in self.pending_uploads_for(composer) {
+            match upload.state {
+                UploadState::Uploading { .. } => return Err("Wait for your files to finish uploading"),
+                UploadState::Failed(_) => return Err("Remove the files that failed to upload before sending"),
+                UploadState::Done(_) => {}
+            }
+        }
+        let (taken, kept) = std::mem::take(&mut self.pending_uploads)
+            .into_iter()
+            .partition::<Vec<_>, _>(|u| u.composer == *composer);
+        self.pending_uploads = kept;
+        Ok(taken
+            .into_iter()
+            .filter_map(|u| match u.state {
+                UploadState::Done(attachment) => Some(attachment),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Fetch the server's attachment limits.
+    pub(crate) fn load_upload_limits(&mut self, cx: &mut Context<Self>) {
+        let limits = self.backend.upload_limits();
+        cx.spawn(async move |this, cx| {
+            let limits = limits.await;
+            this.update(cx, |this, _| match limits {
+                Ok(limits) => this.upload_limits = limits,
+                Err(err) => eprintln!("Failed to load upload limits: {err:#}"),
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Ask where to save `attachment`, then download it there.
+    pub(crate) fn save_attachment(&mut self, attachment: Attachment, window: &mut Window, cx: &mut Context<Self>) {
+        let directory = std::env::home_dir()
+            .map(|home| home.join("Downloads"))
+            .filter(|dir| dir.is_dir())
+            .or_else(std::env::home_dir)
+            .unwrap_or_default();
+        let dest = cx.prompt_for_new_path(&directory, Some(&attachment.filename));
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(Ok(Some(dest))) = dest.await else {
+                return;
+            };
+            let Ok(download) = this.update(cx, |this, _| this.backend.download_attachment(&attachment, dest)) else {
+                return;
+            };
+            let result = cx.background_spawn(download).await;
+            this.update(cx, |this, cx| match result {
+                Ok(()) => this.show_toast(Notification::success(format!("Saved {}", attachment.filename)), cx),
+                Err(err) => this.show_error(format!("Couldn't save {}: {err:#}", attachment.filename), cx),
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Put the image `attachment` on the clipboard.
+    pub(crate) fn copy_image(&mut self, attachment: Attachment, cx: &mut Context<Self>) {
+        let Some(format) = ImageFormat::from_mime_type(&attachment.mime_type) else {
+            self.show_error(format!("Couldn't copy {}: not an image", attachment.filename), cx);
+            return;
+        };
+        let bytes = match attachment.local_path() {
+            Some(path) => {
+                let path = path.to_path_buf();
+                cx.background_spawn(async move { anyhow::Ok(std::fs::read(path)?) })
+            }
+            // Downloaded to a temporary file, like pasted images are uploaded.
+            None => {
+                let dest = self
+                    .scratch_dir()
+                    .and_then(|dir| tempfile::Builder::new().prefix("copied-image-").tempfile_in(dir));
+                let dest = match dest {
+                    Ok(dest) => dest.into_temp_path(),
+                    Err(err) => {
+                        self.show_error(format!("Couldn't copy {}: {err}", attachment.filename), cx);
+                        return;
+                    }
+                };
+                let download = self.backend.download_attachment(&attachment, dest.to_path_buf());
+                cx.background_spawn(async move {
+                    download.await?;
+                    anyhow::Ok(std::fs::read(&dest)?)
+                })
+            }
+        };
+        cx.spawn(async move |this, cx| {
+            let bytes = bytes.await;
+            this.update(cx, |this, cx| match bytes {
+                Ok(bytes) => {
+                    cx.write_to_clipboard(ClipboardItem::new_image(&Image::from_bytes(format, bytes)));
+                    this.show_toast(Notification::success(format!("Copied {}", attachment.filename)), cx);
+                }
+                Err(err) => this.show_error(format!("Couldn't copy {}: {err:#}", attachment.filename), cx),
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// The directory for files that only exist to be uploaded or copied,
+    /// readable by this user only.
+    fn scratch_dir(&self) -> std::io::Result<PathBuf> {
+        let dir = self.auth.data_dir().join("tmp");
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt as _;
+            builder.mode(0o700);
+        }
+        builder.create(&dir)?;
+        // `mode` only applies when the directory is created.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt as _;
+            std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700))?;
+        }
+        Ok(dir)
+    }
+}
+
+fn image_extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "png",
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Webp => "webp",
+        ImageFormat::Gif => "gif",
+        ImageFormat::Svg => "svg",
+        ImageFormat::Bmp => "bmp",
+        ImageFormat::Tiff => "tiff",
+    }
+}
@@ -0,0 +1,269 @@
+//! Attachments — what a file is (sniffed from its first bytes, falling back
+//! to its extension), how big it may be, and the upload handed to the
+//! backend.
+
+use std::fmt;
+use std::fs::File;
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+
+/// How many leading bytes `sniff_mime` looks at.
+const SNIFF_LEN: usize = 512;
+
+/// Limits on attachments, set by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadLimits {
+    /// Largest file accepted, in bytes.
+    pub max_file_size: u64,
+    /// Most files one message may carry.
+    pub max_files: usize,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        Self {
+            max_file_size: 25 * 1024 * 1024,
+            max_files: 10,
+        }
+    }
+}
+
+/// Why a file can't be attached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitError {
+    TooLarge { filename: String, size: u64, max: u64 },
+    TooMany { max: usize },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::TooLarge { filename, size, max } => write!(
+                f,
+                "{filename} is {}; files can be at most {}",
+                format_size(*size),
+                format_size(*max)
+            ),
+            LimitError::TooMany { max } => write!(f, "A message can carry at most {max} files"),
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl UploadLimits {
+    /// Whether `file` may join the `attached` files already on a message.
+    pub fn check(&self, file: &AttachmentUpload, attached: usize) -> Result<(), LimitError> {
+        if attached >= self.max_files {
+            return Err(LimitError::TooMany { max: self.max_files });
+        }
+        if file.size > self.max_file_size {
+            return Err(LimitError::TooLarge {
+                filename: file.filename.clone(),
+                size: file.size,
+                max: self.max_file_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// A local file about to be uploaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachmentUpload {
+    pub path: PathBuf,
+    pub filename: String,
+    pub mime_type: String,
+    pub size: u64,
+}
+
+impl AttachmentUpload {
+    /// Look at the file at `path`: its name, size and type.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let size = file.metadata()?.len();
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        file.by_ref().take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".to_string());
+        Ok(Self {
+            path: path.to_path_buf(),
+            mime_type: sniff_mime(&head, &filename).to_string(),
+            filename,
+            size,
+        })
+    }
+}
+
+/// Magic numbers of the formats worth telling apart.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+    (b"\x1a\x45\xdf\xa3", "video/webm"),
+    (b"OggS", "audio/ogg"),
+    (b"fLaC", "audio/flac"),
+    (b"ID3", "audio/mpeg"),
+];
+
+/// Formats that are ZIP archives inside, told apart by their extension.
+const ZIP_BASED: &[&str] = &[
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    "application/vnd.oasis.opendocument.text",
+    "application/vnd.oasis.opendocument.spreadsheet",
+    "application/vnd.oasis.opendocument.presentation",
+    "application/epub+zip",
+];
+
+/// The MIME type of a file starting with `head`, named `filename`.
+pub fn sniff_mime(head: &[u8], filename: &str) -> &'static str {
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(magic, _)| head.starts_with(magic)) {
+        if *mime == "application/zip"
+            && let Some(zip_based) = mime_from_extension(filename).filter(|mime| ZIP_BASED.contains(mime))
+        {
+            return zip_based;
+        }
+        return mime;
+    }
+    // RIFF and ISO media containers say what they hold after a header.
+    match (head.get(..4), head.get(8..12)) {
+        (Some(b"RIFF"), Some(b"WEBP")) => return "image/webp",
+        (Some(b"RIFF"), Some(b"WAVE")) => return "audio/wav",
+        (_, Some(b"qt  ")) if head.get(4..8) == Some(b"ftyp") => return "video/quicktime",
+        _ if head.get(4..8) == Some(b"ftyp") => return "video/mp4",
+        _ => {}
+    }
+    if let Some(mime) = mime_from_extension(filename) {
+        return mime;
+    }
+    // `head` may end partway through a character.
+    let utf8 = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+    if utf8 && !head.contains(&0) {
+        return "text/plain";
+    }
+    "application/octet-stream"
+}
+
+fn mime_from_extension(filename: &str) -> Option<&'static str> {
+    let (_, extension) = filename.rsplit_once('.')?;
+    Some(match extension.to_ascii_lowercase().as_str() {
+        "svg" => "image/svg+xml",
+        "txt" | "log" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "rs" | "py" | "js" | "ts" | "toml" | "yaml" | "yml" => "text/plain",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        "odt" => "application/vnd.oasis.opendocument.text",
+        "ods" => "application/vnd.oasis.opendocument.spreadsheet",
+        "odp" => "application/vnd.oasis.opendocument.presentation",
+        "epub" => "application/epub+zip",
+        _ => return None,
+    })
+}
+
+/// `1.5 MB` style size for people.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 {
+        format!("{size:.1} {}", UNITS[unit])
+    } else {
+        format!("{size:.0} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniffs_content_before_extension() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n....", "photo.jpg"), "image/png");
+        assert_eq!(sniff_mime(b"RIFF\x10\0\0\0WEBPVP8 ", "x"), "image/webp");
+        assert_eq!(sniff_mime(b"\0\0\0\x18ftypmp42", "clip"), "video/mp4");
+        assert_eq!(sniff_mime(b"%PDF-1.7", "report.bin"), "application/pdf");
+        assert_eq!(sniff_mime(b"<svg xmlns=", "logo.svg"), "image/svg+xml");
+        assert_eq!(sniff_mime(b"fn main() {}", "main"), "text/plain");
+        assert_eq!(sniff_mime(b"\0\x01\x02\x03", "blob"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_zip_based_formats_go_by_extension() {
+        assert_eq!(
+            sniff_mime(b"PK\x03\x04\x14\0", "Report.DOCX"),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        );
+        assert_eq!(
+            sniff_mime(b"PK\x03\x04\x14\0", "budget.xlsx"),
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+        );
+        assert_eq!(sniff_mime(b"PK\x03\x04\x14\0", "photos.zip"), "application/zip");
+        // Only a ZIP archive passes for one.
+        assert_eq!(sniff_mime(b"PK\x03\x04\x14\0", "notes.txt"), "application/zip");
+        assert_eq!(sniff_mime(b"%PDF-1.7", "fake.docx"), "application/pdf");
+    }
+
+    #[test]
+    fn test_formats_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(25 * 1024 * 1024), "25 MB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = UploadLimits {
+            max_file_size: 1024,
+            max_files: 2,
+        };
+        let file = |size| AttachmentUpload {
+            path: PathBuf::from("a.bin"),
+            filename: "a.bin".into(),
+            mime_type: "application/octet-stream".into(),
+            size,
+        };
+        assert_eq!(limits.check(&file(1024), 1), Ok(()));
+        assert_eq!(limits.check(&file(10), 2), Err(LimitError::TooMany { max: 2 }));
+        let too_large = limits.check(&file(2048), 0).unwrap_err();
+        assert_eq!(too_large.to_string(), "a.bin is 2.0 KB; files can be at most 1.0 KB");
+    }
+
+    #[test]
+    fn test_reads_uploads_from_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes");
+        std::fs::write(&path, "hello").unwrap();
+        let upload = AttachmentUpload::from_path(&path).unwrap();
+        assert_eq!((upload.filename.as_str(), upload.size), ("notes", 5));
+        assert_eq!(upload.mime_type, "text/plain");
+        assert!(AttachmentUpload::from_path(&dir.path().join("missing")).is_err());
+    }
+}
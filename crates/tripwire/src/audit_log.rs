@@ -0,0 +1,345 @@
+//! Audit log — a server's record of administrative actions: who changed
+//! which channel, role, member or message, what changed, and why.
+//!
+//! Changes are before/after pairs per field, as text. For sets — a role's
+//! permissions, a member's roles — only what differs is listed: `old` what
+//! was taken away, `new` what was added.
+
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Channel, Role, Server};
+use crate::permissions::{self, PermissionOverwrite, Permissions};
+
+/// How many entries a page of the log holds.
+pub const PAGE_SIZE: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    ChannelCreate,
+    ChannelUpdate,
+    ChannelDelete,
+    RoleCreate,
+    RoleUpdate,
+    RoleDelete,
+    MemberKick,
+    MemberBan,
+    MemberTimeout,
+    MemberTimeoutRemove,
+    /// Nickname changed.
+    MemberUpdate,
+    MemberRoleUpdate,
+    /// Someone else's message deleted.
+    MessageDelete,
+    MessagePin,
+    MessageUnpin,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 15] = [
+        AuditAction::ChannelCreate,
+        AuditAction::ChannelUpdate,
+        AuditAction::ChannelDelete,
+        AuditAction::RoleCreate,
+        AuditAction::RoleUpdate,
+        AuditAction::RoleDelete,
+        AuditAction::MemberKick,
+        AuditAction::MemberBan,
+        AuditAction::MemberTimeout,
+        AuditAction::MemberTimeoutRemove,
+        AuditAction::MemberUpdate,
+        AuditAction::MemberRoleUpdate,
+        AuditAction::MessageDelete,
+        AuditAction::MessagePin,
+        AuditAction::MessageUnpin,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AuditAction::ChannelCreate => "Created channel",
+            AuditAction::ChannelUpdate => "Updated channel",
+            AuditAction::ChannelDelete => "Deleted channel",
+            AuditAction::RoleCreate => "Created role",
+            AuditAction::RoleUpdate => "Updated role",
+            AuditAction::RoleDelete => "Deleted role",
+            AuditAction::MemberKick => "Kicked member",
+            AuditAction::MemberBan => "Banned member",
+            AuditAction::MemberTimeout => "Timed out member",
+            AuditAction::MemberTimeoutRemove => "Removed timeout",
+            AuditAction::MemberUpdate => "Changed nickname",
+            AuditAction::MemberRoleUpdate => "Changed roles",
+            AuditAction::MessageDelete => "Deleted message",
+            AuditAction::MessagePin => "Pinned message",
+            AuditAction::MessageUnpin => "Unpinned message",
+        }
+    }
+}
+
+/// One field's value before and after, `None` where it had none.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditChange {
+    pub key: String,
+    #[serde(default)]
+    pub old: Option<String>,
+    #[serde(default)]
+    pub new: Option<String>,
+}
+
+/// `key` going from `old` to `new`, unless it didn't change.
+pub fn change(key: &str, old: Option<String>, new: Option<String>) -> Option<AuditChange> {
+    (old != new).then(|| AuditChange {
+        key: key.to_string(),
+        old,
+        new,
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub id: String,
+    /// Who did it.
+    pub actor_id: String,
+    pub action: AuditAction,
+    /// The channel, role, member or message author it was done to.
+    #[serde(default)]
+    pub target_id: Option<String>,
+    /// Where, for message actions.
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub changes: Vec<AuditChange>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub created_at: SystemTime,
+}
+
+/// A page of a server's log: entries older than `before`, newest first,
+/// by `user_id` and of `action` if given.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditLogQuery {
+    pub user_id: Option<String>,
+    pub action: Option<AuditAction>,
+    /// ID of the last entry of the previous page.
+    pub before: Option<String>,
+}
+
+impl AuditLogQuery {
+    pub fn matches(&self, entry: &AuditLogEntry) -> bool {
+        self.user_id.as_ref().is_none_or(|id| entry.actor_id == *id)
+            && self.action.is_none_or(|action| entry.action == action)
+    }
+
+    /// The page of `log`, oldest first, that this asks for.
+    pub fn page(&self, log: &[AuditLogEntry]) -> Vec<AuditLogEntry> {
+        let end = self
+            .before
+            .as_ref()
+            .and_then(|before| log.iter().position(|entry| entry.id == *before))
+            .unwrap_or(log.len());
+        log[..end]
+            .iter()
+            .rev()
+            .filter(|entry| self.matches(entry))
+            .take(PAGE_SIZE)
+            .cloned()
+            .collect()
+    }
+}
+
+/// What changed about a role between `old` and `new`; either is `None`
+/// when the role was created or deleted.
+pub fn role_changes(old: Option<&Role>, new: Option<&Role>) -> Vec<AuditChange> {
+    let field = |key: &str, f: fn(&Role) -> Option<String>| change(key, old.and_then(f), new.and_then(f));
+    let granted = |role: Option<&Role>| role.map_or(Permissions::empty(), |role| role.permissions);
+    let (had, has) = (granted(old), granted(new));
+    [
+        field("name", |role| Some(role.name.clone())),
+        field("color", |role| Some(role.color.clone())),
+        field("hoist", |role| role.hoist.then(|| "yes".to_string())),
+        field("mentionable", |role| role.mentionable.then(|| "yes".to_string())),
+        change(
+            "permissions",
+            permission_names(had.difference(has)),
+            permission_names(has.difference(had)),
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// What changed about a channel of `server` between `old` and `new`;
+/// either is `None` when the channel was created or deleted. Overwrites
+/// are listed per role or member.
+pub fn channel_changes(server: &Server, old: Option<&Channel>, new: Option<&Channel>) -> Vec<AuditChange> {
+    let field = |key: &str, f: fn(&Channel) -> Option<String>| change(key, old.and_then(f), new.and_then(f));
+    let mut changes: Vec<AuditChange> = [
+        field("name", |channel| Some(channel.name.clone())),
+        field("type", |channel| Some(channel.kind.label().to_string())),
+        field("topic", |channel| channel.topic.clone()),
+        field("slowmode", |channel| {
+            (channel.slowmode_secs > 0).then(|| format_duration(Duration::from_secs(channel.slowmode_secs.into())))
+        }),
+        field("nsfw", |channel| channel.nsfw.then(|| "yes".to_string())),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let had = old.map_or(&[][..], |channel| channel.overwrites.as_slice());
+    let has = new.map_or(&[][..], |channel| channel.overwrites.as_slice());
+    let mut seen = Vec::new();
+    for target in had.iter().chain(has).map(|o| &o.target) {
+        if seen.contains(&target) {
+            continue;
+        }
+        seen.push(target);
+        let describe = |overwrites: &[PermissionOverwrite]| {
+            let overwrite = overwrites.iter().find(|o| o.target == *target)?;
+            let parts: Vec<String> = [("allow", overwrite.allow), ("deny", overwrite.deny)]
+                .into_iter()
+                .filter_map(|(verb, flags)| Some(format!("{verb} {}", permission_names(flags)?)))
+                .collect();
+            (!parts.is_empty()).then(|| parts.join("; "))
+        };
+        changes.extend(change(&format!("overwrites for {}", target.name(server)), describe(had), describe(has)));
+    }
+    changes
+}
+
+/// Names of `flags` as settings show them, or `None` if there are none.
+fn permission_names(flags: Permissions) -> Option<String> {
+    let names: Vec<&str> = permissions::GROUPS
+        .iter()
+        .flat_map(|(_, infos)| infos.iter())
+        .filter(|info| flags.contains(info.flag))
+        .map(|info| info.name)
+        .collect();
+    (!names.is_empty()).then(|| names.join(", "))
+}
+
+/// The roles a member lost and gained, by name.
+pub fn member_role_change(roles: &[Role], old: &[String], new: &[String]) -> Option<AuditChange> {
+    let names = |from: &[String], without: &[String]| {
+        let names: Vec<&str> = roles
+            .iter()
+            .filter(|role| from.contains(&role.id) && !without.contains(&role.id))
+            .map(|role| role.name.as_str())
+            .collect();
+        (!names.is_empty()).then(|| names.join(", "))
+    };
+    change("roles", names(old, new), names(new, old))
+}
+
+/// A span of time as "45 seconds", "10 minutes", "1 day" and so on,
+/// rounded down to its largest unit.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (count, unit) = match secs {
+        0..60 => (secs, "second"),
+        60..3_600 => (secs / 60, "minute"),
+        3_600..86_400 => (secs / 3_600, "hour"),
+        _ => (secs / 86_400, "day"),
+    };
+    format!("{count} {unit}{}", if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(id: &str, permissions: Permissions) -> Role {
+        Role {
+            id: id.to_string(),
+            name: id.to_string(),
+            color: "#99AAB5".to_string(),
+            permissions,
+            hoist: false,
+            mentionable: false,
+        }
+    }
+
+    fn entry(id: usize, actor_id: &str, action: AuditAction) -> AuditLogEntry {
+        AuditLogEntry {
+            id: id.to_string(),
+            actor_id: actor_id.to_string(),
+            action,
+            target_id: None,
+            channel_id: None,
+            changes: Vec::new(),
+            reason: None,
+            created_at: SystemTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_pages_newest_first() {
+        let log: Vec<AuditLogEntry> = (0..120)
+            .map(|ix| {
+                let action = if ix % 2 == 0 { AuditAction::MemberKick } else { AuditAction::RoleUpdate };
+                entry(ix, if ix % 3 == 0 { "a" } else { "b" }, action)
+            })
+            .collect();
+        let ids = |page: Vec<AuditLogEntry>| page.into_iter().map(|entry| entry.id).collect::<Vec<_>>();
+
+        let first = ids(AuditLogQuery::default().page(&log));
+        assert_eq!((first.len(), first[0].as_str(), first[49].as_str()), (PAGE_SIZE, "119", "70"));
+        let next = AuditLogQuery {
+            before: Some("70".into()),
+            ..AuditLogQuery::default()
+        };
+        assert_eq!(ids(next.page(&log))[0], "69");
+
+        let kicks_by_a = AuditLogQuery {
+            user_id: Some("a".into()),
+            action: Some(AuditAction::MemberKick),
+            before: None,
+        };
+        assert_eq!(ids(kicks_by_a.page(&log))[..3], ["114", "108", "102"]);
+    }
+
+    #[test]
+    fn test_diffs_roles() {
+        let old = role("mod", Permissions::KICK_MEMBERS | Permissions::BAN_MEMBERS);
+        let mut new = old.clone();
+        new.name = "Moderator".into();
+        new.permissions = Permissions::KICK_MEMBERS | Permissions::MANAGE_MESSAGES;
+        let changes = role_changes(Some(&old), Some(&new));
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[0],
+            AuditChange {
+                key: "name".into(),
+                old: Some("mod".into()),
+                new: Some("Moderator".into()),
+            }
+        );
+        assert_eq!(
+            (changes[1].old.as_deref(), changes[1].new.as_deref()),
+            (Some("Ban Members"), Some("Manage Messages"))
+        );
+
+        // Created roles list everything they were made with.
+        let created = role_changes(None, Some(&old));
+        assert_eq!(created.iter().map(|c| c.key.as_str()).collect::<Vec<_>>(), ["name", "color", "permissions"]);
+        assert!(role_changes(Some(&old), Some(&old)).is_empty());
+    }
+
+    #[test]
+    fn test_diffs_member_roles() {
+        let roles = [role("a", Permissions::empty()), role("b", Permissions::empty()), role("c", Permissions::empty())];
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let change = member_role_change(&roles, &ids(&["a", "b"]), &ids(&["b", "c"])).unwrap();
+        assert_eq!((change.old.as_deref(), change.new.as_deref()), (Some("a"), Some("c")));
+        assert!(member_role_change(&roles, &ids(&["a"]), &ids(&["a"])).is_none());
+    }
+
+    #[test]
+    fn test_formats_durations() {
+        assert_eq!(format_duration(Duration::from_secs(60)), "1 minute");
+        assert_eq!(format_duration(Duration::from_secs(2 * 3_600 + 5)), "2 hours");
+        assert_eq!(format_duration(Duration::from_secs(7 * 86_400)), "7 days");
+    }
+}
@@ -1,108 +1,280 @@
+use anyhow::{Context as _, Result};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+use zeroize::{Zeroize as _, Zeroizing};
+
+use crate::backend::{AuthError, AuthSession};
 use crate::models::{User, UserStatus};
+use crate::secure_store::{self, SecretStore};
 
-/// Persisted auth data stored to disk.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Persisted auth data, kept in the platform `SecretStore`. Tokens are wiped
+/// from memory when it's dropped.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct StoredAuth {
     pub token: String,
     pub user: User,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<SystemTime>,
+    #[serde(default)]
+    pub mfa_enabled: bool,
+}
+
+impl Drop for StoredAuth {
+    fn drop(&mut self) {
+        self.token.zeroize();
+        self.refresh_token.zeroize();
+    }
+}
+
+/// Everything kept in the `SecretStore`: every signed-in account and which
+/// one is active.
+#[derive(Default, Deserialize)]
+struct StoredAccounts {
+    active: Option<String>,
+    accounts: Vec<StoredAuth>,
+}
+
+/// `StoredAccounts` as written, borrowing the tokens instead of copying
+/// them somewhere they wouldn't be wiped.
+#[derive(Serialize)]
+struct PersistedAuth<'a> {
+    active: Option<&'a str>,
+    accounts: &'a [StoredAuth],
+}
+
+impl StoredAccounts {
+    /// Parse a stored blob, accepting the single-session format older builds
+    /// wrote.
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        serde_json::from_slice(bytes).ok().or_else(|| {
+            let single: StoredAuth = serde_json::from_slice(bytes).ok()?;
+            Some(Self {
+                active: Some(single.user.id.clone()),
+                accounts: vec![single],
+            })
+        })
+    }
 }
 
-/// In-memory auth state. Loads/saves from a local JSON file.
-#[derive(Debug, Clone)]
+/// In-memory auth state for every signed-in account. The `current_user` /
+/// `token` fields mirror the active account. Accounts are persisted through
+/// a `SecretStore`; sessions left in the plaintext `auth.json` by older
+/// builds are moved there on first load.
+#[derive(Clone)]
 pub struct AuthState {
+    /// All signed-in accounts, in the order they were added.
+    pub accounts: Vec<StoredAuth>,
     pub current_user: Option<User>,
     pub token: Option<String>,
-    pub login_error: Option<String>,
+    pub refresh_token: Option<String>,
+    /// When `token` expires; `None` for tokens that don't.
+    pub expires_at: Option<SystemTime>,
+    pub login_error: Option<AuthError>,
+    /// A login request is in flight.
+    pub login_pending: bool,
+    /// Ticket for a login waiting on its second factor.
+    pub mfa_ticket: Option<String>,
+    store: Arc<dyn SecretStore>,
+    /// Where per-account data such as the message cache lives.
+    data_dir: PathBuf,
+    /// Plaintext `auth.json` written by older builds.
+    legacy_path: PathBuf,
 }
 
 impl AuthState {
     /// Create a new auth state, loading any persisted session from disk.
-    pub fn new() -> Self {
-        if let Some(stored) = Self::load_from_disk() {
-            Self {
-                current_user: Some(stored.user),
-                token: Some(stored.token),
-                login_error: None,
-            }
-        } else {
-            Self {
-                current_user: None,
-                token: None,
-                login_error: None,
-            }
-        }
+    /// Fails if there's no per-user directory to keep it in, rather than
+    /// falling back to one other users can read.
+    pub fn new() -> Result<Self> {
+        let data_dir = Self::default_data_dir().context("can't find a home directory to keep Tripwire's data in")?;
+        let store = secure_store::default_store(&data_dir);
+        Ok(Self::with_store(store.into(), data_dir))
+    }
+
+    fn with_store(store: Arc<dyn SecretStore>, data_dir: PathBuf) -> Self {
+        let legacy_path = data_dir.join("auth.json");
+        let stored = Self::load(store.as_ref(), &legacy_path).unwrap_or_default();
+        let mut this = Self {
+            accounts: stored.accounts,
+            current_user: None,
+            token: None,
+            refresh_token: None,
+            expires_at: None,
+            login_error: None,
+            login_pending: false,
+            mfa_ticket: None,
+            store,
+            data_dir,
+            legacy_path,
+        };
+        let active = stored
+            .active
+            .and_then(|id| this.account_index(&id))
+            .or((!this.accounts.is_empty()).then_some(0));
+        this.activate(active);
+        this
+    }
+
+    fn account_index(&self, user_id: &str) -> Option<usize> {
+        self.accounts.iter().position(|a| a.user.id == user_id)
+    }
+
+    /// Point the active-session fields at `accounts[index]`, or clear them.
+    fn activate(&mut self, index: Option<usize>) {
+        let account = index.and_then(|ix| self.accounts.get(ix));
+        self.current_user = account.map(|a| a.user.clone());
+        self.token.zeroize();
+        self.token = account.map(|a| a.token.clone());
+        self.refresh_token.zeroize();
+        self.refresh_token = account.and_then(|a| a.refresh_token.clone());
+        self.expires_at = account.and_then(|a| a.expires_at);
     }
 
     pub fn is_authenticated(&self) -> bool {
         self.token.is_some()
     }
 
-    /// Mock login — in a real app this would call the Tripwire API.
-    pub fn login(&mut self, email: &str, password: &str) -> bool {
-        if email.trim().is_empty() {
-            self.login_error = Some("Email is required.".into());
-            return false;
-        }
-        if password.len() < 6 {
-            self.login_error = Some("Password must be at least 6 characters.".into());
-            return false;
-        }
+    /// Time left until the token expires, zero if it already has. `None`
+    /// when logged out or the token never expires.
+    pub fn expires_in(&self) -> Option<Duration> {
+        let expires_at = self.expires_at.filter(|_| self.is_authenticated())?;
+        Some(
+            expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO),
+        )
+    }
 
-        let username = email
-            .split('@')
-            .next()
-            .unwrap_or("user")
-            .to_string();
-
-        let user = User {
-            id: format!("user_{}", username),
-            username,
-            discriminator: "0001".to_string(),
-            status: UserStatus::Online,
+    /// Adopt a session from a successful login or refresh, adding the
+    /// account if it's new, make it the active one and persist.
+    pub fn apply_session(&mut self, session: AuthSession) {
+        let account = StoredAuth {
+            token: session.token.clone(),
+            user: session.user.clone(),
+            refresh_token: session.refresh_token.clone(),
+            expires_at: session.expires_at,
+            mfa_enabled: session.mfa_enabled,
         };
-        let token = format!("token_mock_{}", email);
-
-        self.current_user = Some(user.clone());
-        self.token = Some(token.clone());
+        let index = match self.account_index(&session.user.id) {
+            Some(ix) => {
+                self.accounts[ix] = account;
+                ix
+            }
+            None => {
+                self.accounts.push(account);
+                self.accounts.len() - 1
+            }
+        };
+        self.activate(Some(index));
         self.login_error = None;
+        self.login_pending = false;
+        self.mfa_ticket = None;
+        self.persist();
+    }
 
-        self.persist(&StoredAuth { token, user });
-        true
+    /// Whether the active account has two-factor authentication turned on.
+    pub fn mfa_enabled(&self) -> bool {
+        self.active_account().is_some_and(|a| a.mfa_enabled)
     }
 
-    /// Instantly log in with a dev user — bypasses credential checks.
-    pub fn bypass_login(&mut self) {
-        let user = User {
-            id: "dev_user".to_string(),
-            username: "DevUser".to_string(),
-            discriminator: "9999".to_string(),
-            status: UserStatus::Online,
+    /// Record that two-factor authentication was turned on or off for the
+    /// active account.
+    pub fn set_mfa_enabled(&mut self, enabled: bool) {
+        let Some(ix) = self
+            .current_user
+            .as_ref()
+            .and_then(|user| self.account_index(&user.id))
+        else {
+            return;
         };
-        let token = "dev_bypass_token".to_string();
+        self.accounts[ix].mfa_enabled = enabled;
+        self.persist();
+    }
+
+    fn active_account(&self) -> Option<&StoredAuth> {
+        let user = self.current_user.as_ref()?;
+        self.accounts.iter().find(|a| a.user.id == user.id)
+    }
 
-        self.current_user = Some(user.clone());
-        self.token = Some(token.clone());
+    /// Make another signed-in account the active one. Returns `false` if
+    /// there is no such account.
+    pub fn switch_account(&mut self, user_id: &str) -> bool {
+        let Some(index) = self.account_index(user_id) else {
+            return false;
+        };
+        self.activate(Some(index));
         self.login_error = None;
+        self.persist();
+        true
+    }
 
-        self.persist(&StoredAuth { token, user });
+    /// Session for the dev user — lets local builds skip credential checks.
+    pub fn dev_session() -> AuthSession {
+        AuthSession {
+            user: User {
+                id: "dev_user".to_string(),
+                username: "DevUser".to_string(),
+                discriminator: "9999".to_string(),
+                status: UserStatus::Online,
+            },
+            token: "dev_bypass_token".to_string(),
+            refresh_token: None,
+            expires_at: None,
+            mfa_enabled: false,
+        }
     }
 
-    /// Clear auth state and remove persisted session.
+    /// Sign the active account out, scrubbing its tokens from memory, and
+    /// fall back to the next signed-in account if there is one. Once the
+    /// last account is gone the persisted secret is erased entirely.
     pub fn logout(&mut self) {
-        self.current_user = None;
-        self.token = None;
+        if let Some(ix) = self
+            .current_user
+            .as_ref()
+            .and_then(|user| self.account_index(&user.id))
+        {
+            // Dropping a `StoredAuth` zeroizes its tokens.
+            self.accounts.remove(ix);
+        }
+        self.activate((!self.accounts.is_empty()).then_some(0));
         self.login_error = None;
-        if let Some(path) = Self::auth_file_path() {
-            let _ = std::fs::remove_file(path);
+        self.login_pending = false;
+        self.mfa_ticket = None;
+
+        if self.accounts.is_empty() {
+            if let Err(err) = self.store.wipe() {
+                eprintln!("Failed to erase session from {}: {err:#}", self.store.name());
+            }
+        } else {
+            self.persist();
+        }
+        if let Err(err) = secure_store::shred(&self.legacy_path) {
+            eprintln!("Failed to remove {}: {err:#}", self.legacy_path.display());
+        }
+    }
+
+    /// Log out because the session can't be renewed. If that was the last
+    /// account, leave a message for the login screen.
+    pub fn expire(&mut self) {
+        self.logout();
+        if !self.is_authenticated() {
+            self.login_error = Some(AuthError::SessionExpired);
         }
     }
 
     // ── Disk persistence ──────────────────────────────────────────────────────
 
-    fn data_dir() -> Option<PathBuf> {
+    /// Directory for everything Tripwire keeps on disk.
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    fn default_data_dir() -> Option<PathBuf> {
         #[cfg(target_os = "windows")]
         {
             std::env::var("APPDATA")
@@ -126,22 +298,157 @@ impl AuthState {
         }
     }
 
-    fn auth_file_path() -> Option<PathBuf> {
-        Self::data_dir().map(|d| d.join("auth.json"))
+    fn load(store: &dyn SecretStore, legacy_path: &Path) -> Option<StoredAccounts> {
+        match store.load() {
+            Ok(Some(secret)) => StoredAccounts::parse(&secret),
+            Ok(None) => Self::migrate_legacy(store, legacy_path),
+            Err(err) => {
+                eprintln!("Failed to read session from {}: {err:#}", store.name());
+                None
+            }
+        }
+    }
+
+    /// Move a plaintext `auth.json` into `store`. The file is only removed
+    /// once the session is safely stored.
+    fn migrate_legacy(store: &dyn SecretStore, legacy_path: &Path) -> Option<StoredAccounts> {
+        let contents = Zeroizing::new(std::fs::read(legacy_path).ok()?);
+        let stored = StoredAccounts::parse(&contents);
+        if stored.is_some() {
+            if let Err(err) = store.save(&contents) {
+                eprintln!("Failed to migrate session to {}: {err:#}", store.name());
+                return stored;
+            }
+        }
+        if let Err(err) = secure_store::shred(legacy_path) {
+            eprintln!("Failed to remove {}: {err:#}", legacy_path.display());
+        }
+        stored
     }
 
-    fn load_from_disk() -> Option<StoredAuth> {
-        let path = Self::auth_file_path()?;
-        let contents = std::fs::read_to_string(path).ok()?;
-        serde_json::from_str(&contents).ok()
+    fn persist(&self) {
+        let stored = PersistedAuth {
+            active: self.current_user.as_ref().map(|u| u.id.as_str()),
+            accounts: &self.accounts,
+        };
+        let Ok(json) = serde_json::to_vec(&stored) else { return };
+        let json = Zeroizing::new(json);
+        if let Err(err) = self.store.save(&json) {
+            eprintln!("Failed to save session to {}: {err:#}", self.store.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secure_store::EncryptedFileStore;
+
+    fn session() -> AuthSession {
+        session_for("u1", "tok")
     }
 
-    fn persist(&self, auth: &StoredAuth) {
-        let Some(dir) = Self::data_dir() else { return };
-        let _ = std::fs::create_dir_all(&dir);
-        let Some(path) = Self::auth_file_path() else { return };
-        if let Ok(json) = serde_json::to_string_pretty(auth) {
-            let _ = std::fs::write(path, json);
+    fn session_for(user_id: &str, token: &str) -> AuthSession {
+        AuthSession {
+            user: User {
+                id: user_id.into(),
+                username: format!("name_{user_id}"),
+                discriminator: "0001".into(),
+                status: UserStatus::Online,
+            },
+            token: token.into(),
+            refresh_token: Some("ref".into()),
+            expires_at: None,
+            mfa_enabled: false,
         }
     }
+
+    #[test]
+    fn test_migrates_plaintext_auth_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy_path = dir.path().join("auth.json");
+        std::fs::write(
+            &legacy_path,
+            r#"{"token":"old_token","user":{"id":"u1","username":"alice","discriminator":"0001","status":"Online"}}"#,
+        )
+        .unwrap();
+
+        let store: Arc<dyn SecretStore> = Arc::new(EncryptedFileStore::new(dir.path()));
+        let auth = AuthState::with_store(store.clone(), dir.path().into());
+        assert_eq!(auth.token.as_deref(), Some("old_token"));
+        assert_eq!(auth.refresh_token, None);
+        assert!(!legacy_path.exists());
+
+        let reloaded = AuthState::with_store(store, dir.path().into());
+        assert_eq!(reloaded.token.as_deref(), Some("old_token"));
+    }
+
+    #[test]
+    fn test_session_persists_and_logout_wipes_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn SecretStore> = Arc::new(EncryptedFileStore::new(dir.path()));
+
+        let mut auth = AuthState::with_store(store.clone(), dir.path().into());
+        assert!(!auth.is_authenticated());
+        auth.apply_session(session());
+
+        let reloaded = AuthState::with_store(store.clone(), dir.path().into());
+        assert_eq!(reloaded.token.as_deref(), Some("tok"));
+        assert_eq!(reloaded.refresh_token.as_deref(), Some("ref"));
+        assert!(!reloaded.mfa_enabled());
+
+        auth.set_mfa_enabled(true);
+        assert!(AuthState::with_store(store.clone(), dir.path().into()).mfa_enabled());
+
+        auth.logout();
+        assert_eq!(auth.token, None);
+        assert_eq!(store.load().unwrap(), None);
+        assert!(!AuthState::with_store(store, dir.path().into()).is_authenticated());
+    }
+
+    #[test]
+    fn test_multiple_accounts_switch_and_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn SecretStore> = Arc::new(EncryptedFileStore::new(dir.path()));
+
+        let mut auth = AuthState::with_store(store.clone(), dir.path().into());
+        auth.apply_session(session_for("work", "tok_work"));
+        auth.apply_session(session_for("home", "tok_home"));
+        assert_eq!(auth.accounts.len(), 2);
+        assert_eq!(auth.token.as_deref(), Some("tok_home"));
+
+        assert!(auth.switch_account("work"));
+        assert_eq!(auth.current_user.as_ref().unwrap().id, "work");
+        assert_eq!(auth.token.as_deref(), Some("tok_work"));
+        assert!(!auth.switch_account("nobody"));
+
+        // Re-logging into an existing account replaces it in place.
+        auth.apply_session(session_for("home", "tok_home_2"));
+        assert_eq!(auth.accounts.len(), 2);
+
+        let reloaded = AuthState::with_store(store.clone(), dir.path().into());
+        assert_eq!(reloaded.current_user.as_ref().unwrap().id, "home");
+        assert_eq!(reloaded.token.as_deref(), Some("tok_home_2"));
+        assert_eq!(reloaded.accounts.len(), 2);
+    }
+
+    #[test]
+    fn test_logout_falls_back_to_remaining_account() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn SecretStore> = Arc::new(EncryptedFileStore::new(dir.path()));
+
+        let mut auth = AuthState::with_store(store.clone(), dir.path().into());
+        auth.apply_session(session_for("work", "tok_work"));
+        auth.apply_session(session_for("home", "tok_home"));
+
+        auth.expire();
+        assert_eq!(auth.current_user.as_ref().unwrap().id, "work");
+        assert_eq!(auth.login_error, None);
+        assert!(store.load().unwrap().is_some());
+
+        auth.expire();
+        assert!(!auth.is_authenticated());
+        assert_eq!(auth.login_error, Some(AuthError::SessionExpired));
+        assert_eq!(store.load().unwrap(), None);
+    }
 }
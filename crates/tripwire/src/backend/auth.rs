@@ -0,0 +1,125 @@
+//! Authentication types shared by every backend: the session a successful
+//! login hands back, and the structured errors the login screen shows.
+
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::models::User;
+
+/// Future returned by the authentication calls. Unlike `BackendFuture`,
+/// failures are typed so the UI can tell them apart.
+pub type AuthFuture<T> = BoxFuture<'static, Result<T, AuthError>>;
+
+/// A freshly issued (or refreshed) session.
+#[derive(Clone, PartialEq)]
+pub struct AuthSession {
+    pub user: User,
+    pub token: String,
+    /// Token to trade for a new session once `token` expires.
+    pub refresh_token: Option<String>,
+    /// When `token` stops being accepted. `None` means it never expires.
+    pub expires_at: Option<SystemTime>,
+    /// Whether the account has two-factor authentication turned on.
+    pub mfa_enabled: bool,
+}
+
+/// Leaves the tokens out, so a session that ends up in a log or a failed
+/// assertion doesn't leak them.
+impl fmt::Debug for AuthSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthSession")
+            .field("user", &self.user.id)
+            .field("expires_at", &self.expires_at)
+            .field("mfa_enabled", &self.mfa_enabled)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Second-factor proof for a login that came back `TwoFactorRequired`, or
+/// for turning two-factor authentication off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", content = "code", rename_all = "snake_case")]
+pub enum MfaCode {
+    /// Six digits from an authenticator app.
+    Totp(String),
+    /// One of the single-use codes handed out at enrollment.
+    Backup(String),
+}
+
+/// A pending TOTP enrollment: the secret to put in the authenticator app,
+/// both as base32 and as an `otpauth://` URI.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MfaEnrollment {
+    pub secret: String,
+    pub uri: String,
+}
+
+/// Why an authentication call failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// The input was rejected before anything was sent.
+    Validation(String),
+    InvalidCredentials,
+    /// Too many attempts; `retry_after` is the server's hint, if it gave one.
+    RateLimited { retry_after: Option<Duration> },
+    /// The password was right but the account needs a second factor.
+    /// `ticket` identifies this login attempt for the follow-up request.
+    TwoFactorRequired { ticket: String },
+    /// A TOTP or backup code was wrong or already used.
+    InvalidCode,
+    /// The login attempt behind a `TwoFactorRequired` ticket is over; the
+    /// user has to enter their password again.
+    MfaTicketExpired,
+    /// The refresh token was rejected; the user has to log in again.
+    SessionExpired,
+    /// The request never got a response.
+    Network(String),
+    /// Any other unexpected response.
+    Server { status: u16, message: String },
+}
+
+impl AuthError {
+    /// Worth retrying later without asking the user for anything.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AuthError::Network(_) | AuthError::RateLimited { .. } => true,
+            AuthError::Server { status, .. } => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Validation(message) => f.write_str(message),
+            AuthError::InvalidCredentials => f.write_str("Login or password is invalid."),
+            AuthError::RateLimited {
+                retry_after: Some(after),
+            } => {
+                let secs = after.as_secs() + u64::from(after.subsec_nanos() > 0);
+                write!(f, "Too many attempts. Try again in {secs} seconds.")
+            }
+            AuthError::RateLimited { retry_after: None } => {
+                f.write_str("Too many attempts. Try again in a moment.")
+            }
+            AuthError::TwoFactorRequired { .. } => {
+                f.write_str("This account requires two-factor authentication.")
+            }
+            AuthError::InvalidCode => f.write_str("Invalid two-factor code."),
+            AuthError::MfaTicketExpired => {
+                f.write_str("This login attempt has expired. Please log in again.")
+            }
+            AuthError::SessionExpired => f.write_str("Your session has expired. Please log in again."),
+            AuthError::Network(message) => write!(f, "Couldn't reach the server: {message}"),
+            AuthError::Server { status, message } => {
+                write!(f, "The server returned an error ({status}): {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
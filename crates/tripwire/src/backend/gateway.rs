@@ -0,0 +1,552 @@
+//! Real-time gateway — a WebSocket connection that streams server events.
+//!
+//! Every frame is a JSON object:
+//!
+//! ```text
+//! { "op": <opcode>, "d": <payload>, "s": <sequence>, "t": <event name> }
+//! ```
+//!
+//! `s` and `t` are only present on dispatches (op 0). A session runs:
+//!
+//! 1. server → `HELLO { heartbeat_interval }` (ms)
+//! 2. client → `IDENTIFY { token }`, or `RESUME { token, session_id, seq }`
+//!    when reconnecting to a session that is still alive
+//! 3. server → `READY { session_id }` / `RESUMED`, followed by dispatches
+//! 4. client → `HEARTBEAT <last seq>` every interval; server → `HEARTBEAT_ACK`
+//!
+//! A heartbeat that isn't acknowledged before the next one is due means the
+//! connection is dead, so the client drops it and resumes on a new one.
+//!
+//! The connection runs on its own thread with blocking I/O; events are
+//! forwarded to the UI over an unbounded channel.
+
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::rand_core::RngCore as _;
+use anyhow::{Context as _, Result, anyhow, bail};
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message as WsMessage, WebSocket};
+
+use crate::models::{Message, UserStatus, VoiceParticipant};
+
+mod opcode {
+    pub const DISPATCH: u8 = 0;
+    pub const HEARTBEAT: u8 = 1;
+    pub const IDENTIFY: u8 = 2;
+    pub const RESUME: u8 = 6;
+    pub const RECONNECT: u8 = 7;
+    pub const INVALID_SESSION: u8 = 9;
+    pub const HELLO: u8 = 10;
+    pub const HEARTBEAT_ACK: u8 = 11;
+}
+
+/// How long a blocking read waits before the loop checks heartbeats and
+/// shutdown again.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Upper bound for the reconnect backoff.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// A dispatched event, already decoded from its `t` / `d` pair.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "t", content = "d", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum GatewayEvent {
+    Ready {
+        session_id: String,
+    },
+    Resumed,
+    MessageCreate {
+        channel_id: String,
+        message: Message,
+    },
+    MessageUpdate {
+        channel_id: String,
+        message: Message,
+    },
+    MessageDelete {
+        channel_id: String,
+        message_id: String,
+    },
+    MessageReactionAdd {
+        channel_id: String,
+        message_id: String,
+        user_id: String,
+        emoji: String,
+    },
+    MessageReactionRemove {
+        channel_id: String,
+        message_id: String,
+        user_id: String,
+        emoji: String,
+    },
+    /// The current user read `channel_id` up to `message_id`, possibly on
+    /// another device.
+    MessageAck {
+        channel_id: String,
+        message_id: String,
+    },
+    TypingStart {
+        channel_id: String,
+        user_id: String,
+    },
+    PresenceUpdate {
+        user_id: String,
+        status: UserStatus,
+    },
+    /// A user joined, left (`channel_id: None`) or changed state in a voice
+    /// channel.
+    VoiceStateUpdate {
+        channel_id: Option<String>,
+        participant: VoiceParticipant,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct Frame {
+    op: u8,
+    #[serde(default)]
+    d: Value,
+    #[serde(default)]
+    s: Option<u64>,
+    #[serde(default)]
+    t: Option<String>,
+}
+
+/// Where to connect and who to identify as.
+#[derive(Debug, Clone)]
+pub struct GatewayConfig {
+    pub url: String,
+    pub token: String,
+    /// Delay before reconnecting after a drop; doubles on every failed
+    /// attempt after that. Each wait gets up to half again at random.
+    pub reconnect_delay: Duration,
+}
+
+impl GatewayConfig {
+    pub fn new(url: impl Into<String>, token: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            token: token.into(),
+            reconnect_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A running gateway connection. Dropping it closes the socket and stops the
+/// connection thread.
+pub struct GatewayConnection {
+    shutdown: Arc<AtomicBool>,
+}
+
+impl GatewayConnection {
+    /// Start connecting in the background. Events arrive on the returned
+    /// receiver until the connection is dropped.
+    pub fn connect(config: GatewayConfig) -> (Self, UnboundedReceiver<GatewayEvent>) {
+        let (events_tx, events_rx) = unbounded();
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread_shutdown = shutdown.clone();
+        thread::Builder::new()
+            .name("tripwire-gateway".into())
+            .spawn(move || run(config, events_tx, thread_shutdown))
+            .expect("failed to spawn gateway thread");
+
+        (Self { shutdown }, events_rx)
+    }
+}
+
+impl Drop for GatewayConnection {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+    }
+}
+
+// ── Connection loop ──────────────────────────────────────────────────────────
+
+/// Resume state carried across reconnects.
+#[derive(Default)]
+struct SessionState {
+    session_id: Option<String>,
+    seq: Option<u64>,
+}
+
+enum SessionEnd {
+    /// The UI dropped the connection (or its receiver).
+    Shutdown,
+    /// The server asked us to reconnect; do so after the base delay.
+    Reconnect,
+}
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+fn run(config: GatewayConfig, events: UnboundedSender<GatewayEvent>, shutdown: Arc<AtomicBool>) {
+    let mut state = SessionState::default();
+    let mut failures = 0u32;
+
+    while !shutdown.load(Ordering::Relaxed) {
+        let mut established = false;
+        let delay = match run_session(&config, &mut state, &events, &shutdown, &mut established) {
+            Ok(SessionEnd::Shutdown) => break,
+            Ok(SessionEnd::Reconnect) => {
+                failures = 0;
+                reconnect_delay(config.reconnect_delay, failures)
+            }
+            Err(err) => {
+                eprintln!("Gateway connection lost: {err:#}");
+                if established {
+                    failures = 0;
+                }
+                let delay = reconnect_delay(config.reconnect_delay, failures);
+                failures = failures.saturating_add(1);
+                delay
+            }
+        };
+        if !sleep_unless_shutdown(with_jitter(delay), &shutdown) {
+            break;
+        }
+    }
+}
+
+/// Backoff after `failures` consecutive failed attempts: `base`, `2 * base`,
+/// `4 * base`… capped at [`MAX_RECONNECT_DELAY`].
+fn reconnect_delay(base: Duration, failures: u32) -> Duration {
+    base.saturating_mul(1 << failures.min(16)).min(MAX_RECONNECT_DELAY)
+}
+
+/// `delay` plus up to half again, so clients dropped at the same time don't
+/// all come back at once.
+fn with_jitter(delay: Duration) -> Duration {
+    delay + delay.mul_f64(f64::from(OsRng.next_u32()) / f64::from(u32::MAX) / 2.0)
+}
+
+/// Returns `false` if shutdown was requested while sleeping.
+fn sleep_unless_shutdown(duration: Duration, shutdown: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        if shutdown.load(Ordering::Relaxed) {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - Instant::now()));
+    }
+    !shutdown.load(Ordering::Relaxed)
+}
+
+fn run_session(
+    config: &GatewayConfig,
+    state: &mut SessionState,
+    events: &UnboundedSender<GatewayEvent>,
+    shutdown: &AtomicBool,
+    established: &mut bool,
+) -> Result<SessionEnd> {
+    let (mut socket, _) = tungstenite::connect(config.url.as_str())
+        .with_context(|| format!("connecting to {}", config.url))?;
+    set_read_timeout(&socket, POLL_INTERVAL)?;
+
+    let hello = loop {
+        if shutdown.load(Ordering::Relaxed) {
+            socket.close(None).ok();
+            return Ok(SessionEnd::Shutdown);
+        }
+        if let Some(frame) = read_frame(&mut socket)? {
+            if frame.op != opcode::HELLO {
+                bail!("expected HELLO, got op {}", frame.op);
+            }
+            break frame;
+        }
+    };
+    let heartbeat_interval = hello
+        .d
+        .get("heartbeat_interval")
+        .and_then(Value::as_u64)
+        .map(Duration::from_millis)
+        .ok_or_else(|| anyhow!("HELLO without heartbeat_interval"))?;
+    *established = true;
+
+    match (&state.session_id, state.seq) {
+        (Some(session_id), Some(seq)) => send_frame(
+            &mut socket,
+            opcode::RESUME,
+            json!({ "token": config.token, "session_id": session_id, "seq": seq }),
+        )?,
+        _ => send_frame(&mut socket, opcode::IDENTIFY, json!({ "token": config.token }))?,
+    }
+
+    let mut next_heartbeat = Instant::now() + heartbeat_interval;
+    let mut awaiting_ack = false;
+
+    loop {
+        if shutdown.load(Ordering::Relaxed) || events.is_closed() {
+            socket.close(None).ok();
+            return Ok(SessionEnd::Shutdown);
+        }
+
+        if Instant::now() >= next_heartbeat {
+            if awaiting_ack {
+                bail!("heartbeat not acknowledged");
+            }
+            send_frame(&mut socket, opcode::HEARTBEAT, json!(state.seq))?;
+            awaiting_ack = true;
+            next_heartbeat = Instant::now() + heartbeat_interval;
+        }
+
+        let Some(frame) = read_frame(&mut socket)? else {
+            continue;
+        };
+        match frame.op {
+            opcode::DISPATCH => {
+                if let Some(seq) = frame.s {
+                    state.seq = Some(seq);
+                }
+                let Some(name) = frame.t else {
+                    continue;
+                };
+                match serde_json::from_value::<GatewayEvent>(json!({ "t": name, "d": frame.d })) {
+                    Ok(event) => {
+                        if let GatewayEvent::Ready { session_id } = &event {
+                            state.session_id = Some(session_id.clone());
+                        }
+                        if events.unbounded_send(event).is_err() {
+                            socket.close(None).ok();
+                            return Ok(SessionEnd::Shutdown);
+                        }
+                    }
+                    // Unknown or malformed events are skipped so newer servers
+                    // don't break older clients.
+                    Err(err) => eprintln!("Ignoring gateway event {name}: {err}"),
+                }
+            }
+            opcode::HEARTBEAT => {
+                send_frame(&mut socket, opcode::HEARTBEAT, json!(state.seq))?;
+                awaiting_ack = true;
+                next_heartbeat = Instant::now() + heartbeat_interval;
+            }
+            opcode::HEARTBEAT_ACK => awaiting_ack = false,
+            opcode::RECONNECT => {
+                socket.close(None).ok();
+                return Ok(SessionEnd::Reconnect);
+            }
+            opcode::INVALID_SESSION => {
+                if frame.d.as_bool() != Some(true) {
+                    *state = SessionState::default();
+                }
+                socket.close(None).ok();
+                return Ok(SessionEnd::Reconnect);
+            }
+            op => eprintln!("Ignoring gateway op {op}"),
+        }
+    }
+}
+
+fn send_frame(socket: &mut Socket, op: u8, d: Value) -> Result<()> {
+    let text = serde_json::to_string(&json!({ "op": op, "d": d }))?;
+    socket.send(WsMessage::text(text))?;
+    Ok(())
+}
+
+/// Read the next frame, or `None` if nothing arrived within the poll
+/// interval.
+fn read_frame(socket: &mut Socket) -> Result<Option<Frame>> {
+    match socket.read() {
+        Ok(WsMessage::Text(text)) => Ok(Some(serde_json::from_str(&text)?)),
+        Ok(WsMessage::Close(frame)) => bail!("closed by server: {frame:?}"),
+        Ok(_) => Ok(None),
+        Err(tungstenite::Error::Io(err))
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn set_read_timeout(socket: &Socket, timeout: Duration) -> Result<()> {
+    let stream = match socket.get_ref() {
+        MaybeTlsStream::Plain(stream) => stream,
+        MaybeTlsStream::Rustls(stream) => stream.get_ref(),
+        #[allow(unreachable_patterns)]
+        _ => return Ok(()),
+    };
+    stream.set_read_timeout(Some(timeout))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    /// In-process stand-in for the gateway server.
+    struct StandIn {
+        listener: TcpListener,
+    }
+
+    impl StandIn {
+        fn new() -> Self {
+            Self {
+                listener: TcpListener::bind("127.0.0.1:0").unwrap(),
+            }
+        }
+
+        fn url(&self) -> String {
+            format!("ws://{}", self.listener.local_addr().unwrap())
+        }
+
+        fn accept(&self, heartbeat_ms: u64) -> WebSocket<TcpStream> {
+            let (stream, _) = self.listener.accept().unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            send(&mut socket, json!({ "op": 10, "d": { "heartbeat_interval": heartbeat_ms } }));
+            socket
+        }
+    }
+
+    fn send(socket: &mut WebSocket<TcpStream>, frame: Value) {
+        socket.send(WsMessage::text(frame.to_string())).unwrap();
+    }
+
+    fn dispatch(socket: &mut WebSocket<TcpStream>, seq: u64, t: &str, d: Value) {
+        send(socket, json!({ "op": 0, "s": seq, "t": t, "d": d }));
+    }
+
+    /// Read frames until one with `op` arrives, acknowledging heartbeats on
+    /// the way.
+    fn expect_op(socket: &mut WebSocket<TcpStream>, op: u8) -> Value {
+        loop {
+            let msg = socket.read().unwrap();
+            let WsMessage::Text(text) = msg else {
+                continue;
+            };
+            let frame: Value = serde_json::from_str(&text).unwrap();
+            if frame["op"] == op {
+                return frame["d"].clone();
+            }
+            if frame["op"] == opcode::HEARTBEAT {
+                send(socket, json!({ "op": 11 }));
+            }
+        }
+    }
+
+    fn next_event(events: &mut UnboundedReceiver<GatewayEvent>) -> GatewayEvent {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Ok(event) = events.try_recv() {
+                return event;
+            }
+            assert!(Instant::now() < deadline, "timed out waiting for event");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_identify_and_dispatch() {
+        let server = StandIn::new();
+        let (_conn, mut events) = GatewayConnection::connect(GatewayConfig::new(server.url(), "tok"));
+
+        let mut socket = server.accept(45_000);
+        assert_eq!(expect_op(&mut socket, opcode::IDENTIFY), json!({ "token": "tok" }));
+
+        dispatch(&mut socket, 1, "READY", json!({ "session_id": "s1" }));
+        dispatch(&mut socket, 2, "TYPING_START", json!({ "channel_id": "103", "user_id": "u2" }));
+        dispatch(&mut socket, 3, "SOMETHING_NEW", json!({}));
+        dispatch(&mut socket, 4, "PRESENCE_UPDATE", json!({ "user_id": "u2", "status": "Idle" }));
+
+        assert_eq!(
+            next_event(&mut events),
+            GatewayEvent::Ready { session_id: "s1".into() }
+        );
+        assert_eq!(
+            next_event(&mut events),
+            GatewayEvent::TypingStart {
+                channel_id: "103".into(),
+                user_id: "u2".into(),
+            }
+        );
+        assert_eq!(
+            next_event(&mut events),
+            GatewayEvent::PresenceUpdate {
+                user_id: "u2".into(),
+                status: UserStatus::Idle,
+            }
+        );
+    }
+
+    #[test]
+    fn test_heartbeat_carries_last_sequence() {
+        let server = StandIn::new();
+        let (_conn, _events) = GatewayConnection::connect(GatewayConfig::new(server.url(), "tok"));
+
+        let mut socket = server.accept(50);
+        expect_op(&mut socket, opcode::IDENTIFY);
+        dispatch(&mut socket, 7, "READY", json!({ "session_id": "s1" }));
+
+        // The first heartbeat may race the dispatch; the next one can't.
+        let mut seq = expect_op(&mut socket, opcode::HEARTBEAT);
+        if seq.is_null() {
+            send(&mut socket, json!({ "op": 11 }));
+            seq = expect_op(&mut socket, opcode::HEARTBEAT);
+        }
+        assert_eq!(seq, json!(7));
+    }
+
+    #[test]
+    fn test_resumes_after_disconnect() {
+        let server = StandIn::new();
+        let (_conn, mut events) = GatewayConnection::connect(GatewayConfig::new(server.url(), "tok"));
+
+        let mut socket = server.accept(45_000);
+        expect_op(&mut socket, opcode::IDENTIFY);
+        dispatch(&mut socket, 1, "READY", json!({ "session_id": "s1" }));
+        dispatch(&mut socket, 2, "TYPING_START", json!({ "channel_id": "103", "user_id": "u2" }));
+        next_event(&mut events);
+        next_event(&mut events);
+        drop(socket);
+
+        let mut socket = server.accept(45_000);
+        assert_eq!(
+            expect_op(&mut socket, opcode::RESUME),
+            json!({ "token": "tok", "session_id": "s1", "seq": 2 })
+        );
+        dispatch(&mut socket, 3, "RESUMED", Value::Null);
+        assert_eq!(next_event(&mut events), GatewayEvent::Resumed);
+    }
+
+    #[test]
+    fn test_invalid_session_starts_fresh() {
+        let server = StandIn::new();
+        let (_conn, mut events) = GatewayConnection::connect(GatewayConfig::new(server.url(), "tok"));
+
+        let mut socket = server.accept(45_000);
+        expect_op(&mut socket, opcode::IDENTIFY);
+        dispatch(&mut socket, 1, "READY", json!({ "session_id": "s1" }));
+        next_event(&mut events);
+        send(&mut socket, json!({ "op": 9, "d": false }));
+
+        let mut socket = server.accept(45_000);
+        assert_eq!(expect_op(&mut socket, opcode::IDENTIFY), json!({ "token": "tok" }));
+    }
+
+    #[test]
+    fn test_reconnect_delay_backs_off() {
+        let base = Duration::from_secs(1);
+        assert_eq!(reconnect_delay(base, 0), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(base, 2), Duration::from_secs(4));
+        assert_eq!(reconnect_delay(base, 40), MAX_RECONNECT_DELAY);
+        for _ in 0..100 {
+            let delay = with_jitter(base);
+            assert!(delay >= base && delay <= base * 3 / 2, "{delay:?}");
+        }
+    }
+}
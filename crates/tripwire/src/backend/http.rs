@@ -0,0 +1,1053 @@
+//! Backend that talks to a Tripwire API server over HTTP.
+//!
+//! Endpoints follow a REST layout rooted at the configured base URL:
+//!
+//! ```text
+//! POST   /auth/login
+//! POST   /auth/refresh
+//! POST   /auth/mfa
+//! POST   /users/@me/mfa/totp/enroll
+//! POST   /users/@me/mfa/totp/enable
+//! POST   /users/@me/mfa/totp/disable
+//! GET    /servers
+//! PATCH  /servers/{id}/roles
+//! POST   /servers/{id}/categories
+//! PATCH  /servers/{id}/categories/{name}
+//! DELETE /servers/{id}/categories/{name}
+//! POST   /servers/{id}/channels
+//! PATCH  /servers/{id}/channels/{channel_id}
+//! DELETE /servers/{id}/channels/{channel_id}
+//! PATCH  /servers/{id}/members/{user_id}
+//! DELETE /servers/{id}/members/{user_id}
+//! PUT    /servers/{id}/bans/{user_id}
+//! GET    /servers/{id}/audit-log?limit=&before=&user_id=&action_type=
+//! GET    /users/@me/channels
+//! GET    /users/@me/read-states
+//! GET    /channels/{id}/messages?limit=&before=
+//! POST   /channels/{id}/messages
+//! PATCH  /channels/{id}/messages/{message_id}
+//! DELETE /channels/{id}/messages/{message_id}
+//! PUT    /channels/{id}/messages/{message_id}/reactions/{emoji}/@me
+//! DELETE /channels/{id}/messages/{message_id}/reactions/{emoji}/@me
+//! POST   /channels/{id}/messages/{message_id}/ack
+//! POST   /channels/{id}/attachments?filename=
+//! GET    /attachments/limits
+//! POST   /channels/{id}/typing
+//! PATCH  /users/@me/presence
+//! ```
+//!
+//! Direct messages are channels on the server side, so both kinds of
+//! `MessageTarget` share the `/channels` routes. Attachments are uploaded
+//! as the raw file body before the message that carries them is sent. The
+//! auth routes answer with a `SessionResponse`; see `auth_error` for how
+//! failures are mapped. Channel and member changes answer with the server
+//! as saved; a timeout is a member's `communication_disabled_until`, in
+//! Unix seconds. The real-time gateway lives at `/gateway` on the same
+//! host, over `ws://` or `wss://`.
+
+use std::io;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{self, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use futures::io::AllowStdIo;
+use futures::{AsyncRead, AsyncReadExt as _, FutureExt as _, ready};
+use gpui::http_client::{AsyncBody, HttpClient, Method, Request, Response, StatusCode, Url};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use super::{
+    AuthError, AuthFuture, AuthSession, BackendFuture, BackendSession, ChatBackend, MemberUpdate,
+    MessageDraft, MessageTarget, MfaCode, MfaEnrollment, StatusError, UploadProgress,
+};
+use crate::attachments::{AttachmentUpload, UploadLimits};
+use crate::audit_log::{AuditLogEntry, AuditLogQuery, PAGE_SIZE};
+use crate::channels::ChannelEdit;
+use crate::members::ModerationAction;
+use crate::models::{Attachment, DirectMessageChannel, Message, Server, User, UserStatus};
+use crate::read_state::ReadState;
+use crate::role_draft::RoleChanges;
+
+/// A response read to completion, whatever its status.
+struct RawResponse {
+    status: StatusCode,
+    retry_after: Option<Duration>,
+    body: String,
+}
+
+/// Body of a successful `/auth/login`, `/auth/refresh` or `/auth/mfa`. A login that needs
+/// a second factor comes back with `mfa` set and a `ticket` instead of a
+/// token.
+#[derive(Deserialize)]
+struct SessionResponse {
+    token: Option<String>,
+    user: Option<User>,
+    refresh_token: Option<String>,
+    /// Token lifetime in seconds.
+    expires_in: Option<u64>,
+    #[serde(default)]
+    mfa: bool,
+    ticket: Option<String>,
+    /// Whether the account has two-factor authentication turned on.
+    #[serde(default)]
+    mfa_enabled: bool,
+}
+
+/// Body of a successful `/users/@me/mfa/totp/enable`.
+#[derive(Deserialize)]
+struct BackupCodesResponse {
+    backup_codes: Vec<String>,
+}
+
+/// Error body the API sends alongside non-2xx statuses.
+#[derive(Deserialize, Default)]
+struct ErrorResponse {
+    message: Option<String>,
+    /// Seconds until a rate limit lifts, if the `Retry-After` header is
+    /// missing.
+    retry_after: Option<f64>,
+}
+
+pub struct HttpBackend {
+    client: Arc<dyn HttpClient>,
+    base_url: Url,
+    token: RwLock<Option<String>>,
+}
+
+impl HttpBackend {
+    pub fn new(client: Arc<dyn HttpClient>, base_url: Url) -> Self {
+        Self {
+            client,
+            base_url,
+            token: RwLock::new(None),
+        }
+    }
+
+    /// Build an endpoint URL by appending percent-encoded path segments to
+    /// the base URL.
+    fn url(&self, segments: &[&str]) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("API base URL cannot have a path: {}", self.base_url))?
+            .pop_if_empty()
+            .extend(segments);
+        Ok(url)
+    }
+
+    fn messages_url(&self, target: &MessageTarget, rest: &[&str]) -> Result<Url> {
+        let mut segments = vec!["channels", target.id(), "messages"];
+        segments.extend_from_slice(rest);
+        self.url(&segments)
+    }
+
+    /// Send a request and read the whole response, whatever its status.
+    /// Only transport failures are errors.
+    fn request(
+        &self,
+        method: Method,
+        url: Result<Url>,
+        body: Option<serde_json::Value>,
+        token: Option<String>,
+    ) -> BackendFuture<RawResponse> {
+        let client = self.client.clone();
+
+        async move {
+            let url = url?;
+            let mut builder = Request::builder()
+                .method(method)
+                .uri(url.as_str())
+                .header("Accept", "application/json");
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {token}"));
+            }
+            let body = match body {
+                Some(json) => {
+                    builder = builder.header("Content-Type", "application/json");
+                    AsyncBody::from(serde_json::to_string(&json)?)
+                }
+                None => AsyncBody::empty(),
+            };
+
+            read_response(client.send(builder.body(body)?).await?).await
+        }
+        .boxed()
+    }
+
+    /// Send an authenticated request and return the raw response body,
+    /// failing on any non-2xx status.
+    fn send(
+        &self,
+        method: Method,
+        url: Result<Url>,
+        body: Option<serde_json::Value>,
+    ) -> BackendFuture<String> {
+        let token = self.token.read().ok().and_then(|t| t.clone());
+        let description = match &url {
+            Ok(url) => format!("{method} {url}"),
+            Err(_) => method.to_string(),
+        };
+        self.request(method, url, body, token)
+            .map(move |response| {
+                let response = response?;
+                if !response.status.is_success() {
+                    return Err(StatusError {
+                        status: response.status.as_u16(),
+                        message: format!("{description} failed with {}: {}", response.status, response.body),
+                    }
+                    .into());
+                }
+                Ok(response.body)
+            })
+            .boxed()
+    }
+
+    /// POST to one of the `/auth` routes and turn the answer into a session.
+    /// `rejected` is what a 400 means for this route and `unauthorized`
+    /// what a 401 or 403 means.
+    fn auth_request(
+        &self,
+        route: &str,
+        body: serde_json::Value,
+        rejected: AuthError,
+        unauthorized: AuthError,
+    ) -> AuthFuture<AuthSession> {
+        self.auth_call(
+            self.url(&["auth", route]),
+            body,
+            None,
+            rejected,
+            unauthorized,
+            parse_session,
+        )
+    }
+
+    /// POST to one of the account security routes, which need the current
+    /// session, and parse a successful answer with `parse`.
+    fn mfa_request<T: Send + 'static>(
+        &self,
+        route: &[&str],
+        body: serde_json::Value,
+        parse: fn(&RawResponse) -> Result<T, AuthError>,
+    ) -> AuthFuture<T> {
+        let mut segments = vec!["users", "@me", "mfa", "totp"];
+        segments.extend_from_slice(route);
+        let token = self.token.read().ok().and_then(|t| t.clone());
+        self.auth_call(
+            self.url(&segments),
+            body,
+            token,
+            AuthError::InvalidCode,
+            AuthError::SessionExpired,
+            parse,
+        )
+    }
+
+    fn auth_call<T: Send + 'static>(
+        &self,
+        url: Result<Url>,
+        body: serde_json::Value,
+        token: Option<String>,
+        rejected: AuthError,
+        unauthorized: AuthError,
+        parse: fn(&RawResponse) -> Result<T, AuthError>,
+    ) -> AuthFuture<T> {
+        self.request(Method::POST, url, Some(body), token)
+            .map(move |response| {
+                let response = response.map_err(|err| AuthError::Network(format!("{err:#}")))?;
+                if !response.status.is_success() {
+                    return Err(auth_error(&response, rejected, unauthorized));
+                }
+                parse(&response)
+            })
+            .boxed()
+    }
+
+    fn send_json<T: DeserializeOwned + Send + 'static>(
+        &self,
+        method: Method,
+        url: Result<Url>,
+        body: Option<serde_json::Value>,
+    ) -> BackendFuture<T> {
+        self.send(method, url, body)
+            .map(|text| Ok(serde_json::from_str(&text?)?))
+            .boxed()
+    }
+
+    fn send_empty(
+        &self,
+        method: Method,
+        url: Result<Url>,
+        body: Option<serde_json::Value>,
+    ) -> BackendFuture<()> {
+        self.send(method, url, body).map(|text| text.map(|_| ())).boxed()
+    }
+}
+
+/// Read `response` to completion.
+async fn read_response(mut response: Response<AsyncBody>) -> Result<RawResponse> {
+    let retry_after = response
+        .headers()
+        .get("Retry-After")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+    let mut text = String::new();
+    response.body_mut().read_to_string(&mut text).await?;
+
+    Ok(RawResponse {
+        status: response.status(),
+        retry_after,
+        body: text,
+    })
+}
+
+/// Upload body that reports how much of it the HTTP client has read.
+struct CountingReader<R> {
+    inner: R,
+    sent: u64,
+    progress: UploadProgress,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for CountingReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let read = ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        self.sent += read as u64;
+        self.progress.unbounded_send(self.sent).ok();
+        Poll::Ready(Ok(read))
+    }
+}
+
+/// Map a failed auth response to the error the login screen shows. A 400
+/// means the route rejected what was entered (`rejected`), a 401 or 403
+/// that the ticket or token it was sent with is no longer accepted
+/// (`unauthorized`).
+fn auth_error(response: &RawResponse, rejected: AuthError, unauthorized: AuthError) -> AuthError {
+    let error: ErrorResponse = serde_json::from_str(&response.body).unwrap_or_default();
+    match response.status {
+        StatusCode::BAD_REQUEST => rejected,
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => unauthorized,
+        StatusCode::TOO_MANY_REQUESTS => AuthError::RateLimited {
+            retry_after: response.retry_after.or_else(|| {
+                error
+                    .retry_after
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            }),
+        },
+        status => AuthError::Server {
+            status: status.as_u16(),
+            message: error
+                .message
+                .unwrap_or_else(|| status.canonical_reason().unwrap_or("unknown error").into()),
+        },
+    }
+}
+
+/// Parse a successful auth response body as `T`.
+fn parse_body<T: DeserializeOwned>(response: &RawResponse) -> Result<T, AuthError> {
+    serde_json::from_str(&response.body).map_err(|err| AuthError::Server {
+        status: response.status.as_u16(),
+        message: format!("malformed response: {err}"),
+    })
+}
+
+fn parse_session(response: &RawResponse) -> Result<AuthSession, AuthError> {
+    let session: SessionResponse = parse_body(response)?;
+
+    if session.mfa {
+        return Err(AuthError::TwoFactorRequired {
+            ticket: session.ticket.unwrap_or_default(),
+        });
+    }
+    let (Some(token), Some(user)) = (session.token, session.user) else {
+        return Err(AuthError::Server {
+            status: response.status.as_u16(),
+            message: "session response is missing the token or user".into(),
+        });
+    };
+    Ok(AuthSession {
+        user,
+        token,
+        refresh_token: session.refresh_token,
+        expires_at: session
+            .expires_in
+            .map(|secs| SystemTime::now() + Duration::from_secs(secs)),
+        mfa_enabled: session.mfa_enabled,
+    })
+}
+
+impl ChatBackend for HttpBackend {
+    fn login(&self, login: &str, password: &str) -> AuthFuture<AuthSession> {
+        self.auth_request(
+            "login",
+            json!({ "login": login, "password": password }),
+            AuthError::InvalidCredentials,
+            AuthError::InvalidCredentials,
+        )
+    }
+
+    fn refresh_session(&self, refresh_token: &str) -> AuthFuture<AuthSession> {
+        self.auth_request(
+            "refresh",
+            json!({ "refresh_token": refresh_token }),
+            AuthError::SessionExpired,
+            AuthError::SessionExpired,
+        )
+    }
+
+    fn verify_mfa(&self, ticket: &str, code: MfaCode) -> AuthFuture<AuthSession> {
+        let mut body = json!(code);
+        body["ticket"] = json!(ticket);
+        self.auth_request("mfa", body, AuthError::InvalidCode, AuthError::MfaTicketExpired)
+    }
+
+    fn begin_mfa_enrollment(&self) -> AuthFuture<MfaEnrollment> {
+        self.mfa_request(&["enroll"], json!({}), parse_body)
+    }
+
+    fn enable_mfa(&self, code: &str) -> AuthFuture<Vec<String>> {
+        self.mfa_request(&["enable"], json!({ "code": code }), |response| {
+            parse_body::<BackupCodesResponse>(response).map(|body| body.backup_codes)
+        })
+    }
+
+    fn disable_mfa(&self, code: MfaCode) -> AuthFuture<()> {
+        self.mfa_request(&["disable"], json!(code), |_| Ok(()))
+    }
+
+    fn set_session(&self, session: Option<BackendSession>) {
+        if let Ok(mut token) = self.token.write() {
+            *token = session.map(|s| s.token);
+        }
+    }
+
+    fn list_servers(&self) -> BackendFuture<Vec<Server>> {
+        self.send_json(Method::GET, self.url(&["servers"]), None)
+    }
+
+    fn list_dm_channels(&self) -> BackendFuture<Vec<DirectMessageChannel>> {
+        self.send_json(Method::GET, self.url(&["users", "@me", "channels"]), None)
+    }
+
+    fn update_roles(&self, server_id: &str, changes: RoleChanges) -> BackendFuture<Server> {
+        let body = json!({
+            "create": changes.created,
+            "update": changes.updated,
+            "delete": changes.deleted,
+            "order": changes.order,
+            "grant": changes.granted,
+            "revoke": changes.revoked,
+        });
+        self.send_json(Method::PATCH, self.url(&["servers", server_id, "roles"]), Some(body))
+    }
+
+    fn edit_channels(&self, server_id: &str, edit: ChannelEdit) -> BackendFuture<Server> {
+        let (method, url, body) = match edit {
+            ChannelEdit::CreateCategory { name } => (
+                Method::POST,
+                self.url(&["servers", server_id, "categories"]),
+                json!({ "name": name }),
+            ),
+            ChannelEdit::DeleteCategory { name } => (
+                Method::DELETE,
+                self.url(&["servers", server_id, "categories", &name]),
+                json!({}),
+            ),
+            ChannelEdit::MoveCategory { name, to } => (
+                Method::PATCH,
+                self.url(&["servers", server_id, "categories", &name]),
+                json!({ "position": to }),
+            ),
+            ChannelEdit::CreateChannel { category, name, kind } => (
+                Method::POST,
+                self.url(&["servers", server_id, "channels"]),
+                json!({ "category": category, "name": name, "kind": kind }),
+            ),
+            ChannelEdit::UpdateChannel { channel_id, update } => {
+                let mut body = json!({});
+                if let Some(name) = update.name {
+                    body["name"] = json!(name);
+                }
+                if let Some(topic) = update.topic {
+                    body["topic"] = json!(topic);
+                }
+                if let Some(slowmode_secs) = update.slowmode_secs {
+                    body["slowmode_secs"] = json!(slowmode_secs);
+                }
+                if let Some(nsfw) = update.nsfw {
+                    body["nsfw"] = json!(nsfw);
+                }
+                if let Some(overwrites) = update.overwrites {
+                    body["overwrites"] = json!(overwrites);
+                }
+                (Method::PATCH, self.url(&["servers", server_id, "channels", &channel_id]), body)
+            }
+            ChannelEdit::DeleteChannel { channel_id } => (
+                Method::DELETE,
+                self.url(&["servers", server_id, "channels", &channel_id]),
+                json!({}),
+            ),
+            ChannelEdit::MoveChannel { channel_id, category, to } => (
+                Method::PATCH,
+                self.url(&["servers", server_id, "channels", &channel_id]),
+                json!({ "category": category, "position": to }),
+            ),
+        };
+        self.send_json(method, url, Some(body))
+    }
+
+    fn update_member(
+        &self,
+        server_id: &str,
+        user_id: &str,
+        update: MemberUpdate,
+        reason: Option<String>,
+    ) -> BackendFuture<Server> {
+        let mut body = json!({ "reason": reason });
+        if let Some(nickname) = update.nickname {
+            body["nick"] = json!(nickname);
+        }
+        if let Some(roles) = update.roles {
+            body["roles"] = json!(roles);
+        }
+        self.send_json(
+            Method::PATCH,
+            self.url(&["servers", server_id, "members", user_id]),
+            Some(body),
+        )
+    }
+
+    fn moderate_member(
+        &self,
+        server_id: &str,
+        user_id: &str,
+        action: ModerationAction,
+        reason: Option<String>,
+    ) -> BackendFuture<Server> {
+        let member = self.url(&["servers", server_id, "members", user_id]);
+        let timeout = |until: Option<SystemTime>| {
+            let until = until.map(|until| until.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs());
+            json!({ "communication_disabled_until": until, "reason": reason })
+        };
+        let (method, url, body) = match action {
+            ModerationAction::Kick => (Method::DELETE, member, json!({ "reason": reason })),
+            ModerationAction::Ban { delete_messages } => (
+                Method::PUT,
+                self.url(&["servers", server_id, "bans", user_id]),
+                json!({ "delete_message_seconds": delete_messages.as_secs(), "reason": reason }),
+            ),
+            ModerationAction::Timeout { until } => (Method::PATCH, member, timeout(Some(until))),
+            ModerationAction::RemoveTimeout => (Method::PATCH, member, timeout(None)),
+        };
+        self.send_json(method, url, Some(body))
+    }
+
+    fn fetch_audit_log(&self, server_id: &str, query: AuditLogQuery) -> BackendFuture<Vec<AuditLogEntry>> {
+        let url = self.url(&["servers", server_id, "audit-log"]).map(|mut url| {
+            {
+                let mut pairs = url.query_pairs_mut();
+                pairs.append_pair("limit", &PAGE_SIZE.to_string());
+                if let Some(before) = &query.before {
+                    pairs.append_pair("before", before);
+                }
+                if let Some(user_id) = &query.user_id {
+                    pairs.append_pair("user_id", user_id);
+                }
+                if let Some(action) = query.action
+                    && let Ok(serde_json::Value::String(action)) = serde_json::to_value(action)
+                {
+                    pairs.append_pair("action_type", &action);
+                }
+            }
+            url
+        });
+        self.send_json(Method::GET, url, None)
+    }
+
+    fn fetch_history(
+        &self,
+        target: &MessageTarget,
+        before: Option<String>,
+        limit: usize,
+    ) -> BackendFuture<Vec<Message>> {
+        let url = self.messages_url(target, &[]).map(|mut url| {
+            {
+                let mut query = url.query_pairs_mut();
+                query.append_pair("limit", &limit.to_string());
+                if let Some(before) = &before {
+                    query.append_pair("before", before);
+                }
+            }
+            url
+        });
+        self.send_json(Method::GET, url, None)
+    }
+
+    fn send_message(&self, target: &MessageTarget, draft: MessageDraft) -> BackendFuture<Message> {
+        let body = match serde_json::to_value(&draft) {
+            Ok(body) => body,
+            Err(err) => return futures::future::ready(Err(err.into())).boxed(),
+        };
+        self.send_json(Method::POST, self.messages_url(target, &[]), Some(body))
+    }
+
+    fn edit_message(
+        &self,
+        target: &MessageTarget,
+        message_id: &str,
+        content: String,
+    ) -> BackendFuture<Message> {
+        self.send_json(
+            Method::PATCH,
+            self.messages_url(target, &[message_id]),
+            Some(json!({ "content": content })),
+        )
+    }
+
+    fn delete_message(&self, target: &MessageTarget, message_id: &str) -> BackendFuture<()> {
+        self.send_empty(Method::DELETE, self.messages_url(target, &[message_id]), None)
+    }
+
+    fn set_pinned(&self, target: &MessageTarget, message_id: &str, pinned: bool) -> BackendFuture<()> {
+        let method = if pinned { Method::PUT } else { Method::DELETE };
+        self.send_empty(method, self.url(&["channels", target.id(), "pins", message_id]), None)
+    }
+
+    fn set_reaction(
+        &self,
+        target: &MessageTarget,
+        message_id: &str,
+        emoji: &str,
+        add: bool,
+    ) -> BackendFuture<()> {
+        let method = if add { Method::PUT } else { Method::DELETE };
+        let url = self.messages_url(target, &[message_id, "reactions", emoji, "@me"]);
+        self.send_empty(method, url, None)
+    }
+
+    fn fetch_read_states(&self) -> BackendFuture<Vec<ReadState>> {
+        self.send_json(Method::GET, self.url(&["users", "@me", "read-states"]), None)
+    }
+
+    fn ack_message(&self, target: &MessageTarget, message_id: &str) -> BackendFuture<()> {
+        self.send_empty(Method::POST, self.messages_url(target, &[message_id, "ack"]), None)
+    }
+
+    fn start_typing(&self, target: &MessageTarget) -> BackendFuture<()> {
+        self.send_empty(
+            Method::POST,
+            self.url(&["channels", target.id(), "typing"]),
+            None,
+        )
+    }
+
+    fn set_presence(&self, status: UserStatus) -> BackendFuture<()> {
+        self.send_empty(
+            Method::PATCH,
+            self.url(&["users", "@me", "presence"]),
+            Some(json!({ "status": status })),
+        )
+    }
+
+    fn upload_limits(&self) -> BackendFuture<UploadLimits> {
+        self.send_json(Method::GET, self.url(&["attachments", "limits"]), None)
+    }
+
+    fn upload_attachment(
+        &self,
+        target: &MessageTarget,
+        file: AttachmentUpload,
+        progress: UploadProgress,
+    ) -> BackendFuture<Attachment> {
+        let client = self.client.clone();
+        let token = self.token.read().ok().and_then(|t| t.clone());
+        let url = self.url(&["channels", target.id(), "attachments"]).map(|mut url| {
+            url.query_pairs_mut().append_pair("filename", &file.filename);
+            url
+        });
+
+        async move {
+            let url = url?;
+            let reader = std::fs::File::open(&file.path)
+                .with_context(|| format!("opening {}", file.path.display()))?;
+            let body = AsyncBody::from_reader(CountingReader {
+                inner: AllowStdIo::new(reader),
+                sent: 0,
+                progress,
+            });
+            let mut builder = Request::builder()
+                .method(Method::POST)
+                .uri(url.as_str())
+                .header("Accept", "application/json")
+                .header("Content-Type", file.mime_type.as_str())
+                .header("Content-Length", file.size);
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {token}"));
+            }
+
+            let response = read_response(client.send(builder.body(body)?).await?).await?;
+            match response.status {
+                status if status.is_success() => Ok(serde_json::from_str(&response.body)?),
+                StatusCode::PAYLOAD_TOO_LARGE => bail!("{} is larger than the server accepts", file.filename),
+                status => bail!("Uploading {} failed with {status}: {}", file.filename, response.body),
+            }
+        }
+        .boxed()
+    }
+
+    fn download_attachment(&self, attachment: &Attachment, dest: PathBuf) -> BackendFuture<()> {
+        let client = self.client.clone();
+        let url = attachment.url.clone();
+        // Files may be served from elsewhere; only the API gets the token.
+        let same_origin = Url::parse(&url).is_ok_and(|url| url.origin() == self.base_url.origin());
+        let token = self
+            .token
+            .read()
+            .ok()
+            .and_then(|t| t.clone())
+            .filter(|_| same_origin);
+
+        async move {
+            let mut builder = Request::builder().method(Method::GET).uri(url.as_str());
+            if let Some(token) = token {
+                builder = builder.header("Authorization", format!("Bearer {token}"));
+            }
+            let mut response = client.send(builder.body(AsyncBody::empty())?).await?;
+            if !response.status().is_success() {
+                bail!("GET {url} failed with {}", response.status());
+            }
+            let file = std::fs::File::create(&dest)
+                .with_context(|| format!("creating {}", dest.display()))?;
+            if let Err(err) = futures::io::copy(response.body_mut(), &mut AllowStdIo::new(file)).await {
+                std::fs::remove_file(&dest).ok();
+                return Err(err.into());
+            }
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn gateway_url(&self) -> Option<String> {
+        let mut url = self.url(&["gateway"]).ok()?;
+        let scheme = match url.scheme() {
+            "https" => "wss",
+            _ => "ws",
+        };
+        url.set_scheme(scheme).ok()?;
+        Some(url.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead as _, BufReader, Read as _, Write as _};
+    use std::net::TcpListener;
+    use std::sync::Mutex;
+    use std::thread;
+
+    use futures::executor::block_on;
+    use reqwest_client::ReqwestClient;
+
+    use super::*;
+    use crate::backend::is_transient;
+
+    /// A request as seen by the stand-in server.
+    #[derive(Debug)]
+    struct Recorded {
+        request_line: String,
+        body: serde_json::Value,
+        authorization: Option<String>,
+    }
+
+    /// Minimal HTTP/1.1 server answering each connection with the next
+    /// canned response, then closing it.
+    struct StandIn {
+        url: Url,
+        requests: Arc<Mutex<Vec<Recorded>>>,
+    }
+
+    impl StandIn {
+        fn serve(responses: Vec<String>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = Url::parse(&format!("http://{}/api", listener.local_addr().unwrap())).unwrap();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+
+            let recorded = requests.clone();
+            thread::spawn(move || {
+                for response in responses {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+
+                    let mut content_length = 0;
+                    let mut authorization = None;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        let line = line.trim_end();
+                        if line.is_empty() {
+                            break;
+                        }
+                        let (name, value) = line.split_once(':').unwrap();
+                        match name.to_ascii_lowercase().as_str() {
+                            "content-length" => content_length = value.trim().parse().unwrap(),
+                            "authorization" => authorization = Some(value.trim().to_string()),
+                            _ => {}
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+
+                    recorded.lock().unwrap().push(Recorded {
+                        request_line: request_line.trim_end().to_string(),
+                        body: serde_json::from_slice(&body).unwrap_or_default(),
+                        authorization,
+                    });
+                    reader.get_mut().write_all(response.as_bytes()).unwrap();
+                }
+            });
+
+            Self { url, requests }
+        }
+
+        fn backend(&self) -> HttpBackend {
+            HttpBackend::new(Arc::new(ReqwestClient::new()), self.url.clone())
+        }
+    }
+
+    fn response(status: &str, headers: &[&str], body: &str) -> String {
+        let mut text = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+        for header in headers {
+            text.push_str(header);
+            text.push_str("\r\n");
+        }
+        text.push_str(&format!("Content-Length: {}\r\n\r\n{body}", body.len()));
+        text
+    }
+
+    const USER_JSON: &str =
+        r#"{"id":"u1","username":"alice","discriminator":"0001","status":"Online"}"#;
+
+    #[test]
+    fn test_login_success() {
+        let body = format!(
+            r#"{{"token":"tok","refresh_token":"ref","expires_in":3600,"user":{USER_JSON}}}"#
+        );
+        let server = StandIn::serve(vec![response("200 OK", &[], &body)]);
+        let backend = server.backend();
+
+        let session = block_on(backend.login("alice@example.com", "hunter22")).unwrap();
+        assert_eq!(session.user.username, "alice");
+        assert_eq!(session.token, "tok");
+        assert_eq!(session.refresh_token.as_deref(), Some("ref"));
+        let expires_in = session
+            .expires_at
+            .unwrap()
+            .duration_since(SystemTime::now())
+            .unwrap();
+        assert!(expires_in > Duration::from_secs(3500));
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].request_line, "POST /api/auth/login HTTP/1.1");
+        assert_eq!(requests[0].body["login"], "alice@example.com");
+        assert_eq!(requests[0].body["password"], "hunter22");
+        assert_eq!(requests[0].authorization, None);
+    }
+
+    #[test]
+    fn test_login_errors_are_structured() {
+        let server = StandIn::serve(vec![
+            response("401 Unauthorized", &[], r#"{"message":"bad password"}"#),
+            response("429 Too Many Requests", &["Retry-After: 30"], "{}"),
+            response("429 Too Many Requests", &[], r#"{"retry_after":1.5}"#),
+            response("200 OK", &[], r#"{"mfa":true,"ticket":"t-123"}"#),
+            response("503 Service Unavailable", &[], r#"{"message":"maintenance"}"#),
+        ]);
+        let backend = server.backend();
+        let login = || block_on(backend.login("alice", "hunter22"));
+
+        assert_eq!(login(), Err(AuthError::InvalidCredentials));
+        assert_eq!(
+            login(),
+            Err(AuthError::RateLimited {
+                retry_after: Some(Duration::from_secs(30))
+            })
+        );
+        assert_eq!(
+            login(),
+            Err(AuthError::RateLimited {
+                retry_after: Some(Duration::from_millis(1500))
+            })
+        );
+        assert_eq!(
+            login(),
+            Err(AuthError::TwoFactorRequired {
+                ticket: "t-123".into()
+            })
+        );
+        assert_eq!(
+            login(),
+            Err(AuthError::Server {
+                status: 503,
+                message: "maintenance".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_login_network_failure() {
+        // Bind and immediately drop a listener to get a port nothing serves.
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let url = Url::parse(&format!("http://{addr}")).unwrap();
+        let backend = HttpBackend::new(Arc::new(ReqwestClient::new()), url);
+
+        let result = block_on(backend.login("alice", "hunter22"));
+        assert!(matches!(result, Err(AuthError::Network(_))), "{result:?}");
+    }
+
+    #[test]
+    fn test_refresh_session() {
+        let body = format!(r#"{{"token":"tok2","refresh_token":"ref2","user":{USER_JSON}}}"#);
+        let server = StandIn::serve(vec![
+            response("200 OK", &[], &body),
+            response("401 Unauthorized", &[], "{}"),
+            response("404 Not Found", &[], "{}"),
+            response("503 Service Unavailable", &[], "{}"),
+        ]);
+        let backend = server.backend();
+
+        let session = block_on(backend.refresh_session("ref1")).unwrap();
+        assert_eq!(session.token, "tok2");
+        assert_eq!(session.expires_at, None);
+        assert_eq!(
+            block_on(backend.refresh_session("ref2")),
+            Err(AuthError::SessionExpired)
+        );
+        // Only a server-side failure is worth retrying.
+        assert!(!block_on(backend.refresh_session("ref2")).unwrap_err().is_transient());
+        assert!(block_on(backend.refresh_session("ref2")).unwrap_err().is_transient());
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].request_line, "POST /api/auth/refresh HTTP/1.1");
+        assert_eq!(requests[0].body["refresh_token"], "ref1");
+    }
+
+    #[test]
+    fn test_mfa_routes() {
+        let session = format!(r#"{{"token":"tok","mfa_enabled":true,"user":{USER_JSON}}}"#);
+        let server = StandIn::serve(vec![
+            response("400 Bad Request", &[], "{}"),
+            response("401 Unauthorized", &[], "{}"),
+            response("200 OK", &[], &session),
+            response("200 OK", &[], r#"{"secret":"ABC","uri":"otpauth://totp/x"}"#),
+            response("200 OK", &[], r#"{"backup_codes":["abcd-efgh"]}"#),
+            response("204 No Content", &[], ""),
+        ]);
+        let backend = server.backend();
+
+        assert_eq!(
+            block_on(backend.verify_mfa("t-1", MfaCode::Totp("000000".into()))),
+            Err(AuthError::InvalidCode)
+        );
+        assert_eq!(
+            block_on(backend.verify_mfa("t-0", MfaCode::Totp("000000".into()))),
+            Err(AuthError::MfaTicketExpired)
+        );
+        let session = block_on(backend.verify_mfa("t-1", MfaCode::Backup("abcd-efgh".into())));
+        assert!(session.unwrap().mfa_enabled);
+
+        backend.set_session(Some(BackendSession {
+            user: serde_json::from_str(USER_JSON).unwrap(),
+            token: "tok".into(),
+        }));
+        let enrollment = block_on(backend.begin_mfa_enrollment()).unwrap();
+        assert_eq!(enrollment.secret, "ABC");
+        let codes = block_on(backend.enable_mfa("123456")).unwrap();
+        assert_eq!(codes, vec!["abcd-efgh".to_string()]);
+        block_on(backend.disable_mfa(MfaCode::Totp("123456".into()))).unwrap();
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].request_line, "POST /api/auth/mfa HTTP/1.1");
+        assert_eq!(requests[0].body["ticket"], "t-1");
+        assert_eq!(requests[0].body["method"], "totp");
+        assert_eq!(requests[1].body["ticket"], "t-0");
+        assert_eq!(requests[2].body["method"], "backup");
+        assert_eq!(requests[2].body["code"], "abcd-efgh");
+        assert_eq!(
+            requests[3].request_line,
+            "POST /api/users/@me/mfa/totp/enroll HTTP/1.1"
+        );
+        assert_eq!(requests[3].authorization.as_deref(), Some("Bearer tok"));
+        assert_eq!(requests[4].body["code"], "123456");
+        assert_eq!(
+            requests[5].request_line,
+            "POST /api/users/@me/mfa/totp/disable HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn test_requests_carry_session_token() {
+        let server = StandIn::serve(vec![response("200 OK", &[], "[]")]);
+        let backend = server.backend();
+        backend.set_session(Some(BackendSession {
+            user: serde_json::from_str(USER_JSON).unwrap(),
+            token: "tok".into(),
+        }));
+
+        let servers = block_on(backend.list_servers()).unwrap();
+        assert!(servers.is_empty());
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests[0].request_line, "GET /api/servers HTTP/1.1");
+        assert_eq!(requests[0].authorization.as_deref(), Some("Bearer tok"));
+    }
+
+    #[test]
+    fn test_error_statuses_say_whether_to_retry() {
+        let server = StandIn::serve(vec![
+            response("403 Forbidden", &[], r#"{"message":"missing permissions"}"#),
+            response("429 Too Many Requests", &[], "{}"),
+            response("503 Service Unavailable", &[], "{}"),
+        ]);
+        let backend = server.backend();
+        let err = block_on(backend.list_servers()).unwrap_err();
+        assert_eq!(err.downcast_ref::<StatusError>().map(|err| err.status), Some(403));
+        assert!(!is_transient(&err));
+        assert!(is_transient(&block_on(backend.list_servers()).unwrap_err()));
+        assert!(is_transient(&block_on(backend.list_servers()).unwrap_err()));
+    }
+
+    #[test]
+    fn test_upload_attachment_streams_the_file() {
+        let attachment = r#"{"id":"a1","filename":"notes.txt","mime_type":"text/plain","size":5,"url":"https://cdn.example/a1"}"#;
+        let server = StandIn::serve(vec![
+            response("200 OK", &[], attachment),
+            response("413 Payload Too Large", &[], "{}"),
+        ]);
+        let backend = server.backend();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "hello").unwrap();
+        let upload = AttachmentUpload::from_path(&path).unwrap();
+        let target = MessageTarget::Channel("c1".into());
+        let (progress, sent) = futures::channel::mpsc::unbounded();
+
+        let uploaded = block_on(backend.upload_attachment(&target, upload.clone(), progress.clone())).unwrap();
+        assert_eq!(uploaded.url, "https://cdn.example/a1");
+        drop(progress);
+        assert_eq!(block_on(futures::StreamExt::collect::<Vec<_>>(sent)).last(), Some(&5));
+
+        let (progress, _) = futures::channel::mpsc::unbounded();
+        let err = block_on(backend.upload_attachment(&target, upload, progress)).unwrap_err();
+        assert_eq!(err.to_string(), "notes.txt is larger than the server accepts");
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(
+            requests[0].request_line,
+            "POST /api/channels/c1/attachments?filename=notes.txt HTTP/1.1"
+        );
+    }
+}
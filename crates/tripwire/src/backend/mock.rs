@@ -0,0 +1,1310 @@
+//! In-memory backend serving the sample data from `mock_data`.
+
+use std::collections::HashMap;
+use std::io::{Read as _, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, Result, anyhow, bail};
+use futures::FutureExt as _;
+
+use super::{
+    AuthError, AuthFuture, AuthSession, BackendFuture, BackendSession, ChatBackend, MemberUpdate,
+    MessageDraft, MessageTarget, MfaCode, MfaEnrollment, StatusError, UploadProgress,
+};
+use crate::attachments::{AttachmentUpload, UploadLimits};
+use crate::audit_log::{self, AuditAction, AuditLogEntry, AuditLogQuery};
+use crate::channels::{self, ChannelEdit};
+use crate::members::{self, ModerationAction};
+use crate::mock_data;
+use crate::totp;
+use crate::models::{Attachment, AttachmentSource, DirectMessageChannel, MemberInfo, Message, Role, Server, User, UserStatus};
+use crate::permissions::{self, Permissions};
+use crate::read_state::ReadState;
+use crate::role_draft::RoleChanges;
+
+/// How much of a file `upload_attachment` copies between progress reports.
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Backend that keeps everything in memory. Message ids are assigned from a
+/// counter so runs are reproducible.
+pub struct MockBackend {
+    state: Mutex<MockState>,
+}
+
+struct MockState {
+    session: Option<BackendSession>,
+    servers: Vec<Server>,
+    dm_channels: Vec<DirectMessageChannel>,
+    /// History per target, seeded from `mock_data` on first access.
+    messages: HashMap<MessageTarget, Vec<Message>>,
+    /// Refresh tokens handed out by `login`, and whose session they renew.
+    refresh_tokens: HashMap<String, User>,
+    /// Two-factor settings per user id.
+    mfa: HashMap<String, MockMfa>,
+    /// Logins waiting for a second factor, by ticket.
+    mfa_tickets: HashMap<String, User>,
+    /// Ids of sent messages by draft nonce, to ignore retried sends.
+    sent_nonces: HashMap<String, String>,
+    /// Read state per conversation id.
+    read_states: HashMap<String, ReadState>,
+    /// Audit log per server id, oldest first.
+    audit_log: HashMap<String, Vec<AuditLogEntry>>,
+    /// Copies of uploaded files, made on the first upload and removed with
+    /// the backend.
+    uploads: Option<tempfile::TempDir>,
+    next_id: u64,
+}
+
+struct MockMfa {
+    secret: Vec<u8>,
+    /// `false` while enrollment waits for its first code.
+    enabled: bool,
+    /// Unused backup codes, normalized.
+    backup_codes: Vec<String>,
+}
+
+impl MockMfa {
+    /// Check `code`, consuming it if it's a backup code.
+    fn accept(&mut self, code: &MfaCode) -> bool {
+        match code {
+            MfaCode::Totp(code) => totp::verify(&self.secret, code, unix_now()),
+            MfaCode::Backup(code) => {
+                let code = totp::normalize_backup_code(code);
+                let before = self.backup_codes.len();
+                self.backup_codes.retain(|c| *c != code);
+                self.backup_codes.len() != before
+            }
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl MockState {
+    fn history_mut(&mut self, target: &MessageTarget) -> &mut Vec<Message> {
+        self.messages
+            .entry(target.clone())
+            .or_insert_with(|| match target {
+                MessageTarget::Channel(id) => mock_data::make_messages_for(id),
+                MessageTarget::DirectMessage(id) => mock_data::make_dm_messages_for(id),
+            })
+    }
+
+    fn message_mut(&mut self, target: &MessageTarget, message_id: &str) -> Result<&mut Message> {
+        self.history_mut(target)
+            .iter_mut()
+            .find(|m| m.id == message_id)
+            .with_context(|| format!("message {message_id} not found"))
+    }
+
+    fn session_user(&self) -> Result<User, AuthError> {
+        self.session
+            .as_ref()
+            .map(|s| s.user.clone())
+            .ok_or(AuthError::SessionExpired)
+    }
+
+    fn current_user_id(&self) -> Result<String> {
+        self.session
+            .as_ref()
+            .map(|s| s.user.id.clone())
+            .ok_or_else(|| anyhow!("not logged in"))
+    }
+
+    fn server_ix(&self, server_id: &str) -> Result<usize> {
+        self.servers
+            .iter()
+            .position(|s| s.id == server_id)
+            .with_context(|| format!("server {server_id} not found"))
+    }
+
+    /// Note in `server_id`'s audit log that `actor_id` did `action`,
+    /// returning the entry to fill in.
+    fn record(
+        &mut self,
+        server_id: &str,
+        actor_id: &str,
+        action: AuditAction,
+        target_id: Option<&str>,
+    ) -> &mut AuditLogEntry {
+        let entry = AuditLogEntry {
+            id: format!("mock_audit_{}", self.next_id),
+            actor_id: actor_id.to_string(),
+            action,
+            target_id: target_id.map(String::from),
+            channel_id: None,
+            changes: Vec::new(),
+            reason: None,
+            created_at: SystemTime::now(),
+        };
+        self.next_id += 1;
+        let log = self.audit_log.entry(server_id.to_string()).or_default();
+        log.push(entry);
+        log.last_mut().expect("just pushed")
+    }
+
+    /// Note a moderator's `action` on a message by `author_id` in the log
+    /// of the server `target` is in, if any.
+    fn record_message_action(&mut self, target: &MessageTarget, actor_id: &str, action: AuditAction, author_id: &str) {
+        let MessageTarget::Channel(channel_id) = target else {
+            return;
+        };
+        if let Some(server_id) = self.channel_server(channel_id) {
+            self.record(&server_id, actor_id, action, Some(author_id)).channel_id = Some(channel_id.clone());
+        }
+    }
+
+    /// ID of the server `channel_id` is in.
+    fn channel_server(&self, channel_id: &str) -> Option<String> {
+        self.servers
+            .iter()
+            .find(|server| server.all_channels().iter().any(|c| c.id == channel_id))
+            .map(|server| server.id.clone())
+    }
+
+    /// Fail unless the signed-in user has `required` in `target`.
+    fn require(&self, target: &MessageTarget, required: Permissions) -> Result<()> {
+        let user_id = self.current_user_id()?;
+        let granted = match target {
+            MessageTarget::DirectMessage(_) => Permissions::direct_message(),
+            MessageTarget::Channel(id) => self
+                .servers
+                .iter()
+                .find_map(|server| {
+                    let channel = server.all_channels().into_iter().find(|c| c.id == *id)?;
+                    Some(permissions::compute_permissions(server, &user_id, channel))
+                })
+                .ok_or_else(|| StatusError {
+                    status: 404,
+                    message: format!("channel {id} not found"),
+                })?,
+        };
+        // Answered like the server would, so callers don't retry.
+        if !granted.contains(required) {
+            return Err(StatusError {
+                status: 403,
+                message: format!("missing permissions: {:?}", required.difference(granted)),
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(MockState {
+                session: None,
+                servers: mock_data::make_servers(),
+                dm_channels: mock_data::make_dm_channels(),
+                messages: HashMap::new(),
+                refresh_tokens: HashMap::new(),
+                mfa: HashMap::new(),
+                mfa_tickets: HashMap::new(),
+                sent_nonces: HashMap::new(),
+                read_states: mock_data::make_read_states()
+                    .into_iter()
+                    .map(|state| (state.conversation_id.clone(), state))
+                    .collect(),
+                audit_log: mock_data::make_audit_log(),
+                uploads: None,
+                next_id: 1,
+            }),
+        }
+    }
+
+    /// Run `f` against the locked state and wrap the result in a ready future.
+    fn with_state<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut MockState) -> Result<T>,
+    ) -> BackendFuture<T> {
+        let result = match self.state.lock() {
+            Ok(mut state) => f(&mut state),
+            Err(_) => Err(anyhow!("mock backend state poisoned")),
+        };
+        futures::future::ready(result).boxed()
+    }
+
+    /// Like `with_state`, for the authentication calls.
+    fn with_auth_state<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut MockState) -> Result<T, AuthError>,
+    ) -> AuthFuture<T> {
+        let result = match self.state.lock() {
+            Ok(mut state) => f(&mut state),
+            Err(_) => Err(AuthError::Network("mock backend state poisoned".into())),
+        };
+        futures::future::ready(result).boxed()
+    }
+
+    /// Issue a session for `user` with a fresh refresh token. Mock tokens
+    /// never expire.
+    fn issue_session(state: &mut MockState, user: User) -> AuthSession {
+        let refresh_token = format!("refresh_mock_{}_{}", user.id, state.next_id);
+        state.next_id += 1;
+        state.refresh_tokens.insert(refresh_token.clone(), user.clone());
+        // Whoever signs in administers the first server, so there's
+        // something to moderate; elsewhere they're an ordinary member.
+        if let Some(server) = state.servers.first_mut()
+            && !server.members.iter().any(|m| m.id == user.id)
+        {
+            server.members.push(user.clone());
+            server.member_roles.insert(user.id.clone(), vec!["r1".to_string()]);
+            server.member_info.insert(
+                user.id.clone(),
+                MemberInfo {
+                    joined_at: Some(SystemTime::now()),
+                    ..MemberInfo::default()
+                },
+            );
+        }
+        AuthSession {
+            token: format!("token_mock_{}", user.id),
+            refresh_token: Some(refresh_token),
+            expires_at: None,
+            mfa_enabled: state.mfa.get(&user.id).is_some_and(|mfa| mfa.enabled),
+            user,
+        }
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChatBackend for MockBackend {
+    /// Accepts any login with a password of at least six characters, asking
+    /// for a second factor once the user has enabled it.
+    fn login(&self, login: &str, password: &str) -> AuthFuture<AuthSession> {
+        if password.len() < 6 {
+            return futures::future::ready(Err(AuthError::InvalidCredentials)).boxed();
+        }
+        let username = login.split('@').next().unwrap_or("user").to_string();
+        let user = User {
+            id: format!("user_{}", username),
+            username,
+            discriminator: "0001".to_string(),
+            status: UserStatus::Online,
+        };
+        self.with_auth_state(move |state| {
+            if state.mfa.get(&user.id).is_some_and(|mfa| mfa.enabled) {
+                let ticket = format!("mfa_ticket_{}", state.next_id);
+                state.next_id += 1;
+                state.mfa_tickets.insert(ticket.clone(), user);
+                return Err(AuthError::TwoFactorRequired { ticket });
+            }
+            Ok(Self::issue_session(state, user))
+        })
+    }
+
+    fn refresh_session(&self, refresh_token: &str) -> AuthFuture<AuthSession> {
+        let refresh_token = refresh_token.to_string();
+        self.with_auth_state(move |state| match state.refresh_tokens.remove(&refresh_token) {
+            Some(user) => Ok(Self::issue_session(state, user)),
+            None => Err(AuthError::SessionExpired),
+        })
+    }
+
+    fn verify_mfa(&self, ticket: &str, code: MfaCode) -> AuthFuture<AuthSession> {
+        let ticket = ticket.to_string();
+        self.with_auth_state(move |state| {
+            let user = state
+                .mfa_tickets
+                .get(&ticket)
+                .cloned()
+                .ok_or(AuthError::MfaTicketExpired)?;
+            let accepted = state
+                .mfa
+                .get_mut(&user.id)
+                .is_some_and(|mfa| mfa.accept(&code));
+            if !accepted {
+                return Err(AuthError::InvalidCode);
+            }
+            state.mfa_tickets.remove(&ticket);
+            Ok(Self::issue_session(state, user))
+        })
+    }
+
+    fn begin_mfa_enrollment(&self) -> AuthFuture<MfaEnrollment> {
+        self.with_auth_state(|state| {
+            let user = state.session_user()?;
+            if state.mfa.get(&user.id).is_some_and(|mfa| mfa.enabled) {
+                return Err(AuthError::Validation(
+                    "Two-factor authentication is already enabled.".into(),
+                ));
+            }
+            let secret = totp::generate_secret();
+            let enrollment = MfaEnrollment {
+                secret: totp::encode_secret(&secret),
+                uri: totp::otpauth_uri(&secret, &user.username, "Tripwire"),
+            };
+            state.mfa.insert(
+                user.id,
+                MockMfa {
+                    secret,
+                    enabled: false,
+                    backup_codes: Vec::new(),
+                },
+            );
+            Ok(enrollment)
+        })
+    }
+
+    fn enable_mfa(&self, code: &str) -> AuthFuture<Vec<String>> {
+        let code = code.to_string();
+        self.with_auth_state(move |state| {
+            let user = state.session_user()?;
+            let mfa = state
+                .mfa
+                .get_mut(&user.id)
+                .filter(|mfa| !mfa.enabled)
+                .ok_or_else(|| AuthError::Validation("Start enrollment first.".into()))?;
+            if !totp::verify(&mfa.secret, &code, unix_now()) {
+                return Err(AuthError::InvalidCode);
+            }
+            let codes = totp::generate_backup_codes(10);
+            mfa.enabled = true;
+            mfa.backup_codes = codes.iter().map(|c| totp::normalize_backup_code(c)).collect();
+            Ok(codes)
+        })
+    }
+
+    fn disable_mfa(&self, code: MfaCode) -> AuthFuture<()> {
+        self.with_auth_state(move |state| {
+            let user = state.session_user()?;
+            let accepted = state
+                .mfa
+                .get_mut(&user.id)
+                .filter(|mfa| mfa.enabled)
+                .is_some_and(|mfa| mfa.accept(&code));
+            if !accepted {
+                return Err(AuthError::InvalidCode);
+            }
+            state.mfa.remove(&user.id);
+            Ok(())
+        })
+    }
+
+    fn set_session(&self, session: Option<BackendSession>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.session = session;
+        }
+    }
+
+    fn list_servers(&self) -> BackendFuture<Vec<Server>> {
+        self.with_state(|state| Ok(state.servers.clone()))
+    }
+
+    fn list_dm_channels(&self) -> BackendFuture<Vec<DirectMessageChannel>> {
+        self.with_state(|state| Ok(state.dm_channels.clone()))
+    }
+
+    fn update_roles(&self, server_id: &str, changes: RoleChanges) -> BackendFuture<Server> {
+        let server_id = server_id.to_string();
+        self.with_state(move |state| {
+            let user_id = state.current_user_id()?;
+            let ix = state.server_ix(&server_id)?;
+            let mut next_id = state.next_id;
+            let server = &mut state.servers[ix];
+            let old_roles = server.roles.clone();
+            let old_member_roles = server.member_roles.clone();
+            changes.apply(server, &user_id, || {
+                let id = format!("mock_role_{next_id}");
+                next_id += 1;
+                id
+            })?;
+            let saved = server.clone();
+            state.next_id = next_id;
+
+            for (ix, role) in saved.roles.iter().enumerate() {
+                let old_ix = old_roles.iter().position(|r| r.id == role.id);
+                let mut changes = audit_log::role_changes(old_ix.map(|ix| &old_roles[ix]), Some(role));
+                changes.extend(audit_log::change(
+                    "position",
+                    old_ix.map(|ix| (ix + 1).to_string()),
+                    Some((ix + 1).to_string()),
+                ));
+                let action = match old_ix {
+                    None => AuditAction::RoleCreate,
+                    Some(_) if !changes.is_empty() => AuditAction::RoleUpdate,
+                    Some(_) => continue,
+                };
+                state.record(&server_id, &user_id, action, Some(&role.id)).changes = changes;
+            }
+            for role in old_roles.iter().filter(|r| !saved.roles.iter().any(|new| new.id == r.id)) {
+                state.record(&server_id, &user_id, AuditAction::RoleDelete, Some(&role.id)).changes =
+                    audit_log::role_changes(Some(role), None);
+            }
+            // Deleted roles' names are still wanted for those who held them.
+            let all_roles: Vec<Role> = saved
+                .roles
+                .iter()
+                .chain(old_roles.iter().filter(|r| !saved.roles.iter().any(|new| new.id == r.id)))
+                .cloned()
+                .collect();
+            let mut holders: Vec<&String> = old_member_roles.keys().chain(saved.member_roles.keys()).collect();
+            holders.sort();
+            holders.dedup();
+            for holder in holders {
+                let old = old_member_roles.get(holder).map_or(&[][..], Vec::as_slice);
+                let new = saved.member_roles.get(holder).map_or(&[][..], Vec::as_slice);
+                if let Some(change) = audit_log::member_role_change(&all_roles, old, new) {
+                    state.record(&server_id, &user_id, AuditAction::MemberRoleUpdate, Some(holder)).changes = vec![change];
+                }
+            }
+            Ok(saved)
+        })
+    }
+
+    fn edit_channels(&self, server_id: &str, edit: ChannelEdit) -> BackendFuture<Server> {
+        let server_id = server_id.to_string();
+        self.with_state(move |state| {
+            let user_id = state.current_user_id()?;
+            let ix = state.server_ix(&server_id)?;
+            let granted = permissions::server_permissions(&state.servers[ix], &user_id);
+            if !granted.contains(Permissions::MANAGE_CHANNELS) {
+                bail!("missing permissions: {:?}", Permissions::MANAGE_CHANNELS);
+            }
+            let old_categories = state.servers[ix].categories.clone();
+            let mut categories = old_categories.clone();
+            edit.apply(&mut categories)?;
+
+            for channel in categories.iter_mut().flat_map(|c| c.channels.iter_mut()) {
+                channel.overwrites.retain(|o| !o.allow.is_empty() || !o.deny.is_empty());
+                if channel.id == channels::NEW_CHANNEL_ID {
+                    channel.id = format!("mock_channel_{}", state.next_id);
+                    state.next_id += 1;
+                }
+            }
+            state.servers[ix].categories = categories;
+            let saved = state.servers[ix].clone();
+
+            for category in &saved.categories {
+                if !old_categories.iter().any(|c| c.name == category.name) {
+                    state.record(&server_id, &user_id, AuditAction::ChannelCreate, None).changes =
+                        audit_log::change("category", None, Some(category.name.clone())).into_iter().collect();
+                }
+            }
+            for category in &saved.categories {
+                for (ch_ix, channel) in category.channels.iter().enumerate() {
+                    let old = channels::find_channel(&old_categories, &channel.id);
+                    let old_channel = old.map(|(cat_ix, ch_ix)| &old_categories[cat_ix].channels[ch_ix]);
+                    let mut changes = audit_log::channel_changes(&saved, old_channel, Some(channel));
+                    changes.extend(audit_log::change(
+                        "category",
+                        old.map(|(cat_ix, _)| old_categories[cat_ix].name.clone()),
+                        Some(category.name.clone()),
+                    ));
+                    // Moving to another category isn't also a new position.
+                    if old.is_some_and(|(old_cat, _)| old_categories[old_cat].name == category.name) {
+                        changes.extend(audit_log::change(
+                            "position",
+                            old.map(|(_, ch_ix)| (ch_ix + 1).to_string()),
+                            Some((ch_ix + 1).to_string()),
+                        ));
+                    }
+                    let action = match old {
+                        None => AuditAction::ChannelCreate,
+                        Some(_) if !changes.is_empty() => AuditAction::ChannelUpdate,
+                        Some(_) => continue,
+                    };
+                    state.record(&server_id, &user_id, action, Some(&channel.id)).changes = changes;
+                }
+            }
+            for category in &old_categories {
+                for channel in &category.channels {
+                    if channels::find_channel(&saved.categories, &channel.id).is_none() {
+                        state.record(&server_id, &user_id, AuditAction::ChannelDelete, Some(&channel.id)).changes =
+                            audit_log::channel_changes(&saved, Some(channel), None);
+                    }
+                }
+                if !saved.categories.iter().any(|c| c.name == category.name) {
+                    state.record(&server_id, &user_id, AuditAction::ChannelDelete, None).changes =
+                        audit_log::change("category", Some(category.name.clone()), None).into_iter().collect();
+                }
+            }
+            Ok(saved)
+        })
+    }
+
+    fn update_member(
+        &self,
+        server_id: &str,
+        user_id: &str,
+        update: MemberUpdate,
+        reason: Option<String>,
+    ) -> BackendFuture<Server> {
+        let (server_id, target_id) = (server_id.to_string(), user_id.to_string());
+        self.with_state(move |state| {
+            let actor_id = state.current_user_id()?;
+            let ix = state.server_ix(&server_id)?;
+            let server = &state.servers[ix];
+            if !server.members.iter().any(|m| m.id == target_id) {
+                bail!("member {target_id} not found");
+            }
+            let nickname = update
+                .nickname
+                .map(|nickname| nickname.filter(|n| !n.trim().is_empty()))
+                .filter(|nickname| {
+                    *nickname != server.member_info.get(&target_id).and_then(|info| info.nickname.clone())
+                });
+            if nickname.is_some() && !members::can_change_nickname(server, &actor_id, &target_id) {
+                bail!("can't change {target_id}'s nickname");
+            }
+            let held = server.member_roles.get(&target_id).cloned().unwrap_or_default();
+            let roles = update.roles.filter(|roles| {
+                roles.len() != held.len() || roles.iter().any(|role_id| !held.contains(role_id))
+            });
+            if let Some(roles) = &roles {
+                for role_id in roles.iter().filter(|r| !held.contains(r)).chain(held.iter().filter(|r| !roles.contains(r))) {
+                    if !members::can_assign_role(server, &actor_id, &target_id, role_id) {
+                        bail!("can't assign role {role_id}");
+                    }
+                }
+            }
+
+            let server = &mut state.servers[ix];
+            let info = server.member_info.entry(target_id.clone()).or_default();
+            let renamed = nickname.and_then(|nickname| {
+                audit_log::change("nickname", std::mem::replace(&mut info.nickname, nickname.clone()), nickname)
+            });
+            let reassigned = roles.and_then(|roles| {
+                let change = audit_log::member_role_change(&server.roles, &held, &roles);
+                server.member_roles.insert(target_id.clone(), roles);
+                server.member_roles.retain(|_, held| !held.is_empty());
+                change
+            });
+            let saved = server.clone();
+            if let Some(change) = renamed {
+                let entry = state.record(&server_id, &actor_id, AuditAction::MemberUpdate, Some(&target_id));
+                entry.changes = vec![change];
+                entry.reason = reason.clone();
+            }
+            if let Some(change) = reassigned {
+                let entry = state.record(&server_id, &actor_id, AuditAction::MemberRoleUpdate, Some(&target_id));
+                entry.changes = vec![change];
+                entry.reason = reason;
+            }
+            Ok(saved)
+        })
+    }
+
+    fn moderate_member(
+        &self,
+        server_id: &str,
+        user_id: &str,
+        action: ModerationAction,
+        reason: Option<String>,
+    ) -> BackendFuture<Server> {
+        let (server_id, target_id) = (server_id.to_string(), user_id.to_string());
+        self.with_state(move |state| {
+            let actor_id = state.current_user_id()?;
+            let ix = state.server_ix(&server_id)?;
+            let server = &state.servers[ix];
+            if !server.members.iter().any(|m| m.id == target_id) {
+                bail!("member {target_id} not found");
+            }
+            if !members::can_moderate(server, &actor_id, &target_id, &action) {
+                bail!("can't {} {target_id}", action.label().to_lowercase());
+            }
+
+            let channels: Vec<String> = server.all_channels().into_iter().map(|c| c.id.clone()).collect();
+            let server = &mut state.servers[ix];
+            let (audit_action, changes) = match action {
+                ModerationAction::Kick | ModerationAction::Ban { .. } => {
+                    server.members.retain(|m| m.id != target_id);
+                    server.member_roles.remove(&target_id);
+                    server.member_info.remove(&target_id);
+                    if let ModerationAction::Ban { delete_messages } = action {
+                        let since = SystemTime::now() - delete_messages;
+                        // Only histories already seeded; the rest predate
+                        // any window.
+                        for channel_id in channels {
+                            if let Some(history) = state.messages.get_mut(&MessageTarget::Channel(channel_id)) {
+                                history.retain(|m| m.author.id != target_id || m.created_at < since);
+                            }
+                        }
+                        let deleted = (!delete_messages.is_zero()).then(|| audit_log::format_duration(delete_messages));
+                        (AuditAction::MemberBan, audit_log::change("deleted messages from", None, deleted))
+                    } else {
+                        (AuditAction::MemberKick, None)
+                    }
+                }
+                ModerationAction::Timeout { until } => {
+                    server.member_info.entry(target_id.clone()).or_default().timed_out_until = Some(until);
+                    let span = until.duration_since(SystemTime::now()).unwrap_or_default();
+                    (AuditAction::MemberTimeout, audit_log::change("timeout", None, Some(audit_log::format_duration(span))))
+                }
+                ModerationAction::RemoveTimeout => {
+                    server.member_info.entry(target_id.clone()).or_default().timed_out_until = None;
+                    (AuditAction::MemberTimeoutRemove, None)
+                }
+            };
+            let saved = state.servers[ix].clone();
+            let entry = state.record(&server_id, &actor_id, audit_action, Some(&target_id));
+            entry.changes = changes.into_iter().collect();
+            entry.reason = reason;
+            Ok(saved)
+        })
+    }
+
+    fn fetch_audit_log(&self, server_id: &str, query: AuditLogQuery) -> BackendFuture<Vec<AuditLogEntry>> {
+        let server_id = server_id.to_string();
+        self.with_state(move |state| {
+            let user_id = state.current_user_id()?;
+            let ix = state.server_ix(&server_id)?;
+            let granted = permissions::server_permissions(&state.servers[ix], &user_id);
+            if !granted.contains(Permissions::VIEW_AUDIT_LOG) {
+                bail!("missing permissions: {:?}", Permissions::VIEW_AUDIT_LOG);
+            }
+            Ok(query.page(state.audit_log.get(&server_id).map_or(&[], Vec::as_slice)))
+        })
+    }
+
+    fn fetch_history(
+        &self,
+        target: &MessageTarget,
+        before: Option<String>,
+        limit: usize,
+    ) -> BackendFuture<Vec<Message>> {
+        let target = target.clone();
+        self.with_state(move |state| {
+            let history = state.history_mut(&target);
+            let end = match before {
+                Some(before) => history
+                    .iter()
+                    .position(|m| m.id == before)
+                    .unwrap_or(0),
+                None => history.len(),
+            };
+            let start = end.saturating_sub(limit);
+            Ok(history[start..end].to_vec())
+        })
+    }
+
+    fn send_message(&self, target: &MessageTarget, draft: MessageDraft) -> BackendFuture<Message> {
+        let target = target.clone();
+        self.with_state(move |state| {
+            let author = state
+                .session
+                .as_ref()
+                .map(|s| s.user.clone())
+                .ok_or_else(|| anyhow!("not logged in"))?;
+            state.require(&target, Permissions::SEND_MESSAGES)?;
+            if let Some(id) = draft.nonce.as_ref().and_then(|n| state.sent_nonces.get(n)) {
+                let id = id.clone();
+                if let Some(sent) = state.history_mut(&target).iter().find(|m| m.id == id) {
+                    return Ok(sent.clone());
+                }
+            }
+            let id = format!("mock_{}", state.next_id);
+            state.next_id += 1;
+            if let Some(nonce) = draft.nonce {
+                state.sent_nonces.insert(nonce, id.clone());
+            }
+
+            let message = Message {
+                id,
+                author,
+                content: draft.content,
+                timestamp: "Just now".to_string(),
+                edited: false,
+                edited_timestamp: None,
+                revisions: Vec::new(),
+                attachments: draft.attachments,
+                embeds: Vec::new(),
+                reactions: HashMap::new(),
+                reply_to: draft.reply_to.map(Box::new),
+                mentioned_users: draft.mentioned_users,
+                pinned: false,
+                thread_id: None,
+                thread_count: 0,
+                created_at: std::time::SystemTime::now(),
+            };
+            state.history_mut(&target).push(message.clone());
+            Ok(message)
+        })
+    }
+
+    fn edit_message(
+        &self,
+        target: &MessageTarget,
+        message_id: &str,
+        content: String,
+    ) -> BackendFuture<Message> {
+        let target = target.clone();
+        let message_id = message_id.to_string();
+        self.with_state(move |state| {
+            let msg = state.message_mut(&target, &message_id)?;
+            msg.edit(content, "Just now".to_string());
+            Ok(msg.clone())
+        })
+    }
+
+    fn delete_message(&self, target: &MessageTarget, message_id: &str) -> BackendFuture<()> {
+        let target = target.clone();
+        let message_id = message_id.to_string();
+        self.with_state(move |state| {
+            let user_id = state.current_user_id()?;
+            let author_id = state.message_mut(&target, &message_id)?.author.id.clone();
+            if author_id != user_id {
+                state.require(&target, Permissions::MANAGE_MESSAGES)?;
+            }
+            state.history_mut(&target).retain(|m| m.id != message_id);
+            if author_id != user_id {
+                state.record_message_action(&target, &user_id, AuditAction::MessageDelete, &author_id);
+            }
+            Ok(())
+        })
+    }
+
+    fn set_pinned(&self, target: &MessageTarget, message_id: &str, pinned: bool) -> BackendFuture<()> {
+        let target = target.clone();
+        let message_id = message_id.to_string();
+        self.with_state(move |state| {
+            state.require(&target, Permissions::MANAGE_MESSAGES)?;
+            let message = state.message_mut(&target, &message_id)?;
+            if message.pinned != pinned {
+                message.pinned = pinned;
+                let author_id = message.author.id.clone();
+                let action = if pinned { AuditAction::MessagePin } else { AuditAction::MessageUnpin };
+                let user_id = state.current_user_id()?;
+                state.record_message_action(&target, &user_id, action, &author_id);
+            }
+            Ok(())
+        })
+    }
+
+    fn set_reaction(
+        &self,
+        target: &MessageTarget,
+        message_id: &str,
+        emoji: &str,
+        add: bool,
+    ) -> BackendFuture<()> {
+        let target = target.clone();
+        let message_id = message_id.to_string();
+        let emoji = emoji.to_string();
+        self.with_state(move |state| {
+            let user_id = state.current_user_id()?;
+            let msg = state.message_mut(&target, &message_id)?;
+            if add && !msg.user_reacted(&emoji, &user_id) {
+                msg.add_reaction(emoji, user_id);
+            } else if !add {
+                msg.remove_reaction(&emoji, &user_id);
+            }
+            Ok(())
+        })
+    }
+
+    fn fetch_read_states(&self) -> BackendFuture<Vec<ReadState>> {
+        self.with_state(|state| Ok(state.read_states.values().cloned().collect()))
+    }
+
+    fn ack_message(&self, target: &MessageTarget, message_id: &str) -> BackendFuture<()> {
+        let (conversation_id, message_id) = (target.id().to_string(), message_id.to_string());
+        self.with_state(move |state| {
+            state.read_states.insert(
+                conversation_id.clone(),
+                ReadState {
+                    conversation_id,
+                    last_read: Some(message_id),
+                    unread: 0,
+                    mentions: 0,
+                },
+            );
+            Ok(())
+        })
+    }
+
+    fn start_typing(&self, _target: &MessageTarget) -> BackendFuture<()> {
+        self.with_state(|_| Ok(()))
+    }
+
+    fn set_presence(&self, status: UserStatus) -> BackendFuture<()> {
+        self.with_state(move |state| {
+            if let Some(session) = state.session.as_mut() {
+                session.user.status = status;
+            }
+            Ok(())
+        })
+    }
+
+    fn upload_limits(&self) -> BackendFuture<UploadLimits> {
+        self.with_state(|_| Ok(UploadLimits::default()))
+    }
+
+    /// The file is copied into a temporary directory, on a thread of its
+    /// own, and the attachment points at the copy.
+    fn upload_attachment(
+        &self,
+        _target: &MessageTarget,
+        file: AttachmentUpload,
+        progress: UploadProgress,
+    ) -> BackendFuture<Attachment> {
+        let dest = self.with_state(|state| {
+            state.current_user_id()?;
+            let id = format!("mock_attachment_{}", state.next_id);
+            state.next_id += 1;
+            // Kept like a server keeps it, so the original can go.
+            let uploads = match &mut state.uploads {
+                Some(uploads) => uploads,
+                uploads => uploads.insert(tempfile::tempdir()?),
+            };
+            Ok((id.clone(), uploads.path().join(id)))
+        });
+
+        async move {
+            let (id, dest) = dest.await?;
+            let (done, copied) = futures::channel::oneshot::channel();
+            let source = file.path.clone();
+            let copy_to = dest.clone();
+            std::thread::spawn(move || {
+                done.send(copy_with_progress(&source, &copy_to, &progress)).ok();
+            });
+            let size = copied.await.context("upload was interrupted")??;
+            Ok(Attachment {
+                id,
+                filename: file.filename,
+                mime_type: file.mime_type,
+                size,
+                url: dest.to_string_lossy().into_owned(),
+                source: AttachmentSource::Local,
+            })
+        }
+        .boxed()
+    }
+
+    fn download_attachment(&self, attachment: &Attachment, dest: PathBuf) -> BackendFuture<()> {
+        let source = attachment.local_path().map(Path::to_path_buf);
+        let url = attachment.url.clone();
+        self.with_state(move |_| {
+            let source = source.ok_or_else(|| anyhow!("the mock backend can't download {url}"))?;
+            std::fs::copy(&source, &dest).with_context(|| format!("copying {}", source.display()))?;
+            Ok(())
+        })
+    }
+}
+
+/// Copy `from` to `to` a chunk at a time, reporting the bytes copied so far
+/// after each one, like `HttpBackend` does while sending.
+fn copy_with_progress(from: &Path, to: &Path, progress: &UploadProgress) -> Result<u64> {
+    let mut reader = std::fs::File::open(from).with_context(|| format!("reading {}", from.display()))?;
+    let mut writer = std::fs::File::create(to).with_context(|| format!("writing {}", to.display()))?;
+    let mut buf = vec![0; UPLOAD_CHUNK_SIZE];
+    let mut copied = 0;
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            return Ok(copied);
+        }
+        writer.write_all(&buf[..read])?;
+        copied += read as u64;
+        progress.unbounded_send(copied).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::mock_data::make_user;
+    use crate::channels::ChannelUpdate;
+    use crate::role_draft::RoleDraft;
+    use crate::models::ChannelKind;
+
+    fn logged_in_backend() -> MockBackend {
+        let backend = MockBackend::new();
+        backend.set_session(Some(BackendSession {
+            user: make_user("me", "Me", "0001", UserStatus::Online),
+            token: "test".to_string(),
+        }));
+        backend
+    }
+
+    #[test]
+    fn test_lists_fixture_servers_and_dms() {
+        let backend = MockBackend::new();
+        let servers = block_on(backend.list_servers()).unwrap();
+        assert_eq!(servers.len(), 3);
+        assert_eq!(servers[0].name, "Tripwire HQ");
+
+        let dms = block_on(backend.list_dm_channels()).unwrap();
+        assert_eq!(dms[0].id, "dm-bob");
+    }
+
+    #[test]
+    fn test_login_and_refresh_rotate_tokens() {
+        let backend = MockBackend::new();
+        assert_eq!(
+            block_on(backend.login("alice@example.com", "short")),
+            Err(AuthError::InvalidCredentials)
+        );
+
+        let session = block_on(backend.login("alice@example.com", "hunter22")).unwrap();
+        assert_eq!(session.user.id, "user_alice");
+        let refresh_token = session.refresh_token.unwrap();
+
+        let renewed = block_on(backend.refresh_session(&refresh_token)).unwrap();
+        assert_eq!(renewed.user.id, "user_alice");
+        assert_ne!(renewed.refresh_token.as_deref(), Some(refresh_token.as_str()));
+        assert_eq!(
+            block_on(backend.refresh_session(&refresh_token)),
+            Err(AuthError::SessionExpired)
+        );
+    }
+
+    #[test]
+    fn test_mfa_enrollment_and_login() {
+        let backend = MockBackend::new();
+        let session = block_on(backend.login("alice", "hunter22")).unwrap();
+        assert!(!session.mfa_enabled);
+        backend.set_session(Some(BackendSession {
+            user: session.user.clone(),
+            token: session.token.clone(),
+        }));
+
+        let enrollment = block_on(backend.begin_mfa_enrollment()).unwrap();
+        assert!(enrollment.uri.starts_with("otpauth://totp/Tripwire:alice?"));
+        let secret = totp::decode_secret(&enrollment.secret).unwrap();
+        assert_eq!(block_on(backend.enable_mfa("000000")), Err(AuthError::InvalidCode));
+        let backup_codes = block_on(backend.enable_mfa(&totp::code_at(&secret, unix_now()))).unwrap();
+        assert_eq!(backup_codes.len(), 10);
+
+        let Err(AuthError::TwoFactorRequired { ticket }) = block_on(backend.login("alice", "hunter22"))
+        else {
+            panic!("expected a second-factor challenge");
+        };
+        assert_eq!(
+            block_on(backend.verify_mfa(&ticket, MfaCode::Totp("000000".into()))),
+            Err(AuthError::InvalidCode)
+        );
+        let backup = MfaCode::Backup(backup_codes[0].to_uppercase());
+        let session = block_on(backend.verify_mfa(&ticket, backup.clone())).unwrap();
+        assert!(session.mfa_enabled);
+        assert_eq!(
+            block_on(backend.verify_mfa(&ticket, MfaCode::Totp("000000".into()))),
+            Err(AuthError::MfaTicketExpired)
+        );
+
+        // Backup codes are single-use.
+        let Err(AuthError::TwoFactorRequired { ticket }) = block_on(backend.login("alice", "hunter22"))
+        else {
+            panic!("expected a second-factor challenge");
+        };
+        assert_eq!(block_on(backend.verify_mfa(&ticket, backup)), Err(AuthError::InvalidCode));
+
+        let code = MfaCode::Totp(totp::code_at(&secret, unix_now()));
+        block_on(backend.disable_mfa(code)).unwrap();
+        assert!(block_on(backend.login("alice", "hunter22")).is_ok());
+    }
+
+    #[test]
+    fn test_fetch_history_pages_backwards() {
+        let backend = MockBackend::new();
+        let general = MessageTarget::Channel("103".into());
+
+        let newest = block_on(backend.fetch_history(&general, None, 3)).unwrap();
+        let ids: Vec<_> = newest.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m5", "m6", "m7"]);
+
+        let older = block_on(backend.fetch_history(&general, Some("m5".into()), 10)).unwrap();
+        let ids: Vec<_> = older.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, ["m1", "m2", "m3", "m4"]);
+    }
+
+    #[test]
+    fn test_send_requires_session() {
+        let backend = MockBackend::new();
+        let target = MessageTarget::Channel("103".into());
+        assert!(block_on(backend.send_message(&target, MessageDraft::default())).is_err());
+    }
+
+    #[test]
+    fn test_send_edit_delete_roundtrip() {
+        let backend = logged_in_backend();
+        let target = MessageTarget::DirectMessage("dm-bob".into());
+
+        let draft = MessageDraft {
+            content: "hello".into(),
+            nonce: Some("n1".into()),
+            ..Default::default()
+        };
+        let sent = block_on(backend.send_message(&target, draft.clone())).unwrap();
+        assert_eq!(sent.id, "mock_1");
+        assert_eq!(sent.author.id, "me");
+        // A retry with the same nonce doesn't post it twice.
+        assert_eq!(block_on(backend.send_message(&target, draft)).unwrap(), sent);
+
+        let edited = block_on(backend.edit_message(&target, &sent.id, "hi".into())).unwrap();
+        assert!(edited.edited);
+        assert_eq!(edited.content, "hi");
+        let edited = block_on(backend.edit_message(&target, &sent.id, "hi there".into())).unwrap();
+        let earlier: Vec<_> = edited.revisions.iter().map(|r| r.content.as_str()).collect();
+        assert_eq!(earlier, ["hello", "hi"]);
+        assert_eq!(edited.revisions[0].timestamp, sent.timestamp);
+        // Saving the same text again isn't an edit.
+        let unchanged = block_on(backend.edit_message(&target, &sent.id, "hi there".into())).unwrap();
+        assert_eq!(unchanged.revisions.len(), 2);
+
+        block_on(backend.delete_message(&target, &sent.id)).unwrap();
+        let history = block_on(backend.fetch_history(&target, None, 50)).unwrap();
+        assert!(history.iter().all(|m| m.id != sent.id));
+    }
+
+    #[test]
+    fn test_moderation_needs_permissions() {
+        let backend = logged_in_backend();
+        let general = MessageTarget::Channel("103".into());
+        let draft = MessageDraft {
+            content: "hello".into(),
+            ..Default::default()
+        };
+        assert!(block_on(backend.send_message(&general, draft.clone())).is_ok());
+        assert!(block_on(backend.send_message(&MessageTarget::Channel("101".into()), draft)).is_err());
+        assert!(block_on(backend.set_pinned(&general, "m1", true)).is_err());
+        assert!(block_on(backend.delete_message(&general, "m1")).is_err());
+
+        // Signing in makes the user an administrator of the first server.
+        let session = block_on(backend.login("alice", "hunter22")).unwrap();
+        backend.set_session(Some(BackendSession {
+            user: session.user,
+            token: session.token,
+        }));
+        block_on(backend.set_pinned(&general, "m1", true)).unwrap();
+        let history = block_on(backend.fetch_history(&general, None, 50)).unwrap();
+        assert!(history.iter().find(|m| m.id == "m1").unwrap().pinned);
+        block_on(backend.delete_message(&general, "m1")).unwrap();
+    }
+
+    #[test]
+    fn test_update_roles_is_checked_and_keeps_other_changes() {
+        let backend = logged_in_backend();
+        let server = block_on(backend.list_servers()).unwrap().remove(0);
+        let mut draft = RoleDraft::new(&server, "me");
+        assert!(draft.create_role().is_none());
+        let forged = RoleChanges {
+            deleted: vec!["r3".into()],
+            ..Default::default()
+        };
+        assert!(block_on(backend.update_roles(&server.id, forged)).is_err());
+
+        let session = block_on(backend.login("alice", "hunter22")).unwrap();
+        backend.set_session(Some(BackendSession {
+            user: session.user,
+            token: session.token,
+        }));
+        // Alice holds a role there now.
+        let server = block_on(backend.list_servers()).unwrap().remove(0);
+        draft = RoleDraft::new(&server, "u1");
+        let new = draft.create_role().unwrap();
+        draft.set_holder(&new, "u2", true);
+        // Alice can't touch her own top role, whatever the client sends.
+        let mut changes = draft.changes();
+        changes.deleted.push("r1".into());
+        assert!(block_on(backend.update_roles(&server.id, changes)).is_err());
+
+        // Roles given out elsewhere since the editor opened are kept.
+        let update = MemberUpdate {
+            nickname: None,
+            roles: Some(vec!["r3".into()]),
+        };
+        block_on(backend.update_member(&server.id, "u4", update, None)).unwrap();
+        let saved = block_on(backend.update_roles(&server.id, draft.changes())).unwrap();
+        let created = &saved.roles.last().unwrap().id;
+        assert_ne!(created, &new);
+        assert_eq!(saved.member_roles["u2"], ["r2".to_string(), created.clone()]);
+        assert_eq!(saved.member_roles["u4"], ["r3"]);
+        assert_eq!(block_on(backend.list_servers()).unwrap()[0].roles, saved.roles);
+
+        let log = block_on(backend.fetch_audit_log(&server.id, AuditLogQuery::default())).unwrap();
+        assert_eq!(
+            log.iter().take(2).map(|entry| entry.action).collect::<Vec<_>>(),
+            [AuditAction::MemberRoleUpdate, AuditAction::RoleCreate]
+        );
+    }
+
+    #[test]
+    fn test_moderation_is_checked_and_audited() {
+        let backend = logged_in_backend();
+        let server_id = block_on(backend.list_servers()).unwrap()[0].id.clone();
+        assert!(block_on(backend.moderate_member(&server_id, "u3", ModerationAction::Kick, None)).is_err());
+
+        let session = block_on(backend.login("alice", "hunter22")).unwrap();
+        backend.set_session(Some(BackendSession {
+            user: session.user,
+            token: session.token,
+        }));
+        // u1 shares Alice's top role.
+        assert!(block_on(backend.moderate_member(&server_id, "u1", ModerationAction::Kick, None)).is_err());
+
+        let until = SystemTime::now() + std::time::Duration::from_secs(60);
+        let server = block_on(backend.moderate_member(&server_id, "u2", ModerationAction::Timeout { until }, None)).unwrap();
+        assert_eq!(server.member_info["u2"].timed_out_until, Some(until));
+        let server = block_on(backend.moderate_member(&server_id, "u3", ModerationAction::Kick, Some("spam".into()))).unwrap();
+        assert!(!server.members.iter().any(|m| m.id == "u3"));
+        let update = MemberUpdate {
+            nickname: Some(Some("Bob".into())),
+            roles: Some(Vec::new()),
+        };
+        let server = block_on(backend.update_member(&server_id, "u2", update, None)).unwrap();
+        assert_eq!(server.member_info["u2"].nickname.as_deref(), Some("Bob"));
+        assert!(!server.member_roles.contains_key("u2"));
+
+        let log = block_on(backend.fetch_audit_log(&server_id, AuditLogQuery::default())).unwrap();
+        // Newest first, above the sample entries.
+        let summary: Vec<_> = log
+            .iter()
+            .take(4)
+            .map(|entry| (entry.action, entry.target_id.as_deref().unwrap(), entry.reason.as_deref()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (AuditAction::MemberRoleUpdate, "u2", None),
+                (AuditAction::MemberUpdate, "u2", None),
+                (AuditAction::MemberKick, "u3", Some("spam")),
+                (AuditAction::MemberTimeout, "u2", None),
+            ]
+        );
+        assert_eq!(
+            (log[1].changes[0].old.as_deref(), log[1].changes[0].new.as_deref()),
+            (Some("Bobby"), Some("Bob"))
+        );
+    }
+
+    #[test]
+    fn test_edit_channels_is_checked_and_audited() {
+        let backend = logged_in_backend();
+        let server = block_on(backend.list_servers()).unwrap().remove(0);
+        let delete = ChannelEdit::DeleteChannel { channel_id: "105".into() };
+        assert!(block_on(backend.edit_channels(&server.id, delete.clone())).is_err());
+
+        let session = block_on(backend.login("alice", "hunter22")).unwrap();
+        backend.set_session(Some(BackendSession {
+            user: session.user,
+            token: session.token,
+        }));
+        let rename = ChannelEdit::UpdateChannel {
+            channel_id: "102".into(),
+            update: ChannelUpdate {
+                name: Some("guidelines".into()),
+                slowmode_secs: Some(30),
+                ..ChannelUpdate::default()
+            },
+        };
+        let create = ChannelEdit::CreateChannel {
+            category: server.categories[1].name.clone(),
+            name: "Clips".into(),
+            kind: ChannelKind::Media,
+        };
+        let duplicate = ChannelEdit::CreateCategory {
+            name: server.categories[0].name.to_uppercase(),
+        };
+        assert!(block_on(backend.edit_channels(&server.id, duplicate)).is_err());
+        for edit in [rename, delete.clone()] {
+            block_on(backend.edit_channels(&server.id, edit)).unwrap();
+        }
+        // Edits made elsewhere meanwhile aren't undone.
+        let saved = block_on(backend.edit_channels(&server.id, create)).unwrap();
+        let created = &saved.categories[1].channels.last().unwrap().id;
+        assert_ne!(created, channels::NEW_CHANNEL_ID);
+        assert!(channels::find_channel(&saved.categories, "105").is_none());
+        assert!(block_on(backend.edit_channels(&server.id, delete)).is_err());
+
+        let log = block_on(backend.fetch_audit_log(&server.id, AuditLogQuery::default())).unwrap();
+        let summary: Vec<_> = log
+            .iter()
+            .take(4)
+            .map(|entry| (entry.action, entry.target_id.as_deref().unwrap()))
+            .collect();
+        // Media sharing moved up into the deleted channel's place.
+        assert_eq!(
+            summary,
+            [
+                (AuditAction::ChannelCreate, created.as_str()),
+                (AuditAction::ChannelDelete, "105"),
+                (AuditAction::ChannelUpdate, "108"),
+                (AuditAction::ChannelUpdate, "102"),
+            ]
+        );
+        let keys: Vec<_> = log[3].changes.iter().map(|change| change.key.as_str()).collect();
+        assert_eq!(keys, ["name", "slowmode"]);
+    }
+
+    #[test]
+    fn test_set_reaction_is_idempotent() {
+        let backend = logged_in_backend();
+        let target = MessageTarget::Channel("103".into());
+
+        block_on(backend.set_reaction(&target, "m1", "👍", true)).unwrap();
+        block_on(backend.set_reaction(&target, "m1", "👍", true)).unwrap();
+        let history = block_on(backend.fetch_history(&target, None, 50)).unwrap();
+        let m1 = history.iter().find(|m| m.id == "m1").unwrap();
+        assert_eq!(m1.reaction_count("👍"), 1);
+
+        block_on(backend.set_reaction(&target, "m1", "👍", false)).unwrap();
+        let history = block_on(backend.fetch_history(&target, None, 50)).unwrap();
+        let m1 = history.iter().find(|m| m.id == "m1").unwrap();
+        assert_eq!(m1.reaction_count("👍"), 0);
+    }
+
+    #[test]
+    fn test_ack_clears_read_state() {
+        let backend = logged_in_backend();
+        let unread = |backend: &MockBackend| {
+            block_on(backend.fetch_read_states())
+                .unwrap()
+                .into_iter()
+                .find(|state| state.conversation_id == "103")
+                .map(|state| (state.last_read, state.unread))
+        };
+        assert_eq!(unread(&backend), Some((None, 5)));
+
+        block_on(backend.ack_message(&MessageTarget::Channel("103".into()), "m9")).unwrap();
+        assert_eq!(unread(&backend), Some((Some("m9".into()), 0)));
+    }
+
+    #[test]
+    fn test_upload_and_download_attachment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "hello".repeat(UPLOAD_CHUNK_SIZE / 2)).unwrap();
+        let upload = AttachmentUpload::from_path(&path).unwrap();
+        let target = MessageTarget::Channel("103".into());
+        let (progress, mut sent) = futures::channel::mpsc::unbounded();
+
+        assert!(block_on(MockBackend::new().upload_attachment(&target, upload.clone(), progress.clone())).is_err());
+        let backend = logged_in_backend();
+        let attachment = block_on(backend.upload_attachment(&target, upload, progress)).unwrap();
+        let size = 5 * UPLOAD_CHUNK_SIZE as u64 / 2;
+        assert_eq!((attachment.size, attachment.mime_type.as_str()), (size, "text/plain"));
+        // Reported as it goes, not only at the end.
+        let reported: Vec<u64> = std::iter::from_fn(|| sent.try_recv().ok()).collect();
+        assert!(reported.len() > 1 && reported.is_sorted(), "{reported:?}");
+        assert_eq!(reported.last(), Some(&size));
+        // The backend has its own copy.
+        std::fs::remove_file(&path).unwrap();
+
+        let dest = dir.path().join("saved.txt");
+        block_on(backend.download_attachment(&attachment, dest.clone())).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap().len() as u64, size);
+
+        // A path from a server is never taken as a local file.
+        let json = serde_json::to_string(&attachment).unwrap();
+        let sent_by_server: Attachment = serde_json::from_str(&json).unwrap();
+        assert_eq!(sent_by_server.local_path(), None);
+        assert!(block_on(backend.download_attachment(&sent_by_server, dest)).is_err());
+    }
+}
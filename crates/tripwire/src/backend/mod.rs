@@ -0,0 +1,253 @@
+//! Chat backends — the source of servers, channels and messages.
+//!
+//! `TripwireApp` never talks to a server directly; it goes through an
+//! `Arc<dyn ChatBackend>`. Two implementations ship:
+//!
+//! - [`MockBackend`] — the in-memory sample data from `mock_data`, used for
+//!   local development and as a deterministic fixture in tests.
+//! - [`HttpBackend`] — talks to a Tripwire API server over HTTP.
+//!
+//! Logging in also goes through the backend: [`ChatBackend::login`] trades
+//! credentials for an [`AuthSession`], and [`ChatBackend::refresh_session`]
+//! renews it before the token expires.
+//!
+//! Backends that support real-time updates also expose a [`gateway`] URL;
+//! the UI keeps a `GatewayConnection` open to it while logged in.
+
+mod auth;
+pub mod gateway;
+mod http;
+mod mock;
+
+pub use auth::{AuthError, AuthFuture, AuthSession, MfaCode, MfaEnrollment};
+pub use gateway::{GatewayConfig, GatewayConnection, GatewayEvent};
+pub use http::HttpBackend;
+pub use mock::MockBackend;
+
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use futures::channel::mpsc::UnboundedSender;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{
+    Attachment, DirectMessageChannel, Message, MessageReply, Server, User, UserStatus,
+};
+use crate::attachments::{AttachmentUpload, UploadLimits};
+use crate::audit_log::{AuditLogEntry, AuditLogQuery};
+use crate::channels::ChannelEdit;
+use crate::members::ModerationAction;
+use crate::read_state::ReadState;
+use crate::role_draft::RoleChanges;
+
+/// Future returned by every backend call. Backends are shared across tasks,
+/// so results must not borrow from the backend.
+pub type BackendFuture<T> = BoxFuture<'static, Result<T>>;
+
+/// A request the server answered with an error status. Backend calls fail
+/// with it, inside the `anyhow::Error`, so callers can tell a refusal from
+/// a request that never got an answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl StatusError {
+    /// Worth retrying later: the server was busy or broken rather than
+    /// refusing the request.
+    pub fn is_transient(&self) -> bool {
+        self.status == 429 || self.status >= 500
+    }
+}
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+/// Whether a failed backend call is worth making again as it is: it never
+/// got an answer, or the answer was a transient [`StatusError`].
+pub fn is_transient(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<StatusError>().is_none_or(StatusError::is_transient)
+}
+
+/// Receives the number of bytes of an upload sent so far.
+pub type UploadProgress = UnboundedSender<u64>;
+
+/// Where a message lives: a server channel or a direct message conversation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MessageTarget {
+    Channel(String),
+    DirectMessage(String),
+}
+
+impl MessageTarget {
+    /// The channel or DM id, regardless of kind.
+    pub fn id(&self) -> &str {
+        match self {
+            MessageTarget::Channel(id) | MessageTarget::DirectMessage(id) => id,
+        }
+    }
+}
+
+/// A message the local user is about to send.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageDraft {
+    pub content: String,
+    /// Files already uploaded with `upload_attachment`.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    pub reply_to: Option<MessageReply>,
+    pub mentioned_users: Vec<String>,
+    /// Client-chosen id, the same on every retry, so the server can drop
+    /// drafts it has already accepted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+/// Changes to a member; what's `None` stays as it is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemberUpdate {
+    /// `Some(None)` clears the nickname.
+    pub nickname: Option<Option<String>>,
+    /// Every role they should hold.
+    pub roles: Option<Vec<String>>,
+}
+
+/// The authenticated identity a backend acts on behalf of.
+#[derive(Clone)]
+pub struct BackendSession {
+    pub user: User,
+    pub token: String,
+}
+
+/// Everything the UI needs from a chat server.
+pub trait ChatBackend: Send + Sync {
+    /// Exchange credentials for a session. Does not change the backend's
+    /// own session; the caller passes the result to `set_session`.
+    fn login(&self, login: &str, password: &str) -> AuthFuture<AuthSession>;
+
+    /// Trade a refresh token for a new session.
+    fn refresh_session(&self, refresh_token: &str) -> AuthFuture<AuthSession>;
+
+    /// Finish a login that failed with `AuthError::TwoFactorRequired`.
+    fn verify_mfa(&self, ticket: &str, code: MfaCode) -> AuthFuture<AuthSession>;
+
+    /// Start enrolling the current user in TOTP. Nothing changes until
+    /// `enable_mfa` confirms a code from the new secret.
+    fn begin_mfa_enrollment(&self) -> AuthFuture<MfaEnrollment>;
+
+    /// Turn two-factor authentication on, returning the backup codes.
+    fn enable_mfa(&self, code: &str) -> AuthFuture<Vec<String>>;
+
+    fn disable_mfa(&self, code: MfaCode) -> AuthFuture<()>;
+
+    /// Set (or clear, on logout) the identity used for subsequent calls.
+    fn set_session(&self, session: Option<BackendSession>);
+
+    fn list_servers(&self) -> BackendFuture<Vec<Server>>;
+
+    fn list_dm_channels(&self) -> BackendFuture<Vec<DirectMessageChannel>>;
+
+    /// Make `changes` to `server_id`'s roles and who holds them, giving new
+    /// roles real IDs. Returns the server as saved.
+    fn update_roles(&self, server_id: &str, changes: RoleChanges) -> BackendFuture<Server>;
+
+    /// Make one change to `server_id`'s categories and channels. Returns
+    /// the server as saved.
+    fn edit_channels(&self, server_id: &str, edit: ChannelEdit) -> BackendFuture<Server>;
+
+    /// Change `user_id`'s nickname or roles in `server_id`, noting
+    /// `reason` in the audit log. Returns the server as saved.
+    fn update_member(
+        &self,
+        server_id: &str,
+        user_id: &str,
+        update: MemberUpdate,
+        reason: Option<String>,
+    ) -> BackendFuture<Server>;
+
+    /// Kick, ban or time out `user_id`, noting `reason` in the audit log.
+    /// Returns the server as saved.
+    fn moderate_member(
+        &self,
+        server_id: &str,
+        user_id: &str,
+        action: ModerationAction,
+        reason: Option<String>,
+    ) -> BackendFuture<Server>;
+
+    /// Fetch a page of `server_id`'s audit log, newest first.
+    fn fetch_audit_log(&self, server_id: &str, query: AuditLogQuery) -> BackendFuture<Vec<AuditLogEntry>>;
+
+    /// Fetch up to `limit` messages older than `before` (or the newest ones
+    /// when `before` is `None`), oldest first.
+    fn fetch_history(
+        &self,
+        target: &MessageTarget,
+        before: Option<String>,
+        limit: usize,
+    ) -> BackendFuture<Vec<Message>>;
+
+    /// Send a message and return it as stored by the server.
+    fn send_message(&self, target: &MessageTarget, draft: MessageDraft) -> BackendFuture<Message>;
+
+    fn edit_message(
+        &self,
+        target: &MessageTarget,
+        message_id: &str,
+        content: String,
+    ) -> BackendFuture<Message>;
+
+    fn delete_message(&self, target: &MessageTarget, message_id: &str) -> BackendFuture<()>;
+
+    /// Pin (`pinned == true`) or unpin a message.
+    fn set_pinned(&self, target: &MessageTarget, message_id: &str, pinned: bool) -> BackendFuture<()>;
+
+    /// Add (`add == true`) or remove the current user's `emoji` reaction.
+    fn set_reaction(
+        &self,
+        target: &MessageTarget,
+        message_id: &str,
+        emoji: &str,
+        add: bool,
+    ) -> BackendFuture<()>;
+
+    /// Read state of every conversation with something unread, and of those
+    /// read on other devices.
+    fn fetch_read_states(&self) -> BackendFuture<Vec<ReadState>>;
+
+    /// Mark `target` read up to `message_id`.
+    fn ack_message(&self, target: &MessageTarget, message_id: &str) -> BackendFuture<()>;
+
+    /// Tell other members the current user is typing in `target`.
+    fn start_typing(&self, target: &MessageTarget) -> BackendFuture<()>;
+
+    fn set_presence(&self, status: UserStatus) -> BackendFuture<()>;
+
+    /// How large, and how many, attachments may be.
+    fn upload_limits(&self) -> BackendFuture<UploadLimits>;
+
+    /// Upload `file` for a message in `target`, reporting bytes sent on
+    /// `progress`. Dropping the future cancels the upload.
+    fn upload_attachment(
+        &self,
+        target: &MessageTarget,
+        file: AttachmentUpload,
+        progress: UploadProgress,
+    ) -> BackendFuture<Attachment>;
+
+    /// Save `attachment` to `dest`.
+    fn download_attachment(&self, attachment: &Attachment, dest: PathBuf) -> BackendFuture<()>;
+
+    /// WebSocket URL of the real-time gateway, if this backend has one.
+    fn gateway_url(&self) -> Option<String> {
+        None
+    }
+}
@@ -0,0 +1,445 @@
+//! Channel layout — a server's categories and channels and the edits made
+//! to them: creating, moving and deleting them, and the permission
+//! overwrites of a channel. Edits are saved one at a time as
+//! `ChannelEdit`s, so they don't undo what others changed meanwhile.
+//!
+//! Categories have no IDs; they're known by name, which is unique within
+//! a server.
+
+use anyhow::{Context as _, Result, bail};
+
+use crate::models::{Channel, ChannelCategory, ChannelKind};
+use crate::permissions::{OverwriteTarget, PermissionOverwrite, Permissions};
+
+/// The slowmode settings offered, as labels and seconds between messages.
+pub const SLOWMODE_CHOICES: &[(&str, u32)] = &[
+    ("Off", 0),
+    ("5 seconds", 5),
+    ("10 seconds", 10),
+    ("30 seconds", 30),
+    ("1 minute", 60),
+    ("5 minutes", 300),
+    ("15 minutes", 900),
+    ("1 hour", 3_600),
+    ("6 hours", 21_600),
+];
+
+/// Longest name a channel or category may have.
+pub const MAX_NAME_LEN: usize = 100;
+
+/// ID a channel has until the backend gives it a real one.
+pub const NEW_CHANNEL_ID: &str = "new-channel";
+
+/// One change to a server's channels.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelEdit {
+    CreateCategory {
+        name: String,
+    },
+    /// Deletes the channels in it too.
+    DeleteCategory {
+        name: String,
+    },
+    MoveCategory {
+        name: String,
+        to: usize,
+    },
+    CreateChannel {
+        /// Name of the category it goes in, at the end.
+        category: String,
+        name: String,
+        kind: ChannelKind,
+    },
+    UpdateChannel {
+        channel_id: String,
+        update: ChannelUpdate,
+    },
+    DeleteChannel {
+        channel_id: String,
+    },
+    /// Move a channel to position `to` of the category called `category`.
+    MoveChannel {
+        channel_id: String,
+        category: String,
+        to: usize,
+    },
+}
+
+/// Changes to a channel's settings; what's `None` stays as it is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelUpdate {
+    pub name: Option<String>,
+    /// `Some(None)` clears the topic.
+    pub topic: Option<Option<String>>,
+    pub slowmode_secs: Option<u32>,
+    pub nsfw: Option<bool>,
+    /// Every overwrite the channel should have.
+    pub overwrites: Option<Vec<PermissionOverwrite>>,
+}
+
+impl ChannelUpdate {
+    /// What it takes to make `channel` like `edited`.
+    pub fn between(channel: &Channel, edited: &Channel) -> Self {
+        fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<T> {
+            (old != new).then(|| new.clone())
+        }
+        Self {
+            name: changed(&channel.name, &edited.name),
+            topic: changed(&channel.topic, &edited.topic),
+            slowmode_secs: changed(&channel.slowmode_secs, &edited.slowmode_secs),
+            nsfw: changed(&channel.nsfw, &edited.nsfw),
+            overwrites: changed(&channel.overwrites, &edited.overwrites),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl ChannelEdit {
+    /// Make the edit to `categories`, failing if what it names isn't there
+    /// or it would leave a name empty or a category name taken. A created
+    /// channel has the ID `NEW_CHANNEL_ID`.
+    pub fn apply(&self, categories: &mut Vec<ChannelCategory>) -> Result<()> {
+        let category_ix = |categories: &[ChannelCategory], name: &str| {
+            categories
+                .iter()
+                .position(|c| c.name == name)
+                .with_context(|| format!("category {name} not found"))
+        };
+        let channel_ix = |categories: &[ChannelCategory], channel_id: &str| {
+            find_channel(categories, channel_id).with_context(|| format!("channel {channel_id} not found"))
+        };
+        match self {
+            ChannelEdit::CreateCategory { name } => {
+                let name = name.trim();
+                if name.is_empty() {
+                    bail!("categories need a name");
+                }
+                if has_category(categories, name) {
+                    bail!("there's already a category called {name}");
+                }
+                categories.push(ChannelCategory {
+                    name: name.chars().take(MAX_NAME_LEN).collect(),
+                    channels: Vec::new(),
+                    collapsed: false,
+                    overwrites: Vec::new(),
+                });
+            }
+            ChannelEdit::DeleteCategory { name } => {
+                let ix = category_ix(categories, name)?;
+                categories.remove(ix);
+            }
+            ChannelEdit::MoveCategory { name, to } => {
+                let ix = category_ix(categories, name)?;
+                move_category(categories, ix, *to);
+            }
+            ChannelEdit::CreateChannel { category, name, kind } => {
+                let ix = category_ix(categories, category)?;
+                let channel = new_channel(name, kind.clone());
+                if channel.name.is_empty() {
+                    bail!("channels need a name");
+                }
+                categories[ix].channels.push(channel);
+            }
+            ChannelEdit::UpdateChannel { channel_id, update } => {
+                let (cat_ix, ix) = channel_ix(categories, channel_id)?;
+                let channel = &mut categories[cat_ix].channels[ix];
+                if let Some(name) = &update.name {
+                    let name = channel_name(name, &channel.kind);
+                    if name.is_empty() {
+                        bail!("channels need a name");
+                    }
+                    channel.name = name;
+                }
+                if let Some(topic) = &update.topic {
+                    channel.topic = topic.as_ref().map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+                }
+                if let Some(slowmode_secs) = update.slowmode_secs {
+                    channel.slowmode_secs = slowmode_secs;
+                }
+                if let Some(nsfw) = update.nsfw {
+                    channel.nsfw = nsfw;
+                }
+                if let Some(overwrites) = &update.overwrites {
+                    channel.overwrites = overwrites.clone();
+                }
+            }
+            ChannelEdit::DeleteChannel { channel_id } => {
+                let (cat_ix, ix) = channel_ix(categories, channel_id)?;
+                categories[cat_ix].channels.remove(ix);
+            }
+            ChannelEdit::MoveChannel { channel_id, category, to } => {
+                let ix = category_ix(categories, category)?;
+                channel_ix(categories, channel_id)?;
+                move_channel(categories, channel_id, ix, *to);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `name` as a channel of `kind` is named: text-like channels are lower
+/// case with dashes between words, voice and stage channels keep their
+/// spaces and capitals. Cut to `MAX_NAME_LEN`.
+pub fn channel_name(name: &str, kind: &ChannelKind) -> String {
+    let words = name.split_whitespace();
+    let name: String = if kind.is_voice_based() {
+        words.collect::<Vec<_>>().join(" ")
+    } else {
+        words.map(str::to_lowercase).collect::<Vec<_>>().join("-")
+    };
+    name.chars().take(MAX_NAME_LEN).collect()
+}
+
+/// A channel of `kind` as it's first made, with nothing overwritten.
+pub fn new_channel(name: &str, kind: ChannelKind) -> Channel {
+    Channel {
+        id: NEW_CHANNEL_ID.to_string(),
+        name: channel_name(name, &kind),
+        kind,
+        topic: None,
+        slowmode_secs: 0,
+        nsfw: false,
+        members_connected: 0,
+        voice_participants: Vec::new(),
+        overwrites: Vec::new(),
+    }
+}
+
+/// The permissions a channel of `kind` lists for overwriting: what can
+/// differ from channel to channel, leaving out voice permissions in text
+/// channels and the other way round.
+pub fn overwritable(kind: &ChannelKind) -> Permissions {
+    let general = Permissions::VIEW_CHANNEL | Permissions::MANAGE_CHANNELS | Permissions::MANAGE_ROLES;
+    if kind.is_voice_based() {
+        general
+            | Permissions::CONNECT
+            | Permissions::SPEAK
+            | Permissions::STREAM
+            | Permissions::MUTE_MEMBERS
+            | Permissions::DEAFEN_MEMBERS
+            | Permissions::MOVE_MEMBERS
+    } else {
+        general
+            | Permissions::SEND_MESSAGES
+            | Permissions::CREATE_THREADS
+            | Permissions::ATTACH_FILES
+            | Permissions::ADD_REACTIONS
+            | Permissions::MENTION_EVERYONE
+            | Permissions::MANAGE_MESSAGES
+    }
+}
+
+/// Where `channel_id` is: its category's index, then its own in there.
+pub fn find_channel(categories: &[ChannelCategory], channel_id: &str) -> Option<(usize, usize)> {
+    categories.iter().enumerate().find_map(|(cat_ix, category)| {
+        let ix = category.channels.iter().position(|c| c.id == channel_id)?;
+        Some((cat_ix, ix))
+    })
+}
+
+/// Whether a category is already called `name`, ignoring case.
+pub fn has_category(categories: &[ChannelCategory], name: &str) -> bool {
+    categories.iter().any(|c| c.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Move `channel_id` to position `to` of the category at `category`,
+/// shifting those between. Returns whether anything moved.
+pub fn move_channel(categories: &mut [ChannelCategory], channel_id: &str, category: usize, to: usize) -> bool {
+    let Some((from_cat, from)) = find_channel(categories, channel_id) else {
+        return false;
+    };
+    if category >= categories.len() {
+        return false;
+    }
+    let channel = categories[from_cat].channels.remove(from);
+    let to = to.min(categories[category].channels.len());
+    categories[category].channels.insert(to, channel);
+    (from_cat, from) != (category, to)
+}
+
+/// Move the category at `from` to position `to`, shifting those between.
+/// Returns whether anything moved.
+pub fn move_category(categories: &mut Vec<ChannelCategory>, from: usize, to: usize) -> bool {
+    if from >= categories.len() {
+        return false;
+    }
+    let to = to.min(categories.len() - 1);
+    let category = categories.remove(from);
+    categories.insert(to, category);
+    from != to
+}
+
+/// What `target`'s overwrite does to `flag`: `Some(true)` allows it,
+/// `Some(false)` denies it, `None` leaves it to the roles.
+pub fn overwrite_state(overwrites: &[PermissionOverwrite], target: &OverwriteTarget, flag: Permissions) -> Option<bool> {
+    let overwrite = overwrites.iter().find(|o| o.target == *target)?;
+    if overwrite.allow.contains(flag) {
+        Some(true)
+    } else if overwrite.deny.contains(flag) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Have `target`'s overwrite allow `flag`, deny it, or neither. Targets
+/// get an overwrite when they first need one, and keep it even once it
+/// does nothing, so they stay listed while being edited.
+pub fn set_overwrite(
+    overwrites: &mut Vec<PermissionOverwrite>,
+    target: &OverwriteTarget,
+    flag: Permissions,
+    state: Option<bool>,
+) {
+    let overwrite = match overwrites.iter().position(|o| o.target == *target) {
+        Some(ix) => &mut overwrites[ix],
+        None => {
+            overwrites.push(PermissionOverwrite {
+                target: target.clone(),
+                allow: Permissions::empty(),
+                deny: Permissions::empty(),
+            });
+            overwrites.last_mut().expect("just pushed")
+        }
+    };
+    overwrite.allow.set(flag, state == Some(true));
+    overwrite.deny.set(flag, state == Some(false));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(name: &str, channels: &[&str]) -> ChannelCategory {
+        ChannelCategory {
+            name: name.to_string(),
+            channels: channels
+                .iter()
+                .map(|id| Channel {
+                    id: id.to_string(),
+                    ..new_channel(id, ChannelKind::Text)
+                })
+                .collect(),
+            collapsed: false,
+            overwrites: Vec::new(),
+        }
+    }
+
+    fn layout(categories: &[ChannelCategory]) -> Vec<(String, Vec<String>)> {
+        categories
+            .iter()
+            .map(|c| (c.name.clone(), c.channels.iter().map(|c| c.id.clone()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_names_channels_by_kind() {
+        assert_eq!(channel_name("  Off  Topic ", &ChannelKind::Text), "off-topic");
+        assert_eq!(channel_name("Game Night", &ChannelKind::Voice), "Game Night");
+        assert_eq!(channel_name(&"x".repeat(150), &ChannelKind::Forum).len(), MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn test_moves_channels_and_categories() {
+        let mut categories = vec![category("A", &["1", "2", "3"]), category("B", &["4"])];
+        assert!(move_channel(&mut categories, "1", 0, 2));
+        assert!(move_channel(&mut categories, "3", 1, 0));
+        assert!(!move_channel(&mut categories, "2", 0, 0));
+        assert!(move_channel(&mut categories, "4", 0, 10));
+        assert_eq!(
+            layout(&categories),
+            [("A".into(), vec!["2".into(), "1".into(), "4".into()]), ("B".into(), vec!["3".into()])]
+        );
+
+        assert!(move_category(&mut categories, 1, 0));
+        assert!(!move_category(&mut categories, 0, 0));
+        assert_eq!(categories[0].name, "B");
+        assert!(has_category(&categories, " a "));
+    }
+
+    #[test]
+    fn test_applies_edits() {
+        let mut categories = vec![category("A", &["1", "2"]), category("B", &["3"])];
+        let edits = [
+            ChannelEdit::MoveChannel {
+                channel_id: "1".into(),
+                category: "B".into(),
+                to: 0,
+            },
+            ChannelEdit::CreateCategory { name: " C ".into() },
+            ChannelEdit::MoveCategory { name: "C".into(), to: 0 },
+            ChannelEdit::CreateChannel {
+                category: "C".into(),
+                name: "New Things".into(),
+                kind: ChannelKind::Text,
+            },
+            ChannelEdit::DeleteChannel { channel_id: "2".into() },
+            ChannelEdit::DeleteCategory { name: "A".into() },
+        ];
+        for edit in &edits {
+            edit.apply(&mut categories).unwrap();
+        }
+        assert_eq!(
+            layout(&categories),
+            [
+                ("C".into(), vec![NEW_CHANNEL_ID.into()]),
+                ("B".into(), vec!["1".into(), "3".into()])
+            ]
+        );
+        assert_eq!(categories[0].channels[0].name, "new-things");
+
+        let rename = |name: &str| ChannelEdit::UpdateChannel {
+            channel_id: "3".into(),
+            update: ChannelUpdate {
+                name: Some(name.into()),
+                topic: Some(Some(" About ".into())),
+                ..ChannelUpdate::default()
+            },
+        };
+        rename("Three").apply(&mut categories).unwrap();
+        let three = &categories[1].channels[1];
+        assert_eq!((three.name.as_str(), three.topic.as_deref()), ("three", Some("About")));
+        assert_eq!(ChannelUpdate::between(three, three), ChannelUpdate::default());
+
+        // Nothing changes when an edit fails.
+        let before = layout(&categories);
+        for edit in [
+            rename("  "),
+            ChannelEdit::CreateCategory { name: "b".into() },
+            ChannelEdit::DeleteChannel { channel_id: "2".into() },
+            ChannelEdit::MoveChannel {
+                channel_id: "3".into(),
+                category: "A".into(),
+                to: 0,
+            },
+        ] {
+            assert!(edit.apply(&mut categories).is_err(), "{edit:?}");
+        }
+        assert_eq!(layout(&categories), before);
+        assert_eq!(categories[1].channels[1].name, "three");
+    }
+
+    #[test]
+    fn test_sets_overwrites() {
+        let mut overwrites = Vec::new();
+        let role = OverwriteTarget::Role("mod".into());
+        set_overwrite(&mut overwrites, &role, Permissions::SEND_MESSAGES, Some(false));
+        set_overwrite(&mut overwrites, &role, Permissions::VIEW_CHANNEL, Some(true));
+        assert_eq!(overwrite_state(&overwrites, &role, Permissions::SEND_MESSAGES), Some(false));
+        assert_eq!(overwrite_state(&overwrites, &OverwriteTarget::Everyone, Permissions::SEND_MESSAGES), None);
+
+        set_overwrite(&mut overwrites, &role, Permissions::SEND_MESSAGES, Some(true));
+        set_overwrite(&mut overwrites, &role, Permissions::VIEW_CHANNEL, None);
+        assert_eq!(
+            overwrites,
+            [PermissionOverwrite {
+                target: role,
+                allow: Permissions::SEND_MESSAGES,
+                deny: Permissions::empty(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,175 @@
+//! Word-level diff of two texts, for showing what an edit changed.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Same,
+    Added,
+    Removed,
+}
+
+/// Above this many word pairs the texts are shown as wholly replaced,
+/// rather than compared in quadratic time.
+const MAX_COMPARISONS: usize = 1_000_000;
+
+/// How `old` became `new`, as runs of unchanged, added and removed text.
+/// Where text was replaced, what was removed comes first.
+pub fn word_diff<'a>(old: &'a str, new: &'a str) -> Vec<(Change, &'a str)> {
+    let (old_words, new_words) = (words(old), words(new));
+    let ops = if old_words.len().saturating_mul(new_words.len()) > MAX_COMPARISONS {
+        (0..old_words.len())
+            .map(Op::Removed)
+            .chain((0..new_words.len()).map(Op::Added))
+            .collect()
+    } else {
+        let old_text: Vec<&str> = old_words.iter().map(|word| &old[word.clone()]).collect();
+        let new_text: Vec<&str> = new_words.iter().map(|word| &new[word.clone()]).collect();
+        compare(&old_text, &new_text)
+    };
+
+    let mut runs: Vec<(Change, Range<usize>)> = Vec::new();
+    let mut push = |change: Change, word: &Range<usize>| match runs.last_mut() {
+        Some((last, run)) if *last == change && run.end == word.start => run.end = word.end,
+        _ => runs.push((change, word.clone())),
+    };
+    let mut ops = ops.into_iter().peekable();
+    while let Some(op) = ops.next() {
+        if let Op::Same(_, j) = op {
+            push(Change::Same, &new_words[j]);
+            continue;
+        }
+        // A stretch of changes: everything removed, then everything added.
+        let mut changed = vec![op];
+        while let Some(op) = ops.next_if(|op| !matches!(op, Op::Same(..))) {
+            changed.push(op);
+        }
+        for op in &changed {
+            if let Op::Removed(i) = op {
+                push(Change::Removed, &old_words[*i]);
+            }
+        }
+        for op in &changed {
+            if let Op::Added(j) = op {
+                push(Change::Added, &new_words[*j]);
+            }
+        }
+    }
+    runs.into_iter()
+        .map(|(change, run)| match change {
+            Change::Removed => (change, &old[run]),
+            Change::Same | Change::Added => (change, &new[run]),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    /// Word `.0` of the old text is word `.1` of the new one.
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// Turn `old` into `new`, keeping their longest common subsequence.
+/// Whitespace kept between two changes counts as changed too, so replaced
+/// phrases read as one.
+fn compare(old: &[&str], new: &[&str]) -> Vec<Op> {
+    // common[i][j]: length of the longest common subsequence of old[i..]
+    // and new[j..].
+    let mut common = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push(Op::Same(i, j));
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            ops.push(Op::Removed(i));
+            i += 1;
+        } else {
+            ops.push(Op::Added(j));
+            j += 1;
+        }
+    }
+
+    let changed = |op: Option<&Op>| matches!(op, Some(Op::Removed(_) | Op::Added(_)));
+    let mut ix = 1;
+    while ix + 1 < ops.len() {
+        if let Op::Same(i, j) = ops[ix]
+            && old[i].trim().is_empty()
+            && changed(ops.get(ix - 1))
+            && changed(ops.get(ix + 1))
+        {
+            ops[ix] = Op::Removed(i);
+            ops.insert(ix + 1, Op::Added(j));
+        }
+        ix += 1;
+    }
+    ops
+}
+
+/// Byte ranges of the words of `text` and of the whitespace between them.
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        let next = chars.peek().copied();
+        if next.is_none_or(|(_, next)| next.is_whitespace() != c.is_whitespace()) {
+            let end = next.map_or(text.len(), |(ix, _)| ix);
+            words.push(start..end);
+            start = end;
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Change::*;
+
+    #[test]
+    fn test_splits_words_and_whitespace() {
+        let text = "  héllo wörld\n";
+        let words: Vec<_> = words(text).into_iter().map(|word| &text[word]).collect();
+        assert_eq!(words, ["  ", "héllo", " ", "wörld", "\n"]);
+        assert!(super::words("").is_empty());
+    }
+
+    #[test]
+    fn test_diffs_by_word() {
+        assert_eq!(
+            word_diff("the quick fox jumps", "the slow brown fox jumps high"),
+            [
+                (Same, "the "),
+                (Removed, "quick "),
+                (Added, "slow brown "),
+                (Same, "fox jumps"),
+                (Added, " high"),
+            ]
+        );
+        assert_eq!(word_diff("same text", "same text"), [(Same, "same text")]);
+        assert_eq!(word_diff("", "new"), [(Added, "new")]);
+        assert_eq!(word_diff("old", ""), [(Removed, "old")]);
+    }
+
+    #[test]
+    fn test_replaces_whole_texts_past_the_limit() {
+        let old = "a ".repeat(1001);
+        let new = "b ".repeat(1001);
+        assert_eq!(word_diff(&old, &new), [(Removed, old.as_str()), (Added, new.as_str())]);
+    }
+}
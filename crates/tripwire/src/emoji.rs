@@ -0,0 +1,346 @@
+//! Emoji — the Unicode table with CLDR short names (generated from
+//! `data/emoji-test.txt` by `build.rs`), shortcodes for `:name:`
+//! completion, skin tones, and the `<:name:id>` tokens custom server emoji
+//! are sent as.
+
+use serde::{Deserialize, Serialize};
+
+/// Shortcode matches offered by `search`.
+const MAX_RESULTS: usize = 10;
+
+/// One emoji of the Unicode table.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Emoji {
+    pub emoji: &'static str,
+    /// CLDR short name, e.g. `waving hand`.
+    pub name: &'static str,
+    /// The short name as a shortcode, e.g. `waving_hand`.
+    pub shortcode: &'static str,
+    pub category: Category,
+    /// Variants for each non-default `SkinTone`; empty if there are none.
+    tones: &'static [&'static str],
+}
+
+include!(concat!(env!("OUT_DIR"), "/emoji_table.rs"));
+
+impl Emoji {
+    pub fn has_tones(&self) -> bool {
+        !self.tones.is_empty()
+    }
+
+    /// This emoji in `tone`, or as is if it has no skin tones.
+    pub fn with_tone(&self, tone: SkinTone) -> &'static str {
+        match tone {
+            SkinTone::Default => self.emoji,
+            tone => self.tones.get(tone as usize - 1).copied().unwrap_or(self.emoji),
+        }
+    }
+}
+
+/// The Unicode emoji groups, as the picker's tabs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    SmileysEmotion,
+    PeopleBody,
+    AnimalsNature,
+    FoodDrink,
+    TravelPlaces,
+    Activities,
+    Objects,
+    Symbols,
+    Flags,
+}
+
+impl Category {
+    pub const ALL: [Category; 9] = [
+        Category::SmileysEmotion,
+        Category::PeopleBody,
+        Category::AnimalsNature,
+        Category::FoodDrink,
+        Category::TravelPlaces,
+        Category::Activities,
+        Category::Objects,
+        Category::Symbols,
+        Category::Flags,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::SmileysEmotion => "Smileys & Emotion",
+            Category::PeopleBody => "People & Body",
+            Category::AnimalsNature => "Animals & Nature",
+            Category::FoodDrink => "Food & Drink",
+            Category::TravelPlaces => "Travel & Places",
+            Category::Activities => "Activities",
+            Category::Objects => "Objects",
+            Category::Symbols => "Symbols",
+            Category::Flags => "Flags",
+        }
+    }
+
+    /// The emoji standing for this category on its tab.
+    pub fn icon(self) -> &'static str {
+        match self {
+            Category::SmileysEmotion => "😀",
+            Category::PeopleBody => "👋",
+            Category::AnimalsNature => "🐻",
+            Category::FoodDrink => "🍔",
+            Category::TravelPlaces => "🚗",
+            Category::Activities => "⚽",
+            Category::Objects => "💡",
+            Category::Symbols => "💯",
+            Category::Flags => "🏁",
+        }
+    }
+
+    pub fn emoji(self) -> impl Iterator<Item = &'static Emoji> {
+        EMOJI.iter().filter(move |e| e.category == self)
+    }
+}
+
+/// Skin tone applied to the people and hands that have one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SkinTone {
+    #[default]
+    Default,
+    Light,
+    MediumLight,
+    Medium,
+    MediumDark,
+    Dark,
+}
+
+impl SkinTone {
+    pub const ALL: [SkinTone; 6] = [
+        SkinTone::Default,
+        SkinTone::Light,
+        SkinTone::MediumLight,
+        SkinTone::Medium,
+        SkinTone::MediumDark,
+        SkinTone::Dark,
+    ];
+
+    /// A raised hand in this tone, for the tone picker.
+    pub fn swatch(self) -> &'static str {
+        match self {
+            SkinTone::Default => "✋",
+            SkinTone::Light => "✋🏻",
+            SkinTone::MediumLight => "✋🏼",
+            SkinTone::Medium => "✋🏽",
+            SkinTone::MediumDark => "✋🏾",
+            SkinTone::Dark => "✋🏿",
+        }
+    }
+}
+
+/// Shortcodes people know from other chat apps, where they differ from the
+/// CLDR names. Most used first.
+const ALIASES: &[(&str, &str)] = &[
+    ("thumbsup", "👍"),
+    ("thumbsdown", "👎"),
+    ("joy", "😂"),
+    ("heart", "❤️"),
+    ("smile", "😄"),
+    ("grinning", "😀"),
+    ("slight_smile", "🙂"),
+    ("wink", "😉"),
+    ("blush", "😊"),
+    ("heart_eyes", "😍"),
+    ("thinking", "🤔"),
+    ("sob", "😭"),
+    ("cry", "😢"),
+    ("sweat_smile", "😅"),
+    ("rofl", "🤣"),
+    ("upside_down", "🙃"),
+    ("unamused", "😒"),
+    ("rolling_eyes", "🙄"),
+    ("sleeping", "😴"),
+    ("sunglasses", "😎"),
+    ("nerd", "🤓"),
+    ("open_mouth", "😮"),
+    ("scream", "😱"),
+    ("angry", "😠"),
+    ("rage", "😡"),
+    ("skull", "💀"),
+    ("clown", "🤡"),
+    ("eyes", "👀"),
+    ("wave", "👋"),
+    ("ok_hand", "👌"),
+    ("clap", "👏"),
+    ("raised_hands", "🙌"),
+    ("pray", "🙏"),
+    ("muscle", "💪"),
+    ("point_up", "☝️"),
+    ("v", "✌️"),
+    ("crossed_fingers", "🤞"),
+    ("fire", "🔥"),
+    ("sparkles", "✨"),
+    ("tada", "🎉"),
+    ("100", "💯"),
+    ("white_check_mark", "✅"),
+    ("x", "❌"),
+    ("zap", "⚡"),
+    ("bulb", "💡"),
+    ("memo", "📝"),
+    ("mag", "🔍"),
+    ("beers", "🍻"),
+    ("cat", "🐱"),
+    ("dog", "🐶"),
+];
+
+/// The emoji `shortcode` stands for, alias or CLDR name.
+pub fn by_shortcode(shortcode: &str) -> Option<&'static str> {
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == shortcode)
+        .map(|(_, emoji)| *emoji)
+        .or_else(|| EMOJI.iter().find(|e| e.shortcode == shortcode).map(|e| e.emoji))
+}
+
+/// The table entry for `emoji`, in any skin tone.
+pub fn find(emoji: &str) -> Option<&'static Emoji> {
+    EMOJI
+        .iter()
+        .find(|e| e.emoji == emoji || e.tones.contains(&emoji))
+}
+
+/// Shortcodes matching `query` with their emoji: prefix matches before
+/// substring matches, aliases before CLDR names. One shortcode per emoji.
+pub fn search(query: &str) -> Vec<(&'static str, &'static str)> {
+    let query = query.to_lowercase();
+    let candidates = || {
+        ALIASES
+            .iter()
+            .copied()
+            .chain(EMOJI.iter().map(|e| (e.shortcode, e.emoji)))
+    };
+    let mut results: Vec<(&str, &str)> = Vec::new();
+    let prefix = candidates().filter(|(code, _)| code.starts_with(&query));
+    let substring = candidates().filter(|(code, _)| code.contains(&query));
+    for (code, emoji) in prefix.chain(substring) {
+        if results.len() == MAX_RESULTS {
+            break;
+        }
+        if !results.iter().any(|(_, seen)| *seen == emoji) {
+            results.push((code, emoji));
+        }
+    }
+    results
+}
+
+/// Every emoji whose shortcode, or one of its aliases, contains `query`.
+pub fn matching(query: &str) -> Vec<&'static Emoji> {
+    let query = query.to_lowercase().replace(' ', "_");
+    EMOJI
+        .iter()
+        .filter(|e| {
+            e.shortcode.contains(&query)
+                || ALIASES
+                    .iter()
+                    .any(|(alias, emoji)| *emoji == e.emoji && alias.contains(&query))
+        })
+        .collect()
+}
+
+// ── Custom emoji ─────────────────────────────────────────────────────────────
+
+/// A custom emoji token in message text, `<:name:id>` or `<a:name:id>` for
+/// animated ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomEmojiRef {
+    pub id: String,
+    pub name: String,
+    pub animated: bool,
+}
+
+impl CustomEmojiRef {
+    pub fn token(&self) -> String {
+        let prefix = if self.animated { "a" } else { "" };
+        format!("<{prefix}:{}:{}>", self.name, self.id)
+    }
+}
+
+/// The custom emoji token at the start of `text`, and its length in bytes.
+pub fn parse_custom(text: &str) -> Option<(CustomEmojiRef, usize)> {
+    let rest = text.strip_prefix('<')?;
+    let (animated, rest) = match rest.strip_prefix("a:") {
+        Some(rest) => (true, rest),
+        None => (false, rest.strip_prefix(':')?),
+    };
+    let end = rest.find('>')?;
+    let (name, id) = rest[..end].split_once(':')?;
+    let is_word = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    let len = text.len() - rest.len() + end + 1;
+    (is_word(name) && is_word(id)).then(|| {
+        (
+            CustomEmojiRef {
+                id: id.to_string(),
+                name: name.to_string(),
+                animated,
+            },
+            len,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_has_every_category() {
+        for category in Category::ALL {
+            assert!(category.emoji().count() > 10, "{category:?}");
+        }
+        let wave = find("👋").unwrap();
+        assert_eq!(wave.name, "waving hand");
+        assert_eq!(wave.shortcode, "waving_hand");
+        assert_eq!(wave.category, Category::PeopleBody);
+        assert_eq!(find("🇺🇸").unwrap().shortcode, "flag_united_states");
+        assert_eq!(find("#\u{fe0f}\u{20e3}").unwrap().shortcode, "keycap_hash");
+    }
+
+    #[test]
+    fn test_skin_tones() {
+        let wave = find("👋").unwrap();
+        assert!(wave.has_tones());
+        assert_eq!(wave.with_tone(SkinTone::Default), "👋");
+        assert_eq!(wave.with_tone(SkinTone::Medium), "👋🏽");
+        assert_eq!(find("👋🏿"), Some(wave));
+
+        let smile = find("😀").unwrap();
+        assert!(!smile.has_tones());
+        assert_eq!(smile.with_tone(SkinTone::Dark), "😀");
+    }
+
+    #[test]
+    fn test_shortcodes() {
+        assert_eq!(by_shortcode("thumbsup"), Some("👍"));
+        assert_eq!(by_shortcode("thumbs_up"), Some("👍"));
+        assert_eq!(by_shortcode("nope_not_an_emoji"), None);
+
+        let codes: Vec<&str> = search("thu").into_iter().map(|(code, _)| code).collect();
+        assert_eq!(&codes[..2], ["thumbsup", "thumbsdown"]);
+        // `thumbs_up` is the same emoji as `thumbsup`.
+        assert!(!codes.contains(&"thumbs_up"));
+        assert!(search("smiling_face").len() > 1);
+
+        let matches = matching("thumbsup");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].emoji, "👍");
+        assert!(matching("face with").len() > 10);
+    }
+
+    #[test]
+    fn test_parses_custom_emoji_tokens() {
+        let (emoji, len) = parse_custom("<:party_parrot:e1> yay").unwrap();
+        assert_eq!((emoji.name.as_str(), emoji.id.as_str(), emoji.animated, len), ("party_parrot", "e1", false, 18));
+        assert_eq!(emoji.token(), "<:party_parrot:e1>");
+        let (emoji, len) = parse_custom("<a:blob:e2>").unwrap();
+        assert!(emoji.animated);
+        assert_eq!(len, 11);
+        assert_eq!(parse_custom("<:blob>"), None);
+        assert_eq!(parse_custom("<@u1>"), None);
+        assert_eq!(parse_custom("<:bl ob:e2>"), None);
+    }
+}
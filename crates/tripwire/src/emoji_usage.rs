@@ -0,0 +1,136 @@
+//! Frequently used emoji and the preferred skin tone, saved as `emoji.json`
+//! in the data directory so the picker remembers them across launches.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::emoji::SkinTone;
+use crate::settings_file::{load_json_settings, save_json_settings};
+
+const FILE_NAME: &str = "emoji.json";
+/// Emoji tracked at most; the least used are forgotten past this.
+const MAX_TRACKED: usize = 64;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EmojiUsage {
+    #[serde(default)]
+    pub skin_tone: SkinTone,
+    /// How often, and how recently, each emoji (or custom emoji token) was
+    /// picked.
+    #[serde(default)]
+    uses: HashMap<String, Usage>,
+    /// Bumped on every pick, ordering `Usage::last_used`.
+    #[serde(default)]
+    clock: u64,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Usage {
+    count: u32,
+    last_used: u64,
+}
+
+impl EmojiUsage {
+    /// Load the usage saved in `dir`, starting empty if there is none.
+    pub fn load(dir: &Path) -> Self {
+        Self {
+            path: Some(dir.join(FILE_NAME)),
+            ..load_json_settings(dir, FILE_NAME)
+        }
+    }
+
+    pub fn record(&mut self, emoji: &str) {
+        self.clock += 1;
+        let usage = self.uses.entry(emoji.to_string()).or_insert(Usage {
+            count: 0,
+            last_used: 0,
+        });
+        usage.count += 1;
+        usage.last_used = self.clock;
+        if self.uses.len() > MAX_TRACKED
+            && let Some(least) = self.ranked().pop()
+        {
+            self.uses.remove(&least);
+        }
+        self.save();
+    }
+
+    pub fn set_skin_tone(&mut self, tone: SkinTone) {
+        self.skin_tone = tone;
+        self.save();
+    }
+
+    /// The `limit` most used emoji, most recent first among equals.
+    pub fn frequent(&self, limit: usize) -> Vec<String> {
+        let mut ranked = self.ranked();
+        ranked.truncate(limit);
+        ranked
+    }
+
+    fn ranked(&self) -> Vec<String> {
+        let mut uses: Vec<(&String, &Usage)> = self.uses.iter().collect();
+        uses.sort_by(|(_, a), (_, b)| {
+            b.count
+                .cmp(&a.count)
+                .then(b.last_used.cmp(&a.last_used))
+        });
+        uses.into_iter().map(|(emoji, _)| emoji.clone()).collect()
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            save_json_settings(path, self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_ranks_by_count_then_recency() {
+        let mut usage = EmojiUsage::default();
+        for emoji in ["👍", "🎉", "👍", "🔥", "🎉", "😂"] {
+            usage.record(emoji);
+        }
+        assert_eq!(usage.frequent(10), ["🎉", "👍", "😂", "🔥"]);
+        assert_eq!(usage.frequent(1), ["🎉"]);
+    }
+
+    #[test]
+    fn test_forgets_least_used() {
+        let mut usage = EmojiUsage::default();
+        usage.record("👍");
+        usage.record("👍");
+        for ix in 0..MAX_TRACKED {
+            usage.record(&ix.to_string());
+        }
+        let frequent = usage.frequent(MAX_TRACKED + 1);
+        assert_eq!(frequent.len(), MAX_TRACKED);
+        assert_eq!(frequent[0], "👍");
+        assert!(!frequent.contains(&"0".to_string()));
+    }
+
+    #[test]
+    fn test_round_trips_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut usage = EmojiUsage::load(dir.path());
+        usage.record("<:ferris:e1>");
+        usage.record("👋🏽");
+        usage.set_skin_tone(SkinTone::Medium);
+
+        let loaded = EmojiUsage::load(dir.path());
+        assert_eq!(loaded.skin_tone, SkinTone::Medium);
+        assert_eq!(loaded.frequent(10), ["👋🏽", "<:ferris:e1>"]);
+
+        fs::write(dir.path().join("emoji.json"), "not json").unwrap();
+        assert!(EmojiUsage::load(dir.path()).frequent(10).is_empty());
+    }
+}
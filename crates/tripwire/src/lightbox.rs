@@ -0,0 +1,303 @@
+//! Lightbox state — which of a conversation's media is open, and how the
+//! image is zoomed and panned. Sizes and positions are in logical pixels;
+//! an image is shown at one image pixel per logical pixel at 100%.
+
+use gpui::{Bounds, Point, Size, point, size};
+
+use crate::models::{Attachment, Message};
+
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 8.;
+/// Zoom factor of one step in or out.
+pub const ZOOM_STEP: f32 = 1.25;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zoom {
+    /// Shrunk to fit the viewport, never enlarged.
+    Fit,
+    Scale(f32),
+}
+
+#[derive(Debug)]
+pub struct Lightbox {
+    items: Vec<Attachment>,
+    index: usize,
+    zoom: Zoom,
+    /// Of the image's center from the viewport's.
+    offset: Point<f32>,
+    /// Where the pointer was when the image was last dragged.
+    drag_from: Option<Point<f32>>,
+    /// Natural size of the open image, once it's loaded.
+    image_size: Option<Size<f32>>,
+    /// Where the image is shown, in window coordinates.
+    viewport: Bounds<f32>,
+}
+
+/// The images and videos attached to `messages`, oldest first.
+pub fn media(messages: &[Message]) -> Vec<Attachment> {
+    messages
+        .iter()
+        .flat_map(|m| &m.attachments)
+        .filter(|a| a.is_image() || a.is_video())
+        .cloned()
+        .collect()
+}
+
+impl Lightbox {
+    /// Open `attachment`, stepping through `items` from there. It's shown on
+    /// its own if it isn't one of them.
+    pub fn new(mut items: Vec<Attachment>, attachment: &Attachment) -> Self {
+        let index = match items.iter().position(|a| a.id == attachment.id) {
+            Some(index) => index,
+            None => {
+                items = vec![attachment.clone()];
+                0
+            }
+        };
+        Self {
+            items,
+            index,
+            zoom: Zoom::Fit,
+            offset: Point::default(),
+            drag_from: None,
+            image_size: None,
+            viewport: Bounds::default(),
+        }
+    }
+
+    pub fn current(&self) -> &Attachment {
+        &self.items[self.index]
+    }
+
+    /// Position of the open item, from 0, and how many there are.
+    pub fn position(&self) -> (usize, usize) {
+        (self.index, self.items.len())
+    }
+
+    pub fn has_previous(&self) -> bool {
+        self.index > 0
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.index + 1 < self.items.len()
+    }
+
+    pub fn previous(&mut self) {
+        if self.has_previous() {
+            self.show(self.index - 1);
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.has_next() {
+            self.show(self.index + 1);
+        }
+    }
+
+    fn show(&mut self, index: usize) {
+        self.index = index;
+        self.zoom = Zoom::Fit;
+        self.offset = Point::default();
+        self.drag_from = None;
+        self.image_size = None;
+    }
+
+    pub fn zoom(&self) -> Zoom {
+        self.zoom
+    }
+
+    pub fn set_image_size(&mut self, image_size: Size<f32>) {
+        self.image_size = Some(image_size);
+    }
+
+    /// Returns whether the viewport moved or resized.
+    pub fn set_viewport(&mut self, viewport: Bounds<f32>) -> bool {
+        if self.viewport == viewport {
+            return false;
+        }
+        self.viewport = viewport;
+        self.clamp_offset();
+        true
+    }
+
+    /// How much the image is enlarged; 1 is actual size.
+    pub fn scale(&self) -> f32 {
+        match (self.zoom, self.image_size) {
+            (Zoom::Scale(scale), _) => scale,
+            (Zoom::Fit, Some(image)) if image.width > 0. && image.height > 0. => {
+                let viewport = self.viewport.size;
+                (viewport.width / image.width).min(viewport.height / image.height).min(1.)
+            }
+            (Zoom::Fit, _) => 1.,
+        }
+    }
+
+    /// Where the image is drawn, relative to the viewport; `None` until
+    /// it's loaded.
+    pub fn image_bounds(&self) -> Option<Bounds<f32>> {
+        let image = self.image_size?;
+        let scale = self.scale();
+        let shown = size(image.width * scale, image.height * scale);
+        Some(Bounds::new(
+            point(
+                (self.viewport.size.width - shown.width) / 2. + self.offset.x,
+                (self.viewport.size.height - shown.height) / 2. + self.offset.y,
+            ),
+            shown,
+        ))
+    }
+
+    /// Zoom in (`factor` above 1) or out, keeping the point of the image
+    /// under `anchor`, in window coordinates, where it is. Without an
+    /// anchor the viewport's center stays put.
+    pub fn zoom_by(&mut self, factor: f32, anchor: Option<Point<f32>>) {
+        let old = self.scale();
+        let new = (old * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        let anchor = anchor
+            .map(|p| {
+                point(
+                    p.x - self.viewport.origin.x - self.viewport.size.width / 2.,
+                    p.y - self.viewport.origin.y - self.viewport.size.height / 2.,
+                )
+            })
+            .unwrap_or_default();
+        let ratio = new / old;
+        self.offset = point(
+            anchor.x - (anchor.x - self.offset.x) * ratio,
+            anchor.y - (anchor.y - self.offset.y) * ratio,
+        );
+        self.zoom = Zoom::Scale(new);
+        self.clamp_offset();
+    }
+
+    /// Switch between fitting the viewport and actual size.
+    pub fn toggle_actual_size(&mut self) {
+        self.zoom = match self.zoom {
+            Zoom::Fit => Zoom::Scale(1.),
+            Zoom::Scale(_) => Zoom::Fit,
+        };
+        self.offset = Point::default();
+    }
+
+    pub fn start_drag(&mut self, position: Point<f32>) {
+        self.drag_from = Some(position);
+    }
+
+    /// Pan by how far the pointer moved since the last call. Returns
+    /// whether a drag is in progress.
+    pub fn drag_to(&mut self, position: Point<f32>) -> bool {
+        let Some(from) = self.drag_from.replace(position) else {
+            return false;
+        };
+        self.offset = point(self.offset.x + position.x - from.x, self.offset.y + position.y - from.y);
+        self.clamp_offset();
+        true
+    }
+
+    pub fn end_drag(&mut self) {
+        self.drag_from = None;
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag_from.is_some()
+    }
+
+    /// Keep the image over the viewport: it can only be panned along an
+    /// axis it overflows, and not past its edges.
+    fn clamp_offset(&mut self) {
+        let Some(image) = self.image_size else {
+            return;
+        };
+        let scale = self.scale();
+        let max_x = ((image.width * scale - self.viewport.size.width) / 2.).max(0.);
+        let max_y = ((image.height * scale - self.viewport.size.height) / 2.).max(0.);
+        self.offset = point(self.offset.x.clamp(-max_x, max_x), self.offset.y.clamp(-max_y, max_y));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AttachmentSource;
+
+    fn image(id: &str) -> Attachment {
+        Attachment {
+            id: id.to_string(),
+            filename: format!("{id}.png"),
+            mime_type: "image/png".to_string(),
+            size: 1,
+            url: format!("https://cdn.example.com/{id}.png"),
+            source: AttachmentSource::Remote,
+        }
+    }
+
+    /// A 2000×1000 image in an 800×600 viewport at (100, 50).
+    fn lightbox() -> Lightbox {
+        let mut lightbox = Lightbox::new(vec![image("a"), image("b")], &image("a"));
+        lightbox.set_viewport(Bounds::new(point(100., 50.), size(800., 600.)));
+        lightbox.set_image_size(size(2000., 1000.));
+        lightbox
+    }
+
+    #[test]
+    fn test_fits_without_enlarging() {
+        let mut lightbox = lightbox();
+        assert_eq!(lightbox.scale(), 0.4);
+        assert_eq!(lightbox.image_bounds(), Some(Bounds::new(point(0., 100.), size(800., 400.))));
+
+        lightbox.set_image_size(size(200., 100.));
+        assert_eq!(lightbox.scale(), 1.);
+
+        lightbox.toggle_actual_size();
+        lightbox.set_image_size(size(2000., 1000.));
+        assert_eq!(lightbox.scale(), 1.);
+        lightbox.toggle_actual_size();
+        assert_eq!(lightbox.zoom(), Zoom::Fit);
+    }
+
+    #[test]
+    fn test_zooms_around_the_pointer() {
+        let mut lightbox = lightbox();
+        // 100 px right of the viewport's center, on the image's
+        // (1000 + 100 / 0.4)th column.
+        lightbox.zoom_by(2., Some(point(600., 350.)));
+        assert_eq!(lightbox.scale(), 0.8);
+        let bounds = lightbox.image_bounds().unwrap();
+        assert_eq!((500. - bounds.origin.x) / 0.8, 1250.);
+
+        lightbox.zoom_by(1000., None);
+        assert_eq!(lightbox.scale(), MAX_ZOOM);
+        lightbox.zoom_by(0., None);
+        assert_eq!(lightbox.scale(), MIN_ZOOM);
+    }
+
+    #[test]
+    fn test_pans_only_within_the_image() {
+        let mut lightbox = lightbox();
+        lightbox.start_drag(point(0., 0.));
+        assert!(lightbox.drag_to(point(50., 50.)));
+        assert_eq!(lightbox.image_bounds().unwrap().origin, point(0., 100.));
+
+        lightbox.toggle_actual_size();
+        lightbox.drag_to(point(5000., 5000.));
+        lightbox.end_drag();
+        assert!(!lightbox.drag_to(point(0., 0.)));
+        // 2000×1000 in 800×600: 600 px to spare on either side, 200 above.
+        assert_eq!(lightbox.image_bounds().unwrap().origin, point(0., 0.));
+    }
+
+    #[test]
+    fn test_steps_through_media_from_a_fresh_view() {
+        let mut lightbox = lightbox();
+        assert!(!lightbox.has_previous());
+        lightbox.zoom_by(2., None);
+        lightbox.next();
+        assert_eq!((lightbox.current().id.as_str(), lightbox.position()), ("b", (1, 2)));
+        assert_eq!(lightbox.zoom(), Zoom::Fit);
+        lightbox.next();
+        assert_eq!(lightbox.position(), (1, 2));
+
+        let alone = Lightbox::new(vec![image("a")], &image("c"));
+        assert_eq!((alone.current().id.as_str(), alone.position()), ("c", (0, 1)));
+    }
+}
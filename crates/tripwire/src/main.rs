@@ -5,44 +5,134 @@
 //! ```
 //! main.rs
 //! ├── models.rs           — Data types (User, Server, Channel, Message)
-//! ├── auth_state.rs       — Local auth persistence to disk
+//! ├── permissions.rs      — Permission flags, channel overwrites, role hierarchy
+//! ├── role_draft.rs       — Staged role edits, limited by the editor's rank
+//! ├── members.rs          — Member filters and sorting, moderation rules
+//! ├── channels.rs         — Channel layout edits: moving, naming, overwrites
+//! ├── audit_log.rs        — Audit log entries, before/after changes, paging
+//! ├── auth_state.rs       — Session state and persistence
+//! ├── secure_store.rs     — Keyring / encrypted-file secret storage
+//! ├── message_store.rs    — Per-account SQLite message cache
+//! ├── outbox.rs           — Unacknowledged outgoing messages, retry backoff
+//! ├── read_state.rs       — Last read message, unread and mention counts
+//! ├── attachments.rs      — Attachment type sniffing, upload limits
+//! ├── unfurl.rs           — Link preview fetching and metadata parsing, cached
+//! ├── privacy.rs          — Privacy settings, persisted
+//! ├── settings_file.rs    — Loading and saving JSON settings files
+//! ├── lightbox.rs         — Media viewer state: stepping, zoom and pan
+//! ├── notifications/      — Notification settings and rules, desktop notification bus
+//! ├── search.rs           — Search query filters and result snippets
+//! ├── diff.rs             — Word-level diff of message revisions
+//! ├── markdown.rs         — Discord-flavoured message markdown
+//! ├── mentions.rs         — Mention tokens, composer completions
+//! ├── emoji.rs            — Unicode emoji table (from build.rs), skin tones, custom emoji tokens
+//! ├── emoji_usage.rs      — Frequently used emoji and skin tone, persisted
+//! ├── totp.rs             — TOTP codes and backup codes for two-factor auth
+//! ├── backend/            — ChatBackend trait, auth, mock / HTTP backends, gateway
 //! ├── mock_data.rs        — Sample servers / channels / messages
 //! └── app.rs              — TripwireApp entity + Render impl
+//!     ├── accounts.rs     — impl TripwireApp: account switching, per-account caches
+//!     ├── audit_log.rs    — impl TripwireApp: audit log screen in server settings
 //!     ├── auth_view.rs    — impl TripwireApp: login screen
+//!     ├── channels.rs     — impl TripwireApp: creating, editing, reordering and deleting channels
+//!     ├── delivery.rs     — impl TripwireApp: sending through the outbox
+//!     ├── uploads.rs      — impl TripwireApp: attaching (picked, dropped, pasted), uploads, downloads
+//!     ├── members.rs      — impl TripwireApp: member table and moderation in server settings
+//!     ├── message_cache.rs — impl TripwireApp: offline history via message_store
+//!     ├── mention_completions.rs — impl TripwireApp: composer @/#/: completions
+//!     ├── message_list.rs — impl TripwireApp: virtualized message list, older pages
+//!     ├── notifications.rs — impl TripwireApp: notifying on new messages, toasts
+//!     ├── permissions.rs  — impl TripwireApp: the user's permissions where they are
+//!     ├── read_state.rs   — impl TripwireApp: acking viewed channels, mark as read
+//!     ├── realtime.rs     — impl TripwireApp: gateway events → state
+//!     ├── roles.rs        — impl TripwireApp: role editor in server settings
+//!     ├── search.rs       — impl TripwireApp: message search, jump to message
+//!     ├── session.rs      — impl TripwireApp: login, token refresh
+//!     ├── two_factor.rs   — impl TripwireApp: 2FA login step, TOTP enrollment
 //!     └── app_view/
 //!         ├── mod.rs      — impl TripwireApp: top-level Discord layout
 //!         ├── server_list.rs   — left icon strip
-//!         ├── channel_list.rs  — channel/category sidebar
+//!         ├── channel_list.rs  — channel/category sidebar, drag to reorder
+//!         ├── channel_dialogs.rs — create, channel settings and delete dialogs
 //!         ├── chat_area.rs     — message list + composer
-//!         └── members_panel.rs — online/offline user list
+//!         ├── message_body.rs  — message markdown, spoilers, code blocks
+//!         ├── edit_history.rs  — edit history dialog with word diffs
+//!         ├── embeds.rs        — embed cards, link previews
+//!         ├── lightbox.rs      — full-window image / video viewer
+//!         ├── emoji_picker.rs  — reaction / composer emoji picker
+//!         ├── search_panel.rs  — search results sidebar
+//!         └── members_panel.rs — user list, grouped by hoisted role
 //! ```
 
 mod app;
+mod attachments;
+mod audit_log;
 mod auth_state;
+mod backend;
+mod channels;
+mod diff;
+mod emoji;
+mod emoji_usage;
+mod lightbox;
+mod markdown;
+mod members;
+mod mentions;
+mod message_store;
 mod mock_data;
 mod models;
+mod notifications;
+mod outbox;
+mod permissions;
+mod privacy;
+mod read_state;
+mod role_draft;
+mod search;
+mod secure_store;
+mod settings_file;
 mod titlebar;
+mod totp;
+mod unfurl;
+
+use std::sync::Arc;
 
 use gpui::{
     App, Application, Bounds, WindowBounds, WindowKind,
     WindowOptions, actions, px, size,
 };
+use gpui::http_client::Url;
 use gpui::Focusable;
 use gpui::AppContext;
 use gpui_component::Root;
 use gpui_component_assets::Assets;
+use reqwest_client::ReqwestClient;
 
 use app::TripwireApp;
+use auth_state::AuthState;
+use backend::{ChatBackend, HttpBackend, MockBackend};
 use titlebar::TripwireTitleBar;
 
 actions!(tripwire, [Quit]);
 
+/// Environment variable pointing the client at a Tripwire API server.
+/// When unset, the built-in mock backend is used.
+const API_URL_ENV: &str = "TRIPWIRE_API_URL";
+
 fn main() {
-    let app = Application::new().with_assets(Assets);
+    let auth = match AuthState::new() {
+        Ok(auth) => auth,
+        Err(err) => {
+            eprintln!("Tripwire can't start: {err:#}");
+            std::process::exit(1);
+        }
+    };
+    let app = Application::new()
+        .with_assets(Assets)
+        .with_http_client(Arc::new(ReqwestClient::new()));
 
     app.run(move |cx| {
         // Initialize the gpui-component library (theme, icons, fonts, etc.)
         gpui_component::init(cx);
+        app::app_view::lightbox::init(cx);
 
         cx.bind_keys([
             #[cfg(target_os = "macos")]
@@ -57,11 +147,25 @@ fn main() {
 
         cx.activate(true);
 
-        open_window(cx);
+        open_window(auth, cx);
     });
 }
 
-fn open_window(cx: &mut App) {
+fn make_backend(cx: &App) -> Arc<dyn ChatBackend> {
+    let Ok(url) = std::env::var(API_URL_ENV) else {
+        return Arc::new(MockBackend::new());
+    };
+    match Url::parse(&url) {
+        Ok(base_url) => Arc::new(HttpBackend::new(cx.http_client(), base_url)),
+        Err(err) => {
+            eprintln!("Invalid {API_URL_ENV} {url:?}: {err}; using mock backend");
+            Arc::new(MockBackend::new())
+        }
+    }
+}
+
+fn open_window(auth: AuthState, cx: &mut App) {
+    let backend = make_backend(cx);
     let window_size = compute_window_size(cx);
     let bounds = Bounds::centered(None, window_size, cx);
 
@@ -83,7 +187,7 @@ fn open_window(cx: &mut App) {
 
         let window = cx
             .open_window(options, |window, cx| {
-                let app_view = cx.new(|cx| TripwireApp::new(window, cx));
+                let app_view = cx.new(|cx| TripwireApp::new(backend, auth, window, cx));
 
                 // Focus the root entity so keyboard shortcuts work immediately
                 let focus = app_view.focus_handle(cx);
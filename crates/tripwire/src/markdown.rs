@@ -0,0 +1,588 @@
+//! Discord-flavoured markdown for message bodies.
+//!
+//! Only what fits in a chat line is recognised: `**bold**`, `*italic*` /
+//! `_italic_`, `__underline__`, `~~strikethrough~~`, `` `inline code` ``,
+//! fenced code blocks, `> ` / `>>> ` block quotes, `||spoilers||` and masked
+//! links (`[label](https://…)`), plus bare `http(s)://` links, mention
+//! tokens (see `mentions`) and custom emoji tokens. Headings, tables, lists
+//! and images stay plain text so a message can't take over the layout.
+//! Backslash escapes a punctuation character.
+
+use crate::emoji::{self, CustomEmojiRef};
+use crate::mentions::{self, Mention};
+
+/// A top-level piece of a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Paragraph(Vec<Span>),
+    Quote(Vec<Span>),
+    Code { lang: Option<String>, code: String },
+}
+
+/// Formatting applied to a run of text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpanStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub code: bool,
+    /// Index of the spoiler this text is hidden in, counted across the
+    /// whole message.
+    pub spoiler: Option<usize>,
+}
+
+/// A run of text with the same formatting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+    /// Target of the link this text is part of.
+    pub link: Option<String>,
+    /// The mention this text is the token of.
+    pub mention: Option<Mention>,
+    /// The custom emoji this text is the token of.
+    pub emoji: Option<CustomEmojiRef>,
+}
+
+/// Parse a message body into blocks.
+pub fn parse(content: &str) -> Vec<Block> {
+    let mut parser = Parser::default();
+    let lines: Vec<&str> = content.split('\n').collect();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut quote: Vec<&str> = Vec::new();
+
+    let mut ix = 0;
+    while ix < lines.len() {
+        let line = lines[ix];
+        if let Some((lang, code, end)) = code_block(&lines, ix) {
+            parser.flush_paragraph(&mut paragraph);
+            parser.flush_quote(&mut quote);
+            parser.blocks.push(Block::Code { lang, code });
+            ix = end + 1;
+            continue;
+        }
+        if line == ">>>" || line.starts_with(">>> ") {
+            // Quotes everything that follows.
+            parser.flush_paragraph(&mut paragraph);
+            quote.push(line.get(4..).unwrap_or(""));
+            quote.extend(&lines[ix + 1..]);
+            break;
+        }
+        if line == ">" || line.starts_with("> ") {
+            parser.flush_paragraph(&mut paragraph);
+            quote.push(line.get(2..).unwrap_or(""));
+        } else {
+            parser.flush_quote(&mut quote);
+            paragraph.push(line);
+        }
+        ix += 1;
+    }
+    parser.flush_paragraph(&mut paragraph);
+    parser.flush_quote(&mut quote);
+    parser.blocks
+}
+
+/// The code block opening at `lines[start]`, if it's closed: its language,
+/// code, and the index of the closing line.
+fn code_block(lines: &[&str], start: usize) -> Option<(Option<String>, String, usize)> {
+    let first = lines[start].strip_prefix("```")?;
+    if let Some(close) = first.find("```") {
+        let code = &first[..close];
+        return (!code.trim().is_empty()).then(|| (None, code.to_string(), start));
+    }
+
+    let (end, last) = lines
+        .iter()
+        .enumerate()
+        .skip(start + 1)
+        .find_map(|(ix, line)| line.find("```").map(|close| (ix, &line[..close])))?;
+    let is_lang = !first.is_empty()
+        && first
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '#' | '_' | '.'));
+    let mut code: Vec<&str> = Vec::new();
+    if !is_lang && !first.is_empty() {
+        code.push(first);
+    }
+    code.extend(&lines[start + 1..end]);
+    if !last.is_empty() {
+        code.push(last);
+    }
+    Some((is_lang.then(|| first.to_string()), code.join("\n"), end))
+}
+
+#[derive(Default)]
+struct Parser {
+    blocks: Vec<Block>,
+    spoilers: usize,
+}
+
+impl Parser {
+    fn flush_paragraph(&mut self, lines: &mut Vec<&str>) {
+        let text = lines.join("\n");
+        lines.clear();
+        let text = text.trim_matches('\n');
+        if !text.is_empty() {
+            let spans = self.inline(text);
+            self.blocks.push(Block::Paragraph(spans));
+        }
+    }
+
+    fn flush_quote(&mut self, lines: &mut Vec<&str>) {
+        if lines.is_empty() {
+            return;
+        }
+        let text = lines.join("\n");
+        lines.clear();
+        let spans = self.inline(&text);
+        self.blocks.push(Block::Quote(spans));
+    }
+
+    fn inline(&mut self, text: &str) -> Vec<Span> {
+        let mut spans = Vec::new();
+        self.parse_inline(text, SpanStyle::default(), None, &mut spans);
+        spans
+    }
+
+    fn parse_inline(&mut self, text: &str, style: SpanStyle, link: Option<&str>, out: &mut Vec<Span>) {
+        let mut plain = String::new();
+        let mut ix = 0;
+        while ix < text.len() {
+            let rest = &text[ix..];
+
+            if let Some(escaped) = rest
+                .strip_prefix('\\')
+                .and_then(|r| r.chars().next())
+                .filter(|c| c.is_ascii_punctuation())
+            {
+                plain.push(escaped);
+                ix += 2;
+                continue;
+            }
+
+            if rest.starts_with('`') {
+                let ticks = rest.len() - rest.trim_start_matches('`').len();
+                let fence = &rest[..ticks];
+                if let Some(close) = rest[ticks..].find(fence) {
+                    let code = &rest[ticks..ticks + close];
+                    if !code.trim().is_empty() {
+                        push_span(out, &mut plain, style, link);
+                        let code_style = SpanStyle { code: true, ..style };
+                        out.push(Span {
+                            text: code.to_string(),
+                            style: code_style,
+                            link: link.map(String::from),
+                            mention: None,
+                            emoji: None,
+                        });
+                        ix += ticks * 2 + close;
+                        continue;
+                    }
+                }
+                // Not code; keep the whole run of backticks literal.
+                plain.push_str(fence);
+                ix += ticks;
+                continue;
+            }
+
+            if let Some(delim) = ["||", "**", "__", "~~", "*", "_"]
+                .into_iter()
+                .find(|d| rest.starts_with(d))
+                && let Some(close) = closing_delimiter(text, ix, delim)
+            {
+                push_span(out, &mut plain, style, link);
+                let mut inner = style;
+                match delim {
+                    "||" => {
+                        inner.spoiler = Some(self.spoilers);
+                        self.spoilers += 1;
+                    }
+                    "**" => inner.bold = true,
+                    "__" => inner.underline = true,
+                    "~~" => inner.strikethrough = true,
+                    _ => inner.italic = true,
+                }
+                self.parse_inline(&text[ix + delim.len()..close], inner, link, out);
+                ix = close + delim.len();
+                continue;
+            }
+
+            if link.is_none()
+                && rest.starts_with('[')
+                && let Some((label, url, len)) = masked_link(rest)
+            {
+                push_span(out, &mut plain, style, link);
+                self.parse_inline(label, style, Some(url), out);
+                ix += len;
+                continue;
+            }
+
+            if link.is_none()
+                && is_url_start(rest)
+                && !text[..ix].ends_with(|c: char| c.is_alphanumeric())
+            {
+                let url = bare_url(rest);
+                push_span(out, &mut plain, style, link);
+                out.push(Span {
+                    text: url.to_string(),
+                    style,
+                    link: Some(url.to_string()),
+                    mention: None,
+                    emoji: None,
+                });
+                ix += url.len();
+                continue;
+            }
+
+            if link.is_none()
+                && rest.starts_with(['<', '@'])
+                && !text[..ix].ends_with(|c: char| c.is_alphanumeric())
+                && let Some((mention, len)) = mentions::parse_token(rest)
+            {
+                push_span(out, &mut plain, style, link);
+                out.push(Span {
+                    text: rest[..len].to_string(),
+                    style,
+                    link: None,
+                    mention: Some(mention),
+                    emoji: None,
+                });
+                ix += len;
+                continue;
+            }
+
+            if link.is_none()
+                && rest.starts_with('<')
+                && let Some((custom, len)) = emoji::parse_custom(rest)
+            {
+                push_span(out, &mut plain, style, link);
+                out.push(Span {
+                    text: rest[..len].to_string(),
+                    style,
+                    link: None,
+                    mention: None,
+                    emoji: Some(custom),
+                });
+                ix += len;
+                continue;
+            }
+
+            let c = rest.chars().next().unwrap_or_default();
+            plain.push(c);
+            ix += c.len_utf8();
+        }
+        push_span(out, &mut plain, style, link);
+    }
+}
+
+/// Append `plain` as a span, merging it into the previous one if that's
+/// formatted the same.
+fn push_span(out: &mut Vec<Span>, plain: &mut String, style: SpanStyle, link: Option<&str>) {
+    if plain.is_empty() {
+        return;
+    }
+    match out.last_mut() {
+        Some(last) if last.style == style && last.link.as_deref() == link && last.mention.is_none() && last.emoji.is_none() => {
+            last.text.push_str(plain);
+            plain.clear();
+        }
+        _ => out.push(Span {
+            text: std::mem::take(plain),
+            style,
+            link: link.map(String::from),
+            mention: None,
+            emoji: None,
+        }),
+    }
+}
+
+/// Where the `delim` opened at `open` is closed, if it is.
+fn closing_delimiter(text: &str, open: usize, delim: &str) -> Option<usize> {
+    let start = open + delim.len();
+    let repeat = delim.as_bytes()[0];
+    let mut search = start;
+    while let Some(found) = text[search..].find(delim) {
+        let mut close = search + found;
+        // `***both***`: close at the end of the run, leaving `*` inside.
+        while text.as_bytes().get(close + delim.len()) == Some(&repeat) {
+            close += 1;
+        }
+        let inner = &text[start..close];
+        let flanked = match delim {
+            "*" | "_" => !inner.starts_with(char::is_whitespace) && !inner.ends_with(char::is_whitespace),
+            _ => true,
+        };
+        // `snake_case_names` aren't italic.
+        let word_bound = delim != "_"
+            || (!text[..open].ends_with(|c: char| c.is_alphanumeric())
+                && !text[close + 1..].starts_with(|c: char| c.is_alphanumeric()));
+        if !inner.is_empty() && flanked && word_bound {
+            return Some(close);
+        }
+        search = close + 1;
+    }
+    None
+}
+
+/// `[label](url)` at the start of `text`: the label, url and length.
+fn masked_link(text: &str) -> Option<(&str, &str, usize)> {
+    let label_end = text.find("](")?;
+    let label = &text[1..label_end];
+    let url_start = label_end + 2;
+    let url_len = text[url_start..].find(')')?;
+    let url = &text[url_start..url_start + url_len];
+    let valid = !label.trim().is_empty()
+        && !label.contains(['[', ']', '\n'])
+        && is_url_start(url)
+        && !url.contains(char::is_whitespace);
+    valid.then_some((label, url, url_start + url_len + 1))
+}
+
+fn is_url_start(text: &str) -> bool {
+    ["https://", "http://"]
+        .iter()
+        .any(|scheme| text.len() > scheme.len() && text.get(..scheme.len()).is_some_and(|p| p.eq_ignore_ascii_case(scheme)))
+}
+
+/// The URL at the start of `text`, without trailing punctuation.
+fn bare_url(text: &str) -> &str {
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    text[..end].trim_end_matches(['.', ',', ':', ';', '!', '?', ')', '"', '\''])
+}
+
+/// Links in `content` worth previewing, in order and without repeats:
+/// those outside code and spoilers.
+pub fn links(content: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for block in parse(content) {
+        let (Block::Paragraph(spans) | Block::Quote(spans)) = block else {
+            continue;
+        };
+        for span in spans {
+            if let Some(link) = span.link
+                && !span.style.code
+                && span.style.spoiler.is_none()
+                && !links.contains(&link)
+            {
+                links.push(link);
+            }
+        }
+    }
+    links
+}
+
+/// The text of `spans`, with each span's byte range in it.
+pub fn layout(spans: &[Span]) -> (String, Vec<std::ops::Range<usize>>) {
+    let mut text = String::new();
+    let mut ranges = Vec::with_capacity(spans.len());
+    for span in spans {
+        let start = text.len();
+        text.push_str(&span.text);
+        ranges.push(start..text.len());
+    }
+    (text, ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(text: &str, style: SpanStyle) -> Span {
+        Span {
+            text: text.into(),
+            style,
+            link: None,
+            mention: None,
+            emoji: None,
+        }
+    }
+
+    fn plain(text: &str) -> Span {
+        span(text, SpanStyle::default())
+    }
+
+    fn paragraph(content: &str) -> Vec<Span> {
+        match parse(content).as_slice() {
+            [Block::Paragraph(spans)] => spans.clone(),
+            other => panic!("expected one paragraph, got {other:?}"),
+        }
+    }
+
+    const BOLD: SpanStyle = SpanStyle {
+        bold: true,
+        italic: false,
+        underline: false,
+        strikethrough: false,
+        code: false,
+        spoiler: None,
+    };
+    const ITALIC: SpanStyle = SpanStyle { bold: false, italic: true, ..BOLD };
+
+    #[test]
+    fn test_inline_formatting() {
+        assert_eq!(
+            paragraph("a **b** *c* _d_ __e__ ~~f~~ `g*h*`"),
+            [
+                plain("a "),
+                span("b", BOLD),
+                plain(" "),
+                span("c", ITALIC),
+                plain(" "),
+                span("d", ITALIC),
+                plain(" "),
+                span("e", SpanStyle { bold: false, underline: true, ..BOLD }),
+                plain(" "),
+                span("f", SpanStyle { bold: false, strikethrough: true, ..BOLD }),
+                plain(" "),
+                span("g*h*", SpanStyle { bold: false, code: true, ..BOLD }),
+            ]
+        );
+        assert_eq!(
+            paragraph("***both***"),
+            [span("both", SpanStyle { italic: true, ..BOLD })]
+        );
+    }
+
+    #[test]
+    fn test_unmatched_and_escaped_markers_stay_literal() {
+        assert_eq!(paragraph("2 * 3 * 4"), [plain("2 * 3 * 4")]);
+        assert_eq!(paragraph("snake_case_name"), [plain("snake_case_name")]);
+        assert_eq!(paragraph("**open"), [plain("**open")]);
+        assert_eq!(paragraph(r"\*not italic\*"), [plain("*not italic*")]);
+        assert_eq!(paragraph("``"), [plain("``")]);
+        assert_eq!(paragraph("# Not a heading"), [plain("# Not a heading")]);
+        assert_eq!(paragraph("| a | b |\n|---|---|"), [plain("| a | b |\n|---|---|")]);
+    }
+
+    #[test]
+    fn test_spoilers_are_numbered_across_the_message() {
+        let blocks = parse("||one|| and ||**two**||\n> ||three||");
+        let spoilers: Vec<(String, Option<usize>, bool)> = blocks
+            .iter()
+            .flat_map(|block| match block {
+                Block::Paragraph(spans) | Block::Quote(spans) => spans.clone(),
+                Block::Code { .. } => Vec::new(),
+            })
+            .filter(|s| s.style.spoiler.is_some())
+            .map(|s| (s.text, s.style.spoiler, s.style.bold))
+            .collect();
+        assert_eq!(
+            spoilers,
+            [
+                ("one".into(), Some(0), false),
+                ("two".into(), Some(1), true),
+                ("three".into(), Some(2), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        let spans = paragraph("see [the **docs**](https://example.com/a_b) or https://x.io/p.");
+        assert_eq!(spans[0], plain("see "));
+        assert_eq!(spans[1].text, "the ");
+        assert_eq!(spans[1].link.as_deref(), Some("https://example.com/a_b"));
+        assert!(spans[2].style.bold && spans[2].link.is_some());
+        assert_eq!(spans[3], plain(" or "));
+        assert_eq!(spans[4].text, "https://x.io/p");
+        assert_eq!(spans[4].link.as_deref(), Some("https://x.io/p"));
+        assert_eq!(spans[5], plain("."));
+
+        assert_eq!(
+            paragraph("[click](javascript:alert(1))"),
+            [plain("[click](javascript:alert(1))")]
+        );
+        assert_eq!(paragraph("Hello é there"), [plain("Hello é there")]);
+        assert_eq!(paragraph("[café](ftpé://x)"), [plain("[café](ftpé://x)")]);
+    }
+
+    #[test]
+    fn test_mention_tokens() {
+        let spans = paragraph("hi <@u1>, **<#c1>** `<@u2>` a<@u3> @everyone");
+        assert_eq!(spans[1].mention, Some(Mention::User("u1".into())));
+        assert_eq!(spans[1].text, "<@u1>");
+        assert_eq!(spans[2], plain(", "));
+        assert!(spans[3].style.bold);
+        assert_eq!(spans[3].mention, Some(Mention::Channel("c1".into())));
+        assert!(spans[5].style.code && spans[5].mention.is_none());
+        assert_eq!(spans[6], plain(" a<@u3> "));
+        assert_eq!(spans[7].mention, Some(Mention::Everyone));
+    }
+
+    #[test]
+    fn test_custom_emoji_tokens() {
+        let spans = paragraph("nice <:ferris:e1><a:parrot:e2> `<:ferris:e1>`");
+        assert_eq!(spans[0], plain("nice "));
+        assert_eq!(spans[1].emoji.as_ref().map(|e| e.id.as_str()), Some("e1"));
+        assert_eq!(spans[1].text, "<:ferris:e1>");
+        assert!(spans[2].emoji.as_ref().is_some_and(|e| e.animated));
+        assert!(spans[4].style.code && spans[4].emoji.is_none());
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        assert_eq!(
+            parse("before\n```rust\nfn main() {}\n```\nafter"),
+            [
+                Block::Paragraph(vec![plain("before")]),
+                Block::Code {
+                    lang: Some("rust".into()),
+                    code: "fn main() {}".into()
+                },
+                Block::Paragraph(vec![plain("after")]),
+            ]
+        );
+        assert_eq!(
+            parse("```let **x** = 1;```"),
+            [Block::Code {
+                lang: None,
+                code: "let **x** = 1;".into()
+            }]
+        );
+        assert_eq!(
+            parse("```two words\nmore```"),
+            [Block::Code {
+                lang: None,
+                code: "two words\nmore".into()
+            }]
+        );
+        assert_eq!(parse("```\nunclosed"), [Block::Paragraph(vec![plain("```\nunclosed")])]);
+    }
+
+    #[test]
+    fn test_quotes() {
+        assert_eq!(
+            parse("> one\n> **two**\nthree"),
+            [
+                Block::Quote(vec![plain("one\n"), span("two", BOLD)]),
+                Block::Paragraph(vec![plain("three")]),
+            ]
+        );
+        assert_eq!(
+            parse("hi\n>>> all\nof this"),
+            [
+                Block::Paragraph(vec![plain("hi")]),
+                Block::Quote(vec![plain("all\nof this")]),
+            ]
+        );
+        assert_eq!(paragraph(">not a quote"), [plain(">not a quote")]);
+    }
+
+    #[test]
+    fn test_layout_ranges() {
+        let (text, ranges) = layout(&paragraph("a **b** c"));
+        assert_eq!(text, "a b c");
+        assert_eq!(ranges, [0..2, 2..3, 3..5]);
+    }
+
+    #[test]
+    fn test_links_to_preview() {
+        assert_eq!(
+            links("see https://a.io/x and [docs](https://b.io), again https://a.io/x\n> https://c.io"),
+            ["https://a.io/x", "https://b.io", "https://c.io"]
+        );
+        assert!(links("`https://a.io` ||https://b.io||\n```\nhttps://c.io\n```").is_empty());
+        assert_eq!(links("héllo wörld — https://a.io/é"), ["https://a.io/é"]);
+    }
+}
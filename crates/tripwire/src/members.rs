@@ -0,0 +1,371 @@
+//! Members — finding, sorting and moderating a server's members, for the
+//! Members screen of server settings.
+//!
+//! Moderators act only on members ranked below them, and assign only roles
+//! ranked below their own highest role; see `permissions`.
+
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::{MemberInfo, Role, Server, User};
+use crate::permissions::{self, Permissions};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How much of a banned member's recent history goes with them.
+pub const BAN_DELETE_WINDOWS: [(&str, Duration); 5] = [
+    ("Don't delete any", Duration::ZERO),
+    ("Previous hour", Duration::from_secs(60 * 60)),
+    ("Previous 24 hours", DAY),
+    ("Previous 3 days", Duration::from_secs(3 * 24 * 60 * 60)),
+    ("Previous 7 days", Duration::from_secs(7 * 24 * 60 * 60)),
+];
+
+/// How long a timeout lasts.
+pub const TIMEOUT_DURATIONS: [(&str, Duration); 6] = [
+    ("60 seconds", Duration::from_secs(60)),
+    ("5 minutes", Duration::from_secs(5 * 60)),
+    ("10 minutes", Duration::from_secs(10 * 60)),
+    ("1 hour", Duration::from_secs(60 * 60)),
+    ("1 day", DAY),
+    ("1 week", Duration::from_secs(7 * 24 * 60 * 60)),
+];
+
+/// What a moderator does to a member.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModerationAction {
+    /// Remove them; they can join again.
+    Kick,
+    /// Remove them for good, deleting what they posted in the last
+    /// `delete_messages`.
+    Ban { delete_messages: Duration },
+    /// Leave them only reading along until `until`.
+    Timeout { until: SystemTime },
+    RemoveTimeout,
+}
+
+impl ModerationAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ModerationAction::Kick => "Kick",
+            ModerationAction::Ban { .. } => "Ban",
+            ModerationAction::Timeout { .. } => "Timeout",
+            ModerationAction::RemoveTimeout => "Remove Timeout",
+        }
+    }
+
+    pub fn required(&self) -> Permissions {
+        match self {
+            ModerationAction::Kick => Permissions::KICK_MEMBERS,
+            ModerationAction::Ban { .. } => Permissions::BAN_MEMBERS,
+            ModerationAction::Timeout { .. } | ModerationAction::RemoveTimeout => Permissions::MODERATE_MEMBERS,
+        }
+    }
+}
+
+/// Whether `actor` may take `action` against `target`.
+pub fn can_moderate(server: &Server, actor: &str, target: &str, action: &ModerationAction) -> bool {
+    permissions::server_permissions(server, actor).contains(action.required())
+        && permissions::outranks(server, actor, target)
+}
+
+/// Whether `actor` may change `target`'s nickname: their own with Change
+/// Nickname, those of members below them with Manage Nicknames.
+pub fn can_change_nickname(server: &Server, actor: &str, target: &str) -> bool {
+    let granted = permissions::server_permissions(server, actor);
+    if actor == target {
+        granted.intersects(Permissions::CHANGE_NICKNAME | Permissions::MANAGE_NICKNAMES)
+    } else {
+        granted.contains(Permissions::MANAGE_NICKNAMES) && permissions::outranks(server, actor, target)
+    }
+}
+
+/// Whether `actor` may give `role_id` to, or take it from, `target`.
+pub fn can_assign_role(server: &Server, actor: &str, target: &str, role_id: &str) -> bool {
+    permissions::can_manage_role(server, actor, role_id)
+        && (actor == target || permissions::outranks(server, actor, target))
+}
+
+// ── Listing ─────────────────────────────────────────────────────────────────
+
+/// How recently members joined.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum JoinedWithin {
+    #[default]
+    Any,
+    Day,
+    Week,
+    Month,
+    /// Over a month ago, or unknown.
+    Earlier,
+}
+
+impl JoinedWithin {
+    pub const ALL: [JoinedWithin; 5] = [
+        JoinedWithin::Any,
+        JoinedWithin::Day,
+        JoinedWithin::Week,
+        JoinedWithin::Month,
+        JoinedWithin::Earlier,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            JoinedWithin::Any => "Any time",
+            JoinedWithin::Day => "Past day",
+            JoinedWithin::Week => "Past week",
+            JoinedWithin::Month => "Past month",
+            JoinedWithin::Earlier => "Over a month ago",
+        }
+    }
+
+    fn matches(self, joined_at: Option<SystemTime>, now: SystemTime) -> bool {
+        let age = joined_at.map(|joined_at| now.duration_since(joined_at).unwrap_or_default());
+        let within = |limit: Duration| age.is_some_and(|age| age <= limit);
+        match self {
+            JoinedWithin::Any => true,
+            JoinedWithin::Day => within(DAY),
+            JoinedWithin::Week => within(7 * DAY),
+            JoinedWithin::Month => within(30 * DAY),
+            JoinedWithin::Earlier => !within(30 * DAY),
+        }
+    }
+}
+
+/// Which members to list.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemberFilter {
+    /// Part of a username, tag or nickname, in any case.
+    pub query: String,
+    pub role_id: Option<String>,
+    pub joined: JoinedWithin,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MemberSort {
+    #[default]
+    Name,
+    Joined,
+    /// By highest role, highest first.
+    TopRole,
+}
+
+/// A member as listed, with their roles highest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberRow {
+    pub user: User,
+    pub info: MemberInfo,
+    pub roles: Vec<Role>,
+}
+
+impl MemberRow {
+    /// Their nickname, or else their username.
+    pub fn display_name(&self) -> &str {
+        self.info.nickname.as_deref().unwrap_or(&self.user.username)
+    }
+}
+
+/// `server`'s members matching `filter`, sorted by `sort`.
+pub fn member_rows(
+    server: &Server,
+    filter: &MemberFilter,
+    sort: MemberSort,
+    descending: bool,
+    now: SystemTime,
+) -> Vec<MemberRow> {
+    let query = filter.query.trim().to_lowercase();
+    let mut rows: Vec<MemberRow> = server
+        .members
+        .iter()
+        .map(|user| {
+            let held = server.member_roles.get(&user.id);
+            MemberRow {
+                user: user.clone(),
+                info: server.member_info.get(&user.id).cloned().unwrap_or_default(),
+                roles: server
+                    .roles
+                    .iter()
+                    .filter(|role| held.is_some_and(|held| held.contains(&role.id)))
+                    .cloned()
+                    .collect(),
+            }
+        })
+        .filter(|row| {
+            query.is_empty()
+                || row.user.tag().to_lowercase().contains(&query)
+                || row.info.nickname.as_ref().is_some_and(|nick| nick.to_lowercase().contains(&query))
+        })
+        .filter(|row| {
+            filter
+                .role_id
+                .as_ref()
+                .is_none_or(|role_id| row.roles.iter().any(|role| role.id == *role_id))
+        })
+        .filter(|row| filter.joined.matches(row.info.joined_at, now))
+        .collect();
+
+    sort_rows(&mut rows, &server.roles, sort, descending);
+    rows
+}
+
+/// Sort `rows` of a server with `roles`, highest ranked first.
+pub fn sort_rows(rows: &mut [MemberRow], roles: &[Role], sort: MemberSort, descending: bool) {
+    let rank = |row: &MemberRow| {
+        row.roles
+            .first()
+            .and_then(|top| roles.iter().position(|role| role.id == top.id))
+            .unwrap_or(usize::MAX)
+    };
+    rows.sort_by(|a, b| {
+        let by_name = a.display_name().to_lowercase().cmp(&b.display_name().to_lowercase());
+        match sort {
+            MemberSort::Name => by_name,
+            MemberSort::Joined => a.info.joined_at.cmp(&b.info.joined_at).then(by_name),
+            MemberSort::TopRole => rank(a).cmp(&rank(b)).then(by_name),
+        }
+    });
+    if descending {
+        rows.reverse();
+    }
+}
+
+/// How long ago something `age` old happened, roughly: "today", "3 days
+/// ago", "2 years ago".
+pub fn format_age(age: Duration) -> String {
+    let days = age.as_secs() / DAY.as_secs();
+    let (count, unit) = match days {
+        0 => return "today".to_string(),
+        1..30 => (days, "day"),
+        30..365 => (days / 30, "month"),
+        _ => (days / 365, "year"),
+    };
+    format!("{count} {unit}{} ago", if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UserStatus;
+
+    const NOW: Duration = Duration::from_secs(1_000 * 24 * 60 * 60);
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + NOW
+    }
+
+    fn role(id: &str, permissions: Permissions) -> Role {
+        Role {
+            id: id.to_string(),
+            name: id.to_string(),
+            color: "#99AAB5".to_string(),
+            permissions,
+            hoist: false,
+            mentionable: false,
+        }
+    }
+
+    /// `mod` moderates; `zed` (nicknamed "Ada") holds `member`; `amy` holds
+    /// nothing. They joined 100, 3 and 0 days ago.
+    fn server() -> Server {
+        let user = |id: &str| User {
+            id: id.to_string(),
+            username: id.to_string(),
+            discriminator: "0001".to_string(),
+            status: UserStatus::Online,
+        };
+        let info = |days: u32, nickname: Option<&str>| MemberInfo {
+            nickname: nickname.map(String::from),
+            joined_at: Some(now() - days * DAY),
+            timed_out_until: None,
+        };
+        Server {
+            id: "s".to_string(),
+            name: "Server".to_string(),
+            owner_id: None,
+            everyone_permissions: Permissions::default(),
+            categories: Vec::new(),
+            members: vec![user("mod"), user("zed"), user("amy")],
+            roles: vec![
+                role("mod", Permissions::KICK_MEMBERS | Permissions::MANAGE_NICKNAMES | Permissions::MANAGE_ROLES),
+                role("member", Permissions::empty()),
+            ],
+            member_roles: [("mod", "mod"), ("zed", "member")]
+                .into_iter()
+                .map(|(user, role)| (user.to_string(), vec![role.to_string()]))
+                .collect(),
+            member_info: [("mod", info(100, None)), ("zed", info(3, Some("Ada"))), ("amy", info(0, None))]
+                .into_iter()
+                .map(|(user, info)| (user.to_string(), info))
+                .collect(),
+            emojis: Vec::new(),
+        }
+    }
+
+    fn names(rows: &[MemberRow]) -> Vec<&str> {
+        rows.iter().map(|row| row.user.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filters_members() {
+        let server = server();
+        let list = |filter: MemberFilter| names(&member_rows(&server, &filter, MemberSort::Name, false, now())).join(",");
+        assert_eq!(list(MemberFilter::default()), "zed,amy,mod");
+        let query = |query: &str| MemberFilter {
+            query: query.to_string(),
+            ..MemberFilter::default()
+        };
+        assert_eq!(list(query("ADA")), "zed");
+        assert_eq!(list(query("#0001")), "zed,amy,mod");
+        assert_eq!(
+            list(MemberFilter {
+                role_id: Some("member".into()),
+                ..MemberFilter::default()
+            }),
+            "zed"
+        );
+        let joined = |joined| MemberFilter {
+            joined,
+            ..MemberFilter::default()
+        };
+        assert_eq!(list(joined(JoinedWithin::Week)), "zed,amy");
+        assert_eq!(list(joined(JoinedWithin::Day)), "amy");
+        assert_eq!(list(joined(JoinedWithin::Earlier)), "mod");
+    }
+
+    #[test]
+    fn test_sorts_members() {
+        let server = server();
+        let sorted = |sort, descending| names(&member_rows(&server, &MemberFilter::default(), sort, descending, now())).join(",");
+        assert_eq!(sorted(MemberSort::Name, true), "mod,amy,zed");
+        assert_eq!(sorted(MemberSort::Joined, false), "mod,zed,amy");
+        // Those without roles last.
+        assert_eq!(sorted(MemberSort::TopRole, false), "mod,zed,amy");
+    }
+
+    #[test]
+    fn test_formats_age() {
+        assert_eq!(format_age(DAY / 2), "today");
+        assert_eq!(format_age(DAY), "1 day ago");
+        assert_eq!(format_age(45 * DAY), "1 month ago");
+        assert_eq!(format_age(800 * DAY), "2 years ago");
+    }
+
+    #[test]
+    fn test_moderation_needs_rank_and_permission() {
+        let server = server();
+        assert!(can_moderate(&server, "mod", "zed", &ModerationAction::Kick));
+        assert!(!can_moderate(&server, "zed", "amy", &ModerationAction::Kick));
+        assert!(!can_moderate(&server, "mod", "zed", &ModerationAction::Ban { delete_messages: Duration::ZERO }));
+        assert!(!can_moderate(&server, "mod", "mod", &ModerationAction::Kick));
+
+        assert!(can_change_nickname(&server, "zed", "zed"));
+        assert!(can_change_nickname(&server, "mod", "amy"));
+        assert!(!can_change_nickname(&server, "zed", "amy"));
+
+        assert!(can_assign_role(&server, "mod", "amy", "member"));
+        assert!(!can_assign_role(&server, "mod", "amy", "mod"));
+        assert!(!can_assign_role(&server, "zed", "amy", "member"));
+    }
+}
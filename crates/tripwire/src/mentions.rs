@@ -0,0 +1,505 @@
+//! Mentions in message text. The composer shows `@name` and `#channel`;
+//! sent messages carry stable IDs instead — `<@user_id>`, `<@&role_id>`,
+//! `<#channel_id>` and `@everyone` — so they survive renames. Custom
+//! emoji work the same way: `:name:` becomes `<:name:id>`.
+
+use crate::emoji;
+use crate::models::CustomEmoji;
+
+/// Completions offered for one partial mention.
+const MAX_COMPLETIONS: usize = 10;
+
+/// A mention token in message text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mention {
+    User(String),
+    Role(String),
+    Channel(String),
+    Everyone,
+}
+
+/// Something that can be mentioned by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub id: String,
+    pub name: String,
+}
+
+/// A role, with the members holding it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleEntry {
+    pub id: String,
+    pub name: String,
+    pub members: Vec<String>,
+    /// Whether it can be mentioned here. Mentions of other roles still
+    /// read by name, they just aren't offered or recognized when typed.
+    pub mentionable: bool,
+}
+
+/// Who and what can be mentioned in the open conversation.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Directory {
+    pub users: Vec<Entry>,
+    pub roles: Vec<RoleEntry>,
+    pub channels: Vec<Entry>,
+    pub emojis: Vec<CustomEmoji>,
+    /// Whether `@everyone` is offered and notifies anyone. Without it, a
+    /// typed `@everyone` is sent escaped, as plain text.
+    pub can_mention_everyone: bool,
+}
+
+impl Directory {
+    /// How `mention` reads in a message, e.g. `@Alice` or `#general`.
+    /// `None` for users, roles and channels that aren't here.
+    pub fn display(&self, mention: &Mention) -> Option<String> {
+        let find = |entries: &[Entry], id: &str| entries.iter().find(|e| e.id == id).map(|e| e.name.clone());
+        match mention {
+            Mention::User(id) => find(&self.users, id).map(|name| format!("@{name}")),
+            Mention::Role(id) => self
+                .roles
+                .iter()
+                .find(|r| r.id == *id)
+                .map(|r| format!("@{}", r.name)),
+            Mention::Channel(id) => find(&self.channels, id).map(|name| format!("#{name}")),
+            Mention::Everyone => Some("@everyone".to_string()),
+        }
+    }
+
+    /// Users `mention` notifies.
+    fn recipients(&self, mention: &Mention) -> Vec<String> {
+        match mention {
+            Mention::User(id) => vec![id.clone()],
+            Mention::Role(id) => self
+                .roles
+                .iter()
+                .find(|r| r.id == *id)
+                .map(|r| r.members.clone())
+                .unwrap_or_default(),
+            Mention::Everyone if self.can_mention_everyone => {
+                self.users.iter().map(|u| u.id.clone()).collect()
+            }
+            Mention::Everyone => Vec::new(),
+            Mention::Channel(_) => Vec::new(),
+        }
+    }
+}
+
+/// One suggestion for a partial mention.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    /// What the list shows, e.g. `@Alice`.
+    pub label: String,
+    pub detail: Option<String>,
+    /// What replaces the partial mention.
+    pub insert: String,
+}
+
+/// The mention token at the start of `text`, and its length in bytes.
+pub fn parse_token(text: &str) -> Option<(Mention, usize)> {
+    if let Some(rest) = text.strip_prefix("@everyone") {
+        return (!rest.starts_with(is_name_char)).then_some((Mention::Everyone, "@everyone".len()));
+    }
+    let rest = text.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    let inner = &rest[..end];
+    let (mention, id): (fn(String) -> Mention, &str) = if let Some(id) = inner.strip_prefix("@&") {
+        (Mention::Role, id)
+    } else if let Some(id) = inner.strip_prefix('@') {
+        (Mention::User, id)
+    } else if let Some(id) = inner.strip_prefix('#') {
+        (Mention::Channel, id)
+    } else {
+        return None;
+    };
+    let valid = !id.is_empty() && id.chars().all(is_name_char);
+    valid.then(|| (mention(id.to_string()), end + 2))
+}
+
+/// Replace `@name`, `#channel` and custom `:emoji:` in composer text with
+/// ID tokens, and other `:shortcodes:` with their emoji, leaving names
+/// nobody has, and anything inside backticks, as typed.
+pub fn serialize(text: &str, directory: &Directory) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_code = false;
+    let mut ix = 0;
+    while ix < text.len() {
+        let rest = &text[ix..];
+        let c = rest.chars().next().unwrap_or_default();
+        if c == '`' {
+            in_code = !in_code;
+        } else if !in_code
+            && (c == '@' || c == '#')
+            && at_word_start(text, ix)
+            && let Some((token, len)) = resolve(&rest[1..], c, directory)
+        {
+            out.push_str(&token);
+            ix += 1 + len;
+            continue;
+        } else if !in_code
+            && !directory.can_mention_everyone
+            && at_word_start(text, ix)
+            && !text[..ix].ends_with('\\')
+            && parse_token(rest).is_some_and(|(mention, _)| mention == Mention::Everyone)
+        {
+            // Markdown reads the escaped `@` as a plain character.
+            out.push('\\');
+            out.push(c);
+            ix += 1;
+            continue;
+        } else if !in_code
+            && c == ':'
+            && let Some((token, len)) = resolve_emoji(&rest[1..], directory)
+        {
+            out.push_str(&token);
+            ix += 1 + len;
+            continue;
+        }
+        out.push(c);
+        ix += c.len_utf8();
+    }
+    out
+}
+
+/// `content` as plain text, e.g. for a desktop notification: mention tokens
+/// read as names and custom emoji as their `:name:`.
+pub fn plain_text(content: &str, directory: &Directory) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut ix = 0;
+    while ix < content.len() {
+        let rest = &content[ix..];
+        if let Some((mention, len)) = parse_token(rest) {
+            out.push_str(&directory.display(&mention).unwrap_or_else(|| {
+                match mention {
+                    Mention::Channel(_) => "#deleted-channel",
+                    _ => "@unknown",
+                }
+                .to_string()
+            }));
+            ix += len;
+        } else if let Some((emoji, len)) = emoji::parse_custom(rest) {
+            out.push_str(&format!(":{}:", emoji.name));
+            ix += len;
+        } else {
+            let c = rest.chars().next().unwrap_or_default();
+            out.push(c);
+            ix += c.len_utf8();
+        }
+    }
+    out
+}
+
+/// Users notified by the mention tokens in `content`, in order.
+pub fn mentioned_users(content: &str, directory: &Directory) -> Vec<String> {
+    let mut users: Vec<String> = Vec::new();
+    let mut in_code = false;
+    for (ix, c) in content.char_indices() {
+        if c == '`' {
+            in_code = !in_code;
+        }
+        if in_code || !(c == '<' || c == '@') || !at_word_start(content, ix) {
+            continue;
+        }
+        if let Some((mention, _)) = parse_token(&content[ix..]) {
+            for user in directory.recipients(&mention) {
+                if !users.contains(&user) {
+                    users.push(user);
+                }
+            }
+        }
+    }
+    users
+}
+
+/// The partial mention ending at the cursor, given the text before it: the
+/// byte offset of its trigger character, the trigger and what follows it.
+pub fn completion_query(before_cursor: &str) -> Option<(usize, char, &str)> {
+    let name_start = before_cursor
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_name_char(*c))
+        .last()
+        .map_or(before_cursor.len(), |(ix, _)| ix);
+    let trigger = before_cursor[..name_start]
+        .chars()
+        .next_back()
+        .filter(|c| matches!(c, '@' | '#' | ':'))?;
+    let trigger_ix = name_start - 1;
+    let query = &before_cursor[name_start..];
+    let in_code = before_cursor[..trigger_ix].matches('`').count() % 2 == 1;
+    let valid = at_word_start(before_cursor, trigger_ix)
+        && !in_code
+        // `re:` and `10:30` aren't emoji.
+        && (trigger != ':' || query.chars().count() >= 2);
+    valid.then_some((trigger_ix, trigger, query))
+}
+
+/// Suggestions for the partial mention `query` after `trigger`, names
+/// starting with it before those merely containing it.
+pub fn complete(directory: &Directory, trigger: char, query: &str) -> Vec<Completion> {
+    let query = query.to_lowercase();
+    let mut candidates: Vec<(usize, Completion)> = Vec::new();
+    let mut offer = |name: &str, completion: Completion| {
+        let name = name.to_lowercase();
+        let rank = if name.starts_with(&query) {
+            0
+        } else if name.contains(&query) {
+            1
+        } else {
+            return;
+        };
+        candidates.push((rank, completion));
+    };
+    match trigger {
+        '@' => {
+            for user in &directory.users {
+                offer(&user.name, Completion {
+                    label: format!("@{}", user.name),
+                    detail: None,
+                    insert: format!("@{} ", user.name),
+                });
+            }
+            for role in directory.roles.iter().filter(|r| r.mentionable) {
+                offer(&role.name, Completion {
+                    label: format!("@{}", role.name),
+                    detail: Some("Role".to_string()),
+                    insert: format!("@{} ", role.name),
+                });
+            }
+            if directory.can_mention_everyone {
+                offer("everyone", Completion {
+                    label: "@everyone".to_string(),
+                    detail: Some("Notify everyone here".to_string()),
+                    insert: "@everyone ".to_string(),
+                });
+            }
+        }
+        '#' => {
+            for channel in &directory.channels {
+                offer(&channel.name, Completion {
+                    label: format!("#{}", channel.name),
+                    detail: None,
+                    insert: format!("#{} ", channel.name),
+                });
+            }
+        }
+        ':' => {
+            for custom in &directory.emojis {
+                offer(&custom.name, Completion {
+                    label: format!(":{}:", custom.name),
+                    detail: Some("Server emoji".to_string()),
+                    insert: format!(":{}: ", custom.name),
+                });
+            }
+            // Already ranked, and only matches.
+            for (name, emoji) in emoji::search(&query) {
+                candidates.push((1, Completion {
+                    label: format!(":{name}:"),
+                    detail: Some(emoji.to_string()),
+                    insert: format!("{emoji} "),
+                }));
+            }
+        }
+        _ => {}
+    }
+    // Stable, so equally ranked candidates keep directory order.
+    candidates.sort_by_key(|(rank, _)| *rank);
+    candidates
+        .into_iter()
+        .take(MAX_COMPLETIONS)
+        .map(|(_, completion)| completion)
+        .collect()
+}
+
+/// The ID token for the longest name `text` starts with, and that name's
+/// length. Users win over roles of the same name.
+fn resolve(text: &str, trigger: char, directory: &Directory) -> Option<(String, usize)> {
+    let names: Vec<(&str, String)> = match trigger {
+        '@' => directory
+            .users
+            .iter()
+            .map(|u| (u.name.as_str(), format!("<@{}>", u.id)))
+            .chain(
+                directory
+                    .roles
+                    .iter()
+                    .filter(|r| r.mentionable)
+                    .map(|r| (r.name.as_str(), format!("<@&{}>", r.id))),
+            )
+            .chain(
+                directory
+                    .can_mention_everyone
+                    .then(|| ("everyone", "@everyone".to_string())),
+            )
+            .collect(),
+        _ => directory
+            .channels
+            .iter()
+            .map(|c| (c.name.as_str(), format!("<#{}>", c.id)))
+            .collect(),
+    };
+    let mut best: Option<(String, usize)> = None;
+    for (name, token) in names {
+        let matches = !name.is_empty()
+            && text.get(..name.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(name))
+            && !text[name.len()..].starts_with(is_name_char);
+        if matches && best.as_ref().is_none_or(|(_, len)| name.len() > *len) {
+            best = Some((token, name.len()));
+        }
+    }
+    best
+}
+
+/// The custom emoji token or emoji for the `name:` `text` starts with, and
+/// the length of that.
+fn resolve_emoji(text: &str, directory: &Directory) -> Option<(String, usize)> {
+    let (name, _) = text.split_once(':')?;
+    let emoji = match directory.emojis.iter().find(|e| e.name == name) {
+        Some(custom) => custom.to_ref().token(),
+        None => emoji::by_shortcode(name)?.to_string(),
+    };
+    Some((emoji, name.len() + 1))
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Whether `ix` starts a word, so `me@example.com` isn't a mention.
+fn at_word_start(text: &str, ix: usize) -> bool {
+    !text[..ix].ends_with(|c: char| is_name_char(c) || c == '@' || c == '#')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, name: &str) -> Entry {
+        Entry {
+            id: id.into(),
+            name: name.into(),
+        }
+    }
+
+    fn directory() -> Directory {
+        Directory {
+            users: vec![entry("u1", "Alice"), entry("u2", "Al"), entry("u3", "Bob")],
+            roles: vec![
+                RoleEntry {
+                    id: "r1".into(),
+                    name: "Core Team".into(),
+                    members: vec!["u2".into(), "u3".into()],
+                    mentionable: true,
+                },
+                RoleEntry {
+                    id: "r2".into(),
+                    name: "Quiet".into(),
+                    members: vec!["u1".into()],
+                    mentionable: false,
+                },
+            ],
+            channels: vec![entry("c1", "general"), entry("c2", "general-dev")],
+            emojis: vec![CustomEmoji {
+                id: "e1".into(),
+                name: "ferris".into(),
+                url: "https://cdn.example/e1.png".into(),
+                animated: false,
+            }],
+            can_mention_everyone: true,
+        }
+    }
+
+    #[test]
+    fn test_serializes_names_as_ids() {
+        let dir = directory();
+        assert_eq!(
+            serialize("hi @alice and @Al, see #general-dev", &dir),
+            "hi <@u1> and <@u2>, see <#c2>"
+        );
+        assert_eq!(serialize("ping @Core Team now", &dir), "ping <@&r1> now");
+        assert_eq!(serialize("@everyone @nobody", &dir), "@everyone @nobody");
+        // Not at a word start, or inside code.
+        assert_eq!(serialize("me@alice.dev `@Bob`", &dir), "me@alice.dev `@Bob`");
+        assert_eq!(serialize("@Alicex", &dir), "@Alicex");
+        assert_eq!(
+            serialize("yay:ferris: :crab: :nope: `:ferris:`", &dir),
+            "yay<:ferris:e1> 🦀 :nope: `:ferris:`"
+        );
+    }
+
+    #[test]
+    fn test_collects_mentioned_users() {
+        let dir = directory();
+        assert_eq!(mentioned_users("<@u1> <@u1> <@&r1>", &dir), ["u1", "u2", "u3"]);
+        assert_eq!(mentioned_users("@everyone", &dir), ["u1", "u2", "u3"]);
+        assert!(mentioned_users("`<@u1>` <#c1> a<@u2>", &dir).is_empty());
+    }
+
+    #[test]
+    fn test_plain_text_reads_tokens_as_names() {
+        let dir = directory();
+        assert_eq!(
+            plain_text("<@u1> <@&r1> in <#c1> <#c9> <@u9> <:ferris:e1>", &dir),
+            "@Alice @Core Team in #general #deleted-channel @unknown :ferris:"
+        );
+    }
+
+    #[test]
+    fn test_parses_tokens() {
+        assert_eq!(parse_token("<@u1> hi"), Some((Mention::User("u1".into()), 5)));
+        assert_eq!(parse_token("<@&r1>"), Some((Mention::Role("r1".into()), 6)));
+        assert_eq!(parse_token("<#c1>"), Some((Mention::Channel("c1".into()), 5)));
+        assert_eq!(parse_token("@everyone!"), Some((Mention::Everyone, 9)));
+        assert_eq!(parse_token("@everyones"), None);
+        assert_eq!(parse_token("<@>"), None);
+        assert_eq!(parse_token("<@u 1>"), None);
+        assert_eq!(parse_token("<b>"), None);
+    }
+
+    #[test]
+    fn test_finds_partial_mention_before_cursor() {
+        assert_eq!(completion_query("hey @al"), Some((4, '@', "al")));
+        assert_eq!(completion_query("@"), Some((0, '@', "")));
+        assert_eq!(completion_query("in #gen"), Some((3, '#', "gen")));
+        assert_eq!(completion_query("nice :th"), Some((5, ':', "th")));
+        assert_eq!(completion_query("re:x"), None);
+        assert_eq!(completion_query("mail me@al"), None);
+        assert_eq!(completion_query("`@al"), None);
+        assert_eq!(completion_query("@al "), None);
+    }
+
+    #[test]
+    fn test_completes_prefix_matches_first() {
+        let dir = directory();
+        let labels = |trigger, query| -> Vec<String> {
+            complete(&dir, trigger, query).into_iter().map(|c| c.label).collect()
+        };
+        assert_eq!(labels('@', "al"), ["@Alice", "@Al"]);
+        assert_eq!(labels('@', "e"), ["@everyone", "@Alice", "@Core Team"]);
+        assert_eq!(labels('#', "dev"), ["#general-dev"]);
+        assert_eq!(complete(&dir, '@', "co")[0].insert, "@Core Team ");
+        assert_eq!(labels(':', "fer")[0], ":ferris:");
+        assert_eq!(complete(&dir, ':', "fer")[0].insert, ":ferris: ");
+        assert_eq!(complete(&dir, ':', "thumbsu")[0].insert, "👍 ");
+    }
+
+    #[test]
+    fn test_only_mentionable_roles_are_offered() {
+        let dir = directory();
+        assert!(complete(&dir, '@', "qui").is_empty());
+        assert_eq!(serialize("@Quiet please", &dir), "@Quiet please");
+        assert_eq!(dir.display(&Mention::Role("r2".into())).as_deref(), Some("@Quiet"));
+    }
+
+    #[test]
+    fn test_everyone_needs_permission() {
+        let dir = Directory {
+            can_mention_everyone: false,
+            ..directory()
+        };
+        assert!(complete(&dir, '@', "every").is_empty());
+        let content = serialize("@everyone look `@everyone` \\@everyone", &dir);
+        assert_eq!(content, "\\@everyone look `@everyone` \\@everyone");
+        assert!(mentioned_users(&content, &dir).is_empty());
+        assert!(mentioned_users("@everyone", &dir).is_empty());
+    }
+}
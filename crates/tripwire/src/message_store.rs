@@ -0,0 +1,860 @@
+//! On-disk message cache.
+//!
+//! Each signed-in account gets its own SQLite database under the data
+//! directory, holding the history of every conversation it has opened plus
+//! the last server and DM lists. It's read before the network answers, so
+//! scrollback shows up instantly and stays readable offline.
+//!
+//! The cache is disposable: a database that can't be opened or migrated is
+//! deleted and started over. [`EvictionPolicy`] keeps it from growing
+//! without bound.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context as _, Result, bail};
+use rusqlite::{Connection, OptionalExtension as _, params};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::models::Message;
+use crate::outbox::OutboxEntry;
+use crate::read_state::ReadState;
+use crate::search::SearchQuery;
+
+const DB_FILE: &str = "messages.sqlite3";
+
+/// Schema changes, applied in order. `PRAGMA user_version` records how many
+/// have run; append new steps, never edit old ones.
+const MIGRATIONS: &[&str] = &[
+    // 1: message history, last use per conversation, and list snapshots.
+    "CREATE TABLE messages (
+        conversation_id TEXT NOT NULL,
+        id TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        body TEXT NOT NULL,
+        PRIMARY KEY (conversation_id, id)
+    );
+    CREATE INDEX messages_by_time ON messages (conversation_id, created_at);
+    CREATE TABLE conversations (
+        id TEXT PRIMARY KEY,
+        last_used INTEGER NOT NULL
+    );
+    CREATE TABLE snapshots (
+        key TEXT PRIMARY KEY,
+        body TEXT NOT NULL
+    );",
+    // 2: messages waiting to be sent.
+    "CREATE TABLE outbox (
+        nonce TEXT PRIMARY KEY,
+        body TEXT NOT NULL
+    );",
+    // 3: full-text index over message content, keyed by `messages.rowid`
+    // and kept in sync by triggers.
+    "CREATE VIRTUAL TABLE message_text USING fts5(
+        content,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+    INSERT INTO message_text (rowid, content)
+        SELECT rowid, json_extract(body, '$.content') FROM messages;
+    CREATE TRIGGER message_text_insert AFTER INSERT ON messages BEGIN
+        INSERT INTO message_text (rowid, content)
+            VALUES (new.rowid, json_extract(new.body, '$.content'));
+    END;
+    CREATE TRIGGER message_text_delete AFTER DELETE ON messages BEGIN
+        DELETE FROM message_text WHERE rowid = old.rowid;
+    END;
+    CREATE TRIGGER message_text_update AFTER UPDATE OF body ON messages BEGIN
+        UPDATE message_text SET content = json_extract(new.body, '$.content')
+            WHERE rowid = new.rowid;
+    END;",
+    // 4: how far each conversation has been read.
+    "CREATE TABLE read_states (
+        conversation_id TEXT PRIMARY KEY,
+        body TEXT NOT NULL
+    );",
+];
+
+/// When cached history gets thrown away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvictionPolicy {
+    /// Conversations not opened for this long are dropped.
+    pub max_age: Duration,
+    /// Total size of cached messages. Past it, the oldest messages of the
+    /// least recently used conversations go first.
+    pub max_bytes: u64,
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(30 * 24 * 60 * 60),
+            max_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Message cache of a single account.
+pub struct MessageStore {
+    conn: Connection,
+    /// Id of the account this cache belongs to.
+    user_id: String,
+}
+
+impl MessageStore {
+    /// Directory holding `user_id`'s cache inside `data_dir`.
+    pub fn account_dir(data_dir: &Path, user_id: &str) -> PathBuf {
+        data_dir.join("accounts").join(file_name_for(user_id))
+    }
+
+    /// Open (or create) `user_id`'s cache, starting over if the existing one
+    /// is unusable, and evict what `policy` no longer allows.
+    pub fn open_for_account(data_dir: &Path, user_id: &str, policy: EvictionPolicy) -> Result<Self> {
+        let dir = Self::account_dir(data_dir, user_id);
+        fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        let path = dir.join(DB_FILE);
+        let store = match Self::open(&path, user_id) {
+            Ok(store) => store,
+            Err(err) => {
+                eprintln!("Discarding message cache {}: {err:#}", path.display());
+                Self::remove_files(&path)?;
+                Self::open(&path, user_id)?
+            }
+        };
+        if let Err(err) = store.evict(policy, SystemTime::now()) {
+            eprintln!("Failed to evict cached messages: {err:#}");
+        }
+        Ok(store)
+    }
+
+    /// Delete `user_id`'s cache, e.g. when the account signs out.
+    pub fn delete_for_account(data_dir: &Path, user_id: &str) -> Result<()> {
+        let dir = Self::account_dir(data_dir, user_id);
+        match fs::remove_dir_all(&dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("removing {}", dir.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn open(path: &Path, user_id: &str) -> Result<Self> {
+        let mut conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        migrate(&mut conn)?;
+        Ok(Self {
+            conn,
+            user_id: user_id.to_string(),
+        })
+    }
+
+    fn remove_files(path: &Path) -> Result<()> {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.as_os_str().to_owned();
+            file.push(suffix);
+            match fs::remove_file(&file) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    // ── Messages ────────────────────────────────────────────────────────────
+
+    /// The newest `limit` cached messages of a conversation (a channel, DM or
+    /// thread id), oldest first.
+    pub fn load(&self, conversation_id: &str, limit: usize) -> Result<Vec<Message>> {
+        let mut statement = self.conn.prepare_cached(
+            "SELECT body FROM (
+                SELECT body, created_at, rowid FROM messages
+                WHERE conversation_id = ?1
+                ORDER BY created_at DESC, rowid DESC
+                LIMIT ?2
+            ) ORDER BY created_at, rowid",
+        )?;
+        let messages = statement
+            .query_map(params![conversation_id, limit as i64], |row| {
+                row.get::<_, String>(0)
+            })?
+            .map(|body| Ok(serde_json::from_str(&body?)?))
+            .collect::<Result<Vec<Message>>>()?;
+        if !messages.is_empty() {
+            self.touch(conversation_id, SystemTime::now())?;
+        }
+        Ok(messages)
+    }
+
+    /// Insert or update `messages`.
+    pub fn save(&mut self, conversation_id: &str, messages: &[Message]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        insert_messages(&tx, conversation_id, messages)?;
+        tx.commit()?;
+        self.touch(conversation_id, SystemTime::now())
+    }
+
+    /// Make `page` — the newest messages, fresh from the server — the cached
+    /// history over the time it spans, dropping cached messages in that range
+    /// the server no longer has. See [`merge_latest`].
+    pub fn replace_latest(&mut self, conversation_id: &str, page: &[Message], complete: bool) -> Result<()> {
+        let (since, until) = page_span(page, complete);
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "DELETE FROM messages
+             WHERE conversation_id = ?1 AND created_at >= ?2 AND created_at <= ?3",
+            params![
+                conversation_id,
+                since.map_or(i64::MIN, unix_millis),
+                until.map_or(i64::MAX, unix_millis)
+            ],
+        )?;
+        insert_messages(&tx, conversation_id, page)?;
+        tx.commit()?;
+        self.touch(conversation_id, SystemTime::now())
+    }
+
+    pub fn remove(&self, conversation_id: &str, message_id: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM messages WHERE conversation_id = ?1 AND id = ?2",
+            params![conversation_id, message_id],
+        )?;
+        Ok(())
+    }
+
+    fn touch(&self, conversation_id: &str, now: SystemTime) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO conversations (id, last_used) VALUES (?1, ?2)
+             ON CONFLICT (id) DO UPDATE SET last_used = excluded.last_used",
+            params![conversation_id, unix_millis(now)],
+        )?;
+        Ok(())
+    }
+
+    /// Up to `limit` cached messages older than `message_id`, oldest first.
+    /// Empty if the message isn't cached.
+    pub fn load_before(&self, conversation_id: &str, message_id: &str, limit: usize) -> Result<Vec<Message>> {
+        let mut statement = self.conn.prepare_cached(
+            "WITH anchor AS (
+                SELECT created_at, rowid FROM messages WHERE conversation_id = ?1 AND id = ?2
+            )
+            SELECT body FROM (
+                SELECT m.body, m.created_at, m.rowid FROM messages m, anchor
+                WHERE m.conversation_id = ?1 AND (m.created_at, m.rowid) < (anchor.created_at, anchor.rowid)
+                ORDER BY m.created_at DESC, m.rowid DESC LIMIT ?3
+            ) ORDER BY created_at, rowid",
+        )?;
+        statement
+            .query_map(params![conversation_id, message_id, limit as i64], |row| {
+                row.get::<_, String>(0)
+            })?
+            .map(|body| Ok(serde_json::from_str(&body?)?))
+            .collect()
+    }
+
+    /// `message_id` with up to `context` cached messages on either side,
+    /// oldest first. Empty if the message isn't cached.
+    pub fn load_around(&self, conversation_id: &str, message_id: &str, context: usize) -> Result<Vec<Message>> {
+        let mut statement = self.conn.prepare_cached(
+            "WITH hit AS (
+                SELECT created_at, rowid FROM messages WHERE conversation_id = ?1 AND id = ?2
+            )
+            SELECT body FROM (
+                SELECT * FROM (
+                    SELECT m.body, m.created_at, m.rowid FROM messages m, hit
+                    WHERE m.conversation_id = ?1 AND (m.created_at, m.rowid) < (hit.created_at, hit.rowid)
+                    ORDER BY m.created_at DESC, m.rowid DESC LIMIT ?3
+                )
+                UNION ALL
+                SELECT * FROM (
+                    SELECT m.body, m.created_at, m.rowid FROM messages m, hit
+                    WHERE m.conversation_id = ?1 AND (m.created_at, m.rowid) >= (hit.created_at, hit.rowid)
+                    ORDER BY m.created_at, m.rowid LIMIT ?3 + 1
+                )
+            ) ORDER BY created_at, rowid",
+        )?;
+        statement
+            .query_map(params![conversation_id, message_id, context as i64], |row| {
+                row.get::<_, String>(0)
+            })?
+            .map(|body| Ok(serde_json::from_str(&body?)?))
+            .collect()
+    }
+
+    // ── Search ──────────────────────────────────────────────────────────────
+
+    /// Cached messages matching `query`'s text and dates that `accept` also
+    /// lets through, newest first, with the conversation each belongs to.
+    /// Stops after `limit` hits.
+    pub fn search(
+        &self,
+        query: &SearchQuery,
+        mut accept: impl FnMut(&str, &Message) -> bool,
+        limit: usize,
+    ) -> Result<Vec<(String, Message)>> {
+        let since = query.after.map_or(i64::MIN, unix_millis);
+        let until = query.before.map_or(i64::MAX, unix_millis);
+        let mut statement;
+        let mut rows = match query.fts_expression() {
+            Some(text) => {
+                statement = self.conn.prepare_cached(
+                    "SELECT m.conversation_id, m.body
+                     FROM message_text JOIN messages m ON m.rowid = message_text.rowid
+                     WHERE message_text MATCH ?1 AND m.created_at >= ?2 AND m.created_at < ?3
+                     ORDER BY m.created_at DESC, m.rowid DESC",
+                )?;
+                statement.query(params![text, since, until])?
+            }
+            None => {
+                statement = self.conn.prepare_cached(
+                    "SELECT conversation_id, body FROM messages
+                     WHERE created_at >= ?1 AND created_at < ?2
+                     ORDER BY created_at DESC, rowid DESC",
+                )?;
+                statement.query(params![since, until])?
+            }
+        };
+
+        let mut hits = Vec::new();
+        while hits.len() < limit
+            && let Some(row) = rows.next()?
+        {
+            let conversation_id: String = row.get(0)?;
+            let message: Message = serde_json::from_str(&row.get::<_, String>(1)?)?;
+            if accept(&conversation_id, &message) {
+                hits.push((conversation_id, message));
+            }
+        }
+        Ok(hits)
+    }
+
+    // ── Outbox ──────────────────────────────────────────────────────────────
+
+    /// Unsent messages, in the order they were queued.
+    pub fn load_outbox(&self) -> Result<Vec<OutboxEntry>> {
+        let mut statement = self
+            .conn
+            .prepare_cached("SELECT body FROM outbox ORDER BY rowid")?;
+        statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|body| Ok(serde_json::from_str(&body?)?))
+            .collect()
+    }
+
+    /// Insert or update an outbox entry, keeping its place in the queue.
+    pub fn save_outbox_entry(&self, entry: &OutboxEntry) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO outbox (nonce, body) VALUES (?1, ?2)
+             ON CONFLICT (nonce) DO UPDATE SET body = excluded.body",
+            params![entry.nonce, serde_json::to_string(entry)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_outbox_entry(&self, nonce: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM outbox WHERE nonce = ?1", [nonce])?;
+        Ok(())
+    }
+
+    // ── Read state ──────────────────────────────────────────────────────────
+
+    pub fn load_read_states(&self) -> Result<Vec<ReadState>> {
+        let mut statement = self
+            .conn
+            .prepare_cached("SELECT body FROM read_states")?;
+        statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|body| Ok(serde_json::from_str(&body?)?))
+            .collect()
+    }
+
+    pub fn save_read_state(&self, state: &ReadState) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO read_states (conversation_id, body) VALUES (?1, ?2)
+             ON CONFLICT (conversation_id) DO UPDATE SET body = excluded.body",
+            params![state.conversation_id, serde_json::to_string(state)?],
+        )?;
+        Ok(())
+    }
+
+    // ── Snapshots ───────────────────────────────────────────────────────────
+
+    /// The value last stored under `key` with [`Self::save_snapshot`].
+    pub fn load_snapshot<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let body: Option<String> = self
+            .conn
+            .query_row("SELECT body FROM snapshots WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        body.map(|body| Ok(serde_json::from_str(&body)?))
+            .transpose()
+    }
+
+    /// Keep a copy of `value`, e.g. the server list, for offline startup.
+    pub fn save_snapshot<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO snapshots (key, body) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET body = excluded.body",
+            params![key, serde_json::to_string(value)?],
+        )?;
+        Ok(())
+    }
+
+    // ── Eviction ────────────────────────────────────────────────────────────
+
+    /// Drop what `policy` no longer allows as of `now`. Returns how many
+    /// messages were removed.
+    pub fn evict(&self, policy: EvictionPolicy, now: SystemTime) -> Result<usize> {
+        let cutoff = unix_millis(now.checked_sub(policy.max_age).unwrap_or(UNIX_EPOCH));
+        let mut removed = self.conn.execute(
+            "DELETE FROM messages WHERE conversation_id IN
+                (SELECT id FROM conversations WHERE last_used < ?1)",
+            [cutoff],
+        )?;
+
+        let total: i64 = self.conn.query_row(
+            "SELECT COALESCE(SUM(LENGTH(CAST(body AS BLOB))), 0) FROM messages",
+            [],
+            |row| row.get(0),
+        )?;
+        let excess = total - policy.max_bytes.min(i64::MAX as u64) as i64;
+        if excess > 0 {
+            // Walk messages from least to most valuable, deleting until what
+            // came before each one covers the excess.
+            removed += self.conn.execute(
+                "DELETE FROM messages WHERE rowid IN (
+                    SELECT rowid FROM (
+                        SELECT m.rowid AS rowid,
+                            SUM(LENGTH(CAST(m.body AS BLOB))) OVER (
+                                ORDER BY COALESCE(c.last_used, 0), m.created_at, m.rowid
+                            ) - LENGTH(CAST(m.body AS BLOB)) AS before
+                        FROM messages m LEFT JOIN conversations c ON c.id = m.conversation_id
+                    ) WHERE before < ?1
+                )",
+                [excess],
+            )?;
+        }
+
+        self.conn.execute(
+            "DELETE FROM conversations WHERE id NOT IN (SELECT conversation_id FROM messages)",
+            [],
+        )?;
+        Ok(removed)
+    }
+}
+
+/// Fold `page`, the newest history fresh from the server, into `cached`:
+/// cached messages from the time `page` spans are replaced by it, older ones
+/// are kept for scrollback and newer ones (arrived while it loaded) stay at
+/// the end. A `complete` page is the whole history, so nothing older stays.
+pub fn merge_latest(cached: Vec<Message>, page: Vec<Message>, complete: bool) -> Vec<Message> {
+    let (since, until) = page_span(&page, complete);
+    let (mut older, mut newer) = (Vec::new(), Vec::new());
+    for message in cached {
+        if page.iter().any(|m| m.id == message.id) {
+            continue;
+        }
+        if since.is_some_and(|since| message.created_at < since) {
+            older.push(message);
+        } else if until.is_some_and(|until| message.created_at > until) {
+            newer.push(message);
+        }
+    }
+    older.extend(page);
+    older.extend(newer);
+    older
+}
+
+/// Time range a page of history covers. `None` bounds are open: a complete
+/// page reaches back to the beginning, and an empty one covers everything.
+fn page_span(page: &[Message], complete: bool) -> (Option<SystemTime>, Option<SystemTime>) {
+    let since = page.iter().map(|m| m.created_at).min().filter(|_| !complete);
+    let until = page.iter().map(|m| m.created_at).max();
+    (since, until)
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        bail!("cache schema version {version} is newer than this build supports");
+    }
+    for (ix, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)
+            .with_context(|| format!("applying cache migration {}", ix + 1))?;
+        tx.pragma_update(None, "user_version", ix + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn insert_messages(conn: &Connection, conversation_id: &str, messages: &[Message]) -> Result<()> {
+    let mut statement = conn.prepare_cached(
+        "INSERT INTO messages (conversation_id, id, created_at, body) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (conversation_id, id) DO UPDATE SET body = excluded.body",
+    )?;
+    for message in messages {
+        statement.execute(params![
+            conversation_id,
+            message.id,
+            unix_millis(message.created_at),
+            serde_json::to_string(message)?,
+        ])?;
+    }
+    Ok(())
+}
+
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis().min(i64::MAX as u128) as i64)
+        .unwrap_or(0)
+}
+
+/// A directory name that's safe on every platform for an arbitrary id.
+fn file_name_for(id: &str) -> String {
+    if !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        id.to_string()
+    } else {
+        let hex: String = id.bytes().map(|b| format!("{b:02x}")).collect();
+        format!("x{hex}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::backend::{MessageDraft, MessageTarget};
+    use crate::models::{User, UserStatus};
+
+    fn message(id: &str, secs: u64, content: &str) -> Message {
+        Message {
+            id: id.into(),
+            author: User {
+                id: "u1".into(),
+                username: "alice".into(),
+                discriminator: "0001".into(),
+                status: UserStatus::Online,
+            },
+            content: content.into(),
+            timestamp: String::new(),
+            edited: false,
+            edited_timestamp: None,
+            revisions: Vec::new(),
+            attachments: Vec::new(),
+            embeds: Vec::new(),
+            reactions: HashMap::new(),
+            reply_to: None,
+            mentioned_users: Vec::new(),
+            pinned: false,
+            thread_id: None,
+            thread_count: 0,
+            created_at: UNIX_EPOCH + Duration::from_secs(secs),
+        }
+    }
+
+    fn ids(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    fn open(dir: &Path) -> MessageStore {
+        MessageStore::open_for_account(dir, "u1", EvictionPolicy::default()).unwrap()
+    }
+
+    #[test]
+    fn test_history_survives_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        store
+            .save("c1", &[message("m2", 20, "b"), message("m1", 10, "a")])
+            .unwrap();
+        store.save("c1", &[message("m3", 30, "c")]).unwrap();
+        store.save("c2", &[message("other", 15, "x")]).unwrap();
+        drop(store);
+
+        let store = open(dir.path());
+        assert_eq!(ids(&store.load("c1", 50).unwrap()), ["m1", "m2", "m3"]);
+        assert_eq!(ids(&store.load("c1", 2).unwrap()), ["m2", "m3"]);
+        assert!(store.load("c3", 50).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_save_updates_and_remove_deletes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        store.save("c1", &[message("m1", 10, "before")]).unwrap();
+        store.save("c1", &[message("m1", 10, "after")]).unwrap();
+        assert_eq!(store.load("c1", 50).unwrap()[0].content, "after");
+
+        store.remove("c1", "m1").unwrap();
+        assert!(store.load("c1", 50).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_replace_latest_drops_messages_deleted_upstream() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        let cached: Vec<_> = (1..=4).map(|i| message(&format!("m{i}"), i * 10, "")).collect();
+        store.save("c1", &cached).unwrap();
+
+        // m3 was deleted while we were away, m5 is new.
+        let page = [message("m2", 20, ""), message("m4", 40, ""), message("m5", 50, "")];
+        store.replace_latest("c1", &page, false).unwrap();
+        assert_eq!(ids(&store.load("c1", 50).unwrap()), ["m1", "m2", "m4", "m5"]);
+
+        // Messages newer than the page aren't touched.
+        store.save("c1", &[message("m6", 60, "")]).unwrap();
+        store.replace_latest("c1", &page[1..], true).unwrap();
+        assert_eq!(ids(&store.load("c1", 50).unwrap()), ["m4", "m5", "m6"]);
+    }
+
+    #[test]
+    fn test_merge_latest() {
+        let cached: Vec<_> = [1, 2, 3, 6]
+            .iter()
+            .map(|i| message(&format!("m{i}"), i * 10, "cached"))
+            .collect();
+        let page = vec![message("m2", 20, "fresh"), message("m4", 40, "fresh")];
+
+        let merged = merge_latest(cached.clone(), page.clone(), false);
+        assert_eq!(ids(&merged), ["m1", "m2", "m4", "m6"]);
+        assert_eq!(merged[1].content, "fresh");
+
+        let merged = merge_latest(cached, page, true);
+        assert_eq!(ids(&merged), ["m2", "m4", "m6"]);
+    }
+
+    #[test]
+    fn test_snapshots_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(dir.path());
+        assert_eq!(store.load_snapshot::<Vec<String>>("servers").unwrap(), None);
+        store.save_snapshot("servers", &vec!["a", "b"]).unwrap();
+        store.save_snapshot("servers", &vec!["c"]).unwrap();
+        assert_eq!(
+            store.load_snapshot::<Vec<String>>("servers").unwrap(),
+            Some(vec!["c".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_outbox_persists_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(dir.path());
+        let entry = |nonce: &str| {
+            OutboxEntry::new(
+                MessageTarget::Channel("c1".into()),
+                MessageDraft::default(),
+                message(nonce, 10, "queued"),
+            )
+        };
+        let mut first = entry("n1");
+        store.save_outbox_entry(&first).unwrap();
+        store.save_outbox_entry(&entry("n2")).unwrap();
+        first.attempts = 3;
+        store.save_outbox_entry(&first).unwrap();
+        drop(store);
+
+        let store = open(dir.path());
+        let entries = store.load_outbox().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].nonce, "n1");
+        assert_eq!(entries[0].attempts, 3);
+        assert_eq!(entries[1].nonce, "n2");
+        store.remove_outbox_entry("n1").unwrap();
+        assert_eq!(store.load_outbox().unwrap()[0].nonce, "n2");
+    }
+
+    #[test]
+    fn test_read_states_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = open(dir.path());
+        let state = |conversation_id: &str, last_read: &str, unread| ReadState {
+            conversation_id: conversation_id.into(),
+            last_read: Some(last_read.into()),
+            unread,
+            mentions: 0,
+        };
+        store.save_read_state(&state("c1", "m1", 3)).unwrap();
+        store.save_read_state(&state("c1", "m4", 0)).unwrap();
+        store.save_read_state(&state("c2", "m2", 1)).unwrap();
+        drop(store);
+
+        let store = open(dir.path());
+        let mut states = store.load_read_states().unwrap();
+        states.sort_by(|a, b| a.conversation_id.cmp(&b.conversation_id));
+        assert_eq!(states, [state("c1", "m4", 0), state("c2", "m2", 1)]);
+    }
+
+    #[test]
+    fn test_search_follows_cached_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        store
+            .save(
+                "c1",
+                &[
+                    message("m1", 10, "Deploying the new build"),
+                    message("m2", 20, "lunch?"),
+                    message("m3", 30, "Déploy finished"),
+                ],
+            )
+            .unwrap();
+        store.save("c2", &[message("m4", 40, "deploy again")]).unwrap();
+        let search = |store: &MessageStore, input: &str| -> Vec<String> {
+            let query = SearchQuery::parse(input).unwrap();
+            store
+                .search(&query, |_, _| true, 10)
+                .unwrap()
+                .into_iter()
+                .map(|(conversation, m)| format!("{conversation}/{}", m.id))
+                .collect()
+        };
+
+        assert_eq!(search(&store, "deploy"), ["c2/m4", "c1/m3", "c1/m1"]);
+        assert_eq!(search(&store, "\"new build\""), ["c1/m1"]);
+
+        store.save("c1", &[message("m2", 20, "deploy after lunch")]).unwrap();
+        store.remove("c2", "m4").unwrap();
+        assert_eq!(search(&store, "DEPLOY"), ["c1/m3", "c1/m2", "c1/m1"]);
+
+        let query = SearchQuery::parse("deploy").unwrap();
+        let hits = store.search(&query, |conversation, m| conversation == "c1" && m.id != "m3", 1).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1.id, "m2");
+
+        let query = SearchQuery {
+            after: Some(UNIX_EPOCH + Duration::from_secs(15)),
+            before: Some(UNIX_EPOCH + Duration::from_secs(30)),
+            ..Default::default()
+        };
+        let hits = store.search(&query, |_, _| true, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].1.id, "m2");
+    }
+
+    #[test]
+    fn test_loads_context_around_a_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        let messages: Vec<Message> = (0..10)
+            .map(|i| message(&format!("m{i}"), i * 10, ""))
+            .collect();
+        store.save("c1", &messages).unwrap();
+
+        assert_eq!(ids(&store.load_around("c1", "m5", 2).unwrap()), ["m3", "m4", "m5", "m6", "m7"]);
+        assert_eq!(ids(&store.load_around("c1", "m0", 2).unwrap()), ["m0", "m1", "m2"]);
+        assert!(store.load_around("c1", "missing", 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_loads_pages_before_a_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        let messages: Vec<Message> = (0..10)
+            .map(|i| message(&format!("m{i}"), i * 10, ""))
+            .collect();
+        store.save("c1", &messages).unwrap();
+
+        assert_eq!(ids(&store.load_before("c1", "m5", 3).unwrap()), ["m2", "m3", "m4"]);
+        assert_eq!(ids(&store.load_before("c1", "m1", 3).unwrap()), ["m0"]);
+        assert!(store.load_before("c1", "m0", 3).unwrap().is_empty());
+        assert!(store.load_before("c1", "missing", 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_evicts_stale_conversations() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        store.save("old", &[message("m1", 10, "")]).unwrap();
+        store.touch("old", SystemTime::now() - Duration::from_secs(40 * 24 * 60 * 60)).unwrap();
+        store.save("new", &[message("m2", 20, "")]).unwrap();
+
+        let removed = store.evict(EvictionPolicy::default(), SystemTime::now()).unwrap();
+        assert_eq!(removed, 1);
+        assert!(store.load("old", 50).unwrap().is_empty());
+        assert_eq!(store.load("new", 50).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used_history_past_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = open(dir.path());
+        let now = SystemTime::now();
+        let text = "x".repeat(1000);
+        let batch = |prefix: &str| -> Vec<Message> {
+            (0..5).map(|i| message(&format!("{prefix}{i}"), i, &text)).collect()
+        };
+        store.save("a", &batch("a")).unwrap();
+        store.save("b", &batch("b")).unwrap();
+        store.touch("a", now - Duration::from_secs(60)).unwrap();
+        store.touch("b", now).unwrap();
+
+        let size_of_one = serde_json::to_string(&batch("a")[0]).unwrap().len() as u64;
+        let policy = EvictionPolicy {
+            max_bytes: size_of_one * 7,
+            ..EvictionPolicy::default()
+        };
+        assert_eq!(store.evict(policy, now).unwrap(), 3);
+        // The oldest messages of the least recently used conversation go.
+        assert_eq!(ids(&store.load("a", 50).unwrap()), ["a3", "a4"]);
+        assert_eq!(store.load("b", 50).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_migrations_run_once_and_newer_caches_start_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = MessageStore::account_dir(dir.path(), "u1").join(DB_FILE);
+        let mut store = open(dir.path());
+        store.save("c1", &[message("m1", 10, "")]).unwrap();
+        let version: usize = store
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        drop(store);
+
+        // Reopening doesn't re-run anything or lose data.
+        assert_eq!(open(dir.path()).load("c1", 50).unwrap().len(), 1);
+
+        // A cache from a newer build is discarded rather than misread.
+        let conn = Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        drop(conn);
+        assert!(MessageStore::open(&path, "u1").is_err());
+        assert!(open(dir.path()).load("c1", 50).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_account_dirs_are_safe_and_deletable() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            MessageStore::account_dir(dir.path(), "user_1"),
+            dir.path().join("accounts").join("user_1")
+        );
+        assert_eq!(
+            MessageStore::account_dir(dir.path(), "../x"),
+            dir.path().join("accounts").join("x2e2e2f78")
+        );
+
+        let mut store = open(dir.path());
+        store.save("c1", &[message("m1", 10, "")]).unwrap();
+        drop(store);
+        MessageStore::delete_for_account(dir.path(), "u1").unwrap();
+        assert!(!MessageStore::account_dir(dir.path(), "u1").exists());
+        MessageStore::delete_for_account(dir.path(), "u1").unwrap();
+    }
+}
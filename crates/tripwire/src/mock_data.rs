@@ -1,7 +1,11 @@
 use crate::models::{VoiceParticipant, 
-    Badge, Channel, ChannelCategory, ChannelKind, DirectMessageChannel, Message, MessageReply,
-    Role, Server, User, UserProfile, UserStatus,
+    Badge, Channel, ChannelCategory, ChannelKind, CustomEmoji, DirectMessageChannel, Embed, EmbedField, Message, MessageReply, MessageRevision,
+    MemberInfo, Role, Server, User, UserProfile, UserStatus,
 };
+use crate::audit_log::{AuditAction, AuditChange, AuditLogEntry};
+use crate::permissions::{OverwriteTarget, PermissionOverwrite, Permissions};
+use crate::read_state::ReadState;
+use std::collections::HashMap;
 use std::time::{SystemTime, Duration};
 
 // Helper to create timestamps relative to now
@@ -18,11 +22,49 @@ pub fn make_user(id: &str, name: &str, disc: &str, status: UserStatus) -> User {
     }
 }
 
+fn make_role(id: &str, name: &str, color: &str, permissions: Permissions, hoist: bool) -> Role {
+    Role {
+        id: id.to_string(),
+        name: name.to_string(),
+        color: color.to_string(),
+        permissions,
+        hoist,
+        mentionable: true,
+    }
+}
+
+fn make_emoji(id: &str, name: &str, animated: bool) -> CustomEmoji {
+    let ext = if animated { "gif" } else { "png" };
+    CustomEmoji {
+        id: id.to_string(),
+        name: name.to_string(),
+        url: format!("https://cdn.tripwire.chat/emojis/{id}.{ext}"),
+        animated,
+    }
+}
+
+/// Members who joined `days` ago, some with nicknames.
+fn make_member_info(members: &[(&str, u64, Option<&str>)]) -> HashMap<String, MemberInfo> {
+    members
+        .iter()
+        .map(|(id, days, nickname)| {
+            let info = MemberInfo {
+                nickname: nickname.map(String::from),
+                joined_at: Some(time_ago(days * 24, 0)),
+                timed_out_until: None,
+            };
+            (id.to_string(), info)
+        })
+        .collect()
+}
+
 pub fn make_servers() -> Vec<Server> {
     vec![
         Server {
             id: "1".to_string(),
             name: "Tripwire HQ".to_string(),
+            owner_id: Some("u1".to_string()),
+            everyone_permissions: Permissions::default(),
             categories: vec![
                 ChannelCategory {
                     name: "Information".to_string(),
@@ -31,22 +73,31 @@ pub fn make_servers() -> Vec<Server> {
                             id: "101".to_string(),
                             name: "announcements".to_string(),
                             kind: ChannelKind::Announcement,
-                            unread: 1,
                             topic: Some("Official announcements only.".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![PermissionOverwrite {
+                                target: OverwriteTarget::Everyone,
+                                allow: Permissions::empty(),
+                                deny: Permissions::SEND_MESSAGES | Permissions::CREATE_THREADS,
+                            }],
                         },
                         Channel {
                             id: "102".to_string(),
                             name: "rules".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 0,
                             topic: Some("Read before participating.".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                     ],
                     collapsed: false,
+                    overwrites: vec![],
                 },
                 ChannelCategory {
                     name: "General".to_string(),
@@ -55,40 +106,49 @@ pub fn make_servers() -> Vec<Server> {
                             id: "103".to_string(),
                             name: "general".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 5,
                             topic: Some("Chat about anything!".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "104".to_string(),
                             name: "introductions".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 0,
                             topic: Some("Introduce yourself to the community.".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "105".to_string(),
                             name: "off-topic".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 12,
                             topic: None,
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "108".to_string(),
                             name: "media-sharing".to_string(),
                             kind: ChannelKind::Media,
-                            unread: 2,
                             topic: Some("Share your photos and videos".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                     ],
                     collapsed: false,
+                    overwrites: vec![],
                 },
                 ChannelCategory {
                     name: "Voice".to_string(),
@@ -97,8 +157,9 @@ pub fn make_servers() -> Vec<Server> {
                             id: "106".to_string(),
                             name: "Lounge".to_string(),
                             kind: ChannelKind::Voice,
-                            unread: 0,
                             topic: None,
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 3,
                             voice_participants: vec![
                                 VoiceParticipant {
@@ -129,27 +190,33 @@ pub fn make_servers() -> Vec<Server> {
                                     is_video: false,
                                 },
                             ],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "107".to_string(),
                             name: "Gaming".to_string(),
                             kind: ChannelKind::Voice,
-                            unread: 0,
                             topic: None,
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "109".to_string(),
                             name: "Town Hall".to_string(),
                             kind: ChannelKind::Stage,
-                            unread: 0,
                             topic: Some("Monthly community meetings".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                     ],
                     collapsed: false,
+                    overwrites: vec![],
                 },
             ],
             members: vec![
@@ -159,11 +226,31 @@ pub fn make_servers() -> Vec<Server> {
                 make_user("u4", "Dave", "0004", UserStatus::Offline),
                 make_user("u5", "Eve", "0005", UserStatus::Offline),
             ],
-            notification_count: 0,
+            roles: vec![
+                make_role("r1", "Administrator", "#F04747", Permissions::ADMINISTRATOR, true),
+                make_role("r2", "Developer", "#5865F2", Permissions::MANAGE_MESSAGES | Permissions::MANAGE_NICKNAMES, true),
+                make_role("r3", "Designer", "#E91E63", Permissions::empty(), false),
+            ],
+            member_roles: [
+                ("u1", vec!["r1", "r2"]),
+                ("u2", vec!["r2"]),
+                ("u3", vec!["r3"]),
+            ]
+            .into_iter()
+            .map(|(user, roles)| (user.to_string(), roles.into_iter().map(String::from).collect()))
+            .collect(),
+            member_info: make_member_info(&[("u1", 400, None), ("u2", 120, Some("Bobby")), ("u3", 45, None), ("u4", 6, None), ("u5", 0, None)]),
+            emojis: vec![
+                make_emoji("e1", "ferris", false),
+                make_emoji("e2", "shipit", false),
+                make_emoji("e3", "party_parrot", true),
+            ],
         },
         Server {
             id: "2".to_string(),
             name: "Dev Corner".to_string(),
+            owner_id: Some("u6".to_string()),
+            everyone_permissions: Permissions::default(),
             categories: vec![
                 ChannelCategory {
                     name: "Dev".to_string(),
@@ -172,40 +259,49 @@ pub fn make_servers() -> Vec<Server> {
                             id: "201".to_string(),
                             name: "general-dev".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 3,
                             topic: Some("Development discussion".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "202".to_string(),
                             name: "rust".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 0,
                             topic: Some("Rustaceans unite!".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "203".to_string(),
                             name: "code-review".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 2,
                             topic: None,
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "204".to_string(),
                             name: "help-forum".to_string(),
                             kind: ChannelKind::Forum,
-                            unread: 8,
                             topic: Some("Ask questions and get help".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                     ],
                     collapsed: false,
+                    overwrites: vec![],
                 },
             ],
             members: vec![
@@ -213,11 +309,16 @@ pub fn make_servers() -> Vec<Server> {
                 make_user("u6", "Frank", "0006", UserStatus::Online),
                 make_user("u7", "Grace", "0007", UserStatus::Offline),
             ],
-            notification_count: 5,
+            roles: vec![],
+            member_roles: Default::default(),
+            member_info: make_member_info(&[("u1", 200, None), ("u6", 300, None), ("u7", 12, Some("gracie"))]),
+            emojis: vec![],
         },
         Server {
             id: "3".to_string(),
             name: "Design Lab".to_string(),
+            owner_id: Some("u8".to_string()),
+            everyone_permissions: Permissions::default(),
             categories: vec![
                 ChannelCategory {
                     name: "Design".to_string(),
@@ -226,29 +327,37 @@ pub fn make_servers() -> Vec<Server> {
                             id: "301".to_string(),
                             name: "inspiration".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 0,
                             topic: Some("Share design inspiration".to_string()),
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                         Channel {
                             id: "302".to_string(),
                             name: "feedback".to_string(),
                             kind: ChannelKind::Text,
-                            unread: 7,
                             topic: None,
+                            slowmode_secs: 0,
+                            nsfw: false,
                             members_connected: 0,
                             voice_participants: vec![],
+                            overwrites: vec![],
                         },
                     ],
                     collapsed: false,
+                    overwrites: vec![],
                 },
             ],
             members: vec![
                 make_user("u8", "Hank", "0008", UserStatus::Online),
                 make_user("u9", "Iris", "0009", UserStatus::Idle),
             ],
-            notification_count: 7,
+            roles: vec![],
+            member_roles: Default::default(),
+            member_info: make_member_info(&[("u8", 90, None), ("u9", 2, None)]),
+            emojis: vec![],
         },
     ]
 }
@@ -267,7 +376,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today at 9:00 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -283,7 +394,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today at 9:02 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -299,7 +412,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today at 9:05 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: Some(Box::new(MessageReply {
                     message_id: "m2".to_string(),
@@ -315,11 +430,27 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
             Message {
                 id: "m4".to_string(),
                 author: alice.clone(),
-                content: "Thanks! Built entirely with GPUI components. The component library is fantastic.".to_string(),
+                content: "Thanks! Built entirely with GPUI components. The component library is fantastic: https://github.com/longbridge/gpui-component".to_string(),
                 timestamp: "Today at 9:07 AM".to_string(),
                 edited: true,
                 edited_timestamp: Some("Today at 9:08 AM".to_string()),
-                attachment: None,
+                revisions: vec![MessageRevision {
+                    content: "Thanks! Built entirely with GPUI components. The library is great.".to_string(),
+                    timestamp: "Today at 9:07 AM".to_string(),
+                }],
+                attachments: Vec::new(),
+                embeds: vec![Embed {
+                    url: Some("https://github.com/longbridge/gpui-component".to_string()),
+                    title: Some("longbridge/gpui-component".to_string()),
+                    description: Some("Rust GUI components for building fantastic cross-platform desktop applications by using GPUI.".to_string()),
+                    site_name: Some("GitHub".to_string()),
+                    thumbnail: None,
+                    color: Some(0x24292f),
+                    fields: vec![
+                        EmbedField { name: "Language".to_string(), value: "Rust".to_string(), inline: true },
+                        EmbedField { name: "License".to_string(), value: "Apache-2.0".to_string(), inline: true },
+                    ],
+                }],
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -335,7 +466,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today at 9:10 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -351,7 +484,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today at 9:12 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -367,7 +502,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today at 9:15 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -385,7 +522,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Yesterday at 8:00 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -401,7 +540,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Yesterday at 8:01 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -419,7 +560,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today at 10:30 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -435,7 +578,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today at 10:35 AM".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -453,7 +598,9 @@ pub fn make_messages_for(channel_id: &str) -> Vec<Message> {
                 timestamp: "Today".to_string(),
                 edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -473,32 +620,83 @@ pub fn make_dm_channels() -> Vec<DirectMessageChannel> {
             recipient: make_user("u2", "Bob", "0002", UserStatus::Idle),
             last_message: Some("That sounds great! Let's do it.".to_string()),
             last_message_time: Some("12:45 PM".to_string()),
-            unread: 2,
         },
         DirectMessageChannel {
             id: "dm-carol".to_string(),
             recipient: make_user("u3", "Carol", "0003", UserStatus::DoNotDisturb),
             last_message: Some("Thanks for the help!".to_string()),
             last_message_time: Some("Yesterday".to_string()),
-            unread: 0,
         },
         DirectMessageChannel {
             id: "dm-dave".to_string(),
             recipient: make_user("u4", "Dave", "0004", UserStatus::Offline),
             last_message: Some("See you later!".to_string()),
             last_message_time: Some("2 days ago".to_string()),
-            unread: 0,
         },
         DirectMessageChannel {
             id: "dm-frank".to_string(),
             recipient: make_user("u6", "Frank", "0006", UserStatus::Online),
             last_message: Some("Check out this new library I found".to_string()),
             last_message_time: Some("10:20 AM".to_string()),
-            unread: 5,
         },
     ]
 }
 
+/// Unread and mention counts to start the sample channels and DMs with.
+pub fn make_read_states() -> Vec<ReadState> {
+    [
+        ("101", 1, 1),
+        ("103", 5, 0),
+        ("105", 12, 0),
+        ("108", 2, 0),
+        ("201", 3, 1),
+        ("203", 2, 0),
+        ("204", 8, 4),
+        ("302", 7, 2),
+        ("dm-bob", 2, 2),
+        ("dm-frank", 5, 5),
+    ]
+    .into_iter()
+    .map(|(conversation_id, unread, mentions)| ReadState {
+        conversation_id: conversation_id.to_string(),
+        last_read: None,
+        unread,
+        mentions,
+    })
+    .collect()
+}
+
+/// A few past moderator actions in Tripwire HQ, oldest first.
+pub fn make_audit_log() -> HashMap<String, Vec<AuditLogEntry>> {
+    let change = |key: &str, old: Option<&str>, new: Option<&str>| AuditChange {
+        key: key.to_string(),
+        old: old.map(String::from),
+        new: new.map(String::from),
+    };
+    let entries = [
+        (72, AuditAction::RoleCreate, "r3", vec![change("name", None, Some("Designer")), change("color", None, Some("#E91E63"))], None),
+        (50, AuditAction::MemberRoleUpdate, "u3", vec![change("roles", None, Some("Designer"))], None),
+        (26, AuditAction::MemberUpdate, "u2", vec![change("nickname", None, Some("Bobby"))], Some("Asked for it")),
+        (5, AuditAction::MessagePin, "u2", Vec::new(), None),
+        (2, AuditAction::MemberTimeout, "u5", vec![change("timeout", None, Some("10 minutes"))], Some("Spamming #general")),
+    ];
+    let log = entries
+        .into_iter()
+        .enumerate()
+        .map(|(ix, (hours, action, target_id, changes, reason))| AuditLogEntry {
+            id: format!("audit-{}", ix + 1),
+            actor_id: "u1".to_string(),
+            action,
+            target_id: Some(target_id.to_string()),
+            channel_id: (action == AuditAction::MessagePin).then(|| "101".to_string()),
+            changes,
+            reason: reason.map(String::from),
+            created_at: time_ago(hours, 0),
+        })
+        .collect();
+    HashMap::from([("1".to_string(), log)])
+}
+
 pub fn make_dm_messages_for(dm_id: &str) -> Vec<Message> {
     let current_user = make_user("u1", "Alice", "0001", UserStatus::Online);
     
@@ -513,7 +711,9 @@ pub fn make_dm_messages_for(dm_id: &str) -> Vec<Message> {
                     timestamp: "Today at 12:30 PM".to_string(),
                     edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -529,7 +729,9 @@ pub fn make_dm_messages_for(dm_id: &str) -> Vec<Message> {
                     timestamp: "Today at 12:45 PM".to_string(),
                     edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -550,7 +752,9 @@ pub fn make_dm_messages_for(dm_id: &str) -> Vec<Message> {
                     timestamp: "Today at 10:20 AM".to_string(),
                     edited: false,
                 edited_timestamp: None,
-                attachment: None,
+                revisions: Vec::new(),
+                attachments: Vec::new(),
+                embeds: Vec::new(),
                 reactions: std::collections::HashMap::new(),
                 reply_to: None,
                 mentioned_users: vec![],
@@ -581,11 +785,17 @@ pub fn make_user_profile(user: User) -> UserProfile {
                     id: "r1".to_string(),
                     name: "Administrator".to_string(),
                     color: "#F04747".to_string(),
+                    permissions: Permissions::ADMINISTRATOR,
+                    hoist: true,
+                    mentionable: true,
                 },
                 Role {
                     id: "r2".to_string(),
                     name: "Developer".to_string(),
                     color: "#5865F2".to_string(),
+                    permissions: Permissions::MANAGE_MESSAGES | Permissions::MANAGE_NICKNAMES,
+                    hoist: true,
+                    mentionable: true,
                 },
             ],
             badges: vec![Badge::EarlySupporter, Badge::ActiveDeveloper],
@@ -603,6 +813,9 @@ pub fn make_user_profile(user: User) -> UserProfile {
                 id: "r2".to_string(),
                 name: "Developer".to_string(),
                 color: "#5865F2".to_string(),
+                permissions: Permissions::MANAGE_MESSAGES | Permissions::MANAGE_NICKNAMES,
+                hoist: true,
+                mentionable: true,
             }],
             badges: vec![Badge::BugHunterLevel1],
             note: None,
@@ -619,6 +832,9 @@ pub fn make_user_profile(user: User) -> UserProfile {
                 id: "r3".to_string(),
                 name: "Designer".to_string(),
                 color: "#E91E63".to_string(),
+                permissions: Permissions::empty(),
+                hoist: false,
+                mentionable: true,
             }],
             badges: vec![Badge::Partner],
             note: None,
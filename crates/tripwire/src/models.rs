@@ -1,6 +1,8 @@
 use gpui_component::IconName;
 use serde::{Deserialize, Serialize};
 
+use crate::permissions::{PermissionOverwrite, Permissions};
+
 // ── Voice State ────────────────────────────────────────────────────────────
 
 #[derive(Debug, Clone, PartialEq)]
@@ -59,7 +61,7 @@ impl UserStatus {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct User {
     pub id: String,
     pub username: String,
@@ -95,11 +97,20 @@ pub struct UserProfile {
     pub note: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Role {
     pub id: String,
     pub name: String,
     pub color: String,
+    /// Granted server-wide to members holding this role.
+    #[serde(default = "Permissions::empty")]
+    pub permissions: Permissions,
+    /// Members holding it are listed under it in the member list.
+    #[serde(default)]
+    pub hoist: bool,
+    /// Anyone can @mention it, not only those who can mention everyone.
+    #[serde(default)]
+    pub mentionable: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -167,7 +178,7 @@ impl Badge {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ChannelKind {
     Text,
     Voice,
@@ -178,6 +189,26 @@ pub enum ChannelKind {
 }
 
 impl ChannelKind {
+    pub const ALL: [ChannelKind; 6] = [
+        ChannelKind::Text,
+        ChannelKind::Voice,
+        ChannelKind::Announcement,
+        ChannelKind::Stage,
+        ChannelKind::Forum,
+        ChannelKind::Media,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ChannelKind::Text => "Text",
+            ChannelKind::Voice => "Voice",
+            ChannelKind::Announcement => "Announcement",
+            ChannelKind::Stage => "Stage",
+            ChannelKind::Forum => "Forum",
+            ChannelKind::Media => "Media",
+        }
+    }
+
     pub fn prefix(&self) -> &'static str {
         match self {
             ChannelKind::Text => "#",
@@ -216,7 +247,7 @@ impl ChannelKind {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VoiceParticipant {
     pub user_id: String,
     pub username: String,
@@ -227,15 +258,23 @@ pub struct VoiceParticipant {
     pub is_video: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Channel {
     pub id: String,
     pub name: String,
     pub kind: ChannelKind,
-    pub unread: usize,
     pub topic: Option<String>,
+    /// Seconds members must wait between messages; 0 for none.
+    #[serde(default)]
+    pub slowmode_secs: u32,
+    /// Age-restricted.
+    #[serde(default)]
+    pub nsfw: bool,
     pub members_connected: usize,
     pub voice_participants: Vec<VoiceParticipant>,
+    /// Adjustments to members' permissions here, on top of the category's.
+    #[serde(default)]
+    pub overwrites: Vec<PermissionOverwrite>,
 }
 
 impl Channel {
@@ -244,20 +283,40 @@ impl Channel {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChannelCategory {
     pub name: String,
     pub channels: Vec<Channel>,
     pub collapsed: bool,
+    /// Adjustments to members' permissions in every channel of the category.
+    #[serde(default)]
+    pub overwrites: Vec<PermissionOverwrite>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Server {
     pub id: String,
     pub name: String,
+    /// Can do anything, whatever their roles.
+    #[serde(default)]
+    pub owner_id: Option<String>,
+    /// Granted to every member, the `@everyone` role.
+    #[serde(default)]
+    pub everyone_permissions: Permissions,
     pub categories: Vec<ChannelCategory>,
     pub members: Vec<User>,
-    pub notification_count: usize,
+    /// Roles defined in this server, highest ranked first.
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    /// IDs of the roles each member holds, by user ID.
+    #[serde(default)]
+    pub member_roles: std::collections::HashMap<String, Vec<String>>,
+    /// Nicknames, join dates and timeouts, by user ID.
+    #[serde(default)]
+    pub member_info: std::collections::HashMap<String, MemberInfo>,
+    /// Custom emoji uploaded to this server.
+    #[serde(default)]
+    pub emojis: Vec<CustomEmoji>,
 }
 
 impl Server {
@@ -273,14 +332,80 @@ impl Server {
     pub fn all_channels(&self) -> Vec<&Channel> {
         self.categories.iter().flat_map(|c| c.channels.iter()).collect()
     }
+
+    /// The highest hoisted role `user_id` holds, which they're listed
+    /// under in the member list.
+    pub fn hoisted_role(&self, user_id: &str) -> Option<&Role> {
+        let held = self.member_roles.get(user_id)?;
+        self.roles.iter().find(|role| role.hoist && held.contains(&role.id))
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What a server knows about one of its members.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemberInfo {
+    /// Shown instead of their username in this server.
+    #[serde(default)]
+    pub nickname: Option<String>,
+    #[serde(default)]
+    pub joined_at: Option<std::time::SystemTime>,
+    /// Until when they can only read along.
+    #[serde(default)]
+    pub timed_out_until: Option<std::time::SystemTime>,
+}
+
+impl MemberInfo {
+    pub fn is_timed_out(&self, now: std::time::SystemTime) -> bool {
+        self.timed_out_until.is_some_and(|until| now < until)
+    }
+}
+
+/// An image-backed emoji belonging to a server, sent in text and reactions
+/// as a `<:name:id>` token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomEmoji {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub animated: bool,
+}
+
+impl CustomEmoji {
+    pub fn to_ref(&self) -> crate::emoji::CustomEmojiRef {
+        crate::emoji::CustomEmojiRef {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            animated: self.animated,
+        }
+    }
+}
+
+/// A file sent with a message, as stored by the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Attachment {
+    pub id: String,
     pub filename: String,
     pub mime_type: String,
-    pub base64_data: String,
-    pub size: usize,
+    pub size: u64,
+    /// Where the file is downloaded from: an `http(s)` URL, or for
+    /// `AttachmentSource::Local` files a path on this machine.
+    pub url: String,
+    /// Never sent by a server, so only the backend that made an attachment
+    /// can say it's on this machine.
+    #[serde(skip)]
+    pub source: AttachmentSource,
+}
+
+/// Where an attachment's file is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AttachmentSource {
+    /// Hosted by the server, at `url`.
+    #[default]
+    Remote,
+    /// On this machine, at the path in `url`; for backends that don't host
+    /// files, like the mock one.
+    Local,
 }
 
 impl Attachment {
@@ -288,12 +413,43 @@ impl Attachment {
         self.mime_type.starts_with("image/")
     }
 
-    pub fn size_mb(&self) -> f64 {
-        self.size as f64 / (1024.0 * 1024.0)
+    pub fn is_video(&self) -> bool {
+        self.mime_type.starts_with("video/")
+    }
+
+    /// `url` as a local path, for files the backend keeps on this machine.
+    pub fn local_path(&self) -> Option<&std::path::Path> {
+        (self.source == AttachmentSource::Local).then(|| std::path::Path::new(&self.url))
     }
 }
 
-#[derive(Debug, Clone)]
+/// A rich card under a message: a preview of a link in it, or sent by the
+/// server.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Embed {
+    /// Where the title links to.
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    /// Image URL.
+    pub thumbnail: Option<String>,
+    /// Accent along the card's edge, as `0xRRGGBB`.
+    pub color: Option<u32>,
+    #[serde(default)]
+    pub fields: Vec<EmbedField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    /// Whether it may sit beside other inline fields.
+    #[serde(default)]
+    pub inline: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     pub id: String,
     pub author: User,
@@ -301,7 +457,13 @@ pub struct Message {
     pub timestamp: String,
     pub edited: bool,
     pub edited_timestamp: Option<String>,
-    pub attachment: Option<Attachment>,
+    /// Earlier versions of `content`, oldest first.
+    #[serde(default)]
+    pub revisions: Vec<MessageRevision>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
     pub reactions: std::collections::HashMap<String, Vec<String>>,
     pub reply_to: Option<Box<MessageReply>>,
     pub mentioned_users: Vec<String>,
@@ -311,7 +473,15 @@ pub struct Message {
     pub created_at: std::time::SystemTime, // For grouping logic
 }
 
-#[derive(Debug, Clone)]
+/// A version of a message's text that was edited away.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageRevision {
+    pub content: String,
+    /// When this version was posted or edited in, as shown to the user.
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MessageReply {
     pub message_id: String,
     pub author: User,
@@ -319,6 +489,20 @@ pub struct MessageReply {
 }
 
 impl Message {
+    /// Replace the text with `content`, edited at `timestamp`, keeping the
+    /// old text in `revisions`. Unchanged text isn't an edit.
+    pub fn edit(&mut self, content: String, timestamp: String) {
+        if content == self.content {
+            return;
+        }
+        let written = self.edited_timestamp.replace(timestamp).unwrap_or_else(|| self.timestamp.clone());
+        self.revisions.push(MessageRevision {
+            content: std::mem::replace(&mut self.content, content),
+            timestamp: written,
+        });
+        self.edited = true;
+    }
+
     pub fn add_reaction(&mut self, emoji: String, user_id: String) {
         self.reactions.entry(emoji).or_default().push(user_id);
     }
@@ -398,13 +582,12 @@ impl Message {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirectMessageChannel {
     pub id: String,
     pub recipient: User,
     pub last_message: Option<String>,
     pub last_message_time: Option<String>,
-    pub unread: usize,
 }
 
 impl DirectMessageChannel {
@@ -0,0 +1,148 @@
+//! Desktop notification buses. On Linux notifications go to the
+//! freedesktop.org notification service on the session D-Bus; tests use
+//! [`RecordingBus`] in its place.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+
+/// Longest body shown; longer messages are cut with an ellipsis.
+const MAX_BODY_CHARS: usize = 200;
+
+/// A notification as sent to the desktop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DesktopNotification {
+    pub summary: String,
+    pub body: String,
+    pub sound: bool,
+}
+
+impl DesktopNotification {
+    pub fn new(summary: impl Into<String>, body: &str, sound: bool) -> Self {
+        let body = match body.char_indices().nth(MAX_BODY_CHARS) {
+            Some((end, _)) => format!("{}…", &body[..end]),
+            None => body.to_string(),
+        };
+        Self {
+            summary: summary.into(),
+            body,
+            sound,
+        }
+    }
+}
+
+/// Somewhere to show desktop notifications.
+pub trait NotificationBus: Send + Sync {
+    fn show(&self, notification: DesktopNotification) -> BoxFuture<'static, Result<()>>;
+}
+
+/// The bus of this platform, if it has one.
+pub fn desktop_bus() -> Option<Arc<dyn NotificationBus>> {
+    #[cfg(target_os = "linux")]
+    return Some(Arc::new(freedesktop::FreedesktopBus::default()));
+    #[cfg(not(target_os = "linux"))]
+    None
+}
+
+#[cfg(target_os = "linux")]
+mod freedesktop {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::Result;
+    use futures::FutureExt as _;
+    use futures::future::BoxFuture;
+    use zbus::zvariant::Value;
+
+    use super::{DesktopNotification, NotificationBus};
+
+    const APP_NAME: &str = "Tripwire";
+    /// Let the notification server pick how long to show it.
+    const DEFAULT_TIMEOUT: i32 = -1;
+
+    /// `org.freedesktop.Notifications` on the session bus, connected to on
+    /// first use.
+    #[derive(Default)]
+    pub struct FreedesktopBus {
+        connection: Arc<Mutex<Option<zbus::Connection>>>,
+    }
+
+    async fn connect(cached: Arc<Mutex<Option<zbus::Connection>>>) -> Result<zbus::Connection> {
+        if let Some(connection) = cached.lock().ok().and_then(|c| c.clone()) {
+            return Ok(connection);
+        }
+        let connection = zbus::Connection::session().await?;
+        if let Ok(mut cached) = cached.lock() {
+            *cached = Some(connection.clone());
+        }
+        Ok(connection)
+    }
+
+    impl NotificationBus for FreedesktopBus {
+        fn show(&self, notification: DesktopNotification) -> BoxFuture<'static, Result<()>> {
+            let cached = self.connection.clone();
+            async move {
+                let connection = connect(cached).await?;
+                let mut hints: HashMap<&str, Value> = HashMap::new();
+                hints.insert("category", Value::from("im.received"));
+                if notification.sound {
+                    hints.insert("sound-name", Value::from("message-new-instant"));
+                } else {
+                    hints.insert("suppress-sound", Value::from(true));
+                }
+                connection
+                    .call_method(
+                        Some("org.freedesktop.Notifications"),
+                        "/org/freedesktop/Notifications",
+                        Some("org.freedesktop.Notifications"),
+                        "Notify",
+                        &(
+                            APP_NAME,
+                            0u32,
+                            "",
+                            notification.summary.as_str(),
+                            notification.body.as_str(),
+                            Vec::<&str>::new(),
+                            hints,
+                            DEFAULT_TIMEOUT,
+                        ),
+                    )
+                    .await?;
+                Ok(())
+            }
+            .boxed()
+        }
+    }
+}
+
+/// Stand-in bus that keeps what it's shown.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecordingBus {
+    pub shown: std::sync::Mutex<Vec<DesktopNotification>>,
+}
+
+#[cfg(test)]
+impl NotificationBus for RecordingBus {
+    fn show(&self, notification: DesktopNotification) -> BoxFuture<'static, Result<()>> {
+        use futures::FutureExt as _;
+
+        self.shown.lock().unwrap().push(notification);
+        futures::future::ready(Ok(())).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_bodies_are_cut() {
+        let short = DesktopNotification::new("Bob", "hi", true);
+        assert_eq!(short.body, "hi");
+        let long = DesktopNotification::new("Bob", &"é".repeat(MAX_BODY_CHARS + 5), true);
+        assert_eq!(long.body.chars().count(), MAX_BODY_CHARS + 1);
+        assert!(long.body.ends_with('…'));
+    }
+}
@@ -0,0 +1,446 @@
+//! Notifications — whether an incoming message should notify, going by the
+//! global settings, per-server and per-channel overrides, mutes and Do Not
+//! Disturb; and where notifications are shown.
+//!
+//! Settings are saved as `notifications.json` in the data directory. What
+//! they decide is shown as an in-app toast while the window has focus, and
+//! on the desktop notification [`bus`] otherwise.
+
+pub mod bus;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Message, UserStatus};
+use crate::settings_file::{load_json_settings, save_json_settings};
+
+pub use bus::{DesktopNotification, NotificationBus};
+
+const FILE_NAME: &str = "notifications.json";
+
+/// Which messages notify.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotifyLevel {
+    All,
+    #[default]
+    Mentions,
+    Nothing,
+}
+
+impl NotifyLevel {
+    pub const ALL: [NotifyLevel; 3] = [NotifyLevel::All, NotifyLevel::Mentions, NotifyLevel::Nothing];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            NotifyLevel::All => "All Messages",
+            NotifyLevel::Mentions => "Only @mentions",
+            NotifyLevel::Nothing => "Nothing",
+        }
+    }
+}
+
+/// How long a server or channel stays muted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Mute {
+    Until(SystemTime),
+    Forever,
+}
+
+impl Mute {
+    /// Mute for `duration` from `now`, or until unmuted.
+    pub fn new(duration: Option<Duration>, now: SystemTime) -> Self {
+        match duration {
+            Some(duration) => Mute::Until(now + duration),
+            None => Mute::Forever,
+        }
+    }
+
+    pub fn is_active(self, now: SystemTime) -> bool {
+        match self {
+            Mute::Until(until) => now < until,
+            Mute::Forever => true,
+        }
+    }
+
+    /// "Muted", or how much longer it lasts as of `now`.
+    pub fn label(self, now: SystemTime) -> String {
+        let Mute::Until(until) = self else {
+            return "Muted".to_string();
+        };
+        let minutes = until.duration_since(now).unwrap_or_default().as_secs().div_ceil(60);
+        match (minutes / 60, minutes % 60) {
+            (0, minutes) => format!("Muted for {minutes} min"),
+            (hours, 0) => format!("Muted for {hours} h"),
+            (hours, minutes) => format!("Muted for {hours} h {minutes} min"),
+        }
+    }
+}
+
+/// What a server or channel overrides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Override {
+    #[serde(default)]
+    pub level: Option<NotifyLevel>,
+    #[serde(default)]
+    pub mute: Option<Mute>,
+}
+
+impl Override {
+    fn is_empty(&self) -> bool {
+        self.level.is_none() && self.mute.is_none()
+    }
+}
+
+/// A server, or a channel or DM, settings can be overridden for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Server(String),
+    /// A server channel or a DM, by ID.
+    Channel(String),
+}
+
+/// A message that just arrived, and what decides whether it notifies.
+#[derive(Debug, Clone)]
+pub struct Incoming<'a> {
+    /// `None` for direct messages.
+    pub server_id: Option<&'a str>,
+    pub conversation_id: &'a str,
+    pub message: &'a Message,
+    pub user_id: &'a str,
+    pub status: UserStatus,
+    /// The conversation is open in the focused window.
+    pub viewing: bool,
+}
+
+/// What became of a notification handed to [`NotificationSettings::route`].
+pub enum Routed {
+    /// Show it inside the window.
+    Toast(DesktopNotification),
+    /// Sent to the desktop bus; resolves once the bus has it.
+    Desktop(BoxFuture<'static, Result<()>>),
+    /// Its kind is turned off, or there's no bus.
+    Dropped,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    /// Show notifications on the desktop while the window isn't focused.
+    #[serde(default = "enabled")]
+    pub desktop: bool,
+    /// Show toasts inside the window while it's focused.
+    #[serde(default = "enabled")]
+    pub toasts: bool,
+    #[serde(default = "enabled")]
+    pub sound: bool,
+    /// Level of servers and channels without an override. Direct messages
+    /// always notify unless overridden.
+    #[serde(default)]
+    pub default_level: NotifyLevel,
+    #[serde(default)]
+    servers: HashMap<String, Override>,
+    #[serde(default)]
+    channels: HashMap<String, Override>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            desktop: true,
+            toasts: true,
+            sound: true,
+            default_level: NotifyLevel::default(),
+            servers: HashMap::new(),
+            channels: HashMap::new(),
+            path: None,
+        }
+    }
+}
+
+impl NotificationSettings {
+    /// Load the settings saved in `dir`, starting from the defaults if there
+    /// are none.
+    pub fn load(dir: &Path) -> Self {
+        Self {
+            path: Some(dir.join(FILE_NAME)),
+            ..load_json_settings(dir, FILE_NAME)
+        }
+    }
+
+    /// Whether `incoming` should notify as of `now`.
+    pub fn should_notify(&self, incoming: &Incoming, now: SystemTime) -> bool {
+        if !self.enabled
+            || incoming.viewing
+            || incoming.status == UserStatus::DoNotDisturb
+            || incoming.message.author.id == incoming.user_id
+        {
+            return false;
+        }
+        let channel = Scope::Channel(incoming.conversation_id.to_string());
+        let server = incoming.server_id.map(|id| Scope::Server(id.to_string()));
+        if self.is_muted(&channel, now) || server.as_ref().is_some_and(|s| self.is_muted(s, now)) {
+            return false;
+        }
+        let level = self
+            .level(&channel)
+            .or_else(|| server.as_ref().and_then(|s| self.level(s)))
+            .unwrap_or(match incoming.server_id {
+                Some(_) => self.default_level,
+                None => NotifyLevel::All,
+            });
+        match level {
+            NotifyLevel::All => true,
+            NotifyLevel::Mentions => incoming.message.is_mentioned(incoming.user_id),
+            NotifyLevel::Nothing => false,
+        }
+    }
+
+    /// Show `notification` as a toast while the window has focus, and on
+    /// `bus` otherwise.
+    pub fn route(
+        &self,
+        notification: DesktopNotification,
+        window_focused: bool,
+        bus: Option<&dyn NotificationBus>,
+    ) -> Routed {
+        match (window_focused, bus) {
+            (true, _) if self.toasts => Routed::Toast(notification),
+            (false, Some(bus)) if self.desktop => Routed::Desktop(bus.show(notification)),
+            _ => Routed::Dropped,
+        }
+    }
+
+    /// The level set on `scope` itself, if any.
+    pub fn level(&self, scope: &Scope) -> Option<NotifyLevel> {
+        self.get(scope)?.level
+    }
+
+    /// Whether `scope` itself is muted as of `now`.
+    pub fn is_muted(&self, scope: &Scope, now: SystemTime) -> bool {
+        self.mute(scope).is_some_and(|mute| mute.is_active(now))
+    }
+
+    pub fn mute(&self, scope: &Scope) -> Option<Mute> {
+        self.get(scope)?.mute
+    }
+
+    /// Override the level of `scope`, or go back to inheriting it.
+    pub fn set_level(&mut self, scope: Scope, level: Option<NotifyLevel>) {
+        self.update(scope, |o| o.level = level);
+    }
+
+    /// Mute `scope`, or unmute it with `None`.
+    pub fn set_mute(&mut self, scope: Scope, mute: Option<Mute>) {
+        self.update(scope, |o| o.mute = mute);
+    }
+
+    /// Every override, servers first.
+    pub fn overrides(&self) -> impl Iterator<Item = (Scope, &Override)> {
+        let servers = self.servers.iter().map(|(id, o)| (Scope::Server(id.clone()), o));
+        let channels = self.channels.iter().map(|(id, o)| (Scope::Channel(id.clone()), o));
+        servers.chain(channels)
+    }
+
+    pub fn clear_override(&mut self, scope: &Scope) {
+        self.map_mut(scope).remove(scope_id(scope));
+        self.save();
+    }
+
+    fn get(&self, scope: &Scope) -> Option<&Override> {
+        match scope {
+            Scope::Server(id) => self.servers.get(id),
+            Scope::Channel(id) => self.channels.get(id),
+        }
+    }
+
+    fn map_mut(&mut self, scope: &Scope) -> &mut HashMap<String, Override> {
+        match scope {
+            Scope::Server(_) => &mut self.servers,
+            Scope::Channel(_) => &mut self.channels,
+        }
+    }
+
+    fn update(&mut self, scope: Scope, f: impl FnOnce(&mut Override)) {
+        let map = self.map_mut(&scope);
+        let id = scope_id(&scope);
+        let entry = map.entry(id.to_string()).or_default();
+        f(entry);
+        if entry.is_empty() {
+            map.remove(id);
+        }
+        self.save();
+    }
+
+    pub fn save(&self) {
+        if let Some(path) = &self.path {
+            save_json_settings(path, self);
+        }
+    }
+}
+
+fn scope_id(scope: &Scope) -> &str {
+    match scope {
+        Scope::Server(id) | Scope::Channel(id) => id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::mock_data::make_user;
+
+    fn message(author: &str, mentions: &[&str]) -> Message {
+        Message {
+            id: "m1".into(),
+            author: make_user(author, author, "0001", UserStatus::Online),
+            content: "hello".into(),
+            timestamp: String::new(),
+            edited: false,
+            edited_timestamp: None,
+            revisions: Vec::new(),
+            attachments: Vec::new(),
+            embeds: Vec::new(),
+            reactions: HashMap::new(),
+            reply_to: None,
+            mentioned_users: mentions.iter().map(|m| m.to_string()).collect(),
+            pinned: false,
+            thread_id: None,
+            thread_count: 0,
+            created_at: UNIX_EPOCH,
+        }
+    }
+
+    fn incoming<'a>(server_id: Option<&'a str>, conversation_id: &'a str, message: &'a Message) -> Incoming<'a> {
+        Incoming {
+            server_id,
+            conversation_id,
+            message,
+            user_id: "me",
+            status: UserStatus::Online,
+            viewing: false,
+        }
+    }
+
+    #[test]
+    fn test_levels_inherit_from_channel_then_server_then_default() {
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        let mut settings = NotificationSettings::default();
+        let plain = message("u2", &[]);
+        let mention = message("u2", &["me"]);
+
+        // Servers notify on mentions by default; DMs always do.
+        assert!(!settings.should_notify(&incoming(Some("s1"), "c1", &plain), now));
+        assert!(settings.should_notify(&incoming(Some("s1"), "c1", &mention), now));
+        assert!(settings.should_notify(&incoming(None, "dm1", &plain), now));
+
+        settings.set_level(Scope::Server("s1".into()), Some(NotifyLevel::All));
+        assert!(settings.should_notify(&incoming(Some("s1"), "c1", &plain), now));
+        settings.set_level(Scope::Channel("c1".into()), Some(NotifyLevel::Nothing));
+        assert!(!settings.should_notify(&incoming(Some("s1"), "c1", &mention), now));
+        assert!(settings.should_notify(&incoming(Some("s1"), "c2", &plain), now));
+
+        settings.set_level(Scope::Channel("c1".into()), None);
+        assert_eq!(settings.overrides().count(), 1);
+        assert!(settings.should_notify(&incoming(Some("s1"), "c1", &plain), now));
+    }
+
+    #[test]
+    fn test_mutes_expire() {
+        let now = UNIX_EPOCH + Duration::from_secs(1000);
+        let hour = Duration::from_secs(60 * 60);
+        let mut settings = NotificationSettings::default();
+        let mention = message("u2", &["me"]);
+
+        settings.set_mute(Scope::Server("s1".into()), Some(Mute::new(Some(hour), now)));
+        assert!(!settings.should_notify(&incoming(Some("s1"), "c1", &mention), now));
+        assert!(settings.should_notify(&incoming(Some("s1"), "c1", &mention), now + hour));
+
+        settings.set_mute(Scope::Channel("dm1".into()), Some(Mute::new(None, now)));
+        assert!(!settings.should_notify(&incoming(None, "dm1", &mention), now + hour * 1000));
+        settings.set_mute(Scope::Channel("dm1".into()), None);
+        assert!(settings.should_notify(&incoming(None, "dm1", &mention), now));
+
+        assert_eq!(Mute::new(Some(hour + Duration::from_secs(90)), now).label(now), "Muted for 1 h 2 min");
+        assert_eq!(Mute::new(Some(hour * 8), now).label(now), "Muted for 8 h");
+        assert_eq!(Mute::Forever.label(now), "Muted");
+    }
+
+    #[test]
+    fn test_dnd_own_messages_and_open_conversation_never_notify() {
+        let now = UNIX_EPOCH;
+        let mut settings = NotificationSettings::default();
+        let mention = message("u2", &["me"]);
+        let own = message("me", &["me"]);
+
+        assert!(!settings.should_notify(&incoming(None, "dm1", &own), now));
+        let dnd = Incoming {
+            status: UserStatus::DoNotDisturb,
+            ..incoming(None, "dm1", &mention)
+        };
+        assert!(!settings.should_notify(&dnd, now));
+        let viewing = Incoming {
+            viewing: true,
+            ..incoming(None, "dm1", &mention)
+        };
+        assert!(!settings.should_notify(&viewing, now));
+
+        settings.enabled = false;
+        assert!(!settings.should_notify(&incoming(None, "dm1", &mention), now));
+    }
+
+    #[test]
+    fn test_routes_to_toasts_while_focused_and_the_bus_otherwise() {
+        let mut settings = NotificationSettings::default();
+        let bus = bus::RecordingBus::default();
+        let notification = || DesktopNotification::new("#general", "hello", true);
+
+        assert!(matches!(
+            settings.route(notification(), true, Some(&bus)),
+            Routed::Toast(n) if n.body == "hello"
+        ));
+        assert!(bus.shown.lock().unwrap().is_empty());
+
+        let Routed::Desktop(shown) = settings.route(notification(), false, Some(&bus)) else {
+            panic!("expected a desktop notification");
+        };
+        futures::executor::block_on(shown).unwrap();
+        assert_eq!(*bus.shown.lock().unwrap(), [notification()]);
+
+        assert!(matches!(settings.route(notification(), false, None), Routed::Dropped));
+        settings.desktop = false;
+        settings.toasts = false;
+        assert!(matches!(settings.route(notification(), false, Some(&bus)), Routed::Dropped));
+        assert!(matches!(settings.route(notification(), true, Some(&bus)), Routed::Dropped));
+        assert_eq!(bus.shown.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_round_trips_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut settings = NotificationSettings::load(dir.path());
+        settings.set_level(Scope::Server("s1".into()), Some(NotifyLevel::Nothing));
+        settings.set_mute(Scope::Channel("c1".into()), Some(Mute::Forever));
+        settings.sound = false;
+        settings.save();
+
+        let loaded = NotificationSettings::load(dir.path());
+        assert!(!loaded.sound);
+        assert_eq!(loaded.level(&Scope::Server("s1".into())), Some(NotifyLevel::Nothing));
+        assert_eq!(loaded.mute(&Scope::Channel("c1".into())), Some(Mute::Forever));
+    }
+}
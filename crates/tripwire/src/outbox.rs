@@ -0,0 +1,182 @@
+//! Messages waiting to be delivered.
+//!
+//! Sending doesn't wait for the backend: the message gets a nonce, shows up
+//! right away as pending, and sits in the [`Outbox`] until the server
+//! acknowledges it. Failed attempts are retried with exponential backoff;
+//! after [`MAX_ATTEMPTS`], or as soon as the server refuses the message, it
+//! is marked failed and left for the user to retry or delete. Every attempt
+//! carries the same nonce so the server can drop duplicates. Entries are
+//! persisted with the account's message cache.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{MessageDraft, MessageTarget};
+use crate::models::Message;
+
+/// Attempts made before giving up and asking the user.
+pub const MAX_ATTEMPTS: u32 = 5;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeliveryState {
+    /// Being sent, or waiting to be retried.
+    Pending,
+    /// Gave up; waiting for the user to retry or delete it.
+    Failed,
+}
+
+/// A message that hasn't been acknowledged by the server yet.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub nonce: String,
+    pub target: MessageTarget,
+    pub draft: MessageDraft,
+    /// What's shown in the conversation meanwhile. Its id is the nonce.
+    pub echo: Message,
+    /// Failed attempts so far.
+    pub attempts: u32,
+    pub state: DeliveryState,
+}
+
+impl OutboxEntry {
+    pub fn new(target: MessageTarget, draft: MessageDraft, echo: Message) -> Self {
+        Self {
+            nonce: echo.id.clone(),
+            target,
+            draft,
+            echo,
+            attempts: 0,
+            state: DeliveryState::Pending,
+        }
+    }
+}
+
+/// Unacknowledged messages, oldest first.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    entries: Vec<OutboxEntry>,
+}
+
+impl Outbox {
+    pub fn new(entries: Vec<OutboxEntry>) -> Self {
+        Self { entries }
+    }
+
+    pub fn push(&mut self, entry: OutboxEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn get(&self, nonce: &str) -> Option<&OutboxEntry> {
+        self.entries.iter().find(|e| e.nonce == nonce)
+    }
+
+    pub fn state(&self, nonce: &str) -> Option<DeliveryState> {
+        self.get(nonce).map(|e| e.state)
+    }
+
+    pub fn remove(&mut self, nonce: &str) -> Option<OutboxEntry> {
+        let ix = self.entries.iter().position(|e| e.nonce == nonce)?;
+        Some(self.entries.remove(ix))
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &OutboxEntry> {
+        self.entries.iter()
+    }
+
+    /// Note a failed attempt. Returns how long to wait before the next one,
+    /// or `None` once the message has failed for good: after too many
+    /// attempts, or right away if the failure isn't `transient`.
+    pub fn record_failure(&mut self, nonce: &str, transient: bool) -> Option<Duration> {
+        let entry = self.entries.iter_mut().find(|e| e.nonce == nonce)?;
+        entry.attempts += 1;
+        if !transient || entry.attempts >= MAX_ATTEMPTS {
+            entry.state = DeliveryState::Failed;
+            None
+        } else {
+            Some(retry_delay(entry.attempts))
+        }
+    }
+
+    /// Put a message back in line after the user asked to retry it.
+    pub fn reset(&mut self, nonce: &str) -> bool {
+        let Some(entry) = self.entries.iter_mut().find(|e| e.nonce == nonce) else {
+            return false;
+        };
+        entry.attempts = 0;
+        entry.state = DeliveryState::Pending;
+        true
+    }
+}
+
+/// Wait before the attempt following `failures` failed ones: doubling from
+/// two seconds, capped at a minute.
+pub fn retry_delay(failures: u32) -> Duration {
+    let exponent = failures.saturating_sub(1).min(16);
+    FIRST_RETRY_DELAY
+        .saturating_mul(1 << exponent)
+        .min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_data;
+
+    fn entry(nonce: &str) -> OutboxEntry {
+        let mut echo = mock_data::make_messages_for("103").remove(0);
+        echo.id = nonce.into();
+        OutboxEntry::new(
+            MessageTarget::Channel("103".into()),
+            MessageDraft {
+                content: echo.content.clone(),
+                nonce: Some(nonce.into()),
+                ..Default::default()
+            },
+            echo,
+        )
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off() {
+        assert_eq!(retry_delay(1), Duration::from_secs(2));
+        assert_eq!(retry_delay(2), Duration::from_secs(4));
+        assert_eq!(retry_delay(3), Duration::from_secs(8));
+        assert_eq!(retry_delay(10), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn test_fails_after_max_attempts_and_can_be_reset() {
+        let mut outbox = Outbox::default();
+        outbox.push(entry("n1"));
+        outbox.push(entry("n2"));
+
+        for _ in 1..MAX_ATTEMPTS {
+            assert!(outbox.record_failure("n1", true).is_some());
+            assert_eq!(outbox.state("n1"), Some(DeliveryState::Pending));
+        }
+        assert_eq!(outbox.record_failure("n1", true), None);
+        assert_eq!(outbox.state("n1"), Some(DeliveryState::Failed));
+        assert_eq!(outbox.state("n2"), Some(DeliveryState::Pending));
+
+        assert!(outbox.reset("n1"));
+        assert_eq!(outbox.state("n1"), Some(DeliveryState::Pending));
+        assert_eq!(outbox.get("n1").unwrap().attempts, 0);
+
+        assert_eq!(outbox.remove("n1").unwrap().nonce, "n1");
+        assert_eq!(outbox.state("n1"), None);
+        assert!(!outbox.reset("n1"));
+        assert_eq!(outbox.record_failure("n1", true), None);
+    }
+
+    #[test]
+    fn test_fails_at_once_when_refused() {
+        let mut outbox = Outbox::default();
+        outbox.push(entry("n1"));
+        assert_eq!(outbox.record_failure("n1", false), None);
+        assert_eq!(outbox.state("n1"), Some(DeliveryState::Failed));
+    }
+}
@@ -0,0 +1,521 @@
+//! Permissions — what a member may do in a server and its channels.
+//!
+//! A member starts with the server's `@everyone` permissions plus those of
+//! every role they hold. Channels then adjust that with overwrites, first
+//! the category's and then the channel's own; within each, the `@everyone`
+//! overwrite applies first, then the member's roles (denies before allows),
+//! then the member's own. The owner and administrators can do everything;
+//! members in a timeout can only read along.
+//!
+//! Roles are ranked by their order in `Server::roles`, highest first; a
+//! member can only manage members and roles ranked below their highest role.
+
+use std::time::SystemTime;
+
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Channel, Server};
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct Permissions: u64 {
+        /// Everything, ignoring overwrites.
+        const ADMINISTRATOR = 1 << 0;
+        const VIEW_CHANNEL = 1 << 1;
+        const SEND_MESSAGES = 1 << 2;
+        const ATTACH_FILES = 1 << 3;
+        const ADD_REACTIONS = 1 << 4;
+        const MENTION_EVERYONE = 1 << 5;
+        const CREATE_THREADS = 1 << 6;
+        /// Delete and pin anyone's messages, and see their edit history.
+        const MANAGE_MESSAGES = 1 << 7;
+        const MANAGE_CHANNELS = 1 << 8;
+        const MANAGE_ROLES = 1 << 9;
+        const MANAGE_SERVER = 1 << 10;
+        const KICK_MEMBERS = 1 << 11;
+        const BAN_MEMBERS = 1 << 12;
+        /// Time members out.
+        const MODERATE_MEMBERS = 1 << 13;
+        const MANAGE_NICKNAMES = 1 << 14;
+        const CHANGE_NICKNAME = 1 << 15;
+        const VIEW_AUDIT_LOG = 1 << 16;
+        /// Join voice and stage channels.
+        const CONNECT = 1 << 17;
+        const SPEAK = 1 << 18;
+        const STREAM = 1 << 19;
+        const MUTE_MEMBERS = 1 << 20;
+        const DEAFEN_MEMBERS = 1 << 21;
+        const MOVE_MEMBERS = 1 << 22;
+    }
+}
+
+impl Default for Permissions {
+    /// What `@everyone` can do in a new server.
+    fn default() -> Self {
+        Self::VIEW_CHANNEL
+            | Self::SEND_MESSAGES
+            | Self::ATTACH_FILES
+            | Self::ADD_REACTIONS
+            | Self::CREATE_THREADS
+            | Self::CHANGE_NICKNAME
+            | Self::CONNECT
+            | Self::SPEAK
+            | Self::STREAM
+    }
+}
+
+impl Permissions {
+    /// What either side of a direct message can do.
+    pub fn direct_message() -> Self {
+        Self::VIEW_CHANNEL
+            | Self::SEND_MESSAGES
+            | Self::CREATE_THREADS
+            | Self::ATTACH_FILES
+            | Self::ADD_REACTIONS
+            | Self::CONNECT
+            | Self::SPEAK
+    }
+}
+
+/// A permission as shown in role and channel settings.
+pub struct PermissionInfo {
+    pub flag: Permissions,
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+const fn info(flag: Permissions, name: &'static str, description: &'static str) -> PermissionInfo {
+    PermissionInfo { flag, name, description }
+}
+
+/// Every permission, grouped by what it's about, in the order settings
+/// list them.
+pub const GROUPS: &[(&str, &[PermissionInfo])] = &[
+    (
+        "General",
+        &[
+            info(Permissions::VIEW_CHANNEL, "View Channels", "See channels and read their messages."),
+            info(Permissions::MANAGE_CHANNELS, "Manage Channels", "Create, edit and delete channels."),
+            info(Permissions::MANAGE_ROLES, "Manage Roles", "Create roles and edit or assign those below their highest role."),
+            info(Permissions::MANAGE_SERVER, "Manage Server", "Change the server's name, icon and emoji."),
+            info(Permissions::VIEW_AUDIT_LOG, "View Audit Log", "See who changed what in the server."),
+        ],
+    ),
+    (
+        "Membership",
+        &[
+            info(Permissions::CHANGE_NICKNAME, "Change Nickname", "Change their own nickname."),
+            info(Permissions::MANAGE_NICKNAMES, "Manage Nicknames", "Change other members' nicknames."),
+            info(Permissions::KICK_MEMBERS, "Kick Members", "Remove members, who can rejoin with an invite."),
+            info(Permissions::BAN_MEMBERS, "Ban Members", "Remove members for good."),
+            info(Permissions::MODERATE_MEMBERS, "Timeout Members", "Stop members from chatting, reacting or speaking for a while."),
+        ],
+    ),
+    (
+        "Text",
+        &[
+            info(Permissions::SEND_MESSAGES, "Send Messages", "Post in text channels and reply in threads."),
+            info(Permissions::CREATE_THREADS, "Create Threads", "Start threads from messages."),
+            info(Permissions::ATTACH_FILES, "Attach Files", "Upload files and images."),
+            info(Permissions::ADD_REACTIONS, "Add Reactions", "React to messages with new emoji."),
+            info(Permissions::MENTION_EVERYONE, "Mention @everyone and All Roles", "Notify everyone, and mention roles that aren't mentionable."),
+            info(Permissions::MANAGE_MESSAGES, "Manage Messages", "Delete and pin others' messages, and see their edit history."),
+        ],
+    ),
+    (
+        "Voice",
+        &[
+            info(Permissions::CONNECT, "Connect", "Join voice and stage channels."),
+            info(Permissions::SPEAK, "Speak", "Talk in voice channels."),
+            info(Permissions::STREAM, "Video", "Share their camera or screen."),
+            info(Permissions::MUTE_MEMBERS, "Mute Members", "Mute others in voice channels."),
+            info(Permissions::DEAFEN_MEMBERS, "Deafen Members", "Deafen others in voice channels."),
+            info(Permissions::MOVE_MEMBERS, "Move Members", "Move others between voice channels."),
+        ],
+    ),
+    (
+        "Advanced",
+        &[info(
+            Permissions::ADMINISTRATOR,
+            "Administrator",
+            "Every permission, in every channel, whatever the overwrites. Grant with care.",
+        )],
+    ),
+];
+
+/// Who an overwrite applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "id", rename_all = "snake_case")]
+pub enum OverwriteTarget {
+    Everyone,
+    Role(String),
+    Member(String),
+}
+
+impl OverwriteTarget {
+    /// How settings show the target: `@everyone`, `@Role` or the member's
+    /// tag, falling back to the ID if they're gone.
+    pub fn name(&self, server: &Server) -> String {
+        match self {
+            OverwriteTarget::Everyone => "@everyone".to_string(),
+            OverwriteTarget::Role(id) => server
+                .roles
+                .iter()
+                .find(|role| role.id == *id)
+                .map_or_else(|| id.clone(), |role| format!("@{}", role.name)),
+            OverwriteTarget::Member(id) => server
+                .members
+                .iter()
+                .find(|user| user.id == *id)
+                .map_or_else(|| id.clone(), |user| user.tag()),
+        }
+    }
+}
+
+/// Permissions granted or taken away in one channel or category.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionOverwrite {
+    pub target: OverwriteTarget,
+    #[serde(default = "Permissions::empty")]
+    pub allow: Permissions,
+    #[serde(default = "Permissions::empty")]
+    pub deny: Permissions,
+}
+
+/// What `user_id` may do in `server` outside of any channel, e.g. manage
+/// roles or kick members.
+pub fn server_permissions(server: &Server, user_id: &str) -> Permissions {
+    if server.owner_id.as_deref() == Some(user_id) {
+        return Permissions::all();
+    }
+    let held = server.member_roles.get(user_id);
+    let permissions = server
+        .roles
+        .iter()
+        .filter(|role| held.is_some_and(|held| held.contains(&role.id)))
+        .fold(server.everyone_permissions, |permissions, role| permissions | role.permissions);
+    if permissions.contains(Permissions::ADMINISTRATOR) {
+        return Permissions::all();
+    }
+    if is_timed_out(server, user_id) {
+        return permissions & Permissions::VIEW_CHANNEL;
+    }
+    permissions
+}
+
+fn is_timed_out(server: &Server, user_id: &str) -> bool {
+    server
+        .member_info
+        .get(user_id)
+        .is_some_and(|info| info.is_timed_out(SystemTime::now()))
+}
+
+/// What `user_id` may do in `channel` of `server`.
+pub fn compute_permissions(server: &Server, user_id: &str, channel: &Channel) -> Permissions {
+    let mut permissions = server_permissions(server, user_id);
+    if permissions == Permissions::all() {
+        return permissions;
+    }
+    let held = server.member_roles.get(user_id).map(Vec::as_slice).unwrap_or_default();
+    let category = server
+        .categories
+        .iter()
+        .find(|category| category.channels.iter().any(|c| c.id == channel.id));
+    for overwrites in category.map(|c| c.overwrites.as_slice()).into_iter().chain([channel.overwrites.as_slice()]) {
+        permissions = apply_overwrites(permissions, overwrites, user_id, held);
+    }
+
+    // What can't be seen or joined can't be used either.
+    if !permissions.contains(Permissions::VIEW_CHANNEL) {
+        return Permissions::empty();
+    }
+    if channel.is_voice_based() && !permissions.contains(Permissions::CONNECT) {
+        permissions.remove(Permissions::SPEAK | Permissions::STREAM | Permissions::MUTE_MEMBERS | Permissions::DEAFEN_MEMBERS | Permissions::MOVE_MEMBERS);
+    }
+    // Overwrites don't lift a timeout.
+    if is_timed_out(server, user_id) {
+        permissions &= Permissions::VIEW_CHANNEL;
+    }
+    permissions
+}
+
+fn apply_overwrites(
+    mut permissions: Permissions,
+    overwrites: &[PermissionOverwrite],
+    user_id: &str,
+    held: &[String],
+) -> Permissions {
+    let mut apply = |overwrite: &PermissionOverwrite| {
+        permissions.remove(overwrite.deny);
+        permissions.insert(overwrite.allow);
+    };
+    if let Some(everyone) = overwrites.iter().find(|o| o.target == OverwriteTarget::Everyone) {
+        apply(everyone);
+    }
+    let (allow, deny) = overwrites
+        .iter()
+        .filter(|o| matches!(&o.target, OverwriteTarget::Role(id) if held.contains(id)))
+        .fold((Permissions::empty(), Permissions::empty()), |(allow, deny), o| (allow | o.allow, deny | o.deny));
+    apply(&PermissionOverwrite {
+        target: OverwriteTarget::Everyone,
+        allow,
+        deny,
+    });
+    if let Some(member) = overwrites
+        .iter()
+        .find(|o| matches!(&o.target, OverwriteTarget::Member(id) if id == user_id))
+    {
+        apply(member);
+    }
+    permissions
+}
+
+/// Rank of `user_id`'s highest role, 0 being the server's highest; `None`
+/// without roles.
+fn rank(server: &Server, user_id: &str) -> Option<usize> {
+    let held = server.member_roles.get(user_id)?;
+    server.roles.iter().position(|role| held.contains(&role.id))
+}
+
+/// Whether `actor` ranks above `target`, so can kick, ban or change the
+/// roles of them. Nobody outranks the owner or themselves.
+pub fn outranks(server: &Server, actor: &str, target: &str) -> bool {
+    if actor == target || server.owner_id.as_deref() == Some(target) {
+        return false;
+    }
+    if server.owner_id.as_deref() == Some(actor) {
+        return true;
+    }
+    match (rank(server, actor), rank(server, target)) {
+        (Some(actor), Some(target)) => actor < target,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// Whether `actor` may edit, assign or delete the role `role_id`: they
+/// need Manage Roles and a higher role than it.
+pub fn can_manage_role(server: &Server, actor: &str, role_id: &str) -> bool {
+    if !server_permissions(server, actor).contains(Permissions::MANAGE_ROLES) {
+        return false;
+    }
+    if server.owner_id.as_deref() == Some(actor) {
+        return true;
+    }
+    let role = server.roles.iter().position(|role| role.id == role_id);
+    matches!((rank(server, actor), role), (Some(actor), Some(role)) if actor < role)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChannelCategory, ChannelKind, MemberInfo, Role};
+
+    fn role(id: &str, permissions: Permissions) -> Role {
+        Role {
+            id: id.to_string(),
+            name: id.to_string(),
+            color: "#99AAB5".to_string(),
+            permissions,
+            hoist: false,
+            mentionable: false,
+        }
+    }
+
+    fn channel(id: &str, kind: ChannelKind, overwrites: Vec<PermissionOverwrite>) -> Channel {
+        Channel {
+            id: id.to_string(),
+            name: id.to_string(),
+            kind,
+            topic: None,
+            slowmode_secs: 0,
+            nsfw: false,
+            members_connected: 0,
+            voice_participants: Vec::new(),
+            overwrites,
+        }
+    }
+
+    fn overwrite(target: OverwriteTarget, allow: Permissions, deny: Permissions) -> PermissionOverwrite {
+        PermissionOverwrite { target, allow, deny }
+    }
+
+    /// `owner` owns it; `admin`, `mod` and `member` hold the roles named
+    /// after them; `guest` holds none. The "staff" category is only for
+    /// moderators.
+    fn server() -> Server {
+        Server {
+            id: "s".to_string(),
+            name: "Server".to_string(),
+            owner_id: Some("owner".to_string()),
+            everyone_permissions: Permissions::default(),
+            categories: vec![
+                ChannelCategory {
+                    name: "Public".to_string(),
+                    channels: vec![
+                        channel("general", ChannelKind::Text, Vec::new()),
+                        channel(
+                            "announcements",
+                            ChannelKind::Announcement,
+                            vec![
+                                overwrite(OverwriteTarget::Everyone, Permissions::empty(), Permissions::SEND_MESSAGES),
+                                overwrite(OverwriteTarget::Role("mod".into()), Permissions::SEND_MESSAGES, Permissions::empty()),
+                            ],
+                        ),
+                        channel(
+                            "lounge",
+                            ChannelKind::Voice,
+                            vec![overwrite(
+                                OverwriteTarget::Member("member".into()),
+                                Permissions::empty(),
+                                Permissions::CONNECT,
+                            )],
+                        ),
+                    ],
+                    collapsed: false,
+                    overwrites: Vec::new(),
+                },
+                ChannelCategory {
+                    name: "Staff".to_string(),
+                    channels: vec![
+                        channel("staff-chat", ChannelKind::Text, Vec::new()),
+                        channel(
+                            "staff-open-house",
+                            ChannelKind::Text,
+                            vec![overwrite(OverwriteTarget::Everyone, Permissions::VIEW_CHANNEL, Permissions::empty())],
+                        ),
+                    ],
+                    collapsed: false,
+                    overwrites: vec![
+                        overwrite(OverwriteTarget::Everyone, Permissions::empty(), Permissions::VIEW_CHANNEL),
+                        overwrite(OverwriteTarget::Role("mod".into()), Permissions::VIEW_CHANNEL, Permissions::empty()),
+                    ],
+                },
+            ],
+            members: Vec::new(),
+            roles: vec![
+                role("admin", Permissions::ADMINISTRATOR),
+                role("mod", Permissions::MANAGE_MESSAGES | Permissions::KICK_MEMBERS | Permissions::MANAGE_ROLES),
+                role("member", Permissions::empty()),
+            ],
+            member_roles: [("admin", "admin"), ("mod", "mod"), ("member", "member")]
+                .into_iter()
+                .map(|(user, role)| (user.to_string(), vec![role.to_string()]))
+                .collect(),
+            member_info: Default::default(),
+            emojis: Vec::new(),
+        }
+    }
+
+    fn in_channel(user: &str, channel_id: &str) -> Permissions {
+        let server = server();
+        let channel = server.all_channels().into_iter().find(|c| c.id == channel_id).unwrap().clone();
+        compute_permissions(&server, user, &channel)
+    }
+
+    #[test]
+    fn test_roles_add_to_everyone() {
+        let server = server();
+        assert_eq!(server_permissions(&server, "guest"), Permissions::default());
+        assert_eq!(
+            server_permissions(&server, "mod"),
+            Permissions::default() | Permissions::MANAGE_MESSAGES | Permissions::KICK_MEMBERS | Permissions::MANAGE_ROLES
+        );
+        assert_eq!(server_permissions(&server, "owner"), Permissions::all());
+        assert_eq!(server_permissions(&server, "admin"), Permissions::all());
+    }
+
+    #[test]
+    fn test_channel_overwrites() {
+        assert!(in_channel("guest", "general").contains(Permissions::SEND_MESSAGES));
+        assert!(!in_channel("guest", "announcements").contains(Permissions::SEND_MESSAGES));
+        assert!(in_channel("guest", "announcements").contains(Permissions::VIEW_CHANNEL));
+        // A role's allow beats the @everyone deny.
+        assert!(in_channel("mod", "announcements").contains(Permissions::SEND_MESSAGES));
+        // Administrators and the owner ignore overwrites.
+        assert_eq!(in_channel("admin", "staff-chat"), Permissions::all());
+        assert_eq!(in_channel("owner", "staff-chat"), Permissions::all());
+    }
+
+    #[test]
+    fn test_timeouts_leave_only_reading() {
+        let mut server = server();
+        let general = server.all_channels()[0].clone();
+        let timeout = |until| MemberInfo {
+            timed_out_until: Some(until),
+            ..MemberInfo::default()
+        };
+        let now = SystemTime::now();
+        server.member_info.insert("mod".into(), timeout(now + std::time::Duration::from_secs(600)));
+        server.member_info.insert("member".into(), timeout(now - std::time::Duration::from_secs(600)));
+        server.member_info.insert("admin".into(), timeout(now + std::time::Duration::from_secs(600)));
+        assert_eq!(server_permissions(&server, "mod"), Permissions::VIEW_CHANNEL);
+        assert_eq!(compute_permissions(&server, "mod", &general), Permissions::VIEW_CHANNEL);
+        // Over already, or not applicable.
+        assert!(compute_permissions(&server, "member", &general).contains(Permissions::SEND_MESSAGES));
+        assert_eq!(server_permissions(&server, "admin"), Permissions::all());
+    }
+
+    #[test]
+    fn test_member_overwrites_apply_last() {
+        let lounge = in_channel("member", "lounge");
+        assert!(!lounge.contains(Permissions::CONNECT));
+        // Without joining there's no speaking either.
+        assert!(!lounge.contains(Permissions::SPEAK));
+        assert!(in_channel("guest", "lounge").contains(Permissions::CONNECT | Permissions::SPEAK));
+    }
+
+    #[test]
+    fn test_category_overwrites_apply_before_the_channels() {
+        // Hidden from everyone but moderators by the category...
+        assert_eq!(in_channel("guest", "staff-chat"), Permissions::empty());
+        assert!(in_channel("mod", "staff-chat").contains(Permissions::VIEW_CHANNEL | Permissions::SEND_MESSAGES));
+        // ...unless the channel opens itself up again.
+        assert!(in_channel("guest", "staff-open-house").contains(Permissions::VIEW_CHANNEL));
+    }
+
+    #[test]
+    fn test_role_denies_beat_role_allows_only_across_layers() {
+        let mut server = server();
+        server.member_roles.insert("both".into(), vec!["mod".into(), "member".into()]);
+        let channel = channel(
+            "c",
+            ChannelKind::Text,
+            vec![
+                overwrite(OverwriteTarget::Role("member".into()), Permissions::empty(), Permissions::ADD_REACTIONS),
+                overwrite(OverwriteTarget::Role("mod".into()), Permissions::ADD_REACTIONS, Permissions::empty()),
+            ],
+        );
+        // Within one layer, any of the member's roles allowing it wins.
+        assert!(compute_permissions(&server, "both", &channel).contains(Permissions::ADD_REACTIONS));
+        assert!(!compute_permissions(&server, "member", &channel).contains(Permissions::ADD_REACTIONS));
+    }
+
+    #[test]
+    fn test_groups_list_every_permission_once() {
+        let listed: Vec<Permissions> = GROUPS.iter().flat_map(|(_, infos)| infos.iter().map(|i| i.flag)).collect();
+        assert_eq!(listed.iter().fold(Permissions::empty(), |all, flag| all | *flag), Permissions::all());
+        assert_eq!(listed.len(), Permissions::all().iter().count());
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let server = server();
+        assert!(outranks(&server, "owner", "admin"));
+        assert!(outranks(&server, "admin", "mod"));
+        assert!(outranks(&server, "mod", "member"));
+        assert!(outranks(&server, "member", "guest"));
+        assert!(!outranks(&server, "mod", "admin"));
+        assert!(!outranks(&server, "admin", "owner"));
+        assert!(!outranks(&server, "mod", "mod"));
+        assert!(!outranks(&server, "guest", "member"));
+
+        assert!(can_manage_role(&server, "mod", "member"));
+        assert!(!can_manage_role(&server, "mod", "mod"));
+        assert!(!can_manage_role(&server, "mod", "admin"));
+        assert!(can_manage_role(&server, "admin", "mod"));
+        assert!(!can_manage_role(&server, "member", "member"));
+    }
+}
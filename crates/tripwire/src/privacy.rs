@@ -0,0 +1,74 @@
+//! Privacy settings, saved as `privacy.json` in the data directory and
+//! shared by all accounts.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings_file::{load_json_settings, save_json_settings};
+
+const FILE_NAME: &str = "privacy.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrivacySettings {
+    /// Fetch previews of links in messages, and the images of embeds. Each
+    /// fetch shows the site this machine's address.
+    #[serde(default = "enabled")]
+    pub link_previews: bool,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Default for PrivacySettings {
+    fn default() -> Self {
+        Self {
+            link_previews: true,
+            path: None,
+        }
+    }
+}
+
+impl PrivacySettings {
+    /// Load the settings saved in `dir`, starting from the defaults if there
+    /// are none.
+    pub fn load(dir: &Path) -> Self {
+        Self {
+            path: Some(dir.join(FILE_NAME)),
+            ..load_json_settings(dir, FILE_NAME)
+        }
+    }
+
+    pub fn set_link_previews(&mut self, enabled: bool) {
+        self.link_previews = enabled;
+        self.save();
+    }
+
+    fn save(&self) {
+        if let Some(path) = &self.path {
+            save_json_settings(path, self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_round_trips_through_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(PrivacySettings::load(dir.path()).link_previews);
+
+        PrivacySettings::load(dir.path()).set_link_previews(false);
+        assert!(!PrivacySettings::load(dir.path()).link_previews);
+
+        fs::write(dir.path().join("privacy.json"), "{}").unwrap();
+        assert!(PrivacySettings::load(dir.path()).link_previews);
+    }
+}
@@ -0,0 +1,164 @@
+//! Read state — how far the user has read each channel and DM, and the
+//! unread and mention counts past that point. The server keeps the
+//! authoritative copy; the message cache keeps a local one so badges are
+//! right before it answers.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::Message;
+
+/// Where the user has read up to in one conversation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReadState {
+    pub conversation_id: String,
+    /// Newest message read.
+    #[serde(default)]
+    pub last_read: Option<String>,
+    /// Messages from others after `last_read`.
+    #[serde(default)]
+    pub unread: usize,
+    /// How many of those mention the user.
+    #[serde(default)]
+    pub mentions: usize,
+}
+
+/// Read state of every conversation the account knows about.
+#[derive(Debug, Default)]
+pub struct ReadStates {
+    states: HashMap<String, ReadState>,
+}
+
+impl ReadStates {
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+
+    /// Take `states` as reported by the server or the cache, keeping the
+    /// state of conversations they don't mention.
+    pub fn merge(&mut self, states: impl IntoIterator<Item = ReadState>) {
+        for state in states {
+            self.states.insert(state.conversation_id.clone(), state);
+        }
+    }
+
+    pub fn last_read(&self, conversation_id: &str) -> Option<&str> {
+        self.states.get(conversation_id)?.last_read.as_deref()
+    }
+
+    pub fn unread(&self, conversation_id: &str) -> usize {
+        self.states.get(conversation_id).map_or(0, |s| s.unread)
+    }
+
+    pub fn mentions(&self, conversation_id: &str) -> usize {
+        self.states.get(conversation_id).map_or(0, |s| s.mentions)
+    }
+
+    /// Count a message that just arrived in `conversation_id`. Sending a
+    /// message reads everything before it. Returns the state if it changed.
+    pub fn note_message(&mut self, conversation_id: &str, message: &Message, user_id: &str) -> Option<&ReadState> {
+        if message.author.id == user_id {
+            return self.ack(conversation_id, &message.id);
+        }
+        let state = self.entry(conversation_id);
+        state.unread += 1;
+        if message.is_mentioned(user_id) {
+            state.mentions += 1;
+        }
+        Some(state)
+    }
+
+    /// Mark `conversation_id` read up to `message_id`. Returns the state if
+    /// it changed.
+    pub fn ack(&mut self, conversation_id: &str, message_id: &str) -> Option<&ReadState> {
+        let state = self.entry(conversation_id);
+        let already_read = state.last_read.as_deref() == Some(message_id) && state.unread == 0 && state.mentions == 0;
+        if already_read {
+            return None;
+        }
+        state.last_read = Some(message_id.to_string());
+        state.unread = 0;
+        state.mentions = 0;
+        Some(state)
+    }
+
+    fn entry(&mut self, conversation_id: &str) -> &mut ReadState {
+        self.states
+            .entry(conversation_id.to_string())
+            .or_insert_with(|| ReadState {
+                conversation_id: conversation_id.to_string(),
+                ..Default::default()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+    use crate::mock_data::make_user;
+    use crate::models::UserStatus;
+
+    fn message(id: &str, author: &str, mentions: &[&str]) -> Message {
+        Message {
+            id: id.into(),
+            author: make_user(author, author, "0001", UserStatus::Online),
+            content: String::new(),
+            timestamp: String::new(),
+            edited: false,
+            edited_timestamp: None,
+            revisions: Vec::new(),
+            attachments: Vec::new(),
+            embeds: Vec::new(),
+            reactions: HashMap::new(),
+            reply_to: None,
+            mentioned_users: mentions.iter().map(|m| m.to_string()).collect(),
+            pinned: false,
+            thread_id: None,
+            thread_count: 0,
+            created_at: UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_counts_unread_and_mentions_until_acked() {
+        let mut states = ReadStates::default();
+        states.note_message("c1", &message("m1", "u2", &[]), "me");
+        states.note_message("c1", &message("m2", "u2", &["me"]), "me");
+        states.note_message("c1", &message("m3", "u3", &["u2"]), "me");
+        assert_eq!((states.unread("c1"), states.mentions("c1")), (3, 1));
+        assert_eq!((states.unread("c2"), states.mentions("c2")), (0, 0));
+
+        let acked = states.ack("c1", "m3").cloned();
+        assert_eq!(acked.map(|s| (s.last_read, s.unread)), Some((Some("m3".into()), 0)));
+        assert_eq!(states.mentions("c1"), 0);
+        assert!(states.ack("c1", "m3").is_none());
+    }
+
+    #[test]
+    fn test_own_messages_mark_read() {
+        let mut states = ReadStates::default();
+        states.note_message("c1", &message("m1", "u2", &["me"]), "me");
+        states.note_message("c1", &message("m2", "me", &[]), "me");
+        assert_eq!((states.unread("c1"), states.mentions("c1")), (0, 0));
+        assert_eq!(states.last_read("c1"), Some("m2"));
+    }
+
+    #[test]
+    fn test_merge_replaces_reported_states() {
+        let mut states = ReadStates::default();
+        states.note_message("c1", &message("m1", "u2", &[]), "me");
+        states.note_message("c2", &message("m2", "u2", &[]), "me");
+        states.merge([ReadState {
+            conversation_id: "c1".into(),
+            last_read: Some("m0".into()),
+            unread: 4,
+            mentions: 2,
+        }]);
+        assert_eq!((states.unread("c1"), states.mentions("c1")), (4, 2));
+        assert_eq!(states.last_read("c1"), Some("m0"));
+        assert_eq!(states.unread("c2"), 1);
+    }
+}
@@ -0,0 +1,526 @@
+//! Role editing — changes to a server's roles and who holds them, staged
+//! until they're saved or discarded together.
+//!
+//! Editors can only touch roles ranked below their own highest role: those
+//! at or above it stay at the top of the list, unchanged, and nothing can
+//! be moved above them. Likewise they can only grant permissions they have.
+//! Saving sends only what the draft changed, as [`RoleChanges`], which the
+//! server checks against those same limits.
+
+use std::collections::HashMap;
+
+use anyhow::{Context as _, Result, bail};
+
+use crate::models::{Role, Server};
+use crate::permissions::{self, Permissions};
+
+/// Color of a new role.
+pub const DEFAULT_ROLE_COLOR: &str = "#99AAB5";
+
+#[derive(Debug, Clone)]
+pub struct RoleDraft {
+    roles: Vec<Role>,
+    member_roles: HashMap<String, Vec<String>>,
+    saved_roles: Vec<Role>,
+    saved_member_roles: HashMap<String, Vec<String>>,
+    /// How many roles at the top of the list the editor can't change.
+    locked: usize,
+    /// What the editor may grant.
+    grantable: Permissions,
+    can_create: bool,
+    next_id: usize,
+}
+
+impl RoleDraft {
+    /// Start editing `server`'s roles as `editor_id`.
+    pub fn new(server: &Server, editor_id: &str) -> Self {
+        let granted = permissions::server_permissions(server, editor_id);
+        let locked = server
+            .roles
+            .iter()
+            .position(|role| permissions::can_manage_role(server, editor_id, &role.id))
+            .unwrap_or(server.roles.len());
+        Self {
+            roles: server.roles.clone(),
+            member_roles: server.member_roles.clone(),
+            saved_roles: server.roles.clone(),
+            saved_member_roles: server.member_roles.clone(),
+            locked,
+            grantable: granted,
+            can_create: granted.contains(Permissions::MANAGE_ROLES),
+            next_id: 1,
+        }
+    }
+
+    /// Highest ranked first.
+    pub fn roles(&self) -> &[Role] {
+        &self.roles
+    }
+
+    pub fn role(&self, id: &str) -> Option<&Role> {
+        self.roles.iter().find(|role| role.id == id)
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.roles.iter().position(|role| role.id == id)
+    }
+
+    pub fn can_create(&self) -> bool {
+        self.can_create
+    }
+
+    pub fn can_edit(&self, id: &str) -> bool {
+        self.position(id).is_some_and(|ix| ix >= self.locked)
+    }
+
+    /// Whether the editor may turn `flag` on or off for roles they can
+    /// edit.
+    pub fn can_grant(&self, flag: Permissions) -> bool {
+        self.grantable.contains(flag)
+    }
+
+    /// Whether anything differs from what's saved.
+    pub fn is_dirty(&self) -> bool {
+        !self.changes().is_empty()
+    }
+
+    /// Add a role at the bottom of the list, returning its ID. New roles
+    /// get IDs the server replaces when they're saved.
+    pub fn create_role(&mut self) -> Option<String> {
+        if !self.can_create {
+            return None;
+        }
+        let id = format!("new-role-{}", self.next_id);
+        self.next_id += 1;
+        self.roles.push(Role {
+            id: id.clone(),
+            name: "new role".to_string(),
+            color: DEFAULT_ROLE_COLOR.to_string(),
+            permissions: Permissions::empty(),
+            hoist: false,
+            mentionable: false,
+        });
+        Some(id)
+    }
+
+    /// Change the role `id`, if the editor can. Permissions they can't
+    /// grant keep their saved state.
+    pub fn update(&mut self, id: &str, f: impl FnOnce(&mut Role)) {
+        if !self.can_edit(id) {
+            return;
+        }
+        let grantable = self.grantable;
+        let Some(role) = self.roles.iter_mut().find(|role| role.id == id) else {
+            return;
+        };
+        let before = role.permissions;
+        f(role);
+        role.permissions = (role.permissions & grantable) | (before & !grantable);
+    }
+
+    pub fn delete_role(&mut self, id: &str) {
+        if !self.can_edit(id) {
+            return;
+        }
+        self.roles.retain(|role| role.id != id);
+        for held in self.member_roles.values_mut() {
+            held.retain(|role_id| role_id != id);
+        }
+        self.member_roles.retain(|_, held| !held.is_empty());
+    }
+
+    /// Move the role `id` to position `to`, shifting those between. Roles
+    /// the editor can't edit stay where they are.
+    pub fn move_role(&mut self, id: &str, to: usize) {
+        let Some(from) = self.position(id).filter(|ix| *ix >= self.locked) else {
+            return;
+        };
+        let to = to.clamp(self.locked, self.roles.len() - 1);
+        let role = self.roles.remove(from);
+        self.roles.insert(to, role);
+    }
+
+    /// IDs of the members holding the role `id`.
+    pub fn holders(&self, id: &str) -> Vec<String> {
+        let mut holders: Vec<String> = self
+            .member_roles
+            .iter()
+            .filter(|(_, held)| held.iter().any(|role_id| role_id == id))
+            .map(|(user_id, _)| user_id.clone())
+            .collect();
+        holders.sort();
+        holders
+    }
+
+    /// Give `user_id` the role `id`, or take it away.
+    pub fn set_holder(&mut self, id: &str, user_id: &str, holds: bool) {
+        if !self.can_edit(id) {
+            return;
+        }
+        let held = self.member_roles.entry(user_id.to_string()).or_default();
+        let has = held.iter().any(|role_id| role_id == id);
+        if holds && !has {
+            held.push(id.to_string());
+        } else if !holds {
+            held.retain(|role_id| role_id != id);
+        }
+        if held.is_empty() {
+            self.member_roles.remove(user_id);
+        }
+    }
+
+    /// What the draft changes compared to what's saved.
+    pub fn changes(&self) -> RoleChanges {
+        let saved = |id: &str| self.saved_roles.iter().find(|role| role.id == id);
+        let created: Vec<Role> = self.roles.iter().filter(|role| saved(&role.id).is_none()).cloned().collect();
+        let updated = self
+            .roles
+            .iter()
+            .filter(|role| saved(&role.id).is_some_and(|saved| saved != *role))
+            .cloned()
+            .collect();
+        let deleted: Vec<String> = self
+            .saved_roles
+            .iter()
+            .filter(|role| self.position(&role.id).is_none())
+            .map(|role| role.id.clone())
+            .collect();
+
+        // New roles start at the bottom, so only a different order than
+        // that is a move.
+        let order: Vec<String> = self.roles.iter().map(|role| role.id.clone()).collect();
+        let unmoved: Vec<String> = self
+            .saved_roles
+            .iter()
+            .filter(|role| !deleted.contains(&role.id))
+            .chain(&created)
+            .map(|role| role.id.clone())
+            .collect();
+
+        let mut granted = Vec::new();
+        let mut revoked = Vec::new();
+        let mut holders: Vec<&String> = self.member_roles.keys().chain(self.saved_member_roles.keys()).collect();
+        holders.sort();
+        holders.dedup();
+        for user_id in holders {
+            let now = self.member_roles.get(user_id).map_or(&[][..], Vec::as_slice);
+            let before = self.saved_member_roles.get(user_id).map_or(&[][..], Vec::as_slice);
+            for role_id in now.iter().filter(|id| !before.contains(id)) {
+                granted.push((user_id.clone(), role_id.clone()));
+            }
+            // Deleting a role takes it from everyone anyway.
+            for role_id in before.iter().filter(|id| !now.contains(id) && !deleted.contains(id)) {
+                revoked.push((user_id.clone(), role_id.clone()));
+            }
+        }
+
+        RoleChanges {
+            created,
+            updated,
+            deleted,
+            order: (order != unmoved).then_some(order),
+            granted,
+            revoked,
+        }
+    }
+
+    /// Throw away every staged change.
+    pub fn discard(&mut self) {
+        self.roles = self.saved_roles.clone();
+        self.member_roles = self.saved_member_roles.clone();
+    }
+
+    /// Take what the server now has as saved, e.g. after saving, keeping
+    /// the editor's limits.
+    pub fn saved(&mut self, server: &Server) {
+        self.roles = server.roles.clone();
+        self.member_roles = server.member_roles.clone();
+        self.saved_roles = server.roles.clone();
+        self.saved_member_roles = server.member_roles.clone();
+    }
+}
+
+/// Changes to a server's roles and who holds them, as a draft makes them.
+/// Anything not mentioned stays as the server has it, so changes made
+/// elsewhere since the draft started aren't undone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoleChanges {
+    /// New roles, with the draft's IDs until the server gives them real
+    /// ones. They go at the bottom of the list.
+    pub created: Vec<Role>,
+    /// Roles whose settings changed, as they should be now.
+    pub updated: Vec<Role>,
+    pub deleted: Vec<String>,
+    /// The draft's roles in their new order, highest ranked first, if it
+    /// changed. Roles the draft doesn't list keep their places.
+    pub order: Option<Vec<String>>,
+    /// `(user_id, role_id)` pairs of roles given to members.
+    pub granted: Vec<(String, String)>,
+    /// `(user_id, role_id)` pairs of roles taken away from members.
+    pub revoked: Vec<(String, String)>,
+}
+
+impl RoleChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Make the changes to `server` on behalf of `actor_id`, giving new
+    /// roles IDs from `new_id`. Fails, changing nothing, unless the actor
+    /// may make every one of them: they need Manage Roles, may only touch
+    /// roles ranked below their own and may only grant or revoke
+    /// permissions they have.
+    pub fn apply(&self, server: &mut Server, actor_id: &str, mut new_id: impl FnMut() -> String) -> Result<()> {
+        let granted = permissions::server_permissions(server, actor_id);
+        if !granted.contains(Permissions::MANAGE_ROLES) {
+            bail!("missing permissions: {:?}", Permissions::MANAGE_ROLES);
+        }
+        let is_new = |id: &str| self.created.iter().any(|role| role.id == id);
+        let check_role = |id: &str| -> Result<()> {
+            let role = server.roles.iter().find(|role| role.id == id).with_context(|| format!("no role {id}"))?;
+            if !permissions::can_manage_role(server, actor_id, id) {
+                bail!("can't change the role {}", role.name);
+            }
+            Ok(())
+        };
+        for role in &self.created {
+            if !granted.contains(role.permissions) {
+                bail!("can't grant {:?}", role.permissions - granted);
+            }
+        }
+        for role in &self.updated {
+            check_role(&role.id)?;
+            let before = server.roles.iter().find(|r| r.id == role.id).map_or(Permissions::empty(), |r| r.permissions);
+            let changed = before ^ role.permissions;
+            if !granted.contains(changed) {
+                bail!("can't grant or revoke {:?}", changed - granted);
+            }
+        }
+        for id in &self.deleted {
+            check_role(id)?;
+        }
+        for (_, role_id) in self.granted.iter().chain(&self.revoked) {
+            if !is_new(role_id) {
+                check_role(role_id)?;
+            }
+        }
+
+        let mut next = server.clone();
+        let mut ids = HashMap::new();
+        for role in &self.created {
+            let id = new_id();
+            ids.insert(role.id.clone(), id.clone());
+            next.roles.push(Role { id, ..role.clone() });
+        }
+        let real_id = |id: &String| ids.get(id).unwrap_or(id).clone();
+        for role in &self.updated {
+            if let Some(existing) = next.roles.iter_mut().find(|r| r.id == role.id) {
+                *existing = role.clone();
+            }
+        }
+        next.roles.retain(|role| !self.deleted.contains(&role.id));
+        for held in next.member_roles.values_mut() {
+            held.retain(|id| !self.deleted.contains(id));
+        }
+
+        if let Some(order) = &self.order {
+            // The listed roles trade places among the slots they hold, so
+            // anything unlisted stays put.
+            let order: Vec<String> = order.iter().map(real_id).filter(|id| next.roles.iter().any(|r| r.id == *id)).collect();
+            let slots: Vec<usize> = (0..next.roles.len()).filter(|ix| order.contains(&next.roles[*ix].id)).collect();
+            if slots.len() != order.len() {
+                bail!("roles can only be listed once");
+            }
+            let listed: Vec<Role> = order.iter().filter_map(|id| next.roles.iter().find(|r| r.id == *id).cloned()).collect();
+            for (slot, role) in slots.into_iter().zip(listed) {
+                next.roles[slot] = role;
+            }
+            let unmanaged = server.roles.iter().filter(|role| !permissions::can_manage_role(server, actor_id, &role.id));
+            for role in unmanaged {
+                let kept = |r: &&Role| {
+                    server.roles.iter().any(|old| old.id == r.id) && next.roles.iter().any(|new| new.id == r.id)
+                };
+                let rank = |roles: &[Role]| roles.iter().filter(kept).position(|r| r.id == role.id);
+                if rank(&next.roles) != rank(&server.roles) {
+                    bail!("can't move roles around {}", role.name);
+                }
+            }
+        }
+
+        for (user_id, role_id) in &self.granted {
+            let held = next.member_roles.entry(user_id.clone()).or_default();
+            let role_id = real_id(role_id);
+            if !held.contains(&role_id) {
+                held.push(role_id);
+            }
+        }
+        for (user_id, role_id) in &self.revoked {
+            if let Some(held) = next.member_roles.get_mut(user_id) {
+                held.retain(|id| id != role_id);
+            }
+        }
+        next.member_roles.retain(|_, held| !held.is_empty());
+
+        *server = next;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn role(id: &str, permissions: Permissions) -> Role {
+        Role {
+            id: id.to_string(),
+            name: id.to_string(),
+            color: DEFAULT_ROLE_COLOR.to_string(),
+            permissions,
+            hoist: false,
+            mentionable: false,
+        }
+    }
+
+    /// `admin` > `mod` > `member`; the users of the same names hold them.
+    fn server() -> Server {
+        Server {
+            id: "s".to_string(),
+            name: "Server".to_string(),
+            owner_id: Some("owner".to_string()),
+            everyone_permissions: Permissions::default(),
+            categories: Vec::new(),
+            members: Vec::new(),
+            roles: vec![
+                role("admin", Permissions::ADMINISTRATOR),
+                role("mod", Permissions::MANAGE_ROLES | Permissions::KICK_MEMBERS),
+                role("member", Permissions::empty()),
+            ],
+            member_roles: [("admin", "admin"), ("mod", "mod"), ("member", "member")]
+                .into_iter()
+                .map(|(user, role)| (user.to_string(), vec![role.to_string()]))
+                .collect(),
+            member_info: Default::default(),
+            emojis: Vec::new(),
+        }
+    }
+
+    fn ids(draft: &RoleDraft) -> Vec<&str> {
+        draft.roles().iter().map(|role| role.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_edits_only_roles_below_the_editors() {
+        let mut draft = RoleDraft::new(&server(), "mod");
+        assert!(!draft.can_edit("admin") && !draft.can_edit("mod") && draft.can_edit("member"));
+        draft.update("admin", |role| role.name = "hacked".into());
+        draft.delete_role("mod");
+        assert!(!draft.is_dirty());
+
+        let owner = RoleDraft::new(&server(), "owner");
+        assert!(owner.can_edit("admin"));
+        let nobody = RoleDraft::new(&server(), "member");
+        assert!(!nobody.can_create() && !nobody.can_edit("member"));
+    }
+
+    #[test]
+    fn test_reorders_below_the_locked_roles() {
+        let mut draft = RoleDraft::new(&server(), "mod");
+        let new = draft.create_role().unwrap();
+        assert_eq!(ids(&draft), ["admin", "mod", "member", new.as_str()]);
+        draft.move_role(&new, 0);
+        assert_eq!(ids(&draft), ["admin", "mod", new.as_str(), "member"]);
+        draft.move_role("admin", 3);
+        assert_eq!(ids(&draft), ["admin", "mod", new.as_str(), "member"]);
+        draft.move_role(&new, 10);
+        assert_eq!(ids(&draft), ["admin", "mod", "member", new.as_str()]);
+    }
+
+    #[test]
+    fn test_grants_only_what_the_editor_has() {
+        let mut draft = RoleDraft::new(&server(), "mod");
+        draft.update("member", |role| role.permissions = Permissions::KICK_MEMBERS | Permissions::BAN_MEMBERS);
+        assert_eq!(draft.role("member").unwrap().permissions, Permissions::KICK_MEMBERS);
+        assert!(draft.can_grant(Permissions::SEND_MESSAGES) && !draft.can_grant(Permissions::ADMINISTRATOR));
+    }
+
+    #[test]
+    fn test_tracks_unsaved_changes() {
+        let mut draft = RoleDraft::new(&server(), "owner");
+        draft.set_holder("member", "mod", true);
+        assert!(draft.is_dirty());
+        assert_eq!(draft.holders("member"), ["member", "mod"]);
+        // Undoing by hand counts as no change.
+        draft.set_holder("member", "mod", false);
+        assert!(!draft.is_dirty());
+
+        draft.delete_role("mod");
+        assert!(draft.is_dirty());
+        assert!(draft.holders("mod").is_empty());
+        // No need to take a deleted role from those who held it.
+        assert!(draft.changes().revoked.is_empty());
+        draft.discard();
+        assert!(!draft.is_dirty());
+        assert_eq!(ids(&draft), ["admin", "mod", "member"]);
+    }
+
+    #[test]
+    fn test_sends_only_what_changed() {
+        let mut draft = RoleDraft::new(&server(), "mod");
+        assert!(draft.changes().is_empty());
+        let new = draft.create_role().unwrap();
+        draft.set_holder(&new, "member", true);
+        draft.update("member", |role| role.name = "regular".into());
+        let changes = draft.changes();
+        assert_eq!(changes.created.len(), 1);
+        assert_eq!(changes.updated[0].name, "regular");
+        assert_eq!(changes.order, None);
+        assert_eq!(changes.granted, [("member".to_string(), new.clone())]);
+
+        // Someone else gives out a role meanwhile; saving keeps it.
+        let mut server = server();
+        server.member_roles.insert("newcomer".to_string(), vec!["member".to_string()]);
+        let mut next_id = 0;
+        changes
+            .apply(&mut server, "mod", || {
+                next_id += 1;
+                format!("role-{next_id}")
+            })
+            .unwrap();
+        assert_eq!(server.roles[2].name, "regular");
+        assert_eq!(server.roles[3].id, "role-1");
+        assert_eq!(server.member_roles["member"], ["member", "role-1"]);
+        assert_eq!(server.member_roles["newcomer"], ["member"]);
+    }
+
+    #[test]
+    fn test_applies_only_within_the_editors_limits() {
+        let apply = |changes: RoleChanges| {
+            let mut server = server();
+            let result = changes.apply(&mut server, "mod", || "role-1".to_string());
+            (result, server)
+        };
+        let mut admin = role("admin", Permissions::ADMINISTRATOR);
+        admin.name = "hacked".into();
+        let forged = [
+            RoleChanges { updated: vec![admin], ..Default::default() },
+            RoleChanges { updated: vec![role("member", Permissions::BAN_MEMBERS)], ..Default::default() },
+            RoleChanges { created: vec![role("new", Permissions::ADMINISTRATOR)], ..Default::default() },
+            RoleChanges { deleted: vec!["mod".into()], ..Default::default() },
+            RoleChanges { granted: vec![("member".into(), "admin".into())], ..Default::default() },
+            RoleChanges {
+                order: Some(vec!["member".into(), "admin".into(), "mod".into()]),
+                ..Default::default()
+            },
+        ];
+        for changes in forged {
+            let (result, after) = apply(changes.clone());
+            assert!(result.is_err(), "{changes:?}");
+            assert_eq!(after.roles, server().roles);
+        }
+
+        let (result, _) = apply(RoleChanges { deleted: vec!["member".into()], ..Default::default() });
+        assert!(result.is_ok());
+        let (result, _) = apply(RoleChanges { granted: vec![("x".into(), "member".into())], ..Default::default() });
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,452 @@
+//! Message search queries.
+//!
+//! A query is free text mixed with Discord-style filters:
+//!
+//! ```text
+//! deploy "release notes" from:alice in:#general has:image mentions:bob
+//! before:2025-06-01 after:2025-05-01 pinned:true
+//! ```
+//!
+//! Text terms are looked up in the message cache's full-text index (see
+//! `MessageStore::search`); the filters then narrow the hits down. Anything
+//! that isn't a known filter is searched for as text.
+
+use std::fmt;
+use std::ops::Range;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::models::Message;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A parsed search query.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Words and quoted phrases that must all appear.
+    pub terms: Vec<String>,
+    /// Author's username.
+    pub from: Option<String>,
+    /// Channel name or DM recipient the message was sent in.
+    pub channel: Option<String>,
+    pub has_image: bool,
+    /// Username of someone the message mentions.
+    pub mentions: Option<String>,
+    /// Sent before the start of this day (UTC).
+    pub before: Option<SystemTime>,
+    /// Sent after the end of this day (UTC).
+    pub after: Option<SystemTime>,
+    pub pinned: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// `before:`/`after:` with something that isn't a `YYYY-MM-DD` date.
+    InvalidDate { filter: &'static str, value: String },
+    /// A filter value that isn't one of the accepted ones.
+    InvalidValue {
+        filter: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueryError::InvalidDate { filter, value } => {
+                write!(f, "{filter}: expects a date like 2025-01-31, not \"{value}\".")
+            }
+            QueryError::InvalidValue {
+                filter,
+                value,
+                expected,
+            } => write!(f, "{filter}: expects {expected}, not \"{value}\"."),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        let mut query = SearchQuery::default();
+        for token in tokenize(input) {
+            let filter = (!token.starts_with('"'))
+                .then(|| token.split_once(':'))
+                .flatten()
+                .map(|(key, value)| (key.to_ascii_lowercase(), unquote(value)))
+                .filter(|(_, value)| !value.is_empty());
+            match filter.as_ref().map(|(key, value)| (key.as_str(), value.as_str())) {
+                Some(("from", value)) => query.from = Some(value.trim_start_matches('@').into()),
+                Some(("in", value)) => {
+                    query.channel = Some(value.trim_start_matches(['#', '@']).into())
+                }
+                Some(("mentions", value)) => {
+                    query.mentions = Some(value.trim_start_matches('@').into())
+                }
+                Some(("has", value)) => {
+                    if !value.eq_ignore_ascii_case("image") {
+                        return Err(QueryError::InvalidValue {
+                            filter: "has",
+                            value: value.into(),
+                            expected: "\"image\"",
+                        });
+                    }
+                    query.has_image = true;
+                }
+                Some(("pinned", value)) => {
+                    query.pinned = Some(match value.to_ascii_lowercase().as_str() {
+                        "true" => true,
+                        "false" => false,
+                        _ => {
+                            return Err(QueryError::InvalidValue {
+                                filter: "pinned",
+                                value: value.into(),
+                                expected: "true or false",
+                            });
+                        }
+                    })
+                }
+                Some(("before", value)) => {
+                    query.before = Some(parse_date(value).ok_or_else(|| {
+                        QueryError::InvalidDate {
+                            filter: "before",
+                            value: value.into(),
+                        }
+                    })?)
+                }
+                Some(("after", value)) => {
+                    let day = parse_date(value).ok_or_else(|| QueryError::InvalidDate {
+                        filter: "after",
+                        value: value.into(),
+                    })?;
+                    query.after = Some(day + Duration::from_secs(SECONDS_PER_DAY));
+                }
+                _ => {
+                    let term = unquote(&token);
+                    if !term.trim().is_empty() {
+                        query.terms.push(term.trim().to_string());
+                    }
+                }
+            }
+        }
+        Ok(query)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == SearchQuery::default()
+    }
+
+    /// The terms as an FTS5 match expression: every term (or phrase) must
+    /// appear, each matching as a prefix. `None` without terms.
+    pub fn fts_expression(&self) -> Option<String> {
+        if self.terms.is_empty() {
+            return None;
+        }
+        let phrases: Vec<String> = self
+            .terms
+            .iter()
+            .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+            .collect();
+        Some(phrases.join(" "))
+    }
+
+    /// Whether `message` passes the filters other than `in:`, which is up to
+    /// the caller. `mentioned_ids` are the ids of the users `mentions:` names.
+    pub fn matches_filters(&self, message: &Message, mentioned_ids: &[String]) -> bool {
+        if let Some(from) = &self.from
+            && !(message.author.username.eq_ignore_ascii_case(from)
+                || message.author.tag().eq_ignore_ascii_case(from))
+        {
+            return false;
+        }
+        if let Some(mentions) = &self.mentions
+            && !message
+                .mentioned_users
+                .iter()
+                .any(|id| id == mentions || mentioned_ids.contains(id))
+        {
+            return false;
+        }
+        if self.has_image && !message.attachments.iter().any(|a| a.is_image()) {
+            return false;
+        }
+        if self.pinned.is_some_and(|pinned| pinned != message.pinned) {
+            return false;
+        }
+        if self.before.is_some_and(|before| message.created_at >= before) {
+            return false;
+        }
+        if self.after.is_some_and(|after| message.created_at < after) {
+            return false;
+        }
+        true
+    }
+
+    /// Whether every term appears in `content`. Only for messages that
+    /// aren't in the index.
+    pub fn matches_text(&self, content: &str) -> bool {
+        self.terms
+            .iter()
+            .all(|term| find_ignore_ascii_case(content, term).next().is_some())
+    }
+}
+
+/// Split on whitespace, keeping quoted runs (quotes included) together.
+fn tokenize(input: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn unquote(value: &str) -> String {
+    value.replace('"', "")
+}
+
+/// Counting days walks the years one by one, so keep them to four digits.
+const MAX_YEAR: u64 = 9999;
+
+/// Midnight UTC at the start of a `YYYY-MM-DD` day.
+fn parse_date(value: &str) -> Option<SystemTime> {
+    let mut parts = value.splitn(3, '-');
+    let year: u64 = parts.next()?.parse().ok()?;
+    let month: u64 = parts.next()?.parse().ok()?;
+    let day: u64 = parts.next()?.parse().ok()?;
+    if !(1970..=MAX_YEAR).contains(&year) || !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    UNIX_EPOCH.checked_add(Duration::from_secs(days_since_epoch(year, month, day) * SECONDS_PER_DAY))
+}
+
+fn is_leap_year(year: u64) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    let days_before_year: u64 = (1970..year)
+        .map(|y| if is_leap_year(y) { 366 } else { 365 })
+        .sum();
+    let days_before_month: u64 = (1..month).map(|m| days_in_month(year, m)).sum();
+    days_before_year + days_before_month + day - 1
+}
+
+// ── Snippets ─────────────────────────────────────────────────────────────────
+
+/// An excerpt of a message around what matched, for the results list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snippet {
+    pub text: String,
+    /// Byte ranges of `text` to highlight, in order and non-overlapping.
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// Cut `content` down to about `max_len` bytes around the first match of
+/// any of `terms`, marking every match.
+pub fn snippet(content: &str, terms: &[String], max_len: usize) -> Snippet {
+    // Same byte length, so match ranges stay valid.
+    let content = content.replace(['\n', '\r', '\t'], " ");
+    let mut matches: Vec<Range<usize>> = terms
+        .iter()
+        .flat_map(|term| find_ignore_ascii_case(&content, term))
+        .collect();
+    matches.sort_by_key(|r| (r.start, r.end));
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in matches {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    if content.len() <= max_len {
+        return Snippet {
+            text: content,
+            highlights: merged,
+        };
+    }
+
+    let first = merged.first().map_or(0, |r| r.start);
+    let mut start = floor_char_boundary(&content, first.saturating_sub(max_len / 4));
+    // Start on a word if there's one close by.
+    if start > 0
+        && let Some(space) = content[start..first].find(' ')
+    {
+        start += space + 1;
+    }
+    let end = floor_char_boundary(&content, (start + max_len).min(content.len()));
+
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < content.len() { "…" } else { "" };
+    let text = format!("{prefix}{}{suffix}", &content[start..end]);
+    let highlights = merged
+        .into_iter()
+        .filter(|r| r.start < end && r.end > start)
+        .map(|r| {
+            let offset = prefix.len();
+            r.start.max(start) - start + offset..r.end.min(end) - start + offset
+        })
+        .collect();
+    Snippet { text, highlights }
+}
+
+/// Byte ranges where `needle` appears in `haystack`, ignoring ASCII case.
+fn find_ignore_ascii_case<'a>(
+    haystack: &'a str,
+    needle: &'a str,
+) -> impl Iterator<Item = Range<usize>> + 'a {
+    let len = needle.len();
+    haystack
+        .char_indices()
+        .map(|(ix, _)| ix)
+        .filter(move |&ix| {
+            len > 0
+                && ix + len <= haystack.len()
+                && haystack.is_char_boundary(ix + len)
+                && haystack.as_bytes()[ix..ix + len].eq_ignore_ascii_case(needle.as_bytes())
+        })
+        .map(move |ix| ix..ix + len)
+}
+
+fn floor_char_boundary(s: &str, mut ix: usize) -> usize {
+    while !s.is_char_boundary(ix) {
+        ix -= 1;
+    }
+    ix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_data;
+
+    fn date(value: &str) -> SystemTime {
+        parse_date(value).unwrap()
+    }
+
+    #[test]
+    fn test_parses_filters_and_terms() {
+        let query = SearchQuery::parse(
+            r#"deploy "release notes" from:@alice in:#general has:image mentions:bob pinned:TRUE before:2025-06-01 after:2025-05-01 what:ever"#,
+        )
+        .unwrap();
+        assert_eq!(query.terms, ["deploy", "release notes", "what:ever"]);
+        assert_eq!(query.from.as_deref(), Some("alice"));
+        assert_eq!(query.channel.as_deref(), Some("general"));
+        assert!(query.has_image);
+        assert_eq!(query.mentions.as_deref(), Some("bob"));
+        assert_eq!(query.pinned, Some(true));
+        assert_eq!(query.before, Some(date("2025-06-01")));
+        assert_eq!(query.after, Some(date("2025-05-02")));
+
+        let query = SearchQuery::parse(r#"from:"Jane Doe" "from:nobody" in:"#).unwrap();
+        assert_eq!(query.from.as_deref(), Some("Jane Doe"));
+        assert_eq!(query.terms, ["from:nobody", "in:"]);
+        assert!(SearchQuery::parse("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_bad_filter_values() {
+        assert_eq!(
+            SearchQuery::parse("before:yesterday"),
+            Err(QueryError::InvalidDate {
+                filter: "before",
+                value: "yesterday".into()
+            })
+        );
+        assert!(SearchQuery::parse("after:2025-02-29").is_err());
+        assert!(SearchQuery::parse("after:2024-02-29").is_ok());
+        assert!(SearchQuery::parse("after:9999-12-31").is_ok());
+        assert!(SearchQuery::parse("before:10000-01-01").is_err());
+        assert!(SearchQuery::parse("before:99999999999-01-01").is_err());
+        assert!(SearchQuery::parse("has:video").is_err());
+        assert!(SearchQuery::parse("pinned:maybe").is_err());
+    }
+
+    #[test]
+    fn test_dates_are_utc_days() {
+        assert_eq!(date("1970-01-01"), UNIX_EPOCH);
+        assert_eq!(date("2000-03-01"), UNIX_EPOCH + Duration::from_secs(951_868_800));
+        assert_eq!(date("2024-12-31"), UNIX_EPOCH + Duration::from_secs(1_735_603_200));
+    }
+
+    #[test]
+    fn test_fts_expression_quotes_terms() {
+        let query = SearchQuery::parse(r#"hello "say ""hi" x*"#).unwrap();
+        assert_eq!(
+            query.fts_expression().as_deref(),
+            Some(r#""hello"* "say hi"* "x*"*"#)
+        );
+        assert_eq!(SearchQuery::parse("from:a").unwrap().fts_expression(), None);
+    }
+
+    #[test]
+    fn test_matches_filters() {
+        let mut message = mock_data::make_messages_for("103").remove(0);
+        message.author.username = "Alice".into();
+        message.mentioned_users = vec!["u2".into()];
+        message.created_at = date("2025-05-10");
+        message.attachments.clear();
+
+        let matches = |input: &str| {
+            SearchQuery::parse(input)
+                .unwrap()
+                .matches_filters(&message, &["u2".to_string()])
+        };
+        assert!(matches("from:alice mentions:bob after:2025-05-09 before:2025-05-11"));
+        assert!(!matches("from:bob"));
+        assert!(!matches("after:2025-05-10"));
+        assert!(!matches("before:2025-05-10"));
+        assert!(!matches("has:image"));
+        assert_eq!(matches("pinned:true"), message.pinned);
+        assert!(
+            !SearchQuery::parse("mentions:bob")
+                .unwrap()
+                .matches_filters(&message, &[])
+        );
+    }
+
+    #[test]
+    fn test_snippet_highlights_matches() {
+        let terms = vec!["rust".to_string(), "GPUI app".to_string()];
+        let snippet = super::snippet("I like Rust and\nthe gpui app", &terms, 100);
+        assert_eq!(snippet.text, "I like Rust and the gpui app");
+        assert_eq!(snippet.highlights, [7..11, 20..28]);
+
+        let long = format!("{} needle {}", "word ".repeat(40), "tail ".repeat(40));
+        let snippet = super::snippet(&long, &["needle".to_string()], 40);
+        assert!(snippet.text.starts_with('…') && snippet.text.ends_with('…'));
+        assert!(snippet.text.len() <= 40 + 2 * "…".len());
+        let range = snippet.highlights[0].clone();
+        assert_eq!(&snippet.text[range], "needle");
+
+        let snippet = super::snippet("héllo wörld", &["wörld".to_string()], 100);
+        assert_eq!(&snippet.text[snippet.highlights[0].clone()], "wörld");
+    }
+}
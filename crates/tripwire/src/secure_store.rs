@@ -0,0 +1,310 @@
+//! Storage for session secrets.
+//!
+//! `AuthState` never writes tokens to disk itself; it hands the serialized
+//! session to a [`SecretStore`]. [`default_store`] prefers the OS keyring
+//! (Keychain, Credential Manager, Secret Service) and falls back to an
+//! [`EncryptedFileStore`] when no keyring is reachable, e.g. on a headless
+//! Linux box without a Secret Service daemon.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context as _, Result, anyhow, bail};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::{Zeroize as _, Zeroizing};
+
+const KEYRING_SERVICE: &str = "tripwire";
+const KEYRING_ACCOUNT: &str = "session";
+
+/// A single opaque secret, stored somewhere safer than a plain file.
+pub trait SecretStore: Send + Sync {
+    /// Short name for logs.
+    fn name(&self) -> &'static str;
+
+    /// The stored secret, or `None` if nothing has been saved.
+    fn load(&self) -> Result<Option<Zeroizing<Vec<u8>>>>;
+
+    fn save(&self, secret: &[u8]) -> Result<()>;
+
+    /// Erase the secret. Succeeds if there was nothing to erase.
+    fn wipe(&self) -> Result<()>;
+}
+
+impl fmt::Debug for dyn SecretStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The OS keyring if it works on this machine, else an encrypted file in
+/// `data_dir`.
+pub fn default_store(data_dir: &Path) -> Box<dyn SecretStore> {
+    match KeyringStore::new() {
+        Ok(store) => Box::new(store),
+        Err(err) => {
+            eprintln!("OS keyring unavailable ({err:#}); using encrypted file storage");
+            Box::new(EncryptedFileStore::new(data_dir))
+        }
+    }
+}
+
+// ── Keyring ─────────────────────────────────────────────────────────────────
+
+pub struct KeyringStore {
+    entry: keyring::Entry,
+}
+
+impl KeyringStore {
+    /// Open the Tripwire entry, failing if the platform keyring can't be
+    /// reached at all.
+    pub fn new() -> Result<Self> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_ACCOUNT)?;
+        // A lookup is the cheapest way to find out whether a keyring daemon
+        // is actually there; a missing entry is fine.
+        match entry.get_secret() {
+            Ok(mut secret) => secret.zeroize(),
+            Err(keyring::Error::NoEntry) => {}
+            Err(err) => return Err(err.into()),
+        }
+        Ok(Self { entry })
+    }
+}
+
+impl SecretStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn load(&self) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        match self.entry.get_secret() {
+            Ok(secret) => Ok(Some(Zeroizing::new(secret))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn save(&self, secret: &[u8]) -> Result<()> {
+        Ok(self.entry.set_secret(secret)?)
+    }
+
+    fn wipe(&self) -> Result<()> {
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+// ── Encrypted file ──────────────────────────────────────────────────────────
+
+/// Leading bytes of an encrypted session file, bumped if the format changes.
+const FILE_MAGIC: &[u8; 4] = b"TWS1";
+const NONCE_LEN: usize = 12;
+const LOCAL_SECRET_LEN: usize = 32;
+
+/// AES-256-GCM encrypted file. The key is derived (HKDF-SHA256) from a
+/// random local secret kept next to it, salted with the machine id where the
+/// OS has one, so copying both files to another machine isn't enough to
+/// read them. Both files are created with mode 0600.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    secret_path: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            path: dir.join("session.enc"),
+            secret_path: dir.join("local.secret"),
+        }
+    }
+
+    /// Derive the file key, creating the local secret if `create` is set.
+    fn key(&self, create: bool) -> Result<Option<Zeroizing<[u8; 32]>>> {
+        let secret = match fs::read(&self.secret_path) {
+            Ok(secret) => Zeroizing::new(secret),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && create => {
+                let secret = Zeroizing::new(Aes256Gcm::generate_key(&mut OsRng).to_vec());
+                write_private(&self.secret_path, &secret)?;
+                secret
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context("reading local secret"),
+        };
+        if secret.len() != LOCAL_SECRET_LEN {
+            bail!("local secret {} is corrupt", self.secret_path.display());
+        }
+
+        let salt = machine_id();
+        let hkdf = Hkdf::<Sha256>::new(salt.as_deref(), &secret);
+        let mut key = Zeroizing::new([0u8; 32]);
+        hkdf.expand(b"tripwire session file v1", key.as_mut())
+            .map_err(|_| anyhow!("key derivation failed"))?;
+        Ok(Some(key))
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted file"
+    }
+
+    fn load(&self) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).context("reading session file"),
+        };
+        let Some(key) = self.key(false)? else {
+            bail!("session file exists but its local secret is missing");
+        };
+
+        let body = contents
+            .strip_prefix(FILE_MAGIC.as_slice())
+            .filter(|body| body.len() > NONCE_LEN)
+            .context("session file has an unknown format")?;
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_ref()));
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("session file could not be decrypted"))?;
+        Ok(Some(Zeroizing::new(plaintext)))
+    }
+
+    fn save(&self, secret: &[u8]) -> Result<()> {
+        let key = self.key(true)?.context("local secret missing")?;
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_ref()));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, secret)
+            .map_err(|_| anyhow!("encrypting session failed"))?;
+
+        let mut contents = Vec::with_capacity(FILE_MAGIC.len() + NONCE_LEN + ciphertext.len());
+        contents.extend_from_slice(FILE_MAGIC);
+        contents.extend_from_slice(&nonce);
+        contents.extend_from_slice(&ciphertext);
+        write_private(&self.path, &contents)
+    }
+
+    fn wipe(&self) -> Result<()> {
+        shred(&self.path)?;
+        shred(&self.secret_path)
+    }
+}
+
+/// Write `contents` to `path`, readable only by the current user.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt as _;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    // `mode` only applies on creation; tighten files left by older builds.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Overwrite a file with zeros before removing it. Missing files are fine.
+pub fn shred(path: &Path) -> Result<()> {
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    if let Ok(mut file) = OpenOptions::new().write(true).open(path) {
+        let zeros = vec![0u8; len as usize];
+        file.write_all(&zeros).ok();
+        file.sync_all().ok();
+    }
+    fs::remove_file(path).with_context(|| format!("removing {}", path.display()))
+}
+
+/// Stable per-machine identifier, used as key-derivation salt.
+fn machine_id() -> Option<Vec<u8>> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| fs::read(path).ok())
+        .map(|id| id.trim_ascii().to_vec())
+        .filter(|id| !id.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_file_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EncryptedFileStore::new(dir.path());
+        assert_eq!(store.load().unwrap(), None);
+
+        store.save(b"token_secret_value").unwrap();
+        let on_disk = fs::read(dir.path().join("session.enc")).unwrap();
+        assert!(on_disk.starts_with(FILE_MAGIC));
+        assert!(
+            !on_disk
+                .windows(b"token_secret_value".len())
+                .any(|w| w == b"token_secret_value")
+        );
+        assert_eq!(store.load().unwrap().unwrap().as_slice(), b"token_secret_value");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_encrypted_files_are_private() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = EncryptedFileStore::new(dir.path());
+        store.save(b"secret").unwrap();
+        for name in ["session.enc", "local.secret"] {
+            let mode = fs::metadata(dir.path().join(name)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_tampered_file_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EncryptedFileStore::new(dir.path());
+        store.save(b"secret").unwrap();
+
+        let path = dir.path().join("session.enc");
+        let mut contents = fs::read(&path).unwrap();
+        *contents.last_mut().unwrap() ^= 1;
+        fs::write(&path, contents).unwrap();
+        assert!(store.load().is_err());
+    }
+
+    #[test]
+    fn test_wipe_removes_file_and_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = EncryptedFileStore::new(dir.path());
+        store.save(b"secret").unwrap();
+        store.wipe().unwrap();
+
+        assert!(!dir.path().join("session.enc").exists());
+        assert!(!dir.path().join("local.secret").exists());
+        assert_eq!(store.load().unwrap(), None);
+        store.wipe().unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+//! Settings kept as a JSON file in the data directory. A missing or
+//! unreadable file means the defaults; failing to save is only logged.
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Load `dir/name`, starting from the defaults if there is none.
+pub fn load_json_settings<T: DeserializeOwned + Default>(dir: &Path, name: &str) -> T {
+    let path = dir.join(name);
+    match fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|err| {
+            eprintln!("Ignoring unreadable {}: {err}", path.display());
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Save `settings` to `path`, creating its directory if it isn't there yet.
+pub fn save_json_settings<T: Serialize>(path: &Path, settings: &T) {
+    let result = serde_json::to_string(settings).map_err(anyhow::Error::from).and_then(|json| {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(fs::write(path, json)?)
+    });
+    if let Err(err) = result {
+        eprintln!("Failed to save {}: {err:#}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn test_falls_back_to_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let loaded: HashMap<String, u32> = load_json_settings(dir.path(), "counts.json");
+        assert!(loaded.is_empty());
+
+        save_json_settings(&dir.path().join("counts.json"), &HashMap::from([("a", 1)]));
+        let loaded: HashMap<String, u32> = load_json_settings(dir.path(), "counts.json");
+        assert_eq!(loaded["a"], 1);
+
+        fs::write(dir.path().join("counts.json"), "not json").unwrap();
+        let loaded: HashMap<String, u32> = load_json_settings(dir.path(), "counts.json");
+        assert!(loaded.is_empty());
+    }
+
+    #[test]
+    fn test_saves_into_a_missing_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().join("not yet");
+        save_json_settings(&data_dir.join("counts.json"), &HashMap::from([("a", 1)]));
+        let loaded: HashMap<String, u32> = load_json_settings(&data_dir, "counts.json");
+        assert_eq!(loaded["a"], 1);
+    }
+}
@@ -0,0 +1,209 @@
+//! Time-based one-time passwords (RFC 6238) and backup codes.
+//!
+//! Pure functions only — no clock or network access — so everything here
+//! can be tested offline against the RFC test vectors. Callers pass the
+//! current Unix time in.
+
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::rand_core::RngCore as _;
+use data_encoding::BASE32_NOPAD;
+use gpui::http_client::Url;
+use hmac::{Hmac, Mac as _};
+use sha1::Sha1;
+
+/// Digits in a code, as shown by every mainstream authenticator app.
+pub const DIGITS: u32 = 6;
+/// Seconds each code is valid for.
+pub const STEP_SECS: u64 = 30;
+/// Codes from this many steps before or after now are also accepted, to
+/// tolerate clock drift between the phone and the server.
+const ALLOWED_SKEW: u64 = 1;
+/// Length of generated secrets in bytes (160 bits, as RFC 4226 recommends).
+const SECRET_LEN: usize = 20;
+
+const BACKUP_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// A fresh random shared secret.
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Base32 form of `secret`, as typed into authenticator apps.
+pub fn encode_secret(secret: &[u8]) -> String {
+    BASE32_NOPAD.encode(secret)
+}
+
+/// Parse a base32 secret, ignoring case, spaces and padding.
+pub fn decode_secret(encoded: &str) -> Option<Vec<u8>> {
+    let normalized: String = encoded
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '=' && *c != '-')
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    BASE32_NOPAD.decode(normalized.as_bytes()).ok()
+}
+
+/// HOTP (RFC 4226) value for `counter`, truncated to `digits` digits.
+pub fn hotp(secret: &[u8], counter: u64, digits: u32) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    binary % 10u32.pow(digits)
+}
+
+/// The code an authenticator shows at `unix_time`.
+pub fn code_at(secret: &[u8], unix_time: u64) -> String {
+    format!(
+        "{:0width$}",
+        hotp(secret, unix_time / STEP_SECS, DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+/// Whether `code` is valid at `unix_time`, allowing for a step of clock
+/// drift either way.
+pub fn verify(secret: &[u8], code: &str, unix_time: u64) -> bool {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    let step = unix_time / STEP_SECS;
+    (step.saturating_sub(ALLOWED_SKEW)..=step + ALLOWED_SKEW).any(|counter| {
+        let expected = format!(
+            "{:0width$}",
+            hotp(secret, counter, DIGITS),
+            width = DIGITS as usize
+        );
+        constant_time_eq(expected.as_bytes(), code.as_bytes())
+    })
+}
+
+/// `otpauth://` URI for enrolling `account` in an authenticator app, usually
+/// shown as a QR code.
+pub fn otpauth_uri(secret: &[u8], account: &str, issuer: &str) -> String {
+    let mut url = Url::parse("otpauth://totp").expect("static URL is valid");
+    url.path_segments_mut()
+        .expect("otpauth URL has a host")
+        .push(&format!("{issuer}:{account}"));
+    url.query_pairs_mut()
+        .append_pair("secret", &encode_secret(secret))
+        .append_pair("issuer", issuer)
+        .append_pair("digits", &DIGITS.to_string())
+        .append_pair("period", &STEP_SECS.to_string());
+    url.into()
+}
+
+/// `count` single-use backup codes of the form `abcd-efgh`. The alphabet
+/// leaves out characters that are easy to misread (0/o, 1/l/i).
+pub fn generate_backup_codes(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let mut bytes = [0u8; 8];
+            OsRng.fill_bytes(&mut bytes);
+            let chars: String = bytes
+                .iter()
+                .map(|b| BACKUP_CODE_ALPHABET[*b as usize % BACKUP_CODE_ALPHABET.len()] as char)
+                .collect();
+            format!("{}-{}", &chars[..4], &chars[4..])
+        })
+        .collect()
+}
+
+/// Canonical form of a typed backup code: lowercase, no separators.
+pub fn normalize_backup_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_hotp_rfc4226_vectors() {
+        let expected = [
+            755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489,
+        ];
+        for (counter, code) in expected.into_iter().enumerate() {
+            assert_eq!(hotp(RFC_SECRET, counter as u64, 6), code, "counter {counter}");
+        }
+    }
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let vectors = [
+            (59, 94287082),
+            (1111111109, 7081804),
+            (1111111111, 14050471),
+            (1234567890, 89005924),
+            (2000000000, 69279037),
+            (20000000000, 65353130),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(hotp(RFC_SECRET, time / STEP_SECS, 8), code, "time {time}");
+        }
+        assert_eq!(code_at(RFC_SECRET, 59), "287082");
+        assert_eq!(code_at(RFC_SECRET, 1111111109), "081804");
+    }
+
+    #[test]
+    fn test_verify_allows_one_step_of_drift() {
+        let now = 1_700_000_000;
+        let code = code_at(RFC_SECRET, now);
+        assert!(verify(RFC_SECRET, &code, now));
+        assert!(verify(RFC_SECRET, &code, now + STEP_SECS));
+        assert!(verify(RFC_SECRET, &code, now - STEP_SECS));
+        assert!(!verify(RFC_SECRET, &code, now + 3 * STEP_SECS));
+        assert!(!verify(RFC_SECRET, "12345", now));
+        assert!(!verify(RFC_SECRET, "abcdef", now));
+    }
+
+    #[test]
+    fn test_secret_base32_roundtrip() {
+        let secret = generate_secret();
+        assert_eq!(secret.len(), SECRET_LEN);
+        let encoded = encode_secret(&secret);
+        assert_eq!(decode_secret(&encoded), Some(secret.clone()));
+        assert_eq!(decode_secret(&encoded.to_lowercase()), Some(secret));
+        assert_eq!(encode_secret(RFC_SECRET), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    }
+
+    #[test]
+    fn test_otpauth_uri() {
+        let uri = otpauth_uri(RFC_SECRET, "alice", "Tripwire");
+        assert_eq!(
+            uri,
+            "otpauth://totp/Tripwire:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ\
+             &issuer=Tripwire&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn test_backup_codes() {
+        let codes = generate_backup_codes(10);
+        assert_eq!(codes.len(), 10);
+        for code in &codes {
+            assert_eq!(code.len(), 9);
+            assert_eq!(&code[4..5], "-");
+        }
+        assert_eq!(normalize_backup_code(" ABCD-efgh "), "abcdefgh");
+    }
+}
@@ -0,0 +1,783 @@
+//! Link previews — fetching the OpenGraph / Twitter card metadata of links
+//! in messages and turning it into `Embed`s. Only the start of a page is
+//! read, fetches give up after a timeout, and results (failures included)
+//! are cached so each link is fetched once while it's on screen. Embed
+//! images are fetched the same way, rather than by the image loader.
+//!
+//! Nothing on this machine or the local network is fetched: every
+//! redirect is followed by hand and checked like the link itself, and
+//! hosts are looked up first so names pointing at private addresses are
+//! refused too. The app's client checks the addresses again as it
+//! connects (see `ReqwestClient::with_address_filter`), so a name can't
+//! be re-pointed once it has passed.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs as _};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use futures::future::{BoxFuture, Either};
+use futures::{AsyncReadExt as _, FutureExt as _};
+use gpui::{Image, ImageFormat};
+use gpui::http_client::{AsyncBody, HttpClient, HttpRequestExt as _, Method, RedirectPolicy, Request, Response, Url};
+
+use crate::models::Embed;
+
+/// Links remembered at most; the oldest are forgotten past this.
+const CACHE_CAPACITY: usize = 256;
+/// How long a preview, or the lack of one, is trusted.
+const CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+const MAX_TITLE_CHARS: usize = 256;
+const MAX_DESCRIPTION_CHARS: usize = 350;
+/// Redirects followed before a link is given up on.
+const MAX_REDIRECTS: usize = 5;
+
+/// Looks up the addresses of a host and port.
+type Resolve = fn(&str, u16) -> std::io::Result<Vec<IpAddr>>;
+
+fn resolve(host: &str, port: u16) -> std::io::Result<Vec<IpAddr>> {
+    Ok((host, port).to_socket_addrs()?.map(|addr| addr.ip()).collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnfurlLimits {
+    /// Bytes of a page read looking for its metadata.
+    pub max_body: usize,
+    /// Largest embed image loaded.
+    pub max_image: usize,
+    /// How long a fetch may take before the link is left without a preview.
+    pub timeout: Duration,
+    /// Links previewed per message.
+    pub per_message: usize,
+}
+
+impl Default for UnfurlLimits {
+    fn default() -> Self {
+        Self {
+            max_body: 512 * 1024,
+            max_image: 8 * 1024 * 1024,
+            timeout: Duration::from_secs(8),
+            per_message: 3,
+        }
+    }
+}
+
+/// Fetches link previews and embed images, and remembers them.
+pub struct Unfurler {
+    client: Arc<dyn HttpClient>,
+    limits: UnfurlLimits,
+    resolve: Resolve,
+    embeds: Arc<Mutex<Cache<Embed>>>,
+    images: Arc<Mutex<Cache<Arc<Image>>>>,
+}
+
+type Cache<T> = HashMap<String, Cached<T>>;
+
+struct Cached<T> {
+    value: Option<T>,
+    /// `None` while the fetch is in flight.
+    fetched: Option<Instant>,
+}
+
+impl<T> Cached<T> {
+    fn is_fresh(&self, now: Instant) -> bool {
+        self.fetched.is_none_or(|at| now.duration_since(at) < CACHE_TTL)
+    }
+}
+
+impl Unfurler {
+    pub fn new(client: Arc<dyn HttpClient>, limits: UnfurlLimits) -> Self {
+        Self {
+            client,
+            limits,
+            resolve,
+            embeds: Arc::default(),
+            images: Arc::default(),
+        }
+    }
+
+    pub fn limits(&self) -> UnfurlLimits {
+        self.limits
+    }
+
+    /// The preview of `url`, if it has been fetched and has one.
+    pub fn cached(&self, url: &str) -> Option<Embed> {
+        cached(&self.embeds, url)
+    }
+
+    /// Fetch the preview of `url`, unless it's cached or already being
+    /// fetched. The fetch is abandoned when `timeout` completes first.
+    pub fn fetch(
+        &self,
+        url: &str,
+        timeout: impl Future<Output = ()> + Send + 'static,
+    ) -> Option<BoxFuture<'static, ()>> {
+        let (client, resolve, max_body) = (self.client.clone(), self.resolve, self.limits.max_body);
+        fetch(&self.embeds, url, timeout, move |url| fetch_embed(client, resolve, url, max_body))
+    }
+
+    /// The embed image at `url`, if it has been fetched and is one.
+    pub fn cached_image(&self, url: &str) -> Option<Arc<Image>> {
+        cached(&self.images, url)
+    }
+
+    /// Fetch the embed image at `url`, like `fetch` does previews.
+    pub fn fetch_image(
+        &self,
+        url: &str,
+        timeout: impl Future<Output = ()> + Send + 'static,
+    ) -> Option<BoxFuture<'static, ()>> {
+        let (client, resolve, max_image) = (self.client.clone(), self.resolve, self.limits.max_image);
+        fetch(&self.images, url, timeout, move |url| fetch_image(client, resolve, url, max_image))
+    }
+}
+
+fn cached<T: Clone>(cache: &Mutex<Cache<T>>, url: &str) -> Option<T> {
+    let cache = cache.lock().unwrap();
+    let cached = cache.get(url).filter(|c| c.is_fresh(Instant::now()))?;
+    cached.value.clone()
+}
+
+/// Start fetching `url` into `cache` with `fetch_url`, unless it's there
+/// or on its way. Makes room first by forgetting stale entries, then the
+/// oldest.
+fn fetch<T, F>(
+    cache: &Arc<Mutex<Cache<T>>>,
+    url: &str,
+    timeout: impl Future<Output = ()> + Send + 'static,
+    fetch_url: impl FnOnce(String) -> F,
+) -> Option<BoxFuture<'static, ()>>
+where
+    T: Send + 'static,
+    F: Future<Output = Result<Option<T>>> + Send + 'static,
+{
+    let now = Instant::now();
+    let mut entries = cache.lock().unwrap();
+    if entries.get(url).is_some_and(|c| c.is_fresh(now)) {
+        return None;
+    }
+    if entries.len() >= CACHE_CAPACITY {
+        entries.retain(|_, c| c.is_fresh(now));
+    }
+    if entries.len() >= CACHE_CAPACITY
+        && let Some(oldest) = entries
+            .iter()
+            .filter_map(|(url, c)| Some((url, c.fetched?)))
+            .min_by_key(|(_, at)| *at)
+            .map(|(url, _)| url.clone())
+    {
+        entries.remove(&oldest);
+    }
+    entries.insert(
+        url.to_string(),
+        Cached {
+            value: None,
+            fetched: None,
+        },
+    );
+    drop(entries);
+
+    let fetch = fetch_url(url.to_string());
+    let (cache, url) = (cache.clone(), url.to_string());
+    Some(
+        async move {
+            let value = match futures::future::select(fetch.boxed(), timeout.boxed()).await {
+                Either::Left((Ok(value), _)) => value,
+                Either::Left((Err(_), _)) | Either::Right(_) => None,
+            };
+            cache.lock().unwrap().insert(
+                url,
+                Cached {
+                    value,
+                    fetched: Some(Instant::now()),
+                },
+            );
+        }
+        .boxed(),
+    )
+}
+
+async fn fetch_embed(
+    client: Arc<dyn HttpClient>,
+    resolve: Resolve,
+    url: String,
+    max_body: usize,
+) -> Result<Option<Embed>> {
+    let url = Url::parse(&url)?;
+    let Some((url, response)) = get_public(client.as_ref(), resolve, url, "text/html,application/xhtml+xml").await? else {
+        return Ok(None);
+    };
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    let content_type = response
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    if content_type.starts_with("image/") {
+        return Ok(Some(Embed {
+            url: Some(url.to_string()),
+            site_name: site_name(&url),
+            thumbnail: Some(url.to_string()),
+            ..Embed::default()
+        }));
+    }
+    if !content_type.starts_with("text/html") && !content_type.starts_with("application/xhtml") {
+        return Ok(None);
+    }
+
+    let mut body = Vec::new();
+    response
+        .into_body()
+        .take(max_body as u64)
+        .read_to_end(&mut body)
+        .await?;
+    Ok(parse_metadata(&String::from_utf8_lossy(&body), &url))
+}
+
+/// The image at `url`, unless it's bigger than `max_image` bytes or in a
+/// format that can't be shown.
+async fn fetch_image(
+    client: Arc<dyn HttpClient>,
+    resolve: Resolve,
+    url: String,
+    max_image: usize,
+) -> Result<Option<Arc<Image>>> {
+    let url = Url::parse(&url)?;
+    let Some((_, response)) = get_public(client.as_ref(), resolve, url, "image/*").await? else {
+        return Ok(None);
+    };
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    let Some(format) = response
+        .headers()
+        .get("Content-Type")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|kind| ImageFormat::from_mime_type(kind.split(';').next().unwrap_or_default().trim()))
+    else {
+        return Ok(None);
+    };
+    let mut bytes = Vec::new();
+    response
+        .into_body()
+        .take(max_image as u64 + 1)
+        .read_to_end(&mut bytes)
+        .await?;
+    Ok((bytes.len() <= max_image).then(|| Arc::new(Image::from_bytes(format, bytes))))
+}
+
+/// GET `url`, following redirects one at a time so each is checked with
+/// `is_public` before it's fetched. Returns where it ended up and the
+/// answer from there, or `None` if it led somewhere private or redirected
+/// too often.
+async fn get_public(
+    client: &dyn HttpClient,
+    resolve: Resolve,
+    mut url: Url,
+    accept: &str,
+) -> Result<Option<(Url, Response<AsyncBody>)>> {
+    for _ in 0..=MAX_REDIRECTS {
+        if !is_public(&url, resolve) {
+            return Ok(None);
+        }
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri(url.as_str())
+            .header("Accept", accept)
+            .follow_redirects(RedirectPolicy::NoFollow)
+            .body(AsyncBody::empty())?;
+        let response = client.send(request).await?;
+        if !response.status().is_redirection() {
+            return Ok(Some((url, response)));
+        }
+        let Some(location) = response.headers().get("Location").and_then(|v| v.to_str().ok()) else {
+            return Ok(None);
+        };
+        url = url.join(location)?;
+    }
+    Ok(None)
+}
+
+/// Whether `url` is a web address away from this machine and the local
+/// network, by name and by every address its host has.
+fn is_public(url: &Url, resolve: Resolve) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return false;
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.eq_ignore_ascii_case("localhost") || host.ends_with(".localhost") || host.ends_with(".local") {
+        return false;
+    }
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_public_ip(ip),
+        Err(_) => resolve(host, port).is_ok_and(|ips| !ips.is_empty() && ips.into_iter().all(is_public_ip)),
+    }
+}
+
+/// Whether `ip` is reachable on the internet, rather than this machine, the
+/// local network or a special-purpose range. IPv6 addresses that carry an
+/// IPv4 one are judged by that.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || a == 0
+                // Reserved, and broadcast.
+                || a >= 240
+                // Carrier-grade NAT.
+                || (a == 100 && (b & 0xc0) == 64)
+                // Benchmarking.
+                || (a == 198 && (b & 0xfe) == 18))
+        }
+        IpAddr::V6(ip) => {
+            let embedded = |high: u16, low: u16| IpAddr::V4(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)));
+            // Mapped (`::ffff:a.b.c.d`) and compatible (`::a.b.c.d`), which
+            // takes in `::` and `::1` too.
+            if let Some(v4) = ip.to_ipv4() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            match ip.segments() {
+                // NAT64.
+                [0x64, 0xff9b, 0, 0, 0, 0, high, low] => is_public_ip(embedded(high, low)),
+                // Local-use NAT64.
+                [0x64, 0xff9b, ..] => false,
+                // 6to4.
+                [0x2002, high, low, ..] => is_public_ip(embedded(high, low)),
+                [first, ..] => {
+                    !(ip.is_multicast()
+                        // Unique local.
+                        || (first & 0xfe00) == 0xfc00
+                        // Link-local and the deprecated site-local.
+                        || (first & 0xffc0) == 0xfe80
+                        || (first & 0xffc0) == 0xfec0)
+                }
+            }
+        }
+    }
+}
+
+/// The preview described by the `<meta>` tags of the page at `page_url`,
+/// preferring OpenGraph over Twitter card tags over plain HTML. Pages
+/// without a title or description have none.
+pub fn parse_metadata(html: &str, page_url: &Url) -> Option<Embed> {
+    // Metadata lives in the head; lowercasing keeps byte offsets.
+    let lower = html.to_ascii_lowercase();
+    let head_end = lower.find("</head").unwrap_or(lower.len());
+    let (html, lower) = (&html[..head_end], &lower[..head_end]);
+
+    let mut meta: HashMap<String, String> = HashMap::new();
+    let mut search = 0;
+    while let Some(start) = lower[search..].find("<meta").map(|ix| search + ix) {
+        let end = lower[start..].find('>').map_or(lower.len(), |ix| start + ix);
+        let attributes = attributes(&html[start + "<meta".len()..end]);
+        let key = attributes
+            .iter()
+            .find(|(name, _)| name == "property" || name == "name")
+            .map(|(_, value)| value.to_ascii_lowercase());
+        let content = attributes.iter().find(|(name, _)| name == "content").map(|(_, value)| value);
+        if let (Some(key), Some(content)) = (key, content) {
+            meta.entry(key).or_insert_with(|| decode_entities(content));
+        }
+        search = end;
+    }
+    let title_tag = lower.find("<title").and_then(|start| {
+        let open_end = start + lower[start..].find('>')? + 1;
+        let close = open_end + lower[open_end..].find("</title")?;
+        Some(decode_entities(&html[open_end..close]))
+    });
+
+    let first = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| meta.get(*key))
+            .map(|value| collapse_whitespace(value))
+            .filter(|value| !value.is_empty())
+    };
+    let title = first(&["og:title", "twitter:title"])
+        .or_else(|| title_tag.map(|t| collapse_whitespace(&t)).filter(|t| !t.is_empty()))
+        .map(|t| truncate(&t, MAX_TITLE_CHARS));
+    let description = first(&["og:description", "twitter:description", "description"])
+        .map(|d| truncate(&d, MAX_DESCRIPTION_CHARS));
+    if title.is_none() && description.is_none() {
+        return None;
+    }
+    let thumbnail = first(&["og:image", "og:image:url", "og:image:secure_url", "twitter:image", "twitter:image:src"])
+        .and_then(|src| page_url.join(&src).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(|url| url.to_string());
+    let url = first(&["og:url"])
+        .and_then(|u| page_url.join(&u).ok())
+        .unwrap_or_else(|| page_url.clone());
+
+    Some(Embed {
+        url: Some(url.to_string()),
+        title,
+        description,
+        site_name: first(&["og:site_name", "twitter:site"]).or_else(|| site_name(page_url)),
+        thumbnail,
+        color: first(&["theme-color"]).and_then(|c| parse_color(&c)),
+        fields: Vec::new(),
+    })
+}
+
+/// `name=value` pairs of a tag, names lowercased; values may be quoted
+/// with either quote or not at all.
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c == '/' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let Some(after_eq) = rest.strip_prefix('=') else {
+            rest = rest.trim_start_matches('/').trim_start();
+            if !name.is_empty() {
+                attributes.push((name, String::new()));
+            }
+            continue;
+        };
+        let after_eq = after_eq.trim_start();
+        let (value, remaining) = match after_eq.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let inner = &after_eq[1..];
+                let end = inner.find(quote).unwrap_or(inner.len());
+                (&inner[..end], inner.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = after_eq.find(char::is_whitespace).unwrap_or(after_eq.len());
+                (&after_eq[..end], &after_eq[end..])
+            }
+        };
+        attributes.push((name, value.to_string()));
+        rest = remaining.trim_start();
+    }
+    attributes
+}
+
+/// Replace the character references common in metadata.
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..].find(';').filter(|len| *len <= 10).map(|len| &rest[1..len + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+fn site_name(url: &Url) -> Option<String> {
+    url.host_str().map(|host| host.trim_start_matches("www.").to_string())
+}
+
+/// `#rgb` or `#rrggbb` as `0xRRGGBB`.
+fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.trim().strip_prefix('#')?;
+    match hex.len() {
+        6 => u32::from_str_radix(hex, 16).ok(),
+        3 => {
+            let short = u32::from_str_radix(hex, 16).ok()?;
+            let (r, g, b) = ((short >> 8) & 0xf, (short >> 4) & 0xf, short & 0xf);
+            Some((r * 0x11) << 16 | (g * 0x11) << 8 | (b * 0x11))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::executor::block_on;
+    use gpui::http_client::http::HeaderValue;
+
+    /// Serves canned pages by URL; `slow` ones never answer, and those of
+    /// kind `redirect` send to their body.
+    struct FakeClient {
+        pages: HashMap<&'static str, (&'static str, String)>,
+        requests: AtomicUsize,
+    }
+
+    impl FakeClient {
+        fn new(pages: impl IntoIterator<Item = (&'static str, &'static str, String)>) -> Arc<Self> {
+            Arc::new(Self {
+                pages: pages.into_iter().map(|(url, kind, body)| (url, (kind, body))).collect(),
+                requests: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    impl HttpClient for FakeClient {
+        fn type_name(&self) -> &'static str {
+            "FakeClient"
+        }
+
+        fn user_agent(&self) -> Option<&HeaderValue> {
+            None
+        }
+
+        fn proxy(&self) -> Option<&Url> {
+            None
+        }
+
+        fn send(&self, req: Request<AsyncBody>) -> BoxFuture<'static, Result<Response<AsyncBody>>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let url = req.uri().to_string();
+            if url.contains("slow") {
+                return futures::future::pending().boxed();
+            }
+            let response = match self.pages.get(url.as_str()) {
+                Some(("redirect", location)) => Response::builder()
+                    .status(302)
+                    .header("Location", location.as_str())
+                    .body(AsyncBody::empty()),
+                Some((kind, body)) => Response::builder()
+                    .status(200)
+                    .header("Content-Type", *kind)
+                    .body(AsyncBody::from(body.clone())),
+                None => Response::builder().status(404).body(AsyncBody::empty()),
+            };
+            futures::future::ready(response.map_err(Into::into)).boxed()
+        }
+    }
+
+    /// `internal.example.com` is on the local network, other hosts aren't.
+    fn fake_resolve(host: &str, _: u16) -> std::io::Result<Vec<IpAddr>> {
+        let ip = if host == "internal.example.com" { [10, 0, 0, 7] } else { [93, 184, 215, 14] };
+        Ok(vec![IpAddr::from(ip)])
+    }
+
+    fn unfurler(client: Arc<FakeClient>, limits: UnfurlLimits) -> Unfurler {
+        Unfurler {
+            resolve: fake_resolve,
+            ..Unfurler::new(client, limits)
+        }
+    }
+
+    fn page(head: &str) -> String {
+        format!("<!doctype html><html><head>{head}</head><body><meta property=\"og:title\" content=\"Body\"></body></html>")
+    }
+
+    #[test]
+    fn test_parses_opengraph_over_twitter_and_html() {
+        let url = Url::parse("https://www.example.com/posts/1").unwrap();
+        let html = page(concat!(
+            "<title>Plain title</title>",
+            "<META name=twitter:title content='Twitter title'>",
+            "<meta property=\"og:title\" content=\"Fish &amp; Chips &#8212; a &quot;guide&quot;\" />",
+            "<meta name=\"description\" content=\"  Crispy\n  and   hot \">",
+            "<meta property=\"og:image\" content=\"/img/fish.png\">",
+            "<meta name=\"theme-color\" content=\"#f80\">",
+        ));
+        let embed = parse_metadata(&html, &url).unwrap();
+        assert_eq!(embed.title.as_deref(), Some("Fish & Chips — a \"guide\""));
+        assert_eq!(embed.description.as_deref(), Some("Crispy and hot"));
+        assert_eq!(embed.site_name.as_deref(), Some("example.com"));
+        assert_eq!(embed.thumbnail.as_deref(), Some("https://www.example.com/img/fish.png"));
+        assert_eq!(embed.color, Some(0xff8800));
+        assert_eq!(embed.url.as_deref(), Some("https://www.example.com/posts/1"));
+
+        let fallback = parse_metadata(&page("<title> Just a\ttitle </title>"), &url).unwrap();
+        assert_eq!(fallback.title.as_deref(), Some("Just a title"));
+        assert_eq!(fallback.thumbnail, None);
+        // Tags outside the head don't count.
+        assert_eq!(parse_metadata(&page("<meta charset=utf-8>"), &url), None);
+    }
+
+    #[test]
+    fn test_fetches_once_and_caches() {
+        let client = FakeClient::new([
+            ("https://example.com/a", "text/html; charset=utf-8", page("<meta property=og:title content=A>")),
+            ("https://example.com/cat.png", "image/png", String::new()),
+            ("https://example.com/file.zip", "application/zip", String::new()),
+        ]);
+        let unfurler = unfurler(client.clone(), UnfurlLimits::default());
+
+        block_on(unfurler.fetch("https://example.com/a", futures::future::pending()).unwrap());
+        assert_eq!(unfurler.cached("https://example.com/a").unwrap().title.as_deref(), Some("A"));
+        assert!(unfurler.fetch("https://example.com/a", futures::future::pending()).is_none());
+        assert_eq!(client.requests.load(Ordering::SeqCst), 1);
+
+        block_on(unfurler.fetch("https://example.com/cat.png", futures::future::pending()).unwrap());
+        let image = unfurler.cached("https://example.com/cat.png").unwrap();
+        assert_eq!(image.thumbnail.as_deref(), Some("https://example.com/cat.png"));
+
+        // Neither are failures fetched again.
+        for url in ["https://example.com/file.zip", "https://example.com/missing"] {
+            block_on(unfurler.fetch(url, futures::future::pending()).unwrap());
+            assert_eq!(unfurler.cached(url), None);
+            assert!(unfurler.fetch(url, futures::future::pending()).is_none());
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let late_title = format!("<meta name=x content='{}'><meta property=og:title content=Late>", "x".repeat(2048));
+        let client = FakeClient::new([("https://example.com/big", "text/html", page(&late_title))]);
+        let limits = UnfurlLimits {
+            max_body: 1024,
+            ..UnfurlLimits::default()
+        };
+        let unfurler = unfurler(client.clone(), limits);
+
+        block_on(unfurler.fetch("https://example.com/big", futures::future::pending()).unwrap());
+        assert_eq!(unfurler.cached("https://example.com/big"), None);
+
+        // A fetch still going when the timeout fires is given up on.
+        block_on(unfurler.fetch("https://example.com/slow", futures::future::ready(())).unwrap());
+        assert_eq!(unfurler.cached("https://example.com/slow"), None);
+        assert!(unfurler.fetch("https://example.com/slow", futures::future::ready(())).is_none());
+
+        // Local addresses aren't fetched at all.
+        let requests = client.requests.load(Ordering::SeqCst);
+        for url in [
+            "http://localhost:8080/",
+            "http://192.168.1.1/admin",
+            "http://[::1]/",
+            "http://[::ffff:127.0.0.1]/",
+            "http://internal.example.com/",
+        ] {
+            block_on(unfurler.fetch(url, futures::future::pending()).unwrap());
+        }
+        assert_eq!(client.requests.load(Ordering::SeqCst), requests);
+    }
+
+    #[test]
+    fn test_fetches_images_within_limits() {
+        let client = FakeClient::new([
+            ("https://example.com/a.png", "image/png", "png".to_string()),
+            ("https://example.com/big.png", "image/png", "x".repeat(64)),
+            ("https://example.com/page", "text/html", page("<title>Page</title>")),
+            ("https://example.com/moved.png", "redirect", "http://127.0.0.1/a.png".to_string()),
+        ]);
+        let limits = UnfurlLimits {
+            max_image: 16,
+            ..UnfurlLimits::default()
+        };
+        let unfurler = unfurler(client.clone(), limits);
+
+        block_on(unfurler.fetch_image("https://example.com/a.png", futures::future::pending()).unwrap());
+        let image = unfurler.cached_image("https://example.com/a.png").unwrap();
+        assert_eq!((image.format(), image.bytes()), (ImageFormat::Png, &b"png"[..]));
+        assert!(unfurler.fetch_image("https://example.com/a.png", futures::future::pending()).is_none());
+
+        for url in ["https://example.com/big.png", "https://example.com/page", "https://example.com/moved.png"] {
+            block_on(unfurler.fetch_image(url, futures::future::pending()).unwrap());
+            assert!(unfurler.cached_image(url).is_none());
+        }
+        block_on(unfurler.fetch_image("http://[::1]/a.png", futures::future::pending()).unwrap());
+        assert_eq!(client.requests.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_public_ips() {
+        let public = |ip: &str| is_public_ip(ip.parse().unwrap());
+        for ip in ["93.184.215.14", "2606:2800:21f::1", "::ffff:93.184.215.14", "64:ff9b::5db8:d70e", "2002:5db8:d70e::1"] {
+            assert!(public(ip), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "100.64.0.1",
+            "169.254.169.254",
+            "198.18.0.1",
+            "198.19.255.255",
+            "224.0.0.251",
+            "240.0.0.1",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::ffff:10.0.0.1",
+            "::10.0.0.1",
+            "64:ff9b::a00:1",
+            "64:ff9b:1::1",
+            "2002:a00:1::1",
+            "fd00::1",
+            "fe80::1",
+            "fec0::1",
+            "ff02::1",
+        ] {
+            assert!(!public(ip), "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_checks_every_redirect() {
+        let client = FakeClient::new([
+            ("https://example.com/short", "redirect", "/post".to_string()),
+            ("https://example.com/post", "text/html", page("<meta property=og:title content=Post>")),
+            ("https://example.com/metadata", "redirect", "http://169.254.169.254/latest".to_string()),
+            ("https://example.com/intranet", "redirect", "http://internal.example.com/".to_string()),
+            ("https://example.com/loop", "redirect", "https://example.com/loop".to_string()),
+        ]);
+        let unfurler = unfurler(client.clone(), UnfurlLimits::default());
+
+        block_on(unfurler.fetch("https://example.com/short", futures::future::pending()).unwrap());
+        let embed = unfurler.cached("https://example.com/short").unwrap();
+        assert_eq!(embed.url.as_deref(), Some("https://example.com/post"));
+
+        let requests = client.requests.load(Ordering::SeqCst);
+        for url in ["https://example.com/metadata", "https://example.com/intranet"] {
+            block_on(unfurler.fetch(url, futures::future::pending()).unwrap());
+            assert_eq!(unfurler.cached(url), None);
+        }
+        // Only the redirects themselves were fetched.
+        assert_eq!(client.requests.load(Ordering::SeqCst), requests + 2);
+
+        block_on(unfurler.fetch("https://example.com/loop", futures::future::pending()).unwrap());
+        assert_eq!(client.requests.load(Ordering::SeqCst), requests + 3 + MAX_REDIRECTS);
+    }
+}
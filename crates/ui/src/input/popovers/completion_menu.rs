@@ -1,16 +1,18 @@
 use std::rc::Rc;
 
 use gpui::{
-    Action, AnyElement, App, AppContext, Context, DismissEvent, Empty, Entity, EventEmitter,
-    Half as _, HighlightStyle, InteractiveElement as _, IntoElement, ParentElement, Pixels, Point,
-    Render, RenderOnce, SharedString, Styled, StyledText, Subscription, Window, deferred, div,
-    prelude::FluentBuilder, px, relative,
+    Action, AnyElement, App, AppContext, Context, Corner, DismissEvent, Empty, Entity,
+    EventEmitter, Half as _, HighlightStyle, InteractiveElement as _, IntoElement, ParentElement,
+    Pixels, Point, Render, RenderOnce, SharedString, Styled, StyledText, Subscription, Window,
+    anchored, deferred, div, prelude::FluentBuilder, px, relative,
 };
 use lsp_types::{CompletionItem, CompletionTextEdit};
 
 const MAX_MENU_WIDTH: Pixels = px(320.);
 const MAX_MENU_HEIGHT: Pixels = px(240.);
 const POPOVER_GAP: Pixels = px(4.);
+/// How far the menu sits left of the cursor, and below its line.
+const CURSOR_OFFSET: Pixels = px(4.);
 
 use crate::{
     ActiveTheme, IndexPath, Selectable, actions, h_flex,
@@ -381,7 +383,7 @@ impl CompletionMenu {
 
         Some(
             scroll_origin + cursor_origin - editor.input_bounds.origin
-                + Point::new(-px(4.), last_layout.line_height + px(4.)),
+                + Point::new(-CURSOR_OFFSET, last_layout.line_height + CURSOR_OFFSET),
         )
     }
 }
@@ -413,45 +415,84 @@ impl Render for CompletionMenu {
         let vertical_layout =
             abs_pos.x + MAX_MENU_WIDTH + POPOVER_GAP + MAX_MENU_WIDTH + POPOVER_GAP
                 > window.bounds().size.width;
+        // Open above the cursor when there is no room below, e.g. a chat composer
+        // at the bottom of the window.
+        let open_above = abs_pos.y + MAX_MENU_HEIGHT > window.bounds().size.height;
+        let line_height = self
+            .editor
+            .read(cx)
+            .last_layout
+            .as_ref()
+            .map_or(px(0.), |layout| layout.line_height);
+
+        let menu = div()
+            .flex()
+            .flex_row()
+            .gap(POPOVER_GAP)
+            .items_start()
+            .when(vertical_layout, |this| this.flex_col())
+            .child(
+                editor_popover("completion-menu", cx)
+                    .max_w(max_width)
+                    .min_w(px(120.))
+                    .child(List::new(&self.list).max_h(MAX_MENU_HEIGHT)),
+            )
+            .when_some(selected_documentation, |this, documentation| {
+                let mut doc = match documentation {
+                    lsp_types::Documentation::String(s) => s.clone(),
+                    lsp_types::Documentation::MarkupContent(mc) => mc.value.clone(),
+                };
+                if vertical_layout {
+                    doc = doc.split("\n").next().unwrap_or_default().to_string();
+                }
 
-        deferred(
-            div()
-                .absolute()
-                .left(pos.x)
-                .top(pos.y)
-                .flex()
-                .flex_row()
-                .gap(POPOVER_GAP)
-                .items_start()
-                .when(vertical_layout, |this| this.flex_col())
-                .child(
-                    editor_popover("completion-menu", cx)
-                        .max_w(max_width)
-                        .min_w(px(120.))
-                        .child(List::new(&self.list).max_h(MAX_MENU_HEIGHT)),
+                this.child(
+                    div().child(
+                        editor_popover("completion-menu", cx)
+                            .w(MAX_MENU_WIDTH)
+                            .px_2()
+                            .child(render_markdown("doc", doc, window, cx)),
+                    ),
                 )
-                .when_some(selected_documentation, |this, documentation| {
-                    let mut doc = match documentation {
-                        lsp_types::Documentation::String(s) => s.clone(),
-                        lsp_types::Documentation::MarkupContent(mc) => mc.value.clone(),
-                    };
-                    if vertical_layout {
-                        doc = doc.split("\n").next().unwrap_or_default().to_string();
-                    }
+            })
+            .on_mouse_down_out(cx.listener(|this, _, _, cx| {
+                this.hide(cx);
+            }));
+
+        if open_above {
+            deferred(
+                anchored()
+                    .anchor(Corner::BottomLeft)
+                    .position(origin_above(abs_pos, line_height))
+                    .child(menu),
+            )
+            .into_any_element()
+        } else {
+            deferred(menu.absolute().left(pos.x).top(pos.y)).into_any_element()
+        }
+    }
+}
 
-                    this.child(
-                        div().child(
-                            editor_popover("completion-menu", cx)
-                                .w(MAX_MENU_WIDTH)
-                                .px_2()
-                                .child(render_markdown("doc", doc, window, cx)),
-                        ),
-                    )
-                })
-                .on_mouse_down_out(cx.listener(|this, _, _, cx| {
-                    this.hide(cx);
-                })),
-        )
-        .into_any_element()
+/// Where the bottom-left corner of a menu opening above the cursor goes,
+/// given `origin`, the top-left corner it has when opening below the
+/// cursor's line: `POPOVER_GAP` above the top of that line.
+fn origin_above(origin: Point<Pixels>, line_height: Pixels) -> Point<Pixels> {
+    let line_top = origin.y - CURSOR_OFFSET - line_height;
+    Point::new(origin.x, line_top - POPOVER_GAP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_above_leaves_one_gap() {
+        // The cursor's line spans y = 100..120, so below it the menu starts
+        // at 124.
+        let below = Point::new(px(46.), px(120.) + CURSOR_OFFSET);
+        assert_eq!(
+            origin_above(below, px(20.)),
+            Point::new(px(46.), px(100.) - POPOVER_GAP)
+        );
     }
 }